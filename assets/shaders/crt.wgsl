@@ -0,0 +1,39 @@
+// An old CRT screen over the whole frame: curved glass, scanlines and
+// corners falling off into the dark.
+
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0)
+var screen_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var screen_sampler: sampler;
+
+// How far the picture bulges out towards the corners
+const CURVATURE: f32 = 0.06;
+// Screen pixels per scanline, and how much the gaps between them darken
+const SCANLINE_HEIGHT: f32 = 3.0;
+const SCANLINE_DARKNESS: f32 = 0.3;
+// How much darker the corners get than the middle
+const VIGNETTE: f32 = 0.45;
+const PI: f32 = 3.14159265;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // From -1 to 1 across the screen, pushed outwards more the further it is from the middle
+    let centered = in.uv * 2.0 - 1.0;
+    let bent = centered * (1.0 + CURVATURE * dot(centered, centered));
+    let uv = bent * 0.5 + 0.5;
+
+    var color = textureSample(screen_texture, screen_sampler, uv).rgb;
+
+    let lines = f32(textureDimensions(screen_texture).y) / SCANLINE_HEIGHT;
+    let scanline = 0.5 + 0.5 * cos(uv.y * lines * 2.0 * PI);
+    color = color * (1.0 - SCANLINE_DARKNESS * scanline);
+
+    // Squared distance from the middle, 1 in the corners
+    color = color * (1.0 - VIGNETTE * dot(centered, centered) / 2.0);
+
+    // The glass curves away from the edges of the picture, leaving black behind
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), vec4<f32>(color, 1.0), inside);
+}
@@ -0,0 +1,26 @@
+// The dark over a night match: nearly black, but for the soft-edged circles
+// the lights make.
+
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0)
+var<uniform> lights: array<vec4<f32>, 16>;
+
+// How much of the court the dark hides where no light reaches
+const DARKNESS: f32 = 0.96;
+// How much of the way out from its middle a light starts fading
+const FALLOFF: f32 = 0.6;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    var light = 0.0;
+    for (var i = 0; i < 16; i = i + 1) {
+        let radius = lights[i].z;
+        if (radius <= 0.0) {
+            continue;
+        }
+        let distance = length(in.world_position.xy - lights[i].xy);
+        light = max(light, 1.0 - smoothstep(radius * FALLOFF, radius, distance));
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, DARKNESS * (1.0 - light));
+}
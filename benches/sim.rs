@@ -0,0 +1,158 @@
+//! How long the simulation's hot paths take, to check that changes to them,
+//! like how collisions are found, don't make them slower. Run with
+//! `cargo bench --bench sim`.
+
+use bevy::{ecs::world::CommandQueue, prelude::*};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use pingis::{
+    ai::predict_path,
+    arena::{spawn_layout, Arena},
+    sim::{
+        defer_commands, launch_ball, systems, BallHitEvent, BallKind, CollisionEvent,
+        GameplayConfig, GoalEvent, MatchClock, MatchEndedEvent, MatchOver, MatchRules, MatchStats,
+        Portals, RacketInput, Rally, Score, ServeCallEvent, ServeEvent, SimInput, SimRng, Wind,
+    },
+};
+
+const SEED: u64 = 0x5eed;
+
+fn classic_arena() -> Arena {
+    let bytes = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/arenas/classic.arena.ron"
+    ))
+    .unwrap();
+    ron::de::from_bytes(&bytes).unwrap()
+}
+
+/// A match on the classic arena with nothing but the simulation in it,
+/// `balls` of them in play, going every which way, and no end to it.
+fn simulation(balls: usize) -> App {
+    let arena = classic_arena();
+    let mut app = App::new();
+    app.insert_resource(MatchRules {
+        points_to_win: 1000,
+        ..default()
+    })
+    .insert_resource(arena.bounds())
+    .init_resource::<Score>()
+    .init_resource::<Rally>()
+    .init_resource::<MatchStats>()
+    .init_resource::<GameplayConfig>()
+    .init_resource::<MatchOver>()
+    .init_resource::<MatchClock>()
+    .init_resource::<SimInput>()
+    .init_resource::<Wind>()
+    .init_resource::<Portals>()
+    .insert_resource(SimRng::new(SEED))
+    .add_event::<CollisionEvent>()
+    .add_event::<GoalEvent>()
+    .add_event::<BallHitEvent>()
+    .add_event::<ServeEvent>()
+    .add_event::<ServeCallEvent>()
+    .add_event::<MatchEndedEvent>()
+    .add_systems(Update, systems());
+    defer_commands(&mut app, Update);
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, app.world());
+    spawn_layout(&mut commands, &arena);
+    for ball in 0..balls {
+        let angle = ball as f32 * 2.39;
+        launch_ball(
+            &mut commands,
+            BallKind::Standard,
+            Vec2::new(0.0, (ball as f32 * 13.0) % 200.0 - 100.0),
+            Vec2::new(angle.cos(), angle.sin()) * 500.0,
+        );
+    }
+    queue.apply(app.world_mut());
+    app
+}
+
+/// Both rackets all over the court, the same way every time.
+fn scripted_input(step: usize) -> SimInput {
+    let left = RacketInput {
+        up: step % 97 < 40,
+        down: step % 61 > 35,
+        smash: step % 173 < 50,
+        tilt_up: step % 89 < 20,
+        tilt_down: step % 113 > 80,
+        dash: step % 149 < 5,
+    };
+    let right = RacketInput {
+        up: (step / 13).is_multiple_of(3),
+        down: (step / 7) % 5 == 1,
+        smash: step % 131 > 90,
+        tilt_up: (step / 11) % 7 == 2,
+        tilt_down: (step / 17) % 4 == 3,
+        dash: step % 211 == 7,
+    };
+    SimInput([left, right, default(), default()])
+}
+
+/// A single step, which with many balls in play is mostly finding what each
+/// of them has hit.
+fn collision_sweep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision_sweep");
+    for balls in [1, 16, 64] {
+        group.bench_function(format!("{} balls", balls), |b| {
+            b.iter_batched_ref(
+                || simulation(balls),
+                |app| app.update(),
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Where a ball gets to across the court, the way the hard CPU works it out,
+/// from a shallow angle with few bounces to a steep one with many.
+fn trajectory_prediction(c: &mut Criterion) {
+    let bounds = classic_arena().bounds();
+    let mut group = c.benchmark_group("trajectory_prediction");
+    for (name, velocity) in [
+        ("shallow", Vec2::new(600.0, 150.0)),
+        ("steep", Vec2::new(150.0, 900.0)),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                predict_path(
+                    black_box(Vec2::new(bounds.min.x + 50.0, 0.0)),
+                    black_box(velocity),
+                    bounds.max.x - 50.0,
+                    &bounds,
+                    black_box(7.5),
+                )
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Ten thousand steps of a match, nearly three minutes of play.
+fn headless_match(c: &mut Criterion) {
+    const STEPS: usize = 10_000;
+    c.bench_function("headless_match_10k_steps", |b| {
+        b.iter_batched_ref(
+            || simulation(1),
+            |app| {
+                for step in 0..STEPS {
+                    app.insert_resource(scripted_input(step));
+                    app.update();
+                }
+                black_box(*app.world().resource::<Score>())
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(
+    benches,
+    collision_sweep,
+    trajectory_prediction,
+    headless_match
+);
+criterion_main!(benches);
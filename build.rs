@@ -0,0 +1,20 @@
+//! Stamps the build with the commit it's from, as `PINGIS_BUILD`, so online
+//! players can tell whether they're running the same game. Outside a git
+//! checkout there's no commit, and the game makes do with its version.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=PINGIS_BUILD={}", commit.trim());
+    }
+}
@@ -0,0 +1,399 @@
+//! Achievements for things done at this computer: a first win, a long rally,
+//! a shutout, a win with the ball at its fastest and a win over the hard CPU.
+//! Each is unlocked once, kept in a JSON file in the user's data directory,
+//! announced with a toast in the corner, and listed on a screen of their own
+//! off the menu.
+//!
+//! Unlocking goes through [`Achievements::unlock`], which passes each one on
+//! to every [`AchievementBackend`] as well, so a store with achievements of
+//! its own, like Steam's, only has to add a backend to keep up.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{Cpu, Difficulty},
+    bot::Bot,
+    dashboard::date_label,
+    locale::Strings,
+    recording::Playback,
+    settings::BALL_SPEEDS,
+    sim::{MatchOver, MatchRules, MatchStats, Rally},
+    stats::{data_dir, now},
+    tween::{Ease, Tween},
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        PANEL_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, MatchState, Player,
+};
+
+const ACHIEVEMENTS_FILE: &str = "achievements.json";
+/// Racket hits in a rally for [`Achievement::LongRally`].
+pub const LONG_RALLY: u32 = 20;
+// Seconds a toast is up for, the last of them fading away
+const TOAST_TIME: f32 = 4.0;
+const TOAST_FADE: f32 = 0.6;
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Achievements::load())
+            .add_event::<AchievementUnlocked>()
+            .add_systems(Update, check_rally.run_if(in_state(MatchState::Running)))
+            .add_systems(OnExit(AppState::Playing), check_match)
+            .add_systems(Update, spawn_toasts)
+            .add_systems(OnEnter(AppState::Achievements), spawn_gallery)
+            .add_systems(
+                Update,
+                gallery_action.run_if(in_state(AppState::Achievements)),
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Achievement {
+    /// Winning a match
+    FirstWin,
+    /// A rally of [`LONG_RALLY`] hits
+    LongRally,
+    /// Winning a match without the other side scoring
+    Shutout,
+    /// Winning a match with the ball speed turned all the way up
+    TopSpeedWin,
+    /// Winning a match against a hard CPU
+    BeatHardCpu,
+}
+
+/// Every achievement, in the order they're listed.
+pub const ACHIEVEMENTS: [Achievement; 5] = [
+    Achievement::FirstWin,
+    Achievement::LongRally,
+    Achievement::Shutout,
+    Achievement::TopSpeedWin,
+    Achievement::BeatHardCpu,
+];
+
+impl Achievement {
+    /// What it goes by outside the game, which mustn't ever change, as
+    /// backends may keep it.
+    pub fn id(self) -> &'static str {
+        match self {
+            Achievement::FirstWin => "first_win",
+            Achievement::LongRally => "long_rally",
+            Achievement::Shutout => "shutout",
+            Achievement::TopSpeedWin => "top_speed_win",
+            Achievement::BeatHardCpu => "beat_hard_cpu",
+        }
+    }
+
+    /// Keys of its name and of what it takes in the [`Strings`].
+    fn keys(self) -> (String, String) {
+        let key = format!("achievement.{}", self.id());
+        (format!("{}.name", key), format!("{}.description", key))
+    }
+}
+
+/// Sent when an achievement is unlocked for the first time.
+#[derive(Event)]
+pub struct AchievementUnlocked(pub Achievement);
+
+/// Somewhere else achievements are kept, told of each one as it's unlocked.
+pub trait AchievementBackend: Send + Sync + 'static {
+    fn unlock(&mut self, achievement: Achievement);
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct Unlocked {
+    achievement: Achievement,
+    /// Seconds since the Unix epoch
+    at: u64,
+}
+
+/// Every achievement unlocked so far.
+#[derive(Resource)]
+pub struct Achievements {
+    path: Option<PathBuf>,
+    unlocked: Vec<Unlocked>,
+    backends: Vec<Box<dyn AchievementBackend>>,
+}
+
+impl Achievements {
+    fn load() -> Self {
+        let path = data_dir().map(|dir| dir.join(ACHIEVEMENTS_FILE));
+        let unlocked = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| match serde_json::from_slice(&bytes) {
+                Ok(unlocked) => Some(unlocked),
+                Err(error) => {
+                    warn!("Ignoring unreadable achievements file: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Achievements {
+            path,
+            unlocked,
+            backends: Vec::new(),
+        }
+    }
+
+    /// Has `backend` told of every achievement as it's unlocked, starting
+    /// with the ones already unlocked, in case it's never heard of them.
+    pub fn add_backend(&mut self, mut backend: impl AchievementBackend) {
+        for unlocked in &self.unlocked {
+            backend.unlock(unlocked.achievement);
+        }
+        self.backends.push(Box::new(backend));
+    }
+
+    /// When it was unlocked, in seconds since the Unix epoch, if it has been.
+    pub fn unlocked_at(&self, achievement: Achievement) -> Option<u64> {
+        self.unlocked
+            .iter()
+            .find(|unlocked| unlocked.achievement == achievement)
+            .map(|unlocked| unlocked.at)
+    }
+
+    /// Unlocks it, if it isn't already. Whether it wasn't.
+    pub fn unlock(&mut self, achievement: Achievement) -> bool {
+        if self.unlocked_at(achievement).is_some() {
+            return false;
+        }
+        info!("Unlocked {}", achievement.id());
+        self.unlocked.push(Unlocked {
+            achievement,
+            at: now(),
+        });
+        if let Err(error) = self.save() {
+            warn!("Couldn't save achievements: {}", error);
+        }
+        for backend in &mut self.backends {
+            backend.unlock(achievement);
+        }
+        true
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(&self.unlocked)?)?;
+        Ok(())
+    }
+}
+
+fn unlock(
+    achievements: &mut Achievements,
+    unlocked: &mut EventWriter<AchievementUnlocked>,
+    achievement: Achievement,
+) {
+    if achievements.unlock(achievement) {
+        unlocked.send(AchievementUnlocked(achievement));
+    }
+}
+
+/// Player numbers of the people playing at this computer: everyone not
+/// played by a CPU or a bot. Online matches don't count, as rollback can take
+/// back what looked to have happened in them.
+fn local_players(
+    mode: GameMode,
+    humans: &Query<&Player, (Without<Cpu>, Without<Bot>)>,
+) -> Vec<i32> {
+    if matches!(mode, GameMode::Demo | GameMode::Online) {
+        return Vec::new();
+    }
+    humans.iter().map(|player| player.player_number).collect()
+}
+
+fn check_rally(
+    mut achievements: ResMut<Achievements>,
+    mut unlocked: EventWriter<AchievementUnlocked>,
+    rally: Res<Rally>,
+    mode: Res<GameMode>,
+    humans: Query<&Player, (Without<Cpu>, Without<Bot>)>,
+) {
+    if rally.hits < LONG_RALLY {
+        return;
+    }
+    if !local_players(*mode, &humans).is_empty() {
+        unlock(&mut achievements, &mut unlocked, Achievement::LongRally);
+    }
+}
+
+/// Unlocks whatever winning the match that's just finished earned. Runs as
+/// the match is left, while its players are still about.
+fn check_match(
+    mut achievements: ResMut<Achievements>,
+    mut unlocked: EventWriter<AchievementUnlocked>,
+    match_over: Res<MatchOver>,
+    stats: Res<MatchStats>,
+    rules: Res<MatchRules>,
+    mode: Res<GameMode>,
+    humans: Query<&Player, (Without<Cpu>, Without<Bot>)>,
+    cpus: Query<(&Player, &Cpu)>,
+    playback: Option<Res<Playback>>,
+) {
+    let winner = match match_over.0 {
+        Some(winner) => winner,
+        None => return,
+    };
+    // Watching a replay doesn't win anything again
+    if playback.is_some() || !local_players(*mode, &humans).contains(&winner) {
+        return;
+    }
+    let loser = 3 - winner;
+
+    unlock(&mut achievements, &mut unlocked, Achievement::FirstWin);
+    if stats.player(loser).points == 0 {
+        unlock(&mut achievements, &mut unlocked, Achievement::Shutout);
+    }
+    if rules.ball_speed >= BALL_SPEEDS[BALL_SPEEDS.len() - 1] {
+        unlock(&mut achievements, &mut unlocked, Achievement::TopSpeedWin);
+    }
+    let beat_hard_cpu = cpus
+        .iter()
+        .any(|(player, cpu)| player.player_number == loser && cpu.difficulty() == Difficulty::Hard);
+    if beat_hard_cpu {
+        unlock(&mut achievements, &mut unlocked, Achievement::BeatHardCpu);
+    }
+}
+
+/// A toast saying an achievement's been unlocked, whatever's on screen.
+#[derive(Component)]
+struct Toast;
+
+fn spawn_toasts(
+    mut commands: Commands,
+    mut unlocked: EventReader<AchievementUnlocked>,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    toasts: Query<(), With<Toast>>,
+) {
+    // Each new one goes under those already up
+    let already = toasts.iter().count();
+    for (below, AchievementUnlocked(achievement)) in (already..).zip(unlocked.read()) {
+        let (name, _) = achievement.keys();
+        let message = strings.fill("achievement.unlocked", &[("name", &strings.get(&name))]);
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        right: Val::Px(16.0),
+                        top: Val::Px(16.0 + below as f32 * 56.0),
+                        padding: UiRect::all(Val::Px(12.0)),
+                        ..default()
+                    },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                },
+                Toast,
+                Tween::new(
+                    TOAST_FADE,
+                    Ease::Linear,
+                    |color: &mut BackgroundColor, along| {
+                        color.0.set_alpha(PANEL_COLOR.alpha() * (1.0 - along));
+                    },
+                )
+                .after(TOAST_TIME - TOAST_FADE)
+                .then_despawn(),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    text(&font, message, 22.0, ACCENT_COLOR),
+                    Tween::new(TOAST_FADE, Ease::Linear, |text: &mut Text, along| {
+                        text.sections[0].style.color.set_alpha(1.0 - along);
+                    })
+                    .after(TOAST_TIME - TOAST_FADE),
+                ));
+            });
+    }
+}
+
+#[derive(Component)]
+struct BackButton;
+
+fn spawn_gallery(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    achievements: Res<Achievements>,
+) {
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Achievements)))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("achievements.title"), 48.0, TEXT_COLOR).with_style(
+                    Style {
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                ),
+            );
+
+            for achievement in ACHIEVEMENTS {
+                let (name, description) = achievement.keys();
+                let unlocked_at = achievements.unlocked_at(achievement);
+                let (status, color) = match unlocked_at {
+                    Some(at) => (
+                        strings.fill(
+                            "achievements.unlocked",
+                            &[("date", &date_label(at / 86400))],
+                        ),
+                        ACCENT_COLOR,
+                    ),
+                    None => (
+                        strings.get("achievements.locked").to_string(),
+                        DIM_TEXT_COLOR,
+                    ),
+                };
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            margin: UiRect::all(Val::Px(6.0)),
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(text(&font, strings.get(&name), 26.0, color));
+                        parent.spawn(text(
+                            &font,
+                            format!("{}  ({})", strings.get(&description), status),
+                            18.0,
+                            DIM_TEXT_COLOR,
+                        ));
+                    });
+            }
+
+            spawn_button(parent, &font, 0, strings.get("back")).insert(BackButton);
+        });
+}
+
+fn gallery_action(
+    mut activated: EventReader<ButtonActivated>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let back = activated
+        .read()
+        .any(|event| back_buttons.get(event.0).is_ok())
+        || keyboard_input.just_pressed(KeyCode::Escape);
+    if back {
+        state.set(AppState::Menu);
+    }
+}
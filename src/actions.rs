@@ -0,0 +1,241 @@
+//! What players ask of their rackets, whatever they ask it with. Keys,
+//! gamepads, the mouse and fingers on the screen all press [`Action`]s for
+//! a seat, and so do the CPUs and bots playing one, and everything
+//! downstream only ever looks at the actions: the simulation's input is made
+//! from them, and online what's sent to the other end.
+//!
+//! Devices press theirs in [`PressActions`], between the input being read and
+//! the rollback session asking for the next step's, once anything that keeps
+//! the keys for itself, like the console or the chat, has had them. The CPUs
+//! and bots press theirs in the update, before [`read_input`] turns them all
+//! into the simulation's, the actions being the game's
+//! [`InputSource`](crate::seams::InputSource).
+//!
+//! [`read_input`]: crate::seams::read_input
+
+use bevy::{
+    input::{
+        gamepad::{Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType},
+        InputSystem,
+    },
+    prelude::*,
+    utils::HashSet,
+};
+
+use crate::{
+    sim::{RacketInput, SEATS},
+    Player,
+};
+
+// How far a stick has to be pushed to count as moving the racket
+const STICK_DEAD_ZONE: f32 = 0.5;
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Actions>()
+            .init_resource::<GamepadSeats>()
+            .configure_sets(PreUpdate, PressActions.after(InputSystem))
+            .add_systems(
+                PreUpdate,
+                (
+                    start_frame.before(PressActions),
+                    (press_keys, press_gamepads).in_set(PressActions),
+                ),
+            );
+    }
+}
+
+/// Set the devices press [`Action`]s in, in [`PreUpdate`] once the input's
+/// been read. Whatever keeps keys for itself goes before it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PressActions;
+
+/// Something a player can ask of their racket, or of the match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    /// Held to charge a smash, which goes off when let go of
+    Smash,
+    TiltUp,
+    TiltDown,
+    Dash,
+    /// Leaves the match
+    Pause,
+}
+
+/// The actions each [`Racket::seat`] has held this frame, and had held the
+/// frame before.
+///
+/// [`Racket::seat`]: crate::sim::Racket::seat
+#[derive(Resource, Default)]
+pub struct Actions {
+    held: [HashSet<Action>; SEATS],
+    before: [HashSet<Action>; SEATS],
+}
+
+impl Actions {
+    /// Holds `action` at `seat` for this frame. Seats past the court's are
+    /// ignored.
+    pub fn press(&mut self, seat: usize, action: Action) {
+        if let Some(held) = self.held.get_mut(seat) {
+            held.insert(action);
+        }
+    }
+
+    pub fn pressed(&self, seat: usize, action: Action) -> bool {
+        self.held
+            .get(seat)
+            .is_some_and(|held| held.contains(&action))
+    }
+
+    /// Whether anything at all is held at `seat`.
+    pub fn any_pressed(&self, seat: usize) -> bool {
+        self.held.get(seat).is_some_and(|held| !held.is_empty())
+    }
+
+    /// Whether anyone's started holding `action` this frame.
+    pub fn any_just_pressed(&self, action: Action) -> bool {
+        self.held
+            .iter()
+            .zip(self.before.iter())
+            .any(|(held, before)| held.contains(&action) && !before.contains(&action))
+    }
+
+    /// What the racket at `seat` is asked to do.
+    pub fn racket_input(&self, seat: usize) -> RacketInput {
+        RacketInput {
+            up: self.pressed(seat, Action::MoveUp),
+            down: self.pressed(seat, Action::MoveDown),
+            smash: self.pressed(seat, Action::Smash),
+            tilt_up: self.pressed(seat, Action::TiltUp),
+            tilt_down: self.pressed(seat, Action::TiltDown),
+            dash: self.pressed(seat, Action::Dash),
+        }
+    }
+
+    /// Has the racket at `seat` do just what `input` asks, whatever anything
+    /// else pressed for it, for a CPU or bot taking the seat over.
+    pub fn take_over(&mut self, seat: usize, input: RacketInput) {
+        let held = match self.held.get_mut(seat) {
+            Some(held) => held,
+            None => return,
+        };
+        held.retain(|action| *action == Action::Pause);
+        for (pressed, action) in [
+            (input.up, Action::MoveUp),
+            (input.down, Action::MoveDown),
+            (input.smash, Action::Smash),
+            (input.tilt_up, Action::TiltUp),
+            (input.tilt_down, Action::TiltDown),
+            (input.dash, Action::Dash),
+        ] {
+            if pressed {
+                held.insert(action);
+            }
+        }
+    }
+}
+
+/// The gamepad playing each seat, if one is. Gamepads take the free seats
+/// in the order they're connected, and give them up when they're unplugged,
+/// see [`hotplug`](crate::hotplug).
+#[derive(Resource, Default)]
+pub(crate) struct GamepadSeats([Option<Gamepad>; SEATS]);
+
+impl GamepadSeats {
+    pub fn gamepad(&self, seat: usize) -> Option<Gamepad> {
+        self.0.get(seat).copied().flatten()
+    }
+
+    pub fn seat(&self, gamepad: Gamepad) -> Option<usize> {
+        self.0.iter().position(|taken| *taken == Some(gamepad))
+    }
+
+    /// Every gamepad playing a seat, with the seat.
+    pub fn assigned(&self) -> impl Iterator<Item = (usize, Gamepad)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(seat, gamepad)| Some((seat, (*gamepad)?)))
+    }
+
+    /// Gives `gamepad` `seat`, from whichever it had before.
+    pub fn assign(&mut self, seat: usize, gamepad: Gamepad) {
+        self.release(gamepad);
+        if let Some(taken) = self.0.get_mut(seat) {
+            *taken = Some(gamepad);
+        }
+    }
+
+    /// Gives `gamepad` the first free seat, if it hasn't one and there is one.
+    pub fn claim(&mut self, gamepad: Gamepad) -> Option<usize> {
+        if let Some(seat) = self.seat(gamepad) {
+            return Some(seat);
+        }
+        let seat = self.0.iter().position(Option::is_none)?;
+        self.0[seat] = Some(gamepad);
+        Some(seat)
+    }
+
+    /// Frees the seat `gamepad` was playing, if it was playing one.
+    pub fn release(&mut self, gamepad: Gamepad) -> Option<usize> {
+        let seat = self.seat(gamepad)?;
+        self.0[seat] = None;
+        Some(seat)
+    }
+}
+
+fn start_frame(mut actions: ResMut<Actions>) {
+    let actions = &mut *actions;
+    for (held, before) in actions.held.iter_mut().zip(actions.before.iter_mut()) {
+        *before = std::mem::take(held);
+    }
+}
+
+fn press_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    players: Query<&Player>,
+    mut actions: ResMut<Actions>,
+) {
+    for player in players.iter() {
+        for (key, action) in player.movement_keys.bindings() {
+            if keyboard_input.pressed(key) {
+                actions.press(player.seat, action);
+            }
+        }
+    }
+}
+
+fn press_gamepads(
+    seats: Res<GamepadSeats>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<Actions>,
+) {
+    for (seat, gamepad) in seats.assigned() {
+        for (button, action) in [
+            (GamepadButtonType::DPadUp, Action::MoveUp),
+            (GamepadButtonType::DPadDown, Action::MoveDown),
+            (GamepadButtonType::South, Action::Smash),
+            (GamepadButtonType::LeftTrigger, Action::TiltUp),
+            (GamepadButtonType::RightTrigger, Action::TiltDown),
+            (GamepadButtonType::West, Action::Dash),
+            (GamepadButtonType::Start, Action::Pause),
+        ] {
+            if buttons.pressed(GamepadButton::new(gamepad, button)) {
+                actions.press(seat, action);
+            }
+        }
+        let stick = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or_default();
+        if stick > STICK_DEAD_ZONE {
+            actions.press(seat, Action::MoveUp);
+        } else if stick < -STICK_DEAD_ZONE {
+            actions.press(seat, Action::MoveDown);
+        }
+    }
+}
@@ -0,0 +1,578 @@
+//! The CPU player. Any player with a [`Cpu`] attached gets its racket moved for
+//! it, through the same [`Actions`](crate::actions::Actions) the keyboard
+//! presses, so the simulation can't tell the difference. It's a
+//! [`RacketController`] like any bot.
+//!
+//! How a CPU goes about it, on top of how well it plays, is down to its
+//! [`Personality`], read from the `.personality.ron` files in
+//! `assets/personalities`.
+
+use std::time::Duration;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadedFolder},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    actions::Actions,
+    arena::ArenaBounds,
+    bot::{drive, BotRegistry, RacketController, Snapshot},
+    recording::Playback,
+    seams::read_input,
+    settings::Settings,
+    sim::{Racket, RacketInput, RacketSpeed, Score, SimRng, RACKET_SPEED},
+    GameMode, MatchState, Player, StartingSeed,
+};
+
+// Mixed into the match's seed for a CPU's, so it doesn't make its choices off
+// the same numbers the simulation makes its own with
+const CPU_SEED: u64 = 0x5eed_c0de_0fc9;
+// Bounces followed when predicting where the ball will go. The ball can't
+// bounce back and forth forever, but a near-vertical one comes close
+const MAX_PREDICTED_BOUNCES: usize = 16;
+// A CPU that smashes lets go this many seconds before the ball gets to it
+const SMASH_RELEASE_TIME: f32 = 0.15;
+// A hard CPU dashes for a ball it's further from than this many seconds at its
+// usual speed would get it
+const DASH_MARGIN: f32 = 0.1;
+/// The most catching up can speed a CPU up or slow it down by, as a fraction
+/// of how it usually plays.
+const MAX_CATCH_UP: f32 = 0.5;
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Personality>()
+            .init_asset_loader::<PersonalityLoader>()
+            .add_systems(Startup, load_personalities)
+            .add_systems(
+                Update,
+                (
+                    bring_on_cpu_opponent,
+                    seed_cpus.after(bring_on_cpu_opponent),
+                    dress_cpus,
+                    catch_up,
+                    drive::<Cpu, Time>
+                        .before(read_input::<Actions>)
+                        .after(seed_cpus)
+                        .after(dress_cpus)
+                        .after(catch_up),
+                )
+                    .run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// How well a CPU plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Heads for where the ball would go if nothing was in the way
+    Normal,
+    /// Works out every bounce on the way to its racket
+    Hard,
+}
+
+impl Difficulty {
+    /// Seconds between the CPU making up its mind about where to go.
+    fn reaction_time(self) -> f32 {
+        match self {
+            Difficulty::Normal => 0.2,
+            Difficulty::Hard => 0.08,
+        }
+    }
+
+    /// How far off its target the racket's center can be before it bothers moving.
+    fn dead_zone(self) -> f32 {
+        match self {
+            Difficulty::Normal => 18.0,
+            Difficulty::Hard => 8.0,
+        }
+    }
+}
+
+/// The way a CPU goes about chasing the ball. Anything left out of a file
+/// plays it straight.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Personality {
+    /// How far off, in pixels, its idea of where the ball will get to can be.
+    /// It guesses again every time it makes up its mind
+    pub misjudge: f32,
+    /// How far back towards the middle it goes between shots, from 0 (stays
+    /// where it is) to 1 (all the way)
+    pub recover: f32,
+    /// How far from the middle of its racket it tries to meet the ball, from
+    /// 0 to 1 at the end, which sends it off at more of an angle
+    pub edge: f32,
+    /// Turns its face to send the ball back towards the far side of the court
+    pub tilts: bool,
+    /// The chance of it charging a smash for any one ball, which it lets go
+    /// just before the ball gets there
+    pub smash_chance: f32,
+}
+
+impl Default for Personality {
+    fn default() -> Self {
+        Personality {
+            misjudge: 0.0,
+            recover: 1.0,
+            edge: 0.0,
+            tilts: false,
+            smash_chance: 0.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PersonalityLoader;
+
+impl AssetLoader for PersonalityLoader {
+    type Asset = Personality;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Personality, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["personality.ron"]
+    }
+}
+
+/// Every personality in `assets/personalities`, kept loaded so the settings
+/// can offer them.
+#[derive(Resource)]
+pub struct Personalities {
+    folder: Handle<LoadedFolder>,
+}
+
+impl Personalities {
+    /// The name of every personality's file, in alphabetical order, once the
+    /// folder's loaded.
+    pub fn names(&self, asset_server: &AssetServer, folders: &Assets<LoadedFolder>) -> Vec<String> {
+        let folder = match folders.get(&self.folder) {
+            Some(folder) => folder,
+            None => return Vec::new(),
+        };
+        let mut names: Vec<String> = folder
+            .handles
+            .iter()
+            .filter_map(|handle| asset_server.get_path(handle.id()))
+            .filter_map(|path| {
+                let file = path.path().file_name()?.to_str()?;
+                Some(file.strip_suffix(".personality.ron")?.to_string())
+            })
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// `<name>` is `assets/personalities/<name>.personality.ron`.
+pub fn load_personality(asset_server: &AssetServer, name: &str) -> Handle<Personality> {
+    asset_server.load(format!("personalities/{}.personality.ron", name))
+}
+
+/// Lets the CPU play for the player it's attached to. Their keys do nothing
+/// in the meantime.
+#[derive(Component)]
+pub struct Cpu {
+    difficulty: Difficulty,
+    personality: Option<Handle<Personality>>,
+    /// Its personality once that's loaded, playing it straight until then
+    style: Personality,
+    reaction: Timer,
+    /// Where along y the racket is heading
+    target: Option<f32>,
+    /// Whether it's going to smash the ball on its way, once there is one
+    smashing: Option<bool>,
+    rng: SimRng,
+    /// How much quicker than usual it's playing to catch up, or slower to
+    /// let the other player catch up, see [`catch_up_boost`]
+    boost: f32,
+}
+
+impl Cpu {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Cpu {
+            difficulty,
+            personality: None,
+            style: Personality::default(),
+            reaction: Timer::from_seconds(difficulty.reaction_time(), TimerMode::Repeating),
+            target: None,
+            smashing: None,
+            // Until it's seeded from the match it plays in, see `seed_cpus`
+            rng: SimRng::default(),
+            boost: 1.0,
+        }
+    }
+
+    pub fn with_personality(self, personality: Handle<Personality>) -> Self {
+        Cpu {
+            personality: Some(personality),
+            ..self
+        }
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// The personality it plays with, if it's been given one.
+    #[cfg(feature = "devtools")]
+    pub fn personality(&self) -> Option<&Handle<Personality>> {
+        self.personality.as_ref()
+    }
+}
+
+fn load_personalities(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // A missing folder is only logged, leaving no personalities to offer
+    let folder = asset_server.load_folder("personalities");
+    commands.insert_resource(Personalities { folder });
+}
+
+/// Seeds every CPU that's new to the match from the match's own seed, so the
+/// same seed plays out the same, but a different one for each seat, so that
+/// no two CPUs make the same choices.
+fn seed_cpus(starting_seed: Res<StartingSeed>, mut cpus: Query<(&Player, &mut Cpu), Added<Cpu>>) {
+    for (player, mut cpu) in cpus.iter_mut() {
+        cpu.rng = SimRng::new(starting_seed.0 ^ CPU_SEED.wrapping_mul(player.seat as u64 + 1));
+    }
+}
+
+/// Hands player 2 over to a CPU or a bot in a match against one, picked in
+/// the settings, once the match has started.
+fn bring_on_cpu_opponent(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    bots: Res<BotRegistry>,
+    playback: Option<Res<Playback>>,
+    mut players: Query<(Entity, &mut Player), Added<Player>>,
+) {
+    // A replay has what the CPU pressed already
+    if playback.is_some() {
+        return;
+    }
+    let name = match &settings.cpu_opponent {
+        Some(name) if matches!(*mode, GameMode::Versus | GameMode::Arcade) => name,
+        _ => return,
+    };
+
+    if let Some((entity, mut player)) = players
+        .iter_mut()
+        .find(|(_, player)| player.player_number == 2)
+    {
+        if let Some(bot) = bots.new_bot(name) {
+            player.name = name.clone();
+            commands.entity(entity).insert(bot);
+            return;
+        }
+        player.name = cpu_name(name);
+        let personality = load_personality(&asset_server, name);
+        commands
+            .entity(entity)
+            .insert(Cpu::new(Difficulty::Normal).with_personality(personality));
+    }
+}
+
+/// What a CPU with the personality called `name` goes by.
+pub fn cpu_name(name: &str) -> String {
+    let mut name = name.to_string();
+    if let Some(first) = name.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    name
+}
+
+/// The path the ball takes to `x`, bouncing off the top and bottom of the
+/// court: every bounce on the way, then where it gets there. Empty if it's
+/// heading the other way.
+pub fn predict_path(
+    mut position: Vec2,
+    mut velocity: Vec2,
+    x: f32,
+    bounds: &ArenaBounds,
+    radius: f32,
+) -> Vec<Vec2> {
+    let mut path = Vec::new();
+    if velocity.x == 0.0 || (x - position.x) * velocity.x <= 0.0 {
+        return path;
+    }
+
+    let (bottom, top) = (bounds.min.y + radius, bounds.max.y - radius);
+    for _ in 0..MAX_PREDICTED_BOUNCES {
+        let to_x = (x - position.x) / velocity.x;
+        let to_wall = if velocity.y > 0.0 {
+            (top - position.y) / velocity.y
+        } else if velocity.y < 0.0 {
+            (bottom - position.y) / velocity.y
+        } else {
+            f32::INFINITY
+        };
+
+        if to_wall >= to_x || top <= bottom {
+            path.push(position + velocity * to_x);
+            return path;
+        }
+        position += velocity * to_wall.max(0.0);
+        velocity.y = -velocity.y;
+        path.push(position);
+    }
+    path
+}
+
+/// Keeps every CPU's personality up to date with its file.
+fn dress_cpus(personalities: Res<Assets<Personality>>, mut cpus: Query<&mut Cpu>) {
+    for mut cpu in cpus.iter_mut() {
+        if let Some(personality) = cpu
+            .personality
+            .as_ref()
+            .and_then(|handle| personalities.get(handle))
+        {
+            cpu.style = *personality;
+        }
+    }
+}
+
+/// How much quicker than usual a CPU plays when it's `behind` by that many
+/// points, or slower when it's ahead, with catching up set to `strength` a point.
+pub fn catch_up_boost(behind: i32, strength: f32) -> f32 {
+    1.0 + (behind as f32 * strength).clamp(-MAX_CATCH_UP, MAX_CATCH_UP)
+}
+
+/// How much a CPU's racket has been sped up by catching up, so that it can
+/// be put back.
+#[derive(Component)]
+struct CatchUpPace(f32);
+
+/// Speeds up a CPU that's behind, in its reactions and its racket, and slows
+/// down one that's ahead, as much as the settings say to. Not in the demo,
+/// where there's nobody to keep it close for.
+fn catch_up(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    mut cpus: Query<(&Player, &mut Cpu)>,
+    mut rackets: Query<(Entity, Ref<Racket>, &mut RacketSpeed, Option<&CatchUpPace>)>,
+) {
+    let strength = if *mode == GameMode::Demo {
+        0.0
+    } else {
+        settings.cpu_catch_up
+    };
+
+    for (player, mut cpu) in cpus.iter_mut() {
+        let opponent = 3 - player.player_number;
+        let behind = score.points(opponent) - score.points(player.player_number);
+        let boost = catch_up_boost(behind, strength);
+        if boost != cpu.boost {
+            cpu.boost = boost;
+            let reaction_time = cpu.difficulty.reaction_time() / boost;
+            cpu.reaction
+                .set_duration(Duration::from_secs_f32(reaction_time));
+        }
+
+        for (entity, racket, mut speed, pace) in rackets.iter_mut() {
+            // A new racket has yet to be given its usual speed
+            if racket.seat != player.seat || racket.is_added() {
+                continue;
+            }
+            let paced = pace.map_or(1.0, |pace| pace.0);
+            if paced != boost {
+                speed.0 *= boost / paced;
+                commands.entity(entity).insert(CatchUpPace(boost));
+            }
+        }
+    }
+}
+
+impl RacketController for Cpu {
+    fn control(&mut self, snapshot: &Snapshot) -> RacketInput {
+        let _span = info_span!(
+            "ai",
+            player = snapshot.racket.player_number,
+            difficulty = ?self.difficulty
+        )
+        .entered();
+        let bounds = &snapshot.bounds;
+        let center = (bounds.min.y + bounds.max.y) / 2.0;
+        let personality = self.style;
+        let racket_x = snapshot.racket.position.x;
+        let racket_y = snapshot.racket.position.y;
+        let incoming = snapshot.incoming();
+
+        // Make up its mind about smashing once for each ball that comes its way
+        self.smashing = match (incoming, self.smashing) {
+            (Some(_), None) => Some(self.rng.range(0.0, 1.0) < personality.smash_chance),
+            (Some(_), smashing) => smashing,
+            (None, _) => None,
+        };
+
+        // The racket's center can't get any closer to the edge than this
+        let reach = snapshot.racket.extent.y / 2.0;
+        if self.reaction.tick(snapshot.delta).just_finished() || self.target.is_none() {
+            self.target = Some(match incoming {
+                Some(ball) => {
+                    let crossing = match self.difficulty {
+                        Difficulty::Normal => {
+                            let to_x = snapshot.arriving_in(ball);
+                            (ball.position.y + ball.velocity.y * to_x)
+                                .clamp(bounds.min.y, bounds.max.y)
+                        }
+                        Difficulty::Hard => predict_path(
+                            ball.position,
+                            ball.velocity,
+                            racket_x,
+                            bounds,
+                            ball.radius,
+                        )
+                        .last()
+                        .map_or(ball.position.y, |crossing| crossing.y),
+                    };
+                    let misjudged =
+                        crossing + self.rng.range(-personality.misjudge, personality.misjudge);
+                    // Meeting it on the end nearer the middle sends it off across the court
+                    misjudged + (misjudged - center).signum() * personality.edge * reach
+                }
+                // Nothing to chase, so wait for the next one
+                None => racket_y + (center - racket_y) * personality.recover,
+            });
+        }
+
+        let target = self.target.unwrap_or(center);
+        let target = target.clamp(
+            bounds.min.y + reach,
+            (bounds.max.y - reach).max(bounds.min.y + reach),
+        );
+        let offset = target - racket_y;
+        let angles = personality.tilts;
+        let arriving_in = incoming.map(|ball| snapshot.arriving_in(ball));
+        RacketInput {
+            up: offset > self.difficulty.dead_zone(),
+            down: offset < -self.difficulty.dead_zone(),
+            smash: self.smashing == Some(true)
+                && arriving_in.is_some_and(|time| time > SMASH_RELEASE_TIME),
+            tilt_up: angles && racket_y < center,
+            tilt_down: angles && racket_y > center,
+            dash: self.difficulty == Difficulty::Hard
+                && arriving_in
+                    .is_some_and(|time| offset.abs() > RACKET_SPEED * (time - DASH_MARGIN)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::bot::{BallView, RacketView};
+
+    #[test]
+    fn prediction_follows_bounces_off_the_walls() {
+        let bounds = ArenaBounds {
+            min: Vec2::new(-400.0, -100.0),
+            max: Vec2::new(400.0, 100.0),
+        };
+
+        // Up and to the right at 45 degrees: off the top at x = 100, off the
+        // bottom at x = 300, and back up to y = -50 by the time it reaches x = 350
+        let path = predict_path(Vec2::ZERO, Vec2::new(1.0, 1.0), 350.0, &bounds, 0.0);
+        assert_eq!(
+            path,
+            vec![
+                Vec2::new(100.0, 100.0),
+                Vec2::new(300.0, -100.0),
+                Vec2::new(350.0, -50.0)
+            ]
+        );
+
+        assert!(predict_path(Vec2::ZERO, Vec2::new(-1.0, 1.0), 350.0, &bounds, 0.0).is_empty());
+    }
+
+    #[test]
+    fn cpu_heads_for_where_the_ball_is_going() {
+        let mut snapshot = Snapshot {
+            delta: Duration::from_millis(16),
+            bounds: ArenaBounds {
+                min: Vec2::new(-400.0, -200.0),
+                max: Vec2::new(400.0, 200.0),
+            },
+            racket: RacketView {
+                player_number: 2,
+                position: Vec2::new(300.0, 0.0),
+                extent: Vec2::new(20.0, 120.0),
+            },
+            others: Vec::new(),
+            // Gets to the racket's x at y = 150
+            balls: vec![BallView {
+                position: Vec2::ZERO,
+                velocity: Vec2::new(300.0, 150.0),
+                radius: 10.0,
+            }],
+        };
+
+        let mut cpu = Cpu::new(Difficulty::Hard);
+        let input = cpu.control(&snapshot);
+        assert!(input.up && !input.down);
+
+        // Once the ball's going the other way it waits in the middle
+        snapshot.racket.position.y = 100.0;
+        snapshot.balls[0].velocity.x = -300.0;
+        let mut cpu = Cpu::new(Difficulty::Hard);
+        let input = cpu.control(&snapshot);
+        assert!(input.down && !input.up);
+    }
+
+    #[test]
+    fn catching_up_goes_only_so_far() {
+        assert_eq!(catch_up_boost(3, 0.0), 1.0);
+        assert_eq!(catch_up_boost(2, 0.1), 1.2);
+        assert_eq!(catch_up_boost(-2, 0.1), 0.8);
+        assert_eq!(catch_up_boost(10, 0.1), 1.0 + MAX_CATCH_UP);
+        assert_eq!(catch_up_boost(-10, 0.2), 1.0 - MAX_CATCH_UP);
+    }
+
+    /// What the CPUs at each seat of a match started with `seed` have their
+    /// rng at.
+    fn seeded(seed: u64) -> Vec<u64> {
+        let mut app = App::new();
+        app.insert_resource(StartingSeed(seed))
+            .add_systems(Update, seed_cpus);
+        let cpus: Vec<Entity> = (0..2)
+            .map(|seat| {
+                let player = Player { seat, ..default() };
+                app.world_mut()
+                    .spawn((player, Cpu::new(Difficulty::Normal)))
+                    .id()
+            })
+            .collect();
+        app.update();
+        cpus.iter()
+            .map(|cpu| app.world().get::<Cpu>(*cpu).unwrap().rng.state())
+            .collect()
+    }
+
+    #[test]
+    fn cpus_are_seeded_from_the_match() {
+        let rngs = seeded(7);
+        assert_eq!(rngs, seeded(7));
+        assert_ne!(rngs, seeded(8));
+        // Not the same for both seats
+        assert_ne!(rngs[0], rngs[1]);
+    }
+}
@@ -0,0 +1,260 @@
+//! An announcer calling the score, what's riding on the next point and the
+//! end of the match.
+//!
+//! What gets said is up to the voice pack picked in the settings, read from a
+//! `.announcer.ron` file in `assets/announcers` or a mod that maps every [`Call`] it
+//! has a line for to a clip under `assets`. Calls a pack has nothing for, or
+//! whose clip is missing, are just left unsaid, so a pack can be as small as a
+//! single "Game!".
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState},
+    audio::Volume,
+    prelude::*,
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+use crate::{
+    mods::{self, Content, Mods},
+    settings::Settings,
+    sim::{GoalEvent, MatchRules, Score, Stakes},
+    start_match, AppState, GameMode, MatchState,
+};
+
+pub struct AnnouncerPlugin;
+
+impl Plugin for AnnouncerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<VoicePack>()
+            .init_asset_loader::<VoicePackLoader>()
+            .init_resource::<Lines>()
+            .init_resource::<Called>()
+            .add_systems(Update, select_voice_pack)
+            .add_systems(Update, load_lines.after(select_voice_pack))
+            .add_systems(OnEnter(AppState::Playing), call_start.after(start_match))
+            .add_systems(Update, call_goals.run_if(in_state(MatchState::Running)));
+    }
+}
+
+/// Something the announcer can say.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Call {
+    /// A match starting
+    Start,
+    /// The score after a goal, the scorer's points first
+    Score(i32, i32),
+    /// The next point could win the match
+    MatchPoint,
+    /// The next point could win the set, but not the match
+    SetPoint,
+    /// Tied, with the set to be won by two
+    Deuce,
+    /// A goal that won a set, but not the match
+    Set,
+    /// A goal that won the match
+    Game,
+}
+
+/// A voice pack, with the clip for each call it has a line for, as a path
+/// under `assets`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct VoicePack {
+    pub name: String,
+    pub lines: HashMap<Call, String>,
+}
+
+#[derive(Default)]
+pub struct VoicePackLoader;
+
+impl AssetLoader for VoicePackLoader {
+    type Asset = VoicePack;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<VoicePack, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut pack: VoicePack = ron::de::from_bytes(&bytes)?;
+        for path in pack.lines.values_mut() {
+            *path = mods::resolve(load_context.path(), path);
+        }
+        Ok(pack)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["announcer.ron"]
+    }
+}
+
+/// The voice pack picked in the settings, which may still be loading.
+#[derive(Resource)]
+struct SelectedVoicePack(Handle<VoicePack>);
+
+/// The clips of the voice pack in use, loaded up front so nothing's said late.
+#[derive(Resource, Default)]
+struct Lines(HashMap<Call, Handle<AudioSource>>);
+
+/// The score last called. Rollback sends a goal again when it plays its step
+/// again, but it's only called the once.
+#[derive(Resource, Default)]
+struct Called(Option<Score>);
+
+/// Matches nobody's keeping score in out loud.
+fn announced(mode: GameMode) -> bool {
+    !matches!(
+        mode,
+        GameMode::Demo | GameMode::Practice | GameMode::Survival
+    )
+}
+
+fn select_voice_pack(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mods: Res<Mods>,
+    mut lines: ResMut<Lines>,
+    mut current: Local<Option<String>>,
+) {
+    if *current == settings.announcer {
+        return;
+    }
+    *current = settings.announcer.clone();
+
+    lines.0.clear();
+    match &settings.announcer {
+        // `<name>` is `assets/announcers/<name>.announcer.ron`, unless a mod
+        // brought it
+        Some(name) => {
+            let path = mods
+                .asset_path(Content::VoicePack, name)
+                .unwrap_or_else(|| format!("announcers/{}.announcer.ron", name));
+            commands.insert_resource(SelectedVoicePack(asset_server.load(&path)));
+        }
+        None => commands.remove_resource::<SelectedVoicePack>(),
+    }
+}
+
+fn load_lines(
+    mut events: EventReader<AssetEvent<VoicePack>>,
+    packs: Res<Assets<VoicePack>>,
+    selected: Option<Res<SelectedVoicePack>>,
+    asset_server: Res<AssetServer>,
+    mut lines: ResMut<Lines>,
+    mut pending: Local<bool>,
+) {
+    let selected = match selected {
+        Some(selected) => selected,
+        None => return,
+    };
+    // As soon as the pack is there, and again whenever its file changes
+    let modified = events.read().any(|event| match event {
+        AssetEvent::Modified { id } => *id == selected.0.id(),
+        _ => false,
+    });
+    if selected.is_changed() || modified {
+        *pending = true;
+    }
+    if !*pending {
+        return;
+    }
+
+    if let Some(pack) = packs.get(&selected.0) {
+        info!("Using announcer \"{}\"", pack.name);
+        lines.0 = pack
+            .lines
+            .iter()
+            .map(|(call, path)| (*call, asset_server.load(path.clone())))
+            .collect();
+        *pending = false;
+    }
+}
+
+/// Says the first of `calls` the voice pack has a line for, as loud as the
+/// settings have it.
+fn say(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    lines: &Lines,
+    settings: &Settings,
+    calls: &[Call],
+) {
+    let clip = calls.iter().find_map(|call| {
+        lines.0.get(call).filter(|clip| {
+            !matches!(
+                asset_server.get_load_state(clip.id()),
+                Some(LoadState::Failed(_))
+            )
+        })
+    });
+    if let Some(clip) = clip {
+        let volume = settings.volume * settings.announcer_volume;
+        commands.spawn((
+            clip.clone(),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(volume)),
+        ));
+    }
+}
+
+fn call_start(
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    lines: Res<Lines>,
+    mut called: ResMut<Called>,
+) {
+    called.0 = None;
+    if announced(*mode) {
+        say(
+            &mut commands,
+            &asset_server,
+            &lines,
+            &settings,
+            &[Call::Start],
+        );
+    }
+}
+
+fn call_goals(
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    lines: Res<Lines>,
+    mut goals: EventReader<GoalEvent>,
+    mut called: ResMut<Called>,
+) {
+    let goal = match goals.read().last() {
+        Some(goal) if announced(*mode) => *goal,
+        _ => return,
+    };
+    if called.0 == Some(*score) {
+        return;
+    }
+    called.0 = Some(*score);
+
+    let scorer = score.points(goal.scored_by);
+    let other = score.points(3 - goal.scored_by);
+    let calls = if rules.wins_match(&score) {
+        vec![Call::Game, Call::Set]
+    } else if rules.set_winner(&score).is_some() {
+        vec![Call::Set]
+    } else {
+        match rules.stakes(&score) {
+            Some(Stakes::MatchPoint) => vec![Call::MatchPoint, Call::Score(scorer, other)],
+            Some(Stakes::SetPoint) => vec![Call::SetPoint, Call::Score(scorer, other)],
+            // Saying how many all it is would only be saying it's a tie
+            Some(Stakes::Deuce) => vec![Call::Deuce],
+            None => vec![Call::Score(scorer, other)],
+        }
+    };
+    say(&mut commands, &asset_server, &lines, &settings, &calls);
+}
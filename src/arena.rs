@@ -0,0 +1,489 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli_arg,
+    mods::{Content, Mods},
+    recording::Playback,
+    saved_match::SavedMatch,
+    settings::Settings,
+    sim::{
+        spawn_racket, Collider, Goal, MatchRules, Obstacle, Side, RACKET_THICCNESS,
+        RACKET_WALL_OFFSET,
+    },
+    theme::{Theme, ThemePart},
+    AppState, GameMode, MatchState,
+};
+
+/// The arenas in `assets/arenas`.
+pub const ARENAS: [&str; 2] = ["classic", "pillars"];
+const DEFAULT_ARENA: &str = "classic";
+
+// Court markings are painted on, under everything else
+const MARKING_WIDTH: f32 = 4.0;
+const MARKING_Z: f32 = -1.0;
+const CENTER_DASH: f32 = 20.0;
+const CENTER_GAP: f32 = 14.0;
+const CENTER_CIRCLE_RADIUS: f32 = 70.0;
+// Sprites making up the center circle
+const CENTER_CIRCLE_SEGMENTS: usize = 40;
+
+/// The sizes the court can be, in the order the settings go through them.
+pub const ARENA_SIZES: [ArenaSize; 4] = [
+    ArenaSize::Small,
+    ArenaSize::Classic,
+    ArenaSize::Wide,
+    ArenaSize::Tall,
+];
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Arena>()
+            .init_asset_loader::<ArenaLoader>()
+            .init_resource::<ArenaBounds>()
+            .init_resource::<ArenaExtent>()
+            .init_resource::<ArenaSize>()
+            .add_systems(Startup, load_arena)
+            .add_systems(Update, spawn_arena.run_if(in_state(MatchState::Running)));
+    }
+}
+
+/// A court layout, read from an `.arena.ron` file in `assets/arenas` or a mod.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Arena {
+    pub name: String,
+    pub background: Color,
+    pub walls: Vec<Block>,
+    #[serde(default)]
+    pub obstacles: Vec<Block>,
+    pub goals: Vec<GoalZone>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Block {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+/// The area behind a racket. A ball entering it is a point for the other side.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct GoalZone {
+    pub side: Side,
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl Arena {
+    fn goal(&self, side: Side) -> Option<&GoalZone> {
+        self.goals.iter().find(|goal| goal.side == side)
+    }
+
+    /// The rectangle between the goal lines that the rackets and the ball play in.
+    pub fn bounds(&self) -> ArenaBounds {
+        let mut bounds = ArenaBounds::default();
+        for goal in &self.goals {
+            let inner_edge = match goal.side {
+                Side::Left => goal.position.x + goal.size.x / 2.0,
+                Side::Right => goal.position.x - goal.size.x / 2.0,
+            };
+            bounds.min.x = bounds.min.x.min(inner_edge);
+            bounds.max.x = bounds.max.x.max(inner_edge);
+            bounds.min.y = bounds.min.y.min(goal.position.y - goal.size.y / 2.0);
+            bounds.max.y = bounds.max.y.max(goal.position.y + goal.size.y / 2.0);
+        }
+        bounds
+    }
+
+    /// How far the arena's pieces reach from the origin along each axis.
+    pub fn extent(&self) -> ArenaExtent {
+        let goals = self.goals.iter().map(|goal| (goal.position, goal.size));
+        let blocks = self.walls.iter().chain(&self.obstacles);
+        let extent = blocks
+            .map(|block| (block.position, block.size))
+            .chain(goals)
+            .fold(Vec2::ZERO, |extent, (position, size)| {
+                extent.max(position.abs() + size / 2.0)
+            });
+        ArenaExtent(extent)
+    }
+
+    /// The layout stretched `scale` times across and along. Obstacles keep
+    /// their size, and walls and goals their thickness, stretching only the
+    /// way they reach across the middle of the court. The court between them
+    /// stretches exactly `scale` times.
+    pub fn scaled(&self, scale: Vec2) -> Arena {
+        let stretch = |position: Vec2, size: Vec2| {
+            let (x, width) = stretch_axis(position.x, size.x, scale.x);
+            let (y, height) = stretch_axis(position.y, size.y, scale.y);
+            (Vec2::new(x, y), Vec2::new(width, height))
+        };
+        let block = |block: &Block| {
+            let (position, size) = stretch(block.position, block.size);
+            Block { position, size }
+        };
+        Arena {
+            name: self.name.clone(),
+            background: self.background,
+            walls: self.walls.iter().map(block).collect(),
+            obstacles: self
+                .obstacles
+                .iter()
+                .map(|obstacle| Block {
+                    position: obstacle.position * scale,
+                    size: obstacle.size,
+                })
+                .collect(),
+            goals: self
+                .goals
+                .iter()
+                .map(|goal| {
+                    let (position, size) = stretch(goal.position, goal.size);
+                    GoalZone {
+                        side: goal.side,
+                        position,
+                        size,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Where a piece `size` long at `position` along an axis goes when the court
+/// stretches `scale` times along it, and how long it is then. Reaching across
+/// the middle, it stretches; otherwise the edge facing the middle moves.
+fn stretch_axis(position: f32, size: f32, scale: f32) -> (f32, f32) {
+    if position.abs() < size / 2.0 {
+        (position * scale, size * scale)
+    } else {
+        let inner_edge = position.abs() - size / 2.0;
+        (position.signum() * (inner_edge * scale + size / 2.0), size)
+    }
+}
+
+/// How big the court is, as a stretch of the arena's own layout. Picked in
+/// the settings, and kept as a resource for the court being played on.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArenaSize {
+    /// Less running for everyone
+    Small,
+    /// As the arena's laid out
+    #[default]
+    Classic,
+    /// Further between the rackets
+    Wide,
+    /// More court for each racket to cover
+    Tall,
+}
+
+impl ArenaSize {
+    /// Key of its name in the [`Strings`](crate::locale::Strings).
+    pub fn name_key(self) -> &'static str {
+        match self {
+            ArenaSize::Small => "arena_size.small",
+            ArenaSize::Classic => "arena_size.classic",
+            ArenaSize::Wide => "arena_size.wide",
+            ArenaSize::Tall => "arena_size.tall",
+        }
+    }
+
+    /// How many times the arena's stretched across and along.
+    pub fn scale(self) -> Vec2 {
+        match self {
+            ArenaSize::Small => Vec2::splat(0.8),
+            ArenaSize::Classic => Vec2::ONE,
+            ArenaSize::Wide => Vec2::new(1.25, 1.0),
+            ArenaSize::Tall => Vec2::new(1.0, 1.3),
+        }
+    }
+}
+
+/// Playable area of the currently loaded arena, used to keep rackets on the court.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct ArenaBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+/// Half the size of the area that has to stay in view for all of the current
+/// arena to be seen, centered on the origin.
+#[derive(Resource, Default, Debug)]
+pub struct ArenaExtent(pub Vec2);
+
+#[derive(Default)]
+pub struct ArenaLoader;
+
+impl AssetLoader for ArenaLoader {
+    type Asset = Arena;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Arena, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct ArenaHandle(Handle<Arena>);
+
+/// Everything spawned from the arena layout. Despawned when the layout is reloaded.
+#[derive(Component)]
+struct ArenaPiece;
+
+/// The arena matches are played in. `--arena <name>` picks
+/// `assets/arenas/<name>.arena.ron`, or the arena a mod brought by that name.
+pub fn arena_name() -> String {
+    cli_arg("arena").unwrap_or_else(|| DEFAULT_ARENA.to_string())
+}
+
+/// Has the next match played in the arena called `name`.
+pub fn use_arena(commands: &mut Commands, asset_server: &AssetServer, mods: &Mods, name: &str) {
+    let path = mods
+        .asset_path(Content::Arena, name)
+        .unwrap_or_else(|| format!("arenas/{}.arena.ron", name));
+    let handle = asset_server.load(&path);
+    commands.insert_resource(ArenaHandle(handle));
+}
+
+fn load_arena(mut commands: Commands, asset_server: Res<AssetServer>, mods: Res<Mods>) {
+    use_arena(&mut commands, &asset_server, &mods, &arena_name());
+}
+
+fn spawn_arena(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Arena>>,
+    arenas: Res<Assets<Arena>>,
+    handle: Res<ArenaHandle>,
+    pieces: Query<Entity, With<ArenaPiece>>,
+    mut clear_color: ResMut<ClearColor>,
+    mut bounds: ResMut<ArenaBounds>,
+    mut extent: ResMut<ArenaExtent>,
+    mut size: ResMut<ArenaSize>,
+    theme: Option<Res<Theme>>,
+    settings: Res<Settings>,
+    rules: Res<MatchRules>,
+    mode: Res<GameMode>,
+    saved: Option<Res<SavedMatch>>,
+    playback: Option<Res<Playback>>,
+) {
+    // Spawn the arena when a match starts, and again whenever its file changes
+    let reloaded = events.read().any(|event| match event {
+        AssetEvent::Modified { id } => *id == handle.0.id(),
+        _ => false,
+    });
+    if !pieces.is_empty() && !reloaded {
+        return;
+    }
+
+    if let Some(arena) = arenas.get(&handle.0) {
+        // A saved match carries on where it was played, and a replay's played
+        // back there, and both ends of an online match have to play on the
+        // same court. Otherwise it's the match's own size, if it has one
+        *size = match (saved, playback, *mode) {
+            (Some(saved), _, _) => saved.arena_size,
+            (None, Some(playback), _) => playback.arena_size(),
+            (None, None, GameMode::Online) => ArenaSize::Classic,
+            (None, None, _) => rules.arena_size.unwrap_or(settings.arena_size),
+        };
+        let arena = &arena.scaled(size.scale());
+        info!("Loading arena \"{}\" ({:?})", arena.name, *size);
+
+        for entity in pieces.iter() {
+            commands.entity(entity).despawn();
+        }
+
+        clear_color.0 = theme
+            .and_then(|theme| theme.background)
+            .unwrap_or(arena.background);
+        *bounds = arena.bounds();
+        *extent = arena.extent();
+
+        spawn_layout(&mut commands, arena);
+        spawn_markings(&mut commands, &bounds);
+    }
+}
+
+/// Spawns the walls, obstacles, goals and rackets of `arena`.
+pub fn spawn_layout(commands: &mut Commands, arena: &Arena) {
+    for wall in &arena.walls {
+        spawn_block(commands, wall, ThemePart::Wall).insert(Collider);
+    }
+    for obstacle in &arena.obstacles {
+        spawn_block(commands, obstacle, ThemePart::Obstacle)
+            .insert(Collider)
+            .insert(Obstacle);
+    }
+    for goal in &arena.goals {
+        let block = Block {
+            position: goal.position,
+            size: goal.size,
+        };
+        spawn_block(commands, &block, ThemePart::Goal)
+            .insert(Goal)
+            .insert(goal.side);
+    }
+
+    for side in [Side::Left, Side::Right] {
+        let goal = match arena.goal(side) {
+            Some(goal) => goal,
+            None => {
+                warn!("Arena \"{}\" has no {:?} goal", arena.name, side);
+                continue;
+            }
+        };
+        // Rackets stand a fixed distance in front of their own goal line
+        let goal_line = goal.position.x - side.direction() * goal.size.x / 2.0;
+        let x = goal_line - side.direction() * (RACKET_WALL_OFFSET + RACKET_THICCNESS / 2.0);
+        spawn_racket(commands, side, Vec3::new(x, goal.position.y, 0.0))
+            .insert(ArenaPiece)
+            .insert(StateScoped(AppState::Playing));
+    }
+}
+
+/// Paints the court lines inside `bounds`: its outline, a dashed center line
+/// with a circle round the middle, and a line down the length of the court
+/// splitting each half into two serve boxes.
+fn spawn_markings(commands: &mut Commands, bounds: &ArenaBounds) {
+    let center = (bounds.min + bounds.max) / 2.0;
+    let size = bounds.max - bounds.min;
+    let mut line = |position: Vec2, size: Vec2, rotation: f32| {
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform {
+                    translation: position.extend(MARKING_Z),
+                    rotation: Quat::from_rotation_z(rotation),
+                    scale: size.extend(1.0),
+                },
+                ..default()
+            },
+            ThemePart::Marking,
+            ArenaPiece,
+            StateScoped(AppState::Playing),
+        ));
+    };
+
+    // Outline, just inside the bounds
+    let inset = MARKING_WIDTH / 2.0;
+    for y in [bounds.min.y + inset, bounds.max.y - inset] {
+        line(
+            Vec2::new(center.x, y),
+            Vec2::new(size.x, MARKING_WIDTH),
+            0.0,
+        );
+    }
+    for x in [bounds.min.x + inset, bounds.max.x - inset] {
+        line(
+            Vec2::new(x, center.y),
+            Vec2::new(MARKING_WIDTH, size.y),
+            0.0,
+        );
+    }
+
+    // Serve boxes
+    line(center, Vec2::new(size.x, MARKING_WIDTH), 0.0);
+
+    // Dashed center line, with the dashes spread evenly from wall to wall
+    let dashes = ((size.y + CENTER_GAP) / (CENTER_DASH + CENTER_GAP))
+        .floor()
+        .max(1.0);
+    let spacing = size.y / dashes;
+    for dash in 0..dashes as usize {
+        let y = bounds.min.y + spacing * (dash as f32 + 0.5);
+        line(
+            Vec2::new(center.x, y),
+            Vec2::new(MARKING_WIDTH, spacing - CENTER_GAP),
+            0.0,
+        );
+    }
+
+    // Center circle, as short tangent strokes
+    let stroke = TAU * CENTER_CIRCLE_RADIUS / CENTER_CIRCLE_SEGMENTS as f32;
+    for segment in 0..CENTER_CIRCLE_SEGMENTS {
+        let angle = TAU * segment as f32 / CENTER_CIRCLE_SEGMENTS as f32;
+        let position = center + Vec2::new(angle.cos(), angle.sin()) * CENTER_CIRCLE_RADIUS;
+        line(position, Vec2::new(MARKING_WIDTH, stroke), angle);
+    }
+}
+
+fn spawn_block<'a>(
+    commands: &'a mut Commands,
+    block: &Block,
+    part: ThemePart,
+) -> bevy::ecs::system::EntityCommands<'a> {
+    let mut entity = commands.spawn(SpriteBundle {
+        transform: Transform {
+            // The z-scale of 2D objects must always be 1.0,
+            // or their ordering will be affected in surprising ways.
+            // See https://github.com/bevyengine/bevy/issues/4149
+            translation: block.position.extend(0.0),
+            scale: block.size.extend(1.0),
+            ..default()
+        },
+        ..default()
+    });
+    entity
+        .insert(part)
+        .insert(ArenaPiece)
+        .insert(StateScoped(AppState::Playing));
+    entity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// However the court's stretched, the walls meet the goals along the
+    /// court's edges, and the court's stretched just as much.
+    #[test]
+    fn stretched_courts_stay_closed() {
+        let bytes = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/arenas/pillars.arena.ron"
+        ))
+        .unwrap();
+        let arena: Arena = ron::de::from_bytes(&bytes).unwrap();
+        let unstretched = arena.bounds();
+
+        for size in ARENA_SIZES {
+            let scaled = arena.scaled(size.scale());
+            let bounds = scaled.bounds();
+            assert!(
+                bounds.min.abs_diff_eq(unstretched.min * size.scale(), 1e-3),
+                "{:?}",
+                size
+            );
+            assert!(
+                bounds.max.abs_diff_eq(unstretched.max * size.scale(), 1e-3),
+                "{:?}",
+                size
+            );
+
+            for wall in &scaled.walls {
+                let inner_edge = wall.position.y.abs() - wall.size.y / 2.0;
+                assert!((inner_edge - bounds.max.y).abs() < 1e-3, "{:?}", size);
+                assert_eq!(wall.size.y, arena.walls[0].size.y, "{:?}", size);
+            }
+            for (obstacle, original) in scaled.obstacles.iter().zip(&arena.obstacles) {
+                assert_eq!(obstacle.size, original.size);
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Stars drifting slowly by far behind the court, in the theme's backdrop
+//! paint. The nearer ones are bigger and drift faster, and they all twinkle.
+//!
+//! Nothing moves with reduced effects on, the stars are hidden then.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::ArenaExtent, settings::Settings, sim::SimRng, start_match, stats, theme::ThemePart,
+    AppState, MatchState,
+};
+
+const STARS: usize = 80;
+// Sizes of the farthest and the nearest stars
+const STAR_SIZES: (f32, f32) = (1.0, 3.0);
+// How fast a star of size 1 drifts to the left, in pixels per second
+const DRIFT: f32 = 6.0;
+// Seconds a twinkle takes, give or take, and how small a star gets in one
+const TWINKLE_TIMES: (f32, f32) = (2.0, 5.0);
+const TWINKLE_DEPTH: f32 = 0.5;
+// Room around the court so leaning cameras don't find an edge
+const MARGIN: f32 = 1.25;
+// Far behind, under the court markings
+const BACKDROP_Z: f32 = -2.0;
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Playing), spawn_stars.after(start_match))
+            .add_systems(Update, drift_stars.run_if(in_state(MatchState::Running)));
+    }
+}
+
+#[derive(Component)]
+struct Star {
+    /// Where it is, from -1 to 1 across the backdrop each way
+    position: Vec2,
+    size: f32,
+    twinkle_time: f32,
+    /// Where it is in its twinkle, from 0 to 1
+    phase: f32,
+}
+
+fn spawn_stars(mut commands: Commands) {
+    let mut rng = SimRng::new(stats::now());
+    for _ in 0..STARS {
+        commands.spawn((
+            SpriteBundle {
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Star {
+                position: Vec2::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0)),
+                size: rng.range(STAR_SIZES.0, STAR_SIZES.1),
+                twinkle_time: rng.range(TWINKLE_TIMES.0, TWINKLE_TIMES.1),
+                phase: rng.range(0.0, 1.0),
+            },
+            ThemePart::Backdrop,
+            StateScoped(AppState::Playing),
+        ));
+    }
+}
+
+/// Drifts the stars to the left, round to the right once they're off the
+/// edge, and twinkles them.
+fn drift_stars(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    extent: Res<ArenaExtent>,
+    mut stars: Query<(&mut Star, &mut Transform, &mut Visibility)>,
+) {
+    let half_size = extent.0 * MARGIN;
+    for (mut star, mut transform, mut visibility) in stars.iter_mut() {
+        if settings.effects_reduced() || half_size == Vec2::ZERO {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        let drift = DRIFT * star.size * time.delta_seconds() / half_size.x;
+        star.position.x = (star.position.x - drift + 1.0).rem_euclid(2.0) - 1.0;
+        transform.translation = (star.position * half_size).extend(BACKDROP_Z);
+
+        star.phase = (star.phase + time.delta_seconds() / star.twinkle_time).fract();
+        let twinkle = 1.0 - TWINKLE_DEPTH * (0.5 + 0.5 * (star.phase * TAU).sin());
+        transform.scale = Vec3::new(star.size * twinkle, star.size * twinkle, 1.0);
+    }
+}
@@ -0,0 +1,225 @@
+//! The ball-cam: a second camera up close on the ball, shown picture in
+//! picture in the bottom right corner of the window, with the ball's speed
+//! next to it. Turned on in the settings, or with Tab mid-match.
+//!
+//! The speed is drawn on a render layer only the ball-cam sees, so it's not
+//! on the court itself. The ball-cam draws after the main camera, and the CRT
+//! filter's if that's on, so it's never filtered.
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{ClearColorConfig, ScalingMode, Viewport},
+        view::RenderLayers,
+    },
+    window::PrimaryWindow,
+};
+
+use crate::{
+    settings::Settings,
+    sim::{Ball, Netted, Velocity},
+    ui::{text_style, UiFont, DIM_TEXT_COLOR, PANEL_COLOR},
+    AppState, MatchState,
+};
+
+/// Only the ball-cam sees this layer.
+const BALL_CAM_LAYER: usize = 1;
+/// How much of the window's width the picture takes up.
+const WIDTH: f32 = 0.28;
+const ASPECT: f32 = 16.0 / 9.0;
+/// Logical pixels between the picture and the edges of the window.
+const MARGIN: f32 = 16.0;
+const BORDER: f32 = 3.0;
+/// How much of the court the ball-cam sees above and below the ball.
+const HALF_HEIGHT: f32 = 90.0;
+/// How quickly it catches up with the ball, per second.
+const EASING: f32 = 8.0;
+const SPEED_SIZE: f32 = 14.0;
+// Over everything on the court, even the dark of a night match
+const LABEL_Z: f32 = 60.0;
+/// Where the speed goes from the ball.
+const SPEED_OFFSET: Vec2 = Vec2::new(0.0, -24.0);
+
+pub struct BallCamPlugin;
+
+impl Plugin for BallCamPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_ball_cam,
+                show_ball_cam.after(toggle_ball_cam),
+                follow_ball.after(show_ball_cam),
+            )
+                .run_if(in_state(MatchState::Running)),
+        );
+    }
+}
+
+/// The camera, the frame behind the picture and the speed readout, all of
+/// which come and go together.
+#[derive(Component)]
+struct BallCam;
+
+#[derive(Component)]
+struct BallCamCamera;
+
+#[derive(Component)]
+struct BallCamFrame;
+
+#[derive(Component)]
+struct SpeedLabel;
+
+fn toggle_ball_cam(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        settings.ball_cam = !settings.ball_cam;
+    }
+}
+
+/// Where the picture goes in the window, its corner and size in logical
+/// pixels, from the top left.
+fn picture(window: &Window) -> (Vec2, Vec2) {
+    let size = Vec2::new(window.width() * WIDTH, window.width() * WIDTH / ASPECT);
+    let corner = Vec2::new(window.width(), window.height()) - size - MARGIN;
+    (corner, size)
+}
+
+/// Spawns the ball-cam when it's turned on and despawns it when it's turned
+/// off, and keeps the picture in its corner as the window changes size.
+fn show_ball_cam(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    font: Res<UiFont>,
+    shown: Query<Entity, With<BallCam>>,
+    mut cameras: Query<&mut Camera, With<BallCamCamera>>,
+    mut frames: Query<&mut Style, With<BallCamFrame>>,
+) {
+    let on = !shown.is_empty();
+    if settings.ball_cam != on {
+        if on {
+            for entity in shown.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        } else {
+            spawn_ball_cam(&mut commands, &font);
+        }
+        return;
+    }
+
+    let window = match windows.get_single() {
+        Ok(window) if window.physical_width() > 0 => window,
+        _ => return,
+    };
+    let (corner, size) = picture(window);
+    let scale = window.scale_factor();
+    let viewport = Viewport {
+        physical_position: (corner * scale).as_uvec2(),
+        physical_size: (size * scale).as_uvec2().max(UVec2::ONE),
+        ..default()
+    };
+    for mut camera in cameras.iter_mut() {
+        if camera
+            .viewport
+            .as_ref()
+            .map(|old| (old.physical_position, old.physical_size))
+            != Some((viewport.physical_position, viewport.physical_size))
+        {
+            camera.viewport = Some(viewport.clone());
+        }
+    }
+    for mut style in frames.iter_mut() {
+        style.right = Val::Px(MARGIN - BORDER);
+        style.bottom = Val::Px(MARGIN - BORDER);
+        style.width = Val::Px(size.x + 2.0 * BORDER);
+        style.height = Val::Px(size.y + 2.0 * BORDER);
+    }
+}
+
+fn spawn_ball_cam(commands: &mut Commands, font: &UiFont) {
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                // After the main camera and the CRT filter's
+                order: 2,
+                // Over what's already been drawn, only where its picture is
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            projection: OrthographicProjection {
+                scaling_mode: ScalingMode::Fixed {
+                    width: 2.0 * HALF_HEIGHT * ASPECT,
+                    height: 2.0 * HALF_HEIGHT,
+                },
+                ..Camera2dBundle::default().projection
+            },
+            ..default()
+        },
+        RenderLayers::default().with(BALL_CAM_LAYER),
+        BallCamCamera,
+        BallCam,
+        StateScoped(AppState::Playing),
+    ));
+
+    // Behind the picture, drawn with the rest of the UI before it
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            background_color: PANEL_COLOR.into(),
+            ..default()
+        },
+        BallCamFrame,
+        BallCam,
+        StateScoped(AppState::Playing),
+    ));
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", text_style(font, SPEED_SIZE, DIM_TEXT_COLOR))
+                .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0.0, 0.0, LABEL_Z),
+            ..default()
+        },
+        RenderLayers::layer(BALL_CAM_LAYER),
+        SpeedLabel,
+        BallCam,
+        StateScoped(AppState::Playing),
+    ));
+}
+
+/// Eases the ball-cam towards the fastest ball in play, with its speed
+/// under it.
+fn follow_ball(
+    time: Res<Time>,
+    balls: Query<(&Transform, &Velocity), (With<Ball>, Without<Netted>)>,
+    mut cameras: Query<&mut Transform, (With<BallCamCamera>, Without<Ball>)>,
+    mut labels: Query<
+        (&mut Transform, &mut Text),
+        (With<SpeedLabel>, Without<Ball>, Without<BallCamCamera>),
+    >,
+) {
+    let ball = balls
+        .iter()
+        .max_by(|(_, a), (_, b)| a.length().total_cmp(&b.length()));
+    let (position, speed) = match ball {
+        Some((transform, velocity)) => (transform.translation.truncate(), velocity.length()),
+        None => return,
+    };
+
+    let easing = 1.0 - (-EASING * time.delta_seconds()).exp();
+    for mut transform in cameras.iter_mut() {
+        let at = transform.translation.truncate();
+        let to = at + (position - at) * easing;
+        transform.translation = to.extend(transform.translation.z);
+    }
+    for (mut transform, mut text) in labels.iter_mut() {
+        transform.translation = (position + SPEED_OFFSET).extend(LABEL_Z);
+        let value = format!("{:.0}", speed);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
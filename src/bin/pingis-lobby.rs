@@ -0,0 +1,181 @@
+//! Lobby for quick matches. Pairs up players looking for a game two by two
+//! and tells each where to find the other, after which they play each other
+//! directly. If they can't, it passes their packets on between them for as
+//! long as the match goes on, and forgets them once it's over.
+//!
+//! `pingis-lobby --port <port>`
+
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::*};
+use pingis::{
+    cli_arg,
+    lobby::{LobbyMessage, SeekerMessage},
+    logging::LoggingPlugin,
+    relay::{decode, encode, receive_all, TIMEOUT},
+};
+
+const DEFAULT_PORT: u16 = 7100;
+// Seconds between looks at the socket, which is all the lobby does
+const POLL_INTERVAL: f32 = 0.05;
+
+fn main() {
+    let port = cli_arg("port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+
+    let socket = UdpSocket::bind(("0.0.0.0", port)).expect("couldn't bind the lobby port");
+    socket
+        .set_nonblocking(true)
+        .expect("couldn't make the socket non-blocking");
+
+    App::new()
+        .add_plugins(LoggingPlugin)
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f32(
+                POLL_INTERVAL,
+            ))),
+        )
+        .insert_resource(Lobby {
+            socket,
+            waiting: Vec::new(),
+            matched: HashMap::new(),
+        })
+        .add_systems(Startup, move || info!("Lobby open on port {}", port))
+        .add_systems(Update, pair_players)
+        .run();
+}
+
+#[derive(Resource)]
+struct Lobby {
+    socket: UdpSocket,
+    /// Everyone looking for a game, longest waiting first
+    waiting: Vec<Seeker>,
+    /// Everyone paired up, until they've gone quiet. Asking again gets them
+    /// the same answer, in case the first was lost on the way
+    matched: HashMap<SocketAddr, Pairing>,
+}
+
+struct Seeker {
+    address: SocketAddr,
+    last_heard: f64,
+}
+
+struct Pairing {
+    peer: SocketAddr,
+    player_number: i32,
+    seed: u64,
+    last_heard: f64,
+}
+
+impl Pairing {
+    fn message(&self) -> LobbyMessage {
+        LobbyMessage::Matched {
+            peer: self.peer,
+            player_number: self.player_number,
+            seed: self.seed,
+        }
+    }
+}
+
+impl Lobby {
+    fn send(&self, address: SocketAddr, message: &LobbyMessage) {
+        if let Err(error) = self.socket.send_to(&encode(message), address) {
+            debug!("Couldn't send to {}: {}", address, error);
+        }
+    }
+
+    fn seek(&mut self, address: SocketAddr, now: f64) {
+        if let Some(pairing) = self.matched.get_mut(&address) {
+            pairing.last_heard = now;
+            let message = pairing.message();
+            self.send(address, &message);
+            return;
+        }
+        if let Some(seeker) = self
+            .waiting
+            .iter_mut()
+            .find(|seeker| seeker.address == address)
+        {
+            seeker.last_heard = now;
+            self.send(address, &LobbyMessage::Waiting);
+            return;
+        }
+        if self.waiting.is_empty() {
+            info!("{} is looking for a match", address);
+            self.waiting.push(Seeker {
+                address,
+                last_heard: now,
+            });
+            self.send(address, &LobbyMessage::Waiting);
+            return;
+        }
+
+        // Whoever's waited longest gets the newcomer, and gets to be player 1
+        let first = self.waiting.remove(0).address;
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        info!("Matched {} with {}, seed {}", first, address, seed);
+        for (player_number, (player, peer)) in
+            [(first, address), (address, first)].into_iter().enumerate()
+        {
+            let pairing = Pairing {
+                peer,
+                player_number: player_number as i32 + 1,
+                seed,
+                last_heard: now,
+            };
+            self.send(player, &pairing.message());
+            self.matched.insert(player, pairing);
+        }
+    }
+
+    /// Passes `packet` on from `address` to whoever they were paired with.
+    /// Either of them relaying keeps both paired, as the other may only just
+    /// be finding out it has to.
+    fn relay(&mut self, address: SocketAddr, packet: Vec<u8>, now: f64) {
+        let peer = match self.matched.get(&address) {
+            Some(pairing) => pairing.peer,
+            None => return,
+        };
+        for player in [address, peer] {
+            if let Some(pairing) = self.matched.get_mut(&player) {
+                pairing.last_heard = now;
+            }
+        }
+        self.send(peer, &LobbyMessage::Relayed(packet));
+    }
+}
+
+fn pair_players(mut lobby: ResMut<Lobby>, time: Res<Time>) {
+    let now = time.elapsed_seconds_f64();
+
+    for (address, packet) in receive_all(&lobby.socket) {
+        match decode(&packet) {
+            Some(SeekerMessage::Seek) => lobby.seek(address, now),
+            Some(SeekerMessage::Cancel) => {
+                info!("{} stopped looking", address);
+                lobby.waiting.retain(|seeker| seeker.address != address);
+            }
+            Some(SeekerMessage::Relay(packet)) => lobby.relay(address, packet, now),
+            None => debug!("Ignoring a garbled packet from {}", address),
+        }
+    }
+
+    lobby.waiting.retain(|seeker| {
+        let silent = now - seeker.last_heard > TIMEOUT;
+        if silent {
+            info!("{} timed out", seeker.address);
+        }
+        !silent
+    });
+    lobby
+        .matched
+        .retain(|_, pairing| now - pairing.last_heard < TIMEOUT);
+}
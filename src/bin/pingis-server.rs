@@ -0,0 +1,296 @@
+//! Dedicated server for online matches. Runs the simulation without a window
+//! and relays its state to the two clients that join.
+//!
+//! `pingis-server --port <port> --arena <name> --seed <number>`
+
+// Same as the game: systems take what they need as parameters
+#![allow(clippy::too_many_arguments)]
+
+use std::{
+    net::{SocketAddr, UdpSocket},
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::*};
+use pingis::{
+    arena::{spawn_layout, Arena},
+    cli_arg,
+    logging::LoggingPlugin,
+    protocol::{BUILD, PROTOCOL_VERSION},
+    relay::{decode, encode, receive_all, ClientMessage, MatchSnapshot, ServerMessage, TIMEOUT},
+    sim::{
+        self, spawn_ball, Ball, BallHitEvent, CollisionEvent, FixedStepPlugin, GameplayConfig,
+        GoalEvent, MatchClock, MatchEndedEvent, MatchOver, MatchRules, MatchStats, Portals, Racket,
+        Rally, Score, ServeCallEvent, ServeEvent, SimInput, SimRng, SimSystems, TimeStep, Velocity,
+        Wind,
+    },
+};
+
+const DEFAULT_PORT: u16 = 7000;
+const DEFAULT_ARENA: &str = "classic";
+
+fn main() {
+    let port = cli_arg("port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let arena = load_arena(&cli_arg("arena").unwrap_or_else(|| DEFAULT_ARENA.to_string()));
+    let seed = cli_arg("seed").and_then(|seed| seed.parse().ok());
+
+    let socket = UdpSocket::bind(("0.0.0.0", port)).expect("couldn't bind the server port");
+    socket
+        .set_nonblocking(true)
+        .expect("couldn't make the socket non-blocking");
+
+    App::new()
+        .add_plugins(LoggingPlugin)
+        // Poll twice per step so packets don't sit around waiting
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f32(
+                TimeStep::default().seconds() / 2.0,
+            ))),
+        )
+        .init_resource::<MatchRules>()
+        .insert_resource(arena.bounds())
+        .insert_resource(ServerArena(arena))
+        .insert_resource(Server {
+            socket,
+            clients: [None, None],
+            seed,
+        })
+        .init_resource::<Score>()
+        .init_resource::<Rally>()
+        .init_resource::<MatchStats>()
+        .init_resource::<GameplayConfig>()
+        .init_resource::<MatchOver>()
+        .init_resource::<MatchClock>()
+        .init_resource::<SimInput>()
+        .init_resource::<SimRng>()
+        .init_resource::<Wind>()
+        .init_resource::<Portals>()
+        .add_event::<CollisionEvent>()
+        .add_event::<GoalEvent>()
+        .add_event::<BallHitEvent>()
+        .add_event::<ServeEvent>()
+        .add_event::<ServeCallEvent>()
+        .add_event::<MatchEndedEvent>()
+        .add_systems(Startup, move || info!("Listening on port {}", port))
+        .add_systems(Update, receive)
+        .add_systems(Update, manage_match.after(receive))
+        .add_plugins(FixedStepPlugin)
+        .add_systems(FixedUpdate, sim::systems().run_if(match_running))
+        .add_systems(FixedUpdate, broadcast.after(SimSystems))
+        .run();
+}
+
+/// Reads `assets/arenas/<name>.arena.ron`, from where Bevy's asset server would.
+fn load_arena(name: &str) -> Arena {
+    let root = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let exe = std::env::current_exe().ok()?;
+            Some(exe.parent()?.to_path_buf())
+        })
+        .unwrap_or_default();
+    let path = root.join(format!("assets/arenas/{}.arena.ron", name));
+    let bytes = std::fs::read(&path)
+        .unwrap_or_else(|error| panic!("couldn't read {}: {}", path.display(), error));
+    ron::de::from_bytes(&bytes)
+        .unwrap_or_else(|error| panic!("couldn't parse {}: {}", path.display(), error))
+}
+
+#[derive(Resource)]
+struct ServerArena(Arena);
+
+#[derive(Resource)]
+struct Server {
+    socket: UdpSocket,
+    /// Indexed by player number minus one
+    clients: [Option<Client>; 2],
+    /// Serves every match from the same seed, to play one again exactly
+    seed: Option<u64>,
+}
+
+struct Client {
+    address: SocketAddr,
+    last_heard: f64,
+}
+
+impl Server {
+    fn send(&self, address: SocketAddr, message: &ServerMessage) {
+        if let Err(error) = self.socket.send_to(&encode(message), address) {
+            debug!("Couldn't send to {}: {}", address, error);
+        }
+    }
+
+    fn slot_of(&self, address: SocketAddr) -> Option<usize> {
+        self.clients
+            .iter()
+            .position(|client| matches!(client, Some(client) if client.address == address))
+    }
+
+    fn is_full(&self) -> bool {
+        self.clients.iter().all(Option::is_some)
+    }
+}
+
+fn receive(mut server: ResMut<Server>, mut input: ResMut<SimInput>, time: Res<Time>) {
+    let now = time.elapsed_seconds_f64();
+
+    for (address, packet) in receive_all(&server.socket) {
+        let message = match decode(&packet) {
+            Some(message) => message,
+            None => continue,
+        };
+        let slot = server.slot_of(address);
+        if let Some(slot) = slot {
+            server.clients[slot].as_mut().unwrap().last_heard = now;
+        }
+
+        match (message, slot) {
+            (ClientMessage::Join { protocol, build }, _)
+                if protocol != PROTOCOL_VERSION || build != BUILD =>
+            {
+                info!(
+                    "Turning away {}, on protocol {} and build {}",
+                    address, protocol, build
+                );
+                let message = ServerMessage::Incompatible {
+                    protocol: PROTOCOL_VERSION,
+                    build: BUILD.to_string(),
+                };
+                server.send(address, &message);
+            }
+            (ClientMessage::Join { .. }, Some(slot)) => {
+                let player_number = slot as i32 + 1;
+                server.send(address, &ServerMessage::Welcome { player_number });
+            }
+            (ClientMessage::Join { .. }, None) => {
+                match server.clients.iter().position(Option::is_none) {
+                    Some(slot) => {
+                        info!("{} joined as player {}", address, slot + 1);
+                        server.clients[slot] = Some(Client {
+                            address,
+                            last_heard: now,
+                        });
+                        let player_number = slot as i32 + 1;
+                        server.send(address, &ServerMessage::Welcome { player_number });
+                    }
+                    None => server.send(address, &ServerMessage::Full),
+                }
+            }
+            (ClientMessage::Input(racket), Some(slot)) => input.0[slot] = racket,
+            (ClientMessage::Chat(text), Some(slot)) => {
+                let message = ServerMessage::Chat {
+                    player_number: slot as i32 + 1,
+                    text,
+                };
+                for (other, client) in server.clients.iter().enumerate() {
+                    if let Some(client) = client.as_ref().filter(|_| other != slot) {
+                        server.send(client.address, &message);
+                    }
+                }
+            }
+            (ClientMessage::Leave, Some(slot)) => {
+                info!("Player {} left", slot + 1);
+                server.clients[slot] = None;
+                input.0[slot] = default();
+            }
+            (_, None) => {}
+        }
+    }
+
+    for slot in 0..server.clients.len() {
+        let silent =
+            matches!(&server.clients[slot], Some(client) if now - client.last_heard > TIMEOUT);
+        if silent {
+            info!("Player {} timed out", slot + 1);
+            server.clients[slot] = None;
+            input.0[slot] = default();
+        }
+    }
+}
+
+/// Starts a fresh match once both players are in, and abandons it if one leaves.
+fn manage_match(
+    mut commands: Commands,
+    server: Res<Server>,
+    arena: Res<ServerArena>,
+    balls: Query<Entity, With<Ball>>,
+    pieces: Query<Entity, With<Transform>>,
+    mut score: ResMut<Score>,
+    mut rally: ResMut<Rally>,
+    mut stats: ResMut<MatchStats>,
+    mut match_over: ResMut<MatchOver>,
+    rules: Res<MatchRules>,
+    config: Res<GameplayConfig>,
+    mut rng: ResMut<SimRng>,
+    mut serve_events: EventWriter<ServeEvent>,
+) {
+    let playing = !balls.is_empty();
+
+    if server.is_full() && !playing {
+        let seed = server.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default()
+        });
+        info!("Starting match with seed {}", seed);
+
+        for entity in pieces.iter() {
+            commands.entity(entity).despawn();
+        }
+        spawn_layout(&mut commands, &arena.0);
+        *score = Score::default();
+        *rally = Rally::default();
+        *stats = MatchStats::default();
+        *match_over = MatchOver::default();
+        *rng = SimRng::new(seed);
+        spawn_ball(&mut commands, &rules, &config, &mut *rng, &mut serve_events);
+    } else if !server.is_full() && playing {
+        info!("Match abandoned");
+        for ball in balls.iter() {
+            commands.entity(ball).despawn();
+        }
+    }
+}
+
+fn match_running(
+    server: Res<Server>,
+    match_over: Res<MatchOver>,
+    balls: Query<(), With<Ball>>,
+) -> bool {
+    server.is_full() && match_over.0.is_none() && !balls.is_empty()
+}
+
+fn broadcast(
+    server: Res<Server>,
+    balls: Query<(&Transform, &Velocity), With<Ball>>,
+    rackets: Query<(&Racket, &Transform)>,
+    score: Res<Score>,
+    stats: Res<MatchStats>,
+    match_over: Res<MatchOver>,
+) {
+    let (ball_transform, ball_velocity) = match balls.iter().next() {
+        Some(ball) => ball,
+        None => return,
+    };
+
+    let mut snapshot = MatchSnapshot {
+        ball_position: ball_transform.translation.truncate(),
+        ball_velocity: ball_velocity.0,
+        rackets: [Vec2::ZERO; 2],
+        score: *score,
+        stats: *stats,
+        winner: match_over.0,
+    };
+    for (racket, transform) in rackets.iter() {
+        snapshot.rackets[(racket.player_number - 1) as usize] = transform.translation.truncate();
+    }
+
+    let message = ServerMessage::State(snapshot);
+    for client in server.clients.iter().flatten() {
+        server.send(client.address, &message);
+    }
+}
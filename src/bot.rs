@@ -0,0 +1,182 @@
+//! Anything that plays a racket for a player. Controllers see a [`Snapshot`]
+//! of the match and answer with a [`RacketInput`], pressing the same actions
+//! the keyboard does, so the built-in CPU and bots from elsewhere play by the same rules.
+//!
+//! Bots are compiled in by registering them with the [`BotRegistry`], which
+//! the ones in `src/bots.rs` do behind the `bots` feature, and scripted ones
+//! are registered as they're loaded. The settings offer every registered bot
+//! as player 2.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::Actions,
+    arena::ArenaBounds,
+    seams::{read_input, Clock},
+    sim::{extent, Ball, Netted, Racket, RacketInput, Velocity},
+    MatchState, Player,
+};
+
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BotRegistry>().add_systems(
+            Update,
+            drive::<Bot, Time>
+                .before(read_input::<Actions>)
+                .run_if(in_state(MatchState::Running)),
+        );
+    }
+}
+
+/// Plays a racket, a frame at a time.
+pub trait RacketController: Send + Sync + 'static {
+    fn control(&mut self, snapshot: &Snapshot) -> RacketInput;
+}
+
+/// What a controller gets to see of the match.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Time since the last snapshot
+    pub delta: Duration,
+    pub bounds: ArenaBounds,
+    /// The racket being played
+    pub racket: RacketView,
+    /// Everybody else's, partners and all
+    pub others: Vec<RacketView>,
+    /// Every ball in play, leaving out any on their way into the net
+    pub balls: Vec<BallView>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RacketView {
+    pub player_number: i32,
+    pub position: Vec2,
+    /// Width and height of the box the racket fills, however it's turned
+    pub extent: Vec2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BallView {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+}
+
+impl Snapshot {
+    /// The first ball heading towards the racket, if any are.
+    pub fn incoming(&self) -> Option<&BallView> {
+        let x = self.racket.position.x;
+        self.balls
+            .iter()
+            .find(|ball| (x - ball.position.x) * ball.velocity.x > 0.0)
+    }
+
+    /// Seconds until `ball` gets to the racket, along x.
+    pub fn arriving_in(&self, ball: &BallView) -> f32 {
+        (self.racket.position.x - ball.position.x) / ball.velocity.x
+    }
+}
+
+/// A bot from the [`BotRegistry`], playing for the player it's attached to.
+#[derive(Component)]
+pub struct Bot(Box<dyn RacketController>);
+
+impl RacketController for Bot {
+    fn control(&mut self, snapshot: &Snapshot) -> RacketInput {
+        self.0.control(snapshot)
+    }
+}
+
+/// Makes a new one of a bot, for every match it plays.
+type NewBot = Box<dyn Fn() -> Box<dyn RacketController> + Send + Sync>;
+
+/// Every bot compiled in or loaded, by name, in the order they were registered.
+#[derive(Resource, Default)]
+pub struct BotRegistry(Vec<(String, NewBot)>);
+
+impl BotRegistry {
+    /// Adds a bot, made afresh by `new` for every match it plays. A name
+    /// that's already taken is left with the bot it has.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        new: impl Fn() -> Box<dyn RacketController> + Send + Sync + 'static,
+    ) {
+        let name = name.into();
+        if self.contains(&name) {
+            warn!("There's already a bot called \"{}\"", name);
+            return;
+        }
+        self.0.push((name, Box::new(new)));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|(registered, _)| registered == name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// A new one of the bot called `name`, if there is one.
+    pub fn new_bot(&self, name: &str) -> Option<Bot> {
+        self.0
+            .iter()
+            .find(|(registered, _)| registered == name)
+            .map(|(_, new)| Bot(new()))
+    }
+}
+
+/// Has every player with a `C` attached play through it. Their keys do
+/// nothing in the meantime.
+pub(crate) fn drive<C: RacketController + Component, T: Clock>(
+    time: Res<T>,
+    bounds: Res<ArenaBounds>,
+    mut controlled: Query<(&Player, &mut C)>,
+    rackets: Query<(&Racket, &Transform)>,
+    balls: Query<(&Transform, &Velocity), (With<Ball>, Without<Netted>)>,
+    mut actions: ResMut<Actions>,
+) {
+    let views: Vec<(usize, RacketView)> = rackets
+        .iter()
+        .map(|(racket, transform)| {
+            let view = RacketView {
+                player_number: racket.player_number,
+                position: transform.translation.truncate(),
+                extent: extent(transform),
+            };
+            (racket.seat, view)
+        })
+        .collect();
+    let balls: Vec<BallView> = balls
+        .iter()
+        .map(|(transform, velocity)| BallView {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            radius: transform.scale.y / 2.0,
+        })
+        .collect();
+
+    for (player, mut controller) in controlled.iter_mut() {
+        let racket = match views.iter().find(|(seat, _)| *seat == player.seat) {
+            Some((_, view)) => *view,
+            None => continue,
+        };
+        let snapshot = Snapshot {
+            delta: time.delta(),
+            bounds: *bounds,
+            racket,
+            others: views
+                .iter()
+                .filter(|(seat, _)| *seat != player.seat)
+                .map(|(_, view)| *view)
+                .collect(),
+            balls: balls.clone(),
+        };
+        actions.take_over(player.seat, controller.control(&snapshot));
+    }
+}
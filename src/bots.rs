@@ -0,0 +1,43 @@
+//! Bots compiled into the game with the `bots` feature. Anything registered
+//! here turns up as an opponent in the settings.
+
+use bevy::prelude::*;
+
+use crate::{
+    bot::{BotRegistry, RacketController, Snapshot},
+    sim::RacketInput,
+};
+
+pub struct BotsPlugin;
+
+impl Plugin for BotsPlugin {
+    fn build(&self, app: &mut App) {
+        let mut registry = app
+            .init_resource::<BotRegistry>()
+            .world_mut()
+            .resource_mut::<BotRegistry>();
+        registry.register("Follower", || Box::new(Follower));
+    }
+}
+
+/// How far off the ball the racket's middle can be before it moves.
+const FOLLOWER_DEAD_ZONE: f32 = 10.0;
+
+/// Keeps level with the ball wherever it is, coming or going. Hard to get
+/// one past straight on, easy to beat with an angle.
+struct Follower;
+
+impl RacketController for Follower {
+    fn control(&mut self, snapshot: &Snapshot) -> RacketInput {
+        let ball = match snapshot.incoming().or_else(|| snapshot.balls.first()) {
+            Some(ball) => ball,
+            None => return RacketInput::default(),
+        };
+        let offset = ball.position.y - snapshot.racket.position.y;
+        RacketInput {
+            up: offset > FOLLOWER_DEAD_ZONE,
+            down: offset < -FOLLOWER_DEAD_ZONE,
+            ..RacketInput::default()
+        }
+    }
+}
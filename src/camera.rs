@@ -0,0 +1,97 @@
+//! A camera that eases a little way towards the ball, and pulls back a bit
+//! more when a rally gets fast, for anyone who turns it on in the settings.
+//! It only ever moves as far as still keeps all of the court in view.
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::ArenaExtent,
+    settings::Settings,
+    sim::{Ball, Netted, Velocity, BALL_SPEED},
+    AppState, MainCamera, MatchState,
+};
+
+// How much of the way to the ball the camera leans
+const FOLLOW: f32 = 0.15;
+// Zoomed out by this fraction just to have room to lean, and by up to this
+// much more as the ball speeds up from one speed to another
+const ROOM: f32 = 0.04;
+const FAST_ZOOM: f32 = 0.04;
+const FAST_FROM: f32 = 1.5 * BALL_SPEED;
+const FAST_FULL: f32 = 3.0 * BALL_SPEED;
+// How quickly the camera catches up with where it's going, per second
+const EASING: f32 = 3.0;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FollowCamera>()
+            .add_systems(Update, follow_ball.run_if(in_state(MatchState::Running)))
+            .add_systems(OnExit(AppState::Playing), stop_following);
+    }
+}
+
+/// How far the camera has followed the ball.
+#[derive(Resource, Default)]
+struct FollowCamera {
+    /// What's currently added to the camera's position
+    offset: Vec2,
+    /// How much further out the camera is zoomed, as a fraction
+    pull_back: f32,
+}
+
+fn follow_ball(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    extent: Res<ArenaExtent>,
+    mut follow: ResMut<FollowCamera>,
+    balls: Query<(&Transform, &Velocity), (With<Ball>, Without<Netted>)>,
+    mut cameras: Query<
+        (&mut Transform, &mut OrthographicProjection),
+        (With<MainCamera>, Without<Ball>),
+    >,
+) {
+    let (mut target, mut pull_back) = (Vec2::ZERO, 0.0);
+    if settings.follow_camera {
+        // With more than one ball in play, somewhere in among them
+        let count = balls.iter().count();
+        if count > 0 {
+            let sum = balls.iter().fold(Vec2::ZERO, |sum, (transform, _)| {
+                sum + transform.translation.truncate()
+            });
+            target = sum / count as f32 * FOLLOW;
+        }
+        let speed = balls
+            .iter()
+            .map(|(_, velocity)| velocity.length())
+            .fold(0.0, f32::max);
+        let fast = ((speed - FAST_FROM) / (FAST_FULL - FAST_FROM)).clamp(0.0, 1.0);
+        pull_back = ROOM + FAST_ZOOM * fast;
+    }
+
+    let easing = 1.0 - (-EASING * time.delta_seconds()).exp();
+    let pull_back = follow.pull_back + (pull_back - follow.pull_back) * easing;
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        // As far as there's room for, zoomed out as much as it is
+        let half_view = projection.area.max / projection.scale * (1.0 + pull_back);
+        let room = (half_view - extent.0).max(Vec2::ZERO);
+        let offset = (follow.offset + (target - follow.offset) * easing).clamp(-room, room);
+
+        transform.translation += (offset - follow.offset).extend(0.0);
+        projection.scale *= (1.0 + pull_back) / (1.0 + follow.pull_back);
+        follow.offset = offset;
+    }
+    follow.pull_back = pull_back;
+}
+
+fn stop_following(
+    mut follow: ResMut<FollowCamera>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        transform.translation -= follow.offset.extend(0.0);
+        projection.scale /= 1.0 + follow.pull_back;
+    }
+    *follow = FollowCamera::default();
+}
@@ -0,0 +1,367 @@
+//! The campaign: a ladder of CPU opponents for player 1 to beat one after
+//! another, each with a racket and a way of playing of their own. How far up
+//! the ladder the player has got is saved between sessions.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{load_personality, Cpu, Difficulty},
+    locale::Strings,
+    profiles::Profiles,
+    restart_state,
+    sim::{MatchOver, Racket, RacketLength, RacketSpeed, RACKET_LENGTH},
+    stats::data_dir,
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        PANEL_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, MatchNames, MatchState, Player,
+};
+
+const PROGRESS_FILE: &str = "campaign.json";
+/// How many opponents there are on the ladder.
+pub const RUNGS: usize = 5;
+const RUNG_WIDTH: f32 = 460.0;
+
+/// One opponent on the ladder.
+struct Rung {
+    /// Kept as it is in every language, being who the stats have them down as
+    name: &'static str,
+    /// Key of how they play in the [`Strings`], for the ladder screen
+    blurb: &'static str,
+    difficulty: Difficulty,
+    /// Which of the files in `assets/personalities` they play like
+    personality: &'static str,
+    /// As a multiple of the usual racket's
+    racket_length: f32,
+    racket_speed: f32,
+}
+
+/// Easiest first.
+const LADDER: [Rung; RUNGS] = [
+    Rung {
+        name: "Rookie",
+        blurb: "campaign.rookie",
+        difficulty: Difficulty::Normal,
+        personality: "lazy",
+        racket_length: 1.4,
+        racket_speed: 0.7,
+    },
+    Rung {
+        name: "Journeyman",
+        blurb: "campaign.journeyman",
+        difficulty: Difficulty::Normal,
+        personality: "steady",
+        racket_length: 1.1,
+        racket_speed: 0.9,
+    },
+    Rung {
+        name: "Trickster",
+        blurb: "campaign.trickster",
+        difficulty: Difficulty::Normal,
+        personality: "trickster",
+        racket_length: 1.0,
+        racket_speed: 1.1,
+    },
+    Rung {
+        name: "Bruiser",
+        blurb: "campaign.bruiser",
+        difficulty: Difficulty::Hard,
+        personality: "smasher",
+        racket_length: 0.9,
+        racket_speed: 1.1,
+    },
+    Rung {
+        name: "Champion",
+        blurb: "campaign.champion",
+        difficulty: Difficulty::Hard,
+        personality: "trickster",
+        racket_length: 0.8,
+        racket_speed: 1.4,
+    },
+];
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Campaign::load())
+            .add_systems(
+                OnEnter(AppState::Campaign),
+                (record_result, spawn_ladder.after(record_result)),
+            )
+            .add_systems(Update, ladder_action.run_if(in_state(AppState::Campaign)))
+            .add_systems(
+                Update,
+                (bring_on_opponent, fit_opponent).run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// What gets saved of the campaign.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+struct Progress {
+    /// Opponents beaten so far, from the bottom of the ladder up
+    beaten: usize,
+}
+
+/// How far up the ladder the player is, kept in a JSON file in the user's
+/// data directory.
+#[derive(Resource, Default)]
+pub struct Campaign {
+    path: Option<PathBuf>,
+    progress: Progress,
+    /// The rung being played for
+    playing: Option<usize>,
+}
+
+impl Campaign {
+    fn load() -> Self {
+        let path = data_dir().map(|dir| dir.join(PROGRESS_FILE));
+        let progress = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| match serde_json::from_slice(&bytes) {
+                Ok(progress) => Some(progress),
+                Err(error) => {
+                    warn!("Ignoring unreadable campaign progress: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Campaign {
+            path,
+            progress,
+            playing: None,
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(&self.progress)?)?;
+        Ok(())
+    }
+
+    fn set_beaten(&mut self, beaten: usize) {
+        self.progress.beaten = beaten;
+        if let Err(error) = self.save() {
+            warn!("Couldn't save campaign progress: {}", error);
+        }
+    }
+
+    /// The next opponent to beat, unless they've all been beaten.
+    fn next(&self) -> Option<usize> {
+        (self.progress.beaten < LADDER.len()).then_some(self.progress.beaten)
+    }
+
+    /// The rung of the opponent of the match being played, from 0 at the
+    /// bottom, if it's part of the campaign.
+    pub fn rung(&self, mode: GameMode) -> Option<usize> {
+        self.playing.filter(|_| mode == GameMode::Campaign)
+    }
+
+    /// The opponent of the match being played, if it's part of the campaign.
+    fn opponent(&self, mode: GameMode) -> Option<&'static Rung> {
+        self.rung(mode).map(|rung| &LADDER[rung])
+    }
+}
+
+#[derive(Component)]
+enum LadderAction {
+    Play,
+    StartOver,
+    Leave,
+}
+
+/// Moves the player up a rung if they won the match that was just played.
+fn record_result(mut campaign: ResMut<Campaign>, match_over: Res<MatchOver>) {
+    if let (Some(rung), Some(1)) = (campaign.playing.take(), match_over.0) {
+        if rung == campaign.progress.beaten {
+            campaign.set_beaten(rung + 1);
+        }
+    }
+}
+
+fn spawn_ladder(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    campaign: Res<Campaign>,
+) {
+    let next = campaign.next();
+
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Campaign)))
+        .with_children(|parent| {
+            let (title, color) = match next {
+                Some(_) => (strings.get("campaign.title"), TEXT_COLOR),
+                None => (strings.get("campaign.beaten_title"), ACCENT_COLOR),
+            };
+            parent.spawn(text(&font, title, 48.0, color).with_style(Style {
+                margin: UiRect::all(Val::Px(10.0)),
+                ..default()
+            }));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(16.0)),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    // Top of the ladder first
+                    for (index, rung) in LADDER.iter().enumerate().rev() {
+                        let (color, status) = if index < campaign.progress.beaten {
+                            (DIM_TEXT_COLOR, strings.get("campaign.beaten"))
+                        } else if Some(index) == next {
+                            (ACCENT_COLOR, strings.get("campaign.next"))
+                        } else {
+                            (TEXT_COLOR, "")
+                        };
+                        spawn_rung(parent, &font, &strings, index, rung, status, color);
+                    }
+                });
+
+            match next {
+                Some(index) => {
+                    let label = strings.fill("campaign.play", &[("name", &LADDER[index].name)]);
+                    spawn_button(parent, &font, 0, &label).insert(LadderAction::Play);
+                    spawn_button(parent, &font, 1, strings.get("leave"))
+                        .insert(LadderAction::Leave);
+                }
+                None => {
+                    parent.spawn(text(
+                        &font,
+                        strings.get("campaign.well_played"),
+                        24.0,
+                        TEXT_COLOR,
+                    ));
+                    spawn_button(parent, &font, 0, strings.get("campaign.start_over"))
+                        .insert(LadderAction::StartOver);
+                    spawn_button(parent, &font, 1, strings.get("back_to_menu"))
+                        .insert(LadderAction::Leave);
+                }
+            }
+        });
+}
+
+fn spawn_rung(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    strings: &Strings,
+    index: usize,
+    rung: &Rung,
+    status: &str,
+    color: Color,
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(RUNG_WIDTH),
+                flex_direction: FlexDirection::Column,
+                margin: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            let heading = format!("{}. {}   {}", index + 1, rung.name, status);
+            parent.spawn(text(font, heading, 22.0, color));
+            parent.spawn(text(font, strings.get(rung.blurb), 16.0, DIM_TEXT_COLOR));
+        });
+}
+
+fn ladder_action(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    actions: Query<&LadderAction>,
+    mut campaign: ResMut<Campaign>,
+    profiles: Res<Profiles>,
+    strings: Res<Strings>,
+    mut names: ResMut<MatchNames>,
+    mut mode: ResMut<GameMode>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Menu);
+        return;
+    }
+
+    for event in activated.read() {
+        match actions.get(event.0) {
+            Ok(LadderAction::Play) => {
+                if let Some(next) = campaign.next() {
+                    names.0 = Some([profiles.name(1, &strings), LADDER[next].name.to_string()]);
+                    campaign.playing = Some(next);
+                    *mode = GameMode::Campaign;
+                    state.set(AppState::Playing);
+                }
+            }
+            Ok(LadderAction::StartOver) => {
+                campaign.set_beaten(0);
+                // Back round to show the ladder from the bottom again
+                commands.add(restart_state);
+            }
+            Ok(LadderAction::Leave) => {
+                state.set(AppState::Menu);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Hands player 2 over to the CPU of the rung being played for, once the
+/// match has started.
+fn bring_on_opponent(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mode: Res<GameMode>,
+    campaign: Res<Campaign>,
+    players: Query<(Entity, &Player), Added<Player>>,
+) {
+    let rung = match campaign.opponent(*mode) {
+        Some(rung) => rung,
+        None => return,
+    };
+    let personality = load_personality(&asset_server, rung.personality);
+    let cpu = Cpu::new(rung.difficulty).with_personality(personality);
+    if let Some((entity, _)) = players.iter().find(|(_, player)| player.player_number == 2) {
+        commands.entity(entity).insert(cpu);
+    }
+}
+
+/// Gives the opponent's racket its size and speed, whenever the arena spawns one.
+fn fit_opponent(
+    mode: Res<GameMode>,
+    campaign: Res<Campaign>,
+    mut rackets: Query<(&Racket, &mut RacketLength, &mut RacketSpeed), Added<Racket>>,
+) {
+    let rung = match campaign.opponent(*mode) {
+        Some(rung) => rung,
+        None => return,
+    };
+    for (racket, mut length, mut speed) in rackets.iter_mut() {
+        if racket.player_number == 2 {
+            *length = RacketLength::new(RACKET_LENGTH * rung.racket_length);
+            *speed = RacketSpeed(rung.racket_speed);
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Screenshots and clips, for sharing a rally worth showing off. Tapping F12
+//! saves the window as a PNG, and holding it down saves the last
+//! [`CLIP_SECONDS`] of the match as a GIF instead, both in `captures/` in the
+//! data directory.
+//!
+//! Both are taken with Bevy's [`ScreenshotManager`], a screenshot when it's
+//! asked for and, while a match is on, [`CLIP_FPS`] of them a second for the
+//! clip, each shrunk down and kept until it's too old for one.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{prelude::*, render::view::screenshot::ScreenshotManager, window::PrimaryWindow};
+
+use crate::{
+    stats::{data_dir, now},
+    AppState,
+};
+
+const CAPTURE_DIR: &str = "captures";
+const CAPTURE_KEY: KeyCode = KeyCode::F12;
+// How long the key has to be held for a clip rather than a screenshot
+const HOLD_SECONDS: f64 = 0.5;
+const CLIP_SECONDS: usize = 5;
+const CLIP_FPS: usize = 10;
+// The widest a clip is, so it's small enough to pass around
+const CLIP_WIDTH: u32 = 320;
+// How hard the GIF encoder works at picking each frame's colours, from 1,
+// the best, to 30, the quickest
+const CLIP_QUANTIZE_SPEED: i32 = 10;
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Capture>()
+            .add_systems(Update, capture)
+            .add_systems(OnEnter(AppState::Playing), start_clip);
+    }
+}
+
+/// A frame of a clip, shrunk down.
+#[derive(Clone)]
+struct ClipFrame {
+    width: u32,
+    height: u32,
+    /// Three bytes a pixel, red, green and blue, row by row from the top
+    rgb: Vec<u8>,
+}
+
+/// The frames of the clip so far, shared with the screenshots still on
+/// their way back from the GPU.
+#[derive(Clone, Default)]
+struct Clip(Arc<Mutex<VecDeque<ClipFrame>>>);
+
+impl Clip {
+    fn push(&self, frame: ClipFrame) {
+        let Ok(mut clip) = self.0.lock() else {
+            return;
+        };
+        // A resized window starts the clip over, all its frames are the same size
+        if clip
+            .back()
+            .is_some_and(|last| (last.width, last.height) != (frame.width, frame.height))
+        {
+            clip.clear();
+        }
+        clip.push_back(frame);
+        while clip.len() > CLIP_SECONDS * CLIP_FPS {
+            clip.pop_front();
+        }
+    }
+
+    fn frames(&self) -> Vec<ClipFrame> {
+        self.0
+            .lock()
+            .map(|clip| clip.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn clear(&self) {
+        if let Ok(mut clip) = self.0.lock() {
+            clip.clear();
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Capture {
+    held_since: Option<f64>,
+    /// Whether holding the key has already saved a clip
+    clip_saved: bool,
+    last_clip_frame: f64,
+    clip: Clip,
+}
+
+fn start_clip(capture: Res<Capture>) {
+    capture.clip.clear();
+}
+
+fn capture(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<AppState>>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    mut capture: ResMut<Capture>,
+) {
+    let window = match windows.get_single() {
+        Ok(window) => window,
+        Err(_) => return,
+    };
+    let now = time.elapsed_seconds_f64();
+
+    if keyboard_input.just_pressed(CAPTURE_KEY) {
+        capture.held_since = Some(now);
+        capture.clip_saved = false;
+    }
+    let held_long = capture
+        .held_since
+        .is_some_and(|since| now - since >= HOLD_SECONDS);
+    if held_long && !capture.clip_saved {
+        capture.clip_saved = true;
+        let clip = capture.clip.frames();
+        if clip.is_empty() {
+            info!("There's nothing recorded yet to make a clip of");
+        } else {
+            save_in_background("clip", "gif", move || Ok(encode_gif(&clip)?));
+        }
+    }
+    if keyboard_input.just_released(CAPTURE_KEY) {
+        if !capture.clip_saved {
+            save_screenshot(&mut screenshots, window);
+        }
+        capture.held_since = None;
+    }
+
+    let recording = *state.get() == AppState::Playing;
+    if recording && now - capture.last_clip_frame >= 1.0 / CLIP_FPS as f64 {
+        let clip = capture.clip.clone();
+        // Only the one a frame, so there's no clip frame on a screenshot's
+        let taken =
+            screenshots.take_screenshot(window, move |image| match image.try_into_dynamic() {
+                Ok(image) => {
+                    let image = image.thumbnail(CLIP_WIDTH, u32::MAX).to_rgb8();
+                    clip.push(ClipFrame {
+                        width: image.width(),
+                        height: image.height(),
+                        rgb: image.into_raw(),
+                    });
+                }
+                Err(error) => warn!("Couldn't keep a frame for a clip: {}", error),
+            });
+        if taken.is_ok() {
+            capture.last_clip_frame = now;
+        }
+    }
+}
+
+fn save_screenshot(screenshots: &mut ScreenshotManager, window: Entity) {
+    let path = match capture_path("screenshot", "png", now()) {
+        Ok(path) => path,
+        Err(error) => {
+            warn!("Couldn't save a screenshot: {}", error);
+            return;
+        }
+    };
+    // It says where it's saved once it has been
+    if screenshots.save_screenshot_to_disk(window, path).is_err() {
+        warn!("Couldn't save a screenshot, there's one being taken already");
+    }
+}
+
+/// Where a capture called `name` taken at `taken_at` is saved.
+fn capture_path(
+    name: &str,
+    extension: &str,
+    taken_at: u64,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = data_dir().ok_or("no data directory")?.join(CAPTURE_DIR);
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{}-{}.{}", name, taken_at, extension)))
+}
+
+/// Encodes and writes a capture off the main thread, so saving one doesn't
+/// hitch the match it's taken in.
+fn save_in_background(
+    name: &'static str,
+    extension: &'static str,
+    encode: impl FnOnce() -> Result<Vec<u8>, Box<dyn std::error::Error>> + Send + 'static,
+) {
+    let taken_at = now();
+    std::thread::spawn(move || {
+        let save = || -> Result<PathBuf, Box<dyn std::error::Error>> {
+            let path = capture_path(name, extension, taken_at)?;
+            fs::write(&path, encode()?)?;
+            Ok(path)
+        };
+        match save() {
+            Ok(path) => info!("Saved a {} to {}", name, path.display()),
+            Err(error) => warn!("Couldn't save a {}: {}", name, error),
+        }
+    });
+}
+
+/// A looping GIF of the clip, played at the rate it was recorded at.
+fn encode_gif(clip: &[ClipFrame]) -> Result<Vec<u8>, gif::EncodingError> {
+    let (width, height) = clip
+        .first()
+        .map_or((0, 0), |frame| (frame.width as u16, frame.height as u16));
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut bytes, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for frame in clip {
+            let mut frame = gif::Frame::from_rgb_speed(
+                frame.width as u16,
+                frame.height as u16,
+                &frame.rgb,
+                CLIP_QUANTIZE_SPEED,
+            );
+            frame.delay = (100 / CLIP_FPS) as u16;
+            encoder.write_frame(&frame)?;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gif_holds_every_frame() {
+        let frame = |rgb: [u8; 3]| ClipFrame {
+            width: 3,
+            height: 2,
+            rgb: rgb.repeat(6),
+        };
+        let clip = [frame([255, 0, 0]), frame([0, 0, 255])];
+        let bytes = encode_gif(&clip).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (3, 2));
+        for recorded in &clip {
+            let frame = decoder.read_next_frame().unwrap().unwrap();
+            assert_eq!(frame.delay, (100 / CLIP_FPS) as u16);
+            let rgb: Vec<u8> = frame
+                .buffer
+                .chunks(4)
+                .flat_map(|pixel| pixel[..3].to_vec())
+                .collect();
+            assert_eq!(rgb, recorded.rgb);
+        }
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+}
@@ -0,0 +1,247 @@
+//! What happens when somebody scores: play stops for a moment, the camera
+//! leans in towards where the ball went in while its net lights up, a "+1"
+//! rises by the scorer's name, and once play picks up again the ball's serve
+//! is counted down while it settles in the net.
+//!
+//! Only local matches stop for it, online ones can't wait for anybody.
+
+use bevy::prelude::*;
+
+use crate::{
+    seams::Clock,
+    settings::Settings,
+    sim::{GoalEvent, MatchRules, Netted, Score, Side},
+    start_match,
+    tween::{Ease, Tween},
+    ui::{text, UiFont, ACCENT_COLOR},
+    AppState, GameMode, MainCamera, MatchState,
+};
+
+// Seconds play stops for, and how far into them the camera is leaning in
+// all the way and starts to lean back out
+const LENGTH: f32 = 1.0;
+const LEANED_IN: f32 = 0.3;
+const LEANING_OUT: f32 = 0.6;
+// Leaning all the way in zooms in by this fraction, and moves the camera this
+// fraction of the way to where the ball went in
+const ZOOM: f32 = 0.08;
+const PAN: f32 = 0.15;
+// How far the "+1" rises over the celebration, in pixels, from the top of the screen
+const POPUP_TOP: f32 = 64.0;
+const POPUP_RISE: f32 = 32.0;
+// What the serve is counted down in, so that every count is this long
+const COUNTDOWN_BEAT: f32 = 1.0 / 3.0;
+const COUNTDOWN_SIZE: f32 = 72.0;
+
+pub struct CelebrationPlugin;
+
+impl Plugin for CelebrationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Celebration>()
+            .add_systems(
+                OnEnter(AppState::Playing),
+                spawn_countdown.after(start_match),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_celebration,
+                    celebrate::<Time>.after(start_celebration),
+                    count_down_serve.after(celebrate::<Time>),
+                )
+                    .run_if(in_state(MatchState::Running)),
+            )
+            .add_systems(OnExit(AppState::Playing), stop_celebrating);
+    }
+}
+
+/// The celebration of the goal just scored, while it plays.
+#[derive(Resource, Default)]
+pub struct Celebration {
+    timeline: Option<Timeline>,
+    /// Where the ball went in
+    focus: Vec2,
+    /// What's currently added to the camera's position
+    pan: Vec2,
+    /// How far the camera's leaning in, from 0 to 1
+    lean: f32,
+}
+
+impl Celebration {
+    /// Whether play is stopped for it.
+    pub fn is_playing(&self) -> bool {
+        self.timeline.is_some()
+    }
+}
+
+/// How far along a sequence is, in seconds, for telling how far through each
+/// of its parts it is.
+#[derive(Default, Clone, Copy)]
+struct Timeline {
+    elapsed: f32,
+}
+
+impl Timeline {
+    /// How far through the part from `start` to `end` it is, from 0 until it
+    /// starts to 1 once it's over.
+    fn progress(&self, start: f32, end: f32) -> f32 {
+        ((self.elapsed - start) / (end - start)).clamp(0.0, 1.0)
+    }
+}
+
+/// Whether goals stop play for a celebration. Goals are too many to stop
+/// for in practice, and the only one in survival ends it.
+fn celebrates(mode: GameMode) -> bool {
+    !matches!(
+        mode,
+        GameMode::Practice | GameMode::Survival | GameMode::Online
+    )
+}
+
+/// The "+1" by the scorer's name.
+#[derive(Component)]
+struct Popup;
+
+/// Counts down to the next serve.
+#[derive(Component)]
+struct Countdown;
+
+fn spawn_countdown(mut commands: Commands, font: Res<UiFont>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn((text(&font, "", COUNTDOWN_SIZE, ACCENT_COLOR), Countdown));
+        });
+}
+
+fn start_celebration(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    font: Res<UiFont>,
+    mut goals: EventReader<GoalEvent>,
+    mut celebration: ResMut<Celebration>,
+    popups: Query<Entity, With<Popup>>,
+) {
+    let goal = match goals.read().last() {
+        Some(goal) if celebrates(*mode) => *goal,
+        _ => return,
+    };
+
+    celebration.timeline = Some(Timeline::default());
+    celebration.focus = goal.position;
+    for popup in popups.iter() {
+        commands.entity(popup).despawn_recursive();
+    }
+    // Next to the scorer's name on the scoreboard, either side of the middle
+    let (left, right) = if score.player_on(Side::Left) == goal.scored_by {
+        (Val::Percent(40.0), Val::Auto)
+    } else {
+        (Val::Auto, Val::Percent(40.0))
+    };
+    commands.spawn((
+        text(&font, "+1", 32.0, ACCENT_COLOR).with_style(Style {
+            position_type: PositionType::Absolute,
+            left,
+            right,
+            top: Val::Px(POPUP_TOP),
+            ..default()
+        }),
+        Tween::new(LENGTH, Ease::Out, |style: &mut Style, along| {
+            style.top = Val::Px(POPUP_TOP - POPUP_RISE * along);
+        }),
+        Tween::new(
+            LENGTH - LEANING_OUT,
+            Ease::Linear,
+            |text: &mut Text, along| {
+                text.sections[0].style.color.set_alpha(1.0 - along);
+            },
+        )
+        .after(LEANING_OUT)
+        .then_despawn(),
+        Popup,
+        StateScoped(AppState::Playing),
+    ));
+}
+
+fn celebrate<C: Clock>(
+    time: Res<C>,
+    settings: Res<Settings>,
+    mut celebration: ResMut<Celebration>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let mut timeline = match celebration.timeline {
+        Some(timeline) => timeline,
+        None => return,
+    };
+    timeline.elapsed += time.delta_seconds();
+
+    let lean = if settings.effects_reduced() {
+        0.0
+    } else {
+        Ease::Out.apply(timeline.progress(0.0, LEANED_IN))
+            * (1.0 - Ease::InOut.apply(timeline.progress(LEANING_OUT, LENGTH)))
+    };
+    let pan = celebration.focus * PAN * lean;
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        transform.translation += (pan - celebration.pan).extend(0.0);
+        projection.scale *= (1.0 - ZOOM * lean) / (1.0 - ZOOM * celebration.lean);
+    }
+    celebration.pan = pan;
+    celebration.lean = lean;
+
+    celebration.timeline = (timeline.elapsed < LENGTH).then_some(timeline);
+}
+
+/// Shows how long's left until the serve while a ball that was celebrated
+/// settles in the net, unless it won the match and there's no serve to come.
+fn count_down_serve(
+    mode: Res<GameMode>,
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    celebration: Res<Celebration>,
+    netted: Query<&Netted>,
+    mut countdowns: Query<&mut Text, With<Countdown>>,
+) {
+    let count = match netted.iter().next() {
+        Some(netted)
+            if celebrates(*mode) && !celebration.is_playing() && !rules.wins_match(&score) =>
+        {
+            (netted.time_to_serve() / COUNTDOWN_BEAT).ceil()
+        }
+        _ => 0.0,
+    };
+
+    for mut text in countdowns.iter_mut() {
+        text.sections[0].value = if count > 0.0 {
+            format!("{:.0}", count)
+        } else {
+            String::new()
+        };
+    }
+}
+
+fn stop_celebrating(
+    mut celebration: ResMut<Celebration>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        transform.translation -= celebration.pan.extend(0.0);
+        projection.scale /= 1.0 - ZOOM * celebration.lean;
+    }
+    *celebration = Celebration::default();
+}
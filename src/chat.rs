@@ -0,0 +1,192 @@
+//! Text chat during online matches.
+//!
+//! Enter opens a line to type into and sends it, Escape throws it away. While
+//! a line is open the keyboard belongs to the chat, so the rackets stay put and
+//! Escape doesn't leave the match. Whichever connection the match is using
+//! sends [`OutgoingChat`] and turns what arrives into [`ChatLine`]s.
+
+use std::collections::VecDeque;
+
+use bevy::{
+    input::{keyboard::KeyboardInput, InputSystem},
+    prelude::*,
+};
+
+use crate::{
+    actions::PressActions,
+    display::toggle_fullscreen,
+    locale::Strings,
+    ui::{text_style, typed, UiFont, DIM_TEXT_COLOR, TEXT_COLOR},
+    AppState, GameMode, MatchState,
+};
+
+/// Longest message that can be sent, in characters.
+pub const MAX_CHAT_LENGTH: usize = 120;
+// How many of the latest lines stay on screen
+const CHAT_LINES: usize = 6;
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatLog>()
+            .init_resource::<ChatDraft>()
+            .add_event::<OutgoingChat>()
+            .add_event::<ChatLine>()
+            // Before anything else looks at the keyboard, so typing can hide it
+            .add_systems(
+                PreUpdate,
+                type_chat
+                    .run_if(in_online_match)
+                    .after(InputSystem)
+                    .after(toggle_fullscreen)
+                    .before(PressActions),
+            )
+            .add_systems(OnEnter(AppState::Playing), spawn_chat)
+            .add_systems(
+                Update,
+                (log_chat, show_chat.after(log_chat)).run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// A message the local player typed, for the connection to send.
+#[derive(Event)]
+pub struct OutgoingChat(pub String);
+
+/// A message from either player, to show in the chat.
+#[derive(Event, Clone)]
+pub struct ChatLine {
+    pub player_number: i32,
+    pub text: String,
+}
+
+#[derive(Resource, Default)]
+struct ChatLog(VecDeque<ChatLine>);
+
+/// The line being typed, if the chat is open.
+#[derive(Resource, Default)]
+struct ChatDraft(Option<String>);
+
+#[derive(Component)]
+struct ChatOverlay;
+
+/// Drops anything that can't be shown on one line and caps the length, for
+/// messages coming from the other side.
+pub fn clean(text: &str) -> String {
+    text.chars()
+        .filter(|character| !character.is_control())
+        .take(MAX_CHAT_LENGTH)
+        .collect()
+}
+
+fn in_online_match(state: Res<State<AppState>>, mode: Res<GameMode>) -> bool {
+    *state.get() == AppState::Playing && *mode == GameMode::Online
+}
+
+fn type_chat(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut keys: EventReader<KeyboardInput>,
+    mut draft: ResMut<ChatDraft>,
+    mut outgoing: EventWriter<OutgoingChat>,
+) {
+    // Looking before borrowing mutably keeps the overlay from redrawing every frame
+    if draft.0.is_none() {
+        keys.clear();
+        if keyboard_input.just_pressed(KeyCode::Enter) {
+            keyboard_input.reset(KeyCode::Enter);
+            draft.0 = Some(String::new());
+        }
+        return;
+    }
+
+    let line = draft.0.get_or_insert_with(String::new);
+
+    for character in typed(keys.read()) {
+        if !character.is_control() && line.chars().count() < MAX_CHAT_LENGTH {
+            line.push(character);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        line.pop();
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        let line = draft.0.take().unwrap_or_default();
+        let line = line.trim();
+        if !line.is_empty() {
+            outgoing.send(OutgoingChat(line.to_string()));
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        draft.0 = None;
+    }
+
+    // Nothing else gets to see the keys while typing
+    keyboard_input.reset_all();
+}
+
+fn spawn_chat(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    mode: Res<GameMode>,
+    mut log: ResMut<ChatLog>,
+    mut draft: ResMut<ChatDraft>,
+) {
+    log.0.clear();
+    draft.0 = None;
+    if *mode != GameMode::Online {
+        return;
+    }
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new("", text_style(&font, 18.0, TEXT_COLOR)),
+            TextSection::new("", text_style(&font, 18.0, DIM_TEXT_COLOR)),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(16.0),
+            top: Val::Px(16.0),
+            ..default()
+        }),
+        ChatOverlay,
+        StateScoped(AppState::Playing),
+    ));
+}
+
+fn log_chat(mut lines: EventReader<ChatLine>, mut log: ResMut<ChatLog>) {
+    for line in lines.read() {
+        info!("Player {}: {}", line.player_number, line.text);
+        log.0.push_back(line.clone());
+        if log.0.len() > CHAT_LINES {
+            log.0.pop_front();
+        }
+    }
+}
+
+fn show_chat(
+    log: Res<ChatLog>,
+    draft: Res<ChatDraft>,
+    strings: Res<Strings>,
+    mut overlays: Query<&mut Text, With<ChatOverlay>>,
+) {
+    if !log.is_changed() && !draft.is_changed() {
+        return;
+    }
+    for mut text in overlays.iter_mut() {
+        text.sections[0].value = log
+            .0
+            .iter()
+            .map(|line| {
+                strings.fill(
+                    "chat.line",
+                    &[("number", &line.player_number), ("text", &line.text)],
+                ) + "\n"
+            })
+            .collect();
+        text.sections[1].value = match &draft.0 {
+            Some(line) => format!("> {}_", line),
+            None => strings.get("chat.hint").to_string(),
+        };
+    }
+}
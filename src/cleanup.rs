@@ -0,0 +1,123 @@
+//! Entities belonging to a state, despawned when it's left. Whatever a
+//! screen or a match spawns is [`StateScoped`] to the state it's spawned for,
+//! and goes with everything under it when that state's exited, however it's
+//! left: for another, or restarted. The pause menu goes the same way when the
+//! match is resumed or left, scoped to [`MatchState::Paused`].
+
+use bevy::prelude::*;
+
+use crate::{AppState, MatchState};
+
+pub struct CleanupPlugin;
+
+impl Plugin for CleanupPlugin {
+    fn build(&self, app: &mut App) {
+        app.enable_state_scoped_entities::<AppState>()
+            .enable_state_scoped_entities::<MatchState>();
+    }
+}
+
+/// Despawns what's scoped to `state`, for [`crate::restart_state`], which
+/// leaves and enters it again without a transition to do it.
+pub(crate) fn despawn_scoped<S: States>(world: &mut World, state: &S) {
+    let scoped: Vec<Entity> = world
+        .query::<(Entity, &StateScoped<S>)>()
+        .iter(world)
+        .filter(|(_, scoped)| scoped.0 == *state)
+        .map(|(entity, _)| entity)
+        .collect();
+    for entity in scoped {
+        despawn_with_children_recursive(world, entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+
+    /// Leaving a state despawns what's scoped to it and nothing else, and
+    /// pausing a match doesn't despawn the match.
+    #[test]
+    fn leaving_a_state_despawns_its_entities() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_state(AppState::Menu)
+            .add_sub_state::<MatchState>()
+            .add_plugins(CleanupPlugin);
+
+        let menu = app.world_mut().spawn(StateScoped(AppState::Menu)).id();
+        let child = app.world_mut().spawn_empty().id();
+        app.world_mut().entity_mut(menu).push_children(&[child]);
+        let unmarked = app.world_mut().spawn_empty().id();
+        app.update();
+
+        app.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        assert!(app.world().get_entity(menu).is_none());
+        assert!(app.world().get_entity(child).is_none());
+        assert!(app.world().get_entity(unmarked).is_some());
+
+        let ball = app.world_mut().spawn(StateScoped(AppState::Playing)).id();
+        app.world_mut()
+            .resource_mut::<NextState<MatchState>>()
+            .set(MatchState::Paused);
+        app.update();
+        let pause_menu = app.world_mut().spawn(StateScoped(MatchState::Paused)).id();
+        app.world_mut()
+            .resource_mut::<NextState<MatchState>>()
+            .set(MatchState::Running);
+        app.update();
+        assert!(app.world().get_entity(pause_menu).is_none());
+        assert!(app.world().get_entity(ball).is_some());
+
+        app.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::GameOver);
+        app.update();
+        assert!(app.world().get_entity(ball).is_none());
+    }
+
+    /// A match left while it's paused takes the pause menu with it.
+    #[test]
+    fn leaving_a_paused_match_despawns_the_pause_menu() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_state(AppState::Playing)
+            .add_sub_state::<MatchState>()
+            .add_plugins(CleanupPlugin);
+        app.update();
+
+        app.world_mut()
+            .resource_mut::<NextState<MatchState>>()
+            .set(MatchState::Paused);
+        app.update();
+        let pause_menu = app.world_mut().spawn(StateScoped(MatchState::Paused)).id();
+        app.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Menu);
+        app.update();
+        assert!(app.world().get_entity(pause_menu).is_none());
+    }
+
+    /// Restarting a state despawns what's scoped to it, for it to be spawned
+    /// afresh, and leaves what's scoped to the others.
+    #[test]
+    fn restarting_a_state_despawns_its_entities() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_state(AppState::Settings)
+            .add_sub_state::<MatchState>()
+            .add_plugins(CleanupPlugin);
+        app.update();
+
+        let settings = app.world_mut().spawn(StateScoped(AppState::Settings)).id();
+        let menu = app.world_mut().spawn(StateScoped(AppState::Menu)).id();
+        crate::restart_state(app.world_mut());
+        assert!(app.world().get_entity(settings).is_none());
+        assert!(app.world().get_entity(menu).is_some());
+    }
+}
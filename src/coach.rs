@@ -0,0 +1,308 @@
+//! A coach for beginners, switched on in the settings. Over every point of a
+//! local match it keeps an eye on where the players' rackets were and how
+//! they hit the ball, and when one of them loses the point it picks the one
+//! thing they'd do best to try differently, shown while the next serve's
+//! counted down. Only the players at the keys are coached, never a CPU or a
+//! bot.
+
+use bevy::prelude::*;
+
+use crate::{
+    ai::Cpu,
+    arena::ArenaBounds,
+    bot::Bot,
+    locale::Strings,
+    settings::Settings,
+    sim::{Ball, BallHitEvent, GoalEvent, MatchRules, Netted, Racket, Score, ServeEvent, Velocity},
+    start_match,
+    ui::{text, UiFont, TEXT_COLOR},
+    AppState, GameMode, MatchState, Player,
+};
+
+/// How far off the middle of the court a racket can wait for the ball, on
+/// average, as a fraction of the way to the top or bottom, before it's too far.
+const OFF_CENTER: f32 = 0.35;
+/// How far from the racket's center a hit has to be, from 0 to 1 at the end,
+/// to angle the ball much.
+const EDGE_HIT: f32 = 0.5;
+/// Returns it takes without a single one angled before it's worth saying.
+const STRAIGHT_RETURNS: u32 = 3;
+/// Returns in a point without a smash before it's worth saying.
+const SMASHLESS_RETURNS: u32 = 6;
+// How far up the screen the tip is, under the countdown
+const TIP_BOTTOM: f32 = 30.0;
+
+pub struct CoachPlugin;
+
+impl Plugin for CoachPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Coach>()
+            .add_systems(
+                OnEnter(AppState::Playing),
+                (clear_coach.after(start_match), spawn_tip.after(start_match)),
+            )
+            .add_systems(
+                Update,
+                (watch_point, show_tip.after(watch_point)).run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Something a player could try, for the point they just lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tip {
+    /// Wait for the ball nearer the middle of the court
+    Center,
+    /// Go for the ball sooner
+    Early,
+    /// Hit with the ends of the racket, to send the ball off at an angle
+    Edge,
+    /// Smash now and then
+    Smash,
+}
+
+impl Tip {
+    /// Key of what the coach says in the [`Strings`].
+    fn key(self) -> &'static str {
+        match self {
+            Tip::Center => "coach.center",
+            Tip::Early => "coach.early",
+            Tip::Edge => "coach.edge",
+            Tip::Smash => "coach.smash",
+        }
+    }
+}
+
+/// What a player's done over the point so far.
+#[derive(Default, Debug, Clone, Copy)]
+struct PointLog {
+    /// Frames they were waiting for the ball to come back, with it heading
+    /// to the other end
+    waiting: u32,
+    /// How far off the middle of the court their racket was over those
+    /// frames, added up, as a fraction of the way to the top or bottom
+    off_center: f32,
+    returns: u32,
+    /// Returns hit nearer the end of the racket than the middle
+    edge_returns: u32,
+    smashes: u32,
+}
+
+impl PointLog {
+    /// The tip for a point lost with the ball going by `missed_by` pixels past
+    /// the end of a racket `length` long, if there's one worth giving. The
+    /// most likely reason for losing it comes first.
+    fn tip(&self, missed_by: f32, length: f32) -> Option<Tip> {
+        let off_center = self.off_center / self.waiting.max(1) as f32;
+        if self.waiting > 0 && off_center > OFF_CENTER {
+            Some(Tip::Center)
+        } else if missed_by > length {
+            Some(Tip::Early)
+        } else if self.returns >= STRAIGHT_RETURNS && self.edge_returns == 0 {
+            Some(Tip::Edge)
+        } else if self.returns >= SMASHLESS_RETURNS && self.smashes == 0 {
+            Some(Tip::Smash)
+        } else {
+            None
+        }
+    }
+}
+
+/// What the coach has seen of the point being played, and what it has to
+/// say about the one before.
+#[derive(Resource, Default)]
+struct Coach {
+    /// By player number
+    logs: [PointLog; 2],
+    /// Who lost the last point, and the tip for them
+    tip: Option<(i32, Tip)>,
+}
+
+/// Shows the tip for the point just lost.
+#[derive(Component)]
+struct TipText;
+
+/// Whether players are coached in matches of this kind: the ones played to
+/// the end one on one, against each other or the CPU.
+fn coached(mode: GameMode) -> bool {
+    matches!(
+        mode,
+        GameMode::Versus
+            | GameMode::Blitz
+            | GameMode::Arcade
+            | GameMode::Tournament
+            | GameMode::Campaign
+    )
+}
+
+fn clear_coach(mut coach: ResMut<Coach>) {
+    *coach = Coach::default();
+}
+
+fn spawn_tip(mut commands: Commands, font: Res<UiFont>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Percent(TIP_BOTTOM),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn((text(&font, "", 24.0, TEXT_COLOR), TipText));
+        });
+}
+
+/// Logs what the players at the keys do over each point, and picks the tip
+/// for whoever lost it.
+fn watch_point(
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    bounds: Res<ArenaBounds>,
+    mut coach: ResMut<Coach>,
+    mut hits: EventReader<BallHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut serves: EventReader<ServeEvent>,
+    players: Query<&Player, (Without<Cpu>, Without<Bot>)>,
+    rackets: Query<(&Racket, &Transform)>,
+    balls: Query<(&Transform, &Velocity), (With<Ball>, Without<Netted>)>,
+) {
+    if !settings.coach || !coached(*mode) {
+        return;
+    }
+    // The racket of player 1 or 2, if they're at the keys
+    let coached_racket = |player_number: i32| {
+        let at_keys = players
+            .iter()
+            .any(|player| player.player_number == player_number && player.seat < 2);
+        if !at_keys {
+            return None;
+        }
+        rackets
+            .iter()
+            .find(|(racket, _)| racket.seat == (player_number - 1) as usize)
+            .map(|(_, transform)| transform)
+    };
+
+    // Once the serve's gone there's nothing more to say about the last point
+    if serves.read().count() > 0 {
+        coach.tip = None;
+    }
+
+    for hit in hits.read() {
+        if let Some(log) = coach.logs.get_mut((hit.player_number - 1) as usize) {
+            log.returns += 1;
+            if hit.along.abs() > EDGE_HIT {
+                log.edge_returns += 1;
+            }
+            if hit.smash > 0.0 {
+                log.smashes += 1;
+            }
+        }
+    }
+
+    if let Some((ball, velocity)) = balls.iter().next() {
+        let middle = (bounds.min.y + bounds.max.y) / 2.0;
+        let half_height = (bounds.max.y - bounds.min.y) / 2.0;
+        for player_number in [1, 2] {
+            let racket = match coached_racket(player_number) {
+                Some(racket) => racket.translation,
+                None => continue,
+            };
+            // Heading away from the racket, towards the other end, where the
+            // middle of the court is the place to wait
+            if velocity.0.x * (racket.x - ball.translation.x) >= 0.0 {
+                continue;
+            }
+            let log = &mut coach.logs[(player_number - 1) as usize];
+            log.waiting += 1;
+            log.off_center += ((racket.y - middle).abs() / half_height).min(1.0);
+        }
+    }
+
+    for goal in goals.read() {
+        let conceded_by = 3 - goal.scored_by;
+        if let Some(racket) = coached_racket(conceded_by) {
+            let log = coach.logs[(conceded_by - 1) as usize];
+            let length = racket.scale.x;
+            let missed_by = (goal.position.y - racket.translation.y).abs() - length / 2.0;
+            coach.tip = log.tip(missed_by, length).map(|tip| (conceded_by, tip));
+        }
+        coach.logs = default();
+    }
+}
+
+fn show_tip(
+    mode: Res<GameMode>,
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    coach: Res<Coach>,
+    strings: Res<Strings>,
+    players: Query<&Player>,
+    netted: Query<&Netted>,
+    mut tips: Query<&mut Text, With<TipText>>,
+) {
+    let waiting = netted.iter().any(|netted| netted.time_to_serve() > 0.0);
+    let line = match coach.tip {
+        Some((player_number, tip)) if waiting && coached(*mode) && !rules.wins_match(&score) => {
+            let name = players
+                .iter()
+                .find(|player| player.player_number == player_number && player.seat < 2)
+                .map_or_else(|| player_number.to_string(), |player| player.name.clone());
+            strings.fill(
+                "coach.tip",
+                &[("name", &name), ("tip", &strings.get(tip.key()))],
+            )
+        }
+        _ => String::new(),
+    };
+    for mut text in tips.iter_mut() {
+        if text.sections[0].value != line {
+            text.sections[0].value = line.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waiting_off_center_comes_first() {
+        let log = PointLog {
+            waiting: 10,
+            off_center: 6.0,
+            returns: 4,
+            ..default()
+        };
+        assert_eq!(log.tip(500.0, 100.0), Some(Tip::Center));
+        let log = PointLog {
+            off_center: 1.0,
+            ..log
+        };
+        assert_eq!(log.tip(500.0, 100.0), Some(Tip::Early));
+        assert_eq!(log.tip(10.0, 100.0), Some(Tip::Edge));
+    }
+
+    #[test]
+    fn nothing_to_say_about_a_good_point() {
+        let log = PointLog {
+            waiting: 10,
+            off_center: 1.0,
+            returns: 8,
+            edge_returns: 3,
+            smashes: 1,
+        };
+        assert_eq!(log.tip(10.0, 100.0), None);
+        // Long rallies want a smash in them
+        let log = PointLog { smashes: 0, ..log };
+        assert_eq!(log.tip(10.0, 100.0), Some(Tip::Smash));
+    }
+}
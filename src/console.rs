@@ -0,0 +1,390 @@
+//! A console dropping down from the top of the window on `~`, for poking at
+//! the game while testing it or showing it off. Each line typed is parsed
+//! into a [`ConsoleCommand`], and `help` lists them.
+//!
+//! With practice hotkeys on in the settings, a few of them can be run with a
+//! single key mid-match as well: R resets the point with a fresh serve, 1 and
+//! 2 serve to that player, and B sends player 1 a ball at the speed last
+//! given to `ball`.
+//!
+//! While it's open the keyboard belongs to the console, so the rackets stay
+//! put. Online matches can't be changed from it, as both ends have to agree
+//! on everything that happens in them.
+
+use std::collections::VecDeque;
+
+use bevy::{input::InputSystem, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    actions::PressActions,
+    display::toggle_fullscreen,
+    settings::Settings,
+    sim::{
+        serve_ball, serve_velocity, spawn_ball, Ball, GameplayConfig, MatchRules, Score,
+        ServeEvent, Side, SimRng,
+    },
+    AppState, GameMode, MatchState,
+};
+
+// How many lines of what's been typed and what came of it are kept
+const CONSOLE_LINES: usize = 100;
+const HELP: &str = "\
+set <ball_speed|racket_speed> <pixels a second>
+set bounce_angle <degrees>
+score <player 1's points> <player 2's points>
+spawn_ball
+reset_point
+serve <1|2>
+ball [pixels a second]
+state <menu|playing|gameover|stats|history|settings|profiles|tournament|bracket|campaign|season>";
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_event::<ConsoleCommand>()
+            // Before anything else looks at the keyboard, so typing can hide it
+            .add_systems(
+                PreUpdate,
+                toggle_console
+                    .after(InputSystem)
+                    .before(toggle_fullscreen)
+                    .before(PressActions),
+            )
+            .add_systems(Update, show_console)
+            .add_systems(Update, run_commands.after(show_console))
+            .add_systems(
+                Update,
+                practice_hotkeys
+                    .before(run_commands)
+                    .run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Something typed into the console.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+enum ConsoleCommand {
+    Help,
+    /// Changes one of the rules of the match being played
+    Set(Tunable, f32),
+    /// Changes the score of the set being played
+    Score(i32, i32),
+    /// Serves another ball into the match being played
+    SpawnBall,
+    /// Takes every ball out of play and serves a fresh one, as at the start
+    ResetPoint,
+    /// Takes every ball out of play and serves one to this side's player
+    Serve(Side),
+    /// Takes every ball out of play and serves one to player 1 at this many
+    /// pixels a second, or a serve's speed
+    Ball(Option<f32>),
+    /// Goes straight to another screen
+    State(AppState),
+}
+
+/// What `set` can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tunable {
+    /// How fast serves go, in pixels a second
+    BallSpeed,
+    /// How fast rackets move, in pixels a second
+    RacketSpeed,
+    /// How far off straight the end of a racket sends the ball, in degrees
+    BounceAngle,
+}
+
+impl Tunable {
+    fn name(self) -> &'static str {
+        match self {
+            Tunable::BallSpeed => "ball_speed",
+            Tunable::RacketSpeed => "racket_speed",
+            Tunable::BounceAngle => "bounce_angle",
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Console {
+    open: bool,
+    /// The line being typed
+    draft: String,
+    log: VecDeque<String>,
+    /// What `ball` was last given, which the hotkey serves at
+    ball_speed: Option<f32>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.log.push_back(line.into());
+        while self.log.len() > CONSOLE_LINES {
+            self.log.pop_front();
+        }
+    }
+}
+
+fn parse(line: &str) -> Result<ConsoleCommand, String> {
+    let number = |word: &str| {
+        word.parse::<i32>()
+            .map_err(|_| format!("{} isn't a whole number", word))
+    };
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["help"] => Ok(ConsoleCommand::Help),
+        ["set", name, value] => {
+            let tunable = [
+                Tunable::BallSpeed,
+                Tunable::RacketSpeed,
+                Tunable::BounceAngle,
+            ]
+            .into_iter()
+            .find(|tunable| tunable.name() == *name)
+            .ok_or_else(|| format!("There's no {} to set", name))?;
+            let value = value
+                .parse()
+                .map_err(|_| format!("{} isn't a number", value))?;
+            Ok(ConsoleCommand::Set(tunable, value))
+        }
+        ["score", player1, player2] => {
+            Ok(ConsoleCommand::Score(number(player1)?, number(player2)?))
+        }
+        ["spawn_ball"] => Ok(ConsoleCommand::SpawnBall),
+        ["reset_point"] => Ok(ConsoleCommand::ResetPoint),
+        ["serve", "1"] => Ok(ConsoleCommand::Serve(Side::Left)),
+        ["serve", "2"] => Ok(ConsoleCommand::Serve(Side::Right)),
+        ["ball"] => Ok(ConsoleCommand::Ball(None)),
+        ["ball", speed] => {
+            let speed = speed
+                .parse()
+                .map_err(|_| format!("{} isn't a number", speed))?;
+            Ok(ConsoleCommand::Ball(Some(speed)))
+        }
+        ["state", name] => {
+            let state = match *name {
+                "menu" => AppState::Menu,
+                "playing" => AppState::Playing,
+                "gameover" => AppState::GameOver,
+                "stats" => AppState::Stats,
+                "history" => AppState::History,
+                "achievements" => AppState::Achievements,
+                "mods" => AppState::Mods,
+                "replays" => AppState::Replays,
+                "settings" => AppState::Settings,
+                "profiles" => AppState::Profiles,
+                "tournament" => AppState::TournamentSetup,
+                "bracket" => AppState::Bracket,
+                "campaign" => AppState::Campaign,
+                "season" => AppState::Season,
+                "custom" => AppState::CustomMatch,
+                "drills" => AppState::Drills,
+                _ => return Err(format!("There's no screen called {}", name)),
+            };
+            Ok(ConsoleCommand::State(state))
+        }
+        ["set" | "score" | "spawn_ball" | "reset_point" | "serve" | "ball" | "state", ..] => {
+            Err("That's not how it goes, try help".to_string())
+        }
+        [name, ..] => Err(format!("There's no command {}, try help", name)),
+        [] => Err("Type a command, or help".to_string()),
+    }
+}
+
+fn toggle_console(mut keyboard_input: ResMut<ButtonInput<KeyCode>>, mut console: ResMut<Console>) {
+    let was_open = console.open;
+    if keyboard_input.just_pressed(KeyCode::Backquote) {
+        console.open = !console.open;
+    } else if console.open && keyboard_input.just_pressed(KeyCode::Escape) {
+        console.open = false;
+    }
+
+    // Nothing else gets to see the keys while typing, or the one closing it
+    if was_open || console.open {
+        keyboard_input.reset_all();
+    }
+}
+
+fn show_console(
+    mut egui_context: EguiContexts,
+    mut console: ResMut<Console>,
+    mut commands: EventWriter<ConsoleCommand>,
+) {
+    if !console.open {
+        return;
+    }
+    let mut submitted = None;
+    egui::TopBottomPanel::top("console").show(egui_context.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in console.log.iter() {
+                    ui.monospace(line);
+                }
+            });
+        let draft = ui.add(
+            egui::TextEdit::singleline(&mut console.draft)
+                .font(egui::TextStyle::Monospace)
+                .desired_width(f32::INFINITY),
+        );
+        // The key that opened the console gets typed into it too
+        console.draft.retain(|character| character != '`');
+        if draft.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+            submitted = Some(std::mem::take(&mut console.draft));
+        }
+        draft.request_focus();
+    });
+
+    if let Some(line) = submitted {
+        console.print(format!("> {}", line));
+        match parse(&line) {
+            Ok(command) => {
+                commands.send(command);
+            }
+            Err(error) => console.print(error),
+        }
+    }
+}
+
+/// The commands with a key of their own, when the settings give them one.
+/// Doubles leaves them be, as R is player 3's.
+fn practice_hotkeys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    console: Res<Console>,
+    mut commands: EventWriter<ConsoleCommand>,
+) {
+    if !settings.practice_hotkeys || *mode == GameMode::Doubles {
+        return;
+    }
+    for (key, command) in [
+        (KeyCode::KeyR, ConsoleCommand::ResetPoint),
+        (KeyCode::Digit1, ConsoleCommand::Serve(Side::Left)),
+        (KeyCode::Digit2, ConsoleCommand::Serve(Side::Right)),
+        (KeyCode::KeyB, ConsoleCommand::Ball(console.ball_speed)),
+    ] {
+        if keyboard_input.just_pressed(key) {
+            commands.send(command);
+        }
+    }
+}
+
+fn run_commands(
+    mut commands: Commands,
+    mut typed: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mode: Res<GameMode>,
+    mut rules: ResMut<MatchRules>,
+    config: Res<GameplayConfig>,
+    mut score: ResMut<Score>,
+    mut rng: ResMut<SimRng>,
+    mut serve_events: EventWriter<ServeEvent>,
+    balls: Query<Entity, With<Ball>>,
+) {
+    for command in typed.read() {
+        let in_match = *state.get() == AppState::Playing;
+        let changes_match = !matches!(command, ConsoleCommand::Help | ConsoleCommand::State(_));
+        if changes_match && !in_match {
+            console.print("Start a match first");
+            continue;
+        }
+        if changes_match && *mode == GameMode::Online {
+            console.print("Online matches can't be changed from here");
+            continue;
+        }
+
+        match *command {
+            ConsoleCommand::Help => console.print(HELP),
+            ConsoleCommand::Set(tunable, value) => {
+                match tunable {
+                    Tunable::BallSpeed => rules.ball_speed = value / config.ball_speed,
+                    Tunable::RacketSpeed => rules.racket_speed = value / config.racket_speed,
+                    Tunable::BounceAngle => rules.bounce_angle = value.to_radians(),
+                }
+                console.print(format!("{} is {} for this match", tunable.name(), value));
+            }
+            ConsoleCommand::Score(player1, player2) => {
+                score.player1 = player1;
+                score.player2 = player2;
+                console.print(format!("It's {}-{}", player1, player2));
+            }
+            ConsoleCommand::SpawnBall => {
+                spawn_ball(&mut commands, &rules, &config, &mut *rng, &mut serve_events)
+                    .insert(StateScoped(AppState::Playing));
+                console.print("Served another ball");
+            }
+            ConsoleCommand::ResetPoint | ConsoleCommand::Serve(_) | ConsoleCommand::Ball(_) => {
+                for ball in balls.iter() {
+                    commands.entity(ball).despawn_recursive();
+                }
+                let receiver = match *command {
+                    ConsoleCommand::Serve(side) => side,
+                    ConsoleCommand::Ball(_) => Side::Left,
+                    _ => Side::Right,
+                };
+                let mut velocity = serve_velocity(receiver, &rules, &config, &mut *rng);
+                if let ConsoleCommand::Ball(speed) = *command {
+                    console.ball_speed = speed;
+                    if let Some(speed) = speed {
+                        velocity = velocity.normalize_or_zero() * speed;
+                    }
+                }
+                serve_ball(
+                    &mut commands,
+                    rules.ball,
+                    receiver,
+                    velocity,
+                    &mut serve_events,
+                )
+                .insert(StateScoped(AppState::Playing));
+                console.print(format!(
+                    "Served to player {} at {:.0}",
+                    receiver.player_number(),
+                    velocity.length()
+                ));
+            }
+            ConsoleCommand::State(to) if *state.get() == to => {
+                console.print(format!("Can't go to {:?}, it's there already", to))
+            }
+            ConsoleCommand::State(to) => {
+                next_state.set(to);
+                console.print(format!("Going to {:?}", to));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_lines_become_commands() {
+        assert_eq!(
+            parse("set ball_speed 600"),
+            Ok(ConsoleCommand::Set(Tunable::BallSpeed, 600.0))
+        );
+        assert_eq!(parse("  score 5 3 "), Ok(ConsoleCommand::Score(5, 3)));
+        assert_eq!(parse("spawn_ball"), Ok(ConsoleCommand::SpawnBall));
+        assert_eq!(parse("reset_point"), Ok(ConsoleCommand::ResetPoint));
+        assert_eq!(parse("serve 2"), Ok(ConsoleCommand::Serve(Side::Right)));
+        assert_eq!(parse("ball"), Ok(ConsoleCommand::Ball(None)));
+        assert_eq!(parse("ball 900"), Ok(ConsoleCommand::Ball(Some(900.0))));
+        assert_eq!(
+            parse("state gameover"),
+            Ok(ConsoleCommand::State(AppState::GameOver))
+        );
+
+        assert!(parse("set ball_speed fast").is_err());
+        assert!(parse("set gravity 3").is_err());
+        assert!(parse("score 5").is_err());
+        assert!(parse("serve 3").is_err());
+        assert!(parse("ball fast").is_err());
+        assert!(parse("jump").is_err());
+        assert!(parse("").is_err());
+    }
+}
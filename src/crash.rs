@@ -0,0 +1,252 @@
+//! Crash reports. A panic anywhere writes one to `crashes` next to the stats,
+//! named for when it happened: the panic and its backtrace, what the game was
+//! doing, the last gameplay events, and the settings. The next time the game
+//! starts, the menu says it crashed, and offers to open the report.
+//!
+//! What a report tells is kept up to date as the game runs, behind a lock the
+//! panic hook can get at, since by the time it runs the app can't be asked.
+
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    error::Error,
+    fs,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, TryLockError},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    settings::Settings,
+    sim::{
+        BallHitEvent, GoalEvent, MatchEndedEvent, MatchRules, Score, ServeCallEvent, ServeEvent,
+    },
+    stats::{data_dir, now},
+    AppState, GameMode,
+};
+
+const CRASH_DIR: &str = "crashes";
+/// Names the report of the last crash, until it's been looked at or dismissed
+const PENDING_FILE: &str = "last_crash";
+/// How many of the latest gameplay events a report lists
+const LOGGED_EVENTS: usize = 100;
+
+pub struct CrashPlugin;
+
+impl Plugin for CrashPlugin {
+    fn build(&self, app: &mut App) {
+        let log = CrashLog::default();
+        install_hook(log.0.clone());
+        app.insert_resource(log)
+            .add_systems(Last, note_state)
+            .add_systems(Last, log_events);
+    }
+}
+
+/// What the game was doing, as a report tells it.
+#[derive(Default)]
+struct Snapshot {
+    state: String,
+    mode: String,
+    score: String,
+    rules: String,
+    settings: String,
+    /// The latest first, each with the seconds since startup it came at
+    events: VecDeque<String>,
+}
+
+impl Snapshot {
+    fn log(&mut self, seconds: f64, event: impl std::fmt::Debug) {
+        self.events
+            .push_front(format!("{:>10.3}s {:?}", seconds, event));
+        self.events.truncate(LOGGED_EVENTS);
+    }
+}
+
+/// The [`Snapshot`] shared with the panic hook.
+#[derive(Resource, Default, Clone)]
+struct CrashLog(Arc<Mutex<Snapshot>>);
+
+fn crash_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(CRASH_DIR))
+}
+
+/// Writes a report of every panic, after whatever was done with panics
+/// before, like printing them.
+fn install_hook(log: Arc<Mutex<Snapshot>>) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        match write_report(info, &log) {
+            Ok(Some(path)) => eprintln!("Wrote a crash report to {}", path.display()),
+            Ok(None) => {}
+            Err(error) => eprintln!("Couldn't write a crash report: {}", error),
+        }
+    }));
+}
+
+fn write_report(
+    info: &PanicHookInfo,
+    log: &Mutex<Snapshot>,
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let dir = match crash_dir() {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    let mut report = format!(
+        "Pingis {} crashed at {} (seconds since the Unix epoch) on {} {}\n\n{}\n\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        now(),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        info,
+        Backtrace::force_capture(),
+    );
+    // Whatever panicked might have been holding it, and can't let go now
+    let snapshot = match log.try_lock() {
+        Ok(snapshot) => Some(snapshot),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    };
+    match snapshot {
+        Some(snapshot) => {
+            report += &format!(
+                "State: {}\nMode: {}\nScore: {}\nRules: {}\n\n",
+                snapshot.state, snapshot.mode, snapshot.score, snapshot.rules
+            );
+            report += &format!("Last {} gameplay events:\n", snapshot.events.len());
+            for event in snapshot.events.iter().rev() {
+                report += event;
+                report.push('\n');
+            }
+            report += &format!("\nSettings:\n{}\n", snapshot.settings);
+        }
+        None => report += "What the game was doing couldn't be got at\n",
+    }
+
+    fs::create_dir_all(&dir)?;
+    let name = format!("crash-{}.txt", now());
+    let path = dir.join(&name);
+    fs::write(&path, report)?;
+    fs::write(dir.join(PENDING_FILE), name)?;
+    Ok(Some(path))
+}
+
+/// The report of the crash the game last ended in, if nobody's looked at it
+/// or dismissed it yet.
+pub fn pending() -> Option<PathBuf> {
+    let dir = crash_dir()?;
+    let name = fs::read_to_string(dir.join(PENDING_FILE)).ok()?;
+    let path = dir.join(name.trim());
+    path.exists().then_some(path)
+}
+
+/// Stops the menu mentioning the last crash, leaving its report where it is.
+pub fn dismiss() {
+    if let Some(dir) = crash_dir() {
+        match fs::remove_file(dir.join(PENDING_FILE)) {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => warn!("Couldn't dismiss the crash report: {}", error),
+        }
+    }
+}
+
+/// Opens a report in whatever the system opens text files with.
+pub fn open(path: &Path) {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    if let Err(error) = command.arg(path).spawn() {
+        warn!("Couldn't open {}: {}", path.display(), error);
+    }
+}
+
+/// Keeps what the game's doing up to date for a report, as it changes.
+fn note_state(
+    log: Res<CrashLog>,
+    time: Res<Time>,
+    state: Res<State<AppState>>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    rules: Res<MatchRules>,
+    settings: Res<Settings>,
+) {
+    let changed = state.is_changed()
+        || mode.is_changed()
+        || score.is_changed()
+        || rules.is_changed()
+        || settings.is_changed();
+    if !changed {
+        return;
+    }
+    let mut snapshot = match log.0.lock() {
+        Ok(snapshot) => snapshot,
+        Err(_) => return,
+    };
+    if state.is_changed() {
+        snapshot.log(time.elapsed_seconds_f64(), state.get());
+    }
+    snapshot.state = format!("{:?}", state.get());
+    snapshot.mode = format!("{:?}", *mode);
+    snapshot.score = format!("{:?}", *score);
+    snapshot.rules = format!("{:?}", *rules);
+    snapshot.settings = ron::ser::to_string_pretty(&*settings, default())
+        .unwrap_or_else(|error| format!("(couldn't be written out: {})", error));
+}
+
+fn log_events(
+    log: Res<CrashLog>,
+    time: Res<Time>,
+    mut goals: EventReader<GoalEvent>,
+    mut hits: EventReader<BallHitEvent>,
+    mut serves: EventReader<ServeEvent>,
+    mut calls: EventReader<ServeCallEvent>,
+    mut match_ends: EventReader<MatchEndedEvent>,
+) {
+    let mut snapshot = match log.0.lock() {
+        Ok(snapshot) => snapshot,
+        Err(_) => return,
+    };
+    let seconds = time.elapsed_seconds_f64();
+    for event in goals.read() {
+        snapshot.log(seconds, event);
+    }
+    for event in hits.read() {
+        snapshot.log(seconds, event);
+    }
+    for event in serves.read() {
+        snapshot.log(seconds, event);
+    }
+    for event in calls.read() {
+        snapshot.log(seconds, event);
+    }
+    for event in match_ends.read() {
+        snapshot.log(seconds, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_latest_events_are_kept() {
+        let mut snapshot = Snapshot::default();
+        for event in 0..LOGGED_EVENTS + 5 {
+            snapshot.log(event as f64, event);
+        }
+        assert_eq!(snapshot.events.len(), LOGGED_EVENTS);
+        assert!(snapshot.events[0].ends_with(&format!("s {}", LOGGED_EVENTS + 4)));
+        assert!(snapshot.events[LOGGED_EVENTS - 1].ends_with("s 5"));
+    }
+}
@@ -0,0 +1,150 @@
+//! The CRT filter: scanlines, curved glass and a vignette over everything.
+//!
+//! While it's on, the main camera draws into an image instead of the window,
+//! and a second camera shows that image through `shaders/crt.wgsl`.
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages,
+        },
+        view::RenderLayers,
+    },
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle},
+    window::{PrimaryWindow, WindowRef, WindowResized},
+};
+
+use crate::{settings::Settings, MainCamera};
+
+// Only the screen camera sees this layer, and it sees nothing else
+const SCREEN_LAYER: usize = 31;
+
+pub struct CrtPlugin;
+
+impl Plugin for CrtPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<CrtMaterial>::default())
+            .add_systems(Update, update_crt);
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+struct CrtMaterial {
+    /// What the main camera drew this frame
+    #[texture(0)]
+    #[sampler(1)]
+    screen: Handle<Image>,
+}
+
+impl Material2d for CrtMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/crt.wgsl".into()
+    }
+}
+
+/// The quad and camera showing the filtered picture.
+#[derive(Component)]
+struct CrtScreen;
+
+fn render_target(width: u32, height: u32) -> Image {
+    let size = Extent3d {
+        width,
+        height,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    // Zero-filled
+    image.resize(size);
+    image
+}
+
+fn update_crt(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    windows: Query<(Entity, &Window), With<PrimaryWindow>>,
+    mut resized: EventReader<WindowResized>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CrtMaterial>>,
+    mut cameras: Query<&mut Camera, With<MainCamera>>,
+    screens: Query<Entity, With<CrtScreen>>,
+) {
+    let on = !screens.is_empty();
+    let wanted = settings.crt_filter && !settings.reduced_motion;
+    let primary = windows.get_single().ok();
+    let resized = resized
+        .read()
+        .any(|event| Some(event.window) == primary.map(|(entity, _)| entity));
+    // The image has to match the window, so a resize means starting over
+    let rebuild = on && resized;
+    if wanted == on && !rebuild {
+        return;
+    }
+
+    if on {
+        for entity in screens.iter() {
+            commands.entity(entity).despawn();
+        }
+        for mut camera in cameras.iter_mut() {
+            camera.target = RenderTarget::Window(WindowRef::Primary);
+        }
+    }
+
+    let window = match primary {
+        Some((_, window)) if wanted => window,
+        _ => return,
+    };
+    if window.physical_width() == 0 || window.physical_height() == 0 {
+        // Minimized, there's nothing to draw into
+        return;
+    }
+
+    let image = images.add(render_target(
+        window.physical_width(),
+        window.physical_height(),
+    ));
+    for mut camera in cameras.iter_mut() {
+        camera.target = RenderTarget::Image(image.clone());
+    }
+
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(1.0, 1.0)).into(),
+            material: materials.add(CrtMaterial { screen: image }),
+            transform: Transform::from_scale(Vec3::new(window.width(), window.height(), 1.0)),
+            ..default()
+        },
+        RenderLayers::layer(SCREEN_LAYER),
+        CrtScreen,
+    ));
+    // The UI is already in the image, filtered along with everything else
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                // After the main camera
+                order: 1,
+                ..default()
+            },
+            ..default()
+        },
+        RenderLayers::layer(SCREEN_LAYER),
+        CrtScreen,
+    ));
+}
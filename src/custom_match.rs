@@ -0,0 +1,634 @@
+//! Custom matches: a screen of every rule a local match can be played by, from
+//! what a set's played to and whether there's deuce to the ball, the court
+//! and the modifiers, put together into the [`MatchRules`] the match is
+//! started with. A set of rules can be saved as a preset, under a name typed
+//! on the screen or one made up for it, kept in `rule_presets.ron`, and picked
+//! again from the same screen.
+//!
+//! Who's playing, and their handicaps, still go by the settings, as do the
+//! steps the match is played at.
+
+use std::error::Error;
+
+use bevy::{
+    input::{keyboard::KeyboardInput, InputSystem},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::PressActions,
+    arena::{ArenaSize, ARENA_SIZES},
+    locale::Strings,
+    settings::{
+        cycle, Settings, BALL_SPEEDS, BEST_OF, GOLDEN_POINTS, GRAVITY_STRENGTHS, POINTS_TO_WIN,
+        RACKET_SPEEDS,
+    },
+    sim::{MatchRules, WallPath, BALL_KINDS, GRAVITY},
+    stats::{load_ron, save_ron},
+    ui::{
+        screen_root, spawn_button, text, typed, ButtonActivated, MenuButton, MenuFocus, UiFont,
+        DIM_TEXT_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, NextRules,
+};
+
+const PRESETS_FILE: &str = "rule_presets.ron";
+/// Longest name a preset can be given, in characters.
+const MAX_NAME_LENGTH: usize = 24;
+/// Seconds a custom match can be played for, if it's timed.
+const TIME_LIMITS: [Option<u32>; 5] = [None, Some(60), Some(120), Some(180), Some(300)];
+
+pub struct CustomMatchPlugin;
+
+impl Plugin for CustomMatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RulePresets::load())
+            .init_resource::<CustomRules>()
+            // Before the menu looks at the keyboard, so typing doesn't move
+            // the focus
+            .add_systems(PreUpdate, type_name.after(InputSystem).before(PressActions))
+            .add_systems(OnEnter(AppState::CustomMatch), spawn_custom_match)
+            .add_systems(
+                Update,
+                (change_rule, update_rule_labels.after(change_rule))
+                    .run_if(in_state(AppState::CustomMatch)),
+            );
+    }
+}
+
+/// A set of rules saved to be played again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RulePreset {
+    pub name: String,
+    pub rules: MatchRules,
+}
+
+/// Every preset there is, kept in a RON file in the user's data directory.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RulePresets {
+    pub presets: Vec<RulePreset>,
+}
+
+impl RulePresets {
+    fn load() -> Self {
+        load_ron(PRESETS_FILE)
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        save_ron(PRESETS_FILE, self)
+    }
+
+    /// Saves `rules` named `name` over the preset at `index`, or as a new
+    /// one, and gives where it went.
+    fn store(&mut self, index: Option<usize>, rules: MatchRules, name: String) -> usize {
+        match index.filter(|index| *index < self.presets.len()) {
+            Some(index) => {
+                self.presets[index] = RulePreset { name, rules };
+                index
+            }
+            None => {
+                self.presets.push(RulePreset { name, rules });
+                self.presets.len() - 1
+            }
+        }
+    }
+}
+
+/// The rules being put together, the preset they came from, if any, and the
+/// name they're to be saved under.
+#[derive(Resource)]
+struct CustomRules {
+    rules: MatchRules,
+    preset: Option<usize>,
+    name: String,
+    /// Whether the name's being typed
+    naming: bool,
+}
+
+impl Default for CustomRules {
+    fn default() -> Self {
+        CustomRules {
+            rules: MatchRules {
+                arena_size: Some(ArenaSize::default()),
+                ..default()
+            },
+            preset: None,
+            name: String::new(),
+            naming: false,
+        }
+    }
+}
+
+/// A line of the screen, each changed with Left and Right or chosen with
+/// Enter.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleLine {
+    Preset,
+    Name,
+    PointsToWin,
+    Deuce,
+    BestOf,
+    GoldenPoint,
+    TimeLimit,
+    Ball,
+    BallSpeed,
+    RacketSpeed,
+    ArenaSize,
+    Gravity,
+    Wind,
+    Portals,
+    MovingWall,
+    CenterNet,
+    Shrinking,
+    ServeRules,
+    Night,
+    Play,
+    Save,
+    Delete,
+    Back,
+}
+
+/// The lines in columns side by side, each under its heading, the preset
+/// they're from first.
+const RULE_COLUMNS: [(&str, &[RuleLine]); 3] = [
+    (
+        "settings.group.match",
+        &[
+            RuleLine::Preset,
+            RuleLine::Name,
+            RuleLine::PointsToWin,
+            RuleLine::Deuce,
+            RuleLine::BestOf,
+            RuleLine::GoldenPoint,
+            RuleLine::TimeLimit,
+        ],
+    ),
+    (
+        "settings.group.play",
+        &[
+            RuleLine::Ball,
+            RuleLine::BallSpeed,
+            RuleLine::RacketSpeed,
+            RuleLine::ArenaSize,
+            RuleLine::Gravity,
+            RuleLine::MovingWall,
+        ],
+    ),
+    (
+        "menu.modifiers",
+        &[
+            RuleLine::Wind,
+            RuleLine::Portals,
+            RuleLine::CenterNet,
+            RuleLine::Shrinking,
+            RuleLine::ServeRules,
+            RuleLine::Night,
+        ],
+    ),
+];
+
+/// Under the columns, in a row. Left and Right go along the row, so these
+/// only do anything when they're chosen.
+const ACTIONS: [RuleLine; 4] = [
+    RuleLine::Play,
+    RuleLine::Save,
+    RuleLine::Delete,
+    RuleLine::Back,
+];
+
+/// What to change: the focused line stepped `step` either way with Left or
+/// Right, if it's a rule rather than an action, or else the line chosen,
+/// stepped forward.
+fn pick_change(
+    focused: Option<RuleLine>,
+    step: f32,
+    chosen: Option<RuleLine>,
+) -> Option<(RuleLine, f32)> {
+    focused
+        .filter(|line| step != 0.0 && !ACTIONS.contains(line))
+        .map(|line| (line, step))
+        .or_else(|| chosen.map(|line| (line, 0.0)))
+}
+
+/// The pulls gravity can have, off first.
+fn gravities() -> Vec<Option<f32>> {
+    std::iter::once(None)
+        .chain(
+            GRAVITY_STRENGTHS
+                .iter()
+                .map(|strength| Some(strength * GRAVITY)),
+        )
+        .collect()
+}
+
+fn rule_label(
+    line: RuleLine,
+    custom: &CustomRules,
+    presets: &RulePresets,
+    strings: &Strings,
+) -> String {
+    let rules = &custom.rules;
+    let percent = |fraction: f32| format!("{:.0}%", fraction * 100.0);
+    let toggle = |key: &str, on: bool| strings.fill(key, &[("state", &strings.on_off(on))]);
+    let (key, value) = match line {
+        RuleLine::PointsToWin => ("settings.points_to_win", rules.points_to_win.to_string()),
+        RuleLine::Deuce => ("custom.deuce", strings.on_off(rules.win_by > 1).to_string()),
+        RuleLine::BestOf => ("settings.best_of", rules.best_of.to_string()),
+        RuleLine::GoldenPoint => (
+            "settings.golden_point",
+            match rules.golden_point {
+                Some(at) => strings.fill("settings.golden_point.at", &[("points", &at)]),
+                None => strings.on_off(false).to_string(),
+            },
+        ),
+        RuleLine::TimeLimit => (
+            "custom.time_limit",
+            match rules.time_limit {
+                Some(seconds) => format!("{}:{:02}", seconds / 60, seconds % 60),
+                None => strings.on_off(false).to_string(),
+            },
+        ),
+        RuleLine::Ball => (
+            "settings.ball",
+            strings.get(rules.ball.name_key()).to_string(),
+        ),
+        RuleLine::BallSpeed => ("settings.ball_speed", percent(rules.ball_speed)),
+        RuleLine::RacketSpeed => ("settings.racket_speed", percent(rules.racket_speed)),
+        RuleLine::ArenaSize => (
+            "settings.arena_size",
+            strings
+                .get(rules.arena_size.unwrap_or_default().name_key())
+                .to_string(),
+        ),
+        RuleLine::Gravity => (
+            "settings.gravity",
+            match rules.gravity {
+                Some(gravity) => percent(gravity / GRAVITY),
+                None => strings.on_off(false).to_string(),
+            },
+        ),
+        RuleLine::Wind => return toggle("menu.wind", rules.wind),
+        RuleLine::Portals => return toggle("menu.portals", rules.portals),
+        RuleLine::MovingWall => return toggle("menu.moving_wall", rules.moving_wall.is_some()),
+        RuleLine::CenterNet => return toggle("menu.center_net", rules.center_net),
+        RuleLine::Shrinking => return toggle("menu.shrinking", rules.shrinking),
+        RuleLine::ServeRules => return toggle("menu.serve_rules", rules.serve_rules),
+        RuleLine::Night => return toggle("menu.night", rules.night),
+        RuleLine::Preset => (
+            "custom.preset",
+            match custom.preset.and_then(|index| presets.presets.get(index)) {
+                Some(preset) => preset.name.clone(),
+                None => strings.get("custom.preset.none").to_string(),
+            },
+        ),
+        RuleLine::Name => (
+            "custom.name",
+            if custom.naming {
+                format!("{}_", custom.name)
+            } else if custom.name.is_empty() {
+                strings.get("custom.name.none").to_string()
+            } else {
+                custom.name.clone()
+            },
+        ),
+        RuleLine::Play => return strings.get("custom.play").to_string(),
+        RuleLine::Save => return strings.get("custom.save").to_string(),
+        RuleLine::Delete => return strings.get("custom.delete").to_string(),
+        RuleLine::Back => return strings.get("back").to_string(),
+    };
+    strings.fill(key, &[("value", &value)])
+}
+
+fn spawn_custom_match(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    custom: Res<CustomRules>,
+    presets: Res<RulePresets>,
+) {
+    let label = |line| rule_label(line, &custom, &presets, &strings);
+    let mut index = 0;
+    commands
+        .spawn((screen_root(), StateScoped(AppState::CustomMatch)))
+        .with_children(|parent| {
+            parent.spawn(text(&font, strings.get("custom.title"), 48.0, TEXT_COLOR));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexStart,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (title, lines) in RULE_COLUMNS {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    align_items: AlignItems::Center,
+                                    margin: UiRect::all(Val::Px(10.0)),
+                                    ..default()
+                                },
+                                background_color: Color::NONE.into(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(text(&font, strings.get(title), 24.0, DIM_TEXT_COLOR));
+                                for line in lines.iter() {
+                                    spawn_button(parent, &font, index, &label(*line)).insert(*line);
+                                    index += 1;
+                                }
+                            });
+                    }
+                });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for line in ACTIONS {
+                        spawn_button(parent, &font, index, &label(line)).insert(line);
+                        index += 1;
+                    }
+                });
+            parent.spawn(text(
+                &font,
+                strings.get("custom.hint"),
+                18.0,
+                DIM_TEXT_COLOR,
+            ));
+        });
+}
+
+/// The preset after or before the one at `current`, or none at all between
+/// the last and the first.
+fn cycle_preset(current: Option<usize>, count: usize, step: f32) -> Option<usize> {
+    let choices: Vec<Option<usize>> = std::iter::once(None).chain((0..count).map(Some)).collect();
+    cycle(&choices, current.filter(|index| *index < count), step)
+}
+
+fn change_rule(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    focus: Res<MenuFocus>,
+    mut activated: EventReader<ButtonActivated>,
+    lines: Query<(&MenuButton, &RuleLine)>,
+    settings: Res<Settings>,
+    strings: Res<Strings>,
+    mut custom: ResMut<CustomRules>,
+    mut presets: ResMut<RulePresets>,
+    mut mode: ResMut<GameMode>,
+    mut next_rules: ResMut<NextRules>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Menu);
+        return;
+    }
+
+    let step: f32 = if keyboard_input.just_pressed(KeyCode::ArrowLeft)
+        || keyboard_input.just_pressed(KeyCode::KeyA)
+    {
+        -1.0
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight)
+        || keyboard_input.just_pressed(KeyCode::KeyD)
+    {
+        1.0
+    } else {
+        0.0
+    };
+    let focused = lines
+        .iter()
+        .find(|(button, _)| button.0 == focus.0)
+        .map(|(_, line)| *line);
+    // Choosing a rule steps it forward, wrapping around
+    let chosen = activated
+        .read()
+        .filter_map(|event| lines.get(event.0).ok())
+        .map(|(_, line)| *line)
+        .last();
+    let (line, step) = match pick_change(focused, step, chosen) {
+        Some(change) => change,
+        None => return,
+    };
+
+    let custom = &mut *custom;
+    let rules = &mut custom.rules;
+    match line {
+        RuleLine::PointsToWin => {
+            rules.points_to_win = cycle(&POINTS_TO_WIN, rules.points_to_win, step);
+        }
+        RuleLine::Deuce => rules.win_by = if rules.win_by > 1 { 1 } else { 2 },
+        RuleLine::BestOf => rules.best_of = cycle(&BEST_OF, rules.best_of, step),
+        RuleLine::GoldenPoint => {
+            rules.golden_point = cycle(&GOLDEN_POINTS, rules.golden_point, step);
+        }
+        RuleLine::TimeLimit => rules.time_limit = cycle(&TIME_LIMITS, rules.time_limit, step),
+        RuleLine::Ball => rules.ball = cycle(&BALL_KINDS, rules.ball, step),
+        RuleLine::BallSpeed => rules.ball_speed = cycle(&BALL_SPEEDS, rules.ball_speed, step),
+        RuleLine::RacketSpeed => {
+            rules.racket_speed = cycle(&RACKET_SPEEDS, rules.racket_speed, step);
+        }
+        RuleLine::ArenaSize => {
+            let size = rules.arena_size.unwrap_or_default();
+            rules.arena_size = Some(cycle(&ARENA_SIZES, size, step));
+        }
+        RuleLine::Gravity => rules.gravity = cycle(&gravities(), rules.gravity, step),
+        RuleLine::Wind => rules.wind = !rules.wind,
+        RuleLine::Portals => rules.portals = !rules.portals,
+        RuleLine::MovingWall => {
+            rules.moving_wall = match rules.moving_wall {
+                Some(_) => None,
+                None => Some(WallPath {
+                    reach: settings.wall_reach,
+                    period: settings.wall_period,
+                }),
+            };
+        }
+        RuleLine::CenterNet => rules.center_net = !rules.center_net,
+        RuleLine::Shrinking => rules.shrinking = !rules.shrinking,
+        RuleLine::ServeRules => rules.serve_rules = !rules.serve_rules,
+        RuleLine::Night => rules.night = !rules.night,
+        RuleLine::Preset => {
+            custom.preset = cycle_preset(custom.preset, presets.presets.len(), step);
+            match custom.preset.and_then(|index| presets.presets.get(index)) {
+                Some(preset) => {
+                    custom.rules = preset.rules;
+                    custom.name = preset.name.clone();
+                }
+                None => custom.name.clear(),
+            }
+        }
+        // Typed rather than stepped through
+        RuleLine::Name if step == 0.0 => custom.naming = true,
+        RuleLine::Name => {}
+        RuleLine::Play => {
+            next_rules.0 = Some(MatchRules {
+                handicaps: settings.handicaps,
+                time_step: settings.time_step.clamped(),
+                ..*rules
+            });
+            *mode = GameMode::Versus;
+            state.set(AppState::Playing);
+        }
+        RuleLine::Save => {
+            // Without a name typed, named for the first number no preset has yet
+            let typed = custom.name.trim();
+            let name = if typed.is_empty() {
+                (1..)
+                    .map(|number| strings.fill("custom.new_name", &[("number", &number)]))
+                    .find(|name| presets.presets.iter().all(|preset| preset.name != *name))
+                    .unwrap_or_default()
+            } else {
+                typed.to_string()
+            };
+            custom.preset = Some(presets.store(custom.preset, *rules, name.clone()));
+            custom.name = name;
+            if let Err(error) = presets.save() {
+                warn!("Couldn't save rule presets: {}", error);
+            }
+        }
+        RuleLine::Delete => {
+            custom.name.clear();
+            if let Some(index) = custom.preset.take() {
+                if index < presets.presets.len() {
+                    presets.presets.remove(index);
+                    if let Err(error) = presets.save() {
+                        warn!("Couldn't save rule presets: {}", error);
+                    }
+                }
+            }
+        }
+        RuleLine::Back => {
+            state.set(AppState::Menu);
+        }
+    }
+}
+
+/// Types the name for the preset, while it's being named, until Enter or
+/// Escape.
+fn type_name(
+    state: Res<State<AppState>>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut keys: EventReader<KeyboardInput>,
+    mut custom: ResMut<CustomRules>,
+) {
+    // Looking before borrowing mutably keeps the labels from redrawing every
+    // frame
+    if *state.get() != AppState::CustomMatch || !custom.naming {
+        keys.clear();
+        return;
+    }
+
+    let custom = &mut *custom;
+    for character in typed(keys.read()) {
+        if !character.is_control() && custom.name.chars().count() < MAX_NAME_LENGTH {
+            custom.name.push(character);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        custom.name.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Enter) || keyboard_input.just_pressed(KeyCode::Escape) {
+        custom.naming = false;
+    }
+
+    // Nothing else gets to see the keys while typing
+    keyboard_input.reset_all();
+}
+
+fn update_rule_labels(
+    custom: Res<CustomRules>,
+    presets: Res<RulePresets>,
+    strings: Res<Strings>,
+    buttons: Query<(&RuleLine, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !custom.is_changed() && !presets.is_changed() {
+        return;
+    }
+    for (line, children) in buttons.iter() {
+        let label = rule_label(*line, &custom, &presets, &strings);
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_rules_change_sideways() {
+        assert_eq!(
+            pick_change(Some(RuleLine::Wind), 1.0, None),
+            Some((RuleLine::Wind, 1.0))
+        );
+        // Going along the row of actions doesn't set any of them off
+        for action in ACTIONS {
+            assert_eq!(pick_change(Some(action), 1.0, None), None);
+            assert_eq!(pick_change(Some(action), -1.0, None), None);
+        }
+        assert_eq!(
+            pick_change(Some(RuleLine::Delete), 0.0, Some(RuleLine::Delete)),
+            Some((RuleLine::Delete, 0.0))
+        );
+    }
+
+    #[test]
+    fn presets_cycle_through_none() {
+        assert_eq!(cycle_preset(None, 2, 1.0), Some(0));
+        assert_eq!(cycle_preset(Some(1), 2, 1.0), None);
+        assert_eq!(cycle_preset(None, 2, -1.0), Some(1));
+        // One that's been deleted since counts as none
+        assert_eq!(cycle_preset(Some(5), 2, 1.0), Some(0));
+        assert_eq!(cycle_preset(None, 0, 1.0), None);
+    }
+
+    #[test]
+    fn saving_over_a_preset_renames_it() {
+        let mut presets = RulePresets::default();
+        let rules = MatchRules {
+            points_to_win: 5,
+            ..default()
+        };
+        assert_eq!(presets.store(None, rules, "Quick".to_string()), 0);
+        let rules = MatchRules {
+            wind: true,
+            ..rules
+        };
+        assert_eq!(presets.store(Some(0), rules, "Other".to_string()), 0);
+        assert_eq!(presets.presets.len(), 1);
+        assert_eq!(presets.presets[0].name, "Other");
+        assert!(presets.presets[0].rules.wind);
+    }
+
+    #[test]
+    fn presets_round_trip_through_ron() {
+        let presets = RulePresets {
+            presets: vec![RulePreset {
+                name: "Windy".to_string(),
+                rules: MatchRules {
+                    wind: true,
+                    arena_size: Some(ArenaSize::Wide),
+                    ..default()
+                },
+            }],
+        };
+        let text = ron::ser::to_string(&presets).unwrap();
+        assert_eq!(ron::de::from_str::<RulePresets>(&text).unwrap(), presets);
+    }
+}
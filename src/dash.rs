@@ -0,0 +1,103 @@
+//! The cooldown ring next to each racket, between it and its goal: a circle
+//! of dots that go out when the racket dashes and come back on one after
+//! another as it gets ready to again, all of them lit in the racket's colour
+//! once it can.
+//!
+//! Purely visual, the dash itself is up to the simulation.
+
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, render::view::VisibilitySystems, transform::TransformSystem};
+
+use crate::{
+    sim::{Dash, MatchRules, Racket, Side, RACKET_THICCNESS, RACKET_WALL_OFFSET},
+    AppState,
+};
+
+const RING_DOTS: usize = 12;
+const RING_RADIUS: f32 = 7.0;
+const DOT_SIZE: f32 = 2.5;
+// Dots still to come back on are this faint
+const UNLIT_ALPHA: f32 = 0.2;
+
+pub struct DashPlugin;
+
+impl Plugin for DashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, spawn_rings).add_systems(
+            PostUpdate,
+            follow_rackets
+                .after(spawn_rings)
+                .after(TransformSystem::TransformPropagate)
+                .before(VisibilitySystems::VisibilityPropagate),
+        );
+    }
+}
+
+/// One of the dots in a racket's ring, by how far round it is.
+#[derive(Component)]
+struct RingDot {
+    racket: Entity,
+    index: usize,
+}
+
+/// Gives every new racket a ring, and takes away the rings of any that have
+/// gone, like when the arena's put up again.
+fn spawn_rings(
+    mut commands: Commands,
+    added: Query<Entity, Added<Dash>>,
+    rackets: Query<(), With<Dash>>,
+    dots: Query<(Entity, &RingDot)>,
+) {
+    for (entity, dot) in dots.iter() {
+        if rackets.get(dot.racket).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+    for racket in added.iter() {
+        for index in 0..RING_DOTS {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(DOT_SIZE)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                RingDot { racket, index },
+                StateScoped(AppState::Playing),
+            ));
+        }
+    }
+}
+
+/// Puts the dots where the racket's drawn, which is only known once its
+/// [`GlobalTransform`] has been worked out.
+fn follow_rackets(
+    rules: Res<MatchRules>,
+    rackets: Query<(&GlobalTransform, &Side, &Dash, &Sprite, &Visibility), With<Racket>>,
+    mut dots: Query<
+        (&RingDot, &mut GlobalTransform, &mut Sprite, &mut Visibility),
+        Without<Racket>,
+    >,
+) {
+    for (dot, mut transform, mut sprite, mut visibility) in dots.iter_mut() {
+        let (racket, side, dash, racket_sprite, racket_visibility) = match rackets.get(dot.racket) {
+            Ok(racket) => racket,
+            Err(_) => continue,
+        };
+
+        // Halfway between the back of the racket and its goal line
+        let behind = RACKET_THICCNESS / 2.0 + RACKET_WALL_OFFSET / 2.0;
+        let center = racket.translation() + Vec3::X * side.direction() * behind;
+        // Clockwise from the top, like a clock's hand
+        let angle = TAU * dot.index as f32 / RING_DOTS as f32;
+        let offset = Vec2::new(angle.sin(), angle.cos()) * RING_RADIUS;
+        *transform = GlobalTransform::from_translation(center + offset.extend(0.1));
+
+        let lit = ((1.0 - dash.cooldown(rules.time_step)) * RING_DOTS as f32).floor() as usize;
+        let alpha = if dot.index < lit { 1.0 } else { UNLIT_ALPHA };
+        sprite.color = racket_sprite.color.with_alpha(alpha);
+        *visibility = *racket_visibility;
+    }
+}
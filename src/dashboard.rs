@@ -0,0 +1,430 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    locale::Strings,
+    stats::{MatchRecord, StatsStore},
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        PANEL_COLOR, TEXT_COLOR,
+    },
+    AppState,
+};
+
+// How many of the most recent days with matches the win rate chart shows
+const TREND_DAYS: usize = 10;
+const COLUMN_HEIGHT: f32 = 110.0;
+const BAR_WIDTH: f32 = 300.0;
+
+pub struct DashboardPlugin;
+
+impl Plugin for DashboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedProfile>()
+            .add_systems(OnEnter(AppState::Stats), spawn_dashboard)
+            .add_systems(
+                Update,
+                (change_profile, dashboard_action).run_if(in_state(AppState::Stats)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnDashboard;
+
+#[derive(Component)]
+struct BackButton;
+
+/// Index into [`StatsStore::profiles`] of the profile being shown.
+#[derive(Resource, Default)]
+struct SelectedProfile(usize);
+
+/// Everything the dashboard shows for one profile.
+struct ProfileSummary {
+    matches: usize,
+    wins: usize,
+    losses: usize,
+    points: i32,
+    longest_rally: u32,
+    /// (days since the Unix epoch, fraction of matches won that day)
+    win_rate_by_day: Vec<(u64, f32)>,
+    /// (opponent, average hits per rally against them)
+    rally_by_opponent: Vec<(String, f32)>,
+    /// (key of the mode's name, seconds played)
+    playtime_by_mode: Vec<(&'static str, f32)>,
+}
+
+fn summarize(records: &[MatchRecord], name: &str) -> ProfileSummary {
+    let mut matches = 0;
+    let mut wins = 0;
+    let mut losses = 0;
+    let mut points = 0;
+    let mut longest_rally = 0;
+    let mut days: BTreeMap<u64, (usize, usize)> = BTreeMap::new();
+    let mut opponents: BTreeMap<String, (u32, usize)> = BTreeMap::new();
+    let mut modes: BTreeMap<&'static str, f32> = BTreeMap::new();
+
+    for record in records {
+        let (player, opponent) = match record.sides(name) {
+            Some(sides) => sides,
+            None => continue,
+        };
+
+        matches += 1;
+        let day = days.entry(record.started_at / 86400).or_default();
+        day.1 += 1;
+        if player.won {
+            wins += 1;
+            day.0 += 1;
+        }
+        if opponent.won {
+            losses += 1;
+        }
+        points += player.points;
+
+        let rallies = opponents.entry(opponent.name.clone()).or_default();
+        rallies.0 += record.rallies.iter().sum::<u32>();
+        rallies.1 += record.rallies.len();
+        longest_rally = longest_rally.max(record.longest_rally());
+
+        *modes.entry(record.mode.name_key()).or_default() += record.duration;
+    }
+
+    let mut win_rate_by_day: Vec<(u64, f32)> = days
+        .into_iter()
+        .rev()
+        .take(TREND_DAYS)
+        .map(|(day, (won, played))| (day, won as f32 / played as f32))
+        .collect();
+    win_rate_by_day.reverse();
+
+    ProfileSummary {
+        matches,
+        wins,
+        losses,
+        points,
+        longest_rally,
+        win_rate_by_day,
+        rally_by_opponent: opponents
+            .into_iter()
+            .map(|(opponent, (hits, rallies))| (opponent, hits as f32 / rallies.max(1) as f32))
+            .collect(),
+        playtime_by_mode: modes.into_iter().collect(),
+    }
+}
+
+/// "MM-DD" for a number of days since the Unix epoch.
+pub fn date_label(days: u64) -> String {
+    // Howard Hinnant's civil_from_days, only the month and day are needed
+    let z = days as i64 + 719468;
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    format!("{:02}-{:02}", month, day)
+}
+
+fn spawn_dashboard(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    store: Res<StatsStore>,
+    strings: Res<Strings>,
+    mut selected: ResMut<SelectedProfile>,
+) {
+    let profiles = store.profiles();
+    if selected.0 >= profiles.len() {
+        selected.0 = 0;
+    }
+
+    commands
+        .spawn((screen_root(), OnDashboard, StateScoped(AppState::Stats)))
+        .with_children(|parent| {
+            parent.spawn(text(
+                &font,
+                strings.get("dashboard.title"),
+                48.0,
+                TEXT_COLOR,
+            ));
+
+            match profiles.get(selected.0) {
+                Some(name) => {
+                    parent.spawn(text(&font, format!("< {} >", name), 32.0, ACCENT_COLOR));
+                    spawn_summary(parent, &font, &strings, &summarize(&store.matches, name));
+                }
+                None => {
+                    parent.spawn(text(
+                        &font,
+                        strings.get("dashboard.empty"),
+                        24.0,
+                        DIM_TEXT_COLOR,
+                    ));
+                }
+            }
+
+            parent.spawn(text(
+                &font,
+                strings.get("change_profile"),
+                18.0,
+                DIM_TEXT_COLOR,
+            ));
+            spawn_button(parent, &font, 0, strings.get("back")).insert(BackButton);
+        });
+}
+
+fn spawn_summary(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    strings: &Strings,
+    summary: &ProfileSummary,
+) {
+    let win_rate = 100.0 * summary.wins as f32 / summary.matches.max(1) as f32;
+    parent.spawn(text(
+        font,
+        strings.fill(
+            "dashboard.summary",
+            &[
+                ("wins", &summary.wins),
+                ("losses", &summary.losses),
+                ("win_rate", &format!("{:.0}", win_rate)),
+                ("points", &summary.points),
+                ("rally", &summary.longest_rally),
+            ],
+        ),
+        22.0,
+        TEXT_COLOR,
+    ));
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::FlexStart,
+                margin: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            let columns: Vec<_> = summary
+                .win_rate_by_day
+                .iter()
+                .map(|(day, rate)| (date_label(*day), *rate, format!("{:.0}%", rate * 100.0)))
+                .collect();
+            spawn_panel(parent, font, strings.get("dashboard.win_rate"), |parent| {
+                spawn_column_chart(parent, font, &columns);
+            });
+
+            let longest = summary
+                .rally_by_opponent
+                .iter()
+                .map(|(_, average)| *average)
+                .fold(1.0, f32::max);
+            let rallies: Vec<_> = summary
+                .rally_by_opponent
+                .iter()
+                .map(|(opponent, average)| {
+                    (
+                        opponent.clone(),
+                        average / longest,
+                        format!("{:.1}", average),
+                    )
+                })
+                .collect();
+
+            let most_played = summary
+                .playtime_by_mode
+                .iter()
+                .map(|(_, seconds)| *seconds)
+                .fold(1.0, f32::max);
+            let playtime: Vec<_> = summary
+                .playtime_by_mode
+                .iter()
+                .map(|(mode, seconds)| {
+                    (
+                        strings.get(mode).to_string(),
+                        seconds / most_played,
+                        strings.fill(
+                            "dashboard.minutes",
+                            &[("minutes", &format!("{:.0}", seconds / 60.0))],
+                        ),
+                    )
+                })
+                .collect();
+
+            spawn_panel(parent, font, strings.get("dashboard.rally"), |parent| {
+                spawn_bar_chart(parent, font, &rallies);
+                parent.spawn(text(
+                    font,
+                    strings.get("dashboard.playtime"),
+                    20.0,
+                    TEXT_COLOR,
+                ));
+                spawn_bar_chart(parent, font, &playtime);
+            });
+        });
+}
+
+fn spawn_panel(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    title: &str,
+    contents: impl FnOnce(&mut ChildBuilder),
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexStart,
+                padding: UiRect::all(Val::Px(12.0)),
+                margin: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: PANEL_COLOR.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(text(font, title, 20.0, TEXT_COLOR));
+            contents(parent);
+        });
+}
+
+/// Vertical bars growing up from a common baseline, labeled underneath.
+fn spawn_column_chart(parent: &mut ChildBuilder, font: &UiFont, columns: &[(String, f32, String)]) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::FlexEnd,
+                margin: UiRect::new(Val::Px(0.0), Val::Px(0.0), Val::Px(8.0), Val::Px(0.0)),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for (label, fraction, value) in columns {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            // Stacked bottom-up, so the label goes first
+                            flex_direction: FlexDirection::ColumnReverse,
+                            align_items: AlignItems::Center,
+                            margin: UiRect::new(
+                                Val::Px(3.0),
+                                Val::Px(3.0),
+                                Val::Px(0.0),
+                                Val::Px(0.0),
+                            ),
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(text(font, label, 12.0, DIM_TEXT_COLOR));
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(24.0),
+                                height: Val::Px((fraction * COLUMN_HEIGHT).max(2.0)),
+                                ..default()
+                            },
+                            background_color: ACCENT_COLOR.into(),
+                            ..default()
+                        });
+                        parent.spawn(text(font, value, 12.0, TEXT_COLOR));
+                    });
+            }
+        });
+}
+
+/// Horizontal bars, one row per entry, with the label on the left.
+fn spawn_bar_chart(parent: &mut ChildBuilder, font: &UiFont, bars: &[(String, f32, String)]) {
+    for (label, fraction, value) in bars {
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    margin: UiRect::new(Val::Px(0.0), Val::Px(0.0), Val::Px(6.0), Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            })
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(140.0),
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(text(font, label, 16.0, TEXT_COLOR));
+                    });
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px((fraction * BAR_WIDTH).max(2.0)),
+                        height: Val::Px(16.0),
+                        margin: UiRect::new(Val::Px(0.0), Val::Px(8.0), Val::Px(0.0), Val::Px(0.0)),
+                        ..default()
+                    },
+                    background_color: ACCENT_COLOR.into(),
+                    ..default()
+                });
+                parent.spawn(text(font, value, 16.0, DIM_TEXT_COLOR));
+            });
+    }
+}
+
+fn change_profile(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    store: Res<StatsStore>,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    mut selected: ResMut<SelectedProfile>,
+    screen: Query<Entity, With<OnDashboard>>,
+) {
+    let count = store.profiles().len();
+    if count < 2 {
+        return;
+    }
+
+    let before = selected.0;
+    if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        selected.0 = (selected.0 + count - 1) % count;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        selected.0 = (selected.0 + 1) % count;
+    }
+    if selected.0 == before {
+        return;
+    }
+
+    for entity in screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_dashboard(commands, font, store, strings, selected);
+}
+
+fn dashboard_action(
+    mut activated: EventReader<ButtonActivated>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let back = activated
+        .read()
+        .any(|event| back_buttons.get(event.0).is_ok())
+        || keyboard_input.just_pressed(KeyCode::Escape);
+    if back {
+        state.set(AppState::Menu);
+    }
+}
@@ -0,0 +1,178 @@
+//! Attract mode: a menu left alone for long enough starts the CPU playing
+//! itself, until somebody touches a key.
+
+use bevy::{prelude::*, window::CursorMoved};
+
+use crate::{
+    locale::Strings,
+    seams::Clock,
+    ui::{text, UiFont, DIM_TEXT_COLOR},
+    AppState, GameMode, MatchState,
+};
+
+/// Seconds the menu sits idle before the demo starts.
+const IDLE_SECONDS: f32 = 30.0;
+
+pub struct DemoPlugin;
+
+impl Plugin for DemoPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(IdleTimer(Timer::from_seconds(
+            IDLE_SECONDS,
+            TimerMode::Once,
+        )))
+        .add_systems(OnEnter(AppState::Menu), reset_idle_timer)
+        .add_systems(
+            Update,
+            start_demo_when_idle::<Time>.run_if(in_state(AppState::Menu)),
+        )
+        .add_systems(OnEnter(AppState::Playing), spawn_demo_banner)
+        .add_systems(Update, end_demo.run_if(in_state(MatchState::Running)));
+    }
+}
+
+/// How long the menu has gone without any input.
+#[derive(Resource)]
+struct IdleTimer(Timer);
+
+fn reset_idle_timer(mut idle: ResMut<IdleTimer>) {
+    idle.0.reset();
+}
+
+fn start_demo_when_idle<C: Clock>(
+    time: Res<C>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut cursor_moves: EventReader<CursorMoved>,
+    mut idle: ResMut<IdleTimer>,
+    mut mode: ResMut<GameMode>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let active = keyboard_input.get_pressed().next().is_some()
+        || mouse_input.get_pressed().next().is_some()
+        || cursor_moves.read().count() > 0;
+    if active {
+        idle.0.reset();
+    } else if idle.0.tick(time.delta()).just_finished() {
+        *mode = GameMode::Demo;
+        state.set(AppState::Playing);
+    }
+}
+
+fn spawn_demo_banner(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    mode: Res<GameMode>,
+) {
+    if *mode != GameMode::Demo {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(16.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn(text(
+                &font,
+                strings.get("demo.banner"),
+                24.0,
+                DIM_TEXT_COLOR,
+            ));
+        });
+}
+
+fn end_demo(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut mouse_input: ResMut<ButtonInput<MouseButton>>,
+    mode: Res<GameMode>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if *mode != GameMode::Demo {
+        return;
+    }
+
+    if keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some()
+    {
+        // The key that stops the demo shouldn't also pick something on the menu
+        keyboard_input.reset_all();
+        mouse_input.reset_all();
+        state.set(AppState::Menu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+    use crate::seams::ManualClock;
+
+    fn menu(seconds_a_frame: f64) -> App {
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .insert_state(AppState::Menu)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .add_event::<CursorMoved>()
+            .insert_resource(GameMode::Versus)
+            .insert_resource(ManualClock::new(seconds_a_frame))
+            .insert_resource(IdleTimer(Timer::from_seconds(
+                IDLE_SECONDS,
+                TimerMode::Once,
+            )))
+            .add_systems(
+                Update,
+                start_demo_when_idle::<ManualClock>.run_if(in_state(AppState::Menu)),
+            );
+        app
+    }
+
+    /// Goes by how long the menu's been left, however few frames that took.
+    #[test]
+    fn the_demo_starts_once_the_menu_is_left_alone() {
+        let mut app = menu(IDLE_SECONDS as f64 / 3.0);
+        app.update();
+        app.update();
+        assert_eq!(*app.world().resource::<GameMode>(), GameMode::Versus);
+
+        app.update();
+        // The state it's put in is only entered on the frame after
+        app.update();
+        assert_eq!(*app.world().resource::<GameMode>(), GameMode::Demo);
+        assert_eq!(
+            *app.world().resource::<State<AppState>>().get(),
+            AppState::Playing
+        );
+    }
+
+    #[test]
+    fn pressing_anything_starts_the_wait_over() {
+        let mut app = menu(IDLE_SECONDS as f64 / 3.0);
+        app.update();
+        app.update();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Space);
+        app.update();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .release(KeyCode::Space);
+        app.update();
+        app.update();
+        assert_eq!(*app.world().resource::<GameMode>(), GameMode::Versus);
+    }
+}
@@ -0,0 +1,553 @@
+//! Catching the two ends of an online match drifting apart.
+//!
+//! Rollback only works while both peers' simulations agree step for step, and
+//! when they stop agreeing nothing fails as such: each just goes on playing its
+//! own match. So every [`CHECK_INTERVAL`] steps, once a step is confirmed and
+//! can't be rolled back any more, each end sends the other a checksum of the
+//! simulation as it was after that step. The first checksum that doesn't match
+//! stops the match where it is, both ends swap what their simulation looked
+//! like at that step, and the two are saved side by side in a report, along
+//! with which parts of them differ.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::PathBuf,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    locale::Strings,
+    protocol::BUILD,
+    sim::{
+        Ball, MatchClock, MatchOver, Racket, RacketLength, Rally, Score, Side, SimRng, Spin, Tilt,
+        Velocity,
+    },
+    stats::{data_dir, now},
+    ui::{screen_root, text, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR, PANEL_COLOR, TEXT_COLOR},
+    AppState, MatchState,
+};
+
+/// Steps between checksums.
+pub const CHECK_INTERVAL: i32 = 30;
+const REPORT_DIR: &str = "desyncs";
+// Steps to remember until they're confirmed. More than the prediction
+// window ever lets the simulation get ahead of the confirmed step
+const RECENT_STEPS: usize = 64;
+// Checksums of ours to keep while waiting for the other end's
+const SENT_CHECKS: usize = 32;
+// Seconds between sending our side again, until the other end's is in
+const SNAPSHOT_RETRY: f64 = 1.0;
+
+pub struct DesyncPlugin;
+
+impl Plugin for DesyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimFrame>()
+            .init_resource::<DesyncCheck>()
+            .add_systems(OnEnter(AppState::Playing), reset_check)
+            .add_systems(
+                Update,
+                (save_report, show_report.after(save_report)).run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Steps simulated since the match started. Rolled back with everything else,
+/// so a step simulated again is numbered the same as the first time round.
+#[derive(Resource, Reflect, Default, Debug, Clone, Copy)]
+pub struct SimFrame(pub i32);
+
+/// The parts of the simulation that have to come out the same on both ends,
+/// as they were after one step.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SimState {
+    pub balls: Vec<BallState>,
+    pub rackets: Vec<RacketState>,
+    pub score: Score,
+    pub rally_hits: u32,
+    pub steps_left: u32,
+    pub winner: Option<i32>,
+    pub rng: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BallState {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub spin: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RacketState {
+    pub player_number: i32,
+    pub seat: usize,
+    pub position: Vec2,
+    pub tilt: f32,
+    pub length: f32,
+    pub side: Side,
+}
+
+impl SimState {
+    /// Every bit of it, floats included, so the smallest drift shows.
+    pub fn checksum(&self) -> u64 {
+        // FNV-1a, which unlike std's hashers is promised to stay the same
+        bincode::serialize(self)
+            .unwrap_or_default()
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    /// Which parts of the two aren't the same.
+    pub fn differences(&self, other: &SimState) -> Vec<&'static str> {
+        // Compared bit for bit, as the checksum is
+        let bits = |vec: Vec2| [vec.x.to_bits(), vec.y.to_bits()];
+        let (balls, rackets) = ((&self.balls, &other.balls), (&self.rackets, &other.rackets));
+        [
+            ("ball position", differ(balls, |ball| bits(ball.position))),
+            ("ball velocity", differ(balls, |ball| bits(ball.velocity))),
+            ("ball spin", differ(balls, |ball| ball.spin.to_bits())),
+            (
+                "racket position",
+                differ(rackets, |racket| bits(racket.position)),
+            ),
+            (
+                "racket tilt",
+                differ(rackets, |racket| racket.tilt.to_bits()),
+            ),
+            (
+                "racket length",
+                differ(rackets, |racket| racket.length.to_bits()),
+            ),
+            ("racket side", differ(rackets, |racket| racket.side)),
+            ("score", self.score != other.score),
+            ("rally", self.rally_hits != other.rally_hits),
+            ("clock", self.steps_left != other.steps_left),
+            ("winner", self.winner != other.winner),
+            ("random numbers", self.rng != other.rng),
+        ]
+        .into_iter()
+        .filter(|(_, differs)| *differs)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+/// Whether any of one list's `field`s differs from the other's.
+fn differ<T, U: PartialEq>((ours, theirs): (&Vec<T>, &Vec<T>), field: impl Fn(&T) -> U) -> bool {
+    ours.len() != theirs.len() || ours.iter().zip(theirs).any(|(a, b)| field(a) != field(b))
+}
+
+/// What the two ends send each other about their simulations.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DesyncMessage {
+    /// How the simulation came out after a confirmed step
+    Checksum { frame: i32, checksum: u64 },
+    /// All of it, once the checksums for `frame` didn't match
+    Snapshot { frame: i32, state: SimState },
+}
+
+/// Where the two ends stopped agreeing.
+#[derive(Debug, Clone)]
+pub struct Desync {
+    pub frame: i32,
+    /// Our simulation after that step, if it's still remembered
+    pub ours: Option<SimState>,
+    /// Theirs, once it's come
+    pub theirs: Option<SimState>,
+    last_sent: f64,
+}
+
+impl Desync {
+    pub fn differences(&self) -> Option<Vec<&'static str>> {
+        Some(self.ours.as_ref()?.differences(self.theirs.as_ref()?))
+    }
+}
+
+/// Keeping track of our checksums and theirs. The network fills it in with
+/// what's confirmed and what's come from the other end, and sends on what
+/// it has to say back.
+#[derive(Resource, Default)]
+pub struct DesyncCheck {
+    /// Recent steps, not confirmed yet, in order
+    recent: VecDeque<(i32, SimState)>,
+    /// Checksums we've sent, with what they were of
+    sent: VecDeque<(i32, u64, SimState)>,
+    /// Theirs, until there's one of ours to go with it
+    theirs: HashMap<i32, u64>,
+    /// The last step there's a checksum of
+    checked: Option<i32>,
+    outgoing: Vec<DesyncMessage>,
+    desync: Option<Desync>,
+    /// Where the report's been saved, or why it couldn't be
+    report: Option<Result<PathBuf, String>>,
+    report_complete: bool,
+}
+
+impl DesyncCheck {
+    /// Remembers how a step came out. Stepping again after a rollback
+    /// replaces whatever the step and those after it came to before.
+    pub fn record(&mut self, frame: i32, state: SimState) {
+        while matches!(self.recent.back(), Some((recorded, _)) if *recorded >= frame) {
+            self.recent.pop_back();
+        }
+        self.recent.push_back((frame, state));
+        while self.recent.len() > RECENT_STEPS {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Sends a checksum of every step due one up to `confirmed`, which can't
+    /// change any more, and then sends our side again if the other end
+    /// hasn't sent theirs of a desync yet.
+    pub fn confirm(&mut self, confirmed: i32, now: f64) {
+        while matches!(self.recent.front(), Some((frame, _)) if *frame <= confirmed) {
+            let (frame, state) = self.recent.pop_front().unwrap();
+            if frame % CHECK_INTERVAL != 0 || self.checked.is_some_and(|last| frame <= last) {
+                continue;
+            }
+            let checksum = state.checksum();
+            self.outgoing
+                .push(DesyncMessage::Checksum { frame, checksum });
+            self.sent.push_back((frame, checksum, state));
+            if self.sent.len() > SENT_CHECKS {
+                self.sent.pop_front();
+            }
+            self.checked = Some(frame);
+        }
+        self.compare(now);
+
+        if let Some(desync) = &mut self.desync {
+            if desync.theirs.is_none() && now - desync.last_sent > SNAPSHOT_RETRY {
+                desync.last_sent = now;
+                if let Some(state) = &desync.ours {
+                    self.outgoing.push(DesyncMessage::Snapshot {
+                        frame: desync.frame,
+                        state: state.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Takes in something from the other end.
+    pub fn receive(&mut self, message: DesyncMessage, now: f64) {
+        match message {
+            DesyncMessage::Checksum { frame, checksum } => {
+                self.theirs.insert(frame, checksum);
+                self.compare(now);
+            }
+            DesyncMessage::Snapshot { frame, state } => {
+                // They found it first, and ours of it has to go back to them
+                if self.desync.is_none() {
+                    self.start_desync(frame, now);
+                }
+                if let Some(desync) = &mut self.desync {
+                    if desync.frame == frame {
+                        desync.theirs = Some(state);
+                    }
+                }
+            }
+        }
+    }
+
+    /// What's waiting to go to the other end.
+    pub fn take_outgoing(&mut self) -> Vec<DesyncMessage> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    pub fn desync(&self) -> Option<&Desync> {
+        self.desync.as_ref()
+    }
+
+    fn compare(&mut self, now: f64) {
+        if self.desync.is_some() {
+            return;
+        }
+        let mismatch = self.sent.iter().find_map(|(frame, ours, _)| {
+            let theirs = self.theirs.get(frame)?;
+            (theirs != ours).then_some(*frame)
+        });
+        match mismatch {
+            Some(frame) => self.start_desync(frame, now),
+            None => {
+                // Those that matched are done with, on both sides
+                let theirs = &mut self.theirs;
+                self.sent
+                    .retain(|(frame, _, _)| theirs.remove(frame).is_none());
+                if let Some((oldest, _, _)) = self.sent.front() {
+                    theirs.retain(|frame, _| frame >= oldest);
+                }
+            }
+        }
+    }
+
+    fn start_desync(&mut self, frame: i32, now: f64) {
+        error!("Out of sync with the other player since step {}", frame);
+        let ours = self
+            .sent
+            .iter()
+            .find(|(sent, _, _)| *sent == frame)
+            .map(|(_, _, state)| state.clone());
+        if let Some(state) = &ours {
+            self.outgoing.push(DesyncMessage::Snapshot {
+                frame,
+                state: state.clone(),
+            });
+        }
+        self.desync = Some(Desync {
+            frame,
+            ours,
+            theirs: None,
+            last_sent: now,
+        });
+    }
+}
+
+/// Records how the step just simulated came out. Runs last in the rollback
+/// schedule, after the simulation itself.
+pub fn record_state(
+    mut frame: ResMut<SimFrame>,
+    mut check: ResMut<DesyncCheck>,
+    balls: Query<(&Transform, &Velocity, &Spin), With<Ball>>,
+    rackets: Query<(&Racket, &Side, &Transform, &Tilt, &RacketLength)>,
+    score: Res<Score>,
+    rally: Res<Rally>,
+    clock: Res<MatchClock>,
+    match_over: Res<MatchOver>,
+    rng: Res<SimRng>,
+) {
+    // Entities needn't come in the same order on both ends
+    let mut balls: Vec<_> = balls
+        .iter()
+        .map(|(transform, velocity, spin)| BallState {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            spin: spin.0,
+        })
+        .collect();
+    balls.sort_by(|a, b| a.position.x.total_cmp(&b.position.x));
+    let mut rackets: Vec<_> = rackets
+        .iter()
+        .map(|(racket, side, transform, tilt, length)| RacketState {
+            player_number: racket.player_number,
+            seat: racket.seat,
+            position: transform.translation.truncate(),
+            tilt: tilt.0,
+            length: length.current,
+            side: *side,
+        })
+        .collect();
+    rackets.sort_by_key(|racket| (racket.player_number, racket.seat));
+
+    check.record(
+        frame.0,
+        SimState {
+            balls,
+            rackets,
+            score: *score,
+            rally_hits: rally.hits,
+            steps_left: clock.steps_left,
+            winner: match_over.0,
+            rng: rng.state(),
+        },
+    );
+    frame.0 += 1;
+}
+
+fn reset_check(mut check: ResMut<DesyncCheck>, mut frame: ResMut<SimFrame>) {
+    *check = default();
+    frame.0 = 0;
+}
+
+/// What's saved of a desync, to be gone through side by side with the other
+/// player's.
+#[derive(Serialize)]
+struct DesyncReport<'a> {
+    build: &'a str,
+    frame: i32,
+    differences: Option<Vec<&'static str>>,
+    ours: &'a Option<SimState>,
+    theirs: &'a Option<SimState>,
+}
+
+/// Saves the report as soon as there's a desync, and again once the other
+/// end's side of it has come. Both ends save one.
+fn save_report(mut check: ResMut<DesyncCheck>) {
+    let desync = match &check.desync {
+        Some(desync) => desync,
+        None => return,
+    };
+    let complete = desync.theirs.is_some();
+    if check.report.is_some() && (check.report_complete || !complete) {
+        return;
+    }
+
+    let path = match &check.report {
+        Some(Ok(path)) => Some(path.clone()),
+        _ => data_dir().map(|dir| {
+            dir.join(REPORT_DIR)
+                .join(format!("desync-{}-step-{}.ron", now(), desync.frame))
+        }),
+    };
+    let report = DesyncReport {
+        build: BUILD,
+        frame: desync.frame,
+        differences: desync.differences(),
+        ours: &desync.ours,
+        theirs: &desync.theirs,
+    };
+    let saved = path
+        .ok_or_else(|| "no data directory".to_string())
+        .and_then(|path| {
+            let ron = ron::ser::to_string_pretty(&report, default()).map_err(|e| e.to_string())?;
+            fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+            fs::write(&path, ron).map_err(|e| e.to_string())?;
+            Ok(path)
+        });
+    match &saved {
+        Ok(path) => info!("Saved a desync report to {}", path.display()),
+        Err(error) => error!("Couldn't save the desync report: {}", error),
+    }
+    check.report = Some(saved);
+    check.report_complete = complete;
+}
+
+#[derive(Component)]
+struct DesyncReportPanel;
+
+#[derive(Component)]
+struct DesyncReportText;
+
+fn show_report(
+    mut commands: Commands,
+    check: Res<DesyncCheck>,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    panels: Query<(), With<DesyncReportPanel>>,
+    mut texts: Query<&mut Text, With<DesyncReportText>>,
+) {
+    let desync = match &check.desync {
+        Some(desync) => desync,
+        None => return,
+    };
+
+    let mut lines = vec![strings.fill("desync.frame", &[("frame", &desync.frame)])];
+    lines.push(match desync.differences() {
+        Some(differences) if differences.is_empty() => strings.get("desync.same").to_string(),
+        Some(differences) => {
+            strings.fill("desync.differences", &[("parts", &differences.join(", "))])
+        }
+        None => strings.get("desync.waiting").to_string(),
+    });
+    lines.push(match &check.report {
+        Some(Ok(path)) => strings.fill("desync.saved", &[("path", &path.display())]),
+        Some(Err(error)) => strings.fill("desync.not_saved", &[("error", error)]),
+        None => String::new(),
+    });
+    let details = lines.join("\n");
+
+    if panels.is_empty() {
+        commands
+            .spawn((
+                NodeBundle {
+                    background_color: PANEL_COLOR.into(),
+                    ..screen_root()
+                },
+                DesyncReportPanel,
+                StateScoped(AppState::Playing),
+            ))
+            .with_children(|parent| {
+                let margin = Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                };
+                parent.spawn(
+                    text(&font, strings.get("desync.title"), 48.0, ACCENT_COLOR)
+                        .with_style(margin.clone()),
+                );
+                parent.spawn((
+                    text(&font, details, 20.0, TEXT_COLOR)
+                        .with_text_justify(JustifyText::Center)
+                        .with_style(margin.clone()),
+                    DesyncReportText,
+                ));
+                parent.spawn(
+                    text(&font, strings.get("desync.hint"), 18.0, DIM_TEXT_COLOR)
+                        .with_style(margin),
+                );
+            });
+        return;
+    }
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != details {
+            text.sections[0].value = details.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(x: f32) -> SimState {
+        SimState {
+            balls: vec![BallState {
+                position: Vec2::new(x, 0.0),
+                velocity: Vec2::new(300.0, 20.0),
+                spin: 0.0,
+            }],
+            rackets: Vec::new(),
+            score: default(),
+            rally_hits: 0,
+            steps_left: 0,
+            winner: None,
+            rng: 7,
+        }
+    }
+
+    fn swap(from: &mut DesyncCheck, to: &mut DesyncCheck) {
+        for message in from.take_outgoing() {
+            to.receive(message, 0.0);
+        }
+    }
+
+    #[test]
+    fn checksums_that_differ_swap_both_sides() {
+        let (mut ours, mut theirs) = (DesyncCheck::default(), DesyncCheck::default());
+        for frame in 0..=CHECK_INTERVAL {
+            ours.record(frame, state(frame as f32));
+            theirs.record(frame, state(frame as f32));
+        }
+        // Ours mispredicted the last step, and gets it right on stepping it again
+        ours.record(CHECK_INTERVAL, state(-1.0));
+        ours.record(CHECK_INTERVAL, state(CHECK_INTERVAL as f32));
+        ours.confirm(CHECK_INTERVAL, 0.0);
+        theirs.confirm(CHECK_INTERVAL, 0.0);
+        swap(&mut ours, &mut theirs);
+        swap(&mut theirs, &mut ours);
+        assert!(ours.desync().is_none() && theirs.desync().is_none());
+
+        let next = 2 * CHECK_INTERVAL;
+        ours.record(next, state(1.0));
+        theirs.record(next, state(1.0 + f32::EPSILON));
+        ours.confirm(next, 0.0);
+        theirs.confirm(next, 0.0);
+        // Only theirs gets to ours, so ours finds out first
+        swap(&mut theirs, &mut ours);
+        assert_eq!(ours.desync().map(|desync| desync.frame), Some(next));
+        assert!(theirs.desync().is_none());
+
+        swap(&mut ours, &mut theirs);
+        swap(&mut theirs, &mut ours);
+        for check in [&ours, &theirs] {
+            let desync = check.desync().unwrap();
+            assert_eq!(desync.frame, next);
+            assert_eq!(desync.differences(), Some(vec!["ball position"]));
+        }
+    }
+}
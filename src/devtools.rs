@@ -0,0 +1,138 @@
+//! A panel for tuning how the game plays while it's being played: how fast
+//! the ball and the rackets go, how far off the ends of the rackets send the
+//! ball, and how the CPUs in the match go about it. F2 opens and closes it.
+//!
+//! Everything's changed in place, for the match on now. The rules go back to
+//! the settings' with the next match, and a personality to its file when the
+//! file next changes. Online matches are left alone, as both ends have to
+//! play by the same rules.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+use crate::{
+    ai::{Cpu, Personality},
+    sim::{GameplayConfig, MatchRules},
+    AppState, GameMode, Player,
+};
+
+// Ranges the sliders go over, in pixels a second, degrees and pixels
+const SERVE_SPEEDS: (f32, f32) = (100.0, 1200.0);
+const RACKET_SPEEDS: (f32, f32) = (40.0, 480.0);
+const BOUNCE_ANGLES: (f32, f32) = (0.0, 75.0);
+const MISJUDGEMENTS: (f32, f32) = (0.0, 100.0);
+
+pub struct DevtoolsPlugin;
+
+impl Plugin for DevtoolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(EguiPlugin)
+            .init_resource::<PanelOpen>()
+            .add_systems(Update, toggle_panel)
+            .add_systems(Update, tuning_panel.after(toggle_panel));
+    }
+}
+
+#[derive(Resource, Default)]
+struct PanelOpen(bool);
+
+fn toggle_panel(keyboard_input: Res<ButtonInput<KeyCode>>, mut open: ResMut<PanelOpen>) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        open.0 = !open.0;
+    }
+}
+
+fn tuning_panel(
+    mut egui_context: EguiContexts,
+    mut open: ResMut<PanelOpen>,
+    state: Res<State<AppState>>,
+    mode: Res<GameMode>,
+    mut rules: ResMut<MatchRules>,
+    config: Res<GameplayConfig>,
+    cpus: Query<(&Player, &Cpu)>,
+    mut personalities: ResMut<Assets<Personality>>,
+) {
+    if !open.0 {
+        return;
+    }
+    egui::Window::new("Tuning")
+        .open(&mut open.0)
+        .show(egui_context.ctx_mut(), |ui| {
+            if *state.get() != AppState::Playing {
+                ui.label("Start a match to tune it");
+                return;
+            }
+            if *mode == GameMode::Online {
+                ui.label("Online matches play by the defaults");
+                return;
+            }
+
+            ui.heading("Ball and rackets");
+            // Tuned as copies and only written back when they change, or the
+            // rules would be changed every frame the panel's open
+            let mut serve_speed = rules.ball_speed * config.ball_speed;
+            let mut racket_speed = rules.racket_speed * config.racket_speed;
+            let mut bounce_angle = rules.bounce_angle.to_degrees();
+            let mut changed = false;
+            changed |= ui
+                .add(slider(&mut serve_speed, SERVE_SPEEDS, "Serve speed, px/s"))
+                .changed();
+            changed |= ui
+                .add(slider(
+                    &mut racket_speed,
+                    RACKET_SPEEDS,
+                    "Racket speed, px/s",
+                ))
+                .changed();
+            changed |= ui
+                .add(slider(&mut bounce_angle, BOUNCE_ANGLES, "Bounce angle, °"))
+                .changed();
+            if changed {
+                rules.ball_speed = serve_speed / config.ball_speed;
+                rules.racket_speed = racket_speed / config.racket_speed;
+                rules.bounce_angle = bounce_angle.to_radians();
+            }
+
+            ui.separator();
+            ui.heading("CPUs");
+            if cpus.is_empty() {
+                ui.label("Nobody's playing against a CPU");
+            }
+            for (player, cpu) in cpus.iter() {
+                let style = cpu
+                    .personality()
+                    .and_then(|handle| Some((handle, *personalities.get(handle)?)));
+                let (handle, mut style) = match style {
+                    Some(style) => style,
+                    None => {
+                        ui.label(format!("{} plays it straight", player.name));
+                        continue;
+                    }
+                };
+                ui.label(&player.name);
+                let mut changed = false;
+                changed |= ui
+                    .add(slider(&mut style.misjudge, MISJUDGEMENTS, "Misjudge, px"))
+                    .changed();
+                changed |= ui
+                    .add(slider(&mut style.recover, (0.0, 1.0), "Recover"))
+                    .changed();
+                changed |= ui
+                    .add(slider(&mut style.edge, (0.0, 1.0), "Edge"))
+                    .changed();
+                changed |= ui.checkbox(&mut style.tilts, "Tilts").changed();
+                changed |= ui
+                    .add(slider(&mut style.smash_chance, (0.0, 1.0), "Smash chance"))
+                    .changed();
+                if changed {
+                    if let Some(personality) = personalities.get_mut(handle) {
+                        *personality = style;
+                    }
+                }
+            }
+        });
+}
+
+fn slider<'a>(value: &'a mut f32, (min, max): (f32, f32), label: &str) -> egui::Slider<'a> {
+    egui::Slider::new(value, min..=max).text(label)
+}
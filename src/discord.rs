@@ -0,0 +1,191 @@
+//! What's being played, shown on the player's Discord profile through Rich
+//! Presence: being in the menus, or the kind of match, its score and how long
+//! it's been going.
+//!
+//! Discord needs the id of an application registered with it, which builds
+//! get from `PINGIS_DISCORD_APP` when they're compiled, and runs get from
+//! `--discord-app <id>`. Without one, or without Discord running, nothing's
+//! shown and the game carries on as usual.
+//!
+//! Talking to Discord happens on a thread of its own, which is only told
+//! when what's shown changes, so the game never waits for it.
+
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+use bevy::prelude::*;
+use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
+
+use crate::{ai::Cpu, cli_arg, locale::Strings, sim::Score, stats, AppState, GameMode};
+
+/// The application Discord shows the game as, unless `--discord-app` says
+/// otherwise.
+const APP_ID: Option<&str> = option_env!("PINGIS_DISCORD_APP");
+
+pub struct DiscordPlugin;
+
+impl Plugin for DiscordPlugin {
+    fn build(&self, app: &mut App) {
+        let app_id = cli_arg("discord-app").or_else(|| APP_ID.map(str::to_string));
+        let discord = match app_id {
+            Some(app_id) => Discord::start(app_id),
+            None => {
+                info!("Not showing anything on Discord, as there's no application to show it as");
+                return;
+            }
+        };
+
+        app.insert_resource(discord)
+            .init_resource::<MatchStarted>()
+            .add_systems(OnEnter(AppState::Playing), start_clock)
+            .add_systems(Update, update_presence);
+    }
+}
+
+/// What's on the player's profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Presence {
+    /// The top line, like "Playing vs CPU"
+    details: String,
+    /// The line under it, like the score
+    state: Option<String>,
+    /// When the match started, in seconds since the epoch, for Discord to
+    /// count the time up from
+    since: Option<u64>,
+}
+
+/// The way to the thread talking to Discord, and what it was last told.
+#[derive(Resource)]
+pub struct Discord {
+    updates: Mutex<Sender<Presence>>,
+    shown: Option<Presence>,
+}
+
+impl Discord {
+    fn start(app_id: String) -> Self {
+        let (updates, presences) = mpsc::channel();
+        thread::spawn(move || publish(&app_id, presences));
+        Discord {
+            updates: Mutex::new(updates),
+            shown: None,
+        }
+    }
+
+    fn show(&mut self, presence: Presence) {
+        if self.shown.as_ref() == Some(&presence) {
+            return;
+        }
+        // The thread only goes away along with the game
+        let _ = self.updates.lock().unwrap().send(presence.clone());
+        self.shown = Some(presence);
+    }
+}
+
+/// Passes each presence on to Discord, connecting to it whenever it isn't
+/// connected, as it might only have been started after the game.
+fn publish(app_id: &str, presences: Receiver<Presence>) {
+    let mut client = None;
+    while let Ok(mut presence) = presences.recv() {
+        // Only the latest of any that came while Discord was being waited on
+        // is worth showing
+        while let Ok(later) = presences.try_recv() {
+            presence = later;
+        }
+
+        if client.is_none() {
+            client = connect(app_id);
+        }
+        if let Some(connected) = &mut client {
+            if let Err(error) = connected.set_activity(to_activity(&presence)) {
+                debug!("Lost Discord: {}", error);
+                client = None;
+            }
+        }
+    }
+}
+
+fn connect(app_id: &str) -> Option<DiscordIpcClient> {
+    let mut client = DiscordIpcClient::new(app_id)
+        .map_err(|error| warn!("Couldn't set up Discord: {}", error))
+        .ok()?;
+    match client.connect() {
+        Ok(()) => {
+            info!("Showing what's being played on Discord");
+            Some(client)
+        }
+        Err(error) => {
+            debug!("Couldn't reach Discord: {}", error);
+            None
+        }
+    }
+}
+
+fn to_activity(presence: &Presence) -> activity::Activity<'_> {
+    let mut shown = activity::Activity::new().details(&presence.details);
+    if let Some(state) = &presence.state {
+        shown = shown.state(state);
+    }
+    if let Some(since) = presence.since {
+        shown = shown.timestamps(activity::Timestamps::new().start(since as i64));
+    }
+    shown
+}
+
+/// When the match being played started, in seconds since the epoch.
+#[derive(Resource, Default)]
+struct MatchStarted(u64);
+
+fn start_clock(mut started: ResMut<MatchStarted>) {
+    started.0 = stats::now();
+}
+
+/// Works out what to show from the state of the game, and has it shown if
+/// that's changed.
+fn update_presence(
+    mut discord: ResMut<Discord>,
+    state: Res<State<AppState>>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    started: Res<MatchStarted>,
+    strings: Res<Strings>,
+    cpus: Query<(), With<Cpu>>,
+) {
+    let in_match = matches!(state.get(), AppState::Playing | AppState::GameOver);
+    // The demo plays itself in the menus
+    if !in_match || *mode == GameMode::Demo {
+        discord.show(Presence {
+            details: strings.get("discord.menu").to_string(),
+            state: None,
+            since: None,
+        });
+        return;
+    }
+
+    let details = if *mode == GameMode::Online {
+        strings.get("discord.online").to_string()
+    } else if !cpus.is_empty() {
+        strings.get("discord.vs_cpu").to_string()
+    } else {
+        let mode = strings.get(mode.name_key());
+        strings.fill("discord.playing", &[("mode", &mode)])
+    };
+    let score = strings.fill(
+        "discord.score",
+        &[
+            ("left", &score.player1),
+            ("right", &score.player2),
+            ("left_sets", &score.player1_sets),
+            ("right_sets", &score.player2_sets),
+        ],
+    );
+    discord.show(Presence {
+        details,
+        state: Some(score),
+        since: Some(started.0),
+    });
+}
@@ -0,0 +1,215 @@
+//! Keeping the whole court on screen whatever the window's shape, the window
+//! as it's set to be in the settings, and switching in and out of fullscreen
+//! with Alt+Enter.
+//!
+//! Frames are held to the cap in the settings, if there is one, and away from
+//! a match, with the window in the background or nothing pressed for a while,
+//! they only come as often as it takes to notice something's happened, so a
+//! menu sitting there doesn't run a battery down.
+
+use std::time::Duration;
+
+use bevy::{
+    input::{gamepad::GamepadEvent, keyboard::KeyboardInput, mouse::MouseButtonInput, InputSystem},
+    prelude::*,
+    render::camera::ScalingMode,
+    utils::Instant,
+    window::{CursorMoved, PresentMode, PrimaryWindow, WindowMode, WindowResized},
+    winit::{UpdateMode, WinitSettings},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{arena::ArenaExtent, settings::Settings, AppState, MainCamera};
+
+/// Seconds away from a match without anything pressed before frames slow down.
+const IDLE_AFTER: f32 = 10.0;
+/// The longest between frames while saving power, when nothing's happened.
+const LOW_POWER_WAIT: Duration = Duration::from_millis(100);
+
+/// How the frames are fitted to the screen's refreshes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsyncMode {
+    /// A frame to every refresh, and no faster
+    On,
+    /// The latest frame at every refresh, without tearing and without
+    /// waiting, where the graphics driver can, and as `On` where it can't
+    Fast,
+    /// Frames as fast as they come, tearing and all
+    Off,
+}
+
+/// The ways frames can be fitted to the refreshes, in the order they're picked
+/// from.
+pub const VSYNC_MODES: [VsyncMode; 3] = [VsyncMode::On, VsyncMode::Fast, VsyncMode::Off];
+
+impl VsyncMode {
+    /// Key of its name in the [`Strings`](crate::locale::Strings).
+    pub fn name_key(self) -> &'static str {
+        match self {
+            VsyncMode::On => "vsync.on",
+            VsyncMode::Fast => "vsync.fast",
+            VsyncMode::Off => "vsync.off",
+        }
+    }
+
+    fn present_mode(self) -> PresentMode {
+        match self {
+            VsyncMode::On => PresentMode::AutoVsync,
+            VsyncMode::Fast => PresentMode::Mailbox,
+            VsyncMode::Off => PresentMode::AutoNoVsync,
+        }
+    }
+}
+
+pub struct DisplayPlugin;
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, fit_camera)
+            .add_systems(Update, apply_window_settings)
+            .add_systems(Update, save_power)
+            .add_systems(Last, cap_frames)
+            // Before the menus and the chat see Enter
+            .add_systems(PreUpdate, toggle_fullscreen.after(InputSystem));
+    }
+}
+
+fn fit_camera(
+    windows: Query<(Entity, &Window), With<PrimaryWindow>>,
+    mut resized: EventReader<WindowResized>,
+    extent: Res<ArenaExtent>,
+    mut cameras: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    let (primary, window) = match windows.get_single() {
+        Ok(window) => window,
+        Err(_) => return,
+    };
+    let resized = resized.read().any(|event| event.window == primary);
+    if !resized && !extent.is_changed() {
+        return;
+    }
+    if extent.0 == Vec2::ZERO || window.width() == 0.0 || window.height() == 0.0 {
+        return;
+    }
+
+    // Whichever way the court is too big it fits exactly, the other way gets some room.
+    // Only the window's shape matters, so this also holds when drawing into an image.
+    let aspect = window.width() / window.height();
+    let half_size = Vec2::new(
+        extent.0.x.max(extent.0.y * aspect),
+        extent.0.y.max(extent.0.x / aspect),
+    );
+    for mut projection in cameras.iter_mut() {
+        projection.scaling_mode = ScalingMode::Fixed {
+            width: 2.0 * half_size.x,
+            height: 2.0 * half_size.y,
+        };
+    }
+}
+
+/// Puts the window the way the settings have it. The size is only set when
+/// it's changed in the settings, so it isn't put back after a drag, and never
+/// in a browser, where it's the page's.
+fn apply_window_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut resolution: Local<Option<(u32, u32)>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let mut window = match windows.get_single_mut() {
+        Ok(window) => window,
+        Err(_) => return,
+    };
+
+    let fullscreen = window.mode != WindowMode::Windowed;
+    if settings.fullscreen != fullscreen {
+        window.mode = if settings.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        };
+    }
+    let present_mode = settings.vsync_mode.present_mode();
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+    if *resolution != Some(settings.resolution) && window.canvas.is_none() {
+        let (width, height) = settings.resolution;
+        window.resolution.set(width as f32, height as f32);
+        *resolution = Some(settings.resolution);
+    }
+}
+
+/// Slows the frames right down away from a match, once the window's in the
+/// background or nothing's been pressed for [`IDLE_AFTER`] seconds, and brings
+/// them back as soon as anything is. Online, the lobby keeps its pace so
+/// nobody waiting on it is kept waiting longer.
+fn save_power(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    state: Res<State<AppState>>,
+    winit_settings: Option<ResMut<WinitSettings>>,
+    mut keys: EventReader<KeyboardInput>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut cursor: EventReader<CursorMoved>,
+    mut gamepads: EventReader<GamepadEvent>,
+    mut idle: Local<f32>,
+) {
+    let mut winit_settings = match winit_settings {
+        Some(winit_settings) => winit_settings,
+        None => return,
+    };
+    let pressed = keys.read().count() + buttons.read().count() > 0;
+    let moved = cursor.read().count() + gamepads.read().count() > 0;
+    if pressed || moved {
+        *idle = 0.0;
+    } else {
+        *idle += time.delta_seconds();
+    }
+
+    #[cfg(feature = "networking")]
+    let online = *state.get() == AppState::Network;
+    #[cfg(not(feature = "networking"))]
+    let online = false;
+    let saving = settings.low_power && *state.get() != AppState::Playing && !online;
+    winit_settings.focused_mode = if saving && *idle >= IDLE_AFTER {
+        UpdateMode::reactive(LOW_POWER_WAIT)
+    } else {
+        UpdateMode::Continuous
+    };
+    winit_settings.unfocused_mode = if saving {
+        UpdateMode::reactive_low_power(LOW_POWER_WAIT)
+    } else {
+        UpdateMode::Continuous
+    };
+}
+
+/// Holds each frame back until it's been long enough since the last for the
+/// cap in the settings. A browser keeps to the screen's refreshes already,
+/// and can't be held back.
+fn cap_frames(settings: Res<Settings>, mut last: Local<Option<Instant>>) {
+    let cap = settings.frame_cap.filter(|_| !cfg!(target_arch = "wasm32"));
+    if let (Some(cap), Some(last)) = (cap, *last) {
+        let frame = Duration::from_secs_f64(1.0 / cap.max(1) as f64);
+        if let Some(wait) = frame.checked_sub(last.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+    *last = Some(Instant::now());
+}
+
+pub fn toggle_fullscreen(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+) {
+    let alt = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if !alt || !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+    // It's not for anyone else
+    keyboard_input.reset(KeyCode::Enter);
+
+    settings.fullscreen = !settings.fullscreen;
+}
@@ -0,0 +1,76 @@
+//! Doubles: a second racket on each end, further up the court, for each
+//! player's partner to move with keys of their own.
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::ArenaBounds,
+    sim::{spawn_racket, Racket, Side, Waiting},
+    AppState, GameMode, MatchState,
+};
+
+/// How far up the court from the back racket the front one stands, as a
+/// fraction of the way to the center line.
+const FRONT_POSITION: f32 = 0.4;
+/// A racket waiting for its partner to hit is faded out to this.
+const WAITING_OPACITY: f32 = 0.35;
+
+pub struct DoublesPlugin;
+
+impl Plugin for DoublesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (add_partners, despawn_orphaned_partners, fade_waiting)
+                .run_if(in_state(MatchState::Running).and_then(in_doubles)),
+        );
+    }
+}
+
+/// The front racket of a pair, standing in front of the arena's own.
+#[derive(Component)]
+struct Partner {
+    of: Entity,
+}
+
+fn in_doubles(mode: Res<GameMode>) -> bool {
+    *mode == GameMode::Doubles
+}
+
+/// Gives every racket the arena spawns a partner, whenever it spawns them.
+fn add_partners(
+    mut commands: Commands,
+    bounds: Res<ArenaBounds>,
+    rackets: Query<(Entity, &Racket, &Side, &Transform), (Added<Racket>, Without<Partner>)>,
+) {
+    let center = (bounds.min.x + bounds.max.x) / 2.0;
+    for (entity, racket, side, transform) in rackets.iter() {
+        let mut location = transform.translation;
+        location.x += (center - location.x) * FRONT_POSITION;
+        spawn_racket(&mut commands, *side, location)
+            // In place of the player's own
+            .insert(Racket::partner(racket.player_number))
+            .insert(Partner { of: entity })
+            .insert(StateScoped(AppState::Playing));
+    }
+}
+
+fn despawn_orphaned_partners(
+    mut commands: Commands,
+    partners: Query<(Entity, &Partner)>,
+    rackets: Query<(), (With<Racket>, Without<Partner>)>,
+) {
+    for (entity, partner) in partners.iter() {
+        if rackets.get(partner.of).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn fade_waiting(mut rackets: Query<(&Waiting, &mut Sprite), With<Racket>>) {
+    for (waiting, mut sprite) in rackets.iter_mut() {
+        sprite
+            .color
+            .set_alpha(if waiting.0 { WAITING_OPACITY } else { 1.0 });
+    }
+}
@@ -0,0 +1,857 @@
+//! Drills for practice: the ball machine firing a set run of balls, each from
+//! its own height, at its own speed and angle and after its own wait, rather
+//! than at random for as long as the player likes. A few come with the game,
+//! and players make their own in the drill editor, which saves them as
+//! `.pingis-drill` files in the `drills` folder. Each drill keeps how many
+//! times it's been run to the end, how many of those every ball came back,
+//! and the most that ever did.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::ArenaBounds,
+    locale::Strings,
+    sim::GoalEvent,
+    stats::{data_dir, load_ron, save_ron},
+    ui::{
+        screen_root, spawn_button, text, text_style, ButtonActivated, MenuButton, MenuFocus,
+        UiFont, ACCENT_COLOR, DIM_TEXT_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, MatchState,
+};
+
+pub const EXTENSION: &str = "pingis-drill";
+const DRILL_DIR: &str = "drills";
+const STATS_FILE: &str = "drill_stats.ron";
+// How many drills fit on the screen
+const LISTED_DRILLS: usize = 6;
+const MAX_BALLS: usize = 20;
+// What the editor steps each of a ball's numbers by, and how far they go
+const HEIGHT_STEP: f32 = 0.1;
+const SPEED_STEP: f32 = 0.25;
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED: f32 = 2.0;
+const ANGLE_STEP: f32 = 5.0;
+const MAX_ANGLE: f32 = 45.0;
+const WAIT_STEP: f32 = 0.5;
+const MIN_WAIT: f32 = 0.5;
+const MAX_WAIT: f32 = 5.0;
+
+pub struct DrillsPlugin;
+
+impl Plugin for DrillsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DrillStats::load())
+            .init_resource::<DrillRun>()
+            .init_resource::<DrillEditor>()
+            .add_systems(OnEnter(AppState::Drills), spawn_drills)
+            .add_systems(Update, drills_action.run_if(in_state(AppState::Drills)))
+            .add_systems(OnEnter(AppState::DrillEditor), spawn_editor)
+            .add_systems(
+                Update,
+                (edit_drill, update_editor.after(edit_drill))
+                    .run_if(in_state(AppState::DrillEditor)),
+            )
+            .add_systems(
+                Update,
+                (count_drill_returns, finish_drill.after(count_drill_returns))
+                    .run_if(in_state(MatchState::Running)),
+            )
+            .add_systems(OnExit(AppState::Playing), end_drill);
+    }
+}
+
+/// One ball of a drill.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct DrillServe {
+    /// Where the machine fires it from, from 0 at the bottom of the court to
+    /// 1 at the top
+    pub height: f32,
+    /// How fast it goes, as a multiple of a serve's speed
+    pub speed: f32,
+    /// Degrees off straight at the player, upwards for more than 0
+    pub angle: f32,
+    /// Seconds after the ball before it, or the start of the drill
+    pub wait: f32,
+}
+
+impl Default for DrillServe {
+    fn default() -> Self {
+        DrillServe {
+            height: 0.5,
+            speed: 1.0,
+            angle: 0.0,
+            wait: 2.0,
+        }
+    }
+}
+
+impl DrillServe {
+    /// Where the middle of a machine `size` high goes for it, in `bounds`.
+    pub fn machine_y(&self, bounds: &ArenaBounds, size: f32) -> f32 {
+        let low = bounds.min.y + size / 2.0;
+        let high = bounds.max.y - size / 2.0;
+        low + (high - low).max(0.0) * self.height.clamp(0.0, 1.0)
+    }
+}
+
+/// A run of balls for the ball machine, kept in a RON file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Drill {
+    pub name: String,
+    pub serves: Vec<DrillServe>,
+}
+
+impl Drill {
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::de::from_bytes(&fs::read(path)?)?)
+    }
+
+    /// Saves it to `path`, or to the drills folder named after it.
+    fn save(&self, path: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => drill_dir().ok_or("no data directory")?.join(format!(
+                "{}.{}",
+                file_stem(&self.name),
+                EXTENSION
+            )),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(
+            &path,
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        )?;
+        Ok(path)
+    }
+}
+
+fn drill_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(DRILL_DIR))
+}
+
+/// A file name for a drill called `name`, safe on any system.
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        "drill".to_string()
+    } else {
+        stem
+    }
+}
+
+/// The drills that come with the game.
+fn built_in() -> Vec<Drill> {
+    let ball = |height: f32, speed: f32, angle: f32, wait: f32| DrillServe {
+        height,
+        speed,
+        angle,
+        wait,
+    };
+    vec![
+        Drill {
+            name: "Corners".to_string(),
+            serves: [0.2, 0.8, 0.2, 0.8, 0.1, 0.9]
+                .into_iter()
+                .map(|height| ball(height, 1.0, if height < 0.5 { -15.0 } else { 15.0 }, 2.0))
+                .collect(),
+        },
+        Drill {
+            name: "Speed ladder".to_string(),
+            serves: [0.75, 1.0, 1.25, 1.5, 1.75, 2.0]
+                .into_iter()
+                .map(|speed| ball(0.5, speed, 0.0, 2.5))
+                .collect(),
+        },
+    ]
+}
+
+/// Every drill there is, with the file each of the players' own is in: the
+/// game's first, then theirs by name. One of theirs named the same as one
+/// of the game's takes its place.
+fn all_drills() -> Vec<(Option<PathBuf>, Drill)> {
+    let mut saved: Vec<(Option<PathBuf>, Drill)> = drill_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == EXTENSION)
+        })
+        .filter_map(|path| match Drill::load(&path) {
+            Ok(drill) => Some((Some(path), drill)),
+            Err(error) => {
+                warn!("Ignoring unreadable drill {}: {}", path.display(), error);
+                None
+            }
+        })
+        .collect();
+    saved.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+
+    let mut drills: Vec<_> = built_in()
+        .into_iter()
+        .map(
+            |drill| match saved.iter().position(|(_, saved)| saved.name == drill.name) {
+                Some(index) => saved.remove(index),
+                None => (None, drill),
+            },
+        )
+        .collect();
+    drills.extend(saved);
+    drills
+}
+
+/// How the runs of one drill have gone.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct DrillRecord {
+    /// Runs to the last ball
+    pub runs: u32,
+    /// Runs with every ball returned
+    pub completed: u32,
+    /// The most balls returned in a run
+    pub best: u32,
+}
+
+/// Every drill's record by its name, kept in a RON file in the user's data
+/// directory.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DrillStats {
+    pub records: BTreeMap<String, DrillRecord>,
+    /// The drill last run to the end, how many balls came back and how many
+    /// there were, for the drills screen
+    #[serde(skip)]
+    last_run: Option<(String, u32, usize)>,
+}
+
+impl DrillStats {
+    fn load() -> Self {
+        load_ron(STATS_FILE)
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        save_ron(STATS_FILE, self)
+    }
+
+    /// Counts a run of `drill` to the end, with `returned` balls coming back.
+    fn record(&mut self, drill: &Drill, returned: u32) {
+        let record = self.records.entry(drill.name.clone()).or_default();
+        record.runs += 1;
+        record.best = record.best.max(returned);
+        if returned as usize >= drill.serves.len() {
+            record.completed += 1;
+        }
+        self.last_run = Some((drill.name.clone(), returned, drill.serves.len()));
+    }
+}
+
+/// The drill the ball machine's running in practice, if it is, and how far
+/// it's got.
+#[derive(Resource, Default)]
+pub struct DrillRun {
+    pub drill: Option<Drill>,
+    /// Balls fired so far
+    pub fired: usize,
+    returned: u32,
+    missed: u32,
+}
+
+impl DrillRun {
+    /// The next ball to fire, if there's a drill with any left.
+    pub fn next_serve(&self) -> Option<DrillServe> {
+        self.drill
+            .as_ref()
+            .and_then(|drill| drill.serves.get(self.fired))
+            .copied()
+    }
+
+    /// Whether every ball of the drill has come back or got by.
+    fn is_done(&self) -> bool {
+        self.drill
+            .as_ref()
+            .is_some_and(|drill| (self.returned + self.missed) as usize >= drill.serves.len())
+    }
+}
+
+/// Keeps count of the balls of a drill that come back, in the machine's
+/// goal, and that get by, in the player's.
+fn count_drill_returns(mut goals: EventReader<GoalEvent>, mut run: ResMut<DrillRun>) {
+    for goal in goals.read() {
+        if run.drill.is_none() {
+            continue;
+        }
+        if goal.scored_by == 1 {
+            run.returned += 1;
+        } else {
+            run.missed += 1;
+        }
+    }
+}
+
+/// Back to the drills once the last ball's done with.
+fn finish_drill(run: Res<DrillRun>, mut state: ResMut<NextState<AppState>>) {
+    if run.is_done() {
+        state.set(AppState::Drills);
+    }
+}
+
+/// Records the drill if it was run to the end, and puts the machine back to
+/// firing at random for the next practice.
+fn end_drill(mut run: ResMut<DrillRun>, mut stats: ResMut<DrillStats>) {
+    if run.is_done() {
+        if let Some(drill) = &run.drill {
+            stats.record(drill, run.returned);
+            if let Err(error) = stats.save() {
+                warn!("Couldn't save drill stats: {}", error);
+            }
+        }
+    }
+    *run = DrillRun::default();
+}
+
+#[derive(Component)]
+struct PlayButton(Drill);
+
+#[derive(Component)]
+struct EditButton(Option<PathBuf>, Drill);
+
+#[derive(Component)]
+struct NewButton;
+
+#[derive(Component)]
+struct BackButton;
+
+/// What's said under a drill's name on the drills screen.
+fn record_line(drill: &Drill, record: Option<&DrillRecord>, strings: &Strings) -> String {
+    let balls = drill.serves.len();
+    match record {
+        Some(record) => strings.fill(
+            "drills.record",
+            &[
+                ("balls", &balls),
+                ("completed", &record.completed),
+                ("runs", &record.runs),
+                ("best", &record.best),
+            ],
+        ),
+        None => strings.fill("drills.untried", &[("balls", &balls)]),
+    }
+}
+
+fn spawn_drills(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    stats: Res<DrillStats>,
+) {
+    let drills = all_drills();
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Drills)))
+        .with_children(|parent| {
+            parent.spawn(text(&font, strings.get("drills.title"), 48.0, TEXT_COLOR));
+            if let Some((name, returned, balls)) = &stats.last_run {
+                parent.spawn(text(
+                    &font,
+                    strings.fill(
+                        "drills.last_run",
+                        &[("name", name), ("returned", returned), ("balls", balls)],
+                    ),
+                    22.0,
+                    ACCENT_COLOR,
+                ));
+            }
+
+            let mut index = 0;
+            for (path, drill) in drills.into_iter().take(LISTED_DRILLS) {
+                let line = record_line(&drill, stats.records.get(&drill.name), &strings);
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        spawn_button(parent, &font, index, &drill.name)
+                            .insert(PlayButton(drill.clone()));
+                        spawn_button(parent, &font, index + 1, strings.get("drills.edit"))
+                            .insert(EditButton(path, drill));
+                        parent.spawn(text(&font, line, 18.0, DIM_TEXT_COLOR));
+                    });
+                index += 2;
+            }
+
+            spawn_button(parent, &font, index, strings.get("drills.new")).insert(NewButton);
+            spawn_button(parent, &font, index + 1, strings.get("back")).insert(BackButton);
+            if let Some(dir) = drill_dir() {
+                parent.spawn(text(
+                    &font,
+                    strings.fill("drills.folder", &[("path", &dir.display())]),
+                    16.0,
+                    DIM_TEXT_COLOR,
+                ));
+            }
+        });
+}
+
+fn drills_action(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    strings: Res<Strings>,
+    play_buttons: Query<&PlayButton>,
+    edit_buttons: Query<&EditButton>,
+    new_buttons: Query<(), With<NewButton>>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut run: ResMut<DrillRun>,
+    mut editor: ResMut<DrillEditor>,
+    mut mode: ResMut<GameMode>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Menu);
+        return;
+    }
+
+    for event in activated.read() {
+        if let Ok(PlayButton(drill)) = play_buttons.get(event.0) {
+            if drill.serves.is_empty() {
+                continue;
+            }
+            *run = DrillRun {
+                drill: Some(drill.clone()),
+                ..default()
+            };
+            *mode = GameMode::Practice;
+            state.set(AppState::Playing);
+        } else if let Ok(EditButton(path, drill)) = edit_buttons.get(event.0) {
+            *editor = DrillEditor::new(path.clone(), drill.clone());
+            state.set(AppState::DrillEditor);
+        } else if new_buttons.get(event.0).is_ok() {
+            // Named for the first number no drill has yet
+            let names: Vec<String> = all_drills()
+                .into_iter()
+                .map(|(_, drill)| drill.name)
+                .collect();
+            let name = (1..)
+                .map(|number| strings.fill("drills.new_name", &[("number", &number)]))
+                .find(|name| !names.contains(name))
+                .unwrap_or_default();
+            let drill = Drill {
+                name,
+                serves: vec![DrillServe::default()],
+            };
+            *editor = DrillEditor::new(None, drill);
+            state.set(AppState::DrillEditor);
+        } else if back_buttons.get(event.0).is_ok() {
+            state.set(AppState::Menu);
+        } else {
+            continue;
+        }
+        return;
+    }
+}
+
+/// The drill being edited, and the ball of it.
+#[derive(Resource, Default)]
+struct DrillEditor {
+    /// The file it's in, if it's been saved
+    path: Option<PathBuf>,
+    drill: Drill,
+    ball: usize,
+}
+
+impl DrillEditor {
+    fn new(path: Option<PathBuf>, drill: Drill) -> Self {
+        DrillEditor {
+            path,
+            drill,
+            ball: 0,
+        }
+    }
+}
+
+/// A line of the editor, each changed with Left and Right or chosen with
+/// Enter.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum EditorLine {
+    Ball,
+    Height,
+    Speed,
+    Angle,
+    Wait,
+    Add,
+    Remove,
+    Save,
+    Back,
+}
+
+const EDITOR_LINES: [EditorLine; 9] = [
+    EditorLine::Ball,
+    EditorLine::Height,
+    EditorLine::Speed,
+    EditorLine::Angle,
+    EditorLine::Wait,
+    EditorLine::Add,
+    EditorLine::Remove,
+    EditorLine::Save,
+    EditorLine::Back,
+];
+
+/// Every ball of the drill, one to a line.
+#[derive(Component)]
+struct BallList;
+
+fn height_label(serve: &DrillServe) -> String {
+    format!("{:.0}%", serve.height * 100.0)
+}
+
+fn speed_label(serve: &DrillServe) -> String {
+    format!("{:.2}x", serve.speed)
+}
+
+fn angle_label(serve: &DrillServe) -> String {
+    format!("{:+.0}°", serve.angle)
+}
+
+fn wait_label(serve: &DrillServe) -> String {
+    format!("{:.1} s", serve.wait)
+}
+
+fn editor_label(line: EditorLine, editor: &DrillEditor, strings: &Strings) -> String {
+    let serve = editor
+        .drill
+        .serves
+        .get(editor.ball)
+        .copied()
+        .unwrap_or_default();
+    match line {
+        EditorLine::Ball => strings.fill(
+            "drills.ball",
+            &[
+                ("number", &(editor.ball + 1)),
+                ("count", &editor.drill.serves.len()),
+            ],
+        ),
+        EditorLine::Height => strings.fill("drills.height", &[("value", &height_label(&serve))]),
+        EditorLine::Speed => strings.fill("drills.speed", &[("value", &speed_label(&serve))]),
+        EditorLine::Angle => strings.fill("drills.angle", &[("value", &angle_label(&serve))]),
+        EditorLine::Wait => strings.fill("drills.wait", &[("value", &wait_label(&serve))]),
+        EditorLine::Add => strings.get("drills.add").to_string(),
+        EditorLine::Remove => strings.get("drills.remove").to_string(),
+        EditorLine::Save => strings.get("drills.save").to_string(),
+        EditorLine::Back => strings.get("back").to_string(),
+    }
+}
+
+fn ball_list(editor: &DrillEditor, strings: &Strings) -> Vec<(String, Color)> {
+    editor
+        .drill
+        .serves
+        .iter()
+        .enumerate()
+        .map(|(index, serve)| {
+            let line = strings.fill(
+                "drills.ball_line",
+                &[
+                    ("number", &(index + 1)),
+                    ("height", &height_label(serve)),
+                    ("speed", &speed_label(serve)),
+                    ("angle", &angle_label(serve)),
+                    ("wait", &wait_label(serve)),
+                ],
+            );
+            let color = if index == editor.ball {
+                ACCENT_COLOR
+            } else {
+                DIM_TEXT_COLOR
+            };
+            (line, color)
+        })
+        .collect()
+}
+
+fn spawn_editor(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    editor: Res<DrillEditor>,
+) {
+    commands
+        .spawn((screen_root(), StateScoped(AppState::DrillEditor)))
+        .with_children(|parent| {
+            parent.spawn(text(
+                &font,
+                strings.fill("drills.editing", &[("name", &editor.drill.name)]),
+                40.0,
+                TEXT_COLOR,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexStart,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                margin: UiRect::all(Val::Px(10.0)),
+                                ..default()
+                            },
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (index, line) in EDITOR_LINES.into_iter().enumerate() {
+                                let label = editor_label(line, &editor, &strings);
+                                spawn_button(parent, &font, index, &label).insert(line);
+                            }
+                        });
+
+                    let sections = ball_list(&editor, &strings)
+                        .into_iter()
+                        .map(|(line, color)| {
+                            TextSection::new(format!("{}\n", line), text_style(&font, 18.0, color))
+                        });
+                    parent.spawn((
+                        TextBundle::from_sections(sections).with_style(Style {
+                            margin: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        }),
+                        BallList,
+                    ));
+                });
+            parent.spawn(text(
+                &font,
+                strings.get("drills.hint"),
+                18.0,
+                DIM_TEXT_COLOR,
+            ));
+        });
+}
+
+/// `value` moved `step` places of `by` between `min` and `max`, or up one for
+/// a `step` of 0, going round to `min` past `max`.
+fn nudge(value: f32, step: f32, by: f32, min: f32, max: f32) -> f32 {
+    // Counted in whole steps, or tenths would add up to just over a whole
+    let at = ((value - min) / by).round();
+    let steps = ((max - min) / by).round();
+    let next = if step == 0.0 {
+        (at + 1.0) % (steps + 1.0)
+    } else {
+        (at + step).clamp(0.0, steps)
+    };
+    min + next * by
+}
+
+fn edit_drill(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    focus: Res<MenuFocus>,
+    mut activated: EventReader<ButtonActivated>,
+    lines: Query<(&MenuButton, &EditorLine)>,
+    mut editor: ResMut<DrillEditor>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Drills);
+        return;
+    }
+
+    let step: f32 = if keyboard_input.just_pressed(KeyCode::ArrowLeft)
+        || keyboard_input.just_pressed(KeyCode::KeyA)
+    {
+        -1.0
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight)
+        || keyboard_input.just_pressed(KeyCode::KeyD)
+    {
+        1.0
+    } else {
+        0.0
+    };
+    let focused = lines
+        .iter()
+        .find(|(button, _)| button.0 == focus.0)
+        .map(|(_, line)| (*line, step))
+        .filter(|_| step != 0.0);
+    let chosen = activated
+        .read()
+        .filter_map(|event| lines.get(event.0).ok())
+        .map(|(_, line)| (*line, 0.0))
+        .last();
+    let (line, step) = match focused.or(chosen) {
+        Some(edit) => edit,
+        None => return,
+    };
+
+    let editor = &mut *editor;
+    let count = editor.drill.serves.len();
+    match line {
+        EditorLine::Ball if count > 0 => {
+            editor.ball = nudge(editor.ball as f32, step, 1.0, 0.0, (count - 1) as f32) as usize;
+        }
+        EditorLine::Add if count < MAX_BALLS => {
+            // A copy of the ball being edited, right after it
+            let serve = editor
+                .drill
+                .serves
+                .get(editor.ball)
+                .copied()
+                .unwrap_or_default();
+            let at = (editor.ball + 1).min(count);
+            editor.drill.serves.insert(at, serve);
+            editor.ball = at;
+        }
+        // A drill needs a ball at least
+        EditorLine::Remove if count > 1 => {
+            editor.drill.serves.remove(editor.ball);
+            editor.ball = editor.ball.min(count - 2);
+        }
+        EditorLine::Save => {
+            match editor.drill.save(editor.path.as_deref()) {
+                Ok(path) => {
+                    info!("Saved the drill to {}", path.display());
+                    editor.path = Some(path);
+                }
+                Err(error) => warn!("Couldn't save the drill: {}", error),
+            }
+            state.set(AppState::Drills);
+        }
+        EditorLine::Back => {
+            state.set(AppState::Drills);
+        }
+        _ => {
+            let serve = match editor.drill.serves.get_mut(editor.ball) {
+                Some(serve) => serve,
+                None => return,
+            };
+            match line {
+                EditorLine::Height => {
+                    serve.height = nudge(serve.height, step, HEIGHT_STEP, 0.0, 1.0);
+                }
+                EditorLine::Speed => {
+                    serve.speed = nudge(serve.speed, step, SPEED_STEP, MIN_SPEED, MAX_SPEED);
+                }
+                EditorLine::Angle => {
+                    serve.angle = nudge(serve.angle, step, ANGLE_STEP, -MAX_ANGLE, MAX_ANGLE);
+                }
+                EditorLine::Wait => {
+                    serve.wait = nudge(serve.wait, step, WAIT_STEP, MIN_WAIT, MAX_WAIT);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn update_editor(
+    font: Res<UiFont>,
+    editor: Res<DrillEditor>,
+    strings: Res<Strings>,
+    lines: Query<(&EditorLine, &Children)>,
+    mut texts: Query<&mut Text, Without<BallList>>,
+    mut lists: Query<&mut Text, With<BallList>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    for (line, children) in lines.iter() {
+        let label = editor_label(*line, &editor, &strings);
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+    let balls = ball_list(&editor, &strings);
+    for mut list in lists.iter_mut() {
+        list.sections.truncate(balls.len());
+        for (index, (line, color)) in balls.iter().enumerate() {
+            let value = format!("{}\n", line);
+            match list.sections.get_mut(index) {
+                Some(section) => {
+                    section.value = value;
+                    section.style.color = *color;
+                }
+                None => list
+                    .sections
+                    .push(TextSection::new(value, text_style(&font, 18.0, *color))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nudging_keeps_to_whole_steps() {
+        assert_eq!(nudge(0.5, 1.0, 0.1, 0.0, 1.0), 0.6);
+        assert_eq!(nudge(1.0, 1.0, 0.1, 0.0, 1.0), 1.0);
+        // Choosing it goes round
+        assert_eq!(nudge(45.0, 0.0, 5.0, -45.0, 45.0), -45.0);
+        assert_eq!(nudge(0.5, -1.0, 0.5, 0.5, 5.0), 0.5);
+    }
+
+    #[test]
+    fn drill_names_make_safe_file_names() {
+        assert_eq!(file_stem("Speed ladder"), "speed-ladder");
+        assert_eq!(file_stem("../up"), "---up");
+        assert_eq!(file_stem("  "), "drill");
+    }
+
+    #[test]
+    fn only_whole_runs_of_every_ball_complete_a_drill() {
+        let drill = &built_in()[0];
+        let balls = drill.serves.len() as u32;
+        let mut stats = DrillStats::default();
+        stats.record(drill, balls - 1);
+        stats.record(drill, balls);
+        let record = stats.records[&drill.name];
+        assert_eq!(record.runs, 2);
+        assert_eq!(record.completed, 1);
+        assert_eq!(record.best, balls);
+    }
+
+    #[test]
+    fn drills_round_trip_through_ron() {
+        let drill = built_in().remove(1);
+        let text = ron::ser::to_string(&drill).unwrap();
+        assert_eq!(ron::de::from_str::<Drill>(&text).unwrap(), drill);
+    }
+}
@@ -0,0 +1,201 @@
+//! Particles: sparks off the rackets, dust off the walls and a burst for goals.
+//!
+//! Purely visual, so unlike the simulation this runs on frame time and keeps a
+//! random generator of its own.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::{
+    settings::Settings,
+    sim::{CollisionEvent, GoalEvent, SimRng, Surface},
+    stats,
+    theme::{EffectPaints, Theme},
+    AppState, MatchState,
+};
+
+/// How a batch of particles flies out, in the color the theme gives it.
+struct Burst {
+    count: usize,
+    /// Angle either side of the direction the particles head off in
+    spread: f32,
+    speed: (f32, f32),
+    size: f32,
+    lifetime: f32,
+}
+
+const SPARKS: Burst = Burst {
+    count: 12,
+    spread: 35.0 * PI / 180.0,
+    speed: (150.0, 400.0),
+    size: 5.0,
+    lifetime: 0.35,
+};
+
+const DUST: Burst = Burst {
+    count: 8,
+    spread: 70.0 * PI / 180.0,
+    speed: (30.0, 110.0),
+    size: 7.0,
+    lifetime: 0.6,
+};
+
+const GOAL_BURST: Burst = Burst {
+    count: 60,
+    spread: PI,
+    speed: (100.0, 600.0),
+    size: 8.0,
+    lifetime: 1.0,
+};
+
+// Reduced effects keep this share of every burst
+const REDUCED_SHARE: usize = 4;
+// Fraction of a particle's velocity kept per second
+const DRAG: f32 = 0.05;
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EffectsRng(SimRng::new(stats::now())))
+            .add_systems(
+                Update,
+                (
+                    emit_collision_particles,
+                    emit_goal_particles,
+                    update_particles,
+                )
+                    .run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct EffectsRng(SimRng);
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    size: f32,
+    color: Color,
+    age: Timer,
+}
+
+/// The theme's, or the classic ones before it's loaded.
+fn effect_paints(theme: Option<Res<Theme>>) -> EffectPaints {
+    theme.map(|theme| theme.effects.clone()).unwrap_or_default()
+}
+
+fn emit(
+    commands: &mut Commands,
+    rng: &mut SimRng,
+    settings: &Settings,
+    burst: &Burst,
+    color: Color,
+    position: Vec2,
+    direction: f32,
+) {
+    let count = if settings.reduced_motion {
+        0
+    } else if settings.effects_reduced() {
+        burst.count / REDUCED_SHARE
+    } else {
+        burst.count
+    };
+
+    for _ in 0..count {
+        let angle = direction + rng.range(-burst.spread, burst.spread);
+        let speed = rng.range(burst.speed.0, burst.speed.1);
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform {
+                    translation: position.extend(2.0),
+                    scale: Vec3::new(burst.size, burst.size, 1.0),
+                    ..default()
+                },
+                sprite: Sprite { color, ..default() },
+                ..default()
+            },
+            Particle {
+                velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+                size: burst.size,
+                color,
+                age: Timer::from_seconds(burst.lifetime, TimerMode::Once),
+            },
+            StateScoped(AppState::Playing),
+        ));
+    }
+}
+
+fn emit_collision_particles(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    mut rng: ResMut<EffectsRng>,
+    settings: Res<Settings>,
+    theme: Option<Res<Theme>>,
+) {
+    let paints = effect_paints(theme);
+    for collision in collisions.read() {
+        let (burst, color, skip_when_reduced) = match collision.surface {
+            Surface::Racket => (&SPARKS, paints.sparks, false),
+            Surface::Wall => (&DUST, paints.dust, true),
+        };
+        if skip_when_reduced && settings.effects_reduced() {
+            continue;
+        }
+        // Along the ball's way out of the bounce
+        let direction = collision.velocity.y.atan2(collision.velocity.x);
+        emit(
+            &mut commands,
+            &mut rng.0,
+            &settings,
+            burst,
+            color,
+            collision.position,
+            direction,
+        );
+    }
+}
+
+fn emit_goal_particles(
+    mut commands: Commands,
+    mut goals: EventReader<GoalEvent>,
+    mut rng: ResMut<EffectsRng>,
+    settings: Res<Settings>,
+    theme: Option<Res<Theme>>,
+) {
+    let color = effect_paints(theme).goal_burst;
+    for goal in goals.read() {
+        emit(
+            &mut commands,
+            &mut rng.0,
+            &settings,
+            &GOAL_BURST,
+            color,
+            goal.position,
+            0.0,
+        );
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let drag = DRAG.powf(time.delta_seconds());
+    for (entity, mut particle, mut transform, mut sprite) in particles.iter_mut() {
+        particle.age.tick(time.delta());
+        if particle.age.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity *= drag;
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+        let left = 1.0 - particle.age.fraction();
+        transform.scale = Vec3::new(particle.size * left, particle.size * left, 1.0);
+        sprite.color = particle.color.with_alpha(particle.color.alpha() * left);
+    }
+}
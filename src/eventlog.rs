@@ -0,0 +1,378 @@
+//! Everything that's happened in the game, one line at a time, for working out
+//! how the rules got to where they did: every serve, hit, bounce off a wall,
+//! goal and call, the screens changing, the modifiers a match is played with,
+//! portals opening and closing, and what's riding on each point as it
+//! changes, deuce and golden points included. F8 opens and closes it.
+//!
+//! The log keeps the last few thousand lines, stamped with the seconds since
+//! the game started, and the panel writes the lot to a text file in the
+//! `exports` folder.
+
+use std::{collections::VecDeque, fmt::Write as _, fs, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    sim::{
+        BallHitEvent, CollisionEvent, GoalEvent, MatchEndedEvent, MatchRules, Portals, Score,
+        ServeCall, ServeCallEvent, ServeEvent, Stakes, Surface,
+    },
+    start_match,
+    stats::{data_dir, now},
+    AppState, MatchState,
+};
+
+const EXPORT_DIR: &str = "exports";
+// Lines kept before the oldest go
+const MAX_ENTRIES: usize = 5000;
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EventLog>()
+            .add_systems(OnEnter(AppState::Playing), log_rules.after(start_match))
+            .add_systems(Update, log_states)
+            .add_systems(Update, log_events)
+            .add_systems(Update, log_match.run_if(in_state(MatchState::Running)))
+            .add_systems(Update, toggle_log)
+            .add_systems(
+                Update,
+                draw_log
+                    .after(toggle_log)
+                    .after(log_states)
+                    .after(log_events)
+                    .after(log_match),
+            );
+    }
+}
+
+/// A line of the log.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    /// Seconds since the game started
+    at: f64,
+    text: String,
+}
+
+impl Entry {
+    fn line(&self) -> String {
+        let minutes = (self.at / 60.0).floor();
+        format!(
+            "{:02}:{:06.3}  {}",
+            minutes,
+            self.at - minutes * 60.0,
+            self.text
+        )
+    }
+}
+
+#[derive(Resource, Default)]
+struct EventLog {
+    entries: VecDeque<Entry>,
+    shown: bool,
+    /// How the last export went
+    status: Option<String>,
+    // What was last seen of the things that are logged when they change
+    state: Option<AppState>,
+    stakes: Option<Stakes>,
+    golden_point: bool,
+    portals_open: bool,
+}
+
+impl EventLog {
+    fn push(&mut self, at: f64, text: impl Into<String>) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            at,
+            text: text.into(),
+        });
+    }
+}
+
+/// Writes every line of the log to a file in the exports folder.
+fn export(entries: &VecDeque<Entry>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = data_dir().ok_or("no data directory")?.join(EXPORT_DIR);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("events-{}.log", now()));
+    let mut text = String::new();
+    for entry in entries {
+        writeln!(text, "{}", entry.line())?;
+    }
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+fn describe_score(score: &Score) -> String {
+    format!(
+        "{}-{}, sets {}-{}",
+        score.player1, score.player2, score.player1_sets, score.player2_sets
+    )
+}
+
+/// The rules a match starts with, and whatever it's playing with on top.
+fn log_rules(time: Res<Time>, rules: Res<MatchRules>, mut log: ResMut<EventLog>) {
+    let mut modifiers = Vec::new();
+    if let Some(at) = rules.golden_point {
+        modifiers.push(format!("golden point at {}-{}", at, at));
+    }
+    if let Some(seconds) = rules.time_limit {
+        modifiers.push(format!("{} s time limit", seconds));
+    }
+    if let Some(lives) = rules.lives {
+        modifiers.push(format!("{} lives", lives));
+    }
+    if let Some(gravity) = rules.gravity {
+        modifiers.push(format!("gravity {}", gravity));
+    }
+    for (on, name) in [
+        (rules.wind, "wind"),
+        (rules.portals, "portals"),
+        (rules.night, "night"),
+        (rules.moving_wall.is_some(), "moving wall"),
+        (rules.center_net, "center net"),
+        (rules.shrinking, "shrinking rackets"),
+        (rules.alternate_hits, "alternate hits"),
+        (rules.serve_rules, "serve rules"),
+    ] {
+        if on {
+            modifiers.push(name.to_string());
+        }
+    }
+
+    let at = time.elapsed_seconds_f64();
+    log.push(
+        at,
+        format!(
+            "Match started: first to {} by {}, best of {}",
+            rules.points_to_win, rules.win_by, rules.best_of
+        ),
+    );
+    if !modifiers.is_empty() {
+        log.push(at, format!("Modifiers: {}", modifiers.join(", ")));
+    }
+    log.stakes = None;
+    log.golden_point = false;
+    log.portals_open = false;
+}
+
+fn log_states(time: Res<Time>, state: Res<State<AppState>>, mut log: ResMut<EventLog>) {
+    let current = *state.get();
+    if log.state.as_ref() == Some(&current) {
+        return;
+    }
+    let text = match &log.state {
+        Some(previous) => format!("State: {:?} -> {:?}", previous, current),
+        None => format!("State: {:?}", current),
+    };
+    log.push(time.elapsed_seconds_f64(), text);
+    log.state = Some(current);
+}
+
+/// The simulation's events, from whichever steps ran this frame.
+fn log_events(
+    time: Res<Time>,
+    score: Res<Score>,
+    mut log: ResMut<EventLog>,
+    mut serves: EventReader<ServeEvent>,
+    mut hits: EventReader<BallHitEvent>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut calls: EventReader<ServeCallEvent>,
+    mut ended: EventReader<MatchEndedEvent>,
+) {
+    let at = time.elapsed_seconds_f64();
+    for serve in serves.read() {
+        log.push(
+            at,
+            format!(
+                "Serve from {:.0}, {:.0} at {:.0} px/s",
+                serve.position.x,
+                serve.position.y,
+                serve.velocity.length()
+            ),
+        );
+    }
+    for hit in hits.read() {
+        let smash = if hit.smash > 0.0 {
+            format!(", smash {:.2}", hit.smash)
+        } else {
+            String::new()
+        };
+        log.push(
+            at,
+            format!(
+                "Hit by player {} at {:.0}, {:.0}, {:+.2} along the racket, {:.0} px/s{}",
+                hit.player_number,
+                hit.position.x,
+                hit.position.y,
+                hit.along,
+                hit.velocity.length(),
+                smash
+            ),
+        );
+    }
+    // Racket bounces are the hits above
+    for collision in collisions.read() {
+        if collision.surface == Surface::Wall {
+            log.push(
+                at,
+                format!(
+                    "Wall at {:.0}, {:.0}",
+                    collision.position.x, collision.position.y
+                ),
+            );
+        }
+    }
+    for call in calls.read() {
+        let call_name = match call.call {
+            ServeCall::Fault => "Fault",
+            ServeCall::Let => "Let",
+        };
+        log.push(
+            at,
+            format!("{} on player {}'s serve", call_name, call.server),
+        );
+    }
+    for goal in goals.read() {
+        log.push(
+            at,
+            format!(
+                "Goal for player {} at {:.0}, {:.0}, now {}",
+                goal.scored_by,
+                goal.position.x,
+                goal.position.y,
+                describe_score(&score)
+            ),
+        );
+    }
+    for ended in ended.read() {
+        log.push(
+            at,
+            format!(
+                "Match won by player {}, {}",
+                ended.winner,
+                describe_score(&score)
+            ),
+        );
+    }
+}
+
+/// What changes over a match without an event of its own.
+fn log_match(
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    portals: Res<Portals>,
+    mut log: ResMut<EventLog>,
+) {
+    let at = time.elapsed_seconds_f64();
+
+    let stakes = rules.stakes(&score);
+    if stakes != log.stakes {
+        let text = match stakes {
+            Some(Stakes::Deuce) => "Deuce",
+            Some(Stakes::SetPoint) => "Set point",
+            Some(Stakes::MatchPoint) => "Match point",
+            None => "Nothing riding on the point",
+        };
+        log.push(at, format!("{} at {}", text, describe_score(&score)));
+        log.stakes = stakes;
+    }
+
+    if score.golden_point != log.golden_point {
+        if score.golden_point {
+            log.push(at, format!("Golden point at {}", describe_score(&score)));
+        }
+        log.golden_point = score.golden_point;
+    }
+
+    let portals_open = rules.portals && portals.openness(rules.time_step) > 0.0;
+    if portals_open != log.portals_open {
+        let text = if portals_open {
+            format!(
+                "Portals opening at {:.0}, {:.0} and {:.0}, {:.0}",
+                portals.ends[0].x, portals.ends[0].y, portals.ends[1].x, portals.ends[1].y
+            )
+        } else {
+            "Portals closed".to_string()
+        };
+        log.push(at, text);
+        log.portals_open = portals_open;
+    }
+}
+
+fn toggle_log(keyboard_input: Res<ButtonInput<KeyCode>>, mut log: ResMut<EventLog>) {
+    if keyboard_input.just_pressed(KeyCode::F8) {
+        log.shown = !log.shown;
+    }
+}
+
+fn draw_log(mut egui_context: EguiContexts, mut log: ResMut<EventLog>) {
+    if !log.shown {
+        return;
+    }
+    let log = &mut *log;
+    egui::Window::new("Event log")
+        .open(&mut log.shown)
+        .default_size(egui::vec2(560.0, 360.0))
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Export").clicked() {
+                    log.status = Some(match export(&log.entries) {
+                        Ok(path) => format!("Exported to {}", path.display()),
+                        Err(error) => {
+                            warn!("Couldn't export the event log: {}", error);
+                            format!("Couldn't export: {}", error)
+                        }
+                    });
+                }
+                if ui.button("Clear").clicked() {
+                    log.entries.clear();
+                    log.status = None;
+                }
+                ui.label(format!("{} lines", log.entries.len()));
+            });
+            if let Some(status) = &log.status {
+                ui.label(status);
+            }
+            ui.separator();
+            // Only the lines scrolled to are laid out, as there can be thousands
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show_rows(ui, row_height, log.entries.len(), |ui, rows| {
+                    for entry in log.entries.range(rows) {
+                        ui.monospace(entry.line());
+                    }
+                });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_stamped_in_minutes_and_seconds() {
+        let entry = Entry {
+            at: 75.5,
+            text: "Deuce".to_string(),
+        };
+        assert_eq!(entry.line(), "01:15.500  Deuce");
+    }
+
+    #[test]
+    fn oldest_lines_go_first() {
+        let mut log = EventLog::default();
+        for index in 0..MAX_ENTRIES + 2 {
+            log.push(index as f64, index.to_string());
+        }
+        assert_eq!(log.entries.len(), MAX_ENTRIES);
+        assert_eq!(log.entries[0].text, "2");
+    }
+}
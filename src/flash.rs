@@ -0,0 +1,137 @@
+//! Sprites lighting up for a moment and fading back to their own color. Give
+//! anything with a sprite a [`FlashColor`] to flash it, like a racket gets
+//! whenever the ball hits it, and a goal whenever it's scored in.
+
+use bevy::prelude::*;
+
+use crate::{
+    settings::Settings,
+    sim::{CollisionEvent, Goal, Netted, Racket, Surface},
+    theme::Theme,
+    tween::mix,
+};
+
+// How many seconds a racket flashes for when the ball hits it, in the theme's
+// color for it
+const HIT_FLASH_TIME: f32 = 0.15;
+// Same for a goal that's just been scored in
+const GOAL_FLASH_TIME: f32 = 1.0;
+// With reduced motion, flashes only go this share of the way to their color,
+// enough to see what happened without lighting up the screen
+const GENTLE_FLASH: f32 = 0.3;
+
+pub struct FlashPlugin;
+
+impl Plugin for FlashPlugin {
+    fn build(&self, app: &mut App) {
+        // Flashes that are over are taken off before new ones go on, or a
+        // new one could be taken off along with the old one
+        app.add_systems(Update, fade_flashes)
+            .add_systems(Update, flash_rackets.after(fade_flashes))
+            .add_systems(Update, flash_goals.after(fade_flashes));
+    }
+}
+
+/// Tints the sprite it's on with `color`, fading back to the sprite's own.
+#[derive(Component)]
+pub struct FlashColor {
+    color: Color,
+    timer: Timer,
+    /// The sprite's own color, once the flash has started
+    base: Option<Color>,
+}
+
+impl FlashColor {
+    pub fn new(color: Color, seconds: f32) -> Self {
+        FlashColor {
+            color,
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+            base: None,
+        }
+    }
+
+    /// Flashes `flashing` with `color`. Flashing it again before it's done
+    /// starts over, still fading back to its own color.
+    pub fn start(
+        commands: &mut Commands,
+        flashing: Entity,
+        current: Option<&FlashColor>,
+        color: Color,
+        seconds: f32,
+    ) {
+        let mut flash = FlashColor::new(color, seconds);
+        flash.base = current.and_then(|current| current.base);
+        commands.entity(flashing).insert(flash);
+    }
+}
+
+fn fade_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut flashing: Query<(Entity, &mut Sprite, &mut FlashColor)>,
+) {
+    for (entity, mut sprite, mut flash) in flashing.iter_mut() {
+        let base = *flash.base.get_or_insert(sprite.color);
+        flash.timer.tick(time.delta());
+        let peak = if settings.reduced_motion {
+            mix(base, flash.color, GENTLE_FLASH)
+        } else {
+            flash.color
+        };
+        sprite.color = mix(peak, base, flash.timer.fraction());
+        if flash.timer.finished() {
+            commands.entity(entity).remove::<FlashColor>();
+        }
+    }
+}
+
+fn flash_rackets(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    theme: Option<Res<Theme>>,
+    rackets: Query<(Entity, &Transform, Option<&FlashColor>), With<Racket>>,
+) {
+    let paints = theme.map(|theme| theme.effects.clone()).unwrap_or_default();
+    for collision in collisions.read() {
+        if collision.surface != Surface::Racket {
+            continue;
+        }
+        // The event doesn't say which racket it was, but it's the one the
+        // ball is touching
+        let hit = rackets.iter().min_by(|(_, a, _), (_, b, _)| {
+            let a = a.translation.truncate().distance(collision.position);
+            let b = b.translation.truncate().distance(collision.position);
+            a.total_cmp(&b)
+        });
+        if let Some((racket, _, current)) = hit {
+            FlashColor::start(
+                &mut commands,
+                racket,
+                current,
+                paints.hit_flash,
+                HIT_FLASH_TIME,
+            );
+        }
+    }
+}
+
+fn flash_goals(
+    mut commands: Commands,
+    netted: Query<&Netted, Added<Netted>>,
+    theme: Option<Res<Theme>>,
+    goals: Query<Option<&FlashColor>, With<Goal>>,
+) {
+    let paints = theme.map(|theme| theme.effects.clone()).unwrap_or_default();
+    for netted in netted.iter() {
+        if let Ok(current) = goals.get(netted.goal) {
+            FlashColor::start(
+                &mut commands,
+                netted.goal,
+                current,
+                paints.goal_flash,
+                GOAL_FLASH_TIME,
+            );
+        }
+    }
+}
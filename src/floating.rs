@@ -0,0 +1,209 @@
+//! Text floating up off the court and fading away: "+1" where a point's
+//! scored, "MATCH POINT" when there's one, "FAULT" or "LET" where a serve's
+//! called, and every so often how long the rally's got. Anything else can
+//! have its say the same way, by sending a [`SpawnFloatingText`].
+//!
+//! Purely visual, and on frame time. Rollback can send the simulation's
+//! events again, so each point, call and rally length is only said once.
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::ArenaBounds,
+    locale::Strings,
+    settings::Settings,
+    sim::{BallHitEvent, GoalEvent, MatchRules, Rally, Score, ServeCall, ServeCallEvent},
+    tween::{Ease, Tween},
+    ui::{UiFont, ACCENT_COLOR, TEXT_COLOR},
+    AppState, MatchState,
+};
+
+pub const SMALL_TEXT: f32 = 28.0;
+pub const BIG_TEXT: f32 = 56.0;
+
+// Seconds it's up for, the last of them fading away
+const LIFETIME: f32 = 1.2;
+const FADE_TIME: f32 = 0.5;
+// Pixels it floats up by in that time
+const RISE: f32 = 60.0;
+// Over everything on the court, even the dark of a night match
+const FLOATING_Z: f32 = 60.0;
+// How far in from the ends of the court a point's "+1" goes up, so it's all
+// on screen
+const GOAL_INSET: f32 = 40.0;
+// Every this many hits, the rally's length goes up
+const RALLY_MILESTONE: u32 = 10;
+// How far above where a serve's called the call goes up, clear of a fault's
+// "+1"
+const CALL_RISE: f32 = 40.0;
+
+pub struct FloatingTextPlugin;
+
+impl Plugin for FloatingTextPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnFloatingText>()
+            .add_systems(Update, spawn_floating_text)
+            .add_systems(
+                Update,
+                (
+                    float_points.before(spawn_floating_text),
+                    float_calls.before(spawn_floating_text),
+                    float_rallies.before(spawn_floating_text),
+                )
+                    .run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Sent to have `text` float up off the court from `position`.
+#[derive(Event, Debug, Clone)]
+pub struct SpawnFloatingText {
+    pub text: String,
+    pub position: Vec2,
+    pub color: Color,
+    /// Its font size, like [`SMALL_TEXT`] or [`BIG_TEXT`]
+    pub size: f32,
+}
+
+fn spawn_floating_text(
+    mut commands: Commands,
+    mut events: EventReader<SpawnFloatingText>,
+    font: Res<UiFont>,
+    settings: Res<Settings>,
+) {
+    for event in events.read() {
+        let start = event.position.extend(FLOATING_Z);
+        // With less motion it stays where it is and only fades
+        let rise = if settings.reduced_motion { 0.0 } else { RISE };
+        let color = event.color;
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    event.text.clone(),
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: event.size,
+                        color,
+                    },
+                )
+                .with_justify(JustifyText::Center),
+                transform: Transform::from_translation(start),
+                ..default()
+            },
+            Tween::new(
+                LIFETIME,
+                Ease::Out,
+                move |transform: &mut Transform, along| {
+                    transform.translation = start + Vec3::Y * rise * along;
+                },
+            ),
+            Tween::new(FADE_TIME, Ease::Linear, move |text: &mut Text, along| {
+                text.sections[0]
+                    .style
+                    .color
+                    .set_alpha(color.alpha() * (1.0 - along));
+            })
+            .after(LIFETIME - FADE_TIME)
+            .then_despawn(),
+            StateScoped(AppState::Playing),
+        ));
+    }
+}
+
+/// "+1" by the goal a point went in, and "MATCH POINT" in the middle of the
+/// court if that's what it's come to.
+fn float_points(
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    bounds: Res<ArenaBounds>,
+    strings: Res<Strings>,
+    mut goals: EventReader<GoalEvent>,
+    mut floating: EventWriter<SpawnFloatingText>,
+    mut floated: Local<Option<Score>>,
+) {
+    let goal = match goals.read().last() {
+        Some(goal) => *goal,
+        None => return,
+    };
+    if *floated == Some(*score) {
+        return;
+    }
+    *floated = Some(*score);
+
+    let x = goal
+        .position
+        .x
+        .clamp(bounds.min.x + GOAL_INSET, bounds.max.x - GOAL_INSET);
+    floating.send(SpawnFloatingText {
+        text: strings.get("floating.point").to_string(),
+        position: Vec2::new(x, goal.position.y),
+        color: TEXT_COLOR,
+        size: SMALL_TEXT,
+    });
+    if rules.is_match_point(&score) {
+        floating.send(SpawnFloatingText {
+            text: strings.get("floating.match_point").to_string(),
+            position: (bounds.min + bounds.max) / 2.0,
+            color: ACCENT_COLOR,
+            size: BIG_TEXT,
+        });
+    }
+}
+
+/// "FAULT" or "LET" where a serve's been called. The same call in the same
+/// spot is the same call sent again.
+fn float_calls(
+    strings: Res<Strings>,
+    mut calls: EventReader<ServeCallEvent>,
+    mut floating: EventWriter<SpawnFloatingText>,
+    mut floated: Local<Option<(ServeCall, Vec2)>>,
+) {
+    for call in calls.read() {
+        if *floated == Some((call.call, call.position)) {
+            continue;
+        }
+        *floated = Some((call.call, call.position));
+
+        let key = match call.call {
+            ServeCall::Fault => "floating.fault",
+            ServeCall::Let => "floating.let",
+        };
+        floating.send(SpawnFloatingText {
+            text: strings.get(key).to_string(),
+            position: call.position + Vec2::Y * CALL_RISE,
+            color: ACCENT_COLOR,
+            size: BIG_TEXT,
+        });
+    }
+}
+
+/// The rally's length, every [`RALLY_MILESTONE`] hits, from the racket that
+/// got it there.
+fn float_rallies(
+    rally: Res<Rally>,
+    strings: Res<Strings>,
+    mut hits: EventReader<BallHitEvent>,
+    mut floating: EventWriter<SpawnFloatingText>,
+    mut floated: Local<u32>,
+) {
+    // A new rally, with its milestones all to come
+    if rally.hits < *floated {
+        *floated = 0;
+    }
+    let hit = match hits.read().last() {
+        Some(hit) => *hit,
+        None => return,
+    };
+    let milestone = rally.hits - rally.hits % RALLY_MILESTONE;
+    if milestone == 0 || milestone == *floated {
+        return;
+    }
+    *floated = milestone;
+
+    floating.send(SpawnFloatingText {
+        text: strings.fill("floating.rally", &[("count", &milestone)]),
+        position: hit.position,
+        color: ACCENT_COLOR,
+        size: SMALL_TEXT,
+    });
+}
@@ -0,0 +1,80 @@
+//! Freezing a local match to go through it one step of the simulation at a
+//! time, for seeing what happens in collisions too quick to follow, like the
+//! ball catching the corner of a racket. F5 freezes and unfreezes it, and F6
+//! plays a single step while it's frozen.
+//!
+//! Only the simulation stops. Everything drawn over it keeps up, the overlay
+//! included, and so does the ball machine's clock in practice.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{sim::SimSystems, SteppedLocally};
+
+pub struct FrameStepPlugin;
+
+impl Plugin for FrameStepPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FrameStep>()
+            .add_systems(Update, control_frame_step)
+            .add_systems(Update, show_frozen.after(control_frame_step))
+            .add_systems(
+                FixedUpdate,
+                use_up_step.in_set(SteppedLocally).before(SimSystems),
+            );
+    }
+}
+
+/// Whether the simulation is frozen, and how many steps it's been let have.
+#[derive(Resource, Default)]
+pub struct FrameStep {
+    frozen: bool,
+    steps: u32,
+    /// Steps played since it was frozen
+    played: u32,
+}
+
+impl FrameStep {
+    /// Whether the simulation gets to play its next step.
+    pub fn allows_step(&self) -> bool {
+        !self.frozen || self.steps > 0
+    }
+}
+
+/// Uses up a step the simulation's been let have while it's frozen, as it
+/// plays it.
+fn use_up_step(mut frame_step: ResMut<FrameStep>) {
+    if frame_step.frozen {
+        frame_step.steps -= 1;
+        frame_step.played += 1;
+    }
+}
+
+fn control_frame_step(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut frame_step: ResMut<FrameStep>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        *frame_step = FrameStep {
+            frozen: !frame_step.frozen,
+            ..default()
+        };
+    }
+    if frame_step.frozen && keyboard_input.just_pressed(KeyCode::F6) {
+        frame_step.steps += 1;
+    }
+}
+
+fn show_frozen(mut egui_context: EguiContexts, frame_step: Res<FrameStep>) {
+    if !frame_step.frozen {
+        return;
+    }
+    egui::Area::new(egui::Id::new("frozen"))
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -16.0))
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.monospace(format!(
+                "Frozen, {} steps on. F6 steps, F5 carries on",
+                frame_step.played
+            ));
+        });
+}
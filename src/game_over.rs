@@ -0,0 +1,373 @@
+//! The screen after a match: who won, the match's stats and where on the court
+//! the ball got to, see [`heatmap`](crate::heatmap), and a way to save them,
+//! or a replay of the whole match, see [`recording`](crate::recording).
+//! After a survival run, the table of longest rallies instead.
+//!
+//! Matches that aren't part of something bigger can be played again straight
+//! from here, by the same rules and between the same players, without going
+//! back to the menu. The match just played has been cleared away on the way
+//! here, like any other, so the rematch starts from nothing.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    heatmap::{spawn_heatmap, Heatmap},
+    locale::Strings,
+    player_name,
+    recording::Recorder,
+    sim::{MatchOver, MatchRules, MatchStats, Score},
+    stats::{data_dir, now},
+    survival::SurvivalScores,
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        PANEL_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, MatchNames, NextRules, Player,
+};
+
+const EXPORT_DIR: &str = "exports";
+const LABEL_WIDTH: f32 = 200.0;
+const VALUE_WIDTH: f32 = 140.0;
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastMatch>()
+            .add_systems(OnExit(AppState::Playing), remember_match)
+            .add_systems(OnEnter(AppState::GameOver), spawn_game_over)
+            .add_systems(
+                Update,
+                game_over_action.run_if(in_state(AppState::GameOver)),
+            );
+    }
+}
+
+#[derive(Component)]
+enum GameOverAction {
+    Export,
+    SaveReplay,
+    Rematch,
+    Continue,
+}
+
+/// Who played the match just left, and by what rules, for a rematch.
+#[derive(Resource, Default)]
+struct LastMatch {
+    names: [String; 2],
+    rules: MatchRules,
+}
+
+/// Whether a match of `mode` can be played again from the game-over screen.
+/// A tournament's or the campaign's next match is up to them, and an online
+/// one needs both ends to agree.
+fn has_rematches(mode: GameMode) -> bool {
+    matches!(
+        mode,
+        GameMode::Versus
+            | GameMode::Doubles
+            | GameMode::Blitz
+            | GameMode::Arcade
+            | GameMode::Practice
+            | GameMode::Survival
+    )
+}
+
+/// Keeps what a rematch needs as the match is left, while its players are
+/// still about.
+fn remember_match(
+    rules: Res<MatchRules>,
+    players: Query<&Player>,
+    strings: Res<Strings>,
+    mut last_match: ResMut<LastMatch>,
+) {
+    let name = |player_number| player_name(players.iter(), player_number, &strings);
+    *last_match = LastMatch {
+        names: [name(1), name(2)],
+        rules: *rules,
+    };
+}
+
+/// Where the screen says how the last export went.
+#[derive(Component)]
+struct ExportStatus;
+
+/// What an export writes: the match's stats, and how it ended.
+#[derive(Serialize)]
+struct StatsExport<'a> {
+    mode: GameMode,
+    winner: Option<i32>,
+    stats: &'a MatchStats,
+}
+
+fn export(export: &StatsExport) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = data_dir().ok_or("no data directory")?.join(EXPORT_DIR);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("match-{}.json", now()));
+    fs::write(&path, serde_json::to_vec_pretty(export)?)?;
+    Ok(path)
+}
+
+fn spawn_game_over(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    stats: Res<MatchStats>,
+    score: Res<Score>,
+    rules: Res<MatchRules>,
+    match_over: Res<MatchOver>,
+    mode: Res<GameMode>,
+    survival: Res<SurvivalScores>,
+    heatmap: Res<Heatmap>,
+    recorder: Res<Recorder>,
+    strings: Res<Strings>,
+) {
+    let title = match match_over.0 {
+        // Nobody beats the wall, it's how long the player lasted that counts
+        Some(_) if *mode == GameMode::Survival => {
+            strings.fill("game_over.rally_of", &[("hits", &stats.longest_rally)])
+        }
+        Some(winner) => strings.fill("game_over.winner", &[("player", &winner)]),
+        None => strings.get("game_over.match_over").to_string(),
+    };
+    let percent = |rate: Option<f32>| match rate {
+        Some(rate) => format!("{:.0}%", rate * 100.0),
+        None => "-".to_string(),
+    };
+    let rows = [
+        (
+            "game_over.sets",
+            score.player1_sets.to_string(),
+            score.player2_sets.to_string(),
+        ),
+        (
+            "game_over.points",
+            stats.player1.points.to_string(),
+            stats.player2.points.to_string(),
+        ),
+        (
+            "game_over.hits",
+            stats.player1.hits.to_string(),
+            stats.player2.hits.to_string(),
+        ),
+        (
+            "game_over.serves_won",
+            percent(stats.player1.serve_win_rate()),
+            percent(stats.player2.serve_win_rate()),
+        ),
+    ];
+
+    commands
+        .spawn((screen_root(), StateScoped(AppState::GameOver)))
+        .with_children(|parent| {
+            parent.spawn(text(&font, title, 60.0, ACCENT_COLOR).with_style(Style {
+                margin: UiRect::all(Val::Px(20.0)),
+                ..default()
+            }));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(16.0)),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    if *mode == GameMode::Survival {
+                        spawn_high_scores(parent, &font, &strings, &survival);
+                        return;
+                    }
+
+                    spawn_row(
+                        parent,
+                        &font,
+                        "",
+                        &strings.fill("player", &[("number", &1)]),
+                        &strings.fill("player", &[("number", &2)]),
+                        DIM_TEXT_COLOR,
+                    );
+                    // A single set has nothing to count
+                    let skip = if rules.best_of > 1 { 0 } else { 1 };
+                    for (key, first, second) in rows.iter().skip(skip) {
+                        spawn_row(parent, &font, strings.get(key), first, second, TEXT_COLOR);
+                    }
+
+                    let shared = [
+                        strings.count("game_over.longest_rally", stats.longest_rally),
+                        strings.fill("game_over.total_hits", &[("hits", &stats.total_hits())]),
+                        strings.fill(
+                            "game_over.top_speed",
+                            &[("speed", &format!("{:.0}", stats.top_speed))],
+                        ),
+                    ];
+                    for line in shared {
+                        parent.spawn(text(&font, line, 20.0, TEXT_COLOR).with_style(Style {
+                            margin: UiRect {
+                                top: Val::Px(8.0),
+                                ..default()
+                            },
+                            ..default()
+                        }));
+                    }
+                    spawn_heatmap(parent, &font, &strings, &heatmap);
+                });
+
+            let mut index = 0;
+            if has_rematches(*mode) {
+                spawn_button(parent, &font, index, strings.get("game_over.rematch"))
+                    .insert(GameOverAction::Rematch);
+                index += 1;
+            }
+            spawn_button(parent, &font, index, strings.get("game_over.export"))
+                .insert(GameOverAction::Export);
+            index += 1;
+            if recorder.0.is_some() {
+                spawn_button(parent, &font, index, strings.get("game_over.save_replay"))
+                    .insert(GameOverAction::SaveReplay);
+                index += 1;
+            }
+            spawn_button(parent, &font, index, strings.get("game_over.continue"))
+                .insert(GameOverAction::Continue);
+
+            parent.spawn((
+                text(&font, "", 18.0, DIM_TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+                ExportStatus,
+            ));
+        });
+}
+
+/// The longest survival rallies, with the run just played picked out.
+fn spawn_high_scores(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    strings: &Strings,
+    scores: &SurvivalScores,
+) {
+    spawn_row(
+        parent,
+        font,
+        strings.get("game_over.longest_rallies"),
+        strings.get("game_over.hits"),
+        "",
+        DIM_TEXT_COLOR,
+    );
+    for (rank, record) in scores.best.iter().enumerate() {
+        let color = if scores.last == Some(rank) {
+            ACCENT_COLOR
+        } else {
+            TEXT_COLOR
+        };
+        let label = format!("{}. {}", rank + 1, record.name);
+        spawn_row(parent, font, &label, &record.hits.to_string(), "", color);
+    }
+}
+
+fn spawn_row(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    label: &str,
+    first: &str,
+    second: &str,
+    color: Color,
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                margin: UiRect::all(Val::Px(4.0)),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for (value, width) in [
+                (label, LABEL_WIDTH),
+                (first, VALUE_WIDTH),
+                (second, VALUE_WIDTH),
+            ] {
+                parent.spawn(text(font, value, 22.0, color).with_style(Style {
+                    width: Val::Px(width),
+                    ..default()
+                }));
+            }
+        });
+}
+
+fn game_over_action(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    actions: Query<&GameOverAction>,
+    stats: Res<MatchStats>,
+    match_over: Res<MatchOver>,
+    mode: Res<GameMode>,
+    mut status: Query<&mut Text, With<ExportStatus>>,
+    strings: Res<Strings>,
+    recorder: Res<Recorder>,
+    last_match: Res<LastMatch>,
+    mut names: ResMut<MatchNames>,
+    mut next_rules: ResMut<NextRules>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(mode.after_match());
+        return;
+    }
+
+    for event in activated.read() {
+        match actions.get(event.0) {
+            Ok(GameOverAction::Export) => {
+                let message = match export(&StatsExport {
+                    mode: *mode,
+                    winner: match_over.0,
+                    stats: &stats,
+                }) {
+                    Ok(path) => strings.fill("game_over.saved", &[("path", &path.display())]),
+                    Err(error) => {
+                        warn!("Couldn't export stats: {}", error);
+                        strings.fill("game_over.export_failed", &[("error", &error)])
+                    }
+                };
+                for mut text in status.iter_mut() {
+                    text.sections[0].value = message.clone();
+                }
+            }
+            Ok(GameOverAction::SaveReplay) => {
+                let saved = match &recorder.0 {
+                    Some(recording) => recording.save(),
+                    None => continue,
+                };
+                let message = match saved {
+                    Ok(path) => strings.fill("game_over.saved", &[("path", &path.display())]),
+                    Err(error) => {
+                        warn!("Couldn't save the replay: {}", error);
+                        strings.fill("game_over.replay_failed", &[("error", &error)])
+                    }
+                };
+                for mut text in status.iter_mut() {
+                    text.sections[0].value = message.clone();
+                }
+            }
+            Ok(GameOverAction::Rematch) => {
+                names.0 = Some(last_match.names.clone());
+                next_rules.0 = Some(last_match.rules);
+                state.set(AppState::Playing);
+            }
+            Ok(GameOverAction::Continue) => {
+                state.set(mode.after_match());
+            }
+            Err(_) => {}
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! The simulation's [`GameplayConfig`], read from
+//! `assets/gameplay.config.ron` so how fast things go and how steeply they
+//! bounce can be tuned without recompiling. Debug builds watch the file, and
+//! whatever's saved to it takes effect straight away, mid-match and all.
+//!
+//! Online matches play by the defaults whatever the file says, as the other
+//! end has its own.
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+
+use crate::{sim::GameplayConfig, GameMode};
+
+pub struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<GameplayConfig>()
+            .init_asset_loader::<GameplayConfigLoader>()
+            .init_resource::<GameplayConfig>()
+            .add_systems(Startup, load_config)
+            .add_systems(Update, apply_config);
+    }
+}
+
+#[derive(Default)]
+pub struct GameplayConfigLoader;
+
+impl AssetLoader for GameplayConfigLoader {
+    type Asset = GameplayConfig;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<GameplayConfig, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["config.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct ConfigHandle(Handle<GameplayConfig>);
+
+fn load_config(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle = asset_server.load("gameplay.config.ron");
+    commands.insert_resource(ConfigHandle(handle));
+}
+
+/// Keeps the simulation's config in step with the file, or with the defaults
+/// for online matches. A file that's failed to load leaves the defaults in
+/// place too.
+fn apply_config(
+    handle: Res<ConfigHandle>,
+    configs: Res<Assets<GameplayConfig>>,
+    mode: Res<GameMode>,
+    mut config: ResMut<GameplayConfig>,
+) {
+    let wanted = match configs.get(&handle.0) {
+        Some(loaded) if *mode != GameMode::Online => *loaded,
+        _ => GameplayConfig::default(),
+    };
+    if *config != wanted {
+        info!("Playing with {:?}", wanted);
+        *config = wanted;
+    }
+}
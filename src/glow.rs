@@ -0,0 +1,110 @@
+//! A glow around each racket, brighter the more points in a row its player
+//! has won, and pulsing once their momentum's full and their next smash is a
+//! full one.
+
+use bevy::prelude::*;
+
+use crate::{
+    settings::Settings,
+    sim::{Momentum, Racket},
+    AppState, MatchState,
+};
+
+// Opacity of the glow at full momentum
+const GLOW_OPACITY: f32 = 0.45;
+// Pixels the glow reaches out past the racket on every side
+const GLOW_REACH: f32 = 6.0;
+// Pulses a second at full momentum, and how much of the glow they take away
+const PULSE_RATE: f32 = 2.0;
+const PULSE_DEPTH: f32 = 0.4;
+
+pub struct GlowPlugin;
+
+impl Plugin for GlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                attach_glows,
+                update_glows.after(attach_glows),
+                despawn_orphaned_glows,
+            )
+                .run_if(in_state(MatchState::Running)),
+        );
+    }
+}
+
+/// Kept on the racket: the sprite glowing around it.
+#[derive(Component)]
+struct Glow(Entity);
+
+/// The sprite glowing around a racket.
+#[derive(Component)]
+struct GlowSprite {
+    racket: Entity,
+}
+
+fn attach_glows(
+    mut commands: Commands,
+    rackets: Query<Entity, (With<Racket>, With<Momentum>, Without<Glow>)>,
+) {
+    for racket in rackets.iter() {
+        let glow = commands
+            .spawn((
+                SpriteBundle {
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                GlowSprite { racket },
+                StateScoped(AppState::Playing),
+            ))
+            .id();
+        commands.entity(racket).insert(Glow(glow));
+    }
+}
+
+fn update_glows(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    rackets: Query<(&Transform, &Sprite, &Momentum, &Glow), With<Racket>>,
+    mut glows: Query<(&mut Transform, &mut Sprite, &mut Visibility), Without<Racket>>,
+) {
+    for (racket_transform, racket_sprite, momentum, glow) in rackets.iter() {
+        let (mut transform, mut sprite, mut visibility) = match glows.get_mut(glow.0) {
+            Ok(parts) => parts,
+            Err(_) => continue,
+        };
+        let mut opacity = GLOW_OPACITY * momentum.level();
+        if momentum.is_full() && !settings.reduced_motion {
+            let pulse = (time.elapsed_seconds_f64() as f32 * PULSE_RATE * std::f32::consts::TAU)
+                .cos()
+                * 0.5
+                + 0.5;
+            opacity *= 1.0 - PULSE_DEPTH * pulse;
+        }
+        if opacity <= 0.0 {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        let size = racket_transform.scale.truncate() + 2.0 * GLOW_REACH;
+        transform.translation = racket_transform.translation - Vec3::Z * 0.1;
+        transform.rotation = racket_transform.rotation;
+        transform.scale = size.extend(1.0);
+        sprite.color = racket_sprite.color.with_alpha(opacity);
+    }
+}
+
+/// Takes the glow away with a racket that's gone before the match is over.
+fn despawn_orphaned_glows(
+    mut commands: Commands,
+    glows: Query<(Entity, &GlowSprite)>,
+    rackets: Query<(), With<Racket>>,
+) {
+    for (entity, glow) in glows.iter() {
+        if rackets.get(glow.racket).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Where on their end each player met the ball, and where they let it by,
+//! shown after the match to see which parts of the court they're weak in.
+//! Every return and every goal is logged by how far up the court it was, and
+//! the game-over screen draws them as a strip for each player, split into
+//! bands from the top of the court to the bottom, the brighter the more often
+//! the ball got there.
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::ArenaBounds,
+    locale::Strings,
+    sim::{BallHitEvent, GoalEvent},
+    start_match,
+    ui::{text, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR},
+    AppState, MatchState,
+};
+
+/// How many bands the court's split into, top to bottom.
+const BANDS: usize = 8;
+const CELL_SIZE: Vec2 = Vec2::new(36.0, 14.0);
+/// A band the ball never got to.
+const EMPTY_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.06);
+const CONCEDED_COLOR: Color = Color::srgb(0.85, 0.2, 0.2);
+
+pub struct HeatmapPlugin;
+
+impl Plugin for HeatmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Heatmap>()
+            .add_systems(OnEnter(AppState::Playing), clear_heatmap.after(start_match))
+            .add_systems(Update, log_heatmap.run_if(in_state(MatchState::Running)));
+    }
+}
+
+/// How far up the court, from 0 at the bottom to 1 at the top, each player
+/// returned the ball and let it by, by player number.
+#[derive(Resource, Default)]
+pub struct Heatmap {
+    returned: [Vec<f32>; 2],
+    conceded: [Vec<f32>; 2],
+}
+
+/// How many of `heights` are in each band, the top band first.
+fn bands(heights: &[f32]) -> [u32; BANDS] {
+    let mut bands = [0; BANDS];
+    for height in heights {
+        let band = ((1.0 - height.clamp(0.0, 1.0)) * BANDS as f32) as usize;
+        bands[band.min(BANDS - 1)] += 1;
+    }
+    bands
+}
+
+fn clear_heatmap(mut heatmap: ResMut<Heatmap>) {
+    *heatmap = Heatmap::default();
+}
+
+fn log_heatmap(
+    bounds: Res<ArenaBounds>,
+    mut hits: EventReader<BallHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut heatmap: ResMut<Heatmap>,
+) {
+    let height = |position: Vec2| (position.y - bounds.min.y) / (bounds.max.y - bounds.min.y);
+    for hit in hits.read() {
+        if let Some(returned) = heatmap.returned.get_mut((hit.player_number - 1) as usize) {
+            returned.push(height(hit.position));
+        }
+    }
+    for goal in goals.read() {
+        if let Some(conceded) = heatmap.conceded.get_mut((2 - goal.scored_by) as usize) {
+            conceded.push(height(goal.position));
+        }
+    }
+}
+
+/// Both players' strips side by side, player 1's on the left as they
+/// played, each with where they returned the ball on the outside and where
+/// they let it by on the inside.
+pub fn spawn_heatmap(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    strings: &Strings,
+    heatmap: &Heatmap,
+) {
+    parent.spawn(
+        text(font, strings.get("heatmap.title"), 18.0, DIM_TEXT_COLOR).with_style(Style {
+            margin: UiRect {
+                top: Val::Px(14.0),
+                bottom: Val::Px(4.0),
+                ..default()
+            },
+            ..default()
+        }),
+    );
+
+    let strips = [
+        (bands(&heatmap.returned[0]), ACCENT_COLOR),
+        (bands(&heatmap.conceded[0]), CONCEDED_COLOR),
+        (bands(&heatmap.conceded[1]), CONCEDED_COLOR),
+        (bands(&heatmap.returned[1]), ACCENT_COLOR),
+    ];
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for (index, (counts, color)) in strips.into_iter().enumerate() {
+                let most = counts.iter().copied().max().unwrap_or(0).max(1);
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            margin: UiRect {
+                                // The net between the two players
+                                left: Val::Px(if index == 2 { 24.0 } else { 2.0 }),
+                                right: Val::Px(2.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for count in counts {
+                            let mut color = color;
+                            if count == 0 {
+                                color = EMPTY_COLOR;
+                            } else {
+                                color.set_alpha(0.2 + 0.8 * count as f32 / most as f32);
+                            }
+                            parent.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(CELL_SIZE.x),
+                                    height: Val::Px(CELL_SIZE.y),
+                                    margin: UiRect::all(Val::Px(1.0)),
+                                    ..default()
+                                },
+                                background_color: color.into(),
+                                ..default()
+                            });
+                        }
+                    });
+            }
+        });
+
+    parent.spawn(
+        text(font, strings.get("heatmap.key"), 16.0, DIM_TEXT_COLOR).with_style(Style {
+            margin: UiRect::all(Val::Px(4.0)),
+            ..default()
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heights_fall_in_bands_from_the_top() {
+        let counts = bands(&[1.0, 0.99, 0.5, 0.0, -0.2, 1.4]);
+        assert_eq!(counts[0], 3);
+        assert_eq!(counts[BANDS / 2], 1);
+        assert_eq!(counts[BANDS - 1], 2);
+        assert_eq!(counts.iter().sum::<u32>(), 6);
+    }
+}
@@ -0,0 +1,170 @@
+//! The most recent matches finished, newest first, from the same store the
+//! season stats are worked out from. Left and right narrow it down to the
+//! matches one profile played in, and back out to everyone's.
+
+use bevy::prelude::*;
+
+use crate::{
+    dashboard::date_label,
+    locale::Strings,
+    stats::{MatchRecord, StatsStore},
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        TEXT_COLOR,
+    },
+    AppState,
+};
+
+// How many matches fit on the screen
+const RECENT_MATCHES: usize = 12;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HistoryFilter>()
+            .add_systems(OnEnter(AppState::History), spawn_history)
+            .add_systems(
+                Update,
+                (change_filter, history_action).run_if(in_state(AppState::History)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnHistory;
+
+#[derive(Component)]
+struct BackButton;
+
+/// Whose matches are listed: everyone's at 0, and past that the profile one
+/// before it in [`StatsStore::profiles`].
+#[derive(Resource, Default)]
+struct HistoryFilter(usize);
+
+/// One line of the list, like "09-14  Blitz  Ada 11 - 7 Grace  2:05  rally 14".
+fn match_line(record: &MatchRecord, strings: &Strings) -> String {
+    let [first, second] = &record.players;
+    let seconds = record.duration.max(0.0) as u32;
+    strings.fill(
+        "history.match",
+        &[
+            ("date", &date_label(record.started_at / 86400)),
+            ("mode", &strings.get(record.mode.name_key())),
+            ("first", &first.name),
+            ("first_points", &first.points),
+            ("second_points", &second.points),
+            ("second", &second.name),
+            ("time", &format!("{}:{:02}", seconds / 60, seconds % 60)),
+            ("rally", &record.longest_rally()),
+        ],
+    )
+}
+
+fn spawn_history(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    store: Res<StatsStore>,
+    strings: Res<Strings>,
+    mut filter: ResMut<HistoryFilter>,
+) {
+    let profiles = store.profiles();
+    if filter.0 > profiles.len() {
+        filter.0 = 0;
+    }
+    let profile = filter.0.checked_sub(1).map(|index| &profiles[index]);
+
+    commands
+        .spawn((screen_root(), OnHistory, StateScoped(AppState::History)))
+        .with_children(|parent| {
+            parent.spawn(text(&font, strings.get("history.title"), 48.0, TEXT_COLOR));
+            parent.spawn(text(
+                &font,
+                format!(
+                    "< {} >",
+                    profile.map_or(strings.get("everyone"), String::as_str)
+                ),
+                32.0,
+                ACCENT_COLOR,
+            ));
+
+            let recent: Vec<&MatchRecord> = store
+                .matches
+                .iter()
+                .rev()
+                .filter(|record| profile.is_none_or(|name| record.sides(name).is_some()))
+                .take(RECENT_MATCHES)
+                .collect();
+            if recent.is_empty() {
+                parent.spawn(text(
+                    &font,
+                    strings.get("history.empty"),
+                    24.0,
+                    DIM_TEXT_COLOR,
+                ));
+            }
+            for record in recent {
+                parent.spawn(
+                    text(&font, match_line(record, &strings), 20.0, TEXT_COLOR).with_style(Style {
+                        margin: UiRect::all(Val::Px(3.0)),
+                        ..default()
+                    }),
+                );
+            }
+
+            parent.spawn(text(
+                &font,
+                strings.get("change_profile"),
+                18.0,
+                DIM_TEXT_COLOR,
+            ));
+            spawn_button(parent, &font, 0, strings.get("back")).insert(BackButton);
+        });
+}
+
+fn change_filter(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    store: Res<StatsStore>,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    mut filter: ResMut<HistoryFilter>,
+    screen: Query<Entity, With<OnHistory>>,
+) {
+    // Everyone, and each of the profiles
+    let count = store.profiles().len() + 1;
+    if count < 2 {
+        return;
+    }
+
+    let before = filter.0;
+    if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        filter.0 = (filter.0 + count - 1) % count;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        filter.0 = (filter.0 + 1) % count;
+    }
+    if filter.0 == before {
+        return;
+    }
+
+    for entity in screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_history(commands, font, store, strings, filter);
+}
+
+fn history_action(
+    mut activated: EventReader<ButtonActivated>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let back = activated
+        .read()
+        .any(|event| back_buttons.get(event.0).is_ok())
+        || keyboard_input.just_pressed(KeyCode::Escape);
+    if back {
+        state.set(AppState::Menu);
+    }
+}
@@ -0,0 +1,200 @@
+//! Gamepads plugged in and pulled out. Outside a match, a gamepad takes the
+//! first free seat as soon as it's connected. In the middle of one, a pad
+//! coming or going pauses it: one that's pulled out leaves its player's seat
+//! waiting for another, and the pause menu says so, and a new one takes a
+//! seat by pressing A there, the seat that lost its pad first, then anyone
+//! playing on the keyboard.
+//!
+//! Online matches can't be paused, so a pad connected during one takes the
+//! seat that lost its pad straight away, if one did.
+
+use bevy::{
+    input::gamepad::{GamepadButton, GamepadButtonType, GamepadConnection, GamepadConnectionEvent},
+    prelude::*,
+};
+
+use crate::{
+    actions::GamepadSeats, locale::Strings, pause, sim::SEATS, AppState, GameMode, MatchState,
+    Player,
+};
+
+pub struct HotplugPlugin;
+
+impl Plugin for HotplugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dropped>()
+            .add_systems(Update, hotplug)
+            .add_systems(
+                Update,
+                (claim_seats, update_prompt.after(claim_seats))
+                    .run_if(in_state(MatchState::Paused)),
+            )
+            .add_systems(OnExit(AppState::Playing), settle_seats);
+    }
+}
+
+/// Says on the pause menu whose pad was pulled out, and who a new one can
+/// play for.
+#[derive(Component)]
+pub(crate) struct GamepadPrompt;
+
+/// Seats that lost their gamepad in the match being played, by seat.
+#[derive(Resource, Default)]
+struct Dropped([bool; SEATS]);
+
+/// Whether a pad coming or going can pause a match of `mode`.
+fn pausable(mode: GameMode) -> bool {
+    !matches!(mode, GameMode::Online | GameMode::Demo)
+}
+
+/// The seat a new pad takes out of the ones being `played`: the first to
+/// have lost its pad, or failing that the first playing without one.
+fn claimable(seats: &GamepadSeats, dropped: &Dropped, played: &[usize]) -> Option<usize> {
+    let mut free: Vec<usize> = played
+        .iter()
+        .copied()
+        .filter(|seat| seats.gamepad(*seat).is_none())
+        .collect();
+    free.sort_unstable();
+    free.iter()
+        .copied()
+        .find(|seat| dropped.0[*seat])
+        .or_else(|| free.first().copied())
+}
+
+fn hotplug(
+    mut events: EventReader<GamepadConnectionEvent>,
+    mut seats: ResMut<GamepadSeats>,
+    mut dropped: ResMut<Dropped>,
+    mode: Res<GameMode>,
+    state: Res<State<AppState>>,
+    match_state: Option<Res<State<MatchState>>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    let in_match = *state.get() == AppState::Playing;
+    let mut pause = false;
+    for event in events.read() {
+        match event.connection {
+            GamepadConnection::Connected(_) if !in_match => {
+                if let Some(seat) = seats.claim(event.gamepad) {
+                    info!("Gamepad {} plays seat {}", event.gamepad.id, seat);
+                }
+            }
+            GamepadConnection::Connected(_) if pausable(*mode) => {
+                info!("Gamepad {} connected mid-match", event.gamepad.id);
+                pause = true;
+            }
+            GamepadConnection::Connected(_) => {
+                if let Some(seat) = dropped.0.iter().position(|dropped| *dropped) {
+                    info!("Gamepad {} plays seat {}", event.gamepad.id, seat);
+                    seats.assign(seat, event.gamepad);
+                    dropped.0[seat] = false;
+                }
+            }
+            GamepadConnection::Disconnected => {
+                if let Some(seat) = seats.release(event.gamepad) {
+                    info!(
+                        "Gamepad {} playing seat {} disconnected",
+                        event.gamepad.id, seat
+                    );
+                    dropped.0[seat] = in_match;
+                    pause |= in_match && pausable(*mode);
+                }
+            }
+        }
+    }
+    if pause && pause::running(match_state) {
+        next_state.set(MatchState::Paused);
+    }
+}
+
+/// Gives a seat to any pad that's not playing one and presses A.
+fn claim_seats(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    players: Query<&Player>,
+    mut seats: ResMut<GamepadSeats>,
+    mut dropped: ResMut<Dropped>,
+) {
+    let played: Vec<usize> = players.iter().map(|player| player.seat).collect();
+    for gamepad in gamepads.iter() {
+        let pressed = buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South));
+        if !pressed || seats.seat(gamepad).is_some() {
+            continue;
+        }
+        if let Some(seat) = claimable(&seats, &dropped, &played) {
+            info!("Gamepad {} plays seat {}", gamepad.id, seat);
+            seats.assign(seat, gamepad);
+            dropped.0[seat] = false;
+        }
+    }
+}
+
+fn update_prompt(
+    strings: Res<Strings>,
+    gamepads: Res<Gamepads>,
+    seats: Res<GamepadSeats>,
+    dropped: Res<Dropped>,
+    players: Query<&Player>,
+    mut prompts: Query<&mut Text, With<GamepadPrompt>>,
+) {
+    let player_number = |seat: usize| {
+        players
+            .iter()
+            .find(|player| player.seat == seat)
+            .map_or(seat as i32 + 1, |player| player.player_number)
+    };
+    let mut lines: Vec<String> = (0..SEATS)
+        .filter(|seat| dropped.0[*seat])
+        .map(|seat| strings.fill("pause.disconnected", &[("number", &player_number(seat))]))
+        .collect();
+    let played: Vec<usize> = players.iter().map(|player| player.seat).collect();
+    let spare = gamepads.iter().any(|gamepad| seats.seat(gamepad).is_none());
+    if let Some(seat) = claimable(&seats, &dropped, &played).filter(|_| spare) {
+        lines.push(strings.fill("pause.claim", &[("number", &player_number(seat))]));
+    }
+    let value = lines.join("\n");
+    for mut text in prompts.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Forgets whose pads were pulled out once the match is over, and gives any
+/// pad connected during it a seat for the next.
+fn settle_seats(
+    gamepads: Res<Gamepads>,
+    mut seats: ResMut<GamepadSeats>,
+    mut dropped: ResMut<Dropped>,
+) {
+    *dropped = Dropped::default();
+    let mut waiting: Vec<Gamepad> = gamepads
+        .iter()
+        .filter(|gamepad| seats.seat(*gamepad).is_none())
+        .collect();
+    waiting.sort_by_key(|gamepad| gamepad.id);
+    for gamepad in waiting {
+        seats.claim(gamepad);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_pads_take_the_dropped_seat_first() {
+        let mut seats = GamepadSeats::default();
+        seats.assign(0, Gamepad { id: 0 });
+        let mut dropped = Dropped::default();
+        // Player 2's on the keyboard, and player 3's pad was pulled out
+        dropped.0[2] = true;
+        assert_eq!(claimable(&seats, &dropped, &[0, 1, 2, 3]), Some(2));
+
+        dropped.0[2] = false;
+        assert_eq!(claimable(&seats, &dropped, &[0, 1, 2, 3]), Some(1));
+        // Only seats someone's playing
+        assert_eq!(claimable(&seats, &dropped, &[0]), None);
+    }
+}
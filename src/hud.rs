@@ -0,0 +1,584 @@
+//! The scoreboard over the court during a match, the clock in timed ones and
+//! the lives left in arcade ones, and under it the rally and the ball's speed.
+//! Just under the scoreboard, a bar splits between the two ends by how likely
+//! each player is to win from the score, see [`crate::prob`]. After every
+//! point, a ticker under the scoreboard sums it up for a moment: how long the
+//! rally was, how fast the ball got and who served.
+
+use bevy::prelude::*;
+
+use crate::{
+    locale::Strings,
+    prob::WinTable,
+    settings::Settings,
+    sim::{
+        Ball, BallHitEvent, GoalEvent, MatchClock, MatchOver, MatchRules, MatchStats, Netted,
+        Racket, Rally, Score, ServeEvent, Side, Smash, Stakes, Velocity, BALL_SPEED,
+        NET_SETTLE_TIME,
+    },
+    start_match,
+    tween::{mix, Ease, Tween},
+    ui::{text, text_style, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR, PANEL_COLOR, TEXT_COLOR},
+    AppState, GameMode, MatchState,
+};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Playing), spawn_hud.after(start_match))
+            .add_systems(
+                Update,
+                (
+                    update_hud,
+                    update_clock,
+                    update_readouts,
+                    update_ticker,
+                    update_win_meter,
+                    update_smash_meters,
+                )
+                    .run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Points in the set being played, with who's on which end.
+#[derive(Component)]
+struct Scoreboard;
+
+/// Sets won so far.
+#[derive(Component)]
+struct SetScore;
+
+/// Lives left for the player on one end of an arcade match, a heart each,
+/// with the ones they've lost dimmed.
+#[derive(Component)]
+struct Lives(Side);
+
+const LIFE_ICON: &str = "\u{2665}";
+const LIVES_SIZE: f32 = 28.0;
+
+/// Time left in a timed match.
+#[derive(Component)]
+struct Clock;
+
+const CLOCK_SIZE: f32 = 28.0;
+// In the last seconds the clock turns urgent and pulses with every one that passes
+const WARNING_SECONDS: f32 = 10.0;
+const TICK_PULSE: f32 = 0.4;
+
+/// Announces what's riding on the next point: that it's golden, or a match
+/// point, a set point or deuce.
+#[derive(Component)]
+struct StakesBanner;
+
+/// The bar under the scoreboard, filled from the left as far as the chance
+/// the player on that end has of winning.
+#[derive(Component)]
+struct WinMeter;
+
+/// What the [`WinMeter`] fills, and is hidden along with.
+#[derive(Component)]
+struct WinMeterBar;
+
+const WIN_METER_SIZE: Vec2 = Vec2::new(240.0, 4.0);
+/// How quickly the meter catches up with the chances, per second.
+const WIN_METER_EASING: f32 = 6.0;
+
+/// Sums up the point just played, until the next serve.
+#[derive(Component)]
+struct PointTicker;
+
+// The ticker's gone by the time the ball's served again, at the usual speed,
+// and right away at any other
+const TICKER_FADE: f32 = 0.3;
+
+/// What's been seen of the point being played, from the events sent, for the
+/// ticker once it's over.
+#[derive(Default)]
+struct PointLog {
+    server: i32,
+    top_speed: f32,
+}
+
+/// How charged the smash is for the racket on one end, filling up from the left.
+#[derive(Component)]
+struct SmashMeter(Side);
+
+const SMASH_METER_SIZE: Vec2 = Vec2::new(120.0, 10.0);
+
+/// The hits in the rally being played, or how fast the ball's going. Every
+/// hit pulses it bigger, and it warms up to the accent colour as the rally
+/// gets long or the ball fast.
+#[derive(Component)]
+enum Readout {
+    Rally,
+    Speed,
+}
+
+const READOUT_SIZE: f32 = 22.0;
+// A rally this long, or a ball this fast, has a readout as warm and pulsing
+// as hard as it gets
+const LONG_RALLY: f32 = 20.0;
+const FAST_BALL: f32 = 2.0 * BALL_SPEED;
+// At its hardest, a hit makes a readout this much bigger, shrinking back over
+// this many seconds
+const HIT_PULSE: f32 = 0.5;
+const PULSE_TIME: f32 = 0.25;
+
+fn spawn_hud(mut commands: Commands, font: Res<UiFont>, rules: Res<MatchRules>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(12.0),
+                    width: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("", text_style(&font, 20.0, DIM_TEXT_COLOR)),
+                    TextSection::new("", text_style(&font, 40.0, TEXT_COLOR)),
+                    TextSection::new("", text_style(&font, 20.0, DIM_TEXT_COLOR)),
+                ]),
+                Scoreboard,
+            ));
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(WIN_METER_SIZE.x),
+                            height: Val::Px(WIN_METER_SIZE.y),
+                            margin: UiRect::new(
+                                Val::Px(0.0),
+                                Val::Px(0.0),
+                                Val::Px(4.0),
+                                Val::Px(4.0),
+                            ),
+                            display: Display::None,
+                            ..default()
+                        },
+                        background_color: PANEL_COLOR.into(),
+                        ..default()
+                    },
+                    WinMeterBar,
+                ))
+                .with_children(|bar| {
+                    bar.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(50.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: TEXT_COLOR.into(),
+                            ..default()
+                        },
+                        WinMeter,
+                    ));
+                });
+            if rules.lives.is_some() {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        for side in [Side::Left, Side::Right] {
+                            row.spawn((
+                                TextBundle::from_sections([
+                                    TextSection::new(
+                                        "",
+                                        text_style(&font, LIVES_SIZE, ACCENT_COLOR),
+                                    ),
+                                    TextSection::new(
+                                        "",
+                                        text_style(&font, LIVES_SIZE, DIM_TEXT_COLOR),
+                                    ),
+                                ])
+                                .with_style(Style {
+                                    margin: UiRect::new(
+                                        Val::Px(24.0),
+                                        Val::Px(24.0),
+                                        Val::Px(0.0),
+                                        Val::Px(0.0),
+                                    ),
+                                    ..default()
+                                }),
+                                Lives(side),
+                            ));
+                        }
+                    });
+            }
+            parent.spawn((text(&font, "", CLOCK_SIZE, TEXT_COLOR), Clock));
+            parent.spawn((text(&font, "", 18.0, DIM_TEXT_COLOR), SetScore));
+            parent.spawn((text(&font, "", 28.0, ACCENT_COLOR), StakesBanner));
+            parent.spawn((text(&font, "", 18.0, DIM_TEXT_COLOR), PointTicker));
+        });
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(12.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    padding: UiRect::new(Val::Px(40.0), Val::Px(40.0), Val::Px(0.0), Val::Px(0.0)),
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            for side in [Side::Left, Side::Right] {
+                if side == Side::Right {
+                    parent
+                        .spawn(NodeBundle {
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .with_children(|readouts| {
+                            for readout in [Readout::Rally, Readout::Speed] {
+                                readouts.spawn((
+                                    text(&font, "", READOUT_SIZE, TEXT_COLOR).with_style(Style {
+                                        margin: UiRect::new(
+                                            Val::Px(16.0),
+                                            Val::Px(16.0),
+                                            Val::Px(0.0),
+                                            Val::Px(0.0),
+                                        ),
+                                        ..default()
+                                    }),
+                                    readout,
+                                ));
+                            }
+                        });
+                }
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(SMASH_METER_SIZE.x),
+                            height: Val::Px(SMASH_METER_SIZE.y),
+                            ..default()
+                        },
+                        background_color: PANEL_COLOR.into(),
+                        ..default()
+                    })
+                    .with_children(|meter| {
+                        meter.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(0.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                background_color: TEXT_COLOR.into(),
+                                ..default()
+                            },
+                            SmashMeter(side),
+                        ));
+                    });
+            }
+        });
+}
+
+fn update_hud(
+    score: Res<Score>,
+    rules: Res<MatchRules>,
+    match_over: Res<MatchOver>,
+    mode: Res<GameMode>,
+    strings: Res<Strings>,
+    mut texts: ParamSet<(
+        Query<&mut Text, With<Scoreboard>>,
+        Query<&mut Text, With<SetScore>>,
+        Query<&mut Text, With<StakesBanner>>,
+        Query<(&mut Text, &Lives)>,
+    )>,
+    added: Query<(), Added<Scoreboard>>,
+) {
+    // The final score stays up, rather than the start of a set nobody plays
+    if (!score.is_changed() && added.is_empty()) || match_over.0.is_some() {
+        return;
+    }
+    // The ball machine's and the wall's points aren't anybody's, practice and
+    // survival keep their own count
+    if matches!(*mode, GameMode::Practice | GameMode::Survival) {
+        return;
+    }
+
+    let left = score.player_on(Side::Left);
+    let right = score.player_on(Side::Right);
+    for mut text in texts.p0().iter_mut() {
+        text.sections[0].value = format!("{}   ", strings.fill("hud.player", &[("number", &left)]));
+        // With lives, a point is only what it took off somebody, which the
+        // hearts already show
+        text.sections[1].value = if rules.lives.is_some() {
+            "-".to_string()
+        } else {
+            format!("{} - {}", score.points(left), score.points(right))
+        };
+        text.sections[2].value =
+            format!("   {}", strings.fill("hud.player", &[("number", &right)]));
+    }
+    for mut text in texts.p1().iter_mut() {
+        text.sections[0].value = if rules.best_of > 1 {
+            strings.fill(
+                "hud.sets",
+                &[
+                    ("left", &score.sets(left)),
+                    ("right", &score.sets(right)),
+                    ("best_of", &rules.best_of),
+                ],
+            )
+        } else {
+            String::new()
+        };
+    }
+    for mut text in texts.p2().iter_mut() {
+        // A golden point is a set point with no deuce to come, which is
+        // worth saying unless it's for the match
+        let key = match rules.stakes(&score) {
+            Some(Stakes::MatchPoint) => Some("hud.match_point"),
+            _ if score.golden_point => Some("hud.golden_point"),
+            Some(Stakes::SetPoint) => Some("hud.set_point"),
+            Some(Stakes::Deuce) => Some("hud.deuce"),
+            None => None,
+        };
+        text.sections[0].value = key.map_or_else(String::new, |key| strings.get(key).to_string());
+    }
+    if let Some(lives) = rules.lives {
+        for (mut text, side) in texts.p3().iter_mut() {
+            let player = score.player_on(side.0);
+            let remaining = rules.lives_left(&score, player).unwrap_or(lives);
+            text.sections[0].value = LIFE_ICON.repeat(remaining as usize);
+            text.sections[1].value = LIFE_ICON.repeat((lives - remaining) as usize);
+        }
+    }
+}
+
+fn update_clock(
+    clock: Res<MatchClock>,
+    rules: Res<MatchRules>,
+    mut clocks: Query<&mut Text, With<Clock>>,
+) {
+    if !clock.is_changed() || rules.time_limit.is_none() {
+        return;
+    }
+
+    let seconds = clock.seconds_left(rules.time_step);
+    let whole = seconds.ceil() as u32;
+    for mut text in clocks.iter_mut() {
+        let section = &mut text.sections[0];
+        section.value = format!("{}:{:02}", whole / 60, whole % 60);
+        if seconds > 0.0 && seconds <= WARNING_SECONDS {
+            // Biggest right as a second ticks over, shrinking back until the next
+            let pulse = seconds.fract().powi(2);
+            section.style.color = ACCENT_COLOR;
+            section.style.font_size = CLOCK_SIZE * (1.0 + TICK_PULSE * pulse);
+        } else {
+            section.style.color = TEXT_COLOR;
+            section.style.font_size = CLOCK_SIZE;
+        }
+    }
+}
+
+fn update_readouts(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    strings: Res<Strings>,
+    rally: Res<Rally>,
+    mut hits: EventReader<BallHitEvent>,
+    balls: Query<&Velocity, (With<Ball>, Without<Netted>)>,
+    mut speed: Local<f32>,
+    mut readouts: Query<(Entity, &Readout, &mut Text)>,
+) {
+    let hit = hits.read().count() > 0;
+    // A ball in the net leaves up the speed it went in at
+    if let Some(fastest) = balls
+        .iter()
+        .map(|velocity| velocity.length())
+        .reduce(f32::max)
+    {
+        *speed = fastest;
+    }
+
+    for (entity, readout, mut text) in readouts.iter_mut() {
+        let (value, heat) = match readout {
+            Readout::Rally => (
+                strings.fill("hud.rally", &[("hits", &rally.hits)]),
+                rally.hits as f32 / LONG_RALLY,
+            ),
+            Readout::Speed => (
+                strings.fill("hud.speed", &[("speed", &format!("{:.0}", *speed))]),
+                (*speed - BALL_SPEED) / (FAST_BALL - BALL_SPEED),
+            ),
+        };
+        let heat = heat.clamp(0.0, 1.0);
+        if hit && !settings.effects_reduced() {
+            let pulse = HIT_PULSE * heat;
+            commands.entity(entity).insert(Tween::new(
+                PULSE_TIME,
+                Ease::Out,
+                move |text: &mut Text, along| {
+                    text.sections[0].style.font_size = READOUT_SIZE * (1.0 + pulse * (1.0 - along));
+                },
+            ));
+        }
+
+        let section = &mut text.sections[0];
+        section.value = value;
+        section.style.color = mix(TEXT_COLOR, ACCENT_COLOR, heat);
+    }
+}
+
+fn update_ticker(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    strings: Res<Strings>,
+    stats: Res<MatchStats>,
+    rally: Res<Rally>,
+    mut serves: EventReader<ServeEvent>,
+    mut hits: EventReader<BallHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut log: Local<PointLog>,
+    mut tickers: Query<(Entity, &mut Text), With<PointTicker>>,
+) {
+    // Nobody's keeping score to sum up
+    if matches!(*mode, GameMode::Practice | GameMode::Survival) {
+        return;
+    }
+
+    for serve in serves.read() {
+        *log = PointLog {
+            server: stats.server,
+            top_speed: serve.velocity.length(),
+        };
+        for (entity, mut text) in tickers.iter_mut() {
+            commands.entity(entity).remove::<Tween<Text>>();
+            text.sections[0].value.clear();
+        }
+    }
+    for hit in hits.read() {
+        log.top_speed = log.top_speed.max(hit.velocity.length());
+    }
+    if goals.read().count() == 0 {
+        return;
+    }
+
+    let value = strings.fill(
+        "hud.point",
+        &[
+            ("hits", &rally.finished.last().copied().unwrap_or(0)),
+            ("speed", &format!("{:.0}", log.top_speed)),
+            (
+                "server",
+                &strings.fill("hud.player", &[("number", &log.server)]),
+            ),
+        ],
+    );
+    for (entity, mut text) in tickers.iter_mut() {
+        let section = &mut text.sections[0];
+        section.value = value.clone();
+        section.style.color = DIM_TEXT_COLOR;
+        commands.entity(entity).insert(
+            Tween::new(TICKER_FADE, Ease::Linear, |text: &mut Text, along| {
+                text.sections[0].style.color.set_alpha(1.0 - along);
+            })
+            .after(NET_SETTLE_TIME - TICKER_FADE),
+        );
+    }
+}
+
+fn update_win_meter(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    stats: Res<MatchStats>,
+    mut table: Local<Option<(MatchRules, Option<WinTable>)>>,
+    mut bars: Query<&mut Style, (With<WinMeterBar>, Without<WinMeter>)>,
+    mut meters: Query<&mut Style, With<WinMeter>>,
+) {
+    // Only worked out again for a match with different rules
+    if table.as_ref().map(|(worked_out, _)| worked_out) != Some(&*rules) {
+        *table = Some((*rules, WinTable::new(&rules)));
+    }
+    let table = table
+        .as_ref()
+        .and_then(|(_, table)| table.as_ref())
+        // Practice and survival have nobody to win against
+        .filter(|_| {
+            settings.win_meter && !matches!(*mode, GameMode::Practice | GameMode::Survival)
+        });
+    for mut style in bars.iter_mut() {
+        style.display = if table.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    let table = match table {
+        Some(table) => table,
+        None => return,
+    };
+
+    let chance = table.chance(&score, stats.server);
+    let left = if score.player_on(Side::Left) == 1 {
+        chance
+    } else {
+        1.0 - chance
+    };
+    let easing = 1.0 - (-WIN_METER_EASING * time.delta_seconds()).exp();
+    for mut style in meters.iter_mut() {
+        let shown = match style.width {
+            Val::Percent(percent) => percent / 100.0,
+            _ => 0.5,
+        };
+        style.width = Val::Percent((shown + (left - shown) * easing) * 100.0);
+    }
+}
+
+fn update_smash_meters(
+    rules: Res<MatchRules>,
+    rackets: Query<(&Side, &Smash), With<Racket>>,
+    mut meters: Query<(&SmashMeter, &mut Style, &mut BackgroundColor)>,
+) {
+    for (meter, mut style, mut color) in meters.iter_mut() {
+        let smash = match rackets.iter().find(|(side, _)| **side == meter.0) {
+            Some((_, smash)) => smash,
+            // The ball machine doesn't smash
+            None => {
+                style.width = Val::Percent(0.0);
+                continue;
+            }
+        };
+        // Cooling down, the meter empties out as the racket gets ready again
+        let step = rules.time_step;
+        let (level, fill) = if smash.cooldown(step) > 0.0 {
+            (smash.cooldown(step), DIM_TEXT_COLOR)
+        } else if smash.is_released() || smash.level(step) >= 1.0 {
+            (smash.level(step), ACCENT_COLOR)
+        } else {
+            (smash.level(step), TEXT_COLOR)
+        };
+        style.width = Val::Percent(level * 100.0);
+        *color = fill.into();
+    }
+}
@@ -0,0 +1,159 @@
+//! Standing in for a player who's walked away. When the settings ask for it,
+//! a player who presses nothing for long enough has a CPU take over their
+//! racket, with a "CPU" badge over it to say so, and gets it back the moment
+//! they press anything again. Anyone can get up from the couch mid-match, or
+//! sit down at a match already going, without it stopping.
+//!
+//! Only local matches, where all the keys are on this machine, and only for
+//! players nobody's handed to a CPU or a bot already.
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::Actions,
+    ai::{Cpu, Difficulty},
+    bot::{drive, Bot},
+    locale::Strings,
+    seams::Clock,
+    settings::Settings,
+    sim::{extent, Racket},
+    ui::{UiFont, ACCENT_COLOR},
+    AppState, GameMode, MatchState, Player,
+};
+
+const BADGE_SIZE: f32 = 20.0;
+/// Pixels between the top of the racket and the badge.
+const BADGE_GAP: f32 = 16.0;
+// Over everything on the court, even the dark of a night match
+const BADGE_Z: f32 = 60.0;
+
+pub struct IdlePlugin;
+
+impl Plugin for IdlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                stand_in::<Time>.before(drive::<Cpu, Time>),
+                follow_rackets.after(stand_in::<Time>),
+            )
+                .run_if(in_state(MatchState::Running)),
+        );
+    }
+}
+
+/// Seconds since a player last pressed anything.
+#[derive(Component, Default)]
+struct Idle(f32);
+
+/// The CPU playing for a player while they're away, and the badge saying so.
+#[derive(Component)]
+struct StandIn {
+    badge: Entity,
+}
+
+/// Sits over the racket at this seat.
+#[derive(Component)]
+struct Badge(usize);
+
+/// Whether anyone can be stood in for in a match of `mode`.
+fn local(mode: GameMode) -> bool {
+    !matches!(mode, GameMode::Online | GameMode::Demo)
+}
+
+/// Has a CPU take over for anyone who's been idle too long, and hands their
+/// racket back as soon as they press something. Runs before the CPUs press
+/// theirs, so only what the player pressed is seen.
+fn stand_in<C: Clock>(
+    mut commands: Commands,
+    time: Res<C>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    actions: Res<Actions>,
+    strings: Res<Strings>,
+    font: Res<UiFont>,
+    mut players: Query<(
+        Entity,
+        &Player,
+        Option<&mut Idle>,
+        Option<&StandIn>,
+        Option<&Cpu>,
+    )>,
+    bots: Query<(), With<Bot>>,
+) {
+    let after = match settings.idle_takeover {
+        Some(after) if local(*mode) => after,
+        _ => return,
+    };
+
+    for (entity, player, idle, stand_in, cpu) in players.iter_mut() {
+        // Played by a CPU or a bot from the start
+        if (cpu.is_some() && stand_in.is_none()) || bots.contains(entity) {
+            continue;
+        }
+        let mut idle = match idle {
+            Some(idle) => idle,
+            None => {
+                commands.entity(entity).insert(Idle::default());
+                continue;
+            }
+        };
+
+        if actions.any_pressed(player.seat) {
+            idle.0 = 0.0;
+            if let Some(stand_in) = stand_in {
+                info!("{} is back", player.name);
+                commands.entity(stand_in.badge).despawn_recursive();
+                commands.entity(entity).remove::<StandIn>().remove::<Cpu>();
+            }
+            continue;
+        }
+
+        idle.0 += time.delta_seconds();
+        if idle.0 >= after && stand_in.is_none() {
+            info!("{} has gone idle, a CPU is standing in", player.name);
+            let badge = commands
+                .spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            strings.get("idle.badge"),
+                            TextStyle {
+                                font: font.0.clone(),
+                                font_size: BADGE_SIZE,
+                                color: ACCENT_COLOR,
+                            },
+                        )
+                        .with_justify(JustifyText::Center),
+                        // Out of sight until it's been put over the racket
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    Badge(player.seat),
+                    StateScoped(AppState::Playing),
+                ))
+                .id();
+            commands
+                .entity(entity)
+                .insert(Cpu::new(Difficulty::Normal))
+                .insert(StandIn { badge });
+        }
+    }
+}
+
+/// Keeps every badge over its racket, wherever it's moved or grown to.
+fn follow_rackets(
+    rackets: Query<(&Racket, &Transform), Without<Badge>>,
+    mut badges: Query<(&Badge, &mut Transform, &mut Visibility)>,
+) {
+    for (badge, mut transform, mut visibility) in badges.iter_mut() {
+        let racket = rackets.iter().find(|(racket, _)| racket.seat == badge.0);
+        *visibility = match racket {
+            Some(_) => Visibility::Inherited,
+            None => Visibility::Hidden,
+        };
+        if let Some((_, racket)) = racket {
+            let top = racket.translation.y + extent(racket).y / 2.0;
+            transform.translation = Vec3::new(racket.translation.x, top + BADGE_GAP, BADGE_Z);
+        }
+    }
+}
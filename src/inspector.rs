@@ -0,0 +1,40 @@
+//! Every entity and its components, to look through and change while the
+//! game's running, in the world inspector. F7 opens and closes it.
+//!
+//! Only components that can be reflected show their fields, and only once
+//! they're registered, as the pieces of the court are by the scenes and the
+//! rest of the gameplay's are here.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+
+use crate::{
+    sim::{Netted, ServedOnce},
+    Player,
+};
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InspectorOpen>()
+            .add_plugins(WorldInspectorPlugin::new().run_if(inspector_open))
+            .register_type::<Player>()
+            .register_type::<Netted>()
+            .register_type::<ServedOnce>()
+            .add_systems(Update, toggle_inspector);
+    }
+}
+
+#[derive(Resource, Default)]
+struct InspectorOpen(bool);
+
+fn inspector_open(open: Res<InspectorOpen>) -> bool {
+    open.0
+}
+
+fn toggle_inspector(keyboard_input: Res<ButtonInput<KeyCode>>, mut open: ResMut<InspectorOpen>) {
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        open.0 = !open.0;
+    }
+}
@@ -0,0 +1,133 @@
+//! Drawing the balls and rackets between the simulation's steps. It steps 60
+//! times a second, and on a faster screen anything it moves would otherwise
+//! sit still for a frame or two and then jump.
+//!
+//! Only what's drawn is in between. The balls and rackets are moved to where
+//! they're drawn just long enough for their [`GlobalTransform`]s to be worked
+//! out, then straight back, so the simulation and everything else only ever
+//! sees where they really are. That has them drawn up to a step behind, going
+//! from where they were before the last step to where it left them.
+//!
+//! Online matches step with the rollback session instead, and are drawn as
+//! they are.
+
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::{
+    sim::{Ball, Racket, SimSystems},
+    SteppedLocally,
+};
+
+// Going further than this in a step is being served, going through a portal or
+// changing ends, none of which should be drawn in between
+const MAX_STEP_DISTANCE: f32 = 100.0;
+
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Stepping>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    record_before.before(SimSystems),
+                    record_after.after(SimSystems),
+                )
+                    .in_set(SteppedLocally),
+            )
+            .add_systems(FixedUpdate, end_step.after(record_after))
+            .add_systems(
+                PostUpdate,
+                draw_between.before(TransformSystem::TransformPropagate),
+            )
+            .add_systems(
+                PostUpdate,
+                put_back.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Where a ball or racket was before the last step, and where it left it.
+#[derive(Component)]
+struct Steps {
+    before: Transform,
+    after: Transform,
+    /// Whether it's been moved to where it's drawn, and needs putting back
+    drawn: bool,
+}
+
+/// Whether the simulation played the last step it could have. It doesn't
+/// while a goal's celebrated, a replay's on or it's frozen, and then there's
+/// nothing to draw in between.
+#[derive(Resource, Default)]
+struct Stepping {
+    stepped: bool,
+    live: bool,
+}
+
+fn record_before(
+    mut commands: Commands,
+    mut moved: Query<(Entity, &Transform, Option<&mut Steps>), Or<(With<Ball>, With<Racket>)>>,
+) {
+    for (entity, transform, steps) in moved.iter_mut() {
+        match steps {
+            Some(mut steps) => steps.before = *transform,
+            None => {
+                commands.entity(entity).insert(Steps {
+                    before: *transform,
+                    after: *transform,
+                    drawn: false,
+                });
+            }
+        }
+    }
+}
+
+fn record_after(mut stepping: ResMut<Stepping>, mut moved: Query<(&Transform, &mut Steps)>) {
+    stepping.stepped = true;
+    for (transform, mut steps) in moved.iter_mut() {
+        steps.after = *transform;
+        if steps.before.translation.distance(steps.after.translation) > MAX_STEP_DISTANCE {
+            steps.before = steps.after;
+        }
+    }
+}
+
+fn end_step(mut stepping: ResMut<Stepping>) {
+    stepping.live = std::mem::take(&mut stepping.stepped);
+}
+
+fn draw_between(
+    stepping: Res<Stepping>,
+    time: Res<Time<Fixed>>,
+    mut moved: Query<(&mut Transform, &mut Steps)>,
+) {
+    if !stepping.live {
+        return;
+    }
+    // How far into the next step the clock is
+    let between = time.overstep_fraction().min(1.0);
+    for (mut transform, mut steps) in moved.iter_mut() {
+        // Something other than the simulation has moved it since
+        if transform.translation != steps.after.translation
+            || transform.rotation != steps.after.rotation
+        {
+            continue;
+        }
+        transform.translation = steps
+            .before
+            .translation
+            .lerp(steps.after.translation, between);
+        transform.rotation = steps.before.rotation.slerp(steps.after.rotation, between);
+        steps.drawn = true;
+    }
+}
+
+fn put_back(mut moved: Query<(&mut Transform, &mut Steps)>) {
+    for (mut transform, mut steps) in moved.iter_mut() {
+        if std::mem::take(&mut steps.drawn) {
+            transform.translation = steps.after.translation;
+            transform.rotation = steps.after.rotation;
+        }
+    }
+}
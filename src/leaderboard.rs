@@ -0,0 +1,394 @@
+//! The leaderboard: results of ranked online matches sent to a leaderboard
+//! server, and the best hundred players there on a screen of their own, off
+//! the network menu.
+//!
+//! Nothing is sent anywhere unless the player has turned sharing on in the
+//! settings, there's a server to send to, from `--leaderboard <url>`, and
+//! they're playing as a profile, whose name is what goes on the board. Quick
+//! matches are the ranked ones, as the lobby picked the two players rather
+//! than them picking each other.
+//!
+//! The server needs two things:
+//! - `POST <url>/results`, taking a [`MatchResult`] as JSON
+//! - `GET <url>/top?limit=100`, answering with a JSON list of [`Standing`]s,
+//!   best first
+//!
+//! Every request runs on a thread of its own, so however slow the server is,
+//! the game never waits for it.
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli_arg,
+    locale::Strings,
+    network::NetworkSettings,
+    profiles::Profiles,
+    protocol::{BUILD, PROTOCOL_VERSION},
+    settings::Settings,
+    sim::{MatchOver, Score},
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        TEXT_COLOR,
+    },
+    AppState, GameMode,
+};
+
+/// How many players the leaderboard screen asks for.
+pub const TOP_PLAYERS: usize = 100;
+// Lines in each of the screen's columns
+const COLUMN_LENGTH: usize = 25;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Leaderboard::from_args())
+            .add_systems(OnEnter(AppState::GameOver), submit_result)
+            .add_systems(Update, check_submissions)
+            .add_systems(
+                OnEnter(AppState::Leaderboard),
+                (fetch_top, spawn_leaderboard.after(fetch_top)),
+            )
+            .add_systems(
+                Update,
+                (show_top, leaderboard_action).run_if(in_state(AppState::Leaderboard)),
+            );
+    }
+}
+
+/// What's sent after a ranked match, by each of its players.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchResult {
+    pub player: String,
+    pub won: bool,
+    /// In the last set, which is the one that decided it
+    pub points: i32,
+    pub opponent_points: i32,
+    pub sets: i32,
+    pub opponent_sets: i32,
+    /// The same for both players, so the server can tell their two results
+    /// are of the same match
+    pub seed: u64,
+    pub build: String,
+    pub protocol: u32,
+}
+
+/// One player's line on the board.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Standing {
+    pub name: String,
+    pub rating: i32,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+/// A request going on in the background, and what came of it once it's done.
+pub struct Pending<T>(Arc<Mutex<Option<Result<T, String>>>>);
+
+impl<T: Send + 'static> Pending<T> {
+    fn start(request: impl FnOnce() -> Result<T, String> + Send + 'static) -> Self {
+        let answer = Arc::new(Mutex::new(None));
+        let slot = answer.clone();
+        thread::spawn(move || {
+            let result = request();
+            *slot.lock().unwrap() = Some(result);
+        });
+        Pending(answer)
+    }
+
+    /// What came of it, the once, if it's done.
+    fn take(&self) -> Option<Result<T, String>> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// How the list on the screen is coming along.
+pub enum TopList {
+    NotAsked,
+    Loading(Pending<Vec<Standing>>),
+    Loaded(Vec<Standing>),
+    Failed(String),
+}
+
+#[derive(Resource)]
+pub struct Leaderboard {
+    /// The server, without a slash at the end
+    pub url: Option<String>,
+    pub top: TopList,
+    /// Results on their way, with who they're for
+    submissions: Vec<(String, Pending<()>)>,
+}
+
+impl Leaderboard {
+    /// `--leaderboard <url>`
+    fn from_args() -> Self {
+        Leaderboard {
+            url: cli_arg("leaderboard").map(|url| url.trim_end_matches('/').to_string()),
+            top: TopList::NotAsked,
+            submissions: Vec::new(),
+        }
+    }
+
+    fn submit(&mut self, url: &str, result: MatchResult) {
+        let url = format!("{}/results", url);
+        let player = result.player.clone();
+        self.submissions.push((
+            player,
+            Pending::start(move || {
+                agent()
+                    .post(&url)
+                    .send_json(&result)
+                    .map(|_| ())
+                    .map_err(|error| error.to_string())
+            }),
+        ));
+    }
+
+    fn fetch(&mut self, url: &str) {
+        let url = format!("{}/top", url);
+        self.top = TopList::Loading(Pending::start(move || {
+            agent()
+                .get(&url)
+                .query("limit", &TOP_PLAYERS.to_string())
+                .call()
+                .map_err(|error| error.to_string())?
+                .into_json::<Vec<Standing>>()
+                .map_err(|error| error.to_string())
+        }));
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .user_agent(&format!("pingis/{}", BUILD))
+        .build()
+}
+
+/// Sends the result of a ranked match that's just finished, if the player's
+/// agreed to.
+fn submit_result(
+    mut leaderboard: ResMut<Leaderboard>,
+    settings: Res<Settings>,
+    network: Res<NetworkSettings>,
+    profiles: Res<Profiles>,
+    mode: Res<GameMode>,
+    match_over: Res<MatchOver>,
+    score: Res<Score>,
+) {
+    if *mode != GameMode::Online || !network.ranked || !settings.share_results {
+        return;
+    }
+    let (url, winner) = match (leaderboard.url.clone(), match_over.0) {
+        (Some(url), Some(winner)) => (url, winner),
+        _ => return,
+    };
+    let player_number = network.local_player as i32 + 1;
+    let player = match &profiles.seats[network.local_player] {
+        Some(name) => name.clone(),
+        None => {
+            info!("Not sending the result, as there's no profile to send it under");
+            return;
+        }
+    };
+
+    info!("Sending the result for {} to {}", player, url);
+    leaderboard.submit(
+        &url,
+        MatchResult {
+            player,
+            won: winner == player_number,
+            points: score.points(player_number),
+            opponent_points: score.points(3 - player_number),
+            sets: score.sets(player_number),
+            opponent_sets: score.sets(3 - player_number),
+            seed: network.seed,
+            build: BUILD.to_string(),
+            protocol: PROTOCOL_VERSION,
+        },
+    );
+}
+
+/// Says how each result went once it's through, or not.
+fn check_submissions(mut leaderboard: ResMut<Leaderboard>) {
+    leaderboard
+        .submissions
+        .retain(|(player, pending)| match pending.take() {
+            Some(Ok(())) => {
+                info!("Sent the result for {}", player);
+                false
+            }
+            Some(Err(error)) => {
+                warn!("Couldn't send the result for {}: {}", player, error);
+                false
+            }
+            None => true,
+        });
+}
+
+/// Where the list goes, once it's in.
+#[derive(Component)]
+struct TopListRoot;
+
+#[derive(Component)]
+struct BackButton;
+
+/// Asks for the list afresh every time the screen's opened.
+fn fetch_top(mut leaderboard: ResMut<Leaderboard>) {
+    if let Some(url) = leaderboard.url.clone() {
+        if !matches!(leaderboard.top, TopList::Loading(_)) {
+            leaderboard.fetch(&url);
+        }
+    }
+}
+
+fn spawn_leaderboard(mut commands: Commands, font: Res<UiFont>, strings: Res<Strings>) {
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Leaderboard)))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("leaderboard.title"), 48.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexStart,
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                },
+                TopListRoot,
+            ));
+            spawn_button(parent, &font, 0, strings.get("back")).insert(BackButton);
+        });
+}
+
+/// One player's line, like "1. Ada  1520  (12-3)".
+fn standing_line(rank: usize, standing: &Standing, strings: &Strings) -> String {
+    strings.fill(
+        "leaderboard.line",
+        &[
+            ("rank", &rank),
+            ("name", &standing.name),
+            ("rating", &standing.rating),
+            ("wins", &standing.wins),
+            ("losses", &standing.losses),
+        ],
+    )
+}
+
+/// Fills the list in once it's come, or says why it hasn't.
+fn show_top(
+    mut commands: Commands,
+    mut leaderboard: ResMut<Leaderboard>,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    roots: Query<(Entity, Ref<TopListRoot>)>,
+) {
+    let mut arrived = false;
+    if let TopList::Loading(pending) = &leaderboard.top {
+        arrived = match pending.take() {
+            Some(Ok(top)) => {
+                leaderboard.top = TopList::Loaded(top);
+                true
+            }
+            Some(Err(error)) => {
+                warn!("Couldn't get the leaderboard: {}", error);
+                leaderboard.top = TopList::Failed(error);
+                true
+            }
+            None => false,
+        };
+    }
+    let root = match roots.get_single() {
+        Ok((root, marker)) if arrived || marker.is_added() => root,
+        _ => return,
+    };
+
+    let message = match &leaderboard.top {
+        _ if leaderboard.url.is_none() => Some(strings.get("leaderboard.no_server").to_string()),
+        TopList::NotAsked | TopList::Loading(_) => {
+            Some(strings.get("leaderboard.loading").to_string())
+        }
+        TopList::Failed(error) => Some(strings.fill("leaderboard.failed", &[("error", error)])),
+        TopList::Loaded(top) if top.is_empty() => {
+            Some(strings.get("leaderboard.empty").to_string())
+        }
+        TopList::Loaded(_) => None,
+    };
+
+    commands.entity(root).despawn_descendants();
+    commands.entity(root).with_children(|parent| {
+        if let Some(message) = message {
+            parent.spawn(text(&font, message, 24.0, DIM_TEXT_COLOR));
+            return;
+        }
+        let top = match &leaderboard.top {
+            TopList::Loaded(top) => top,
+            _ => return,
+        };
+        let top = &top[..top.len().min(TOP_PLAYERS)];
+        for (column, standings) in top.chunks(COLUMN_LENGTH).enumerate() {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        margin: UiRect::new(
+                            Val::Px(16.0),
+                            Val::Px(16.0),
+                            Val::Px(0.0),
+                            Val::Px(0.0),
+                        ),
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (index, standing) in standings.iter().enumerate() {
+                        let rank = column * COLUMN_LENGTH + index + 1;
+                        // The top three stand out
+                        let color = if rank <= 3 { ACCENT_COLOR } else { TEXT_COLOR };
+                        parent.spawn(text(
+                            &font,
+                            standing_line(rank, standing, &strings),
+                            14.0,
+                            color,
+                        ));
+                    }
+                });
+        }
+    });
+}
+
+fn leaderboard_action(
+    mut activated: EventReader<ButtonActivated>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let back = activated
+        .read()
+        .any(|event| back_buttons.get(event.0).is_ok())
+        || keyboard_input.just_pressed(KeyCode::Escape);
+    if back {
+        // The network menu would take it for going back again
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Network);
+    }
+}
@@ -0,0 +1,1221 @@
+// Bevy queries with filters get long quickly, and systems take whatever they
+// need as arguments, which is fine
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+// Browsers have no UDP sockets to play online over and no files to load mods
+// from, and no system clock to ask the time without the wasm feature
+#[cfg(all(
+    target_arch = "wasm32",
+    any(feature = "networking", feature = "scripting")
+))]
+compile_error!("networking and scripting don't build for the browser");
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("browser builds need the wasm feature");
+
+pub mod achievements;
+mod actions;
+pub mod ai;
+#[cfg(feature = "audio")]
+mod announcer;
+pub mod arena;
+mod background;
+mod ballcam;
+pub mod bot;
+#[cfg(feature = "bots")]
+mod bots;
+mod camera;
+mod campaign;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+mod celebration;
+#[cfg(feature = "networking")]
+mod chat;
+mod cleanup;
+mod coach;
+#[cfg(feature = "devtools")]
+mod console;
+mod crash;
+mod crt;
+mod custom_match;
+mod dash;
+mod dashboard;
+mod demo;
+#[cfg(feature = "networking")]
+mod desync;
+#[cfg(feature = "devtools")]
+mod devtools;
+#[cfg(feature = "discord")]
+mod discord;
+mod display;
+mod doubles;
+mod drills;
+mod effects;
+#[cfg(feature = "devtools")]
+mod eventlog;
+mod flash;
+mod floating;
+#[cfg(feature = "devtools")]
+mod framestep;
+mod game_over;
+mod gameplay;
+mod glow;
+mod heatmap;
+mod history;
+mod hotplug;
+mod hud;
+mod idle;
+#[cfg(feature = "devtools")]
+mod inspector;
+mod interpolation;
+#[cfg(feature = "leaderboard")]
+mod leaderboard;
+mod loading;
+#[cfg(feature = "networking")]
+pub mod lobby;
+mod locale;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logging;
+mod menu;
+mod mods;
+mod mouse;
+#[cfg(feature = "netdebug")]
+mod netdebug;
+#[cfg(feature = "networking")]
+mod network;
+mod night;
+#[cfg(feature = "devtools")]
+mod overlay;
+mod pause;
+mod portals;
+mod practice;
+mod prob;
+#[cfg(feature = "devtools")]
+mod profiler;
+mod profiles;
+#[cfg(feature = "networking")]
+pub mod protocol;
+mod rating;
+mod recording;
+#[cfg(feature = "networking")]
+pub mod relay;
+mod replay;
+mod rumble;
+mod saved_match;
+mod scenes;
+#[cfg(feature = "scripting")]
+mod scripting;
+pub mod seams;
+mod season;
+mod settings;
+mod shake;
+pub mod sim;
+#[cfg(feature = "audio")]
+mod sonify;
+mod squash;
+mod stakes;
+mod stats;
+mod subsystems;
+mod survival;
+#[cfg(feature = "telemetry")]
+mod telemetry;
+mod theme;
+mod touch;
+mod tournament;
+mod trail;
+#[cfg(feature = "networking")]
+mod transport;
+mod tween;
+mod ui;
+mod wind;
+
+use achievements::AchievementsPlugin;
+use actions::{Action, Actions, ActionsPlugin};
+use ai::{AiPlugin, Cpu, Difficulty};
+use arena::ArenaPlugin;
+use background::BackgroundPlugin;
+use ballcam::BallCamPlugin;
+use bevy::{prelude::*, utils::Instant};
+use bot::BotPlugin;
+use camera::CameraPlugin;
+use campaign::CampaignPlugin;
+use celebration::{Celebration, CelebrationPlugin};
+use cleanup::CleanupPlugin;
+use coach::CoachPlugin;
+use crash::CrashPlugin;
+use crt::CrtPlugin;
+use custom_match::CustomMatchPlugin;
+use dash::DashPlugin;
+use dashboard::DashboardPlugin;
+use demo::DemoPlugin;
+use display::DisplayPlugin;
+use doubles::DoublesPlugin;
+use drills::DrillsPlugin;
+use effects::EffectsPlugin;
+use flash::FlashPlugin;
+use floating::FloatingTextPlugin;
+use game_over::GameOverPlugin;
+use gameplay::GameplayPlugin;
+use glow::GlowPlugin;
+use heatmap::HeatmapPlugin;
+use history::HistoryPlugin;
+use hotplug::HotplugPlugin;
+use hud::HudPlugin;
+use idle::IdlePlugin;
+use interpolation::InterpolationPlugin;
+use loading::LoadingPlugin;
+use locale::LocalePlugin;
+use menu::MenuPlugin;
+use mods::ModsPlugin;
+use mouse::MousePlugin;
+use night::NightPlugin;
+use pause::PausePlugin;
+use portals::PortalsPlugin;
+use practice::PracticePlugin;
+use profiles::{Profiles, ProfilesPlugin};
+use rating::RatingPlugin;
+use recording::{Playback, RecordingPlugin};
+use replay::{Replay, ReplayPlugin};
+use rumble::RumblePlugin;
+use saved_match::SavedMatchPlugin;
+use scenes::ScenesPlugin;
+use seams::read_input;
+use season::SeasonPlugin;
+use serde::{Deserialize, Serialize};
+use settings::{Settings, SettingsPlugin};
+use shake::ShakePlugin;
+use sim::{
+    spawn_ball, BallHitEvent, CollisionEvent, FixedStepPlugin, GameSpeed, GameplayConfig,
+    GoalEvent, MatchClock, MatchEndedEvent, MatchOver, MatchRules, MatchStats, Portals, Racket,
+    RacketLength, RacketSpeed, Rally, Score, ServeCallEvent, ServeEvent, SimInput, SimRng,
+    WallPath, Wind,
+};
+use squash::SquashPlugin;
+use stakes::StakesPlugin;
+use stats::StatsPlugin;
+use subsystems::SubsystemsPlugin;
+use survival::SurvivalPlugin;
+use theme::ThemePlugin;
+use touch::TouchPlugin;
+use tournament::TournamentPlugin;
+use trail::TrailPlugin;
+use tween::TweenPlugin;
+use ui::UiPlugin;
+use wind::WindPlugin;
+
+/// How long a blitz match lasts.
+const BLITZ_SECONDS: u32 = 120;
+
+/// The most simulation steps played in a single frame, catching up on a slow
+/// one, before the rest of the time it took is let go.
+const MAX_CATCH_UP_STEPS: u32 = 4;
+
+/// The whole game, for an app that already has Bevy's `DefaultPlugins`.
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        // First, so whatever goes wrong setting up the rest is reported
+        app.add_plugins(CrashPlugin)
+            .init_state::<AppState>()
+            .add_sub_state::<MatchState>()
+            .add_plugins(ActionsPlugin)
+            .add_plugins(CleanupPlugin)
+            .add_plugins(UiPlugin)
+            .add_plugins(LocalePlugin)
+            .add_plugins(LoadingPlugin)
+            // Before the scripting, which runs the scripts the mods bring
+            .add_plugins(ModsPlugin)
+            .add_plugins(TweenPlugin)
+            .add_plugins(MenuPlugin)
+            .add_plugins(ArenaPlugin)
+            .add_plugins(ScenesPlugin)
+            .add_plugins(GameplayPlugin)
+            .add_plugins(BackgroundPlugin)
+            .add_plugins(StatsPlugin)
+            .add_plugins(DashboardPlugin)
+            .add_plugins(HistoryPlugin)
+            .add_plugins(AchievementsPlugin)
+            .add_plugins(GameOverPlugin)
+            .add_plugins(HeatmapPlugin)
+            .add_plugins(HudPlugin)
+            .add_plugins(TournamentPlugin)
+            .add_plugins(CampaignPlugin)
+            .add_plugins(ProfilesPlugin)
+            .add_plugins(RatingPlugin)
+            .add_plugins(SeasonPlugin)
+            .add_plugins(DrillsPlugin)
+            .add_plugins(CustomMatchPlugin)
+            .add_plugins(BotPlugin)
+            .add_plugins(AiPlugin)
+            .add_plugins(IdlePlugin)
+            .add_plugins(DemoPlugin)
+            .add_plugins(SettingsPlugin)
+            .add_plugins(ThemePlugin)
+            .add_plugins(EffectsPlugin)
+            .add_plugins(FlashPlugin)
+            .add_plugins(FloatingTextPlugin)
+            .add_plugins(ShakePlugin)
+            .add_plugins(CameraPlugin)
+            .add_plugins(BallCamPlugin)
+            .add_plugins(CelebrationPlugin)
+            .add_plugins(CoachPlugin)
+            .add_plugins(ReplayPlugin)
+            .add_plugins(TrailPlugin)
+            .add_plugins(GlowPlugin)
+            .add_plugins(SquashPlugin)
+            .add_plugins(StakesPlugin)
+            .add_plugins(CrtPlugin)
+            .add_plugins(WindPlugin)
+            .add_plugins(PortalsPlugin)
+            .add_plugins(DashPlugin)
+            .add_plugins(NightPlugin)
+            .add_plugins(DoublesPlugin)
+            .add_plugins(DisplayPlugin)
+            .add_plugins(TouchPlugin)
+            .add_plugins(MousePlugin)
+            .add_plugins(PausePlugin)
+            .add_plugins(HotplugPlugin)
+            .add_plugins(RumblePlugin)
+            .add_plugins(SavedMatchPlugin)
+            .add_plugins(SubsystemsPlugin)
+            .init_resource::<MatchRules>()
+            .insert_resource(GameMode::Versus)
+            .init_resource::<Score>()
+            .init_resource::<Rally>()
+            .init_resource::<MatchStats>()
+            .init_resource::<MatchOver>()
+            .init_resource::<MatchClock>()
+            .init_resource::<SimInput>()
+            .init_resource::<SimRng>()
+            .init_resource::<Wind>()
+            .init_resource::<Portals>()
+            .init_resource::<MatchSeed>()
+            .init_resource::<StartingSeed>()
+            .init_resource::<MatchNames>()
+            .init_resource::<NextRules>()
+            .add_event::<CollisionEvent>()
+            .add_event::<GoalEvent>()
+            .add_event::<BallHitEvent>()
+            .add_event::<ServeEvent>()
+            .add_event::<ServeCallEvent>()
+            .add_event::<MatchEndedEvent>()
+            .add_systems(Startup, setup)
+            .add_systems(OnEnter(AppState::Playing), (start_match, set_game_speed))
+            .add_systems(
+                Update,
+                (
+                    read_input::<Actions>,
+                    handicap_rackets,
+                    leave_match,
+                    end_match,
+                )
+                    .run_if(in_state(MatchState::Running)),
+            )
+            // Local matches are stepped in `FixedUpdate` so the simulation goes at a
+            // fixed rate. Its time keeps draining while no match is running, so
+            // coming back from the menu doesn't fast-forward through the time spent there.
+            .add_plugins(FixedStepPlugin)
+            .configure_sets(
+                FixedUpdate,
+                SteppedLocally.run_if(in_state(MatchState::Running).and_then(stepped_locally)),
+            )
+            .add_systems(FixedUpdate, sim::systems().in_set(SteppedLocally))
+            // The ball machine fires along with the simulation, survival speeds the
+            // ball up with it, what's drawn is kept track of with it, and matches are
+            // recorded and played back with it, so it has to be set up first
+            .add_plugins(PracticePlugin)
+            .add_plugins(SurvivalPlugin)
+            .add_plugins(InterpolationPlugin)
+            .add_plugins(RecordingPlugin);
+        // Nothing can be saved in a browser
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugins(capture::CapturePlugin);
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    /// Everything in `assets` being loaded, before the menu comes up
+    #[default]
+    Loading,
+    Menu,
+    Playing,
+    GameOver,
+    Stats,
+    /// The most recent matches finished
+    History,
+    /// Every achievement, and when each was unlocked
+    Achievements,
+    /// What the mods brought, and what was wrong with them
+    Mods,
+    /// The replays saved, to watch one
+    Replays,
+    Settings,
+    /// Choosing who's playing
+    Profiles,
+    /// Entering the players of a tournament
+    TournamentSetup,
+    /// Between the matches of a tournament
+    Bracket,
+    /// The campaign's ladder of opponents, between its matches
+    Campaign,
+    /// Where every profile stands in the ranked season
+    Season,
+    /// Putting together the rules of a custom match
+    CustomMatch,
+    /// The practice drills, to run or edit one
+    Drills,
+    /// Making or changing a drill
+    DrillEditor,
+    #[cfg(feature = "networking")]
+    Network,
+    /// The best players on the leaderboard server
+    #[cfg(feature = "leaderboard")]
+    Leaderboard,
+}
+
+/// Whether the match being played is going or paused, which only means
+/// anything while [`AppState::Playing`]. Every match starts out running.
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::Playing)]
+pub enum MatchState {
+    #[default]
+    Running,
+    /// A local match paused, for the window having lost focus
+    Paused,
+}
+
+/// Leaves the state the game's in and enters it again, for a screen to be
+/// put together afresh. Setting the state it's already in doesn't.
+pub fn restart_state(world: &mut World) {
+    let state = *world.resource::<State<AppState>>().get();
+    let _ = world.try_run_schedule(OnExit(state));
+    cleanup::despawn_scoped(world, &state);
+    let _ = world.try_run_schedule(OnEnter(state));
+}
+
+/// The kind of match being played.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    Versus,
+    /// Versus with a pair of partners on each end, a racket each
+    Doubles,
+    /// Versus against the clock
+    Blitz,
+    /// Versus with lives, one lost for every ball missed
+    Arcade,
+    /// Player 1 against a ball machine, for as long as they like
+    Practice,
+    /// Player 1 against a wall, for as long as they can keep the ball going
+    Survival,
+    /// Versus, as one of the matches of a tournament
+    Tournament,
+    /// Player 1 against one of the CPUs on the campaign's ladder
+    Campaign,
+    Online,
+    /// The CPU playing itself while nobody's at the menu
+    Demo,
+}
+
+impl GameMode {
+    /// Where to go once a match is over or has been left.
+    pub fn after_match(self) -> AppState {
+        match self {
+            GameMode::Tournament => AppState::Bracket,
+            GameMode::Campaign => AppState::Campaign,
+            _ => AppState::Menu,
+        }
+    }
+
+    /// Whether modifiers, and scripted rules, change matches of this kind.
+    /// They only ever change the matches people play each other in.
+    pub fn takes_modifiers(self) -> bool {
+        matches!(
+            self,
+            GameMode::Versus
+                | GameMode::Doubles
+                | GameMode::Blitz
+                | GameMode::Arcade
+                | GameMode::Tournament
+        )
+    }
+
+    /// Key of its name in the [`Strings`](locale::Strings).
+    pub fn name_key(self) -> &'static str {
+        match self {
+            GameMode::Versus => "mode.versus",
+            GameMode::Doubles => "mode.doubles",
+            GameMode::Blitz => "mode.blitz",
+            GameMode::Arcade => "mode.arcade",
+            GameMode::Practice => "mode.practice",
+            GameMode::Survival => "mode.survival",
+            GameMode::Tournament => "mode.tournament",
+            GameMode::Campaign => "mode.campaign",
+            GameMode::Online => "mode.online",
+            GameMode::Demo => "mode.demo",
+        }
+    }
+}
+
+/// Seed for the next match's serves. Taken by the match when it starts; without
+/// one, the match uses `--seed` or the one in the settings, and failing those
+/// seeds itself from the clock.
+#[derive(Resource, Default)]
+pub struct MatchSeed(pub Option<u64>);
+
+/// The seed the match being played started with, for its replay.
+#[derive(Resource, Default)]
+pub struct StartingSeed(pub u64);
+
+/// Names of the next match's players. Taken by the match when it starts;
+/// without them, the players go by their profiles.
+#[derive(Resource, Default)]
+pub struct MatchNames(pub Option<[String; 2]>);
+
+/// Rules for the next match. Taken by the match when it starts; without them,
+/// the match goes by its mode and the settings.
+#[derive(Resource, Default)]
+pub struct NextRules(pub Option<MatchRules>);
+
+/// The value following `--<name>` on the command line.
+pub fn cli_arg(name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    std::env::args().skip_while(|arg| *arg != flag).nth(1)
+}
+
+/// The camera looking at the court, as opposed to any drawing its picture to the window.
+#[derive(Component)]
+struct MainCamera;
+
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+struct Player {
+    player_number: i32,
+    /// Which of the [`SimInput`]s their keys go to, see [`sim::Racket::seat`]
+    seat: usize,
+    name: String,
+    // Keys can't be reflected
+    #[reflect(ignore)]
+    movement_keys: MovementKeys,
+}
+
+/// What player 1 or 2 of `players` is called, or their number if they're
+/// not about.
+fn player_name<'a>(
+    players: impl IntoIterator<Item = &'a Player>,
+    player_number: i32,
+    strings: &locale::Strings,
+) -> String {
+    players
+        .into_iter()
+        .find(|player| player.player_number == player_number && player.seat < 2)
+        .map(|player| player.name.clone())
+        .unwrap_or_else(|| strings.fill("player", &[("number", &player_number)]))
+}
+
+#[derive(Debug)]
+struct MovementKeys {
+    up: KeyCode,
+    down: KeyCode,
+    smash: KeyCode,
+    tilt_up: KeyCode,
+    tilt_down: KeyCode,
+    dash: KeyCode,
+    pause: KeyCode,
+}
+
+// Only there for reflection, which wants a default for every component
+impl Default for MovementKeys {
+    fn default() -> Self {
+        MovementKeys {
+            up: KeyCode::KeyW,
+            down: KeyCode::KeyS,
+            smash: KeyCode::KeyD,
+            tilt_up: KeyCode::KeyQ,
+            tilt_down: KeyCode::KeyA,
+            dash: KeyCode::KeyE,
+            pause: KeyCode::Escape,
+        }
+    }
+}
+
+impl MovementKeys {
+    /// Which key presses which [`Action`].
+    fn bindings(&self) -> [(KeyCode, Action); 7] {
+        [
+            (self.up, Action::MoveUp),
+            (self.down, Action::MoveDown),
+            (self.smash, Action::Smash),
+            (self.tilt_up, Action::TiltUp),
+            (self.tilt_down, Action::TiltDown),
+            (self.dash, Action::Dash),
+            (self.pause, Action::Pause),
+        ]
+    }
+}
+
+fn setup(mut commands: Commands) {
+    // The UI goes on this camera, not the ball-cam or the filters drawn after it
+    commands.spawn((Camera2dBundle::default(), IsDefaultUiCamera, MainCamera));
+}
+
+/// Plays local matches at the speed the settings have. Online ones are timed
+/// by the rollback session, and go at the other end's pace as much as ours.
+fn set_game_speed(mode: Res<GameMode>, settings: Res<Settings>, mut speed: ResMut<GameSpeed>) {
+    *speed = match *mode {
+        // Nobody to slow it down for
+        GameMode::Online | GameMode::Demo => GameSpeed::default(),
+        _ => GameSpeed(settings.game_speed),
+    };
+}
+
+fn start_match(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    // Together, as a system can't take any more on their own
+    (settings, config, mut next_rules, mut starting_seed, strings): (
+        Res<Settings>,
+        Res<GameplayConfig>,
+        ResMut<NextRules>,
+        ResMut<StartingSeed>,
+        Res<locale::Strings>,
+    ),
+    mut rules: ResMut<MatchRules>,
+    mut score: ResMut<Score>,
+    mut rally: ResMut<Rally>,
+    mut stats: ResMut<MatchStats>,
+    mut match_over: ResMut<MatchOver>,
+    mut clock: ResMut<MatchClock>,
+    mut rng: ResMut<SimRng>,
+    mut wind: ResMut<Wind>,
+    mut portals: ResMut<Portals>,
+    mut seed: ResMut<MatchSeed>,
+    mut names: ResMut<MatchNames>,
+    profiles: Res<Profiles>,
+    mut serve_events: EventWriter<ServeEvent>,
+) {
+    *rules = match *mode {
+        GameMode::Versus | GameMode::Doubles | GameMode::Tournament => MatchRules {
+            points_to_win: settings.points_to_win,
+            best_of: settings.best_of,
+            golden_point: settings.golden_point,
+            alternate_hits: *mode == GameMode::Doubles && settings.doubles_turns,
+            ..default()
+        },
+        GameMode::Blitz => MatchRules::blitz(BLITZ_SECONDS),
+        GameMode::Arcade => MatchRules::arcade(settings.arcade_lives),
+        // Nobody wins against the ball machine
+        GameMode::Practice => MatchRules {
+            points_to_win: i32::MAX,
+            best_of: 1,
+            ..default()
+        },
+        // The first ball past the player ends it
+        GameMode::Survival => MatchRules {
+            points_to_win: 1,
+            win_by: 1,
+            best_of: 1,
+            ..default()
+        },
+        // Every rung of the ladder is a single set
+        GameMode::Campaign => MatchRules {
+            best_of: 1,
+            ..default()
+        },
+        GameMode::Online => MatchRules::default(),
+        GameMode::Demo => MatchRules {
+            best_of: 1,
+            ..default()
+        },
+    };
+    if mode.takes_modifiers() {
+        rules.wind = settings.wind;
+        rules.gravity = settings
+            .gravity
+            .then(|| settings.gravity_strength * sim::GRAVITY);
+        rules.portals = settings.portals;
+        rules.night = settings.night;
+        rules.moving_wall = settings.moving_wall.then_some(WallPath {
+            reach: settings.wall_reach,
+            period: settings.wall_period,
+        });
+        rules.center_net = settings.center_net;
+        rules.shrinking = settings.shrinking;
+        rules.serve_rules = settings.serve_rules;
+        rules.ball_speed = settings.ball_speed;
+        rules.racket_speed = settings.racket_speed;
+        rules.ball = settings.ball;
+        rules.handicaps = settings.handicaps;
+    }
+    // Both ends of an online match have to step alike
+    if *mode != GameMode::Online {
+        rules.time_step = settings.time_step.clamped();
+    }
+    // A rematch, or a saved match being carried on with, goes by the rules
+    // the match before it did
+    if let Some(next_rules) = next_rules.0.take() {
+        *rules = next_rules;
+    }
+    *clock = MatchClock::new(&rules);
+    *score = Score::starting(&rules);
+    *rally = Rally::default();
+    *stats = MatchStats::default();
+    *match_over = MatchOver::default();
+
+    let seed = seed
+        .0
+        .take()
+        .or_else(|| cli_arg("seed").and_then(|seed| seed.parse().ok()))
+        .or(settings.seed)
+        .unwrap_or_else(stats::now);
+    info!("Starting match with seed {}", seed);
+    starting_seed.0 = seed;
+    *rng = SimRng::new(seed);
+    *wind = if rules.wind {
+        Wind::new(&mut *rng)
+    } else {
+        Wind::default()
+    };
+    // They open where they will on the first step
+    *portals = Portals::default();
+
+    let [name1, name2] = names.0.take().unwrap_or_else(|| match *mode {
+        // The demo's players aren't anybody, and who's at the other end of an
+        // online match isn't known here
+        GameMode::Online | GameMode::Demo => [1, 2].map(|number| player_name([], number, &strings)),
+        _ => [profiles.name(1, &strings), profiles.name(2, &strings)],
+    });
+
+    let player1 = Player {
+        player_number: 1,
+        seat: 0,
+        name: name1,
+        movement_keys: MovementKeys {
+            up: KeyCode::KeyW,
+            down: KeyCode::KeyS,
+            smash: KeyCode::KeyD,
+            tilt_up: KeyCode::KeyQ,
+            tilt_down: KeyCode::KeyA,
+            dash: KeyCode::KeyE,
+            pause: KeyCode::Escape,
+        },
+    };
+
+    let player2 = Player {
+        player_number: 2,
+        seat: 1,
+        name: name2,
+        movement_keys: MovementKeys {
+            up: KeyCode::ArrowUp,
+            down: KeyCode::ArrowDown,
+            smash: KeyCode::ArrowLeft,
+            tilt_up: KeyCode::ShiftRight,
+            tilt_down: KeyCode::ControlRight,
+            dash: KeyCode::ArrowRight,
+            pause: KeyCode::Escape,
+        },
+    };
+
+    let player1 = commands
+        .spawn((player1, StateScoped(AppState::Playing)))
+        .id();
+    let player2 = commands
+        .spawn((player2, StateScoped(AppState::Playing)))
+        .id();
+    if *mode == GameMode::Demo {
+        // Mismatched, so that somebody misses now and then
+        commands.entity(player1).insert(Cpu::new(Difficulty::Hard));
+        commands
+            .entity(player2)
+            .insert(Cpu::new(Difficulty::Normal));
+    }
+    if *mode == GameMode::Doubles {
+        let partners = [
+            Player {
+                player_number: 1,
+                seat: 2,
+                name: player_name([], 3, &strings),
+                movement_keys: MovementKeys {
+                    up: KeyCode::KeyT,
+                    down: KeyCode::KeyG,
+                    smash: KeyCode::KeyH,
+                    tilt_up: KeyCode::KeyR,
+                    tilt_down: KeyCode::KeyF,
+                    dash: KeyCode::KeyY,
+                    pause: KeyCode::Escape,
+                },
+            },
+            Player {
+                player_number: 2,
+                seat: 3,
+                name: player_name([], 4, &strings),
+                movement_keys: MovementKeys {
+                    up: KeyCode::KeyI,
+                    down: KeyCode::KeyK,
+                    smash: KeyCode::KeyJ,
+                    tilt_up: KeyCode::KeyO,
+                    tilt_down: KeyCode::KeyL,
+                    dash: KeyCode::KeyU,
+                    pause: KeyCode::Escape,
+                },
+            },
+        ];
+        for partner in partners {
+            commands.spawn((partner, StateScoped(AppState::Playing)));
+        }
+    }
+
+    if rules.moving_wall.is_some() {
+        sim::spawn_moving_wall(&mut commands).insert(StateScoped(AppState::Playing));
+    }
+    if rules.center_net {
+        sim::spawn_center_net(&mut commands, &rules).insert(StateScoped(AppState::Playing));
+    }
+
+    // The ball machine serves its own
+    if *mode != GameMode::Practice {
+        spawn_ball(&mut commands, &rules, &config, &mut *rng, &mut serve_events)
+            .insert(StateScoped(AppState::Playing));
+    }
+}
+
+/// Gives every racket, whenever the arena spawns one, its player's handicap.
+fn handicap_rackets(
+    rules: Res<MatchRules>,
+    mut rackets: Query<(&Racket, &mut RacketLength, &mut RacketSpeed), Added<Racket>>,
+) {
+    for (racket, mut length, mut speed) in rackets.iter_mut() {
+        let handicap = rules.handicap(racket.player_number);
+        *length = RacketLength::new(length.full * handicap.racket_length);
+        speed.0 *= handicap.racket_speed;
+    }
+}
+
+/// Set the simulation's in during local matches, run when [`stepped_locally`]
+/// says so, for other systems to step along with it without asking again.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct SteppedLocally;
+
+/// Runs the simulation in [`FixedUpdate`] during local matches that aren't
+/// paused, other than while a goal's being celebrated or the winning one
+/// replayed, or it's been frozen to step through. Online matches are stepped
+/// by the rollback session instead.
+///
+/// No more than [`MAX_CATCH_UP_STEPS`] are played in a frame. A window that
+/// hasn't been drawn for a while, like one left in the background, comes back
+/// with all that time to make up, and past them it's drained without playing
+/// instead of the match being fast-forwarded through it.
+fn stepped_locally(
+    time: Res<Time<Real>>,
+    mut this_frame: Local<(Option<Instant>, u32)>,
+    mode: Res<GameMode>,
+    celebration: Res<Celebration>,
+    replay: Res<Replay>,
+    #[cfg(feature = "devtools")] frame_step: Res<framestep::FrameStep>,
+) -> bool {
+    let stepped = *mode != GameMode::Online && !celebration.is_playing() && !replay.is_playing();
+    let (frame, steps) = &mut *this_frame;
+    if *frame != time.last_update() {
+        *frame = time.last_update();
+        *steps = 0;
+    }
+    let stepped = stepped && *steps < MAX_CATCH_UP_STEPS;
+    // Only a step that would have been played uses up one let through, which
+    // `framestep` does along with the simulation
+    #[cfg(feature = "devtools")]
+    let stepped = stepped && frame_step.allows_step();
+    if stepped {
+        *steps += 1;
+    }
+    stepped
+}
+
+fn leave_match(
+    actions: Res<Actions>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mode: Res<GameMode>,
+    playback: Option<Res<Playback>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if actions.any_just_pressed(Action::Pause) {
+        // The menu it goes back to would take it for going back again
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(if playback.is_some() {
+            AppState::Replays
+        } else {
+            mode.after_match()
+        });
+    }
+}
+
+fn end_match(
+    match_over: Res<MatchOver>,
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    replay: Res<Replay>,
+    playback: Option<Res<Playback>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    // It's over once the winning point's been seen again
+    if replay.is_playing() {
+        return;
+    }
+    if let Some(winner) = match_over.0 {
+        info!("Match over! Player {} wins {:?}", winner, *score);
+        // Nobody's waiting to see how the demo went, and a replay's been seen
+        // to the end before
+        state.set(if playback.is_some() {
+            AppState::Replays
+        } else if *mode == GameMode::Demo {
+            mode.after_match()
+        } else {
+            AppState::GameOver
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::world::CommandQueue,
+        state::app::StatesPlugin,
+        window::{PrimaryWindow, WindowFocused},
+    };
+    use proptest::{
+        collection,
+        prelude::{prop_assert, prop_assert_eq, prop_oneof, proptest, Just, Strategy},
+        sample::select,
+        test_runner::TestCaseError,
+    };
+
+    use super::*;
+    use crate::{
+        arena::{spawn_layout, Arena, ArenaBounds},
+        sim::{extent, launch_ball, Ball, BallKind, RacketInput, BALL_SPEED},
+    };
+
+    /// Keys the harness presses: everyone's, and a few nobody's bound.
+    const KEYS: [KeyCode; 14] = [
+        KeyCode::KeyW,
+        KeyCode::KeyS,
+        KeyCode::KeyD,
+        KeyCode::KeyQ,
+        KeyCode::KeyA,
+        KeyCode::KeyE,
+        KeyCode::Escape,
+        KeyCode::ArrowUp,
+        KeyCode::ArrowDown,
+        KeyCode::ArrowLeft,
+        KeyCode::ShiftRight,
+        KeyCode::ControlRight,
+        KeyCode::ArrowRight,
+        KeyCode::Space,
+    ];
+
+    /// Something done to the match over one frame.
+    #[derive(Debug, Clone)]
+    enum Poke {
+        /// Holds just these keys
+        Hold(Vec<KeyCode>),
+        /// The window losing focus, which pauses the match
+        LoseFocus,
+        /// Picking Resume on the pause menu
+        Resume,
+        /// Starting the match again after it's been left
+        Play,
+        /// Binds one of a player's actions, by its place in
+        /// [`MovementKeys::bindings`], to another key
+        Rebind {
+            player_number: i32,
+            action: usize,
+            key: KeyCode,
+        },
+    }
+
+    fn poke() -> impl Strategy<Value = Poke> {
+        let key = || select(KEYS.to_vec());
+        prop_oneof![
+            // Mostly keys, often several that fight each other, like up and
+            // down together
+            8 => collection::vec(key(), 0..6).prop_map(Poke::Hold),
+            1 => Just(Poke::LoseFocus),
+            1 => Just(Poke::Resume),
+            1 => Just(Poke::Play),
+            1 => (1..=2, 0..7usize, key()).prop_map(|(player_number, action, key)| {
+                Poke::Rebind { player_number, action, key }
+            }),
+        ]
+    }
+
+    fn rebind(keys: &mut MovementKeys, action: usize, key: KeyCode) {
+        let bound = match action {
+            0 => &mut keys.up,
+            1 => &mut keys.down,
+            2 => &mut keys.smash,
+            3 => &mut keys.tilt_up,
+            4 => &mut keys.tilt_down,
+            5 => &mut keys.dash,
+            _ => &mut keys.pause,
+        };
+        *bound = key;
+    }
+
+    /// A local match with nothing but the input, pausing and the simulation
+    /// in it, and the two players' usual keys.
+    fn harness() -> App {
+        let bytes = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/arenas/classic.arena.ron"
+        ))
+        .unwrap();
+        let arena: Arena = ron::de::from_bytes(&bytes).unwrap();
+
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .insert_state(AppState::Playing)
+            .add_sub_state::<MatchState>()
+            .add_plugins(ActionsPlugin)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<Settings>()
+            .insert_resource(GameMode::Versus)
+            .insert_resource(MatchRules {
+                points_to_win: 1000,
+                ..default()
+            })
+            .insert_resource(arena.bounds())
+            .init_resource::<Score>()
+            .init_resource::<Rally>()
+            .init_resource::<MatchStats>()
+            .init_resource::<GameplayConfig>()
+            .init_resource::<MatchOver>()
+            .init_resource::<MatchClock>()
+            .init_resource::<SimInput>()
+            .init_resource::<Wind>()
+            .init_resource::<Portals>()
+            .insert_resource(SimRng::new(0x5eed))
+            .add_event::<WindowFocused>()
+            .add_event::<CollisionEvent>()
+            .add_event::<GoalEvent>()
+            .add_event::<BallHitEvent>()
+            .add_event::<ServeEvent>()
+            .add_event::<ServeCallEvent>()
+            .add_event::<MatchEndedEvent>()
+            .add_systems(
+                Update,
+                (
+                    pause::pause_on_focus_loss,
+                    (read_input::<Actions>, leave_match).run_if(in_state(MatchState::Running)),
+                    sim::systems()
+                        .run_if(in_state(MatchState::Running))
+                        .after(read_input::<Actions>),
+                ),
+            );
+        sim::defer_commands(&mut app, Update);
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, app.world());
+        spawn_layout(&mut commands, &arena);
+        launch_ball(
+            &mut commands,
+            BallKind::Standard,
+            Vec2::ZERO,
+            Vec2::new(BALL_SPEED, 100.0),
+        );
+        queue.apply(app.world_mut());
+        app.world_mut().spawn((Window::default(), PrimaryWindow));
+
+        app.world_mut().spawn(Player {
+            player_number: 1,
+            seat: 0,
+            name: "Player 1".to_string(),
+            movement_keys: MovementKeys {
+                up: KeyCode::KeyW,
+                down: KeyCode::KeyS,
+                smash: KeyCode::KeyD,
+                tilt_up: KeyCode::KeyQ,
+                tilt_down: KeyCode::KeyA,
+                dash: KeyCode::KeyE,
+                pause: KeyCode::Escape,
+            },
+        });
+        app.world_mut().spawn(Player {
+            player_number: 2,
+            seat: 1,
+            name: "Player 2".to_string(),
+            movement_keys: MovementKeys {
+                up: KeyCode::ArrowUp,
+                down: KeyCode::ArrowDown,
+                smash: KeyCode::ArrowLeft,
+                tilt_up: KeyCode::ShiftRight,
+                tilt_down: KeyCode::ControlRight,
+                dash: KeyCode::ArrowRight,
+                pause: KeyCode::Escape,
+            },
+        });
+        app
+    }
+
+    /// Does `poke` to the match, before the frame it's done in.
+    fn apply(app: &mut App, poke: &Poke, held: &mut Vec<KeyCode>) {
+        match poke {
+            Poke::Hold(keys) => held.clone_from(keys),
+            Poke::LoseFocus => {
+                let window = app
+                    .world_mut()
+                    .query_filtered::<Entity, With<PrimaryWindow>>()
+                    .single(app.world());
+                app.world_mut().send_event(WindowFocused {
+                    window,
+                    focused: false,
+                });
+            }
+            Poke::Resume => {
+                let paused = app
+                    .world()
+                    .get_resource::<State<MatchState>>()
+                    .is_some_and(|state| *state.get() == MatchState::Paused);
+                if paused {
+                    app.world_mut()
+                        .resource_mut::<NextState<MatchState>>()
+                        .set(MatchState::Running);
+                }
+            }
+            Poke::Play => {
+                if *app.world().resource::<State<AppState>>().get() == AppState::Menu {
+                    app.world_mut()
+                        .resource_mut::<NextState<AppState>>()
+                        .set(AppState::Playing);
+                }
+            }
+            Poke::Rebind {
+                player_number,
+                action,
+                key,
+            } => {
+                let mut players = app.world_mut().query::<&mut Player>();
+                for mut player in players.iter_mut(app.world_mut()) {
+                    if player.player_number == *player_number {
+                        rebind(&mut player.movement_keys, *action, *key);
+                    }
+                }
+            }
+        }
+
+        // Nothing but the harness touches the keys, so it lets go of the
+        // last frame's itself
+        let mut keyboard_input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keyboard_input.release_all();
+        keyboard_input.clear();
+        for key in held.iter() {
+            keyboard_input.press(*key);
+        }
+    }
+
+    /// What the racket at each seat should be asked to do, with `held` down.
+    fn expected_input(app: &mut App, held: &[KeyCode]) -> [RacketInput; 2] {
+        let mut expected = [RacketInput::default(); 2];
+        let mut players = app.world_mut().query::<&Player>();
+        for player in players.iter(app.world()) {
+            let input = &mut expected[player.seat];
+            for (key, action) in player.movement_keys.bindings() {
+                if !held.contains(&key) {
+                    continue;
+                }
+                match action {
+                    Action::MoveUp => input.up = true,
+                    Action::MoveDown => input.down = true,
+                    Action::Smash => input.smash = true,
+                    Action::TiltUp => input.tilt_up = true,
+                    Action::TiltDown => input.tilt_down = true,
+                    Action::Dash => input.dash = true,
+                    Action::Pause => {}
+                }
+            }
+        }
+        expected
+    }
+
+    /// Whether the match is being played, neither paused nor left. States
+    /// are entered at the start of the frame after the one they're set in, so
+    /// after a frame it's what that frame played in.
+    fn running(app: &App) -> bool {
+        app.world()
+            .get_resource::<State<MatchState>>()
+            .is_some_and(|state| *state.get() == MatchState::Running)
+    }
+
+    fn ball_positions(app: &mut App) -> Vec<Vec3> {
+        let mut balls = app.world_mut().query_filtered::<&Transform, With<Ball>>();
+        balls
+            .iter(app.world())
+            .map(|ball| ball.translation)
+            .collect()
+    }
+
+    /// Plays a frame after each of `pokes`, checking after every one that
+    /// what's held is what the simulation's asked, that rackets stay on the
+    /// court, and that nothing moves while the match isn't being played.
+    fn fuzz(pokes: &[Poke]) -> Result<(), TestCaseError> {
+        let mut app = harness();
+        let bounds = *app.world().resource::<ArenaBounds>();
+        let mut held = Vec::new();
+        for poke in pokes {
+            apply(&mut app, poke, &mut held);
+            let running_before = running(&app);
+            let balls_before = ball_positions(&mut app);
+            app.update();
+            let running_after = running(&app);
+
+            let expected = expected_input(&mut app, &held);
+            let actions = app.world().resource::<Actions>();
+            for (seat, expected) in expected.iter().enumerate() {
+                prop_assert_eq!(actions.racket_input(seat), *expected);
+            }
+            if running_before && running_after {
+                let input = app.world().resource::<SimInput>();
+                prop_assert_eq!(&input.0[..2], &expected[..]);
+            }
+            if !running_before && !running_after {
+                prop_assert_eq!(ball_positions(&mut app), balls_before);
+            }
+
+            let mut rackets = app.world_mut().query_filtered::<&Transform, With<Racket>>();
+            for racket in rackets.iter(app.world()) {
+                let half_height = extent(racket).y / 2.0;
+                prop_assert!(racket.translation.y - half_height >= bounds.min.y - 1e-3);
+                prop_assert!(racket.translation.y + half_height <= bounds.max.y + 1e-3);
+            }
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn no_input_breaks_the_match(pokes in collection::vec(poke(), 1..400)) {
+            fuzz(&pokes)?;
+        }
+
+        #[test]
+        fn mashing_pause_and_resume_breaks_nothing(
+            pokes in collection::vec(
+                prop_oneof![
+                    Just(Poke::LoseFocus),
+                    Just(Poke::Resume),
+                    Just(Poke::Play),
+                    Just(Poke::Hold(vec![KeyCode::Escape])),
+                    Just(Poke::Hold(vec![
+                        KeyCode::KeyW,
+                        KeyCode::KeyS,
+                        KeyCode::ArrowUp,
+                        KeyCode::ArrowDown,
+                    ])),
+                ],
+                1..400,
+            ),
+        ) {
+            fuzz(&pokes)?;
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! The screen the game starts on, while everything in `assets` it might need
+//! is loaded: fonts, themes and their textures, the announcers' voices, and
+//! the rest, along with what the mods brought. The menu only comes up once
+//! it's all there, so nothing's loaded for the first time in the middle of a
+//! rally, to stutter or go missing for a frame. It's all kept loaded from
+//! then on.
+//!
+//! Anything that fails to load is left out, to fail again wherever it's used.
+//! In a browser, where folders can't be listed, nothing's loaded up front.
+
+use bevy::{asset::RecursiveDependencyLoadState, prelude::*};
+
+use crate::{
+    locale::Strings,
+    mods::Mods,
+    ui::{screen_root, text, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR, PANEL_COLOR},
+    AppState,
+};
+
+/// The folders in `assets` loaded before the menu, and everything in them.
+const PRELOADED: &[&str] = &[
+    "fonts",
+    "locales",
+    "themes",
+    "announcers",
+    "arenas",
+    "personalities",
+    "scenes",
+    "shaders",
+];
+const BAR_SIZE: Vec2 = Vec2::new(400.0, 12.0);
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Loading), (preload, spawn_loading_screen))
+            .add_systems(Update, wait_for_assets.run_if(in_state(AppState::Loading)));
+    }
+}
+
+/// Everything loaded before the menu, kept so it stays loaded.
+#[derive(Resource)]
+struct Preloaded(Vec<UntypedHandle>);
+
+/// Fills up as the assets are loaded.
+#[derive(Component)]
+struct ProgressBar;
+
+fn preload(mut commands: Commands, asset_server: Res<AssetServer>, mods: Res<Mods>) {
+    // A folder's loaded once everything in it is
+    let mut handles: Vec<UntypedHandle> = PRELOADED
+        .iter()
+        .map(|folder| asset_server.load_folder(*folder).untyped())
+        .collect();
+    handles.extend(
+        mods.asset_paths()
+            .map(|path| asset_server.load_untyped(path).untyped()),
+    );
+    info!("Preloading {} folders and mod assets", handles.len());
+    commands.insert_resource(Preloaded(handles));
+}
+
+fn spawn_loading_screen(mut commands: Commands, font: Res<UiFont>, strings: Res<Strings>) {
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Loading)))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("loading.title"), 28.0, DIM_TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(12.0)),
+                    ..default()
+                }),
+            );
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(BAR_SIZE.x),
+                        height: Val::Px(BAR_SIZE.y),
+                        ..default()
+                    },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: ACCENT_COLOR.into(),
+                            ..default()
+                        },
+                        ProgressBar,
+                    ));
+                });
+        });
+}
+
+/// How much of `states` is done with, loaded or not, from 0 to 1. Nothing at
+/// all to load is all done.
+fn progress(states: impl Iterator<Item = RecursiveDependencyLoadState>) -> f32 {
+    let (mut done, mut total) = (0, 0);
+    for state in states {
+        total += 1;
+        if matches!(
+            state,
+            RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed
+        ) {
+            done += 1;
+        }
+    }
+    if total == 0 {
+        1.0
+    } else {
+        done as f32 / total as f32
+    }
+}
+
+fn wait_for_assets(
+    asset_server: Res<AssetServer>,
+    preloaded: Option<Res<Preloaded>>,
+    mut bars: Query<&mut Style, With<ProgressBar>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let preloaded = match preloaded {
+        Some(preloaded) => preloaded,
+        None => return,
+    };
+    let progress = progress(
+        preloaded
+            .0
+            .iter()
+            .map(|handle| asset_server.recursive_dependency_load_state(handle.id())),
+    );
+    for mut style in bars.iter_mut() {
+        style.width = Val::Percent(progress * 100.0);
+    }
+    if progress >= 1.0 {
+        info!("Everything's loaded");
+        state.set(AppState::Menu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_count_as_done() {
+        let states = [
+            RecursiveDependencyLoadState::Loaded,
+            RecursiveDependencyLoadState::Failed,
+            RecursiveDependencyLoadState::Loading,
+        ];
+        assert_eq!(progress(states.into_iter()), 2.0 / 3.0);
+        assert_eq!(progress(std::iter::empty()), 1.0);
+    }
+}
@@ -0,0 +1,217 @@
+//! Quick matches: finding someone to play online through a `pingis-lobby`.
+//!
+//! The client keeps telling the lobby it's looking for a game until the lobby
+//! has a second player looking too. It then tells each of them where the
+//! other one is, which player they are and what seed to serve from, and the
+//! two start an ordinary peer-to-peer match with each other. The lobby only
+//! sees any of the match itself if they can't reach each other directly, and
+//! it has to pass their packets on between them.
+//!
+//! The lobby is asked from the port the match is then played on, so the
+//! address it passes on is the one the match's packets will come from.
+
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    locale::Strings,
+    network::{Link, NetworkSettings},
+    relay::{decode, encode, receive_all, TIMEOUT},
+    AppState, GameMode, MatchSeed,
+};
+
+// Seconds between asks while waiting, which also tell the lobby the client's
+// still there
+const SEEK_RETRY: f64 = 1.0;
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<QuickMatch>()
+            .add_systems(
+                Update,
+                (seek_match, show_status.after(seek_match)).run_if(in_state(AppState::Network)),
+            )
+            .add_systems(OnExit(AppState::Network), stop_seeking);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SeekerMessage {
+    /// Looking for a game, sent again every so often until there's one
+    Seek,
+    /// Not any more
+    Cancel,
+    /// To be passed on to the player the lobby paired them with, when the
+    /// two can't reach each other directly (see [`transport`](crate::transport))
+    Relay(Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LobbyMessage {
+    /// Nobody else is looking yet
+    Waiting,
+    /// Somebody is, and here they are
+    Matched {
+        peer: SocketAddr,
+        player_number: i32,
+        seed: u64,
+    },
+    /// Passed on from the player they were paired with
+    Relayed(Vec<u8>),
+}
+
+/// How the search for a quick match is going.
+#[derive(Resource, Default)]
+pub enum QuickMatch {
+    #[default]
+    Idle,
+    Seeking(Seeker),
+    /// The lobby went quiet, or never answered at all
+    NoAnswer,
+}
+
+impl QuickMatch {
+    /// Starts asking the lobby in `settings` for a game, from the port the
+    /// match will be played on.
+    pub fn seek(settings: &NetworkSettings, now: f64) -> io::Result<Self> {
+        let lobby = settings
+            .lobby
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no lobby to ask"))?;
+        let socket = UdpSocket::bind(("0.0.0.0", settings.local_port))?;
+        socket.set_nonblocking(true)?;
+
+        info!("Looking for a match at {}", lobby);
+        let seeker = Seeker {
+            socket,
+            lobby,
+            started: now,
+            last_sent: now,
+            last_heard: None,
+        };
+        seeker.send(&SeekerMessage::Seek);
+        Ok(QuickMatch::Seeking(seeker))
+    }
+
+    pub fn is_seeking(&self) -> bool {
+        matches!(self, QuickMatch::Seeking(_))
+    }
+
+    /// Stops looking, letting the lobby know.
+    pub fn cancel(&mut self) {
+        if let QuickMatch::Seeking(seeker) = self {
+            seeker.send(&SeekerMessage::Cancel);
+        }
+        *self = QuickMatch::Idle;
+    }
+}
+
+pub struct Seeker {
+    socket: UdpSocket,
+    lobby: SocketAddr,
+    started: f64,
+    last_sent: f64,
+    /// When the lobby last answered, if it has yet
+    last_heard: Option<f64>,
+}
+
+impl Seeker {
+    fn send(&self, message: &SeekerMessage) {
+        if let Err(error) = self.socket.send_to(&encode(message), self.lobby) {
+            warn!("Couldn't reach the lobby: {}", error);
+        }
+    }
+}
+
+/// What the network menu says about the search, under its buttons.
+#[derive(Component)]
+pub struct LobbyStatus;
+
+/// Keeps asking the lobby, and starts the match as soon as it's found one.
+fn seek_match(
+    mut quick_match: ResMut<QuickMatch>,
+    mut settings: ResMut<NetworkSettings>,
+    mut mode: ResMut<GameMode>,
+    mut seed: ResMut<MatchSeed>,
+    mut state: ResMut<NextState<AppState>>,
+    time: Res<Time>,
+) {
+    let seeker = match &mut *quick_match {
+        QuickMatch::Seeking(seeker) => seeker,
+        _ => return,
+    };
+    let now = time.elapsed_seconds_f64();
+
+    for (address, packet) in receive_all(&seeker.socket) {
+        if address != seeker.lobby {
+            continue;
+        }
+        seeker.last_heard = Some(now);
+        match decode(&packet) {
+            Some(LobbyMessage::Waiting | LobbyMessage::Relayed(_)) => {}
+            Some(LobbyMessage::Matched {
+                peer,
+                player_number,
+                seed: match_seed,
+            }) => {
+                info!("Matched with {} as player {}", peer, player_number);
+                settings.relay = Some(seeker.lobby);
+                settings.ranked = true;
+                // The match binds the same port, so this one has to go first
+                *quick_match = QuickMatch::Idle;
+                settings.link = Link::Peer;
+                settings.peer = Some(peer);
+                settings.local_player = (player_number.clamp(1, 2) - 1) as usize;
+                settings.seed = match_seed;
+                *mode = GameMode::Online;
+                seed.0 = Some(match_seed);
+                state.set(AppState::Playing);
+                return;
+            }
+            None => debug!("Ignoring a garbled packet"),
+        }
+    }
+
+    if now - seeker.last_heard.unwrap_or(seeker.started) > TIMEOUT {
+        warn!("The lobby didn't answer");
+        *quick_match = QuickMatch::NoAnswer;
+    } else if now - seeker.last_sent > SEEK_RETRY {
+        seeker.last_sent = now;
+        seeker.send(&SeekerMessage::Seek);
+    }
+}
+
+fn show_status(
+    quick_match: Res<QuickMatch>,
+    strings: Res<Strings>,
+    time: Res<Time>,
+    mut texts: Query<&mut Text, With<LobbyStatus>>,
+) {
+    let status = match &*quick_match {
+        QuickMatch::Idle => String::new(),
+        QuickMatch::Seeking(Seeker {
+            last_heard: None, ..
+        }) => strings.get("online.contacting").to_string(),
+        QuickMatch::Seeking(seeker) => {
+            let seconds = (time.elapsed_seconds_f64() - seeker.started) as u64;
+            strings.fill("online.waiting", &[("seconds", &seconds)])
+        }
+        QuickMatch::NoAnswer => strings.get("online.no_answer").to_string(),
+    };
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+/// Leaving the menu before a match is found stops looking for one.
+fn stop_seeking(mut quick_match: ResMut<QuickMatch>) {
+    quick_match.cancel();
+}
@@ -0,0 +1,259 @@
+//! Everything the menus and the HUD say, in the language picked in the
+//! settings, read from `.locale.ron` files in `assets/locales` that map keys
+//! like `"menu.play"` to what's shown for them. Adding a language is adding a
+//! file: every file there is offered in the settings, named by what it calls
+//! its own language.
+//!
+//! English is built in, so there's something to say before any file's been
+//! loaded, and anything a language has no line for is said in English.
+//! Lines can have `{name}`s in them for [`Strings::fill`] to fill in.
+
+use std::fmt::Display;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadedFolder},
+    prelude::*,
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+use crate::{restart_state, settings::Settings, AppState};
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+const ENGLISH: &str = include_str!("../assets/locales/en.locale.ron");
+
+pub struct LocalePlugin;
+
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Locale>()
+            .init_asset_loader::<LocaleLoader>()
+            .insert_resource(Strings::english())
+            .add_systems(Startup, load_locales)
+            .add_systems(Update, select_locale)
+            .add_systems(Update, switch_locale.after(select_locale))
+            .add_systems(Update, respawn_screen.after(switch_locale));
+    }
+}
+
+/// A language: what it calls itself, and its line for each key.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Locale {
+    pub name: String,
+    pub strings: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct LocaleLoader;
+
+impl AssetLoader for LocaleLoader {
+    type Asset = Locale;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Locale, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["locale.ron"]
+    }
+}
+
+/// Every language in `assets/locales`, kept loaded so the settings can offer
+/// them.
+#[derive(Resource)]
+pub struct Locales {
+    folder: Handle<LoadedFolder>,
+}
+
+impl Locales {
+    /// The name of every language's file, in alphabetical order, once the
+    /// folder's loaded.
+    pub fn languages(
+        &self,
+        asset_server: &AssetServer,
+        folders: &Assets<LoadedFolder>,
+    ) -> Vec<String> {
+        let folder = match folders.get(&self.folder) {
+            Some(folder) => folder,
+            None => return Vec::new(),
+        };
+        let mut languages: Vec<String> = folder
+            .handles
+            .iter()
+            .filter_map(|handle| asset_server.get_path(handle.id()))
+            .filter_map(|path| {
+                let file = path.path().file_name()?.to_str()?;
+                Some(file.strip_suffix(".locale.ron")?.to_string())
+            })
+            .collect();
+        languages.sort_unstable();
+        languages
+    }
+}
+
+/// What everything on screen is said with: the language in use, with English
+/// for whatever it doesn't have.
+#[derive(Resource)]
+pub struct Strings {
+    english: Locale,
+    chosen: Option<Locale>,
+}
+
+impl Strings {
+    fn english() -> Self {
+        Strings {
+            english: ron::from_str(ENGLISH).expect("the built in English doesn't parse"),
+            chosen: None,
+        }
+    }
+
+    /// What the language in use calls itself.
+    pub fn language(&self) -> &str {
+        &self.chosen.as_ref().unwrap_or(&self.english).name
+    }
+
+    /// The line for `key`, or the key itself if there's none, to stand out
+    /// as missing.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.chosen
+            .iter()
+            .chain([&self.english])
+            .find_map(|locale| locale.strings.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// The line for `key`, with every `{name}` in it replaced by its value
+    /// in `values`.
+    pub fn fill(&self, key: &str, values: &[(&str, &dyn Display)]) -> String {
+        let mut line = self.get(key).to_string();
+        for (name, value) in values {
+            line = line.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        line
+    }
+
+    /// The line for `count` of something: `<key>.one` for one of it and
+    /// `<key>.other` otherwise, with `{count}` filled in.
+    pub fn count(&self, key: &str, count: u32) -> String {
+        let form = if count == 1 { "one" } else { "other" };
+        self.fill(&format!("{}.{}", key, form), &[("count", &count)])
+    }
+
+    /// "On" or "Off".
+    pub fn on_off(&self, on: bool) -> &str {
+        self.get(if on { "on" } else { "off" })
+    }
+}
+
+fn load_locales(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // A missing folder is only logged, leaving English the only language
+    let folder = asset_server.load_folder("locales");
+    commands.insert_resource(Locales { folder });
+}
+
+/// The language picked in the settings, which may still be loading.
+#[derive(Resource)]
+struct SelectedLocale(Handle<Locale>);
+
+fn select_locale(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mut current: Local<Option<String>>,
+) {
+    if current.as_deref() == Some(settings.language.as_str()) {
+        return;
+    }
+    *current = Some(settings.language.clone());
+
+    // `<language>` is `assets/locales/<language>.locale.ron`
+    let handle = asset_server.load(format!("locales/{}.locale.ron", settings.language));
+    commands.insert_resource(SelectedLocale(handle));
+}
+
+fn switch_locale(
+    mut events: EventReader<AssetEvent<Locale>>,
+    locales: Res<Assets<Locale>>,
+    selected: Option<Res<SelectedLocale>>,
+    mut strings: ResMut<Strings>,
+    mut pending: Local<bool>,
+) {
+    let selected = match selected {
+        Some(selected) => selected,
+        None => return,
+    };
+    // Switch as soon as the language is there, and again whenever its file
+    // changes
+    let modified = events.read().any(|event| match event {
+        AssetEvent::Modified { id } => *id == selected.0.id(),
+        _ => false,
+    });
+    if selected.is_changed() || modified {
+        *pending = true;
+    }
+    if !*pending {
+        return;
+    }
+
+    if let Some(locale) = locales.get(&selected.0) {
+        info!("Speaking {}", locale.name);
+        strings.chosen = Some(locale.clone());
+        *pending = false;
+    }
+}
+
+/// Says what's on screen again in a new language. Only the menu and the
+/// settings are redone, as the settings are where it's changed and the menu
+/// is what's showing when the game starts. Anything else shows up in the new
+/// language the next time it's opened.
+fn respawn_screen(mut commands: Commands, strings: Res<Strings>, state: Res<State<AppState>>) {
+    if !strings.is_changed() || strings.is_added() {
+        return;
+    }
+    if matches!(state.get(), AppState::Menu | AppState::Settings) {
+        commands.add(restart_state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn every_language_has_a_line_for_every_key() {
+        let english = Strings::english().english;
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/locales");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let locale: Locale = ron::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            for key in english.strings.keys() {
+                assert!(
+                    locale.strings.contains_key(key),
+                    "{:?} has no {}",
+                    path,
+                    key
+                );
+            }
+            for key in locale.strings.keys() {
+                assert!(
+                    english.strings.contains_key(key),
+                    "{:?} has {} too",
+                    path,
+                    key
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Logging, in place of Bevy's `LogPlugin`, and tracing where the time goes.
+//!
+//! What's logged is picked by `--log <filter>`, or failing that `RUST_LOG`,
+//! in the usual `info,pingis::network=debug` form. Builds with the `trace`
+//! feature have a span for every system, besides the ones the physics, the
+//! CPUs and the netcode open of their own, and `--trace <file>` writes them
+//! all to a Chrome trace, to open in `chrome://tracing` or Perfetto.
+
+use bevy::{prelude::*, utils::tracing::subscriber};
+use tracing_log::LogTracer;
+use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter, Registry};
+
+use crate::cli_arg;
+
+/// What's logged without `--log` or `RUST_LOG`. wgpu says a lot otherwise.
+pub const DEFAULT_FILTER: &str = "info,wgpu=error";
+
+/// Sets up logging, before anything else is added, so nothing goes unlogged.
+pub struct LoggingPlugin;
+
+impl Plugin for LoggingPlugin {
+    // The app's only needed to keep a trace's guard
+    #[cfg_attr(not(feature = "trace"), allow(unused_variables))]
+    fn build(&self, app: &mut App) {
+        let filter = cli_arg("log")
+            .or_else(|| std::env::var("RUST_LOG").ok())
+            .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+        let filter = EnvFilter::try_new(&filter).unwrap_or_else(|error| {
+            eprintln!("Ignoring the log filter {:?}: {}", filter, error);
+            EnvFilter::new(DEFAULT_FILTER)
+        });
+        let logged = Registry::default().with(filter).with(fmt::Layer::default());
+
+        #[cfg(feature = "trace")]
+        let logged = logged.with(cli_arg("trace").map(|path| {
+            let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+                .file(&path)
+                .include_args(true)
+                .build();
+            // The trace is only written out in full once this goes, along
+            // with the app
+            app.world_mut().insert_non_send_resource(guard);
+            eprintln!("Tracing to {}", path);
+            layer
+        }));
+
+        // Everything that logs through `log` rather than `tracing`, like wgpu
+        if let Err(error) = LogTracer::init() {
+            eprintln!("Couldn't pass `log` messages on: {}", error);
+        }
+        if let Err(error) = subscriber::set_global_default(logged) {
+            eprintln!("Couldn't set up logging: {}", error);
+        }
+    }
+}
@@ -0,0 +1,477 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    crash,
+    locale::Strings,
+    restart_state, saved_match,
+    settings::Settings,
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        TEXT_COLOR,
+    },
+    AppState, GameMode, MatchNames, NextRules,
+};
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Menu), spawn_menu)
+            .add_systems(
+                Update,
+                (
+                    menu_action,
+                    toggle_modifier,
+                    change_racket,
+                    update_modifier_labels
+                        .after(toggle_modifier)
+                        .after(change_racket),
+                )
+                    .run_if(in_state(AppState::Menu)),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum MenuAction {
+    /// Open the report of the crash the game last ended in
+    ViewCrashReport,
+    /// Stop mentioning the crash the game last ended in
+    DismissCrash,
+    /// Carry on with the saved match
+    Continue,
+    Play,
+    Doubles,
+    Blitz,
+    Arcade,
+    Practice,
+    Drills,
+    Survival,
+    Tournament,
+    Custom,
+    Campaign,
+    #[cfg(feature = "networking")]
+    Online,
+    Players,
+    Season,
+    Stats,
+    History,
+    Achievements,
+    Replays,
+    Mods,
+    Settings,
+    Quit,
+}
+
+/// A twist on the usual rules for local matches, switched on and off from its
+/// own column of the menu.
+#[derive(Component, Clone, Copy)]
+enum Modifier {
+    Wind,
+    Gravity,
+    Portals,
+    MovingWall,
+    CenterNet,
+    Shrinking,
+    ServeRules,
+    Night,
+}
+
+const MODIFIERS: [Modifier; 8] = [
+    Modifier::Wind,
+    Modifier::Gravity,
+    Modifier::Portals,
+    Modifier::MovingWall,
+    Modifier::CenterNet,
+    Modifier::Shrinking,
+    Modifier::ServeRules,
+    Modifier::Night,
+];
+
+fn modifier_label(modifier: Modifier, settings: &Settings, strings: &Strings) -> String {
+    let (key, on) = match modifier {
+        Modifier::Wind => ("menu.wind", settings.wind),
+        Modifier::Gravity => ("menu.gravity", settings.gravity),
+        Modifier::Portals => ("menu.portals", settings.portals),
+        Modifier::MovingWall => ("menu.moving_wall", settings.moving_wall),
+        Modifier::CenterNet => ("menu.center_net", settings.center_net),
+        Modifier::Shrinking => ("menu.shrinking", settings.shrinking),
+        Modifier::ServeRules => ("menu.serve_rules", settings.serve_rules),
+        Modifier::Night => ("menu.night", settings.night),
+    };
+    strings.fill(key, &[("state", &strings.on_off(on))])
+}
+
+/// Player 1 or 2's rackets, by index, made longer or shorter or quicker or
+/// slower from the column beside the modifiers. Each press goes on to the
+/// next there is, the same as in the settings' handicaps.
+#[derive(Component, Clone, Copy)]
+enum RacketOption {
+    Length(usize),
+    Speed(usize),
+}
+
+const RACKET_OPTIONS: [RacketOption; 4] = [
+    RacketOption::Length(0),
+    RacketOption::Speed(0),
+    RacketOption::Length(1),
+    RacketOption::Speed(1),
+];
+
+fn racket_option_label(option: RacketOption, settings: &Settings, strings: &Strings) -> String {
+    let (key, player, value) = match option {
+        RacketOption::Length(player) => (
+            "menu.racket_length",
+            player,
+            settings.handicaps[player].racket_length,
+        ),
+        RacketOption::Speed(player) => (
+            "menu.racket_speed",
+            player,
+            settings.handicaps[player].racket_speed,
+        ),
+    };
+    strings.fill(
+        key,
+        &[
+            ("number", &(player + 1)),
+            ("value", &format!("{:.0}%", value * 100.0)),
+        ],
+    )
+}
+
+// Side by side, the ways to play each other, the ways to play alone, and
+// everything else
+const MENU_COLUMNS: [&[(MenuAction, &str)]; 3] = [
+    &[
+        (MenuAction::Play, "menu.play"),
+        (MenuAction::Doubles, "menu.doubles"),
+        (MenuAction::Blitz, "menu.blitz"),
+        (MenuAction::Arcade, "menu.arcade"),
+        (MenuAction::Tournament, "menu.tournament"),
+        (MenuAction::Custom, "menu.custom"),
+        #[cfg(feature = "networking")]
+        (MenuAction::Online, "menu.online"),
+    ],
+    &[
+        (MenuAction::Practice, "menu.practice"),
+        (MenuAction::Drills, "menu.drills"),
+        (MenuAction::Survival, "menu.survival"),
+        (MenuAction::Campaign, "menu.campaign"),
+    ],
+    &[
+        (MenuAction::Players, "menu.players"),
+        (MenuAction::Season, "menu.season"),
+        (MenuAction::Stats, "menu.stats"),
+        (MenuAction::History, "menu.history"),
+        (MenuAction::Achievements, "menu.achievements"),
+        (MenuAction::Replays, "menu.replays"),
+        (MenuAction::Mods, "menu.mods"),
+        (MenuAction::Settings, "menu.settings"),
+        (MenuAction::Quit, "menu.quit"),
+    ],
+];
+
+fn spawn_menu(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    settings: Res<Settings>,
+    strings: Res<Strings>,
+) {
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Menu)))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("menu.title"), 80.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(40.0)),
+                    ..default()
+                }),
+            );
+
+            let mut index = 0;
+            if crash::pending().is_some() {
+                parent.spawn(
+                    text(&font, strings.get("menu.crashed"), 20.0, ACCENT_COLOR).with_style(
+                        Style {
+                            margin: UiRect::all(Val::Px(6.0)),
+                            ..default()
+                        },
+                    ),
+                );
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            margin: UiRect {
+                                bottom: Val::Px(20.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (action, key) in [
+                            (MenuAction::ViewCrashReport, "menu.crash_report"),
+                            (MenuAction::DismissCrash, "menu.dismiss"),
+                        ] {
+                            spawn_button(parent, &font, index, strings.get(key)).insert(action);
+                            index += 1;
+                        }
+                    });
+            }
+            if saved_match::exists() {
+                spawn_button(parent, &font, index, strings.get("menu.continue"))
+                    .insert(MenuAction::Continue);
+                index += 1;
+            }
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexStart,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for items in MENU_COLUMNS {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    margin: UiRect::all(Val::Px(10.0)),
+                                    ..default()
+                                },
+                                background_color: Color::NONE.into(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for (action, key) in items {
+                                    spawn_button(parent, &font, index, strings.get(key))
+                                        .insert(*action);
+                                    index += 1;
+                                }
+                            });
+                    }
+
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                margin: UiRect::all(Val::Px(10.0)),
+                                ..default()
+                            },
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn(text(
+                                &font,
+                                strings.get("menu.modifiers"),
+                                24.0,
+                                DIM_TEXT_COLOR,
+                            ));
+                            for modifier in MODIFIERS {
+                                let label = modifier_label(modifier, &settings, &strings);
+                                spawn_button(parent, &font, index, &label).insert(modifier);
+                                index += 1;
+                            }
+                        });
+
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                margin: UiRect::all(Val::Px(10.0)),
+                                ..default()
+                            },
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn(text(
+                                &font,
+                                strings.get("menu.rackets"),
+                                24.0,
+                                DIM_TEXT_COLOR,
+                            ));
+                            for option in RACKET_OPTIONS {
+                                let label = racket_option_label(option, &settings, &strings);
+                                spawn_button(parent, &font, index, &label).insert(option);
+                                index += 1;
+                            }
+                        });
+                });
+        });
+}
+
+fn menu_action(
+    mut commands: Commands,
+    mut activated: EventReader<ButtonActivated>,
+    actions: Query<&MenuAction>,
+    mut state: ResMut<NextState<AppState>>,
+    mut mode: ResMut<GameMode>,
+    mut names: ResMut<MatchNames>,
+    mut next_rules: ResMut<NextRules>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for event in activated.read() {
+        let action = match actions.get(event.0) {
+            Ok(action) => action,
+            Err(_) => continue,
+        };
+        match action {
+            MenuAction::ViewCrashReport => {
+                if let Some(path) = crash::pending() {
+                    crash::open(&path);
+                }
+                crash::dismiss();
+                commands.add(restart_state);
+            }
+            MenuAction::DismissCrash => {
+                crash::dismiss();
+                commands.add(restart_state);
+            }
+            MenuAction::Continue => {
+                if let Some(saved) = saved_match::take() {
+                    *mode = saved.mode;
+                    names.0 = Some(saved.names.clone());
+                    next_rules.0 = Some(saved.rules);
+                    commands.insert_resource(saved);
+                    state.set(AppState::Playing);
+                }
+            }
+            MenuAction::Play => {
+                *mode = GameMode::Versus;
+                state.set(AppState::Playing);
+            }
+            MenuAction::Doubles => {
+                *mode = GameMode::Doubles;
+                state.set(AppState::Playing);
+            }
+            MenuAction::Blitz => {
+                *mode = GameMode::Blitz;
+                state.set(AppState::Playing);
+            }
+            MenuAction::Arcade => {
+                *mode = GameMode::Arcade;
+                state.set(AppState::Playing);
+            }
+            MenuAction::Practice => {
+                *mode = GameMode::Practice;
+                state.set(AppState::Playing);
+            }
+            MenuAction::Drills => {
+                state.set(AppState::Drills);
+            }
+            MenuAction::Survival => {
+                *mode = GameMode::Survival;
+                state.set(AppState::Playing);
+            }
+            MenuAction::Tournament => {
+                state.set(AppState::TournamentSetup);
+            }
+            MenuAction::Custom => {
+                state.set(AppState::CustomMatch);
+            }
+            MenuAction::Campaign => {
+                state.set(AppState::Campaign);
+            }
+            #[cfg(feature = "networking")]
+            MenuAction::Online => {
+                state.set(AppState::Network);
+            }
+            MenuAction::Players => {
+                state.set(AppState::Profiles);
+            }
+            MenuAction::Season => {
+                state.set(AppState::Season);
+            }
+            MenuAction::Stats => {
+                state.set(AppState::Stats);
+            }
+            MenuAction::History => {
+                state.set(AppState::History);
+            }
+            MenuAction::Achievements => {
+                state.set(AppState::Achievements);
+            }
+            MenuAction::Replays => {
+                state.set(AppState::Replays);
+            }
+            MenuAction::Mods => {
+                state.set(AppState::Mods);
+            }
+            MenuAction::Settings => {
+                state.set(AppState::Settings);
+            }
+            MenuAction::Quit => {
+                exit.send(AppExit::Success);
+            }
+        }
+    }
+}
+
+fn toggle_modifier(
+    mut activated: EventReader<ButtonActivated>,
+    modifiers: Query<&Modifier>,
+    mut settings: ResMut<Settings>,
+) {
+    for event in activated.read() {
+        match modifiers.get(event.0) {
+            Ok(Modifier::Wind) => settings.wind = !settings.wind,
+            Ok(Modifier::Gravity) => settings.gravity = !settings.gravity,
+            Ok(Modifier::Portals) => settings.portals = !settings.portals,
+            Ok(Modifier::MovingWall) => settings.moving_wall = !settings.moving_wall,
+            Ok(Modifier::CenterNet) => settings.center_net = !settings.center_net,
+            Ok(Modifier::Shrinking) => settings.shrinking = !settings.shrinking,
+            Ok(Modifier::ServeRules) => settings.serve_rules = !settings.serve_rules,
+            Ok(Modifier::Night) => settings.night = !settings.night,
+            Err(_) => {}
+        }
+    }
+}
+
+fn change_racket(
+    mut activated: EventReader<ButtonActivated>,
+    options: Query<&RacketOption>,
+    mut settings: ResMut<Settings>,
+) {
+    for event in activated.read() {
+        match options.get(event.0) {
+            Ok(RacketOption::Length(player)) => settings.cycle_racket_length(*player, 0.0),
+            Ok(RacketOption::Speed(player)) => settings.cycle_racket_speed(*player, 0.0),
+            Err(_) => {}
+        }
+    }
+}
+
+fn update_modifier_labels(
+    settings: Res<Settings>,
+    strings: Res<Strings>,
+    buttons: Query<(&Modifier, &Children)>,
+    racket_buttons: Query<(&RacketOption, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let labels = buttons
+        .iter()
+        .map(|(modifier, children)| (modifier_label(*modifier, &settings, &strings), children))
+        .chain(racket_buttons.iter().map(|(option, children)| {
+            (racket_option_label(*option, &settings, &strings), children)
+        }));
+    for (label, children) in labels {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
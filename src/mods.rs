@@ -0,0 +1,419 @@
+//! What players add to the game themselves, from `mods/` next to it: themes,
+//! arenas, voice packs for the announcer and scripts. Each folder in `mods/`
+//! is a mod, named after it, and so is each file there on its own.
+//!
+//! A mod's files are told apart by how their names end, the same as the
+//! game's own in `assets`: `.theme.ron`, `.arena.ron`, `.announcer.ron` and
+//! `.rhai`. Paths in them are under `assets` too, except ones starting with
+//! `./`, which are to files next to them, in the mod.
+//!
+//! Everything's looked over once at startup. A file that doesn't read, is
+//! missing a file it needs or takes a name something else already has is
+//! left out, and the mods screen lists what each mod brought and what was
+//! wrong with the rest.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::{Arena, ARENAS},
+    locale::Strings,
+    settings::VOICE_PACKS,
+    sim::Side,
+    theme::{Theme, THEMES},
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        TEXT_COLOR,
+    },
+    AppState,
+};
+
+const MODS_DIR: &str = "mods";
+const ERROR_COLOR: Color = Color::srgb(0.9, 0.3, 0.3);
+
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        let mods = Mods::scan(Path::new(MODS_DIR));
+        for found in &mods.0 {
+            info!("Loaded mod \"{}\"", found.name);
+            for error in &found.errors {
+                warn!("Mod \"{}\": {}", found.name, error);
+            }
+        }
+
+        app.insert_resource(mods)
+            .add_systems(OnEnter(AppState::Mods), spawn_mods)
+            .add_systems(Update, mods_action.run_if(in_state(AppState::Mods)));
+    }
+}
+
+/// What a mod can bring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Content {
+    Theme,
+    Arena,
+    VoicePack,
+    Script,
+}
+
+impl Content {
+    const ALL: [Content; 4] = [
+        Content::Theme,
+        Content::Arena,
+        Content::VoicePack,
+        Content::Script,
+    ];
+
+    /// How its files' names end.
+    fn suffix(self) -> &'static str {
+        match self {
+            Content::Theme => ".theme.ron",
+            Content::Arena => ".arena.ron",
+            Content::VoicePack => ".announcer.ron",
+            Content::Script => ".rhai",
+        }
+    }
+
+    /// Key of its line on the mods screen, in the [`Strings`].
+    fn key(self) -> &'static str {
+        match self {
+            Content::Theme => "mods.theme",
+            Content::Arena => "mods.arena",
+            Content::VoicePack => "mods.voice_pack",
+            Content::Script => "mods.script",
+        }
+    }
+
+    /// What the game comes with, which a mod can't take the names of.
+    fn built_in(self) -> &'static [&'static str] {
+        match self {
+            Content::Theme => &THEMES,
+            Content::Arena => &ARENAS,
+            Content::VoicePack => &VOICE_PACKS,
+            Content::Script => &[],
+        }
+    }
+}
+
+/// Something a mod brought, named after its file.
+pub struct Found {
+    pub content: Content,
+    pub name: String,
+    path: PathBuf,
+}
+
+/// A folder in `mods/`, or a file there on its own.
+pub struct Mod {
+    pub name: String,
+    pub found: Vec<Found>,
+    /// What was wrong with everything left out
+    pub errors: Vec<String>,
+}
+
+/// Every mod, in alphabetical order.
+#[derive(Resource, Default)]
+pub struct Mods(pub Vec<Mod>);
+
+impl Mods {
+    fn scan(dir: &Path) -> Self {
+        // No mods is fine
+        let entries = match sorted_entries(dir) {
+            Some(entries) => entries,
+            None => return Mods::default(),
+        };
+
+        let mut mods = Vec::new();
+        let mut taken: Vec<(Content, String)> = Content::ALL
+            .into_iter()
+            .flat_map(|content| {
+                content
+                    .built_in()
+                    .iter()
+                    .map(move |name| (content, name.to_string()))
+            })
+            .collect();
+        for entry in entries {
+            let (name, files) = if entry.is_dir() {
+                let name = entry.file_name().unwrap_or_default().to_string_lossy();
+                (name.to_string(), sorted_entries(&entry).unwrap_or_default())
+            } else {
+                match content_of(&entry) {
+                    Some((_, name)) => (name, vec![entry]),
+                    // Anything else in there, like a readme
+                    None => continue,
+                }
+            };
+
+            let mut found_mod = Mod {
+                name,
+                found: Vec::new(),
+                errors: Vec::new(),
+            };
+            for path in files {
+                let (content, name) = match content_of(&path) {
+                    Some(found) => found,
+                    None => continue,
+                };
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                if taken.contains(&(content, name.clone())) {
+                    found_mod.errors.push(format!(
+                        "{}: there's already one called \"{}\"",
+                        file_name, name
+                    ));
+                    continue;
+                }
+                match check(content, &path) {
+                    Ok(()) => {
+                        taken.push((content, name.clone()));
+                        found_mod.found.push(Found {
+                            content,
+                            name,
+                            path,
+                        });
+                    }
+                    Err(error) => found_mod.errors.push(format!("{}: {}", file_name, error)),
+                }
+            }
+            mods.push(found_mod);
+        }
+        Mods(mods)
+    }
+
+    fn all(&self) -> impl Iterator<Item = &Found> {
+        self.0.iter().flat_map(|found_mod| &found_mod.found)
+    }
+
+    /// The names of everything of `content` the mods brought.
+    pub fn names(&self, content: Content) -> impl Iterator<Item = &str> {
+        self.all()
+            .filter(move |found| found.content == content)
+            .map(|found| found.name.as_str())
+    }
+
+    /// Where the asset server finds the `content` called `name`, if a mod
+    /// brought it.
+    pub fn asset_path(&self, content: Content, name: &str) -> Option<String> {
+        self.all()
+            .find(|found| found.content == content && found.name == name)
+            .map(|found| asset_path(&found.path))
+    }
+
+    /// Where the asset server finds everything the mods brought but their
+    /// scripts.
+    pub fn asset_paths(&self) -> impl Iterator<Item = String> + '_ {
+        self.all()
+            .filter(|found| found.content != Content::Script)
+            .map(|found| asset_path(&found.path))
+    }
+
+    /// Every script's file.
+    pub fn scripts(&self) -> Vec<PathBuf> {
+        self.all()
+            .filter(|found| found.content == Content::Script)
+            .map(|found| found.path.clone())
+            .collect()
+    }
+
+    /// Leaves out the file at `path`, which turned out not to work after all.
+    pub fn reject(&mut self, path: &Path, error: String) {
+        for found_mod in &mut self.0 {
+            if let Some(index) = found_mod.found.iter().position(|found| found.path == path) {
+                let found = found_mod.found.remove(index);
+                let file_name = found.path.file_name().unwrap_or_default().to_string_lossy();
+                found_mod.errors.push(format!("{}: {}", file_name, error));
+            }
+        }
+    }
+}
+
+/// Everything in `dir`, in alphabetical order, or nothing if it can't be
+/// read.
+fn sorted_entries(dir: &Path) -> Option<Vec<PathBuf>> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    Some(entries)
+}
+
+/// What the file at `path` is, by how its name ends, and what it's called.
+fn content_of(path: &Path) -> Option<(Content, String)> {
+    let file_name = path.file_name()?.to_str()?;
+    Content::ALL.into_iter().find_map(|content| {
+        file_name
+            .strip_suffix(content.suffix())
+            .filter(|name| !name.is_empty())
+            .map(|name| (content, name.to_string()))
+    })
+}
+
+/// `path` with forward slashes, as the asset server has them everywhere.
+fn slashed(path: &Path) -> String {
+    let parts: Vec<_> = path
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+/// The path under `assets` of a file in `mods/`, which is beside it.
+fn asset_path(path: &Path) -> String {
+    slashed(&Path::new("..").join(path))
+}
+
+/// A `path` named in the file at `asset`, both under `assets`: the game's
+/// own are left as they are, and ones starting with `./` are next to `asset`.
+pub fn resolve(asset: &Path, path: &str) -> String {
+    match path.strip_prefix("./") {
+        Some(own) => slashed(&asset.parent().unwrap_or(Path::new("")).join(own)),
+        None => path.to_string(),
+    }
+}
+
+/// Whether the `content` in the file at `path` is fit to use: it reads, and
+/// has whatever it needs.
+fn check(content: Content, path: &Path) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|error| error.to_string())?;
+    let named: Vec<String> = match content {
+        Content::Theme => {
+            let mut theme: Theme =
+                ron::de::from_bytes(&bytes).map_err(|error| error.to_string())?;
+            theme.paths_mut().map(|path| path.to_string()).collect()
+        }
+        Content::Arena => {
+            let arena: Arena = ron::de::from_bytes(&bytes).map_err(|error| error.to_string())?;
+            for side in [Side::Left, Side::Right] {
+                if !arena.goals.iter().any(|goal| goal.side == side) {
+                    return Err(format!("there's no goal on the {:?}", side));
+                }
+            }
+            Vec::new()
+        }
+        #[cfg(feature = "audio")]
+        Content::VoicePack => {
+            let pack: crate::announcer::VoicePack =
+                ron::de::from_bytes(&bytes).map_err(|error| error.to_string())?;
+            pack.lines.into_values().collect()
+        }
+        #[cfg(not(feature = "audio"))]
+        Content::VoicePack => return Err("this build has no announcer".to_string()),
+        #[cfg(feature = "scripting")]
+        // Compiled along with the rest by the scripting
+        Content::Script => Vec::new(),
+        #[cfg(not(feature = "scripting"))]
+        Content::Script => return Err("this build can't run scripts".to_string()),
+    };
+
+    // Only the mod's own files, as the game's are all there
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for named in named {
+        if let Some(own) = named.strip_prefix("./") {
+            if !dir.join(own).is_file() {
+                return Err(format!("there's no {}", named));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Component)]
+struct BackButton;
+
+fn spawn_mods(mut commands: Commands, font: Res<UiFont>, strings: Res<Strings>, mods: Res<Mods>) {
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Mods)))
+        .with_children(|parent| {
+            parent.spawn(text(&font, strings.get("mods.title"), 48.0, TEXT_COLOR));
+            if mods.0.is_empty() {
+                parent.spawn(text(&font, strings.get("mods.none"), 24.0, DIM_TEXT_COLOR));
+            }
+            let line = |value: String, size: f32, color: Color| {
+                text(&font, value, size, color).with_style(Style {
+                    margin: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                })
+            };
+            for found_mod in &mods.0 {
+                parent.spawn(
+                    text(&font, &found_mod.name, 28.0, ACCENT_COLOR).with_style(Style {
+                        margin: UiRect {
+                            top: Val::Px(14.0),
+                            ..default()
+                        },
+                        ..default()
+                    }),
+                );
+                if found_mod.found.is_empty() && found_mod.errors.is_empty() {
+                    parent.spawn(line(
+                        strings.get("mods.empty").to_string(),
+                        18.0,
+                        DIM_TEXT_COLOR,
+                    ));
+                }
+                for found in &found_mod.found {
+                    let value = strings.fill(found.content.key(), &[("name", &found.name)]);
+                    parent.spawn(line(value, 18.0, TEXT_COLOR));
+                }
+                for error in &found_mod.errors {
+                    parent.spawn(line(error.clone(), 16.0, ERROR_COLOR));
+                }
+            }
+            spawn_button(parent, &font, 0, strings.get("back")).insert(BackButton);
+        });
+}
+
+fn mods_action(
+    mut activated: EventReader<ButtonActivated>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let back = activated
+        .read()
+        .any(|event| back_buttons.get(event.0).is_ok())
+        || keyboard_input.just_pressed(KeyCode::Escape);
+    if back {
+        state.set(AppState::Menu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_paths_are_next_to_the_file() {
+        let asset = Path::new("../mods/sunset/sunset.theme.ron");
+        assert_eq!(
+            resolve(asset, "./textures/ball.png"),
+            "../mods/sunset/textures/ball.png"
+        );
+        assert_eq!(resolve(asset, "fonts/x.ttf"), "fonts/x.ttf");
+        assert_eq!(
+            asset_path(Path::new("mods/sunset/sunset.theme.ron")),
+            "../mods/sunset/sunset.theme.ron"
+        );
+    }
+
+    #[test]
+    fn files_are_told_apart_by_their_endings() {
+        let content = |name: &str| content_of(Path::new(name));
+        assert_eq!(
+            content("mods/a/dusk.theme.ron"),
+            Some((Content::Theme, "dusk".to_string()))
+        );
+        assert_eq!(
+            content("chaser.rhai"),
+            Some((Content::Script, "chaser".to_string()))
+        );
+        assert_eq!(content("README.md"), None);
+        assert_eq!(content(".arena.ron"), None);
+    }
+}
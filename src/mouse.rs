@@ -0,0 +1,71 @@
+//! A racket following the mouse up and down, for whichever player the
+//! settings give it to. It goes after the pointer's height no faster than
+//! the keys would move it, so a flick of the wrist can't throw it across the
+//! court. The left button charges a smash and the right one dashes. Their
+//! keys still work alongside.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    actions::{Action, Actions, PressActions},
+    settings::Settings,
+    sim::Racket,
+    MainCamera,
+};
+
+// How far from the pointer a racket can be, in pixels on screen, and still be
+// left where it is, so it doesn't jitter back and forth around it
+const SLACK: f32 = 6.0;
+
+pub struct MousePlugin;
+
+impl Plugin for MousePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, follow_mouse.in_set(PressActions));
+    }
+}
+
+fn follow_mouse(
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    rackets: Query<(&Racket, &GlobalTransform)>,
+    mut actions: ResMut<Actions>,
+) {
+    let seat = match settings.mouse_player {
+        Some(player_number) => (player_number - 1) as usize,
+        None => return,
+    };
+    // From the top of the window, like the viewport
+    let pointer = match windows.get_single().ok().and_then(Window::cursor_position) {
+        Some(pointer) => pointer,
+        None => return,
+    };
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    let at = rackets
+        .iter()
+        .find(|(racket, _)| racket.seat == seat)
+        .and_then(|(_, transform)| {
+            camera.world_to_viewport(camera_transform, transform.translation())
+        });
+    let at = match at {
+        Some(at) => at,
+        None => return,
+    };
+    if pointer.y < at.y - SLACK {
+        actions.press(seat, Action::MoveUp);
+    } else if pointer.y > at.y + SLACK {
+        actions.press(seat, Action::MoveDown);
+    }
+    if buttons.pressed(MouseButton::Left) {
+        actions.press(seat, Action::Smash);
+    }
+    if buttons.pressed(MouseButton::Right) {
+        actions.press(seat, Action::Dash);
+    }
+}
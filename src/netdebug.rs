@@ -0,0 +1,172 @@
+//! Bad networks on demand, for tuning the netcode.
+//!
+//! `--latency <ms> --jitter <ms> --loss <percent>` hold back and drop the
+//! packets a rollback session sends, and an overlay during online matches
+//! shows the round trip and how many frames GGRS had to simulate again.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+
+use crate::{
+    cli_arg,
+    network::GgrsConfig,
+    sim::SimRng,
+    transport::Transport,
+    ui::{text, UiFont, DIM_TEXT_COLOR},
+    AppState, GameMode, MatchState,
+};
+
+pub struct NetDebugPlugin;
+
+impl Plugin for NetDebugPlugin {
+    fn build(&self, app: &mut App) {
+        let conditions = NetConditions::from_args();
+        info!("Simulating {}", conditions);
+
+        app.insert_resource(conditions)
+            .init_resource::<SimulatedFrames>()
+            .add_systems(OnEnter(AppState::Playing), spawn_readout)
+            .add_systems(Update, update_readout.run_if(in_state(MatchState::Running)));
+    }
+}
+
+/// What happens to every packet a session sends.
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct NetConditions {
+    pub latency: Duration,
+    /// Each packet's delay is off from `latency` by up to this much either way
+    pub jitter: Duration,
+    /// Chance of a packet never arriving, from 0 to 1
+    pub loss: f32,
+}
+
+impl NetConditions {
+    /// `--latency <ms> --jitter <ms> --loss <percent>`
+    pub fn from_args() -> Self {
+        let number = |name| cli_arg(name).and_then(|value| value.parse::<u64>().ok());
+        NetConditions {
+            latency: Duration::from_millis(number("latency").unwrap_or_default()),
+            jitter: Duration::from_millis(number("jitter").unwrap_or_default()),
+            loss: number("loss").unwrap_or_default().min(100) as f32 / 100.0,
+        }
+    }
+}
+
+impl std::fmt::Display for NetConditions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ms ± {} ms latency, {:.0}% loss",
+            self.latency.as_millis(),
+            self.jitter.as_millis(),
+            self.loss * 100.0
+        )
+    }
+}
+
+/// Sends packets the way a network with [`NetConditions`] would deliver them.
+pub struct Conditioner {
+    conditions: NetConditions,
+    rng: SimRng,
+    delayed: Vec<(Instant, Vec<u8>)>,
+}
+
+impl Conditioner {
+    pub fn new(conditions: NetConditions) -> Self {
+        Conditioner {
+            conditions,
+            rng: SimRng::new(crate::stats::now()),
+            delayed: Vec::new(),
+        }
+    }
+
+    pub fn send(&mut self, transport: &Transport, packet: Vec<u8>) {
+        if self.rng.range(0.0, 1.0) < self.conditions.loss {
+            return;
+        }
+        let jitter = self.conditions.jitter.as_secs_f32() * self.rng.range(-1.0, 1.0);
+        let delay = (self.conditions.latency.as_secs_f32() + jitter).max(0.0);
+        self.delayed
+            .push((Instant::now() + Duration::from_secs_f32(delay), packet));
+        self.flush(transport);
+    }
+
+    /// Sends whatever has been held back long enough.
+    pub fn flush(&mut self, transport: &Transport) {
+        let now = Instant::now();
+        self.delayed.retain(|(due, packet)| {
+            if *due > now {
+                return true;
+            }
+            transport.send(packet);
+            false
+        });
+    }
+}
+
+/// Frames the rollback schedule has stepped, counting the ones it stepped
+/// again after a rollback. Not rolled back itself.
+#[derive(Resource, Default)]
+pub struct SimulatedFrames(pub i32);
+
+pub fn count_frame(mut frames: ResMut<SimulatedFrames>) {
+    frames.0 += 1;
+}
+
+#[derive(Component)]
+struct NetReadout;
+
+fn spawn_readout(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    mode: Res<GameMode>,
+    mut frames: ResMut<SimulatedFrames>,
+) {
+    frames.0 = 0;
+    if *mode != GameMode::Online {
+        return;
+    }
+
+    commands.spawn((
+        text(&font, "", 16.0, DIM_TEXT_COLOR).with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(16.0),
+            bottom: Val::Px(16.0),
+            ..default()
+        }),
+        NetReadout,
+        StateScoped(AppState::Playing),
+    ));
+}
+
+fn update_readout(
+    session: Option<Res<Session<GgrsConfig>>>,
+    conditions: Res<NetConditions>,
+    frames: Res<SimulatedFrames>,
+    mut readouts: Query<&mut Text, With<NetReadout>>,
+) {
+    let status = match session.as_deref() {
+        Some(Session::P2P(session)) => {
+            let stats = session
+                .remote_player_handles()
+                .first()
+                .and_then(|handle| session.network_stats(*handle).ok());
+            let rtt = match stats {
+                Some(stats) => format!(
+                    "RTT {} ms, {} frames behind",
+                    stats.ping, stats.local_frames_behind
+                ),
+                None => "Synchronizing".to_string(),
+            };
+            let rolled_back = (frames.0 - session.current_frame()).max(0);
+            format!("{}, {} frames rolled back", rtt, rolled_back)
+        }
+        _ => "No rollback session".to_string(),
+    };
+
+    for mut text in readouts.iter_mut() {
+        text.sections[0].value = format!("{}\nSimulating {}", status, *conditions);
+    }
+}
@@ -0,0 +1,854 @@
+//! Online 1v1 with GGRS rollback netcode.
+//!
+//! Both peers run the [`sim`] systems inside the rollback schedule. Inputs that
+//! haven't arrived yet are predicted, and when a late one turns out different,
+//! GGRS restores the components and resources registered below to the last
+//! confirmed frame and replays from there.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::*;
+use bevy_ggrs::{
+    AddRollbackCommandExtension, GgrsApp, GgrsPlugin, GgrsSchedule, LocalInputs, LocalPlayers,
+    PlayerInputs, ReadInputs, Session,
+};
+use ggrs::{
+    Config, GgrsError, GgrsEvent, Message, NonBlockingSocket, P2PSession, PlayerHandle, PlayerType,
+    SessionBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "netdebug")]
+use crate::netdebug::{Conditioner, NetConditions};
+use crate::{
+    actions::Actions,
+    chat::{clean, ChatLine, OutgoingChat},
+    cli_arg,
+    desync::{record_state, DesyncCheck, DesyncMessage, SimFrame},
+    lobby::{LobbyStatus, QuickMatch},
+    locale::Strings,
+    protocol::{Hello, Mismatch, PROTOCOL_VERSION},
+    relay::{decode, encode},
+    sim::{
+        self, Ball, Dash, MatchClock, MatchOver, MatchRules, MatchStats, Momentum, Netted,
+        PortalCooldown, Portals, Racket, RacketInput, RacketLength, Rally, Score, Served, Side,
+        SimInput, SimRng, SimSystems, Smash, Spin, Swing, Tilt, TimeStep, Velocity, Waiting, Wind,
+    },
+    transport::Transport,
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, MenuButton, MenuFocus, UiFont,
+        ACCENT_COLOR, DIM_TEXT_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, MatchSeed, MatchState,
+};
+
+const DEFAULT_PORT: u16 = 7000;
+const MAX_INPUT_DELAY: usize = 8;
+const MAX_PREDICTION: usize = 16;
+// Seconds between hellos while the other player hasn't answered
+const HELLO_RETRY: f64 = 0.5;
+
+const INPUT_UP: u8 = 1 << 0;
+const INPUT_DOWN: u8 = 1 << 1;
+const INPUT_SMASH: u8 = 1 << 2;
+const INPUT_TILT_UP: u8 = 1 << 3;
+const INPUT_TILT_DOWN: u8 = 1 << 4;
+const INPUT_DASH: u8 = 1 << 5;
+
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(GgrsPlugin::<GgrsConfig>::default())
+            // Online matches always step at the default rate
+            .set_rollback_schedule_fps(TimeStep::default().rate as usize)
+            .rollback_component_with_clone::<Transform>()
+            .rollback_component_with_clone::<Velocity>()
+            .rollback_component_with_clone::<Netted>()
+            .rollback_component_with_clone::<Served>()
+            .rollback_component_with_clone::<Smash>()
+            .rollback_component_with_clone::<Momentum>()
+            .rollback_component_with_clone::<Dash>()
+            .rollback_component_with_clone::<Tilt>()
+            .rollback_component_with_clone::<RacketLength>()
+            .rollback_component_with_clone::<Waiting>()
+            .rollback_component_with_clone::<Swing>()
+            .rollback_component_with_clone::<Spin>()
+            .rollback_component_with_clone::<Side>()
+            .rollback_component_with_clone::<PortalCooldown>()
+            .rollback_resource_with_clone::<Score>()
+            .rollback_resource_with_clone::<Rally>()
+            .rollback_resource_with_clone::<MatchStats>()
+            .rollback_resource_with_clone::<MatchOver>()
+            .rollback_resource_with_clone::<MatchClock>()
+            .rollback_resource_with_clone::<SimRng>()
+            .rollback_resource_with_clone::<Wind>()
+            .rollback_resource_with_clone::<Portals>()
+            .rollback_resource_with_clone::<SimFrame>()
+            .add_systems(ReadInputs, local_input)
+            .add_systems(
+                GgrsSchedule,
+                (
+                    apply_inputs,
+                    sim::systems().after(apply_inputs),
+                    record_state.after(SimSystems),
+                )
+                    .run_if(in_sync),
+            );
+        #[cfg(feature = "netdebug")]
+        app.add_systems(GgrsSchedule, crate::netdebug::count_frame.run_if(in_sync));
+        sim::defer_commands(app, GgrsSchedule);
+
+        app.insert_resource(NetworkSettings::from_args())
+            .init_resource::<NetworkProblem>()
+            .add_systems(OnEnter(AppState::Network), spawn_network_menu)
+            .add_systems(
+                Update,
+                (
+                    change_setting,
+                    update_setting_labels.after(change_setting),
+                    network_menu_action,
+                    update_problem_line.after(network_menu_action),
+                )
+                    .run_if(in_state(AppState::Network)),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_handshake,
+                    start_session.after(start_handshake),
+                    handle_session_events,
+                    exchange_chat,
+                    exchange_checks,
+                )
+                    .run_if(in_state(MatchState::Running)),
+            )
+            .add_systems(OnExit(AppState::Playing), end_session);
+    }
+}
+
+#[derive(Debug)]
+pub struct GgrsConfig;
+
+impl Config for GgrsConfig {
+    type Input = u8;
+    type State = u8;
+    type Address = SocketAddr;
+}
+
+/// How an online match is connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// Straight to the other player, with rollback
+    Peer,
+    /// Through a `pingis-server` that runs the match, see [`relay`](crate::relay)
+    Server,
+}
+
+/// How to reach the other player, and how to trade latency for rollbacks.
+#[derive(Resource, Debug)]
+pub struct NetworkSettings {
+    pub link: Link,
+    pub local_port: u16,
+    pub peer: Option<SocketAddr>,
+    pub server: Option<SocketAddr>,
+    /// Where to look for a quick match, see [`lobby`](crate::lobby)
+    pub lobby: Option<SocketAddr>,
+    /// The lobby that paired the players up, if one did, which passes their
+    /// packets on when they can't reach each other, see
+    /// [`transport`](crate::transport)
+    pub relay: Option<SocketAddr>,
+    pub local_player: PlayerHandle,
+    /// Frames a local input waits before it's used, hiding that much latency
+    pub input_delay: usize,
+    /// How many frames ahead of the last confirmed input the game may predict
+    pub max_prediction: usize,
+    /// Both peers need the same seed to serve the same balls
+    pub seed: u64,
+    /// Whether the match counts for the leaderboard, which only quick
+    /// matches do
+    pub ranked: bool,
+}
+
+impl NetworkSettings {
+    /// `--port <port> --peer <address> --player <1|2> --seed <number> --server <address>
+    /// --lobby <address>`
+    fn from_args() -> Self {
+        let address = |name| {
+            cli_arg(name).and_then(|address| match address.parse() {
+                Ok(address) => Some(address),
+                Err(error) => {
+                    warn!("Ignoring {} address \"{}\": {}", name, address, error);
+                    None
+                }
+            })
+        };
+        let player_number: usize = cli_arg("player")
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(1);
+
+        NetworkSettings {
+            link: Link::Peer,
+            local_port: cli_arg("port")
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(DEFAULT_PORT),
+            peer: address("peer"),
+            server: address("server"),
+            lobby: address("lobby"),
+            relay: None,
+            ranked: false,
+            local_player: player_number.clamp(1, 2) - 1,
+            input_delay: 2,
+            max_prediction: 8,
+            seed: cli_arg("seed")
+                .and_then(|seed| seed.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Starts the rollback session with the other player, once the
+    /// [`Handshake`] has found the two of them compatible.
+    fn start_session(
+        &self,
+        transport: Transport,
+        hello: Hello,
+    ) -> Result<(P2PSession<GgrsConfig>, PeerChat), GgrsError> {
+        let peer = transport.peer();
+        let chat = PeerChat {
+            transport: transport
+                .try_clone()
+                .map_err(|error| GgrsError::InvalidRequest {
+                    info: format!("Couldn't share the socket with chat: {}", error),
+                })?,
+            local_player_number: self.local_player as i32 + 1,
+            inbox: default(),
+            checks: default(),
+        };
+        let socket = ChatSocket {
+            transport,
+            hello,
+            inbox: chat.inbox.clone(),
+            checks: chat.checks.clone(),
+            #[cfg(feature = "netdebug")]
+            conditioner: Conditioner::new(NetConditions::from_args()),
+        };
+
+        let session = SessionBuilder::<GgrsConfig>::new()
+            .with_num_players(2)
+            .with_input_delay(self.input_delay)
+            .with_max_prediction_window(self.max_prediction)?
+            .add_player(PlayerType::Local, self.local_player)?
+            .add_player(PlayerType::Remote(peer), 1 - self.local_player)?
+            .start_p2p_session(socket)?;
+        Ok((session, chat))
+    }
+}
+
+/// Everything sent between peers. Chat and desync checks ride along on the
+/// session's socket, so they get through wherever the match itself does.
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    /// Has to stay the first, so every version can tell another's apart
+    Hello(Hello),
+    Ggrs(Message),
+    Chat(String),
+    Desync(DesyncMessage),
+}
+
+/// Swapping [`Hello`]s with the other player, before there's a session.
+#[derive(Resource)]
+struct Handshake {
+    /// Only ever taken to be handed on to the session
+    transport: Option<Transport>,
+    hello: Hello,
+    last_sent: f64,
+}
+
+impl Handshake {
+    fn send(&mut self, now: f64) {
+        self.last_sent = now;
+        if let Some(transport) = &self.transport {
+            transport.send(&encode(&Packet::Hello(self.hello.clone())));
+        }
+    }
+}
+
+/// Why the last online match couldn't be played, for the network menu to say.
+#[derive(Resource, Default)]
+pub struct NetworkProblem(pub Option<String>);
+
+/// The session's socket, setting chat and desync checks aside for [`PeerChat`]
+/// as GGRS polls it.
+struct ChatSocket {
+    transport: Transport,
+    /// Ours, for answering the other player's if they're still waiting on it
+    hello: Hello,
+    inbox: Arc<Mutex<Vec<String>>>,
+    checks: Arc<Mutex<Vec<DesyncMessage>>>,
+    #[cfg(feature = "netdebug")]
+    conditioner: Conditioner,
+}
+
+impl NonBlockingSocket<SocketAddr> for ChatSocket {
+    // There's only ever the one peer, which the transport already knows
+    fn send_to(&mut self, message: &Message, _address: &SocketAddr) {
+        let packet = encode(&Packet::Ggrs(message.clone()));
+        #[cfg(feature = "netdebug")]
+        self.conditioner.send(&self.transport, packet);
+        #[cfg(not(feature = "netdebug"))]
+        self.transport.send(&packet);
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+        // GGRS polls every frame, which is often enough to let delayed packets out
+        #[cfg(feature = "netdebug")]
+        self.conditioner.flush(&self.transport);
+
+        let peer = self.transport.peer();
+        let mut messages = Vec::new();
+        for packet in self.transport.receive() {
+            match decode(&packet) {
+                Some(Packet::Hello(_)) => {
+                    let hello = encode(&Packet::Hello(self.hello.clone()));
+                    self.transport.send(&hello);
+                }
+                Some(Packet::Ggrs(message)) => messages.push((peer, message)),
+                Some(Packet::Chat(text)) => self.inbox.lock().unwrap().push(text),
+                Some(Packet::Desync(message)) => self.checks.lock().unwrap().push(message),
+                None => debug!("Ignoring a garbled packet from {}", peer),
+            }
+        }
+        messages
+    }
+}
+
+/// Chat with the other peer during a session, and the desync checks that
+/// come along with it.
+#[derive(Resource)]
+struct PeerChat {
+    transport: Transport,
+    local_player_number: i32,
+    inbox: Arc<Mutex<Vec<String>>>,
+    checks: Arc<Mutex<Vec<DesyncMessage>>>,
+}
+
+#[derive(Component)]
+struct ProblemLine;
+
+#[derive(Component, Clone, Copy)]
+enum Setting {
+    InputDelay,
+    MaxPrediction,
+}
+
+#[derive(Component, Clone, Copy)]
+enum NetworkAction {
+    Connect,
+    JoinServer,
+    QuickMatch,
+    #[cfg(feature = "leaderboard")]
+    Leaderboard,
+    Back,
+}
+
+fn setting_label(setting: Setting, settings: &NetworkSettings, strings: &Strings) -> String {
+    match setting {
+        Setting::InputDelay => {
+            strings.fill("online.input_delay", &[("frames", &settings.input_delay)])
+        }
+        Setting::MaxPrediction => {
+            strings.fill("online.rollback", &[("frames", &settings.max_prediction)])
+        }
+    }
+}
+
+fn spawn_network_menu(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    settings: Res<NetworkSettings>,
+    problem: Res<NetworkProblem>,
+) {
+    let peer = match settings.peer {
+        Some(peer) => strings.fill(
+            "online.peer",
+            &[
+                ("number", &(settings.local_player + 1)),
+                ("port", &settings.local_port),
+                ("peer", &peer),
+            ],
+        ),
+        None => strings.get("online.no_peer").to_string(),
+    };
+    let server = match settings.server {
+        Some(server) => strings.fill("online.server", &[("server", &server)]),
+        None => strings.get("online.no_server").to_string(),
+    };
+    let lobby = match settings.lobby {
+        Some(lobby) => strings.fill("online.lobby", &[("lobby", &lobby)]),
+        None => strings.get("online.no_lobby").to_string(),
+    };
+
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Network)))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("online.title"), 60.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+            for line in [peer, server, lobby] {
+                parent.spawn(text(&font, line, 20.0, DIM_TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                }));
+            }
+            let problem = problem.0.clone().unwrap_or_default();
+            parent.spawn((
+                text(&font, problem, 20.0, ACCENT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+                ProblemLine,
+            ));
+
+            for (index, setting) in [Setting::InputDelay, Setting::MaxPrediction]
+                .into_iter()
+                .enumerate()
+            {
+                spawn_button(
+                    parent,
+                    &font,
+                    index,
+                    &setting_label(setting, &settings, &strings),
+                )
+                .insert(setting);
+            }
+            spawn_button(parent, &font, 2, strings.get("online.connect"))
+                .insert(NetworkAction::Connect);
+            spawn_button(parent, &font, 3, strings.get("online.join_server"))
+                .insert(NetworkAction::JoinServer);
+            spawn_button(parent, &font, 4, strings.get("online.quick_match"))
+                .insert(NetworkAction::QuickMatch);
+            #[cfg(feature = "leaderboard")]
+            spawn_button(parent, &font, 5, strings.get("online.leaderboard"))
+                .insert(NetworkAction::Leaderboard);
+            let back = if cfg!(feature = "leaderboard") { 6 } else { 5 };
+            spawn_button(parent, &font, back, strings.get("back")).insert(NetworkAction::Back);
+
+            parent.spawn((
+                text(&font, "", 20.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+                LobbyStatus,
+            ));
+
+            parent.spawn(
+                text(&font, strings.get("online.hint"), 18.0, DIM_TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+        });
+}
+
+fn change_setting(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    focus: Res<MenuFocus>,
+    buttons: Query<(&MenuButton, &Setting)>,
+    mut settings: ResMut<NetworkSettings>,
+) {
+    let step: isize = if keyboard_input.just_pressed(KeyCode::ArrowLeft)
+        || keyboard_input.just_pressed(KeyCode::KeyA)
+    {
+        -1
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight)
+        || keyboard_input.just_pressed(KeyCode::KeyD)
+    {
+        1
+    } else {
+        return;
+    };
+
+    let setting = match buttons.iter().find(|(button, _)| button.0 == focus.0) {
+        Some((_, setting)) => *setting,
+        None => return,
+    };
+    let (value, max) = match setting {
+        Setting::InputDelay => (&mut settings.input_delay, MAX_INPUT_DELAY),
+        Setting::MaxPrediction => (&mut settings.max_prediction, MAX_PREDICTION),
+    };
+    *value = value.saturating_add_signed(step).min(max);
+}
+
+fn update_setting_labels(
+    settings: Res<NetworkSettings>,
+    strings: Res<Strings>,
+    buttons: Query<(&Setting, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (setting, children) in buttons.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = setting_label(*setting, &settings, &strings);
+            }
+        }
+    }
+}
+
+fn update_problem_line(
+    problem: Res<NetworkProblem>,
+    mut lines: Query<&mut Text, With<ProblemLine>>,
+) {
+    if !problem.is_changed() {
+        return;
+    }
+    for mut line in lines.iter_mut() {
+        line.sections[0].value = problem.0.clone().unwrap_or_default();
+    }
+}
+
+fn network_menu_action(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    actions: Query<&NetworkAction>,
+    mut settings: ResMut<NetworkSettings>,
+    mut quick_match: ResMut<QuickMatch>,
+    mut problem: ResMut<NetworkProblem>,
+    mut mode: ResMut<GameMode>,
+    mut seed: ResMut<MatchSeed>,
+    mut state: ResMut<NextState<AppState>>,
+    time: Res<Time>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Menu);
+        return;
+    }
+
+    for event in activated.read() {
+        // Whatever went wrong last time, this is another go
+        if matches!(actions.get(event.0), Ok(action) if !matches!(action, NetworkAction::Back)) {
+            problem.0 = None;
+        }
+        match actions.get(event.0) {
+            Ok(NetworkAction::Connect) => {
+                if settings.peer.is_none() {
+                    warn!("No peer to connect to");
+                    continue;
+                }
+                settings.link = Link::Peer;
+                settings.relay = None;
+                settings.ranked = false;
+                *mode = GameMode::Online;
+                seed.0 = Some(settings.seed);
+                state.set(AppState::Playing);
+            }
+            Ok(NetworkAction::JoinServer) => {
+                if settings.server.is_none() {
+                    warn!("No server to join");
+                    continue;
+                }
+                settings.link = Link::Server;
+                settings.ranked = false;
+                *mode = GameMode::Online;
+                state.set(AppState::Playing);
+            }
+            // Pressed again, it stops looking
+            Ok(NetworkAction::QuickMatch) if quick_match.is_seeking() => quick_match.cancel(),
+            Ok(NetworkAction::QuickMatch) => {
+                match QuickMatch::seek(&settings, time.elapsed_seconds_f64()) {
+                    Ok(seeking) => *quick_match = seeking,
+                    Err(error) => warn!("Couldn't look for a match: {}", error),
+                }
+            }
+            #[cfg(feature = "leaderboard")]
+            Ok(NetworkAction::Leaderboard) => {
+                state.set(AppState::Leaderboard);
+            }
+            Ok(NetworkAction::Back) => {
+                state.set(AppState::Menu);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Starts swapping hellos with the other player once the match's ball and
+/// rackets have been spawned.
+fn start_handshake(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    session: Option<Res<Session<GgrsConfig>>>,
+    handshake: Option<Res<Handshake>>,
+    settings: Res<NetworkSettings>,
+    rules: Res<MatchRules>,
+    rackets: Query<(), With<Racket>>,
+    time: Res<Time>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if *mode != GameMode::Online
+        || settings.link != Link::Peer
+        || session.is_some()
+        || handshake.is_some()
+        || rackets.iter().count() < 2
+    {
+        return;
+    }
+    let peer = match settings.peer {
+        Some(peer) => peer,
+        None => return,
+    };
+
+    let transport = match Transport::bind(settings.local_port, peer, settings.relay) {
+        Ok(transport) => transport,
+        Err(error) => {
+            error!("Couldn't bind to port {}: {}", settings.local_port, error);
+            state.set(AppState::Menu);
+            return;
+        }
+    };
+    info!("Checking {} is running the same game", peer);
+    let mut handshake = Handshake {
+        transport: Some(transport),
+        hello: Hello::new(*rules, settings.seed),
+        last_sent: 0.0,
+    };
+    handshake.send(time.elapsed_seconds_f64());
+    commands.insert_resource(handshake);
+}
+
+/// Starts the session once the other player's hello shows the two of them
+/// can play each other, or goes back to the network menu to say why not.
+/// Both peers take their first snapshot of the match as it was spawned.
+fn start_session(
+    mut commands: Commands,
+    handshake: Option<ResMut<Handshake>>,
+    settings: Res<NetworkSettings>,
+    strings: Res<Strings>,
+    balls: Query<Entity, With<Ball>>,
+    rackets: Query<(Entity, &Racket)>,
+    time: Res<Time>,
+    mut problem: ResMut<NetworkProblem>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let mut handshake = match handshake {
+        Some(handshake) => handshake,
+        None => return,
+    };
+    let transport = match handshake.transport.take() {
+        Some(transport) => transport,
+        None => return,
+    };
+    let peer = transport.peer();
+
+    let mut answer = None;
+    for packet in transport.receive() {
+        match decode(&packet) {
+            Some(Packet::Hello(theirs)) => answer = Some(handshake.hello.check(&theirs)),
+            Some(_) => {}
+            None => match protocol_of(&packet) {
+                Some(theirs) if theirs != PROTOCOL_VERSION => {
+                    answer = Some(Err(Mismatch::Protocol {
+                        ours: PROTOCOL_VERSION,
+                        theirs,
+                    }));
+                }
+                _ => debug!("Ignoring a garbled packet from {}", peer),
+            },
+        }
+    }
+    let now = time.elapsed_seconds_f64();
+    if answer.is_some() {
+        // They may not have heard ours yet, and need it to find out the same
+        transport.send(&encode(&Packet::Hello(handshake.hello.clone())));
+    }
+    match answer {
+        None => {
+            handshake.transport = Some(transport);
+            if now - handshake.last_sent > HELLO_RETRY {
+                handshake.send(now);
+            }
+            return;
+        }
+        Some(Err(mismatch)) => {
+            error!("Can't play {}, who has {}", peer, mismatch);
+            problem.0 = Some(mismatch.describe(&strings));
+            commands.remove_resource::<Handshake>();
+            state.set(AppState::Network);
+            return;
+        }
+        Some(Ok(())) => commands.remove_resource::<Handshake>(),
+    }
+
+    let (p2p, chat) = match settings.start_session(transport, handshake.hello.clone()) {
+        Ok(session) => session,
+        Err(error) => {
+            error!("Couldn't start an online session: {}", error);
+            state.set(AppState::Menu);
+            return;
+        }
+    };
+    info!(
+        "Connecting to {} with {} frames of input delay",
+        peer, settings.input_delay
+    );
+
+    // Rollback tells entities apart by the order they're added to it in,
+    // which has to be the same on both peers
+    for ball in balls.iter() {
+        commands.entity(ball).add_rollback();
+    }
+    let mut rackets: Vec<(Entity, &Racket)> = rackets.iter().collect();
+    rackets.sort_by_key(|(_, racket)| racket.player_number);
+    for (entity, _) in rackets {
+        commands.entity(entity).add_rollback();
+    }
+    commands.insert_resource(Session::P2P(p2p));
+    commands.insert_resource(chat);
+}
+
+/// The protocol version of a hello from a version of the game whose packets
+/// can't otherwise be read, going by it starting the way every hello does.
+fn protocol_of(packet: &[u8]) -> Option<u32> {
+    match decode::<(u32, u32)>(packet) {
+        Some((0, protocol)) => Some(protocol),
+        _ => None,
+    }
+}
+
+fn handle_session_events(
+    mut session: Option<ResMut<Session<GgrsConfig>>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let session = match session.as_deref_mut() {
+        Some(Session::P2P(session)) => session,
+        _ => return,
+    };
+    for event in session.events() {
+        match event {
+            GgrsEvent::Synchronized { addr } => info!("Synchronized with {}", addr),
+            GgrsEvent::Disconnected { addr } => {
+                info!("{} disconnected", addr);
+                state.set(AppState::Menu);
+            }
+            GgrsEvent::NetworkInterrupted {
+                addr,
+                disconnect_timeout,
+            } => warn!(
+                "Lost contact with {}, disconnecting in {}ms",
+                addr, disconnect_timeout
+            ),
+            GgrsEvent::NetworkResumed { addr } => info!("Contact with {} resumed", addr),
+            event => debug!("{:?}", event),
+        }
+    }
+}
+
+fn exchange_chat(
+    chat: Option<Res<PeerChat>>,
+    mut outgoing: EventReader<OutgoingChat>,
+    mut lines: EventWriter<ChatLine>,
+) {
+    let chat = match chat {
+        Some(chat) => chat,
+        None => return,
+    };
+
+    for OutgoingChat(text) in outgoing.read() {
+        chat.transport.send(&encode(&Packet::Chat(text.clone())));
+        lines.send(ChatLine {
+            player_number: chat.local_player_number,
+            text: text.clone(),
+        });
+    }
+    for text in chat.inbox.lock().unwrap().drain(..) {
+        lines.send(ChatLine {
+            player_number: 3 - chat.local_player_number,
+            text: clean(&text),
+        });
+    }
+}
+
+/// Swaps checksums of the confirmed steps with the other peer. The match
+/// stops being stepped as soon as the two don't match, see [`in_sync`], but
+/// GGRS still polls the socket, so the two can go on to swap their sides of
+/// it.
+fn exchange_checks(
+    chat: Option<Res<PeerChat>>,
+    session: Option<Res<Session<GgrsConfig>>>,
+    mut check: ResMut<DesyncCheck>,
+    time: Res<Time>,
+) {
+    let (chat, session) = match (chat, session.as_deref()) {
+        (Some(chat), Some(Session::P2P(session))) => (chat, session),
+        _ => return,
+    };
+    let now = time.elapsed_seconds_f64();
+
+    for message in chat.checks.lock().unwrap().drain(..) {
+        check.receive(message, now);
+    }
+    check.confirm(session.confirmed_frame(), now);
+    for message in check.take_outgoing() {
+        chat.transport.send(&encode(&Packet::Desync(message)));
+    }
+}
+
+/// Whether the two peers' matches still agree, as far as the checks swapped
+/// so far go.
+fn in_sync(check: Res<DesyncCheck>) -> bool {
+    check.desync().is_none()
+}
+
+fn end_session(mut commands: Commands) {
+    commands.remove_resource::<Handshake>();
+    commands.remove_resource::<Session<GgrsConfig>>();
+    commands.remove_resource::<PeerChat>();
+}
+
+/// Packs the actions pressed for the local player into the byte GGRS sends to
+/// the other peer. Their seat is their handle.
+fn local_input(mut commands: Commands, players: Res<LocalPlayers>, actions: Res<Actions>) {
+    let inputs = players
+        .0
+        .iter()
+        .map(|handle| {
+            let pressed = actions.racket_input(*handle);
+            let input = [
+                (pressed.up, INPUT_UP),
+                (pressed.down, INPUT_DOWN),
+                (pressed.smash, INPUT_SMASH),
+                (pressed.tilt_up, INPUT_TILT_UP),
+                (pressed.tilt_down, INPUT_TILT_DOWN),
+                (pressed.dash, INPUT_DASH),
+            ]
+            .into_iter()
+            .filter(|(pressed, _)| *pressed)
+            .fold(0, |input, (_, bit)| input | bit);
+            (*handle, input)
+        })
+        .collect();
+    commands.insert_resource(LocalInputs::<GgrsConfig>(inputs));
+}
+
+fn apply_inputs(inputs: Res<PlayerInputs<GgrsConfig>>, mut sim_input: ResMut<SimInput>) {
+    for (racket, (input, _)) in sim_input.0.iter_mut().zip(inputs.iter()) {
+        *racket = RacketInput {
+            up: input & INPUT_UP != 0,
+            down: input & INPUT_DOWN != 0,
+            smash: input & INPUT_SMASH != 0,
+            tilt_up: input & INPUT_TILT_UP != 0,
+            tilt_down: input & INPUT_TILT_DOWN != 0,
+            dash: input & INPUT_DASH != 0,
+        };
+    }
+}
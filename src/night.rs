@@ -0,0 +1,124 @@
+//! Night matches: the court's dark, but for a circle of light round the ball
+//! and a little lamp on each racket.
+//!
+//! The dark is one big quad over the whole court, drawn through
+//! `shaders/night.wgsl`, that's see-through wherever a light falls on it.
+//! Purely visual, the simulation plays on as it would in daylight, and so do
+//! the CPUs.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle},
+    transform::TransformSystem,
+};
+
+use crate::{
+    sim::{Ball, MatchRules, Racket},
+    start_match, AppState,
+};
+
+/// As many lights as the shader takes, well over the ball and four rackets
+/// of a doubles match.
+const MAX_LIGHTS: usize = 16;
+// How far the light round the ball reaches, as a multiple of the ball's size,
+// and the lamp on each racket
+const BALL_LIGHT: f32 = 4.0;
+const RACKET_LAMP: f32 = 35.0;
+// Over everything on the court. Big enough to cover all of any arena, however
+// far the camera pulls back
+const DARK_Z: f32 = 50.0;
+const DARK_SIZE: f32 = 20_000.0;
+
+pub struct NightPlugin;
+
+impl Plugin for NightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<NightMaterial>::default())
+            .add_systems(OnEnter(AppState::Playing), spawn_dark.after(start_match))
+            .add_systems(
+                PostUpdate,
+                move_lights.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+struct NightMaterial {
+    /// Where each light is and how far it reaches: x and y on the court,
+    /// then the radius. A radius of 0 is a light that's off
+    #[uniform(0)]
+    lights: [Vec4; MAX_LIGHTS],
+}
+
+impl Material2d for NightMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/night.wgsl".into()
+    }
+}
+
+fn spawn_dark(
+    mut commands: Commands,
+    rules: Res<MatchRules>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<NightMaterial>>,
+) {
+    if !rules.night {
+        return;
+    }
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(1.0, 1.0)).into(),
+            material: materials.add(NightMaterial {
+                lights: [Vec4::ZERO; MAX_LIGHTS],
+            }),
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, DARK_Z),
+                scale: Vec3::new(DARK_SIZE, DARK_SIZE, 1.0),
+                ..default()
+            },
+            ..default()
+        },
+        StateScoped(AppState::Playing),
+    ));
+}
+
+/// Puts the lights where the ball and rackets are drawn this frame.
+fn move_lights(
+    mut materials: ResMut<Assets<NightMaterial>>,
+    dark: Query<&Handle<NightMaterial>>,
+    balls: Query<&GlobalTransform, With<Ball>>,
+    rackets: Query<&GlobalTransform, With<Racket>>,
+) {
+    let handle = match dark.get_single() {
+        Ok(handle) => handle,
+        Err(_) => return,
+    };
+    let material = match materials.get_mut(handle) {
+        Some(material) => material,
+        None => return,
+    };
+
+    let ball_lights = balls.iter().map(|transform| {
+        let (scale, _, translation) = transform.to_scale_rotation_translation();
+        translation
+            .truncate()
+            .extend(BALL_LIGHT * scale.y)
+            .extend(0.0)
+    });
+    let racket_lamps = rackets.iter().map(|transform| {
+        transform
+            .translation()
+            .truncate()
+            .extend(RACKET_LAMP)
+            .extend(0.0)
+    });
+    material.lights = [Vec4::ZERO; MAX_LIGHTS];
+    for (slot, light) in material
+        .lights
+        .iter_mut()
+        .zip(ball_lights.chain(racket_lamps))
+    {
+        *slot = light;
+    }
+}
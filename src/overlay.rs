@@ -0,0 +1,143 @@
+//! What the simulation sees, drawn over the game: the outline of everything
+//! the ball can hit, which way and how fast every ball is going and the path
+//! it'll take to the end of the court, and the frame rate and how many
+//! entities there are. F3 turns it on and off.
+
+use bevy::{
+    diagnostic::{DiagnosticPath, DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    ai::predict_path,
+    arena::ArenaBounds,
+    sim::{Ball, Collider, Goal, Racket, Velocity},
+    MainCamera,
+};
+
+const COLLIDER_COLOR: egui::Color32 = egui::Color32::from_rgb(80, 200, 255);
+const GOAL_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 80, 80);
+const BALL_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 255, 120);
+const PATH_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 220, 80);
+// The velocity arrow points to where the ball will be this many seconds on
+const ARROW_SECONDS: f32 = 0.25;
+
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(FrameTimeDiagnosticsPlugin)
+            .init_resource::<OverlayShown>()
+            .add_systems(Update, toggle_overlay)
+            .add_systems(Update, draw_overlay.after(toggle_overlay));
+    }
+}
+
+#[derive(Resource, Default)]
+struct OverlayShown(bool);
+
+fn toggle_overlay(keyboard_input: Res<ButtonInput<KeyCode>>, mut shown: ResMut<OverlayShown>) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        shown.0 = !shown.0;
+    }
+}
+
+fn draw_overlay(
+    shown: Res<OverlayShown>,
+    mut egui_context: EguiContexts,
+    diagnostics: Res<DiagnosticsStore>,
+    bounds: Res<ArenaBounds>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    colliders: Query<&Transform, Or<(With<Collider>, With<Racket>)>>,
+    goals: Query<&Transform, With<Goal>>,
+    balls: Query<(&Transform, &Velocity), With<Ball>>,
+    entities: Query<()>,
+) {
+    if !shown.0 {
+        return;
+    }
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let to_screen = |position: Vec2| {
+        camera
+            .world_to_viewport(camera_transform, position.extend(0.0))
+            .map(|at| egui::pos2(at.x, at.y))
+    };
+    let outline = |transform: &Transform| -> Vec<egui::Pos2> {
+        [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)]
+            .into_iter()
+            .filter_map(|(x, y)| {
+                to_screen(transform.transform_point(Vec3::new(x, y, 0.0)).truncate())
+            })
+            .collect()
+    };
+
+    let ctx = egui_context.ctx_mut();
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("overlay"),
+    ));
+    for transform in colliders.iter() {
+        painter.add(egui::Shape::closed_line(
+            outline(transform),
+            (1.0, COLLIDER_COLOR),
+        ));
+    }
+    for transform in goals.iter() {
+        painter.add(egui::Shape::closed_line(
+            outline(transform),
+            (1.0, GOAL_COLOR),
+        ));
+    }
+    for (transform, velocity) in balls.iter() {
+        painter.add(egui::Shape::closed_line(
+            outline(transform),
+            (1.0, BALL_COLOR),
+        ));
+
+        let position = transform.translation.truncate();
+        let end = if velocity.x < 0.0 {
+            bounds.min.x
+        } else {
+            bounds.max.x
+        };
+        let radius = transform.scale.y / 2.0;
+        let path: Vec<_> = std::iter::once(position)
+            .chain(predict_path(position, velocity.0, end, &bounds, radius))
+            .filter_map(to_screen)
+            .collect();
+        painter.add(egui::Shape::line(path, (1.0, PATH_COLOR)));
+
+        if let (Some(from), Some(to)) = (
+            to_screen(position),
+            to_screen(position + velocity.0 * ARROW_SECONDS),
+        ) {
+            painter.arrow(from, to - from, egui::Stroke::new(2.0_f32, BALL_COLOR));
+        }
+    }
+
+    let average = |path: &DiagnosticPath| {
+        diagnostics
+            .get(path)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    egui::Area::new(egui::Id::new("overlay_readout"))
+        .fixed_pos(egui::pos2(8.0, 8.0))
+        .show(ctx, |ui| {
+            ui.monospace(format!(
+                "{:.0} fps, {:.1} ms",
+                average(&FrameTimeDiagnosticsPlugin::FPS),
+                average(&FrameTimeDiagnosticsPlugin::FRAME_TIME) * 1000.0
+            ));
+            ui.monospace(format!(
+                "{} entities, {} balls, {} colliders",
+                entities.iter().count(),
+                balls.iter().count(),
+                colliders.iter().count()
+            ));
+        });
+}
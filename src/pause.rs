@@ -0,0 +1,281 @@
+//! Pausing a local match when the window loses focus, so nobody concedes a
+//! point while they're off in another window, unless the settings say to
+//! play on. The match is paused without leaving [`AppState::Playing`], so
+//! everything in it stays as it was until it's resumed.
+//!
+//! Whatever the settings, a match is paused too when the play/pause media key
+//! is pressed, which resumes it again, when the window's hidden from view, as
+//! it is behind the lock screen or a screensaver, and when the system
+//! suspends the game, as it does before the machine goes to sleep, so the
+//! rally isn't lost to it on waking up.
+//!
+//! Matches are paused as well when a gamepad's plugged in or pulled out, see
+//! [`hotplug`](crate::hotplug).
+//!
+//! Matches that can be saved have a button to save them as they're left, see
+//! [`saved_match`].
+//!
+//! Online matches don't pause, as the other end would carry on without them.
+
+use bevy::{
+    prelude::*,
+    window::{AppLifecycle, PrimaryWindow, WindowFocused, WindowOccluded},
+};
+
+use crate::{
+    hotplug::GamepadPrompt,
+    locale::Strings,
+    recording::Playback,
+    saved_match::{self, SaveMatch},
+    settings::Settings,
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        PANEL_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, MatchState,
+};
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, pause_on_focus_loss)
+            .add_systems(Update, pause_on_media_key)
+            .add_systems(Update, pause_when_away)
+            .add_systems(OnEnter(MatchState::Paused), spawn_pause_menu)
+            .add_systems(Update, pause_action.run_if(in_state(MatchState::Paused)));
+    }
+}
+
+#[derive(Component)]
+pub(crate) enum PauseAction {
+    Resume,
+    Save,
+    Leave,
+}
+
+/// Whether a match of `mode` can be paused. Nobody's watching the demo to
+/// begin with.
+fn pausable(mode: GameMode) -> bool {
+    !matches!(mode, GameMode::Online | GameMode::Demo)
+}
+
+/// Whether there's a match going, one that's neither paused nor over.
+pub(crate) fn running(state: Option<Res<State<MatchState>>>) -> bool {
+    state.is_some_and(|state| *state.get() == MatchState::Running)
+}
+
+pub(crate) fn pause_on_focus_loss(
+    mut focused: EventReader<WindowFocused>,
+    primary: Query<(), With<PrimaryWindow>>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    state: Option<Res<State<MatchState>>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    let lost = focused
+        .read()
+        .any(|event| primary.contains(event.window) && !event.focused);
+    if lost && pausable(*mode) && settings.pause_on_focus_loss && running(state) {
+        next_state.set(MatchState::Paused);
+    }
+}
+
+fn pause_on_media_key(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mode: Res<GameMode>,
+    state: Option<Res<State<MatchState>>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::MediaPlayPause) && pausable(*mode) && running(state) {
+        // The pause menu would take it for resuming
+        keyboard_input.reset(KeyCode::MediaPlayPause);
+        next_state.set(MatchState::Paused);
+    }
+}
+
+/// Pauses a match nobody can be playing, with the window out of sight or the
+/// game about to be suspended.
+fn pause_when_away(
+    mut occluded: EventReader<WindowOccluded>,
+    mut lifecycle: EventReader<AppLifecycle>,
+    primary: Query<(), With<PrimaryWindow>>,
+    mode: Res<GameMode>,
+    state: Option<Res<State<MatchState>>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    let hidden = occluded
+        .read()
+        .any(|event| primary.contains(event.window) && event.occluded);
+    let suspended = lifecycle
+        .read()
+        .any(|event| matches!(event, AppLifecycle::WillSuspend | AppLifecycle::Suspended));
+    if (hidden || suspended) && pausable(*mode) && running(state) {
+        info!(
+            "Pausing with the game {}",
+            if suspended {
+                "suspended"
+            } else {
+                "out of sight"
+            }
+        );
+        next_state.set(MatchState::Paused);
+    }
+}
+
+fn spawn_pause_menu(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    mode: Res<GameMode>,
+    playback: Option<Res<Playback>>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                background_color: PANEL_COLOR.into(),
+                ..screen_root()
+            },
+            StateScoped(MatchState::Paused),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("pause.title"), 60.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+            parent.spawn((
+                text(&font, "", 20.0, ACCENT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+                GamepadPrompt,
+            ));
+            spawn_button(parent, &font, 0, strings.get("pause.resume")).insert(PauseAction::Resume);
+            let mut index = 1;
+            // A replay can't be carried on with, only watched again
+            if saved_match::can_save(*mode) && playback.is_none() {
+                spawn_button(parent, &font, index, strings.get("pause.save"))
+                    .insert(PauseAction::Save);
+                index += 1;
+            }
+            spawn_button(parent, &font, index, strings.get("pause.leave"))
+                .insert(PauseAction::Leave);
+            parent.spawn(
+                text(&font, strings.get("pause.hint"), 18.0, DIM_TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+        });
+}
+
+pub(crate) fn pause_action(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    actions: Query<&PauseAction>,
+    mode: Res<GameMode>,
+    mut state: ResMut<NextState<AppState>>,
+    mut match_state: ResMut<NextState<MatchState>>,
+    mut save: EventWriter<SaveMatch>,
+) {
+    let chosen = activated
+        .read()
+        .filter_map(|event| actions.get(event.0).ok())
+        .last();
+    let leave = keyboard_input.just_pressed(KeyCode::Escape);
+    let resume = keyboard_input.just_pressed(KeyCode::MediaPlayPause);
+    match chosen {
+        Some(PauseAction::Leave) => {
+            state.set(mode.after_match());
+        }
+        Some(PauseAction::Save) => {
+            // Saved as it's left, while everything in it is still there
+            save.send(SaveMatch);
+            state.set(mode.after_match());
+        }
+        _ if leave => {
+            // The menu it goes back to would take it for going back again
+            keyboard_input.reset(KeyCode::Escape);
+            state.set(mode.after_match());
+        }
+        Some(PauseAction::Resume) => {
+            match_state.set(MatchState::Running);
+        }
+        None if resume => {
+            keyboard_input.reset(KeyCode::MediaPlayPause);
+            match_state.set(MatchState::Running);
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+
+    /// What a match of `mode` is left as once `send` has sent its events to
+    /// the primary window.
+    fn after(mode: GameMode, send: impl FnOnce(&mut World, Entity)) -> MatchState {
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .insert_state(AppState::Playing)
+            .add_sub_state::<MatchState>()
+            .add_event::<WindowOccluded>()
+            .add_event::<AppLifecycle>()
+            .insert_resource(mode)
+            .add_systems(Update, pause_when_away);
+        let window = app.world_mut().spawn(PrimaryWindow).id();
+        send(app.world_mut(), window);
+        // The state it's put in is only entered on the frame after
+        app.update();
+        app.update();
+        *app.world().resource::<State<MatchState>>().get()
+    }
+
+    fn occluded(occluded: bool) -> impl FnOnce(&mut World, Entity) {
+        move |world, window| {
+            world.send_event(WindowOccluded { window, occluded });
+        }
+    }
+
+    #[test]
+    fn hiding_the_window_pauses_the_match() {
+        assert_eq!(after(GameMode::Versus, occluded(true)), MatchState::Paused);
+        assert_eq!(
+            after(GameMode::Versus, occluded(false)),
+            MatchState::Running
+        );
+        // Some other window, not the one being played in
+        let other = |world: &mut World, _| {
+            let window = world.spawn_empty().id();
+            world.send_event(WindowOccluded {
+                window,
+                occluded: true,
+            });
+        };
+        assert_eq!(after(GameMode::Versus, other), MatchState::Running);
+    }
+
+    #[test]
+    fn suspending_the_game_pauses_the_match() {
+        for lifecycle in [AppLifecycle::WillSuspend, AppLifecycle::Suspended] {
+            let suspend = move |world: &mut World, _| {
+                world.send_event(lifecycle);
+            };
+            assert_eq!(after(GameMode::Versus, suspend), MatchState::Paused);
+        }
+        let resume = |world: &mut World, _| {
+            world.send_event(AppLifecycle::WillResume);
+        };
+        assert_eq!(after(GameMode::Versus, resume), MatchState::Running);
+    }
+
+    #[test]
+    fn online_matches_play_on_out_of_sight() {
+        assert_eq!(after(GameMode::Online, occluded(true)), MatchState::Running);
+    }
+}
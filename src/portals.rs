@@ -0,0 +1,69 @@
+//! What the portals look like: a spinning square at each end, each in the
+//! theme's colour for it, growing as the pair opens and shrinking away as it closes.
+//!
+//! Purely visual, where they are and what they do is up to the simulation.
+
+use bevy::prelude::*;
+
+use crate::{
+    seams::Clock,
+    sim::{MatchRules, Portals, PORTAL_RADIUS},
+    start_match,
+    theme::ThemePart,
+    AppState, MatchState,
+};
+
+// Radians a second, the two ends turning opposite ways
+const PORTAL_SPIN: f32 = 1.5;
+// Over the court markings, under the ball
+const PORTAL_Z: f32 = -0.4;
+
+pub struct PortalsPlugin;
+
+impl Plugin for PortalsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Playing), spawn_portals.after(start_match))
+            .add_systems(
+                Update,
+                animate_portals::<Time>.run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Which end of the pair a sprite shows.
+#[derive(Component)]
+struct PortalEnd(usize);
+
+fn spawn_portals(mut commands: Commands, rules: Res<MatchRules>) {
+    if !rules.portals {
+        return;
+    }
+    for end in 0..2 {
+        commands.spawn((
+            SpriteBundle {
+                // Nothing to see until the pair starts opening
+                transform: Transform::from_scale(Vec3::ZERO),
+                ..default()
+            },
+            PortalEnd(end),
+            ThemePart::Portal(end),
+            StateScoped(AppState::Playing),
+        ));
+    }
+}
+
+fn animate_portals<C: Clock>(
+    time: Res<C>,
+    portals: Res<Portals>,
+    rules: Res<MatchRules>,
+    mut ends: Query<(&PortalEnd, &mut Transform)>,
+) {
+    let size = 2.0 * PORTAL_RADIUS * portals.openness(rules.time_step);
+    let spin = PORTAL_SPIN * time.elapsed_seconds_f64() as f32;
+    for (end, mut transform) in ends.iter_mut() {
+        transform.translation = portals.ends[end.0].extend(PORTAL_Z);
+        transform.scale = Vec3::new(size, size, 1.0);
+        let direction = if end.0 == 0 { 1.0 } else { -1.0 };
+        transform.rotation = Quat::from_rotation_z(direction * spin);
+    }
+}
@@ -0,0 +1,376 @@
+//! Practice against a ball machine. It takes player 2's place, firing balls at
+//! player 1 for as long as they like, or the balls of a drill, and counts how
+//! many come back.
+
+use std::{f32::consts::PI, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::{
+    ai::predict_path,
+    arena::ArenaBounds,
+    drills::DrillRun,
+    locale::Strings,
+    settings::Settings,
+    sim::{
+        extent, launch_ball, Ball, GameplayConfig, GoalEvent, MatchRules, Netted, Racket,
+        ServedOnce, Side, SimRng, SimSystems, Velocity,
+    },
+    ui::{text, UiFont, TEXT_COLOR},
+    AppState, GameMode, MatchState,
+};
+
+/// The machine is squatter than the racket it replaces.
+const MACHINE_SIZE: Vec3 = Vec3::new(60.0, 40.0, 0.0);
+
+// The ghost path is dotted, with at most this many dots per ball
+const GHOST_DOTS: usize = 64;
+const GHOST_SPACING: f32 = 24.0;
+const GHOST_DOT_SIZE: f32 = 6.0;
+const GHOST_OPACITY: f32 = 0.3;
+
+pub struct PracticePlugin;
+
+impl Plugin for PracticePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BallMachine>()
+            .init_resource::<PracticeStats>()
+            .add_systems(OnEnter(AppState::Playing), start_practice)
+            .add_systems(
+                Update,
+                (
+                    install_machine,
+                    count_returns,
+                    update_practice_hud.after(count_returns),
+                    attach_ghosts,
+                    update_ghosts.after(attach_ghosts),
+                    despawn_orphaned_ghosts,
+                )
+                    .run_if(in_state(MatchState::Running).and_then(practicing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                fire_ball_machine
+                    .run_if(in_state(MatchState::Running).and_then(practicing))
+                    .before(SimSystems),
+            );
+    }
+}
+
+/// What the ball machine fires, and how often.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct BallMachine {
+    /// Seconds between balls
+    pub interval: f32,
+    /// How fast the balls go, as a multiple of a serve's speed
+    pub speed: f32,
+    /// How far off straight at the player the balls can go, in degrees
+    pub spread: f32,
+}
+
+impl Default for BallMachine {
+    fn default() -> Self {
+        BallMachine {
+            interval: 2.0,
+            speed: 1.0,
+            spread: 30.0,
+        }
+    }
+}
+
+/// How the practice session is going.
+#[derive(Resource, Default, Debug)]
+struct PracticeStats {
+    returned: u32,
+    missed: u32,
+}
+
+impl PracticeStats {
+    /// Fraction of the balls that have come back, of those that are done with.
+    fn accuracy(&self) -> Option<f32> {
+        let done = self.returned + self.missed;
+        (done > 0).then(|| self.returned as f32 / done as f32)
+    }
+}
+
+/// The ball machine, standing where player 2's racket was.
+#[derive(Component)]
+struct Machine {
+    next_ball: Timer,
+}
+
+#[derive(Component)]
+struct PracticeHud;
+
+/// Kept on a ball: the dots showing where it's going.
+#[derive(Component)]
+struct Ghost {
+    dots: Vec<Entity>,
+}
+
+#[derive(Component)]
+struct GhostDot {
+    ball: Entity,
+}
+
+fn practicing(mode: Res<GameMode>) -> bool {
+    *mode == GameMode::Practice
+}
+
+fn start_practice(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    mode: Res<GameMode>,
+    settings: Res<Settings>,
+    mut machine: ResMut<BallMachine>,
+    mut stats: ResMut<PracticeStats>,
+) {
+    if *mode != GameMode::Practice {
+        return;
+    }
+
+    *machine = BallMachine {
+        interval: settings.machine_interval,
+        speed: settings.machine_speed,
+        spread: settings.machine_spread,
+    };
+    *stats = PracticeStats::default();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(12.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn((text(&font, "", 28.0, TEXT_COLOR), PracticeHud));
+        });
+}
+
+/// Turns player 2's racket into the ball machine, whenever the arena spawns one.
+fn install_machine(
+    mut commands: Commands,
+    machine: Res<BallMachine>,
+    mut rackets: Query<(Entity, &Racket, &mut Transform)>,
+) {
+    for (entity, racket, mut transform) in rackets.iter_mut() {
+        if racket.player_number != 2 {
+            continue;
+        }
+        transform.rotation = Quat::IDENTITY;
+        transform.scale = MACHINE_SIZE;
+        commands.entity(entity).remove::<Racket>().insert(Machine {
+            next_ball: Timer::from_seconds(machine.interval, TimerMode::Repeating),
+        });
+    }
+}
+
+/// Fires a ball every so often, at random, or the next ball of the drill
+/// being run from where it's fired from.
+fn fire_ball_machine(
+    mut commands: Commands,
+    ball_machine: Res<BallMachine>,
+    rules: Res<MatchRules>,
+    config: Res<GameplayConfig>,
+    bounds: Res<ArenaBounds>,
+    mut drill: ResMut<DrillRun>,
+    mut machines: Query<(&mut Machine, &mut Transform, &Side)>,
+    mut rng: ResMut<SimRng>,
+) {
+    for (mut machine, mut transform, side) in machines.iter_mut() {
+        let serve = drill.next_serve();
+        if drill.drill.is_some() {
+            // Out of balls
+            let serve = match serve {
+                Some(serve) => serve,
+                None => continue,
+            };
+            machine
+                .next_ball
+                .set_duration(Duration::from_secs_f32(serve.wait));
+            transform.translation.y = serve.machine_y(&bounds, transform.scale.y);
+        }
+        machine
+            .next_ball
+            .tick(Duration::from_secs_f32(rules.time_step.seconds()));
+        if !machine.next_ball.just_finished() {
+            continue;
+        }
+
+        let direction = side.opponent().direction();
+        let (angle, speed) = match serve {
+            Some(serve) => (serve.angle * PI / 180.0, serve.speed),
+            None => {
+                let spread = ball_machine.spread * PI / 180.0;
+                (rng.range(-spread, spread), ball_machine.speed)
+            }
+        };
+        let velocity = Vec2::new(direction * angle.cos(), angle.sin()) * config.ball_speed * speed;
+        // Out of the front of the machine
+        let start =
+            transform.translation.truncate() + Vec2::new(direction * transform.scale.x / 2.0, 0.0);
+        launch_ball(&mut commands, rules.ball, start, velocity)
+            .insert(ServedOnce)
+            .insert(StateScoped(AppState::Playing));
+        if serve.is_some() {
+            drill.fired += 1;
+        }
+    }
+}
+
+/// A ball in the machine's goal came back, one in the player's got past them.
+fn count_returns(mut goals: EventReader<GoalEvent>, mut stats: ResMut<PracticeStats>) {
+    for goal in goals.read() {
+        if goal.scored_by == 1 {
+            stats.returned += 1;
+        } else {
+            stats.missed += 1;
+        }
+    }
+}
+
+fn update_practice_hud(
+    settings: Res<Settings>,
+    strings: Res<Strings>,
+    stats: Res<PracticeStats>,
+    mut texts: Query<&mut Text, With<PracticeHud>>,
+    added: Query<(), Added<PracticeHud>>,
+) {
+    if !stats.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let line = if !settings.practice_score {
+        strings.get("practice.free_play").to_string()
+    } else {
+        match stats.accuracy() {
+            Some(accuracy) => strings.fill(
+                "practice.returned",
+                &[
+                    ("returned", &stats.returned),
+                    ("served", &(stats.returned + stats.missed)),
+                    ("percent", &format!("{:.0}", accuracy * 100.0)),
+                ],
+            ),
+            None => strings.fill(
+                "practice.interval",
+                &[("seconds", &format!("{:.1}", settings.machine_interval))],
+            ),
+        }
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = line.clone();
+    }
+}
+
+fn attach_ghosts(mut commands: Commands, balls: Query<Entity, (With<Ball>, Without<Ghost>)>) {
+    for ball in balls.iter() {
+        let dots = (0..GHOST_DOTS)
+            .map(|_| {
+                commands
+                    .spawn((
+                        SpriteBundle {
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        },
+                        GhostDot { ball },
+                        StateScoped(AppState::Playing),
+                    ))
+                    .id()
+            })
+            .collect();
+        commands.entity(ball).insert(Ghost { dots });
+    }
+}
+
+/// Dots the way to player 1's racket, working out the bounces the same way as
+/// the hard CPU does.
+fn update_ghosts(
+    settings: Res<Settings>,
+    bounds: Res<ArenaBounds>,
+    rackets: Query<(&Racket, &Transform), Without<GhostDot>>,
+    balls: Query<(&Transform, &Velocity, &Sprite, &Ghost, Option<&Netted>), With<Ball>>,
+    mut dots: Query<
+        (&mut Transform, &mut Sprite, &mut Visibility),
+        (With<GhostDot>, Without<Ball>),
+    >,
+) {
+    let face = rackets
+        .iter()
+        .find(|(racket, _)| racket.player_number == 1)
+        .map(|(_, transform)| {
+            // The side facing the middle of the court, where the ball meets it
+            let towards_center = if transform.translation.x < (bounds.min.x + bounds.max.x) / 2.0 {
+                1.0
+            } else {
+                -1.0
+            };
+            transform.translation.x + towards_center * extent(transform).x / 2.0
+        });
+
+    for (ball_transform, velocity, ball_sprite, ghost, netted) in balls.iter() {
+        let start = ball_transform.translation.truncate();
+        let path = match face {
+            Some(face) if settings.ghost_path && netted.is_none() => {
+                let radius = ball_transform.scale.y / 2.0;
+                let mut path = predict_path(start, velocity.0, face, &bounds, radius);
+                path.insert(0, start);
+                path
+            }
+            _ => Vec::new(),
+        };
+
+        // Evenly spaced along the path, whatever corners it turns
+        let mut points = Vec::with_capacity(GHOST_DOTS);
+        let mut carried = GHOST_SPACING;
+        for segment in path.windows(2) {
+            let (from, to) = (segment[0], segment[1]);
+            let length = from.distance(to);
+            let mut along = GHOST_SPACING - carried;
+            while along < length && points.len() < GHOST_DOTS {
+                points.push(from.lerp(to, along / length));
+                along += GHOST_SPACING;
+            }
+            carried = length - (along - GHOST_SPACING);
+        }
+
+        let mut color = ball_sprite.color;
+        color.set_alpha(ball_sprite.color.alpha() * GHOST_OPACITY);
+        for (index, entity) in ghost.dots.iter().enumerate() {
+            let (mut transform, mut sprite, mut visibility) = match dots.get_mut(*entity) {
+                Ok(parts) => parts,
+                Err(_) => continue,
+            };
+            match points.get(index) {
+                Some(point) => {
+                    *visibility = Visibility::Inherited;
+                    transform.translation = point.extend(ball_transform.translation.z - 0.2);
+                    transform.scale = Vec3::new(GHOST_DOT_SIZE, GHOST_DOT_SIZE, 1.0);
+                    sprite.color = color;
+                }
+                None => *visibility = Visibility::Hidden,
+            }
+        }
+    }
+}
+
+fn despawn_orphaned_ghosts(
+    mut commands: Commands,
+    dots: Query<(Entity, &GhostDot)>,
+    balls: Query<(), With<Ball>>,
+) {
+    for (entity, dot) in dots.iter() {
+        if balls.get(dot.ball).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! How likely each player is to win the match from where it stands, for the
+//! meter under the scoreboard.
+//!
+//! Nothing's known about how good either player is, so it's all down to the
+//! score, who's serving and the rules. Every point's taken to go to whoever
+//! served it a little more often than not, and with that each score in a set
+//! is a state of a Markov chain, leading to the next with the chances of
+//! either player winning the point. Player 1's chances of taking the set from
+//! every one of them are worked out once for the rules of the match, and
+//! from those, their chances of taking the match from every count of sets.
+
+use crate::sim::{MatchRules, Score};
+
+/// How often a point's won by whoever served it.
+const SERVE_WIN: f32 = 0.55;
+/// Sets to more than this aren't worked out, as the chances hardly move from
+/// one point to the next in them.
+const MAX_POINTS: i32 = 50;
+/// The chances are worked out over and over until none of them moves by more
+/// than this, as a tie can go on and on when a set has to be won by two.
+const TOLERANCE: f32 = 1e-6;
+const MAX_SWEEPS: usize = 1000;
+
+/// Player 1's chances of winning from every score in a match played by some
+/// rules, with either player to serve.
+pub struct WinTable {
+    rules: MatchRules,
+    /// Points past which a set only turns on the difference between them
+    base: i32,
+    /// How many scores there are for either player in `sets`
+    span: usize,
+    /// Player 1's chance of taking the set from each score, by their points
+    /// then player 2's, for player 1 serving and then player 2
+    sets: Vec<[f32; 2]>,
+    /// Player 1's chance of taking the match at the start of a set, by the
+    /// sets they've won then player 2's, for each serving
+    matches: Vec<[f32; 2]>,
+}
+
+/// Player 1's chance of winning a point served by `server`.
+fn point_chance(server: i32) -> f32 {
+    if server == 1 {
+        SERVE_WIN
+    } else {
+        1.0 - SERVE_WIN
+    }
+}
+
+impl WinTable {
+    /// Works the chances out for `rules`, unless the score can't say who's
+    /// winning: in timed matches, and sets too long to be worth it.
+    pub fn new(rules: &MatchRules) -> Option<Self> {
+        if rules.time_limit.is_some()
+            || rules.points_to_win > MAX_POINTS
+            || rules.golden_point.is_some_and(|at| at > MAX_POINTS)
+        {
+            return None;
+        }
+
+        let base = (rules.points_to_win - 1)
+            .max(rules.golden_point.unwrap_or(0))
+            .max(0);
+        let span = (base + rules.win_by.max(1) + 1) as usize;
+        let mut table = WinTable {
+            rules: *rules,
+            base,
+            span,
+            sets: vec![[0.0; 2]; span * span],
+            matches: Vec::new(),
+        };
+
+        // A tie past the base leads back to itself, so the chances are
+        // filled in again and again until they settle
+        for _ in 0..MAX_SWEEPS {
+            let mut moved: f32 = 0.0;
+            for a in 0..span as i32 {
+                for b in 0..span as i32 {
+                    for server in [1, 2] {
+                        let chance = table.set_chance_from(a, b, server);
+                        let index = table.index(a, b);
+                        let old = &mut table.sets[index][server as usize - 1];
+                        moved = moved.max((chance - *old).abs());
+                        *old = chance;
+                    }
+                }
+            }
+            if moved < TOLERANCE {
+                break;
+            }
+        }
+
+        // Each set's started by whoever won the one before, from the head
+        // starts, and there's no going back on a set, so the match's chances
+        // are worked back from the last
+        let needed = rules.sets_to_win();
+        let start = Score::starting(rules);
+        let mut matches = vec![[0.0; 2]; (needed * needed) as usize];
+        for won in (0..needed).rev() {
+            for lost in (0..needed).rev() {
+                for server in [1, 2] {
+                    let set = table.set_chance(start.player1, start.player2, server);
+                    let after_win = if won + 1 == needed {
+                        1.0
+                    } else {
+                        matches[((won + 1) * needed + lost) as usize][0]
+                    };
+                    let after_loss = if lost + 1 == needed {
+                        0.0
+                    } else {
+                        matches[(won * needed + lost + 1) as usize][1]
+                    };
+                    matches[(won * needed + lost) as usize][server as usize - 1] =
+                        set * after_win + (1.0 - set) * after_loss;
+                }
+            }
+        }
+        table.matches = matches;
+        Some(table)
+    }
+
+    fn index(&self, a: i32, b: i32) -> usize {
+        // Only the difference matters past the base
+        let past = (a.min(b) - self.base).max(0);
+        let a = ((a - past).max(0) as usize).min(self.span - 1);
+        let b = ((b - past).max(0) as usize).min(self.span - 1);
+        a * self.span + b
+    }
+
+    /// Player 1's chance of taking the set from `a` points to `b`, with
+    /// `server` to serve, as far as it's been worked out.
+    fn set_chance(&self, a: i32, b: i32, server: i32) -> f32 {
+        let score = Score {
+            player1: a,
+            player2: b,
+            ..Score::default()
+        };
+        match self.rules.set_winner(&score) {
+            Some(1) => 1.0,
+            Some(_) => 0.0,
+            None => self.sets[self.index(a, b)][server as usize - 1],
+        }
+    }
+
+    /// Player 1's chance of taking the set from `a` points to `b`, from the
+    /// chances of the scores the next point leads to. Whoever wins a point
+    /// serves the next.
+    fn set_chance_from(&self, a: i32, b: i32, server: i32) -> f32 {
+        let point = point_chance(server);
+        // A golden point, so the next point takes the set
+        if self.rules.golden_point.is_some_and(|at| a == b && a >= at) {
+            return point;
+        }
+        point * self.set_chance(a + 1, b, 1) + (1.0 - point) * self.set_chance(a, b + 1, 2)
+    }
+
+    /// Player 1's chance of winning the match from `score`, with `server` to
+    /// serve the next point.
+    pub fn chance(&self, score: &Score, server: i32) -> f32 {
+        match self.rules.match_winner(score) {
+            Some(1) => return 1.0,
+            Some(_) => return 0.0,
+            None => {}
+        }
+
+        let set = match self.rules.set_winner(score) {
+            Some(1) => 1.0,
+            Some(_) => 0.0,
+            None if score.golden_point => point_chance(server),
+            None => self.set_chance(score.player1, score.player2, server),
+        };
+        let needed = self.rules.sets_to_win();
+        let after = |won: i32, lost: i32, server: i32| {
+            if won >= needed {
+                1.0
+            } else if lost >= needed {
+                0.0
+            } else {
+                self.matches[(won * needed + lost) as usize][server as usize - 1]
+            }
+        };
+        let (won, lost) = (score.player1_sets, score.player2_sets);
+        set * after(won + 1, lost, 1) + (1.0 - set) * after(won, lost + 1, 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(player1: i32, player2: i32) -> Score {
+        Score {
+            player1,
+            player2,
+            ..Score::default()
+        }
+    }
+
+    #[test]
+    fn chances_follow_the_score() {
+        let rules = MatchRules::default();
+        let table = WinTable::new(&rules).unwrap();
+        let even = table.chance(&score(0, 0), 1);
+        // Serving's worth something, but not much
+        assert!(even > 0.5 && even < 0.6);
+        assert!(table.chance(&score(3, 0), 1) > even);
+        assert!(table.chance(&score(0, 3), 2) < 1.0 - even);
+
+        // A tie past the end of the set is no different from the first
+        let deuce = table.chance(&score(10, 10), 1);
+        assert!((table.chance(&score(25, 25), 1) - deuce).abs() < 1e-4);
+    }
+
+    #[test]
+    fn a_won_match_is_certain() {
+        let rules = MatchRules::default();
+        let table = WinTable::new(&rules).unwrap();
+        let mut won = score(0, 0);
+        won.player1_sets = rules.sets_to_win();
+        assert_eq!(table.chance(&won, 2), 1.0);
+        let mut lost = score(0, 0);
+        lost.player2_sets = rules.sets_to_win();
+        assert_eq!(table.chance(&lost, 1), 0.0);
+    }
+
+    #[test]
+    fn nothing_for_timed_matches() {
+        assert!(WinTable::new(&MatchRules::blitz(60)).is_none());
+    }
+}
@@ -0,0 +1,220 @@
+//! Where each frame's time goes, for catching whatever's made the game slower:
+//! how long each schedule of the frame takes to run its systems, the
+//! simulation's steps all together, how many entities there are, and how many
+//! steps each frame has had to play to keep up. F4 turns it on and off.
+//!
+//! Schedules are timed from one end to the other, so any system's cost shows
+//! up in the schedule it's added to. Rendering happens apart from all of them
+//! and isn't counted.
+
+use bevy::{
+    app::{FixedMainScheduleOrder, MainScheduleOrder},
+    diagnostic::{
+        Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, EntityCountDiagnosticsPlugin,
+        RegisterDiagnostic,
+    },
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+    utils::Instant,
+};
+use bevy_egui::{egui, EguiContexts};
+
+const STEPS: DiagnosticPath = DiagnosticPath::const_new("profiler/steps");
+const OVERSTEP: DiagnosticPath = DiagnosticPath::const_new("profiler/overstep");
+// Frames of history each readout is averaged over
+const HISTORY: usize = 120;
+
+const STAGE_COUNT: usize = 6;
+
+/// Every schedule timed, with the path its times are kept under.
+static STAGES: [(&str, DiagnosticPath); STAGE_COUNT] = [
+    ("First", DiagnosticPath::const_new("profiler/first")),
+    (
+        "PreUpdate",
+        DiagnosticPath::const_new("profiler/pre_update"),
+    ),
+    ("Update", DiagnosticPath::const_new("profiler/update")),
+    (
+        "Simulation",
+        DiagnosticPath::const_new("profiler/simulation"),
+    ),
+    (
+        "PostUpdate",
+        DiagnosticPath::const_new("profiler/post_update"),
+    ),
+    ("Last", DiagnosticPath::const_new("profiler/last")),
+];
+
+// Where the simulation's schedule is in them
+const SIMULATION: usize = 3;
+
+pub struct ProfilerPlugin;
+
+impl Plugin for ProfilerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(EntityCountDiagnosticsPlugin)
+            .init_resource::<ProfilerShown>()
+            .init_resource::<FrameTimes>()
+            .add_systems(Update, toggle_profiler)
+            .add_systems(Update, draw_profiler.after(toggle_profiler))
+            .add_systems(FrameRecorded, record_frame);
+        for (_, path) in &STAGES {
+            app.register_diagnostic(
+                Diagnostic::new(path.clone())
+                    .with_max_history_length(HISTORY)
+                    .with_suffix("ms"),
+            );
+        }
+        app.register_diagnostic(Diagnostic::new(STEPS).with_max_history_length(HISTORY))
+            .register_diagnostic(Diagnostic::new(OVERSTEP).with_max_history_length(HISTORY));
+    }
+
+    // Once every plugin's had its say in the order schedules run in
+    fn finish(&self, app: &mut App) {
+        time_stage(app, 0, First);
+        time_stage(app, 1, PreUpdate);
+        time_stage(app, 2, Update);
+        time_stage(app, SIMULATION, FixedUpdate);
+        time_stage(app, 4, PostUpdate);
+        time_stage(app, 5, Last);
+        let mut order = app.world_mut().resource_mut::<MainScheduleOrder>();
+        order.labels.push(FrameRecorded.intern());
+    }
+}
+
+/// Run right before the schedule at [`STAGES`]`[index]`.
+#[derive(ScheduleLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Started(usize);
+
+/// Run right after the schedule at [`STAGES`]`[index]`.
+#[derive(ScheduleLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Finished(usize);
+
+/// Run once everything else in the frame has.
+#[derive(ScheduleLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FrameRecorded;
+
+#[derive(Resource, Default)]
+struct ProfilerShown(bool);
+
+/// What the frame so far has spent in each schedule, in seconds.
+#[derive(Resource, Default)]
+struct FrameTimes {
+    started: [Option<Instant>; STAGE_COUNT],
+    spent: [f64; STAGE_COUNT],
+    /// Times the simulation's schedule has run
+    steps: u32,
+}
+
+/// Times every run of `stage`, under [`STAGES`]`[index]`.
+fn time_stage(app: &mut App, index: usize, stage: impl ScheduleLabel) {
+    app.add_systems(Started(index), move |mut times: ResMut<FrameTimes>| {
+        times.started[index] = Some(Instant::now());
+    })
+    .add_systems(Finished(index), move |mut times: ResMut<FrameTimes>| {
+        if let Some(started) = times.started[index].take() {
+            times.spent[index] += started.elapsed().as_secs_f64();
+        }
+        if index == SIMULATION {
+            times.steps += 1;
+        }
+    });
+    // The simulation's steps run inside the main schedule, as many as the
+    // frame needs
+    let world = app.world_mut();
+    if index == SIMULATION {
+        surround(
+            &mut world.resource_mut::<FixedMainScheduleOrder>().labels,
+            index,
+            stage,
+        );
+    } else {
+        surround(
+            &mut world.resource_mut::<MainScheduleOrder>().labels,
+            index,
+            stage,
+        );
+    }
+}
+
+/// Puts [`Started`] and [`Finished`] either side of `stage` in `labels`.
+fn surround(labels: &mut Vec<InternedScheduleLabel>, index: usize, stage: impl ScheduleLabel) {
+    let stage = stage.intern();
+    let at = labels
+        .iter()
+        .position(|label| *label == stage)
+        .expect("a timed schedule isn't run");
+    labels.insert(at + 1, Finished(index).intern());
+    labels.insert(at, Started(index).intern());
+}
+
+/// Hands the frame's times over to the [`DiagnosticsStore`], once everything
+/// else has run.
+fn record_frame(
+    mut times: ResMut<FrameTimes>,
+    time: Res<Time<Fixed>>,
+    mut diagnostics: Diagnostics,
+) {
+    let times = std::mem::take(&mut *times);
+    for ((_, path), spent) in STAGES.iter().zip(times.spent) {
+        diagnostics.add_measurement(path, || spent * 1000.0);
+    }
+    diagnostics.add_measurement(&STEPS, || times.steps as f64);
+    diagnostics.add_measurement(&OVERSTEP, || time.overstep_fraction_f64());
+}
+
+fn toggle_profiler(keyboard_input: Res<ButtonInput<KeyCode>>, mut shown: ResMut<ProfilerShown>) {
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        shown.0 = !shown.0;
+    }
+}
+
+fn draw_profiler(
+    shown: Res<ProfilerShown>,
+    mut egui_context: EguiContexts,
+    diagnostics: Res<DiagnosticsStore>,
+) {
+    if !shown.0 {
+        return;
+    }
+    // The average over the history, and the worst of it
+    let readout = |path: &DiagnosticPath| {
+        diagnostics.get(path).map_or((0.0, 0.0), |diagnostic| {
+            (
+                diagnostic.average().unwrap_or_default(),
+                diagnostic.values().copied().fold(0.0, f64::max),
+            )
+        })
+    };
+
+    egui::Window::new("Profiler")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::Grid::new("profiler_stages").show(ui, |ui| {
+                ui.label("");
+                ui.label("average");
+                ui.label("worst");
+                ui.end_row();
+                for (name, path) in &STAGES {
+                    let (average, worst) = readout(path);
+                    ui.monospace(*name);
+                    ui.monospace(format!("{:.2} ms", average));
+                    ui.monospace(format!("{:.2} ms", worst));
+                    ui.end_row();
+                }
+                let (average, worst) = readout(&STEPS);
+                ui.monospace("Steps a frame");
+                ui.monospace(format!("{:.2}", average));
+                ui.monospace(format!("{:.0}", worst));
+                ui.end_row();
+            });
+            let (overstep, _) = readout(&OVERSTEP);
+            ui.monospace(format!(
+                "{:.0}% into the next step on average",
+                overstep * 100.0
+            ));
+            let (entities, _) = readout(&EntityCountDiagnosticsPlugin::ENTITY_COUNT);
+            ui.monospace(format!("{:.0} entities", entities));
+        });
+}
@@ -0,0 +1,335 @@
+//! Who's playing: names players pick for themselves, kept in a RON file next
+//! to the stats so their matches add up under the same name every time, with
+//! the ratings their ranked matches earn them.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use bevy::{
+    input::{keyboard::KeyboardInput, InputSystem},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::PressActions,
+    display::toggle_fullscreen,
+    locale::Strings,
+    player_name,
+    rating::{with_rating, STARTING_RATING},
+    stats::data_dir,
+    ui::{
+        screen_root, spawn_button, text, typed, ButtonActivated, MenuButton, MenuFocus, UiFont,
+        ACCENT_COLOR, DIM_TEXT_COLOR, TEXT_COLOR,
+    },
+    AppState,
+};
+
+const PROFILES_FILE: &str = "profiles.ron";
+const MAX_NAME_LENGTH: usize = 16;
+
+pub struct ProfilesPlugin;
+
+impl Plugin for ProfilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Profiles::load())
+            .init_resource::<NameDraft>()
+            // Before the menu sees the keys, so typing a name doesn't move the focus
+            .add_systems(
+                PreUpdate,
+                type_name
+                    .run_if(typing_name)
+                    .after(InputSystem)
+                    .after(toggle_fullscreen)
+                    .before(PressActions),
+            )
+            .add_systems(OnEnter(AppState::Profiles), spawn_profiles)
+            .add_systems(
+                Update,
+                (
+                    change_profile,
+                    update_seat_names.after(change_profile),
+                    profiles_action,
+                )
+                    .run_if(in_state(AppState::Profiles)),
+            )
+            .add_systems(OnExit(AppState::Profiles), save_profiles);
+    }
+}
+
+// Missing fields fall back to their defaults, so older files keep loading
+#[derive(Resource, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Profiles {
+    /// Every name that's been entered, in the order they were
+    pub names: Vec<String>,
+    /// The profiles players 1 and 2 are playing as, if any
+    pub seats: [Option<String>; 2],
+    /// Every profile's Elo rating, once they've played a ranked match
+    pub ratings: BTreeMap<String, i32>,
+}
+
+impl Profiles {
+    fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(PROFILES_FILE))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| match ron::de::from_bytes(&bytes) {
+                Ok(profiles) => Some(profiles),
+                Err(error) => {
+                    warn!("Ignoring unreadable profiles file: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    /// What player 1 or 2 is called, which is their number without a profile.
+    pub fn name(&self, player_number: i32, strings: &Strings) -> String {
+        self.seats[(player_number - 1) as usize]
+            .clone()
+            .unwrap_or_else(|| player_name([], player_number, strings))
+    }
+
+    /// How `name` is rated, which is where everyone starts until they've
+    /// played a ranked match.
+    pub fn rating(&self, name: &str) -> i32 {
+        self.ratings.get(name).copied().unwrap_or(STARTING_RATING)
+    }
+
+    /// Puts `name` in `seat`, adding it to the profiles if it's new. The
+    /// players can't both be the same person.
+    fn sit(&mut self, seat: usize, name: String) -> bool {
+        if self.seats[1 - seat].as_ref() == Some(&name) {
+            return false;
+        }
+        if !self.names.contains(&name) {
+            self.names.push(name.clone());
+        }
+        self.seats[seat] = Some(name);
+        true
+    }
+
+    /// Moves `seat` `step` places through its choices: no profile, then every
+    /// profile the other player isn't using.
+    fn cycle(&mut self, seat: usize, step: isize) {
+        let other = self.seats[1 - seat].clone();
+        let choices: Vec<Option<String>> = std::iter::once(None)
+            .chain(
+                self.names
+                    .iter()
+                    .filter(|name| Some(*name) != other.as_ref())
+                    .cloned()
+                    .map(Some),
+            )
+            .collect();
+        let index = choices
+            .iter()
+            .position(|choice| *choice == self.seats[seat])
+            .unwrap_or(0) as isize;
+        let next = (index + step).rem_euclid(choices.len() as isize) as usize;
+        self.seats[seat] = choices[next].clone();
+    }
+}
+
+/// The name being typed, and for which player.
+#[derive(Resource, Default)]
+struct NameDraft(Option<(usize, String)>);
+
+/// The button for player 1 or 2's profile, by index.
+#[derive(Component, Clone, Copy)]
+struct Seat(usize);
+
+/// Who's in a seat, under its button.
+#[derive(Component, Clone, Copy)]
+struct SeatName(usize);
+
+#[derive(Component)]
+struct BackButton;
+
+fn seat_name(
+    seat: usize,
+    profiles: &Profiles,
+    draft: &NameDraft,
+    strings: &Strings,
+) -> (String, Color) {
+    match &draft.0 {
+        Some((typing, name)) if *typing == seat => (format!("{}_", name), ACCENT_COLOR),
+        _ => match &profiles.seats[seat] {
+            Some(name) => (with_rating(name, profiles), TEXT_COLOR),
+            None => (strings.get("profiles.none").to_string(), DIM_TEXT_COLOR),
+        },
+    }
+}
+
+fn spawn_profiles(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    profiles: Res<Profiles>,
+    strings: Res<Strings>,
+    mut draft: ResMut<NameDraft>,
+) {
+    draft.0 = None;
+
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Profiles)))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("profiles.title"), 60.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+
+            for seat in 0..2 {
+                let label = strings.fill("player", &[("number", &(seat + 1))]);
+                spawn_button(parent, &font, seat, &label).insert(Seat(seat));
+                let (name, color) = seat_name(seat, &profiles, &draft, &strings);
+                parent.spawn((
+                    text(&font, name, 24.0, color).with_style(Style {
+                        margin: UiRect {
+                            bottom: Val::Px(12.0),
+                            ..default()
+                        },
+                        ..default()
+                    }),
+                    SeatName(seat),
+                ));
+            }
+            spawn_button(parent, &font, 2, strings.get("back")).insert(BackButton);
+
+            for line in [
+                strings.get("profiles.controls"),
+                strings.get("profiles.saved_under"),
+            ] {
+                parent.spawn(text(&font, line, 18.0, DIM_TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                }));
+            }
+        });
+}
+
+fn typing_name(state: Res<State<AppState>>, draft: Res<NameDraft>) -> bool {
+    *state.get() == AppState::Profiles && draft.0.is_some()
+}
+
+fn type_name(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut keys: EventReader<KeyboardInput>,
+    mut draft: ResMut<NameDraft>,
+    mut profiles: ResMut<Profiles>,
+) {
+    let (seat, name) = match draft.0.as_mut() {
+        Some(draft) => draft,
+        None => return,
+    };
+
+    for character in typed(keys.read()) {
+        if !character.is_control() && name.chars().count() < MAX_NAME_LENGTH {
+            name.push(character);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        name.pop();
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        let name = name.trim().to_string();
+        // Anything that won't do keeps the draft open to fix
+        if !name.is_empty() && profiles.sit(*seat, name) {
+            draft.0 = None;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        draft.0 = None;
+    }
+
+    // Nothing else gets to see the keys while typing
+    keyboard_input.reset_all();
+}
+
+fn change_profile(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    focus: Res<MenuFocus>,
+    draft: Res<NameDraft>,
+    seats: Query<(&MenuButton, &Seat)>,
+    mut profiles: ResMut<Profiles>,
+) {
+    if draft.0.is_some() {
+        return;
+    }
+    let step = if keyboard_input.just_pressed(KeyCode::ArrowLeft)
+        || keyboard_input.just_pressed(KeyCode::KeyA)
+    {
+        -1
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight)
+        || keyboard_input.just_pressed(KeyCode::KeyD)
+    {
+        1
+    } else {
+        return;
+    };
+    if let Some((_, seat)) = seats.iter().find(|(button, _)| button.0 == focus.0) {
+        profiles.cycle(seat.0, step);
+    }
+}
+
+fn update_seat_names(
+    profiles: Res<Profiles>,
+    draft: Res<NameDraft>,
+    strings: Res<Strings>,
+    mut names: Query<(&SeatName, &mut Text)>,
+) {
+    if !profiles.is_changed() && !draft.is_changed() {
+        return;
+    }
+    for (seat, mut text) in names.iter_mut() {
+        let (name, color) = seat_name(seat.0, &profiles, &draft, &strings);
+        text.sections[0].value = name;
+        text.sections[0].style.color = color;
+    }
+}
+
+fn profiles_action(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    seats: Query<&Seat>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut draft: ResMut<NameDraft>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    for event in activated.read() {
+        if let Ok(seat) = seats.get(event.0) {
+            draft.0 = Some((seat.0, String::new()));
+        } else if back_buttons.get(event.0).is_ok() {
+            state.set(AppState::Menu);
+        }
+    }
+    if draft.0.is_none() && keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Menu);
+    }
+}
+
+fn save_profiles(profiles: Res<Profiles>) {
+    if let Err(error) = profiles.save() {
+        warn!("Couldn't save profiles: {}", error);
+    }
+}
@@ -0,0 +1,150 @@
+//! What the two ends of an online match check with each other before
+//! playing: that they speak the same version of the protocol, are running
+//! the same build, and would play by the same rules from the same seed.
+//! Rollback only works if both run the exact same simulation, and a match
+//! between two that don't wouldn't fail, it'd just quietly drift apart.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{locale::Strings, sim::MatchRules};
+
+/// Goes up with every change to what's sent between players, or to how the
+/// server and lobby are talked to.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The commit the game was built from, or its version if it wasn't built
+/// from a git checkout. See `build.rs`.
+pub const BUILD: &str = match option_env!("PINGIS_BUILD") {
+    Some(build) => build,
+    None => env!("CARGO_PKG_VERSION"),
+};
+
+/// First thing either end sends, and sent again until the other's heard.
+/// The version comes first, so any version can read that much of any other's.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub protocol: u32,
+    pub build: String,
+    pub rules: MatchRules,
+    pub seed: u64,
+}
+
+impl Hello {
+    pub fn new(rules: MatchRules, seed: u64) -> Self {
+        Hello {
+            protocol: PROTOCOL_VERSION,
+            build: BUILD.to_string(),
+            rules,
+            seed,
+        }
+    }
+
+    /// Whether a match between `self` and `theirs` would play the same on
+    /// both ends, and if not, the first reason why not.
+    pub fn check(&self, theirs: &Hello) -> Result<(), Mismatch> {
+        if theirs.protocol != self.protocol {
+            Err(Mismatch::Protocol {
+                ours: self.protocol,
+                theirs: theirs.protocol,
+            })
+        } else if theirs.build != self.build {
+            Err(Mismatch::Build {
+                ours: self.build.clone(),
+                theirs: theirs.build.clone(),
+            })
+        } else if theirs.rules != self.rules {
+            Err(Mismatch::Rules)
+        } else if theirs.seed != self.seed {
+            Err(Mismatch::Seed)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Why two ends can't play each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    Protocol { ours: u32, theirs: u32 },
+    Build { ours: String, theirs: String },
+    Rules,
+    Seed,
+}
+
+impl Mismatch {
+    /// What to tell the player, in their language.
+    pub fn describe(&self, strings: &Strings) -> String {
+        match self {
+            Mismatch::Protocol { ours, theirs } => strings.fill(
+                "online.protocol_mismatch",
+                &[("ours", ours), ("theirs", theirs)],
+            ),
+            Mismatch::Build { ours, theirs } => strings.fill(
+                "online.build_mismatch",
+                &[("ours", ours), ("theirs", theirs)],
+            ),
+            Mismatch::Rules => strings.get("online.rules_mismatch").to_string(),
+            Mismatch::Seed => strings.get("online.seed_mismatch").to_string(),
+        }
+    }
+}
+
+/// For the logs, always in English.
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::Protocol { ours, theirs } => {
+                write!(f, "protocol version {} against our {}", theirs, ours)
+            }
+            Mismatch::Build { ours, theirs } => write!(f, "build {} against our {}", theirs, ours),
+            Mismatch::Rules => write!(f, "different match rules"),
+            Mismatch::Seed => write!(f, "a different seed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::default;
+
+    use super::*;
+
+    #[test]
+    fn hellos_have_to_agree_on_everything() {
+        let ours = Hello::new(MatchRules::default(), 7);
+        assert_eq!(ours.check(&ours.clone()), Ok(()));
+
+        let theirs = Hello {
+            seed: 8,
+            ..ours.clone()
+        };
+        assert_eq!(ours.check(&theirs), Err(Mismatch::Seed));
+        let theirs = Hello {
+            rules: MatchRules {
+                points_to_win: 21,
+                ..default()
+            },
+            ..theirs
+        };
+        assert_eq!(ours.check(&theirs), Err(Mismatch::Rules));
+        let theirs = Hello {
+            build: "elsewhere".to_string(),
+            ..theirs
+        };
+        assert!(matches!(ours.check(&theirs), Err(Mismatch::Build { .. })));
+        // The version's what to go by first, as everything else follows from it
+        let theirs = Hello {
+            protocol: PROTOCOL_VERSION + 1,
+            ..theirs
+        };
+        assert_eq!(
+            ours.check(&theirs),
+            Err(Mismatch::Protocol {
+                ours: PROTOCOL_VERSION,
+                theirs: PROTOCOL_VERSION + 1
+            })
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Elo ratings for the players' profiles, kept with them. Every ranked match
+//! two profiles finish against each other moves their ratings, by more the
+//! less expected the result was, and a ranked online match moves the local
+//! player's against an opponent taken to be new. Ratings are shown next to
+//! the names they belong to, and seed tournament brackets.
+//!
+//! Matches against a CPU or a bot, or with anyone playing without a profile,
+//! don't count.
+
+use bevy::prelude::*;
+
+#[cfg(feature = "networking")]
+use crate::network::NetworkSettings;
+use crate::{
+    ai::Cpu, bot::Bot, profiles::Profiles, recording::Playback, sim::MatchOver, AppState, GameMode,
+    Player,
+};
+
+/// What a profile's rated before its first ranked match.
+pub const STARTING_RATING: i32 = 1200;
+/// Most a rating can move by in a single match.
+const K_FACTOR: f32 = 32.0;
+
+pub struct RatingPlugin;
+
+impl Plugin for RatingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(AppState::Playing), rate_match);
+    }
+}
+
+/// Whether matches of this kind change ratings. Only ever the ones people
+/// play each other one on one in.
+fn ranked(mode: GameMode) -> bool {
+    matches!(
+        mode,
+        GameMode::Versus
+            | GameMode::Blitz
+            | GameMode::Arcade
+            | GameMode::Tournament
+            | GameMode::Online
+    )
+}
+
+/// The chances of a player rated `rating` beating one rated `opponent`.
+pub fn expected(rating: i32, opponent: i32) -> f32 {
+    1.0 / (1.0 + 10f32.powf((opponent - rating) as f32 / 400.0))
+}
+
+/// What `rating` becomes after a match against `opponent`, won or lost.
+pub fn rating_after(rating: i32, opponent: i32, won: bool) -> i32 {
+    let result = if won { 1.0 } else { 0.0 };
+    rating + (K_FACTOR * (result - expected(rating, opponent))).round() as i32
+}
+
+/// `name`, and its rating if it's a profile.
+pub fn with_rating(name: &str, profiles: &Profiles) -> String {
+    if profiles.names.iter().any(|profile| profile == name) {
+        format!("{} ({})", name, profiles.rating(name))
+    } else {
+        name.to_string()
+    }
+}
+
+/// Rates a ranked match that was played to the end.
+fn rate_match(
+    mode: Res<GameMode>,
+    match_over: Res<MatchOver>,
+    players: Query<(&Player, Option<&Cpu>, Option<&Bot>)>,
+    mut profiles: ResMut<Profiles>,
+    playback: Option<Res<Playback>>,
+    #[cfg(feature = "networking")] network: Option<Res<NetworkSettings>>,
+) {
+    let winner = match match_over.0 {
+        Some(winner) => winner,
+        None => return,
+    };
+    // A replay was rated when it was played
+    if !ranked(*mode) || playback.is_some() {
+        return;
+    }
+
+    // Only the local player's profile is rated online, as the other player's
+    // is on their own machine
+    #[cfg(feature = "networking")]
+    let online = network.filter(|network| network.ranked).map(|network| {
+        let mut rated = [None, None];
+        rated[network.local_player] = profiles.seats[network.local_player].clone();
+        rated
+    });
+    #[cfg(not(feature = "networking"))]
+    let online = None;
+
+    // The profile rated for each player, by player number
+    let rated: [Option<String>; 2] = if *mode == GameMode::Online {
+        match online {
+            Some(rated) => rated,
+            None => return,
+        }
+    } else {
+        let rated = [1, 2].map(|player_number| {
+            players
+                .iter()
+                .find(|(player, ..)| player.player_number == player_number && player.seat < 2)
+                .filter(|(_, cpu, bot)| cpu.is_none() && bot.is_none())
+                .map(|(player, ..)| player.name.clone())
+                .filter(|name| profiles.names.contains(name))
+        });
+        if rated.iter().any(Option::is_none) {
+            return;
+        }
+        rated
+    };
+
+    let ratings = rated
+        .clone()
+        .map(|name| name.map_or(STARTING_RATING, |name| profiles.rating(&name)));
+    for (index, name) in rated.into_iter().enumerate() {
+        if let Some(name) = name {
+            let rating = rating_after(
+                ratings[index],
+                ratings[1 - index],
+                winner == index as i32 + 1,
+            );
+            info!("{} is now rated {}, from {}", name, rating, ratings[index]);
+            profiles.ratings.insert(name, rating);
+        }
+    }
+    if let Err(error) = profiles.save() {
+        warn!("Couldn't save ratings: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evenly_rated_players_trade_half_the_most() {
+        assert_eq!(expected(STARTING_RATING, STARTING_RATING), 0.5);
+        assert_eq!(rating_after(1500, 1500, true), 1516);
+        assert_eq!(rating_after(1500, 1500, false), 1484);
+    }
+
+    #[test]
+    fn upsets_move_ratings_the_most() {
+        let upset = rating_after(1200, 1600, true) - 1200;
+        let expected_win = rating_after(1600, 1200, true) - 1600;
+        assert!(upset > expected_win);
+        assert!(upset <= K_FACTOR as i32);
+        // What one player gains, the other loses
+        assert_eq!(rating_after(1600, 1200, false) - 1600, -upset);
+    }
+}
@@ -0,0 +1,600 @@
+//! Whole matches kept to watch again, or to pass on to somebody else. The
+//! simulation only goes by its seed, the rules and what was pressed at each
+//! seat every step (see [`sim`](crate::sim)), so that's all a replay is,
+//! along with who played, where, and how it ended. Every local match is
+//! recorded as it's played, and the game-over screen saves it to the
+//! `replays` folder as a `.pingis-replay` file. The menu's "Open replay"
+//! lists what's in there, and `--replay <file>` watches one straight away.
+//!
+//! A replay is played back by the same steps in whatever build opens it, so
+//! one from another version of the game, or of a match against a CPU that
+//! caught up, which changes its racket and not what it presses, can go its
+//! own way after a while.
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::{self, ArenaSize},
+    cli_arg,
+    dashboard::date_label,
+    locale::Strings,
+    mods::Mods,
+    player_name,
+    saved_match::SavedMatch,
+    seams::InputSource,
+    sim::{MatchOver, MatchRules, MatchStats, RacketInput, SimInput, SimSystems, SEATS},
+    start_match,
+    stats::{data_dir, now},
+    ui::{screen_root, spawn_button, text, ButtonActivated, UiFont, DIM_TEXT_COLOR, TEXT_COLOR},
+    AppState, GameMode, MatchNames, MatchSeed, NextRules, Player, StartingSeed, SteppedLocally,
+};
+
+/// Bumped whenever replays change in a way older builds can't read.
+pub const FORMAT_VERSION: u32 = 1;
+pub const EXTENSION: &str = "pingis-replay";
+const REPLAY_DIR: &str = "replays";
+// How many replays fit on the screen, the newest
+const LISTED_REPLAYS: usize = 10;
+const ERROR_COLOR: Color = Color::srgb(0.9, 0.3, 0.3);
+
+pub struct RecordingPlugin;
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recorder>()
+            .add_systems(
+                OnEnter(AppState::Playing),
+                (start_recording.after(start_match), spawn_playback_banner),
+            )
+            .add_systems(OnExit(AppState::Playing), (finish_recording, stop_playback))
+            .add_systems(
+                FixedUpdate,
+                (play_back, record.after(play_back))
+                    .before(SimSystems)
+                    .in_set(SteppedLocally),
+            )
+            .add_systems(OnEnter(AppState::Menu), watch_from_cli)
+            .add_systems(OnEnter(AppState::Replays), spawn_replays)
+            .add_systems(Update, replays_action.run_if(in_state(AppState::Replays)));
+    }
+}
+
+/// A match as it was played, as it's saved in a `.pingis-replay` file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recording {
+    /// See [`FORMAT_VERSION`]
+    version: u32,
+    /// The version of the game it was played in
+    game_version: String,
+    /// Seconds since the Unix epoch when it was played
+    recorded_at: u64,
+    mode: GameMode,
+    names: [String; 2],
+    rules: MatchRules,
+    arena: String,
+    arena_size: ArenaSize,
+    seed: u64,
+    /// Points each player won, and who won the match, if anybody did
+    points: [i32; 2],
+    winner: Option<i32>,
+    /// What every seat held, a run of steps at a time: how many steps, then
+    /// each seat's keys as the bits of a byte, see [`pack`]
+    inputs: Vec<(u32, [u8; SEATS])>,
+}
+
+/// Only the version, to tell a replay from a newer build apart from a broken
+/// one.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+    #[serde(default)]
+    game_version: String,
+}
+
+/// `input` as the bits of a byte, up first.
+fn pack(input: RacketInput) -> u8 {
+    [
+        input.up,
+        input.down,
+        input.smash,
+        input.tilt_up,
+        input.tilt_down,
+        input.dash,
+    ]
+    .into_iter()
+    .enumerate()
+    .fold(0, |bits, (bit, held)| bits | (held as u8) << bit)
+}
+
+fn unpack(bits: u8) -> RacketInput {
+    let held = |bit: u8| bits & (1 << bit) != 0;
+    RacketInput {
+        up: held(0),
+        down: held(1),
+        smash: held(2),
+        tilt_up: held(3),
+        tilt_down: held(4),
+        dash: held(5),
+    }
+}
+
+fn replay_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(REPLAY_DIR))
+}
+
+impl Recording {
+    /// Adds a step's input, to the run before it if nothing's changed.
+    fn push(&mut self, input: &SimInput) {
+        let seats = input.0.map(pack);
+        match self.inputs.last_mut() {
+            Some((steps, last)) if *last == seats => *steps += 1,
+            _ => self.inputs.push((1, seats)),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+        let header: Header = serde_json::from_slice(&bytes)?;
+        if header.version > FORMAT_VERSION {
+            return Err(format!(
+                "saved by a newer version of the game ({})",
+                header.game_version
+            )
+            .into());
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Saves it to the replays folder, named for when it was played.
+    pub fn save(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let dir = replay_dir().ok_or("no data directory")?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("match-{}.{}", self.recorded_at, EXTENSION));
+        fs::write(&path, serde_json::to_vec(self)?)?;
+        Ok(path)
+    }
+
+    /// One line saying what the match was, for the list of replays.
+    fn line(&self, strings: &Strings) -> String {
+        strings.fill(
+            "replays.match",
+            &[
+                ("date", &date_label(self.recorded_at / 86400)),
+                ("mode", &strings.get(self.mode.name_key())),
+                ("first", &self.names[0]),
+                ("first_points", &self.points[0]),
+                ("second_points", &self.points[1]),
+                ("second", &self.names[1]),
+            ],
+        )
+    }
+}
+
+/// The match being played, as it's recorded, and once it's over, the last
+/// one played, to save from the game-over screen. Nothing's recorded of
+/// online matches, the demo or replays being watched, or of saved matches
+/// carried on with, which don't start from their seed.
+#[derive(Resource, Default)]
+pub struct Recorder(pub Option<Recording>);
+
+/// A replay being watched, in place of anybody's input. Only there while
+/// it's on.
+#[derive(Resource)]
+pub struct Playback {
+    recording: Recording,
+    /// The run of inputs up next, and how many of its steps have been played
+    run: usize,
+    step: u32,
+}
+
+impl Playback {
+    pub fn arena_size(&self) -> ArenaSize {
+        self.recording.arena_size
+    }
+}
+
+impl InputSource for Playback {
+    fn next_input(&mut self) -> Option<[RacketInput; SEATS]> {
+        let (steps, seats) = *self.recording.inputs.get(self.run)?;
+        self.step += 1;
+        if self.step >= steps {
+            self.run += 1;
+            self.step = 0;
+        }
+        Some(seats.map(unpack))
+    }
+}
+
+/// What a match recorded as `mode` is watched as: a tournament's or the
+/// campaign's like any other, so nothing comes of how it ends.
+fn watched_as(mode: GameMode) -> GameMode {
+    match mode {
+        GameMode::Tournament | GameMode::Campaign => GameMode::Versus,
+        mode => mode,
+    }
+}
+
+/// Sets the next match up to play `recording` back. It starts once the state
+/// is set to [`AppState::Playing`].
+fn watch(commands: &mut Commands, asset_server: &AssetServer, mods: &Mods, recording: Recording) {
+    if recording.game_version != env!("CARGO_PKG_VERSION") {
+        warn!(
+            "Watching a replay from version {} of the game, which may play out differently",
+            recording.game_version
+        );
+    }
+    arena::use_arena(commands, asset_server, mods, &recording.arena);
+    commands.insert_resource(watched_as(recording.mode));
+    commands.insert_resource(MatchNames(Some(recording.names.clone())));
+    commands.insert_resource(NextRules(Some(recording.rules)));
+    commands.insert_resource(MatchSeed(Some(recording.seed)));
+    commands.insert_resource(Playback {
+        recording,
+        run: 0,
+        step: 0,
+    });
+}
+
+fn start_recording(
+    mode: Res<GameMode>,
+    rules: Res<MatchRules>,
+    seed: Res<StartingSeed>,
+    playback: Option<Res<Playback>>,
+    saved: Option<Res<SavedMatch>>,
+    mut recorder: ResMut<Recorder>,
+) {
+    let recorded = !matches!(*mode, GameMode::Online | GameMode::Demo)
+        && playback.is_none()
+        && saved.is_none();
+    recorder.0 = recorded.then(|| Recording {
+        version: FORMAT_VERSION,
+        game_version: env!("CARGO_PKG_VERSION").to_string(),
+        recorded_at: now(),
+        mode: *mode,
+        names: default(),
+        rules: *rules,
+        arena: arena::arena_name(),
+        arena_size: default(),
+        seed: seed.0,
+        points: [0; 2],
+        winner: None,
+        inputs: Vec::new(),
+    });
+}
+
+fn record(input: Res<SimInput>, mut recorder: ResMut<Recorder>) {
+    if let Some(recording) = &mut recorder.0 {
+        recording.push(&input);
+    }
+}
+
+/// Fills in how the match went as it's left, while its players are still
+/// about. One that was left before the end isn't kept.
+fn finish_recording(
+    match_over: Res<MatchOver>,
+    stats: Res<MatchStats>,
+    arena_size: Res<ArenaSize>,
+    players: Query<&Player>,
+    strings: Res<Strings>,
+    mut recorder: ResMut<Recorder>,
+) {
+    if match_over.0.is_none() {
+        recorder.0 = None;
+    }
+    let recording = match &mut recorder.0 {
+        Some(recording) => recording,
+        None => return,
+    };
+    let name = |player_number| player_name(players.iter(), player_number, &strings);
+    recording.names = [name(1), name(2)];
+    recording.points = [stats.player(1).points, stats.player(2).points];
+    recording.winner = match_over.0;
+    recording.arena_size = *arena_size;
+}
+
+/// Feeds the replay being watched to the simulation, over whatever anybody
+/// pressed. A replay that runs out before the match is over has gone its own
+/// way, and is stopped there.
+fn play_back(
+    playback: Option<ResMut<Playback>>,
+    mut input: ResMut<SimInput>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let mut playback = match playback {
+        Some(playback) => playback,
+        None => return,
+    };
+    match playback.next_input() {
+        Some(next) => input.0 = next,
+        None => {
+            warn!("The replay ran out before the match was over");
+            state.set(AppState::Replays);
+        }
+    }
+}
+
+/// Puts things back the way they were for the matches after a replay.
+fn stop_playback(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mods: Res<Mods>,
+    playback: Option<Res<Playback>>,
+) {
+    if playback.is_some() {
+        commands.remove_resource::<Playback>();
+        arena::use_arena(&mut commands, &asset_server, &mods, &arena::arena_name());
+    }
+}
+
+fn spawn_playback_banner(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    playback: Option<Res<Playback>>,
+) {
+    if playback.is_none() {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(16.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn(text(
+                &font,
+                strings.get("replays.watching"),
+                24.0,
+                DIM_TEXT_COLOR,
+            ));
+        });
+}
+
+/// Watches the replay given with `--replay <file>`, the first time the menu
+/// comes up.
+fn watch_from_cli(
+    mut commands: Commands,
+    mut checked: Local<bool>,
+    asset_server: Res<AssetServer>,
+    mods: Res<Mods>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if std::mem::replace(&mut *checked, true) {
+        return;
+    }
+    let path = match cli_arg("replay") {
+        Some(path) => PathBuf::from(path),
+        None => return,
+    };
+    match Recording::load(&path) {
+        Ok(recording) => {
+            info!("Watching {}", path.display());
+            watch(&mut commands, &asset_server, &mods, recording);
+            state.set(AppState::Playing);
+        }
+        Err(error) => warn!("Couldn't open the replay {}: {}", path.display(), error),
+    }
+}
+
+/// The replays in the replays folder, newest first, or why they can't be
+/// watched.
+fn saved_replays() -> Vec<(PathBuf, Result<Recording, String>)> {
+    let entries = match replay_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        Some(entries) => entries,
+        None => return Vec::new(),
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == EXTENSION)
+        })
+        .collect();
+    paths.sort_by_key(|path| {
+        std::cmp::Reverse(
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+        )
+    });
+    paths
+        .into_iter()
+        .take(LISTED_REPLAYS)
+        .map(|path| {
+            let recording = Recording::load(&path).map_err(|error| error.to_string());
+            (path, recording)
+        })
+        .collect()
+}
+
+#[derive(Component)]
+struct WatchButton(PathBuf);
+
+#[derive(Component)]
+struct BackButton;
+
+fn spawn_replays(mut commands: Commands, font: Res<UiFont>, strings: Res<Strings>) {
+    let replays = saved_replays();
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Replays)))
+        .with_children(|parent| {
+            parent.spawn(text(&font, strings.get("replays.title"), 48.0, TEXT_COLOR));
+            if replays.is_empty() {
+                parent.spawn(text(
+                    &font,
+                    strings.get("replays.none"),
+                    24.0,
+                    DIM_TEXT_COLOR,
+                ));
+            }
+            let mut index = 0;
+            for (path, recording) in replays {
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                match recording {
+                    Ok(recording) => {
+                        spawn_button(parent, &font, index, &recording.line(&strings))
+                            .insert(WatchButton(path));
+                        index += 1;
+                    }
+                    Err(error) => {
+                        let line = strings
+                            .fill("replays.unreadable", &[("name", &name), ("error", &error)]);
+                        parent.spawn(text(&font, line, 16.0, ERROR_COLOR).with_style(Style {
+                            margin: UiRect::all(Val::Px(2.0)),
+                            ..default()
+                        }));
+                    }
+                }
+            }
+            if let Some(dir) = replay_dir() {
+                parent.spawn(
+                    text(
+                        &font,
+                        strings.fill("replays.folder", &[("path", &dir.display())]),
+                        16.0,
+                        DIM_TEXT_COLOR,
+                    )
+                    .with_style(Style {
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    }),
+                );
+            }
+            spawn_button(parent, &font, index, strings.get("back")).insert(BackButton);
+        });
+}
+
+fn replays_action(
+    mut commands: Commands,
+    mut activated: EventReader<ButtonActivated>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    watch_buttons: Query<&WatchButton>,
+    back_buttons: Query<(), With<BackButton>>,
+    asset_server: Res<AssetServer>,
+    mods: Res<Mods>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        state.set(AppState::Menu);
+        return;
+    }
+
+    for event in activated.read() {
+        if back_buttons.get(event.0).is_ok() {
+            state.set(AppState::Menu);
+            return;
+        }
+        let path = match watch_buttons.get(event.0) {
+            Ok(button) => &button.0,
+            Err(_) => continue,
+        };
+        // It could have gone since the screen came up
+        match Recording::load(path) {
+            Ok(recording) => {
+                watch(&mut commands, &asset_server, &mods, recording);
+                state.set(AppState::Playing);
+            }
+            Err(error) => warn!("Couldn't open the replay {}: {}", path.display(), error),
+        }
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording() -> Recording {
+        Recording {
+            version: FORMAT_VERSION,
+            game_version: "0.0.0".to_string(),
+            recorded_at: 0,
+            mode: GameMode::Versus,
+            names: ["Ana".to_string(), "Bo".to_string()],
+            rules: MatchRules::default(),
+            arena: "classic".to_string(),
+            arena_size: ArenaSize::Classic,
+            seed: 0x5eed,
+            points: [11, 9],
+            winner: Some(1),
+            inputs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn inputs_play_back_as_they_were_recorded() {
+        let up = RacketInput {
+            up: true,
+            smash: true,
+            ..default()
+        };
+        let steps = [
+            SimInput::default(),
+            SimInput::default(),
+            SimInput([
+                up,
+                default(),
+                default(),
+                RacketInput {
+                    dash: true,
+                    ..default()
+                },
+            ]),
+            SimInput::default(),
+        ];
+        let mut recording = recording();
+        for input in &steps {
+            recording.push(input);
+        }
+        // Runs of the same input are kept once
+        assert_eq!(recording.inputs.len(), 3);
+
+        let mut playback = Playback {
+            recording,
+            run: 0,
+            step: 0,
+        };
+        for input in &steps {
+            assert_eq!(playback.next_input(), Some(input.0));
+        }
+        assert!(playback.next_input().is_none());
+    }
+
+    #[test]
+    fn replays_from_newer_versions_are_turned_down() {
+        let dir = std::env::temp_dir().join(format!("pingis-replay-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("match.{}", EXTENSION));
+
+        fs::write(&path, serde_json::to_vec(&recording()).unwrap()).unwrap();
+        assert_eq!(Recording::load(&path).unwrap().names, recording().names);
+
+        let mut newer = recording();
+        newer.version = FORMAT_VERSION + 1;
+        fs::write(&path, serde_json::to_vec(&newer).unwrap()).unwrap();
+        assert!(Recording::load(&path).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,304 @@
+//! Online matches through a dedicated `pingis-server`.
+//!
+//! The server owns the simulation. Each client sends it the local player's
+//! racket input every frame and shows whatever state comes back, so neither
+//! player has to host the match.
+
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::Actions,
+    chat::{clean, ChatLine, OutgoingChat},
+    locale::Strings,
+    network::{Link, NetworkProblem, NetworkSettings},
+    protocol::{Mismatch, BUILD, PROTOCOL_VERSION},
+    seams::read_input,
+    sim::{Ball, MatchOver, MatchStats, Racket, RacketInput, Score, SimInput, Velocity},
+    AppState, GameMode, MatchState,
+};
+
+/// Big enough for any message either side sends, and for GGRS's own.
+pub const MAX_PACKET_SIZE: usize = 4096;
+/// Seconds without hearing from the other side before giving up on it.
+pub const TIMEOUT: f64 = 5.0;
+// Seconds between join attempts while the server hasn't answered
+const JOIN_RETRY: f64 = 1.0;
+
+pub struct RelayPlugin;
+
+impl Plugin for RelayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                join_server,
+                exchange_with_server.after(read_input::<Actions>),
+            )
+                .run_if(in_state(MatchState::Running)),
+        )
+        .add_systems(OnExit(AppState::Playing), leave_server);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    /// Asking for a place, from a game that speaks `protocol` and is `build`
+    Join {
+        protocol: u32,
+        build: String,
+    },
+    Input(RacketInput),
+    Chat(String),
+    Leave,
+}
+
+impl ClientMessage {
+    /// Asking to join as this build of the game.
+    pub fn join() -> Self {
+        ClientMessage::Join {
+            protocol: PROTOCOL_VERSION,
+            build: BUILD.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Welcome {
+        player_number: i32,
+    },
+    /// Both players' places are taken
+    Full,
+    /// The server speaks `protocol` and is `build`, and the client doesn't or
+    /// isn't
+    Incompatible {
+        protocol: u32,
+        build: String,
+    },
+    State(MatchSnapshot),
+    /// Passed on from the other player
+    Chat {
+        player_number: i32,
+        text: String,
+    },
+}
+
+/// Everything a client needs to draw one step of the match.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchSnapshot {
+    pub ball_position: Vec2,
+    pub ball_velocity: Vec2,
+    /// Where each racket is, indexed by player number minus one
+    pub rackets: [Vec2; 2],
+    pub score: Score,
+    pub stats: MatchStats,
+    pub winner: Option<i32>,
+}
+
+pub fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    bincode::serialize(message).expect("messages always serialize")
+}
+
+pub fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Option<T> {
+    bincode::deserialize(bytes).ok()
+}
+
+/// Reads every datagram waiting on `socket`.
+pub fn receive_all(socket: &UdpSocket) -> Vec<(SocketAddr, Vec<u8>)> {
+    let mut packets = Vec::new();
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((length, address)) => packets.push((address, buffer[..length].to_vec())),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) => {
+                // Windows reports a peer that went away as an error on the next read
+                debug!("Ignoring socket error: {}", error);
+                if error.kind() != ErrorKind::ConnectionReset {
+                    break;
+                }
+            }
+        }
+    }
+    packets
+}
+
+/// The connection to the server during a match.
+#[derive(Resource)]
+struct RelayClient {
+    socket: UdpSocket,
+    server: SocketAddr,
+    player_number: Option<i32>,
+    last_join: f64,
+    last_heard: f64,
+}
+
+impl RelayClient {
+    fn send(&self, message: &ClientMessage) {
+        if let Err(error) = self.socket.send_to(&encode(message), self.server) {
+            warn!("Couldn't reach the server: {}", error);
+        }
+    }
+}
+
+fn join_server(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    settings: Res<NetworkSettings>,
+    client: Option<Res<RelayClient>>,
+    time: Res<Time>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if *mode != GameMode::Online || settings.link != Link::Server || client.is_some() {
+        return;
+    }
+    let server = match settings.server {
+        Some(server) => server,
+        None => return,
+    };
+
+    let socket = match UdpSocket::bind(("0.0.0.0", 0)).and_then(|socket| {
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }) {
+        Ok(socket) => socket,
+        Err(error) => {
+            error!("Couldn't open a socket: {}", error);
+            state.set(AppState::Menu);
+            return;
+        }
+    };
+
+    info!("Joining server at {}", server);
+    let now = time.elapsed_seconds_f64();
+    let client = RelayClient {
+        socket,
+        server,
+        player_number: None,
+        last_join: now,
+        last_heard: now,
+    };
+    client.send(&ClientMessage::join());
+    commands.insert_resource(client);
+}
+
+fn exchange_with_server(
+    client: Option<ResMut<RelayClient>>,
+    input: Res<SimInput>,
+    time: Res<Time>,
+    mut balls: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+    mut rackets: Query<(&Racket, &mut Transform), Without<Ball>>,
+    mut score: ResMut<Score>,
+    mut stats: ResMut<MatchStats>,
+    mut match_over: ResMut<MatchOver>,
+    mut outgoing_chat: EventReader<OutgoingChat>,
+    mut chat: EventWriter<ChatLine>,
+    strings: Res<Strings>,
+    mut problem: ResMut<NetworkProblem>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let mut client = match client {
+        Some(client) => client,
+        None => return,
+    };
+    let now = time.elapsed_seconds_f64();
+
+    for (address, packet) in receive_all(&client.socket) {
+        if address != client.server {
+            continue;
+        }
+        client.last_heard = now;
+        match decode(&packet) {
+            Some(ServerMessage::Welcome { player_number }) => {
+                if client.player_number.is_none() {
+                    info!("Joined as player {}", player_number);
+                }
+                client.player_number = Some(player_number);
+            }
+            Some(ServerMessage::Full) => {
+                warn!("The server is full");
+                state.set(AppState::Menu);
+                return;
+            }
+            Some(ServerMessage::Incompatible { protocol, build }) => {
+                let mismatch = if protocol != PROTOCOL_VERSION {
+                    Mismatch::Protocol {
+                        ours: PROTOCOL_VERSION,
+                        theirs: protocol,
+                    }
+                } else {
+                    Mismatch::Build {
+                        ours: BUILD.to_string(),
+                        theirs: build,
+                    }
+                };
+                error!("Can't play on the server, which has {}", mismatch);
+                problem.0 = Some(mismatch.describe(&strings));
+                state.set(AppState::Network);
+                return;
+            }
+            Some(ServerMessage::State(snapshot)) => {
+                for (mut transform, mut velocity) in balls.iter_mut() {
+                    transform.translation.x = snapshot.ball_position.x;
+                    transform.translation.y = snapshot.ball_position.y;
+                    velocity.0 = snapshot.ball_velocity;
+                }
+                for (racket, mut transform) in rackets.iter_mut() {
+                    let position = snapshot.rackets[(racket.player_number - 1) as usize];
+                    transform.translation.x = position.x;
+                    transform.translation.y = position.y;
+                }
+                *score = snapshot.score;
+                *stats = snapshot.stats;
+                match_over.0 = snapshot.winner;
+            }
+            Some(ServerMessage::Chat {
+                player_number,
+                text,
+            }) => {
+                chat.send(ChatLine {
+                    player_number,
+                    text: clean(&text),
+                });
+            }
+            None => debug!("Ignoring a garbled packet"),
+        }
+    }
+
+    if now - client.last_heard > TIMEOUT {
+        warn!("Lost contact with the server");
+        state.set(AppState::Menu);
+        return;
+    }
+
+    match client.player_number {
+        Some(player_number) => {
+            client.send(&ClientMessage::Input(input.0[(player_number - 1) as usize]));
+            for OutgoingChat(text) in outgoing_chat.read() {
+                client.send(&ClientMessage::Chat(text.clone()));
+                chat.send(ChatLine {
+                    player_number,
+                    text: text.clone(),
+                });
+            }
+        }
+        None if now - client.last_join > JOIN_RETRY => {
+            client.last_join = now;
+            client.send(&ClientMessage::join());
+        }
+        None => {}
+    }
+}
+
+fn leave_server(mut commands: Commands, client: Option<Res<RelayClient>>) {
+    if let Some(client) = client {
+        client.send(&ClientMessage::Leave);
+        commands.remove_resource::<RelayClient>();
+    }
+}
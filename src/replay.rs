@@ -0,0 +1,208 @@
+//! A slow-motion replay of the point that won the match, before the game-over
+//! screen. The ball and the rackets are kept track of for the last few seconds
+//! of every match, and once the winning point's in, that's played back at
+//! half speed under a "REPLAY" banner. Enter or Space skips it.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    end_match,
+    locale::Strings,
+    seams::Clock,
+    sim::{Ball, GoalEvent, MatchOver, MatchRules, Racket, Score},
+    start_match,
+    ui::{text, UiFont, ACCENT_COLOR},
+    AppState, GameMode, MatchState,
+};
+
+// Seconds of the match the replay covers, and how much slower it's played back
+const REPLAY_SECONDS: f64 = 2.5;
+const SLOW_MOTION: f64 = 0.5;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Replay>()
+            .add_systems(OnEnter(AppState::Playing), reset_replay.after(start_match))
+            .add_systems(
+                Update,
+                (
+                    record::<Time>,
+                    start_replay.after(record::<Time>).before(end_match),
+                    play_replay::<Time>.after(start_replay).before(end_match),
+                )
+                    .run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Where everything was, a frame at a time.
+struct Frame {
+    at: f64,
+    transforms: Vec<(Entity, Transform)>,
+}
+
+#[derive(Resource, Default)]
+pub struct Replay {
+    /// The last few seconds of the match, oldest first
+    frames: VecDeque<Frame>,
+    /// Set once the point that won the match is in, and there's no more to
+    /// keep, until the replay's over
+    kept: bool,
+    /// Seconds into the frames, while they're played back
+    playing: Option<f64>,
+}
+
+impl Replay {
+    /// Whether the replay is playing, and the match shouldn't end or go on.
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+}
+
+/// The matches whose winning points are played again. Not the demo, the one
+/// nobody's watching, or online ones, where the other end won't wait.
+fn replays(mode: GameMode) -> bool {
+    !matches!(mode, GameMode::Demo | GameMode::Online)
+}
+
+#[derive(Component)]
+struct ReplayBanner;
+
+fn reset_replay(mut replay: ResMut<Replay>) {
+    *replay = Replay::default();
+}
+
+fn record<C: Clock>(
+    time: Res<C>,
+    mode: Res<GameMode>,
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    mut goals: EventReader<GoalEvent>,
+    mut replay: ResMut<Replay>,
+    moving: Query<(Entity, &Transform), Or<(With<Ball>, With<Racket>)>>,
+) {
+    if replay.kept || !replays(*mode) {
+        return;
+    }
+
+    let now = time.elapsed_seconds_f64();
+    replay.frames.push_back(Frame {
+        at: now,
+        transforms: moving
+            .iter()
+            .map(|(entity, transform)| (entity, *transform))
+            .collect(),
+    });
+    while replay
+        .frames
+        .front()
+        .is_some_and(|frame| now - frame.at > REPLAY_SECONDS)
+    {
+        replay.frames.pop_front();
+    }
+
+    if goals.read().count() > 0 && rules.wins_match(&score) {
+        replay.kept = true;
+    }
+}
+
+fn start_replay(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    match_over: Res<MatchOver>,
+    mut replay: ResMut<Replay>,
+) {
+    if match_over.0.is_none() || !replay.kept || replay.is_playing() {
+        return;
+    }
+
+    if replay.frames.len() < 2 {
+        replay.kept = false;
+        return;
+    }
+    replay.playing = Some(0.0);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(120.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            ReplayBanner,
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn(text(
+                &font,
+                strings.get("replay.banner"),
+                48.0,
+                ACCENT_COLOR,
+            ));
+        });
+}
+
+fn play_replay<C: Clock>(
+    mut commands: Commands,
+    time: Res<C>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut replay: ResMut<Replay>,
+    mut transforms: Query<&mut Transform>,
+    banners: Query<Entity, With<ReplayBanner>>,
+) {
+    let played = match replay.playing {
+        Some(played) => played + time.delta_seconds_f64() * SLOW_MOTION,
+        None => return,
+    };
+    let start = replay.frames.front().map_or(0.0, |frame| frame.at);
+    let at = start + played;
+
+    let skipped = keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]);
+    let next = replay.frames.iter().position(|frame| frame.at >= at);
+    let next = match next {
+        Some(next) if !skipped => next,
+        _ => {
+            if skipped {
+                keyboard_input.reset(KeyCode::Enter);
+                keyboard_input.reset(KeyCode::Space);
+            }
+            *replay = Replay::default();
+            for banner in banners.iter() {
+                commands.entity(banner).despawn_recursive();
+            }
+            return;
+        }
+    };
+
+    // Slowed down, it's between frames more often than not
+    let after = &replay.frames[next];
+    let before = &replay.frames[next.saturating_sub(1)];
+    let along = if after.at > before.at {
+        ((at - before.at) / (after.at - before.at)) as f32
+    } else {
+        1.0
+    };
+    for (entity, to) in after.transforms.iter() {
+        let from = before
+            .transforms
+            .iter()
+            .find(|(recorded, _)| recorded == entity)
+            .map_or(to, |(_, from)| from);
+        if let Ok(mut transform) = transforms.get_mut(*entity) {
+            transform.translation = from.translation.lerp(to.translation, along);
+            transform.rotation = from.rotation.slerp(to.rotation, along);
+            transform.scale = from.scale.lerp(to.scale, along);
+        }
+    }
+    replay.playing = Some(played);
+}
@@ -0,0 +1,104 @@
+//! Rumble for the players on gamepads: a knock on every return, harder the
+//! faster the ball leaves the racket, and a long one for conceding a point.
+//! Each player's is as strong as the settings say, and nothing a CPU or a bot
+//! plays for rumbles. Pads or platforms that can't rumble are left alone.
+
+use std::time::Duration;
+
+use bevy::{
+    input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest},
+    prelude::*,
+};
+
+use crate::{
+    actions::GamepadSeats,
+    ai::Cpu,
+    bot::Bot,
+    settings::Settings,
+    sim::{BallHitEvent, GoalEvent, BALL_SPEED, SEATS},
+    AppState, MatchState, Player,
+};
+
+/// How long a return rumbles for, in milliseconds.
+const HIT_LENGTH: u64 = 80;
+/// A return this many times a serve's speed rumbles as hard as the settings
+/// allow, and a slower one less, down to [`SOFTEST_HIT`].
+const HARDEST_HIT_SPEED: f32 = 2.5;
+const SOFTEST_HIT: f32 = 0.2;
+const GOAL_LENGTH: u64 = 400;
+
+pub struct RumblePlugin;
+
+impl Plugin for RumblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, rumble.run_if(in_state(MatchState::Running)))
+            .add_systems(OnExit(AppState::Playing), stop_rumbling);
+    }
+}
+
+/// How hard, from 0 to 1, a return at `speed` rumbles at full strength.
+fn hit_strength(speed: f32) -> f32 {
+    (speed / (BALL_SPEED * HARDEST_HIT_SPEED)).clamp(SOFTEST_HIT, 1.0)
+}
+
+fn rumble(
+    settings: Res<Settings>,
+    seats: Res<GamepadSeats>,
+    players: Query<&Player, (Without<Cpu>, Without<Bot>)>,
+    mut hits: EventReader<BallHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut requests: EventWriter<GamepadRumbleRequest>,
+) {
+    // Strength and milliseconds for each player number
+    let mut asked = Vec::new();
+    for hit in hits.read() {
+        asked.push((
+            hit.player_number,
+            hit_strength(hit.velocity.length()),
+            HIT_LENGTH,
+        ));
+    }
+    for goal in goals.read() {
+        asked.push((3 - goal.scored_by, 1.0, GOAL_LENGTH));
+    }
+
+    for (player_number, strength, length) in asked {
+        let intensity = match settings.rumble.get((player_number - 1) as usize) {
+            Some(intensity) if *intensity > 0.0 => *intensity,
+            _ => continue,
+        };
+        // Every pad playing for them, partners and all
+        let pads = players
+            .iter()
+            .filter(|player| player.player_number == player_number)
+            .filter_map(|player| seats.gamepad(player.seat));
+        for gamepad in pads {
+            requests.send(GamepadRumbleRequest::Add {
+                gamepad,
+                duration: Duration::from_millis(length),
+                intensity: GamepadRumbleIntensity::strong_motor(strength * intensity),
+            });
+        }
+    }
+}
+
+/// Nothing carries on rumbling into the menus.
+fn stop_rumbling(seats: Res<GamepadSeats>, mut requests: EventWriter<GamepadRumbleRequest>) {
+    for seat in 0..SEATS {
+        if let Some(gamepad) = seats.gamepad(seat) {
+            requests.send(GamepadRumbleRequest::Stop { gamepad });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faster_returns_rumble_harder() {
+        assert_eq!(hit_strength(0.0), SOFTEST_HIT);
+        assert!(hit_strength(BALL_SPEED) < hit_strength(BALL_SPEED * 2.0));
+        assert_eq!(hit_strength(BALL_SPEED * 10.0), 1.0);
+    }
+}
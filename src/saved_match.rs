@@ -0,0 +1,303 @@
+//! Saving a match from the pause menu to carry on with another time, from
+//! the main menu's "Continue match". What's saved is everything the
+//! simulation goes by: the rules and the size of the court, the score and whose serve it is, the
+//! modifiers' state, and where every ball and racket is and how it's going.
+//! There's only ever the one saved match, and it's gone once it's carried on
+//! with.
+//!
+//! Tournament and campaign matches aren't saved, as there's more to them than
+//! the match itself.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::ArenaSize,
+    locale::Strings,
+    pause, player_name,
+    sim::{
+        launch_ball, Ball, BallKind, Goal, MatchClock, MatchRules, MatchStats, MovingWall, Netted,
+        Portals, Racket, RacketLength, Rally, Score, Side, SimRng, Spin, Tilt, Velocity, Waiting,
+        Wind,
+    },
+    stats::data_dir,
+    AppState, GameMode, MatchState, Player,
+};
+
+const SAVE_FILE: &str = "saved_match.json";
+
+pub struct SavedMatchPlugin;
+
+impl Plugin for SavedMatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveMatch>()
+            .add_systems(
+                Update,
+                save_match
+                    .after(pause::pause_action)
+                    .run_if(in_state(MatchState::Paused)),
+            )
+            .add_systems(
+                Update,
+                resume_match
+                    .after(crate::handicap_rackets)
+                    .run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+/// Sent to save the paused match, as it's left.
+#[derive(Event)]
+pub struct SaveMatch;
+
+/// A match as it was when it was saved.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+pub struct SavedMatch {
+    pub mode: GameMode,
+    pub names: [String; 2],
+    pub rules: MatchRules,
+    /// Saved before courts came in sizes, those were all classic
+    #[serde(default)]
+    pub arena_size: ArenaSize,
+    score: Score,
+    rally: Rally,
+    /// Who's served what, and who's serving now, with the rest of the numbers
+    stats: MatchStats,
+    clock: MatchClock,
+    rng: SimRng,
+    wind: Wind,
+    portals: Portals,
+    balls: Vec<SavedBall>,
+    rackets: Vec<SavedRacket>,
+    moving_wall: Option<SavedWall>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SavedBall {
+    kind: BallKind,
+    position: Vec2,
+    velocity: Vec2,
+    spin: f32,
+    /// The side of the net it's settling in, and how long until it's served
+    /// again, if it's gone in
+    netted: Option<(Side, f32)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SavedRacket {
+    seat: usize,
+    side: Side,
+    position: Vec3,
+    rotation: Quat,
+    tilt: f32,
+    length: RacketLength,
+    waiting: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SavedWall {
+    wall: MovingWall,
+    position: Vec3,
+}
+
+fn save_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(SAVE_FILE))
+}
+
+/// Whether a match of `mode` can be saved, which needs somewhere to save it.
+pub fn can_save(mode: GameMode) -> bool {
+    matches!(
+        mode,
+        GameMode::Versus | GameMode::Doubles | GameMode::Blitz | GameMode::Arcade
+    ) && save_path().is_some()
+}
+
+/// Whether there's a saved match to carry on with.
+pub fn exists() -> bool {
+    save_path().is_some_and(|path| path.exists())
+}
+
+/// The saved match, taken off the disk so it's only carried on with once.
+pub fn take() -> Option<SavedMatch> {
+    let path = save_path()?;
+    let bytes = fs::read(&path).ok()?;
+    if let Err(error) = fs::remove_file(&path) {
+        warn!("Couldn't remove the saved match: {}", error);
+    }
+    match serde_json::from_slice(&bytes) {
+        Ok(saved) => Some(saved),
+        Err(error) => {
+            warn!("Ignoring unreadable saved match: {}", error);
+            None
+        }
+    }
+}
+
+impl SavedMatch {
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match save_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Saves the match when it's left with [`SaveMatch`], before anything in it
+/// is despawned.
+fn save_match(
+    mut requests: EventReader<SaveMatch>,
+    mode: Res<GameMode>,
+    rules: Res<MatchRules>,
+    arena_size: Res<ArenaSize>,
+    score: Res<Score>,
+    rally: Res<Rally>,
+    stats: Res<MatchStats>,
+    clock: Res<MatchClock>,
+    rng: Res<SimRng>,
+    wind: Res<Wind>,
+    portals: Res<Portals>,
+    players: Query<&Player>,
+    strings: Res<Strings>,
+    balls: Query<(&BallKind, &Transform, &Velocity, &Spin, Option<&Netted>), With<Ball>>,
+    rackets: Query<(&Racket, &Side, &Transform, &Tilt, &RacketLength, &Waiting)>,
+    walls: Query<(&MovingWall, &Transform)>,
+) {
+    if requests.read().last().is_none() {
+        return;
+    }
+
+    let name = |player_number| player_name(players.iter(), player_number, &strings);
+    let saved = SavedMatch {
+        mode: *mode,
+        names: [name(1), name(2)],
+        rules: *rules,
+        arena_size: *arena_size,
+        score: *score,
+        rally: rally.clone(),
+        stats: *stats,
+        clock: *clock,
+        rng: rng.clone(),
+        wind: wind.clone(),
+        portals: portals.clone(),
+        balls: balls
+            .iter()
+            .map(|(kind, transform, velocity, spin, netted)| SavedBall {
+                kind: *kind,
+                position: transform.translation.truncate(),
+                velocity: velocity.0,
+                spin: spin.0,
+                netted: netted.map(|netted| (netted.conceded_by(), netted.time_to_serve())),
+            })
+            .collect(),
+        rackets: rackets
+            .iter()
+            .map(
+                |(racket, side, transform, tilt, length, waiting)| SavedRacket {
+                    seat: racket.seat,
+                    side: *side,
+                    position: transform.translation,
+                    rotation: transform.rotation,
+                    tilt: tilt.0,
+                    length: *length,
+                    waiting: waiting.0,
+                },
+            )
+            .collect(),
+        moving_wall: walls.iter().next().map(|(wall, transform)| SavedWall {
+            wall: *wall,
+            position: transform.translation,
+        }),
+    };
+    match saved.save() {
+        Ok(()) => info!("Saved the match"),
+        Err(error) => warn!("Couldn't save the match: {}", error),
+    }
+}
+
+/// Puts a saved match back the way it was, over the fresh one started by its
+/// rules, once the arena's spawned every racket it had.
+fn resume_match(
+    mut commands: Commands,
+    saved: Option<Res<SavedMatch>>,
+    mut score: ResMut<Score>,
+    mut rally: ResMut<Rally>,
+    mut stats: ResMut<MatchStats>,
+    mut clock: ResMut<MatchClock>,
+    mut rng: ResMut<SimRng>,
+    mut wind: ResMut<Wind>,
+    mut portals: ResMut<Portals>,
+    goals: Query<(Entity, &Side), With<Goal>>,
+    balls: Query<Entity, With<Ball>>,
+    mut walls: Query<(&mut MovingWall, &mut Transform), Without<Racket>>,
+    mut rackets: Query<
+        (
+            &Racket,
+            &mut Side,
+            &mut Transform,
+            &mut Tilt,
+            &mut RacketLength,
+            &mut Waiting,
+        ),
+        Without<Goal>,
+    >,
+) {
+    let saved = match saved {
+        Some(saved) => saved,
+        None => return,
+    };
+    if goals.is_empty() || rackets.iter().count() < saved.rackets.len() {
+        return;
+    }
+
+    *score = saved.score;
+    *rally = saved.rally.clone();
+    *stats = saved.stats;
+    *clock = saved.clock;
+    *rng = saved.rng.clone();
+    *wind = saved.wind.clone();
+    *portals = saved.portals.clone();
+
+    for (racket, mut side, mut transform, mut tilt, mut length, mut waiting) in rackets.iter_mut() {
+        if let Some(saved) = saved.rackets.iter().find(|saved| saved.seat == racket.seat) {
+            *side = saved.side;
+            transform.translation = saved.position;
+            transform.rotation = saved.rotation;
+            tilt.0 = saved.tilt;
+            *length = saved.length;
+            waiting.0 = saved.waiting;
+        }
+    }
+
+    for ball in balls.iter() {
+        commands.entity(ball).despawn();
+    }
+    for ball in &saved.balls {
+        let mut entity = launch_ball(&mut commands, ball.kind, ball.position, ball.velocity);
+        entity
+            .insert(Spin(ball.spin))
+            .insert(StateScoped(AppState::Playing));
+        let netted = ball.netted.and_then(|(side, time_to_serve)| {
+            let (goal, _) = goals.iter().find(|(_, goal_side)| **goal_side == side)?;
+            Some(Netted::resumed(goal, side, time_to_serve))
+        });
+        if let Some(netted) = netted {
+            entity.insert(netted);
+        }
+    }
+
+    if let (Some(saved), Some((mut wall, mut transform))) =
+        (&saved.moving_wall, walls.iter_mut().next())
+    {
+        *wall = saved.wall;
+        transform.translation = saved.position;
+    }
+
+    commands.remove_resource::<SavedMatch>();
+}
@@ -0,0 +1,151 @@
+//! Pieces a kind of match adds to the arena, authored as scene files in
+//! `assets/scenes` rather than spawned in code: extra obstacles, rackets or
+//! balls, made of the same components as the ones the arena and the serve
+//! spawn. Each is spawned as its match starts and goes with it.
+//!
+//! Scenes only hold what's been registered with the type registry below, so
+//! pieces are spawned without their sprites, and are given them here once
+//! they're in the world. Online matches never have one, as a scene spawns a
+//! frame or two late and rollback wouldn't know about what's in it.
+
+use bevy::prelude::*;
+
+use crate::{
+    sim::{
+        Ball, BallKind, Collider, Dash, Goal, Momentum, Obstacle, PortalCooldown, Racket,
+        RacketLength, RacketSpeed, Side, Smash, Spin, Swing, Tilt, Velocity, Waiting,
+    },
+    start_match,
+    theme::ThemePart,
+    AppState, GameMode,
+};
+
+pub struct ScenesPlugin;
+
+impl Plugin for ScenesPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ThemePart>()
+            .register_type::<Collider>()
+            .register_type::<Obstacle>()
+            .register_type::<Goal>()
+            .register_type::<Side>()
+            .register_type::<Racket>()
+            .register_type::<Tilt>()
+            .register_type::<Swing>()
+            .register_type::<Smash>()
+            .register_type::<Momentum>()
+            .register_type::<Dash>()
+            .register_type::<RacketLength>()
+            .register_type::<RacketSpeed>()
+            .register_type::<Waiting>()
+            .register_type::<Ball>()
+            .register_type::<BallKind>()
+            .register_type::<Velocity>()
+            .register_type::<Spin>()
+            .register_type::<PortalCooldown>()
+            .add_systems(
+                OnEnter(AppState::Playing),
+                spawn_mode_scene.after(start_match),
+            )
+            .add_systems(Update, dress_pieces);
+    }
+}
+
+/// The scene a kind of match adds to the arena, if it adds one.
+fn scene(mode: GameMode) -> Option<&'static str> {
+    match mode {
+        // Something to keep the idle menu interesting
+        GameMode::Demo => Some("scenes/demo.scn.ron"),
+        _ => None,
+    }
+}
+
+fn spawn_mode_scene(mut commands: Commands, mode: Res<GameMode>, asset_server: Res<AssetServer>) {
+    if let Some(path) = scene(*mode) {
+        commands.spawn((
+            DynamicSceneBundle {
+                scene: asset_server.load(path),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ));
+    }
+}
+
+/// Gives pieces out of a scene, which it spawns as children of its own, the
+/// sprite they couldn't be saved with.
+fn dress_pieces(
+    mut commands: Commands,
+    pieces: Query<(Entity, &ThemePart, Option<&GlobalTransform>), (With<Parent>, Without<Sprite>)>,
+) {
+    for (entity, part, global_transform) in pieces.iter() {
+        let mut piece = commands.entity(entity);
+        piece
+            .insert(Sprite::default())
+            .insert(Handle::<Image>::default())
+            .insert(Visibility::default())
+            .insert(InheritedVisibility::default())
+            .insert(ViewVisibility::default())
+            // Put back, so the theme sees a new part to paint
+            .remove::<ThemePart>()
+            .insert(*part);
+        if global_transform.is_none() {
+            piece.insert(GlobalTransform::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::entity::EntityHashMap, scene::serde::SceneDeserializer};
+    use serde::de::DeserializeSeed;
+
+    use super::*;
+
+    /// Every scene a match can add, read with nothing but the types
+    /// registered for them.
+    #[test]
+    fn scenes_spawn_what_they_say() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(TransformPlugin)
+            .add_plugins(ScenesPlugin);
+
+        let modes = [
+            GameMode::Versus,
+            GameMode::Doubles,
+            GameMode::Blitz,
+            GameMode::Arcade,
+            GameMode::Practice,
+            GameMode::Survival,
+            GameMode::Tournament,
+            GameMode::Campaign,
+            GameMode::Online,
+            GameMode::Demo,
+        ];
+        for path in modes.into_iter().filter_map(scene) {
+            let bytes =
+                std::fs::read(format!("{}/assets/{}", env!("CARGO_MANIFEST_DIR"), path)).unwrap();
+            let scene = {
+                let registry = app.world().resource::<AppTypeRegistry>().read();
+                let mut deserializer = ron::de::Deserializer::from_bytes(&bytes).unwrap();
+                SceneDeserializer {
+                    type_registry: &registry,
+                }
+                .deserialize(&mut deserializer)
+                .unwrap_or_else(|error| panic!("{}: {}", path, error))
+            };
+            assert!(!scene.entities.is_empty(), "{} is empty", path);
+            scene
+                .write_to_world(app.world_mut(), &mut EntityHashMap::default())
+                .unwrap_or_else(|error| panic!("{}: {:?}", path, error));
+        }
+
+        let parts = app
+            .world_mut()
+            .query::<&ThemePart>()
+            .iter(app.world())
+            .count();
+        assert!(parts > 0);
+    }
+}
@@ -0,0 +1,375 @@
+//! Bots and scoring rules written in [Rhai](https://rhai.rs), read from the
+//! `.rhai` files the [mods](crate::mods) bring at startup.
+//!
+//! A script that defines `control(game)` is a bot, named after its file and
+//! offered as player 2 in the settings. `game` has the bounds of the court
+//! (`top`, `bottom`, `left`, `right`), the `racket` being played and the
+//! `others`, each with `x`, `y`, `width` and `height`, the `balls` in play,
+//! each with `x`, `y`, `vx`, `vy` and `radius`, the one that's `incoming` if
+//! any is, and `dt`, the seconds since the last call. It answers with a map of
+//! the buttons to hold down: `up`, `down`, `smash`, `tilt_up`, `tilt_down` and
+//! `dash`.
+//! Anything it puts in `this` is still there next time.
+//!
+//! A script that defines `points(goal)` decides what a goal is worth in the
+//! matches modifiers apply to. `goal` has who it was `scored_by`, the `hits`
+//! in the rally, the `points` it's worth so far and the score, as `player1`
+//! and `player2`, counting it once. With more than one, each gets the points
+//! the one before gave.
+//!
+//! Anything that goes wrong with a script shows up in a console over the game.
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::*;
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Map, AST, FLOAT, INT};
+
+use crate::{
+    ai::cpu_name,
+    bot::{BallView, BotRegistry, RacketController, RacketView, Snapshot},
+    mods::Mods,
+    seams::Clock,
+    sim::{GoalEvent, RacketInput, Rally, Score},
+    ui::{text, UiFont, ACCENT_COLOR},
+    GameMode,
+};
+
+/// Operations a single call to a script can take before it's stopped, so one
+/// that loops forever doesn't take the game with it.
+const MAX_OPERATIONS: u64 = 100_000;
+/// Seconds a message stays in the console.
+const CONSOLE_SECONDS: f64 = 10.0;
+/// Messages the console shows at once, the latest last.
+const CONSOLE_LINES: usize = 6;
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        let console = ScriptConsole::default();
+        let paths = app
+            .world()
+            .get_resource::<Mods>()
+            .map(Mods::scripts)
+            .unwrap_or_default();
+        let (scripts, failed) = load_scripts(paths, &console);
+        // So the mods screen doesn't list them as loaded
+        if let Some(mut mods) = app.world_mut().get_resource_mut::<Mods>() {
+            for (path, error) in failed {
+                mods.reject(&path, error);
+            }
+        }
+
+        let mut rules = Vec::new();
+        let mut bots = app
+            .init_resource::<BotRegistry>()
+            .world_mut()
+            .resource_mut::<BotRegistry>();
+        for script in scripts {
+            if script.defines("control", 1) {
+                let bot = script.clone();
+                bots.register(cpu_name(&script.name), move || {
+                    Box::new(ScriptBot::new(bot.clone()))
+                });
+            }
+            if script.defines("points", 1) {
+                rules.push(script);
+            }
+        }
+
+        app.insert_resource(console)
+            .insert_resource(ScoringRules(rules))
+            .add_systems(PostStartup, spawn_console)
+            .add_systems(Update, update_console::<Time>)
+            // After the step that scored the goal, and long before the ball's
+            // settled in the net and the point can decide anything
+            .add_systems(PostUpdate, score_by_script);
+    }
+}
+
+/// Messages from the scripts, for the console to show.
+#[derive(Resource, Default, Clone)]
+pub struct ScriptConsole(Arc<Mutex<Vec<String>>>);
+
+impl ScriptConsole {
+    fn report(&self, message: String) {
+        warn!("{}", message);
+        if let Ok(mut messages) = self.0.lock() {
+            messages.push(message);
+        }
+    }
+
+    fn take(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .map(|mut messages| std::mem::take(&mut *messages))
+            .unwrap_or_default()
+    }
+}
+
+/// One of the files in `mods/`, compiled.
+struct Script {
+    /// The file's name, without `.rhai`
+    name: String,
+    engine: Arc<Engine>,
+    ast: AST,
+    console: ScriptConsole,
+}
+
+impl Script {
+    fn defines(&self, function: &str, arity: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|metadata| metadata.name == function && metadata.params.len() == arity)
+    }
+
+    /// Calls `function`, with `this` bound to `memory`, reporting anything
+    /// that goes wrong.
+    fn call<T: Clone + Send + Sync + 'static>(
+        &self,
+        function: &str,
+        memory: &mut Dynamic,
+        args: impl FuncArgs,
+    ) -> Option<T> {
+        let options = CallFnOptions::new().bind_this_ptr(memory);
+        let result = self.engine.call_fn_with_options::<T>(
+            options,
+            &mut rhai::Scope::new(),
+            &self.ast,
+            function,
+            args,
+        );
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.console
+                    .report(format!("{}.rhai: {}: {}", self.name, function, error));
+                None
+            }
+        }
+    }
+}
+
+/// Compiles every one of `paths`, along with the ones that didn't and why.
+fn load_scripts(
+    paths: Vec<PathBuf>,
+    console: &ScriptConsole,
+) -> (Vec<Arc<Script>>, Vec<(PathBuf, String)>) {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let engine = Arc::new(engine);
+
+    let mut scripts = Vec::new();
+    let mut failed = Vec::new();
+    for path in paths {
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let compiled = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| engine.compile(source).map_err(|error| error.to_string()));
+        match compiled {
+            Ok(ast) => {
+                info!("Loaded script {}", path.display());
+                scripts.push(Arc::new(Script {
+                    name,
+                    engine: engine.clone(),
+                    ast,
+                    console: console.clone(),
+                }));
+            }
+            Err(error) => {
+                console.report(format!("{}.rhai: {}", name, error));
+                failed.push((path, error));
+            }
+        }
+    }
+    (scripts, failed)
+}
+
+/// A bot played by a script's `control`.
+struct ScriptBot {
+    script: Arc<Script>,
+    /// `this`, kept from one call to the next
+    memory: Dynamic,
+    /// Stops calling a script once it's gone wrong, rather than filling the
+    /// console with the same thing every frame
+    failed: bool,
+}
+
+impl ScriptBot {
+    fn new(script: Arc<Script>) -> Self {
+        ScriptBot {
+            script,
+            memory: Dynamic::from_map(Map::new()),
+            failed: false,
+        }
+    }
+}
+
+impl RacketController for ScriptBot {
+    fn control(&mut self, snapshot: &Snapshot) -> RacketInput {
+        if self.failed {
+            return RacketInput::default();
+        }
+        let buttons = self
+            .script
+            .call::<Map>("control", &mut self.memory, (game(snapshot),));
+        let buttons = match buttons {
+            Some(buttons) => buttons,
+            None => {
+                self.failed = true;
+                return RacketInput::default();
+            }
+        };
+        let held = |button: &str| {
+            buttons
+                .get(button)
+                .and_then(|value| value.as_bool().ok())
+                .unwrap_or(false)
+        };
+        RacketInput {
+            up: held("up"),
+            down: held("down"),
+            smash: held("smash"),
+            tilt_up: held("tilt_up"),
+            tilt_down: held("tilt_down"),
+            dash: held("dash"),
+        }
+    }
+}
+
+fn map<const N: usize>(entries: [(&str, Dynamic); N]) -> Dynamic {
+    let map: Map = entries
+        .into_iter()
+        .map(|(key, value)| (key.into(), value))
+        .collect();
+    Dynamic::from_map(map)
+}
+
+fn float(value: f32) -> Dynamic {
+    Dynamic::from_float(value as FLOAT)
+}
+
+fn racket(view: &RacketView) -> Dynamic {
+    map([
+        ("player", Dynamic::from_int(view.player_number as INT)),
+        ("x", float(view.position.x)),
+        ("y", float(view.position.y)),
+        ("width", float(view.extent.x)),
+        ("height", float(view.extent.y)),
+    ])
+}
+
+fn ball(view: &BallView) -> Dynamic {
+    map([
+        ("x", float(view.position.x)),
+        ("y", float(view.position.y)),
+        ("vx", float(view.velocity.x)),
+        ("vy", float(view.velocity.y)),
+        ("radius", float(view.radius)),
+    ])
+}
+
+/// What a script's `control` gets to see.
+fn game(snapshot: &Snapshot) -> Dynamic {
+    map([
+        ("dt", float(snapshot.delta.as_secs_f32())),
+        ("top", float(snapshot.bounds.max.y)),
+        ("bottom", float(snapshot.bounds.min.y)),
+        ("left", float(snapshot.bounds.min.x)),
+        ("right", float(snapshot.bounds.max.x)),
+        ("racket", racket(&snapshot.racket)),
+        (
+            "others",
+            Dynamic::from_array(snapshot.others.iter().map(racket).collect()),
+        ),
+        (
+            "balls",
+            Dynamic::from_array(snapshot.balls.iter().map(ball).collect()),
+        ),
+        ("incoming", snapshot.incoming().map_or(Dynamic::UNIT, ball)),
+    ])
+}
+
+/// The scripts that define `points`, in the order they're asked.
+#[derive(Resource)]
+struct ScoringRules(Vec<Arc<Script>>);
+
+fn score_by_script(
+    mode: Res<GameMode>,
+    rules: Res<ScoringRules>,
+    rally: Res<Rally>,
+    mut goals: EventReader<GoalEvent>,
+    mut score: ResMut<Score>,
+) {
+    if rules.0.is_empty() || !mode.takes_modifiers() {
+        return;
+    }
+
+    for goal in goals.read() {
+        let mut points: INT = 1;
+        for rule in rules.0.iter() {
+            let info = map([
+                ("scored_by", Dynamic::from_int(goal.scored_by as INT)),
+                (
+                    "hits",
+                    Dynamic::from_int(rally.finished.last().copied().unwrap_or_default() as INT),
+                ),
+                ("points", Dynamic::from_int(points)),
+                ("player1", Dynamic::from_int(score.player1 as INT)),
+                ("player2", Dynamic::from_int(score.player2 as INT)),
+            ]);
+            let mut memory = Dynamic::UNIT;
+            if let Some(worth) = rule.call::<INT>("points", &mut memory, (info,)) {
+                points = worth;
+            }
+        }
+        // The goal itself has already counted once
+        score.add_points(goal.scored_by, points.max(0) as i32 - 1);
+    }
+}
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn spawn_console(mut commands: Commands, font: Res<UiFont>) {
+    commands.spawn((
+        text(&font, "", 16.0, ACCENT_COLOR).with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(16.0),
+            top: Val::Px(16.0),
+            ..default()
+        }),
+        ConsoleText,
+    ));
+}
+
+fn update_console<C: Clock>(
+    time: Res<C>,
+    console: Res<ScriptConsole>,
+    mut shown: Local<Vec<(f64, String)>>,
+    mut texts: Query<&mut Text, With<ConsoleText>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let before = shown.len();
+    shown.retain(|(at, _)| now - at < CONSOLE_SECONDS);
+    let expired = shown.len() != before;
+    let new = console.take();
+    if new.is_empty() && !expired {
+        return;
+    }
+    shown.extend(new.into_iter().map(|message| (now, message)));
+    let excess = shown.len().saturating_sub(CONSOLE_LINES);
+    shown.drain(..excess);
+
+    let lines: Vec<&str> = shown.iter().map(|(_, message)| message.as_str()).collect();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,183 @@
+//! The seams between a match and everything outside it: the time going by,
+//! chance, and what's pressed at each seat. Each is a trait, with the real
+//! thing plugged in by the game and a stand-in here for tests, so a test can
+//! say exactly how long every frame takes, how the dice fall and what's held
+//! down, and a replay can feed a match back the inputs it was played with.
+//!
+//! - [`Clock`]: Bevy's [`Time`] in the game, a [`ManualClock`] in tests.
+//!   Every gameplay system that goes by how long it's been, from standing in
+//!   for an idle player to how long a match took, goes by one.
+//! - [`Rng`]: the [`SimRng`] in the game, a [`FixedRng`] in tests. Everything
+//!   in the simulation that's left to chance takes one, and the CPU's own is
+//!   seeded from the match's, so the same seed plays out the same.
+//! - [`InputSource`]: the [`Actions`] pressed in the game, the
+//!   [`Playback`](crate::recording::Playback) of a replay, or a
+//!   [`ScriptedInput`] in tests. [`read_input`] turns one into the
+//!   simulation's [`SimInput`].
+
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::Actions,
+    sim::{RacketInput, SimInput, SimRng, SEATS},
+};
+
+/// How much time has gone by.
+pub trait Clock: Resource {
+    /// Seconds since the frame before.
+    fn delta_seconds_f64(&self) -> f64;
+
+    /// Seconds since the game started.
+    fn elapsed_seconds_f64(&self) -> f64;
+
+    fn delta_seconds(&self) -> f32 {
+        self.delta_seconds_f64() as f32
+    }
+
+    fn delta(&self) -> Duration {
+        Duration::from_secs_f64(self.delta_seconds_f64())
+    }
+}
+
+impl<T: Default + Send + Sync + 'static> Clock for Time<T> {
+    fn delta_seconds_f64(&self) -> f64 {
+        Time::delta_seconds_f64(self)
+    }
+
+    fn elapsed_seconds_f64(&self) -> f64 {
+        Time::elapsed_seconds_f64(self)
+    }
+}
+
+/// A clock where every frame takes as long as it's told to, and none at all
+/// until it's told, and that's been going for as long as it's told.
+#[derive(Resource, Default, Debug)]
+pub struct ManualClock {
+    frame: f64,
+    elapsed: f64,
+}
+
+impl ManualClock {
+    /// A clock with every frame taking `seconds`.
+    pub fn new(seconds: f64) -> Self {
+        ManualClock {
+            frame: seconds,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Has every frame from now on take `seconds`.
+    pub fn set_frame(&mut self, seconds: f64) {
+        self.frame = seconds;
+    }
+
+    /// Has it been `seconds` since the game started.
+    pub fn set_elapsed(&mut self, seconds: f64) {
+        self.elapsed = seconds;
+    }
+}
+
+impl Clock for ManualClock {
+    fn delta_seconds_f64(&self) -> f64 {
+        self.frame
+    }
+
+    fn elapsed_seconds_f64(&self) -> f64 {
+        self.elapsed
+    }
+}
+
+/// Where chance comes from.
+pub trait Rng {
+    /// A number in `[min, max)`.
+    fn range(&mut self, min: f32, max: f32) -> f32;
+}
+
+impl Rng for SimRng {
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        SimRng::range(self, min, max)
+    }
+}
+
+/// Always comes up the same fraction of the way along, from 0 for the bottom
+/// of a range to just under 1 for the top.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedRng(pub f32);
+
+impl Rng for FixedRng {
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.0.clamp(0.0, 1.0 - f32::EPSILON)
+    }
+}
+
+/// What's pressed at every seat, step after step.
+pub trait InputSource: Resource {
+    /// The input for every seat for the next step, or nothing once there's
+    /// no more.
+    fn next_input(&mut self) -> Option<[RacketInput; SEATS]>;
+}
+
+impl InputSource for Actions {
+    /// Whatever's pressed for each seat now, for as long as it's asked.
+    fn next_input(&mut self) -> Option<[RacketInput; SEATS]> {
+        Some(std::array::from_fn(|seat| self.racket_input(seat)))
+    }
+}
+
+/// Input written out ahead, a step at a time, then nothing.
+#[derive(Resource, Default, Debug)]
+pub struct ScriptedInput(pub VecDeque<[RacketInput; SEATS]>);
+
+impl ScriptedInput {
+    /// `input` held for `steps` steps after whatever's written out already.
+    pub fn hold(&mut self, input: [RacketInput; SEATS], steps: usize) {
+        self.0.extend(std::iter::repeat_n(input, steps));
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn next_input(&mut self) -> Option<[RacketInput; SEATS]> {
+        self.0.pop_front()
+    }
+}
+
+/// Turns what the `I` gives into the simulation's input. Once it's run out,
+/// nothing's pressed.
+pub fn read_input<I: InputSource>(mut source: ResMut<I>, mut input: ResMut<SimInput>) {
+    input.0 = source.next_input().unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_rng_stays_in_range() {
+        assert_eq!(FixedRng(0.0).range(-2.0, 2.0), -2.0);
+        assert_eq!(FixedRng(0.5).range(-2.0, 2.0), 0.0);
+        assert!(FixedRng(1.0).range(-2.0, 2.0) < 2.0);
+    }
+
+    #[test]
+    fn scripted_input_runs_out() {
+        let mut app = App::new();
+        let up = RacketInput {
+            up: true,
+            ..default()
+        };
+        let mut script = ScriptedInput::default();
+        script.hold([up, default(), default(), default()], 2);
+        app.insert_resource(script)
+            .init_resource::<SimInput>()
+            .add_systems(Update, read_input::<ScriptedInput>);
+
+        for _ in 0..2 {
+            app.update();
+            assert!(app.world().resource::<SimInput>().0[0].up);
+        }
+        app.update();
+        assert!(!app.world().resource::<SimInput>().0[0].up);
+    }
+}
@@ -0,0 +1,462 @@
+//! Ranked seasons for the players' profiles, kept in a RON file next to the
+//! profiles. A profile's first few matches of a season against the campaign's
+//! ladder or a ranked online opponent place it, with a starting rank for each
+//! of them won, and every one after moves its rank up or down. Seasons last
+//! as long as the settings say, after which everyone's placed again, and the
+//! season screen shows where each profile stands and where it finished the
+//! season before.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "networking")]
+use crate::network::NetworkSettings;
+use crate::{
+    campaign::{Campaign, RUNGS},
+    locale::Strings,
+    profiles::Profiles,
+    recording::Playback,
+    settings::Settings,
+    sim::MatchOver,
+    stats::{load_ron, now, save_ron},
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, UiFont, ACCENT_COLOR, DIM_TEXT_COLOR,
+        PANEL_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode,
+};
+
+const SEASON_FILE: &str = "season.ron";
+/// Matches a profile plays each season before it has a rank.
+pub const PLACEMENT_MATCHES: u32 = 5;
+/// Rank points every placement match won starts a profile on.
+const PLACEMENT_POINTS: u32 = 200;
+/// Rank points a ranked online win is worth, and a win against the top of
+/// the ladder. The rungs below it are worth less.
+const WIN_POINTS: u32 = 30;
+const LOSS_POINTS: u32 = 20;
+const DAY: u64 = 86400;
+
+/// Key of each rank's name in the [`Strings`], and the rank points it starts
+/// at, lowest first.
+const TIERS: [(&str, u32); 5] = [
+    ("season.tier.bronze", 0),
+    ("season.tier.silver", 300),
+    ("season.tier.gold", 600),
+    ("season.tier.platinum", 900),
+    ("season.tier.diamond", 1200),
+];
+
+pub struct SeasonPlugin;
+
+impl Plugin for SeasonPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Season::load())
+            .add_systems(OnEnter(AppState::Menu), roll_over)
+            .add_systems(OnExit(AppState::Playing), rank_match)
+            .add_systems(
+                OnEnter(AppState::Season),
+                (roll_over, spawn_season.after(roll_over)),
+            )
+            .add_systems(Update, season_action.run_if(in_state(AppState::Season)));
+    }
+}
+
+/// Who a ranked match was against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opponent {
+    /// The CPU on this rung of the campaign's ladder, from 0 at the bottom
+    Ladder(usize),
+    #[cfg_attr(not(feature = "networking"), allow(dead_code))]
+    Online,
+}
+
+impl Opponent {
+    /// Rank points a win against them is worth.
+    fn win_points(self) -> u32 {
+        match self {
+            Opponent::Ladder(rung) => WIN_POINTS * (rung.min(RUNGS - 1) as u32 + 1) / RUNGS as u32,
+            Opponent::Online => WIN_POINTS,
+        }
+    }
+}
+
+/// How a profile's doing this season.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Standing {
+    /// Placement matches played, up to [`PLACEMENT_MATCHES`]
+    pub placed: u32,
+    /// Rank points, once the placement matches are over
+    pub points: u32,
+    /// The most rank points it's had this season
+    pub peak: u32,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+impl Standing {
+    /// Whether the placement matches are over, so it has a rank.
+    pub fn is_placed(&self) -> bool {
+        self.placed >= PLACEMENT_MATCHES
+    }
+
+    /// Index into the ranks of the one it has, if it's been placed.
+    pub fn tier(&self) -> Option<usize> {
+        if !self.is_placed() {
+            return None;
+        }
+        TIERS.iter().rposition(|(_, from)| self.points >= *from)
+    }
+
+    /// Counts a ranked match against `opponent`. During the placement
+    /// matches, each one won is worth a starting rank, and they only add up
+    /// to one at the end.
+    pub fn record(&mut self, opponent: Opponent, won: bool) {
+        if won {
+            self.wins += 1;
+        } else {
+            self.losses += 1;
+        }
+        if !self.is_placed() {
+            self.placed += 1;
+            if won {
+                self.points += PLACEMENT_POINTS;
+            }
+        } else if won {
+            self.points += opponent.win_points();
+        } else {
+            self.points = self.points.saturating_sub(LOSS_POINTS);
+        }
+        if self.is_placed() {
+            self.peak = self.peak.max(self.points);
+        }
+    }
+}
+
+/// The season being played, and how the one before ended, kept in a RON file
+/// in the user's data directory.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Season {
+    /// Counting from 1, with 0 for none having started yet
+    pub number: u32,
+    /// Seconds since the Unix epoch when it started
+    pub started_at: u64,
+    /// Every profile that's played a ranked match this season
+    pub standings: BTreeMap<String, Standing>,
+    /// Where every profile finished the season before, if it was played
+    pub last: BTreeMap<String, Standing>,
+}
+
+impl Season {
+    fn load() -> Self {
+        load_ron(SEASON_FILE)
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        save_ron(SEASON_FILE, self)
+    }
+
+    /// Seconds since the Unix epoch when it ends, with seasons `days` long.
+    pub fn ends_at(&self, days: u32) -> u64 {
+        self.started_at + days.max(1) as u64 * DAY
+    }
+
+    /// Starts a new season if `now` is past the end of this one, or the first
+    /// if there's been none, and says whether it did. After whole seasons
+    /// nobody played, there's nothing to show for the one before.
+    fn roll_over(&mut self, now: u64, days: u32) -> bool {
+        if self.number == 0 {
+            self.number = 1;
+            self.started_at = now;
+            return true;
+        }
+        let length = days.max(1) as u64 * DAY;
+        let ended = now.saturating_sub(self.started_at) / length;
+        if ended == 0 {
+            return false;
+        }
+        self.last = if ended == 1 {
+            std::mem::take(&mut self.standings)
+        } else {
+            BTreeMap::new()
+        };
+        self.standings.clear();
+        self.number += ended as u32;
+        self.started_at += ended * length;
+        true
+    }
+}
+
+/// Starts the next season once this one's over.
+fn roll_over(settings: Res<Settings>, mut season: ResMut<Season>) {
+    if season.roll_over(now(), settings.season_days) {
+        info!("Season {} has started", season.number);
+        if let Err(error) = season.save() {
+            warn!("Couldn't save the season: {}", error);
+        }
+    }
+}
+
+/// Moves the rank of the profile that played a ranked match, against the
+/// campaign's ladder or online, to the end.
+fn rank_match(
+    mode: Res<GameMode>,
+    match_over: Res<MatchOver>,
+    campaign: Res<Campaign>,
+    profiles: Res<Profiles>,
+    mut season: ResMut<Season>,
+    playback: Option<Res<Playback>>,
+    #[cfg(feature = "networking")] network: Option<Res<NetworkSettings>>,
+) {
+    let winner = match match_over.0 {
+        Some(winner) => winner,
+        None => return,
+    };
+    // A replay was ranked when it was played
+    if playback.is_some() {
+        return;
+    }
+
+    // The profile, its player number and who it played
+    #[cfg(feature = "networking")]
+    let online = network
+        .filter(|network| network.ranked)
+        .and_then(|network| {
+            profiles.seats[network.local_player]
+                .clone()
+                .map(|name| (name, network.local_player as i32 + 1, Opponent::Online))
+        });
+    #[cfg(not(feature = "networking"))]
+    let online = None;
+    let ranked = match *mode {
+        GameMode::Campaign => campaign
+            .rung(*mode)
+            .zip(profiles.seats[0].clone())
+            .map(|(rung, name)| (name, 1, Opponent::Ladder(rung))),
+        GameMode::Online => online,
+        _ => None,
+    };
+    let (name, player_number, opponent) = match ranked {
+        Some(ranked) => ranked,
+        None => return,
+    };
+
+    let standing = season.standings.entry(name.clone()).or_default();
+    standing.record(opponent, winner == player_number);
+    info!(
+        "{} has {} season points after {} ranked matches",
+        name,
+        standing.points,
+        standing.wins + standing.losses
+    );
+    if let Err(error) = season.save() {
+        warn!("Couldn't save the season: {}", error);
+    }
+}
+
+#[derive(Component)]
+struct BackButton;
+
+/// One profile's line on the season screen, and the smaller one under it.
+fn standing_lines(name: &str, standing: &Standing, strings: &Strings) -> (String, String) {
+    let tier = match standing.tier() {
+        Some(tier) => tier,
+        None => {
+            return (
+                strings.fill(
+                    "season.placing",
+                    &[
+                        ("name", &name),
+                        ("played", &standing.placed),
+                        ("total", &PLACEMENT_MATCHES),
+                    ],
+                ),
+                strings.fill(
+                    "season.record",
+                    &[("wins", &standing.wins), ("losses", &standing.losses)],
+                ),
+            )
+        }
+    };
+    let line = strings.fill(
+        "season.rank",
+        &[
+            ("name", &name),
+            ("tier", &strings.get(TIERS[tier].0)),
+            ("points", &standing.points),
+            ("wins", &standing.wins),
+            ("losses", &standing.losses),
+        ],
+    );
+    let progress = match TIERS.get(tier + 1) {
+        Some((next, from)) => strings.fill(
+            "season.progress",
+            &[
+                ("peak", &standing.peak),
+                ("points", &(from - standing.points)),
+                ("tier", &strings.get(next)),
+            ],
+        ),
+        None => strings.fill("season.top", &[("peak", &standing.peak)]),
+    };
+    (line, progress)
+}
+
+fn spawn_season(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    settings: Res<Settings>,
+    season: Res<Season>,
+) {
+    let days_left = season
+        .ends_at(settings.season_days)
+        .saturating_sub(now())
+        .div_ceil(DAY);
+
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Season)))
+        .with_children(|parent| {
+            parent.spawn(text(
+                &font,
+                strings.fill("season.title", &[("number", &season.number)]),
+                48.0,
+                TEXT_COLOR,
+            ));
+            parent.spawn(text(
+                &font,
+                strings.count("season.days_left", days_left as u32),
+                20.0,
+                DIM_TEXT_COLOR,
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(16.0)),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    if season.standings.is_empty() {
+                        parent.spawn(text(
+                            &font,
+                            strings.get("season.empty"),
+                            20.0,
+                            DIM_TEXT_COLOR,
+                        ));
+                    }
+                    // Highest ranked first, then whoever's furthest through
+                    // their placement matches
+                    let mut standings: Vec<_> = season.standings.iter().collect();
+                    standings.sort_by_key(|(_, standing)| {
+                        std::cmp::Reverse((standing.is_placed(), standing.points, standing.placed))
+                    });
+                    for (name, standing) in standings {
+                        let (line, progress) = standing_lines(name, standing, &strings);
+                        let color = if standing.is_placed() {
+                            TEXT_COLOR
+                        } else {
+                            ACCENT_COLOR
+                        };
+                        parent.spawn(text(&font, line, 22.0, color).with_style(Style {
+                            margin: UiRect {
+                                top: Val::Px(6.0),
+                                ..default()
+                            },
+                            ..default()
+                        }));
+                        let last = season.last.get(name).and_then(Standing::tier).map(|tier| {
+                            strings.fill("season.last", &[("tier", &strings.get(TIERS[tier].0))])
+                        });
+                        let progress = match last {
+                            Some(last) => format!("{}  {}", progress, last),
+                            None => progress,
+                        };
+                        parent.spawn(text(&font, progress, 16.0, DIM_TEXT_COLOR));
+                    }
+                });
+
+            spawn_button(parent, &font, 0, strings.get("back")).insert(BackButton);
+        });
+}
+
+fn season_action(
+    mut activated: EventReader<ButtonActivated>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let back = activated
+        .read()
+        .any(|event| back_buttons.get(event.0).is_ok())
+        || keyboard_input.just_pressed(KeyCode::Escape);
+    if back {
+        state.set(AppState::Menu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placement_matches_give_a_starting_rank() {
+        let mut standing = Standing::default();
+        for won in [true, true, false, true] {
+            standing.record(Opponent::Online, won);
+            assert_eq!(standing.tier(), None);
+        }
+        standing.record(Opponent::Online, false);
+        assert_eq!(standing.points, 3 * PLACEMENT_POINTS);
+        assert_eq!(standing.tier(), Some(2));
+        assert_eq!(standing.peak, standing.points);
+    }
+
+    #[test]
+    fn ranks_move_with_wins_and_losses() {
+        let mut standing = Standing {
+            placed: PLACEMENT_MATCHES,
+            points: 10,
+            ..default()
+        };
+        standing.record(Opponent::Online, true);
+        assert_eq!(standing.points, 10 + WIN_POINTS);
+        standing.record(Opponent::Online, false);
+        standing.record(Opponent::Online, false);
+        assert_eq!(standing.points, 0);
+        assert_eq!(standing.peak, 10 + WIN_POINTS);
+
+        // The bottom of the ladder is worth the least
+        assert!(Opponent::Ladder(0).win_points() < Opponent::Ladder(RUNGS - 1).win_points());
+        assert_eq!(Opponent::Ladder(RUNGS - 1).win_points(), WIN_POINTS);
+    }
+
+    #[test]
+    fn seasons_end_on_schedule() {
+        let mut season = Season::default();
+        assert!(season.roll_over(1000, 7));
+        assert_eq!(season.number, 1);
+        season.standings.insert("Ada".into(), Standing::default());
+
+        assert!(!season.roll_over(1000 + 7 * DAY - 1, 7));
+        assert!(season.roll_over(1000 + 7 * DAY, 7));
+        assert_eq!(season.number, 2);
+        assert!(season.standings.is_empty());
+        assert!(season.last.contains_key("Ada"));
+        assert_eq!(season.started_at, 1000 + 7 * DAY);
+
+        // Nobody played the seasons in between
+        assert!(season.roll_over(1000 + 30 * DAY, 7));
+        assert_eq!(season.number, 5);
+        assert!(season.last.is_empty());
+    }
+}
@@ -0,0 +1,1258 @@
+//! Player preferences, kept in a RON file next to the stats.
+
+use std::{fs, path::PathBuf};
+
+use bevy::{asset::LoadedFolder, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{cpu_name, Personalities},
+    arena::{ArenaSize, ARENA_SIZES},
+    bot::BotRegistry,
+    display::{VsyncMode, VSYNC_MODES},
+    locale::{Locales, Strings, DEFAULT_LANGUAGE},
+    mods::{Content, Mods},
+    practice::BallMachine,
+    sim::{BallKind, Handicap, MatchRules, TimeStep, BALL_KINDS, STEP_RATES},
+    stats::data_dir,
+    theme::{Palette, PALETTES, THEMES},
+    ui::{
+        screen_root, spawn_button, text, ButtonActivated, MenuButton, MenuFocus, UiFont,
+        ACCENT_COLOR, DIM_TEXT_COLOR, TEXT_COLOR,
+    },
+    AppState,
+};
+
+const SETTINGS_FILE: &str = "settings.ron";
+const SCREEN_SHAKE_STEP: f32 = 0.25;
+const VOLUME_STEP: f32 = 0.1;
+/// Frames a second the game can be held to, if any.
+const FRAME_CAPS: [Option<u32>; 6] = [None, Some(30), Some(60), Some(120), Some(144), Some(240)];
+/// Window sizes to pick from for playing in a window, in logical pixels.
+const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+/// The scores a local match's sets can be played to.
+pub const POINTS_TO_WIN: [i32; 4] = [5, 11, 15, 21];
+/// How fast serves can leave the center, as a multiple of their usual speed.
+pub const BALL_SPEEDS: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+/// How fast local matches can be played, as a share of their usual speed.
+const GAME_SPEEDS: [f32; 5] = [0.5, 0.75, 1.0, 1.25, 1.5];
+/// How fast rackets can move, as a multiple of their usual speed.
+pub const RACKET_SPEEDS: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+/// How many sets a local match can be the best of.
+pub const BEST_OF: [i32; 4] = [1, 3, 5, 7];
+/// The ties a local match can go to sudden death at.
+pub const GOLDEN_POINTS: [Option<i32>; 3] = [None, Some(10), Some(5)];
+/// How many lives each player can start an arcade match with.
+const LIVES: [i32; 4] = [1, 3, 5, 9];
+const ARCADE_LIVES: i32 = 3;
+/// How hard gravity can pull, as a multiple of its usual strength.
+pub const GRAVITY_STRENGTHS: [f32; 4] = [0.5, 1.0, 1.5, 2.0];
+/// Seconds the ball machine can wait between balls.
+const WALL_REACHES: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+const WALL_PERIODS: [f32; 4] = [2.0, 3.0, 4.0, 6.0];
+const MACHINE_INTERVALS: [f32; 4] = [1.0, 1.5, 2.0, 3.0];
+/// How fast the ball machine's balls can go, as a multiple of a serve's speed.
+const MACHINE_SPEEDS: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+/// How far off straight the ball machine can aim, in degrees.
+const MACHINE_SPREADS: [f32; 4] = [0.0, 15.0, 30.0, 45.0];
+/// The voice packs in `assets/announcers` the announcer can speak with.
+pub const VOICE_PACKS: [&str; 1] = ["default"];
+/// How much quicker a CPU can play for every point it's behind, and slower
+/// for every point it's ahead.
+const CATCH_UPS: [f32; 4] = [0.0, 0.05, 0.1, 0.2];
+/// Points a player can start every set with.
+const HEAD_STARTS: [i32; 5] = [0, 1, 2, 3, 5];
+/// How long a player's rackets can be, as a multiple of their usual length.
+const HANDICAP_LENGTHS: [f32; 5] = [0.5, 0.75, 1.0, 1.25, 1.5];
+/// How hard players' gamepads can rumble, from not at all to as hard as they go.
+const RUMBLE_STRENGTHS: [f32; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+/// Whose racket can follow the mouse.
+const MOUSE_PLAYERS: [Option<i32>; 3] = [None, Some(1), Some(2)];
+/// How many pieces each step's movement of the ball can be cut into.
+const SUBSTEPS: [u32; 4] = [1, 2, 4, 8];
+/// How many days a ranked season can last.
+const SEASON_LENGTHS: [u32; 4] = [7, 14, 28, 91];
+/// Seconds a player can go without pressing anything before a CPU stands in.
+const IDLE_TAKEOVERS: [Option<f32>; 4] = [None, Some(10.0), Some(20.0), Some(30.0)];
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load())
+            .init_resource::<SettingsTab>()
+            .add_systems(Update, save_settings)
+            .add_systems(OnEnter(AppState::Settings), spawn_settings_menu)
+            .add_systems(
+                Update,
+                (
+                    change_setting,
+                    update_setting_labels.after(change_setting),
+                    switch_tab,
+                    settings_action,
+                )
+                    .run_if(in_state(AppState::Settings)),
+            );
+    }
+}
+
+// Missing fields fall back to their defaults, so older files keep loading
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// How loud everything is, from 0 (silent) to 1
+    pub volume: f32,
+    /// How loud the announcer is, from 0 to 1, on top of `volume`
+    pub announcer_volume: f32,
+    pub fullscreen: bool,
+    pub vsync_mode: VsyncMode,
+    /// The most frames a second, if there's a limit on top of the vsync's
+    pub frame_cap: Option<u32>,
+    /// Frames slow right down away from a match, once the window's in the
+    /// background or nothing's been pressed for a while
+    pub low_power: bool,
+    /// Width and height of the window when not fullscreen, in logical pixels
+    pub resolution: (u32, u32),
+    /// Pauses a local match when the window loses focus
+    pub pause_on_focus_loss: bool,
+    /// How many points win a set in local matches
+    pub points_to_win: i32,
+    /// How fast serves leave the center in local matches, as a multiple of
+    /// their usual speed
+    pub ball_speed: f32,
+    /// How fast everything in a local match goes, from half to one and a half
+    /// times its usual speed, for anyone it's too fast for or who wants to
+    /// practice for faster
+    pub game_speed: f32,
+    /// How fast rackets move in local matches, as a multiple of their usual
+    /// speed
+    pub racket_speed: f32,
+    /// What the ball's made of in local matches
+    pub ball: BallKind,
+    /// How big the court is, for every match but online ones
+    pub arena_size: ArenaSize,
+    /// How many sets local matches are the best of
+    pub best_of: i32,
+    /// The tie after which the next point wins a set, if any
+    pub golden_point: Option<i32>,
+    /// Local matches are played in the wind, picked from the menu
+    pub wind: bool,
+    /// Local matches are played with gravity, picked from the menu
+    pub gravity: bool,
+    /// How hard gravity pulls, as a multiple of its usual strength
+    pub gravity_strength: f32,
+    /// How many lives each player starts an arcade match with
+    pub arcade_lives: i32,
+    /// Local matches are played with portals on the court, picked from the menu
+    pub portals: bool,
+    /// Local matches are played with a wall going up and down the middle of
+    /// the court, picked from the menu
+    pub moving_wall: bool,
+    /// Local matches are played at night, picked from the menu
+    pub night: bool,
+    /// Local matches are played with a low net across the middle of the
+    /// court, picked from the menu
+    pub center_net: bool,
+    /// How far the moving wall goes towards the top and bottom of the court,
+    /// as a fraction of the way to the walls
+    pub wall_reach: f32,
+    /// Seconds the moving wall takes to go up and down once
+    pub wall_period: f32,
+    /// Local matches are played with rackets shrinking as the rally goes on,
+    /// picked from the menu
+    pub shrinking: bool,
+    /// Local matches are played with serves called for faults and lets,
+    /// picked from the menu
+    pub serve_rules: bool,
+    /// Partners in doubles have to take turns at the ball, rather than
+    /// either of them hitting it
+    pub doubles_turns: bool,
+    /// What each player's given in local matches to even them out, player
+    /// 1's first
+    pub handicaps: [Handicap; 2],
+    /// Name of the personality file in `assets/personalities` for the CPU
+    /// playing player 2 in a match against one, or of a bot compiled in, or
+    /// nothing for two players
+    pub cpu_opponent: Option<String>,
+    /// The player whose racket follows the mouse, if anyone's does, as well
+    /// as their keys
+    pub mouse_player: Option<i32>,
+    /// How hard each player's gamepad rumbles, player 1's first, from 0 (not
+    /// at all) to 1
+    pub rumble: [f32; 2],
+    /// How much quicker a CPU plays for every point it's behind, and slower
+    /// for every point it's ahead, to keep matches against it close
+    pub cpu_catch_up: f32,
+    /// Seconds a player in a local match can press nothing for before a CPU
+    /// stands in for them until they're back, if one ever does
+    pub idle_takeover: Option<f32>,
+    /// A tip after every point lost by a player at the keys, for beginners
+    pub coach: bool,
+    /// Seconds between the ball machine's balls in practice
+    pub machine_interval: f32,
+    /// How fast the ball machine's balls go, as a multiple of a serve's speed
+    pub machine_speed: f32,
+    /// How far off straight at the player the ball machine aims, in degrees
+    pub machine_spread: f32,
+    /// Whether practice counts the returns, rather than being free play
+    pub practice_score: bool,
+    /// Dots the way the ball's going in practice, bounces and all
+    pub ghost_path: bool,
+    /// Keys mid-match for resetting the point and serving, run through the
+    /// console, which only builds with the devtools feature
+    pub practice_hotkeys: bool,
+    /// Fewer particles, for slow machines and for anyone who finds them too busy
+    pub reduced_effects: bool,
+    /// How hard the screen shakes, from 0 (not at all) to 1
+    pub screen_shake: f32,
+    pub ball_trail: bool,
+    /// The camera leans towards the ball, and pulls back in fast rallies
+    pub follow_camera: bool,
+    /// A close-up of the ball in a corner of the window during matches,
+    /// turned on and off with Tab as well
+    pub ball_cam: bool,
+    /// Each player's chances of winning, in a bar under the scoreboard
+    pub win_meter: bool,
+    /// Name of the theme file in `assets/themes`
+    pub theme: String,
+    /// Scanlines and a curved screen, like an old TV
+    pub crt_filter: bool,
+    /// No screen shake, particles or CRT filter, and gentle flashes, for
+    /// anyone who finds things jumping or flashing on screen hard to look at.
+    /// Everything [`Settings::reduced_effects`] turns down goes with them
+    pub reduced_motion: bool,
+    /// Colors for the ball, rackets and portals laid over the theme's, for
+    /// anyone colour blind
+    pub palette: Palette,
+    /// A black court, with the ball and the rackets outlined
+    pub high_contrast: bool,
+    /// Voice pack in `assets/announcers` calling the score, if anyone is
+    pub announcer: Option<String>,
+    /// A tone following the ball, higher the higher up it is and panned to
+    /// where it is across, beeping as it comes at someone's racket
+    pub ball_sound: bool,
+    /// Name of the language file in `assets/locales` everything's said in
+    pub language: String,
+    /// Sends the results of ranked online matches to the leaderboard server,
+    /// which nothing is until it's turned on
+    pub share_results: bool,
+    /// How often local matches step, and how many pieces the ball's moved
+    /// in each step, for machines that can keep up with more
+    pub time_step: TimeStep,
+    /// Days each ranked season lasts, before everyone's placed again
+    pub season_days: u32,
+    /// Serves every local match from this seed, to play one again exactly.
+    /// Only set by hand, and `--seed` takes its place for a single run
+    pub seed: Option<u64>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            volume: 1.0,
+            announcer_volume: 1.0,
+            fullscreen: false,
+            vsync_mode: VsyncMode::On,
+            frame_cap: None,
+            low_power: true,
+            resolution: RESOLUTIONS[0],
+            pause_on_focus_loss: true,
+            points_to_win: MatchRules::default().points_to_win,
+            ball_speed: 1.0,
+            game_speed: 1.0,
+            racket_speed: 1.0,
+            ball: BallKind::Standard,
+            arena_size: ArenaSize::Classic,
+            best_of: MatchRules::default().best_of,
+            golden_point: None,
+            wind: false,
+            gravity: false,
+            gravity_strength: 1.0,
+            arcade_lives: ARCADE_LIVES,
+            portals: false,
+            moving_wall: false,
+            center_net: false,
+            night: false,
+            wall_reach: 0.5,
+            wall_period: 4.0,
+            shrinking: false,
+            serve_rules: false,
+            doubles_turns: true,
+            handicaps: default(),
+            cpu_opponent: None,
+            mouse_player: None,
+            rumble: [1.0; 2],
+            cpu_catch_up: 0.0,
+            idle_takeover: None,
+            coach: false,
+            machine_interval: BallMachine::default().interval,
+            machine_speed: BallMachine::default().speed,
+            machine_spread: BallMachine::default().spread,
+            practice_score: true,
+            ghost_path: true,
+            practice_hotkeys: false,
+            reduced_effects: false,
+            screen_shake: 1.0,
+            ball_trail: true,
+            follow_camera: false,
+            ball_cam: false,
+            win_meter: true,
+            theme: THEMES[0].to_string(),
+            crt_filter: false,
+            reduced_motion: false,
+            palette: Palette::Standard,
+            high_contrast: false,
+            announcer: None,
+            ball_sound: false,
+            language: DEFAULT_LANGUAGE.to_string(),
+            share_results: false,
+            time_step: TimeStep::default(),
+            season_days: 28,
+            seed: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Whether the busier effects are turned down, asked for on their own or
+    /// along with [`Settings::reduced_motion`].
+    pub fn effects_reduced(&self) -> bool {
+        self.reduced_effects || self.reduced_motion
+    }
+
+    fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(SETTINGS_FILE))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| match ron::de::from_bytes(&bytes) {
+                Ok(settings) => Some(settings),
+                Err(error) => {
+                    warn!("Ignoring unreadable settings file: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    /// Moves player `player`'s (by index) racket length `step` places through
+    /// the lengths there are, up for a `step` of 0.
+    pub fn cycle_racket_length(&mut self, player: usize, step: f32) {
+        let handicap = &mut self.handicaps[player];
+        handicap.racket_length = cycle(&HANDICAP_LENGTHS, handicap.racket_length, step);
+    }
+
+    /// Moves player `player`'s (by index) racket speed `step` places through
+    /// the speeds there are, up for a `step` of 0.
+    pub fn cycle_racket_speed(&mut self, player: usize, step: f32) {
+        let handicap = &mut self.handicaps[player];
+        handicap.racket_speed = cycle(&RACKET_SPEEDS, handicap.racket_speed, step);
+    }
+}
+
+#[derive(Component)]
+struct OnSettingsMenu;
+
+/// A page of the settings screen, the one showing being a resource so it's
+/// still there on coming back.
+#[derive(Component, Resource, Default, Clone, Copy, PartialEq, Eq)]
+enum SettingsTab {
+    Audio,
+    Video,
+    Controls,
+    #[default]
+    Gameplay,
+    Handicaps,
+    Advanced,
+}
+
+impl SettingsTab {
+    /// Key of its title in the [`Strings`].
+    fn title_key(self) -> &'static str {
+        match self {
+            SettingsTab::Audio => "settings.tab.audio",
+            SettingsTab::Video => "settings.tab.video",
+            SettingsTab::Controls => "settings.tab.controls",
+            SettingsTab::Gameplay => "settings.tab.gameplay",
+            SettingsTab::Handicaps => "settings.tab.handicaps",
+            SettingsTab::Advanced => "settings.tab.advanced",
+        }
+    }
+
+    /// The tabs there are, in order. Without audio there's nothing to hear.
+    fn all() -> Vec<SettingsTab> {
+        [
+            SettingsTab::Audio,
+            SettingsTab::Video,
+            SettingsTab::Controls,
+            SettingsTab::Gameplay,
+            SettingsTab::Handicaps,
+            SettingsTab::Advanced,
+        ]
+        .into_iter()
+        .filter(|tab| cfg!(feature = "audio") || *tab != SettingsTab::Audio)
+        .collect()
+    }
+
+    /// The page's columns of settings, each with the key of its title.
+    fn groups(self) -> &'static [(&'static str, &'static [Setting])] {
+        match self {
+            SettingsTab::Audio => &[
+                (
+                    "settings.group.volume",
+                    &[Setting::Volume, Setting::AnnouncerVolume],
+                ),
+                ("settings.group.announcer", &[Setting::Announcer]),
+                ("settings.group.accessibility", &[Setting::BallSound]),
+            ],
+            SettingsTab::Video => &[
+                (
+                    "settings.group.window",
+                    &[
+                        Setting::Fullscreen,
+                        Setting::Vsync,
+                        Setting::FrameCap,
+                        Setting::LowPower,
+                        Setting::Resolution,
+                        Setting::PauseOnFocusLoss,
+                    ],
+                ),
+                (
+                    "settings.group.effects",
+                    &[
+                        Setting::Theme,
+                        Setting::Effects,
+                        Setting::ScreenShake,
+                        Setting::BallTrail,
+                        Setting::FollowCamera,
+                        Setting::BallCam,
+                        Setting::WinMeter,
+                        Setting::CrtFilter,
+                    ],
+                ),
+                (
+                    "settings.group.accessibility",
+                    &[
+                        Setting::ReducedMotion,
+                        Setting::Palette,
+                        Setting::HighContrast,
+                    ],
+                ),
+            ],
+            // There's only a leaderboard to share with in builds that have one
+            SettingsTab::Controls if cfg!(feature = "leaderboard") => &[
+                (
+                    "settings.group.rackets",
+                    &[Setting::Mouse, Setting::Rumble(0), Setting::Rumble(1)],
+                ),
+                ("settings.group.language", &[Setting::Language]),
+                ("settings.group.online", &[Setting::ShareResults]),
+            ],
+            SettingsTab::Controls => &[
+                (
+                    "settings.group.rackets",
+                    &[Setting::Mouse, Setting::Rumble(0), Setting::Rumble(1)],
+                ),
+                ("settings.group.language", &[Setting::Language]),
+            ],
+            SettingsTab::Gameplay => &[
+                (
+                    "settings.group.match",
+                    &[
+                        Setting::PointsToWin,
+                        Setting::BestOf,
+                        Setting::GoldenPoint,
+                        Setting::ArcadeLives,
+                        Setting::DoublesTurns,
+                    ],
+                ),
+                (
+                    "settings.group.play",
+                    &[
+                        Setting::GameSpeed,
+                        Setting::BallSpeed,
+                        Setting::RacketSpeed,
+                        Setting::Ball,
+                        Setting::ArenaSize,
+                        Setting::GravityStrength,
+                        Setting::WallReach,
+                        Setting::WallPeriod,
+                        Setting::CpuOpponent,
+                        Setting::CpuCatchUp,
+                        Setting::IdleTakeover,
+                        Setting::Coach,
+                    ],
+                ),
+                (
+                    "settings.group.practice",
+                    &[
+                        Setting::MachineInterval,
+                        Setting::MachineSpeed,
+                        Setting::MachineSpread,
+                        Setting::PracticeScore,
+                        Setting::GhostPath,
+                        // They're console commands, and there's only a
+                        // console with the devtools
+                        #[cfg(feature = "devtools")]
+                        Setting::PracticeHotkeys,
+                    ],
+                ),
+            ],
+            SettingsTab::Handicaps => &[
+                (
+                    "settings.group.player_1",
+                    &[
+                        Setting::HeadStart(0),
+                        Setting::HandicapLength(0),
+                        Setting::HandicapSpeed(0),
+                    ],
+                ),
+                (
+                    "settings.group.player_2",
+                    &[
+                        Setting::HeadStart(1),
+                        Setting::HandicapLength(1),
+                        Setting::HandicapSpeed(1),
+                    ],
+                ),
+            ],
+            SettingsTab::Advanced => &[
+                (
+                    "settings.group.simulation",
+                    &[Setting::StepRate, Setting::Substeps],
+                ),
+                ("settings.group.seasons", &[Setting::SeasonLength]),
+            ],
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum Setting {
+    Volume,
+    AnnouncerVolume,
+    Fullscreen,
+    Vsync,
+    FrameCap,
+    LowPower,
+    Resolution,
+    PauseOnFocusLoss,
+    PointsToWin,
+    GameSpeed,
+    BallSpeed,
+    RacketSpeed,
+    Ball,
+    ArenaSize,
+    BestOf,
+    GoldenPoint,
+    GravityStrength,
+    WallReach,
+    WallPeriod,
+    ArcadeLives,
+    DoublesTurns,
+    CpuOpponent,
+    CpuCatchUp,
+    IdleTakeover,
+    Coach,
+    /// Of the player at this index of [`Settings::handicaps`]
+    HeadStart(usize),
+    HandicapLength(usize),
+    HandicapSpeed(usize),
+    Mouse,
+    /// For the player at this index of [`Settings::rumble`]
+    Rumble(usize),
+    MachineInterval,
+    MachineSpeed,
+    MachineSpread,
+    PracticeScore,
+    GhostPath,
+    #[cfg_attr(not(feature = "devtools"), allow(dead_code))]
+    PracticeHotkeys,
+    Theme,
+    Effects,
+    ScreenShake,
+    BallTrail,
+    FollowCamera,
+    BallCam,
+    WinMeter,
+    CrtFilter,
+    ReducedMotion,
+    Palette,
+    HighContrast,
+    Announcer,
+    BallSound,
+    Language,
+    ShareResults,
+    StepRate,
+    Substeps,
+    SeasonLength,
+}
+
+impl Setting {
+    /// Key of its line in the [`Strings`], with a `{value}` for what it's set
+    /// to.
+    fn key(self) -> &'static str {
+        match self {
+            Setting::Volume => "settings.volume",
+            Setting::AnnouncerVolume => "settings.announcer_volume",
+            Setting::Fullscreen => "settings.fullscreen",
+            Setting::Vsync => "settings.vsync",
+            Setting::FrameCap => "settings.frame_cap",
+            Setting::LowPower => "settings.low_power",
+            Setting::Resolution => "settings.resolution",
+            Setting::PauseOnFocusLoss => "settings.lose_focus",
+            Setting::PointsToWin => "settings.points_to_win",
+            Setting::GameSpeed => "settings.game_speed",
+            Setting::BallSpeed => "settings.ball_speed",
+            Setting::RacketSpeed => "settings.racket_speed",
+            Setting::Ball => "settings.ball",
+            Setting::ArenaSize => "settings.arena_size",
+            Setting::BestOf => "settings.best_of",
+            Setting::GoldenPoint => "settings.golden_point",
+            Setting::GravityStrength => "settings.gravity",
+            Setting::WallReach => "settings.wall_reach",
+            Setting::WallPeriod => "settings.wall_period",
+            Setting::ArcadeLives => "settings.arcade_lives",
+            Setting::DoublesTurns => "settings.doubles",
+            Setting::CpuOpponent => "settings.opponent",
+            Setting::CpuCatchUp => "settings.catch_up",
+            Setting::IdleTakeover => "settings.idle_takeover",
+            Setting::Coach => "settings.coach",
+            Setting::HeadStart(_) => "settings.head_start",
+            Setting::HandicapLength(_) => "settings.racket_length",
+            Setting::HandicapSpeed(_) => "settings.racket_speed",
+            Setting::Mouse => "settings.mouse",
+            Setting::Rumble(0) => "settings.rumble.player_1",
+            Setting::Rumble(_) => "settings.rumble.player_2",
+            Setting::MachineInterval => "settings.machine_interval",
+            Setting::MachineSpeed => "settings.machine_speed",
+            Setting::MachineSpread => "settings.angles",
+            Setting::PracticeScore => "settings.practice_score",
+            Setting::GhostPath => "settings.ghost_path",
+            Setting::PracticeHotkeys => "settings.practice_hotkeys",
+            Setting::Theme => "settings.theme",
+            Setting::Effects => "settings.effects",
+            Setting::ScreenShake => "settings.screen_shake",
+            Setting::BallTrail => "settings.ball_trail",
+            Setting::FollowCamera => "settings.camera",
+            Setting::BallCam => "settings.ball_cam",
+            Setting::WinMeter => "settings.win_meter",
+            Setting::CrtFilter => "settings.crt_filter",
+            Setting::ReducedMotion => "settings.reduced_motion",
+            Setting::Palette => "settings.palette",
+            Setting::HighContrast => "settings.high_contrast",
+            Setting::Announcer => "settings.voice",
+            Setting::BallSound => "settings.ball_sound",
+            Setting::Language => "settings.language",
+            Setting::ShareResults => "settings.share_results",
+            Setting::StepRate => "settings.step_rate",
+            Setting::Substeps => "settings.substeps",
+            Setting::SeasonLength => "settings.season_length",
+        }
+    }
+}
+
+#[derive(Component)]
+struct BackButton;
+
+fn setting_label(
+    setting: Setting,
+    settings: &Settings,
+    bots: &BotRegistry,
+    strings: &Strings,
+) -> String {
+    // Each setting's line has its `{value}` filled in with what it's set to
+    let percent = |fraction: f32| format!("{:.0}%", fraction * 100.0);
+    let value: String = match setting {
+        Setting::Volume => percent(settings.volume),
+        Setting::AnnouncerVolume => percent(settings.announcer_volume),
+        Setting::Fullscreen => strings.on_off(settings.fullscreen).to_string(),
+        Setting::Vsync => strings.get(settings.vsync_mode.name_key()).to_string(),
+        Setting::FrameCap => match settings.frame_cap {
+            Some(fps) => strings.fill("settings.frame_cap.fps", &[("fps", &fps)]),
+            None => strings.on_off(false).to_string(),
+        },
+        Setting::LowPower => strings.on_off(settings.low_power).to_string(),
+        Setting::Resolution => {
+            let (width, height) = settings.resolution;
+            format!("{}x{}", width, height)
+        }
+        Setting::PauseOnFocusLoss => strings
+            .get(if settings.pause_on_focus_loss {
+                "settings.lose_focus.pause"
+            } else {
+                "settings.lose_focus.play_on"
+            })
+            .to_string(),
+        Setting::PointsToWin => settings.points_to_win.to_string(),
+        Setting::GameSpeed => percent(settings.game_speed),
+        Setting::BallSpeed => percent(settings.ball_speed),
+        Setting::RacketSpeed => percent(settings.racket_speed),
+        Setting::Ball => strings.get(settings.ball.name_key()).to_string(),
+        Setting::ArenaSize => strings.get(settings.arena_size.name_key()).to_string(),
+        Setting::BestOf => settings.best_of.to_string(),
+        Setting::GoldenPoint => match settings.golden_point {
+            Some(at) => strings.fill("settings.golden_point.at", &[("points", &at)]),
+            None => strings.on_off(false).to_string(),
+        },
+        Setting::GravityStrength => percent(settings.gravity_strength),
+        Setting::WallReach => percent(settings.wall_reach),
+        Setting::WallPeriod => format!("{:.0}", settings.wall_period),
+        Setting::ArcadeLives => strings.count("settings.lives", settings.arcade_lives as u32),
+        Setting::DoublesTurns => strings
+            .get(if settings.doubles_turns {
+                "settings.doubles.turns"
+            } else {
+                "settings.doubles.free_for_all"
+            })
+            .to_string(),
+        Setting::CpuOpponent => match &settings.cpu_opponent {
+            Some(name) if bots.contains(name) => {
+                strings.fill("settings.opponent.bot", &[("name", name)])
+            }
+            Some(name) => strings.fill("settings.opponent.cpu", &[("name", &cpu_name(name))]),
+            None => strings.get("settings.opponent.human").to_string(),
+        },
+        Setting::CpuCatchUp => {
+            if settings.cpu_catch_up > 0.0 {
+                percent(settings.cpu_catch_up)
+            } else {
+                strings.on_off(false).to_string()
+            }
+        }
+        Setting::IdleTakeover => match settings.idle_takeover {
+            Some(after) => strings.fill("settings.idle_takeover.after", &[("seconds", &after)]),
+            None => strings.on_off(false).to_string(),
+        },
+        Setting::Coach => strings.on_off(settings.coach).to_string(),
+        Setting::HeadStart(player) => match settings.handicaps[player].head_start {
+            0 => strings.get("settings.head_start.none").to_string(),
+            points => strings.count("settings.points", points as u32),
+        },
+        Setting::HandicapLength(player) => percent(settings.handicaps[player].racket_length),
+        Setting::HandicapSpeed(player) => percent(settings.handicaps[player].racket_speed),
+        Setting::Mouse => match settings.mouse_player {
+            Some(player_number) => strings.fill("player", &[("number", &player_number)]),
+            None => strings.on_off(false).to_string(),
+        },
+        Setting::Rumble(player) => {
+            if settings.rumble[player] > 0.0 {
+                percent(settings.rumble[player])
+            } else {
+                strings.on_off(false).to_string()
+            }
+        }
+        Setting::MachineInterval => format!("{:.1}", settings.machine_interval),
+        Setting::MachineSpeed => percent(settings.machine_speed),
+        Setting::MachineSpread => {
+            if settings.machine_spread > 0.0 {
+                strings.fill(
+                    "settings.angles.up_to",
+                    &[("degrees", &format!("{:.0}", settings.machine_spread))],
+                )
+            } else {
+                strings.get("settings.angles.straight").to_string()
+            }
+        }
+        Setting::PracticeScore => strings
+            .get(if settings.practice_score {
+                "settings.practice_score.returns"
+            } else {
+                "practice.free_play"
+            })
+            .to_string(),
+        Setting::GhostPath => strings.on_off(settings.ghost_path).to_string(),
+        Setting::PracticeHotkeys => strings.on_off(settings.practice_hotkeys).to_string(),
+        Setting::Theme => capitalized(&settings.theme),
+        Setting::Effects => strings
+            .get(if settings.reduced_effects {
+                "settings.effects.reduced"
+            } else {
+                "settings.effects.full"
+            })
+            .to_string(),
+        Setting::ScreenShake => percent(settings.screen_shake),
+        Setting::BallTrail => strings.on_off(settings.ball_trail).to_string(),
+        Setting::FollowCamera => strings
+            .get(if settings.follow_camera {
+                "settings.camera.follows"
+            } else {
+                "settings.camera.fixed"
+            })
+            .to_string(),
+        Setting::BallCam => strings.on_off(settings.ball_cam).to_string(),
+        Setting::WinMeter => strings.on_off(settings.win_meter).to_string(),
+        Setting::CrtFilter => strings.on_off(settings.crt_filter).to_string(),
+        Setting::ReducedMotion => strings.on_off(settings.reduced_motion).to_string(),
+        Setting::Palette => strings.get(settings.palette.name_key()).to_string(),
+        Setting::HighContrast => strings.on_off(settings.high_contrast).to_string(),
+        Setting::Announcer => match &settings.announcer {
+            Some(name) => capitalized(name),
+            None => strings.on_off(false).to_string(),
+        },
+        Setting::BallSound => strings.on_off(settings.ball_sound).to_string(),
+        Setting::Language => strings.language().to_string(),
+        Setting::ShareResults => strings.on_off(settings.share_results).to_string(),
+        Setting::StepRate => settings.time_step.rate.to_string(),
+        Setting::Substeps => match settings.time_step.substeps {
+            1 => strings.on_off(false).to_string(),
+            substeps => substeps.to_string(),
+        },
+        Setting::SeasonLength => strings.count("settings.days", settings.season_days),
+    };
+    strings.fill(setting.key(), &[("value", &value)])
+}
+
+/// `name` with its first letter made a capital, for the names of files.
+fn capitalized(name: &str) -> String {
+    let mut name = name.to_string();
+    if let Some(first) = name.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    name
+}
+
+fn spawn_settings_menu(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    settings: Res<Settings>,
+    bots: Res<BotRegistry>,
+    strings: Res<Strings>,
+    tab: Res<SettingsTab>,
+) {
+    spawn_settings_screen(&mut commands, &font, &settings, &bots, &strings, *tab);
+}
+
+fn spawn_settings_screen(
+    commands: &mut Commands,
+    font: &UiFont,
+    settings: &Settings,
+    bots: &BotRegistry,
+    strings: &Strings,
+    tab: SettingsTab,
+) {
+    commands
+        .spawn((
+            screen_root(),
+            OnSettingsMenu,
+            StateScoped(AppState::Settings),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                text(font, strings.get("settings.title"), 60.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+
+            // Tabs can be clicked, but aren't in the keyboard's way up and
+            // down the settings
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for each in SettingsTab::all() {
+                        let color = if each == tab {
+                            ACCENT_COLOR
+                        } else {
+                            DIM_TEXT_COLOR
+                        };
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::all(Val::Px(8.0)),
+                                        margin: UiRect::new(
+                                            Val::Px(16.0),
+                                            Val::Px(16.0),
+                                            Val::Px(0.0),
+                                            Val::Px(0.0),
+                                        ),
+                                        ..default()
+                                    },
+                                    background_color: Color::NONE.into(),
+                                    ..default()
+                                },
+                                each,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(text(
+                                    font,
+                                    strings.get(each.title_key()),
+                                    32.0,
+                                    color,
+                                ));
+                            });
+                    }
+                });
+
+            let mut index = 0;
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexStart,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (title, items) in tab.groups() {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    align_items: AlignItems::Center,
+                                    margin: UiRect::all(Val::Px(10.0)),
+                                    ..default()
+                                },
+                                background_color: Color::NONE.into(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(text(font, strings.get(title), 24.0, DIM_TEXT_COLOR));
+                                for setting in items.iter() {
+                                    spawn_button(
+                                        parent,
+                                        font,
+                                        index,
+                                        &setting_label(*setting, settings, bots, strings),
+                                    )
+                                    .insert(*setting);
+                                    index += 1;
+                                }
+                            });
+                    }
+                });
+            spawn_button(parent, font, index, strings.get("back")).insert(BackButton);
+
+            parent.spawn(
+                text(font, strings.get("settings.hint"), 18.0, DIM_TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+        });
+}
+
+/// Turns to another page with Tab, or Shift+Tab for the one before, or with
+/// a click on its tab.
+fn switch_tab(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    clicked: Query<(&SettingsTab, &Interaction), Changed<Interaction>>,
+    screens: Query<Entity, With<OnSettingsMenu>>,
+    mut tab: ResMut<SettingsTab>,
+    font: Res<UiFont>,
+    settings: Res<Settings>,
+    bots: Res<BotRegistry>,
+    strings: Res<Strings>,
+) {
+    let tabs = SettingsTab::all();
+    let current = tabs.iter().position(|each| *each == *tab).unwrap_or(0);
+    let next = if keyboard_input.just_pressed(KeyCode::Tab) {
+        let back = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let step = if back { tabs.len() - 1 } else { 1 };
+        Some(tabs[(current + step) % tabs.len()])
+    } else {
+        clicked
+            .iter()
+            .find(|(_, interaction)| **interaction == Interaction::Pressed)
+            .map(|(each, _)| *each)
+    };
+
+    match next {
+        Some(next) if next != tabs[current] => *tab = next,
+        _ => return,
+    }
+    for screen in screens.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+    spawn_settings_screen(&mut commands, &font, &settings, &bots, &strings, *tab);
+}
+
+fn change_setting(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    focus: Res<MenuFocus>,
+    mut activated: EventReader<ButtonActivated>,
+    buttons: Query<(Entity, &MenuButton, &Setting)>,
+    mut settings: ResMut<Settings>,
+    personalities: Res<Personalities>,
+    asset_server: Res<AssetServer>,
+    folders: Res<Assets<LoadedFolder>>,
+    bots: Res<BotRegistry>,
+    locales: Res<Locales>,
+    mods: Res<Mods>,
+) {
+    let step: f32 = if keyboard_input.just_pressed(KeyCode::ArrowLeft)
+        || keyboard_input.just_pressed(KeyCode::KeyA)
+    {
+        -1.0
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight)
+        || keyboard_input.just_pressed(KeyCode::KeyD)
+    {
+        1.0
+    } else {
+        0.0
+    };
+    let focused = buttons
+        .iter()
+        .find(|(_, button, _)| button.0 == focus.0)
+        .map(|(_, _, setting)| (*setting, step))
+        .filter(|_| step != 0.0);
+    // Choosing a setting steps it forward, wrapping around
+    let chosen = activated
+        .read()
+        .filter_map(|event| buttons.get(event.0).ok())
+        .map(|(_, _, setting)| (*setting, 0.0))
+        .last();
+
+    match focused.or(chosen) {
+        Some((Setting::Volume, step)) => {
+            settings.volume = nudge(settings.volume, step, VOLUME_STEP);
+        }
+        Some((Setting::AnnouncerVolume, step)) => {
+            settings.announcer_volume = nudge(settings.announcer_volume, step, VOLUME_STEP);
+        }
+        Some((Setting::Fullscreen, _)) => settings.fullscreen = !settings.fullscreen,
+        Some((Setting::Vsync, step)) => {
+            settings.vsync_mode = cycle(&VSYNC_MODES, settings.vsync_mode, step);
+        }
+        Some((Setting::FrameCap, step)) => {
+            settings.frame_cap = cycle(&FRAME_CAPS, settings.frame_cap, step);
+        }
+        Some((Setting::LowPower, _)) => settings.low_power = !settings.low_power,
+        Some((Setting::PauseOnFocusLoss, _)) => {
+            settings.pause_on_focus_loss = !settings.pause_on_focus_loss;
+        }
+        Some((Setting::Resolution, step)) => {
+            settings.resolution = cycle(&RESOLUTIONS, settings.resolution, step);
+        }
+        Some((Setting::PointsToWin, step)) => {
+            settings.points_to_win = cycle(&POINTS_TO_WIN, settings.points_to_win, step);
+        }
+        Some((Setting::GameSpeed, step)) => {
+            settings.game_speed = cycle(&GAME_SPEEDS, settings.game_speed, step);
+        }
+        Some((Setting::BallSpeed, step)) => {
+            settings.ball_speed = cycle(&BALL_SPEEDS, settings.ball_speed, step);
+        }
+        Some((Setting::RacketSpeed, step)) => {
+            settings.racket_speed = cycle(&RACKET_SPEEDS, settings.racket_speed, step);
+        }
+        Some((Setting::Ball, step)) => settings.ball = cycle(&BALL_KINDS, settings.ball, step),
+        Some((Setting::ArenaSize, step)) => {
+            settings.arena_size = cycle(&ARENA_SIZES, settings.arena_size, step);
+        }
+        Some((Setting::BestOf, step)) => {
+            settings.best_of = cycle(&BEST_OF, settings.best_of, step);
+        }
+        Some((Setting::GoldenPoint, step)) => {
+            settings.golden_point = cycle(&GOLDEN_POINTS, settings.golden_point, step);
+        }
+        Some((Setting::GravityStrength, step)) => {
+            settings.gravity_strength = cycle(&GRAVITY_STRENGTHS, settings.gravity_strength, step);
+        }
+        Some((Setting::WallReach, step)) => {
+            settings.wall_reach = cycle(&WALL_REACHES, settings.wall_reach, step);
+        }
+        Some((Setting::WallPeriod, step)) => {
+            settings.wall_period = cycle(&WALL_PERIODS, settings.wall_period, step);
+        }
+        Some((Setting::ArcadeLives, step)) => {
+            settings.arcade_lives = cycle(&LIVES, settings.arcade_lives, step);
+        }
+        Some((Setting::MachineInterval, step)) => {
+            settings.machine_interval = cycle(&MACHINE_INTERVALS, settings.machine_interval, step);
+        }
+        Some((Setting::MachineSpeed, step)) => {
+            settings.machine_speed = cycle(&MACHINE_SPEEDS, settings.machine_speed, step);
+        }
+        Some((Setting::MachineSpread, step)) => {
+            settings.machine_spread = cycle(&MACHINE_SPREADS, settings.machine_spread, step);
+        }
+        Some((Setting::DoublesTurns, _)) => settings.doubles_turns = !settings.doubles_turns,
+        Some((Setting::CpuOpponent, step)) => {
+            let mut names = personalities.names(&asset_server, &folders);
+            names.extend(bots.names().map(str::to_string));
+            let mut choices = vec![None];
+            choices.extend(names.iter().map(|name| Some(name.as_str())));
+            settings.cpu_opponent =
+                cycle(&choices, settings.cpu_opponent.as_deref(), step).map(str::to_string);
+        }
+        Some((Setting::CpuCatchUp, step)) => {
+            settings.cpu_catch_up = cycle(&CATCH_UPS, settings.cpu_catch_up, step);
+        }
+        Some((Setting::IdleTakeover, step)) => {
+            settings.idle_takeover = cycle(&IDLE_TAKEOVERS, settings.idle_takeover, step);
+        }
+        Some((Setting::Coach, _)) => settings.coach = !settings.coach,
+        Some((Setting::HeadStart(player), step)) => {
+            let handicap = &mut settings.handicaps[player];
+            handicap.head_start = cycle(&HEAD_STARTS, handicap.head_start, step);
+        }
+        Some((Setting::HandicapLength(player), step)) => settings.cycle_racket_length(player, step),
+        Some((Setting::HandicapSpeed(player), step)) => settings.cycle_racket_speed(player, step),
+        Some((Setting::Mouse, step)) => {
+            settings.mouse_player = cycle(&MOUSE_PLAYERS, settings.mouse_player, step);
+        }
+        Some((Setting::Rumble(player), step)) => {
+            settings.rumble[player] = cycle(&RUMBLE_STRENGTHS, settings.rumble[player], step);
+        }
+        Some((Setting::PracticeScore, _)) => settings.practice_score = !settings.practice_score,
+        Some((Setting::GhostPath, _)) => settings.ghost_path = !settings.ghost_path,
+        Some((Setting::PracticeHotkeys, _)) => {
+            settings.practice_hotkeys = !settings.practice_hotkeys;
+        }
+        Some((Setting::Theme, step)) => {
+            let mut choices = THEMES.to_vec();
+            choices.extend(mods.names(Content::Theme));
+            settings.theme = cycle(&choices, settings.theme.as_str(), step).to_string();
+        }
+        Some((Setting::Effects, _)) => settings.reduced_effects = !settings.reduced_effects,
+        Some((Setting::BallTrail, _)) => settings.ball_trail = !settings.ball_trail,
+        Some((Setting::FollowCamera, _)) => settings.follow_camera = !settings.follow_camera,
+        Some((Setting::BallCam, _)) => settings.ball_cam = !settings.ball_cam,
+        Some((Setting::WinMeter, _)) => settings.win_meter = !settings.win_meter,
+        Some((Setting::CrtFilter, _)) => settings.crt_filter = !settings.crt_filter,
+        Some((Setting::ReducedMotion, _)) => settings.reduced_motion = !settings.reduced_motion,
+        Some((Setting::Palette, step)) => {
+            settings.palette = cycle(&PALETTES, settings.palette, step);
+        }
+        Some((Setting::HighContrast, _)) => settings.high_contrast = !settings.high_contrast,
+        Some((Setting::Announcer, step)) => {
+            // Or none at all, first
+            let mut choices = vec![None];
+            choices.extend(
+                VOICE_PACKS
+                    .into_iter()
+                    .chain(mods.names(Content::VoicePack))
+                    .map(Some),
+            );
+            settings.announcer =
+                cycle(&choices, settings.announcer.as_deref(), step).map(str::to_string);
+        }
+        Some((Setting::BallSound, _)) => settings.ball_sound = !settings.ball_sound,
+        Some((Setting::ShareResults, _)) => settings.share_results = !settings.share_results,
+        Some((Setting::StepRate, step)) => {
+            let time_step = &mut settings.time_step;
+            time_step.rate = cycle(&STEP_RATES, time_step.rate, step);
+        }
+        Some((Setting::Substeps, step)) => {
+            let time_step = &mut settings.time_step;
+            time_step.substeps = cycle(&SUBSTEPS, time_step.substeps, step);
+        }
+        Some((Setting::SeasonLength, step)) => {
+            settings.season_days = cycle(&SEASON_LENGTHS, settings.season_days, step);
+        }
+        Some((Setting::Language, step)) => {
+            let languages = locales.languages(&asset_server, &folders);
+            let choices: Vec<&str> = languages.iter().map(String::as_str).collect();
+            if !choices.is_empty() {
+                settings.language = cycle(&choices, settings.language.as_str(), step).to_string();
+            }
+        }
+        Some((Setting::ScreenShake, step)) => {
+            settings.screen_shake = nudge(settings.screen_shake, step, SCREEN_SHAKE_STEP);
+        }
+        None => {}
+    }
+}
+
+/// The choice after `current`, or before it for a negative `step`, wrapping
+/// around. Anything that isn't one of the choices is treated as the first.
+pub fn cycle<T: Copy + PartialEq>(choices: &[T], current: T, step: f32) -> T {
+    let index = choices
+        .iter()
+        .position(|choice| *choice == current)
+        .unwrap_or(0);
+    let next = if step < 0.0 {
+        index + choices.len() - 1
+    } else {
+        index + 1
+    };
+    choices[next % choices.len()]
+}
+
+/// A fraction from 0 to 1 moved one `by` up or down for a `step` either way,
+/// stopping at the ends. A `step` of 0 moves it up, round to 0 after 1.
+fn nudge(current: f32, step: f32, by: f32) -> f32 {
+    // Counted in whole steps, or tenths would add up to just over 1
+    let steps = (1.0 / by).round();
+    let at = (current / by).round();
+    let next = if step == 0.0 {
+        (at + 1.0) % (steps + 1.0)
+    } else {
+        (at + step).clamp(0.0, steps)
+    };
+    next / steps
+}
+
+fn update_setting_labels(
+    settings: Res<Settings>,
+    bots: Res<BotRegistry>,
+    strings: Res<Strings>,
+    buttons: Query<(&Setting, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (setting, children) in buttons.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = setting_label(*setting, &settings, &bots, &strings);
+            }
+        }
+    }
+}
+
+fn settings_action(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    back_buttons: Query<(), With<BackButton>>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let back = activated
+        .read()
+        .any(|event| back_buttons.get(event.0).is_ok())
+        || keyboard_input.just_pressed(KeyCode::Escape);
+    if back {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Menu);
+    }
+}
+
+/// Writes the settings out whenever they change, wherever that's from.
+fn save_settings(settings: Res<Settings>) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+    if let Err(error) = settings.save() {
+        warn!("Couldn't save settings: {}", error);
+    }
+}
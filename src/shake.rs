@@ -0,0 +1,104 @@
+//! Screen shake on hard hits and goals.
+
+use bevy::prelude::*;
+
+use crate::{
+    settings::Settings,
+    sim::{CollisionEvent, GoalEvent, SimRng},
+    stats, AppState, MainCamera, MatchState,
+};
+
+// Bounces faster than this shake the screen, harder the faster they are
+const HARD_HIT_SPEED: f32 = 250.0;
+// How much faster than HARD_HIT_SPEED a bounce has to be for the strongest shake
+const HARD_HIT_RANGE: f32 = 500.0;
+const HARD_HIT_TRAUMA: f32 = 0.4;
+const GOAL_TRAUMA: f32 = 0.7;
+// Trauma lost per second
+const TRAUMA_DECAY: f32 = 1.5;
+// How far the camera moves at full trauma and intensity, in world units
+const MAX_OFFSET: f32 = 24.0;
+
+pub struct ShakePlugin;
+
+impl Plugin for ShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScreenShake>()
+            .insert_resource(ShakeRng(SimRng::new(stats::now())))
+            .add_systems(
+                Update,
+                (shake_on_hits, shake_camera.after(shake_on_hits))
+                    .run_if(in_state(MatchState::Running)),
+            )
+            .add_systems(OnExit(AppState::Playing), stop_shaking);
+    }
+}
+
+/// How shaken up the camera is. Trauma goes from 0 to 1 and the offset grows
+/// with its square, so small knocks stay subtle while big ones add up.
+#[derive(Resource, Default)]
+pub struct ScreenShake {
+    pub trauma: f32,
+    /// What's currently added to the camera's position
+    offset: Vec2,
+}
+
+impl ScreenShake {
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+}
+
+#[derive(Resource)]
+struct ShakeRng(SimRng);
+
+fn shake_on_hits(
+    mut collisions: EventReader<CollisionEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut shake: ResMut<ScreenShake>,
+) {
+    for collision in collisions.read() {
+        let hardness = (collision.velocity.length() - HARD_HIT_SPEED) / HARD_HIT_RANGE;
+        if hardness > 0.0 {
+            shake.add_trauma(hardness.min(1.0) * HARD_HIT_TRAUMA);
+        }
+    }
+    for _ in goals.read() {
+        shake.add_trauma(GOAL_TRAUMA);
+    }
+}
+
+fn shake_camera(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut shake: ResMut<ScreenShake>,
+    mut rng: ResMut<ShakeRng>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    if shake.trauma == 0.0 && shake.offset == Vec2::ZERO {
+        return;
+    }
+
+    let screen_shake = if settings.reduced_motion {
+        0.0
+    } else {
+        settings.screen_shake
+    };
+    let strength = shake.trauma * shake.trauma * screen_shake * MAX_OFFSET;
+    let offset = Vec2::new(rng.0.range(-1.0, 1.0), rng.0.range(-1.0, 1.0)) * strength;
+    for mut transform in cameras.iter_mut() {
+        transform.translation += (offset - shake.offset).extend(0.0);
+    }
+    shake.offset = offset;
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.0);
+}
+
+fn stop_shaking(
+    mut shake: ResMut<ScreenShake>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    for mut transform in cameras.iter_mut() {
+        transform.translation -= shake.offset.extend(0.0);
+    }
+    *shake = ScreenShake::default();
+}
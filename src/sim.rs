@@ -0,0 +1,3996 @@
+//! The match simulation: ball and racket physics, goals and scoring.
+//!
+//! Everything in here advances in fixed steps, as long as the match's
+//! [`TimeStep`] says, and reads nothing but [`SimInput`] and [`SimRng`], so
+//! feeding two worlds the same seed and the same inputs plays out exactly the
+//! same match. Keep it that way: no `Time`, no keyboard, no ad-hoc
+//! randomness, no step length but the match's, and an explicit order between
+//! systems.
+
+use std::{cmp::Ordering, f32::consts::PI, time::Duration};
+
+use bevy::{
+    app::{MainScheduleOrder, RunFixedMainLoop},
+    ecs::{
+        schedule::{ScheduleBuildSettings, ScheduleLabel, SystemConfigs},
+        system::EntityCommands,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::{ArenaBounds, ArenaSize},
+    seams::Rng,
+    theme::ThemePart,
+};
+
+/// The rates the simulation can step at, in steps a second, the first being
+/// the default, see [`TimeStep`].
+pub const STEP_RATES: [u32; 3] = [60, 120, 240];
+/// The most pieces each step's movement of the ball can be cut into.
+pub const MAX_SUBSTEPS: u32 = 8;
+/// How fast rackets move by default, see [`GameplayConfig::racket_speed`].
+pub const RACKET_SPEED: f32 = 120.0;
+// How far a racket's face can tilt up or down, and how fast it gets there
+const MAX_TILT: f32 = 30.0 * PI / 180.0;
+const TILT_SPEED: f32 = 120.0 * PI / 180.0;
+
+pub const RACKET_THICCNESS: f32 = 40.0;
+pub const RACKET_WALL_OFFSET: f32 = 20.0;
+pub const RACKET_LENGTH: f32 = 120.0;
+/// The least and most a handicap can make a racket's length or speed, as a
+/// multiple of what it'd be without one, whatever the settings file says.
+const HANDICAP_LIMITS: (f32, f32) = (0.25, 2.0);
+// With rackets shrinking, each return takes this fraction off the racket's
+// length, down to a shortest of this
+const RACKET_SHRINK: f32 = 0.1;
+const MIN_RACKET_LENGTH: f32 = 50.0;
+
+const BALL_SIZE: Vec3 = Vec3::new(30.0, 30.0, 0.0);
+const BALL_STARTING_POSITION: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+/// How fast serves go by default, see [`GameplayConfig::ball_speed`].
+pub const BALL_SPEED: f32 = 300.0;
+// The serve of a golden point is faster, and always at the steepest angle
+const GOLDEN_SERVE_SPEEDUP: f32 = 1.3;
+
+/// How hard gravity pulls the ball down the court at full strength, in pixels
+/// a second squared. See [`MatchRules::gravity`].
+pub const GRAVITY: f32 = 150.0;
+// With gravity, serves are lobbed up the court at somewhere between these angles
+const MIN_LOB_ANGLE: f32 = 35.0 * PI / 180.0;
+const MAX_LOB_ANGLE: f32 = 55.0 * PI / 180.0;
+// How steeply the ball leaves a racket when hit with its very tip
+const MAX_BOUNCE_ANGLE: f32 = 60.0 * PI / 180.0;
+
+// Seconds of holding smash for a full charge
+const SMASH_CHARGE_TIME: f32 = 1.0;
+// Seconds a released smash waits for the ball, before it's wasted
+const SMASH_WINDOW: f32 = 0.25;
+// Seconds after a smash before the racket can charge another
+const SMASH_COOLDOWN: f32 = 3.0;
+// A full smash speeds the ball up by this fraction, and takes this fraction off its angle
+const SMASH_SPEEDUP: f32 = 0.6;
+const SMASH_FLATTENING: f32 = 0.6;
+/// Points in a row a player has to win for their rackets' momentum to be
+/// full, and their next smash a full one however long it was charged.
+pub const MAX_MOMENTUM: u32 = 3;
+
+// Seconds a dash lasts, and how much faster the racket moves during it
+const DASH_TIME: f32 = 0.15;
+const DASH_SPEEDUP: f32 = 3.0;
+// Seconds after a dash before the racket can dash again
+const DASH_COOLDOWN: f32 = 1.5;
+
+// A racket brushing the ball as it hits sets it spinning, with this fraction
+// of the spin it would take for the ball to roll along the racket's face
+const SPIN_TRANSFER: f32 = 0.5;
+// How sharply a spinning ball curves: its heading turns by this many radians a
+// second for every radian a second of spin
+const MAGNUS_EFFECT: f32 = 0.12;
+// Fraction of the ball's spin kept each step, at the default rate
+const SPIN_DECAY: f32 = 0.99;
+
+// The strongest the wind blows, across and along the court, in pixels a
+// second squared. It swings between either way over about this many seconds
+const WIND_STRENGTH: Vec2 = Vec2::new(50.0, 35.0);
+const WIND_PERIOD: Vec2 = Vec2::new(20.0, 12.0);
+
+/// How close the ball has to come to the middle of a portal to go through.
+pub const PORTAL_RADIUS: f32 = 30.0;
+// Portals take this many seconds to open, stay open this long, and close again
+// as quickly, before opening somewhere else
+const PORTAL_OPENING: f32 = 0.5;
+const PORTAL_LIFETIME: f32 = 10.0;
+// A pair of portals turns the ball by up to this much, one way going in at
+// one end and the other way going in at the other
+const MAX_PORTAL_TURN: f32 = 30.0 * PI / 180.0;
+// Seconds after coming out of a portal before the ball can go in again, long
+// enough for it to clear the one it came out of
+const PORTAL_COOLDOWN: f32 = 0.5;
+
+/// Size of the wall going up and down the middle of the court with
+/// [`MatchRules::moving_wall`].
+pub const MOVING_WALL_SIZE: Vec3 = Vec3::new(20.0, 120.0, 0.0);
+
+/// How high the net across the middle of the court with
+/// [`MatchRules::center_net`] stands off the bottom with gravity, and how
+/// thick it is.
+pub const CENTER_NET_HEIGHT: f32 = 60.0;
+pub const CENTER_NET_THICKNESS: f32 = 8.0;
+// Spin below which a ball going over the center net without gravity is a
+// flat shot, and brushes it
+const FLAT_SPIN: f32 = 1.0;
+// Most a flat shot's turned by brushing the center net, either way
+const MAX_NET_DEFLECTION: f32 = 4.0 * PI / 180.0;
+// Fraction of its speed a flat shot keeps brushing the center net
+const NET_BRUSH_SPEED: f32 = 0.95;
+
+/// How long a scored ball rests in the net before the next serve, in seconds
+/// at the usual game speed.
+pub const NET_SETTLE_TIME: f32 = 1.0;
+// Fraction of the ball's velocity kept each step at the default rate while
+// it's in the net
+const NET_DRAG: f32 = 0.9;
+// Fraction of the ball's speed kept when it bounces off the back of the net
+const NET_RESTITUTION: f32 = 0.3;
+
+/// Steps the simulation in [`FixedUpdate`], once for every step of the
+/// match's [`TimeStep`] that's gone by at the [`GameSpeed`], however fast the
+/// frames come. The game and the server both step the simulation in it, so
+/// how the steps are timed is only decided here.
+pub struct FixedStepPlugin;
+
+impl Plugin for FixedStepPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSpeed>()
+            .add_systems(PreUpdate, time_steps);
+        // Right after `Update` rather than before it, so the steps of a frame
+        // play what was pressed in it
+        let mut order = app.world_mut().resource_mut::<MainScheduleOrder>();
+        order
+            .labels
+            .retain(|label| *label != RunFixedMainLoop.intern());
+        order.insert_after(Update, RunFixedMainLoop);
+        defer_commands(app, FixedUpdate);
+    }
+}
+
+/// Leaves everything the systems in `schedule` queue up to be done at the end
+/// of it, rather than between systems that run one after the other. The
+/// simulation's systems only see what's spawned or marked in a step from the
+/// next one on, and recorded matches play back the same for it.
+pub fn defer_commands(app: &mut App, schedule: impl ScheduleLabel) {
+    app.edit_schedule(schedule, |schedule| {
+        schedule.set_build_settings(ScheduleBuildSettings {
+            auto_insert_apply_deferred: false,
+            ..default()
+        });
+    });
+}
+
+/// How fast the steps come, as a share of real time. Slowing it slows
+/// everything in the match alike, the ball, the rackets and every countdown,
+/// as they all go by steps, and the steps themselves stay as long as ever so
+/// the match plays out just the same.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct GameSpeed(pub f32);
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        GameSpeed(1.0)
+    }
+}
+
+/// Has [`FixedUpdate`] come once for every step of the match at the
+/// [`GameSpeed`], which is as often as [`Time<Fixed>`] is told its steps are.
+/// Nothing in the simulation goes by that time, only by steps.
+fn time_steps(speed: Res<GameSpeed>, rules: Res<MatchRules>, mut time: ResMut<Time<Fixed>>) {
+    // At no speed at all the next step never comes
+    if speed.0 <= 0.0 {
+        return;
+    }
+    let step = Duration::from_secs_f64(rules.time_step.seconds() as f64 / speed.0 as f64);
+    if time.timestep() != step {
+        time.set_timestep(step);
+    }
+}
+
+/// How long the simulation's steps are, and how many pieces the ball's
+/// movement is cut into in each of them. Shorter steps and more pieces make
+/// for a truer match, where the ball can't skip past the corner of a racket
+/// it should have clipped, for machines that can keep up with them. Every
+/// length of time in the simulation is counted in steps, so a match plays
+/// the same at any rate, give or take the precision.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct TimeStep {
+    /// Steps a second, one of the [`STEP_RATES`]
+    pub rate: u32,
+    /// Pieces each step's movement of the ball is cut into, from 1 for it
+    /// moving all at once up to [`MAX_SUBSTEPS`]
+    pub substeps: u32,
+}
+
+impl Default for TimeStep {
+    fn default() -> Self {
+        TimeStep {
+            rate: STEP_RATES[0],
+            substeps: 1,
+        }
+    }
+}
+
+impl TimeStep {
+    /// The time step with a rate there is and a number of substeps that
+    /// isn't too many, whatever the settings file says.
+    pub fn clamped(self) -> Self {
+        TimeStep {
+            rate: if STEP_RATES.contains(&self.rate) {
+                self.rate
+            } else {
+                STEP_RATES[0]
+            },
+            substeps: self.substeps.clamp(1, MAX_SUBSTEPS),
+        }
+    }
+
+    /// How long each step is, in seconds.
+    pub fn seconds(&self) -> f32 {
+        1.0 / self.rate.max(1) as f32
+    }
+
+    /// The number of steps closest to `seconds`.
+    pub fn steps(&self, seconds: f32) -> u32 {
+        (seconds / self.seconds()).round() as u32
+    }
+
+    /// What's kept each step of something that keeps `kept` of itself every
+    /// step at the default rate, so it wears off just as fast at any rate.
+    fn decay(&self, kept: f32) -> f32 {
+        kept.powf(STEP_RATES[0] as f32 * self.seconds())
+    }
+}
+
+/// Set of all of the simulation systems.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimSystems;
+
+/// The simulation systems, in a fixed order, in [`SimSystems`]. Whatever
+/// schedule they're added to wants [`defer_commands`] as well.
+pub fn systems() -> SystemConfigs {
+    (
+        (
+            start_dashes,
+            move_rackets.after(start_dashes),
+            keep_rackets_apart.after(move_rackets),
+            charge_smashes,
+            recover_lost_balls,
+            apply_spin.after(move_rackets).after(recover_lost_balls),
+            blow_wind.after(apply_spin),
+            apply_gravity.after(blow_wind),
+            apply_velocity.after(apply_gravity),
+            hang_center_net,
+            brush_center_net
+                .after(apply_velocity)
+                .after(hang_center_net),
+            use_portals.after(brush_center_net),
+            move_walls.after(apply_velocity),
+        ),
+        (
+            check_for_collisions
+                .after(use_portals)
+                .after(move_walls)
+                .after(hang_center_net)
+                .after(keep_rackets_apart)
+                .after(charge_smashes),
+            call_serves.after(check_for_collisions),
+            check_for_goals.after(call_serves),
+            keep_stats.after(check_for_goals),
+            build_momentum.after(check_for_goals),
+            settle_in_net.after(check_for_goals),
+            swap_sides.after(settle_in_net),
+            run_clock.after(settle_in_net),
+        ),
+    )
+        .in_set(SimSystems)
+}
+
+/// A leg up for one of the players, so they can give a better one a game.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Handicap {
+    /// Points they start every set with
+    pub head_start: i32,
+    /// How long their rackets are, as a multiple of [`RACKET_LENGTH`]
+    pub racket_length: f32,
+    /// How fast their rackets move, as a multiple of their [`RacketSpeed`]
+    pub racket_speed: f32,
+}
+
+impl Handicap {
+    /// The handicap with the racket's length and speed within
+    /// [`HANDICAP_LIMITS`], so neither disappears nor fills the court.
+    pub fn clamped(self) -> Self {
+        let (least, most) = HANDICAP_LIMITS;
+        Handicap {
+            racket_length: self.racket_length.clamp(least, most),
+            racket_speed: self.racket_speed.clamp(least, most),
+            ..self
+        }
+    }
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Handicap {
+            head_start: 0,
+            racket_length: 1.0,
+            racket_speed: 1.0,
+        }
+    }
+}
+
+/// How a match is won: sets played to a number of points, with a lead of
+/// `win_by` needed at the end of each (deuce), and the match going to whoever
+/// takes most of `best_of` sets. Both sides of an online match have to agree
+/// on these, so online matches always use the defaults.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MatchRules {
+    pub points_to_win: i32,
+    pub win_by: i32,
+    pub best_of: i32,
+    /// Sudden death: once a set is tied at this many points each, the next
+    /// point wins it, lead or no lead
+    pub golden_point: Option<i32>,
+    /// Seconds a timed match lasts, see [`MatchClock`]
+    pub time_limit: Option<u32>,
+    /// Whether the ball is blown about by [`Wind`]
+    pub wind: bool,
+    /// How hard the ball falls towards the bottom of the court, in pixels a
+    /// second squared, if at all
+    pub gravity: Option<f32>,
+    /// Whether a pair of [`Portals`] opens on the court
+    pub portals: bool,
+    /// Whether the court's dark but for the light round the ball and on the
+    /// rackets. Only how it's drawn, nothing in the simulation changes
+    #[serde(default)]
+    pub night: bool,
+    /// How a wall goes up and down the middle of the court, if there's one,
+    /// see [`MovingWall`]
+    #[serde(default)]
+    pub moving_wall: Option<WallPath>,
+    /// Whether there's a low net across the middle of the court, see
+    /// [`CenterNet`]
+    #[serde(default)]
+    pub center_net: bool,
+    /// Whether rackets get shorter with every return, back to full length
+    /// once the point's over
+    pub shrinking: bool,
+    /// Whether partners in doubles have to take turns hitting the ball,
+    /// see [`Waiting`]
+    pub alternate_hits: bool,
+    /// Balls each player can miss before they've lost, instead of racing to
+    /// a score, see [`MatchRules::arcade`]
+    pub lives: Option<i32>,
+    /// How fast serves leave the center, as a multiple of
+    /// [`GameplayConfig::ball_speed`]
+    pub ball_speed: f32,
+    /// How fast rackets move, as a multiple of their own [`RacketSpeed`]
+    pub racket_speed: f32,
+    /// How far off straight the face of a racket sends the ball back when
+    /// it's hit right on the end, in radians
+    pub bounce_angle: f32,
+    /// What each player's given to make up for the other being better,
+    /// player 1's first
+    #[serde(default)]
+    pub handicaps: [Handicap; 2],
+    /// What the ball's made of
+    #[serde(default)]
+    pub ball: BallKind,
+    /// Whether serves are called: one that comes back over the center line
+    /// before it's been returned is a fault, and the receiver's point, and
+    /// one that clips the top of an [`Obstacle`] is a let, and served again.
+    /// See [`Served`]
+    #[serde(default)]
+    pub serve_rules: bool,
+    /// How long the match's steps are, which only a local match changes
+    /// from the default
+    #[serde(default)]
+    pub time_step: TimeStep,
+    /// How big the court's made, when the match says, rather than going by
+    /// the settings
+    #[serde(default)]
+    pub arena_size: Option<ArenaSize>,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules {
+            points_to_win: 11,
+            win_by: 2,
+            best_of: 3,
+            golden_point: None,
+            time_limit: None,
+            wind: false,
+            gravity: None,
+            portals: false,
+            night: false,
+            moving_wall: None,
+            center_net: false,
+            shrinking: false,
+            alternate_hits: false,
+            lives: None,
+            ball_speed: 1.0,
+            racket_speed: 1.0,
+            bounce_angle: MAX_BOUNCE_ANGLE,
+            handicaps: default(),
+            ball: BallKind::Standard,
+            serve_rules: false,
+            time_step: TimeStep::default(),
+            arena_size: None,
+        }
+    }
+}
+
+/// The numbers the simulation's tuned with, read from
+/// `assets/gameplay.config.ron` and changed as that's saved, see
+/// [`crate::gameplay`]. Both sides of an online match have to agree on
+/// these too, so online matches always use the defaults.
+#[derive(Asset, Resource, TypePath, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct GameplayConfig {
+    /// How fast rackets move up and down the court, in pixels a second
+    pub racket_speed: f32,
+    /// How fast serves leave the center, in pixels a second
+    pub ball_speed: f32,
+    /// Serves leave the center at up to this angle from the horizontal,
+    /// either way, in degrees
+    pub max_serve_angle: f32,
+    /// Steepest a racket's face sends the ball off, tilt and all, in degrees
+    pub max_shot_angle: f32,
+    /// No smash sends the ball faster than this multiple of `ball_speed`,
+    /// however fast it was already going
+    pub max_smash_speed: f32,
+    /// Fraction of its speed the ball keeps bouncing off the bottom of the
+    /// court with gravity, like off a table
+    pub table_restitution: f32,
+}
+
+impl Default for GameplayConfig {
+    fn default() -> Self {
+        GameplayConfig {
+            racket_speed: RACKET_SPEED,
+            ball_speed: BALL_SPEED,
+            max_serve_angle: 30.0,
+            max_shot_angle: 75.0,
+            max_smash_speed: 2.5,
+            table_restitution: 0.85,
+        }
+    }
+}
+
+impl MatchRules {
+    pub fn handicap(&self, player_number: i32) -> Handicap {
+        self.handicaps[if player_number == 1 { 0 } else { 1 }].clamped()
+    }
+
+    /// As many points as possible before the clock runs out. Nobody wins on
+    /// points alone, so there's only ever the one set.
+    pub fn blitz(seconds: u32) -> Self {
+        MatchRules {
+            points_to_win: i32::MAX,
+            best_of: 1,
+            time_limit: Some(seconds),
+            ..default()
+        }
+    }
+
+    /// A life lost for every ball missed, and the first to lose them all
+    /// loses the match. Every point is a life taken, so it's a single set to
+    /// that many, won by a point.
+    pub fn arcade(lives: i32) -> Self {
+        MatchRules {
+            points_to_win: lives,
+            win_by: 1,
+            best_of: 1,
+            lives: Some(lives),
+            ..default()
+        }
+    }
+
+    /// How many lives the player has left in `score`, when they have lives.
+    pub fn lives_left(&self, score: &Score, player_number: i32) -> Option<i32> {
+        self.lives
+            .map(|lives| (lives - score.points(3 - player_number)).max(0))
+    }
+
+    pub fn sets_to_win(&self) -> i32 {
+        self.best_of.max(1) / 2 + 1
+    }
+
+    /// Whether `score` is a tie that makes the next point golden.
+    fn reaches_golden_point(&self, score: &Score) -> bool {
+        self.golden_point
+            .is_some_and(|at| score.player1 == score.player2 && score.player1 >= at)
+    }
+
+    /// The player who has won the set being played, if anyone has.
+    pub fn set_winner(&self, score: &Score) -> Option<i32> {
+        let lead = score.player1 - score.player2;
+        if score.golden_point && lead != 0 {
+            Some(if lead > 0 { 1 } else { 2 })
+        } else if score.player1 >= self.points_to_win && lead >= self.win_by {
+            Some(1)
+        } else if score.player2 >= self.points_to_win && -lead >= self.win_by {
+            Some(2)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the point that made `score` won the match, along with the set
+    /// it's still to be counted for.
+    pub fn wins_match(&self, score: &Score) -> bool {
+        self.set_winner(score)
+            .is_some_and(|winner| score.sets(winner) + 1 >= self.sets_to_win())
+    }
+
+    /// Whether the next point could win the match, for either player.
+    pub fn is_match_point(&self, score: &Score) -> bool {
+        [1, 2].into_iter().any(|player_number| {
+            let mut next = *score;
+            next.add_point(player_number);
+            self.wins_match(&next)
+        })
+    }
+
+    /// Whether the next point could win the set being played, for either
+    /// player.
+    pub fn is_set_point(&self, score: &Score) -> bool {
+        [1, 2].into_iter().any(|player_number| {
+            let mut next = *score;
+            next.add_point(player_number);
+            self.set_winner(&next).is_some()
+        })
+    }
+
+    /// Whether `score` is a tie that the set has to be won from by more than
+    /// the next point, like 10 all to 11.
+    pub fn is_deuce(&self, score: &Score) -> bool {
+        self.win_by > 1
+            && score.player1 == score.player2
+            && score.player1 >= self.points_to_win - 1
+            && !self.is_set_point(score)
+    }
+
+    /// What's riding on the next point in `score`, if anything much.
+    pub fn stakes(&self, score: &Score) -> Option<Stakes> {
+        if self.is_match_point(score) {
+            Some(Stakes::MatchPoint)
+        } else if self.is_set_point(score) {
+            Some(Stakes::SetPoint)
+        } else if self.is_deuce(score) {
+            Some(Stakes::Deuce)
+        } else {
+            None
+        }
+    }
+
+    /// The player who has won enough sets to take the match, if anyone has.
+    pub fn match_winner(&self, score: &Score) -> Option<i32> {
+        if score.player1_sets >= self.sets_to_win() {
+            Some(1)
+        } else if score.player2_sets >= self.sets_to_win() {
+            Some(2)
+        } else {
+            None
+        }
+    }
+}
+
+/// What's riding on the next point, from least to most, see
+/// [`MatchRules::stakes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stakes {
+    /// Tied, with the set to be won by more than a point
+    Deuce,
+    /// It could win the set
+    SetPoint,
+    /// It could win the match
+    MatchPoint,
+}
+
+// The match state below derives `Clone` as well as `Resource`, since that's
+// what rollback needs to snapshot and restore resources.
+
+#[derive(Resource, Reflect, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    /// Points in the set being played
+    pub player1: i32,
+    pub player2: i32,
+    /// Sets won so far
+    pub player1_sets: i32,
+    pub player2_sets: i32,
+    /// The next point wins the set, see [`MatchRules::golden_point`]
+    pub golden_point: bool,
+}
+
+impl Score {
+    /// Love all, but for anyone's head start.
+    pub fn starting(rules: &MatchRules) -> Self {
+        Score {
+            player1: rules.handicap(1).head_start,
+            player2: rules.handicap(2).head_start,
+            ..default()
+        }
+    }
+
+    fn add_point(&mut self, player_number: i32) {
+        self.add_points(player_number, 1);
+    }
+
+    /// Points on top of the usual one for a goal, or taken away for fewer.
+    pub fn add_points(&mut self, player_number: i32, points: i32) {
+        if player_number == 1 {
+            self.player1 += points;
+        } else {
+            self.player2 += points;
+        }
+    }
+
+    pub fn points(&self, player_number: i32) -> i32 {
+        if player_number == 1 {
+            self.player1
+        } else {
+            self.player2
+        }
+    }
+
+    pub fn sets(&self, player_number: i32) -> i32 {
+        if player_number == 1 {
+            self.player1_sets
+        } else {
+            self.player2_sets
+        }
+    }
+
+    /// Gives `player_number` the set and starts the next one from love all,
+    /// or from anyone's head start.
+    fn win_set(&mut self, player_number: i32, rules: &MatchRules) {
+        if player_number == 1 {
+            self.player1_sets += 1;
+        } else {
+            self.player2_sets += 1;
+        }
+        self.player1 = rules.handicap(1).head_start;
+        self.player2 = rules.handicap(2).head_start;
+        self.golden_point = false;
+    }
+
+    /// Whether the players have changed ends, which they do after every set.
+    pub fn ends_swapped(&self) -> bool {
+        (self.player1_sets + self.player2_sets) % 2 == 1
+    }
+
+    /// Who's playing from `side` in the set being played.
+    pub fn player_on(&self, side: Side) -> i32 {
+        if self.ends_swapped() {
+            side.opponent().player_number()
+        } else {
+            side.player_number()
+        }
+    }
+
+    /// Which end `player_number` is playing from in the set being played.
+    pub fn side_of(&self, player_number: i32) -> Side {
+        let side = if player_number == 1 {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        if self.ends_swapped() {
+            side.opponent()
+        } else {
+            side
+        }
+    }
+}
+
+/// Racket hits in the rally being played, and in every finished one.
+#[derive(Resource, Reflect, Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Rally {
+    pub hits: u32,
+    pub finished: Vec<u32>,
+}
+
+/// Numbers from the match so far, for the game-over screen.
+#[derive(Resource, Reflect, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MatchStats {
+    pub player1: PlayerStats,
+    pub player2: PlayerStats,
+    /// Most racket hits in a single rally
+    pub longest_rally: u32,
+    /// Fastest the ball has gone, in world units per second
+    pub top_speed: f32,
+    /// Who served the point being played
+    pub server: i32,
+}
+
+#[derive(Reflect, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub struct PlayerStats {
+    pub points: i32,
+    pub hits: u32,
+    pub serves: u32,
+    /// Points won on their own serve
+    pub serves_won: u32,
+}
+
+impl Default for MatchStats {
+    fn default() -> Self {
+        MatchStats {
+            player1: default(),
+            player2: default(),
+            longest_rally: 0,
+            top_speed: 0.0,
+            // The first serve goes to the right, from player 1
+            server: 1,
+        }
+    }
+}
+
+impl MatchStats {
+    pub fn player(&self, player_number: i32) -> &PlayerStats {
+        if player_number == 1 {
+            &self.player1
+        } else {
+            &self.player2
+        }
+    }
+
+    fn player_mut(&mut self, player_number: i32) -> &mut PlayerStats {
+        if player_number == 1 {
+            &mut self.player1
+        } else {
+            &mut self.player2
+        }
+    }
+
+    pub fn total_hits(&self) -> u32 {
+        self.player1.hits + self.player2.hits
+    }
+
+    fn end_rally(&mut self, scored_by: i32, hits: u32) {
+        self.player_mut(scored_by).points += 1;
+        let server = self.server;
+        let serving = self.player_mut(server);
+        serving.serves += 1;
+        if scored_by == server {
+            serving.serves_won += 1;
+        }
+        self.longest_rally = self.longest_rally.max(hits);
+        // The ball is served away from whoever just won the point
+        self.server = scored_by;
+    }
+}
+
+impl PlayerStats {
+    /// Share of their serves they won the point on, if they've served at all.
+    pub fn serve_win_rate(&self) -> Option<f32> {
+        (self.serves > 0).then(|| self.serves_won as f32 / self.serves as f32)
+    }
+}
+
+/// Steps left in a timed match. Counting steps rather than seconds makes time
+/// run out on the very same step everywhere. Untimed matches sit at zero.
+#[derive(Resource, Reflect, Serialize, Deserialize, Default, Debug, Clone, Copy)]
+pub struct MatchClock {
+    pub steps_left: u32,
+}
+
+impl MatchClock {
+    pub fn new(rules: &MatchRules) -> Self {
+        MatchClock {
+            steps_left: rules
+                .time_limit
+                .map_or(0, |seconds| rules.time_step.steps(seconds as f32)),
+        }
+    }
+
+    /// Seconds left, with steps as long as `step` says.
+    pub fn seconds_left(&self, step: TimeStep) -> f32 {
+        self.steps_left as f32 * step.seconds()
+    }
+}
+
+/// The wind in a windy match. It blows a bit differently every match, but
+/// always slowly, so there's time to see it turn.
+#[derive(Resource, Reflect, Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Wind {
+    /// Where along its swing the wind starts, across and along the court, as
+    /// fractions of a full swing
+    phases: Vec2,
+    steps: u32,
+}
+
+impl Wind {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Wind {
+            phases: Vec2::new(rng.range(0.0, 1.0), rng.range(0.0, 1.0)),
+            steps: 0,
+        }
+    }
+
+    /// How hard it's blowing the ball, and which way, in pixels a second
+    /// squared, with steps as long as `step` says.
+    pub fn force(&self, step: TimeStep) -> Vec2 {
+        let turns = self.steps as f32 * step.seconds() / WIND_PERIOD + self.phases;
+        // A second, faster swing on top keeps it from being too regular
+        let swing =
+            |turns: f32| 0.75 * (turns * 2.0 * PI).sin() + 0.25 * (turns * 2.0 * PI * 2.7).sin();
+        Vec2::new(swing(turns.x), swing(turns.y)) * WIND_STRENGTH
+    }
+
+    /// How hard it's blowing, from 0 for not at all to 1 for as hard as it can.
+    pub fn strength(&self, step: TimeStep) -> f32 {
+        (self.force(step).length() / WIND_STRENGTH.length()).min(1.0)
+    }
+}
+
+/// A pair of portals on the court. A ball going into one comes out of the
+/// other just as fast, turned by `turn` going one way and back again going
+/// the other. Every so often they close and open again somewhere else.
+#[derive(Resource, Reflect, Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Portals {
+    pub ends: [Vec2; 2],
+    turn: f32,
+    /// Steps since the pair started opening where it is
+    steps: u32,
+}
+
+impl Portals {
+    /// How far open the portals are, from 0 for shut to 1 for wide open,
+    /// with steps as long as `step` says.
+    pub fn openness(&self, step: TimeStep) -> f32 {
+        let age = self.steps as f32 * step.seconds();
+        let left = PORTAL_LIFETIME + 2.0 * PORTAL_OPENING - age;
+        (age.min(left) / PORTAL_OPENING).clamp(0.0, 1.0)
+    }
+
+    /// One end somewhere in each half of the court, clear of the rackets and
+    /// of the serve.
+    fn open(&mut self, bounds: &ArenaBounds, rng: &mut impl Rng) {
+        let center = (bounds.min + bounds.max) / 2.0;
+        let size = bounds.max - bounds.min;
+        let x = rng.range(0.1, 0.3) * size.x;
+        let y = [
+            rng.range(bounds.min.y + PORTAL_RADIUS, bounds.max.y - PORTAL_RADIUS),
+            rng.range(bounds.min.y + PORTAL_RADIUS, bounds.max.y - PORTAL_RADIUS),
+        ];
+        self.ends = [Vec2::new(center.x - x, y[0]), Vec2::new(center.x + x, y[1])];
+        self.turn = rng.range(-MAX_PORTAL_TURN, MAX_PORTAL_TURN);
+        self.steps = 0;
+    }
+}
+
+/// How the wall in the middle of the court goes up and down, see
+/// [`MovingWall`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WallPath {
+    /// How far it goes towards the top and bottom of the court, from 0 for
+    /// not at all to 1 for right up to the walls
+    pub reach: f32,
+    /// Seconds it takes to go up, down and back to where it started
+    pub period: f32,
+}
+
+/// The wall going up and down the middle of the court with
+/// [`MatchRules::moving_wall`]. It's a [`Collider`] like the rest, only the
+/// ball bounces off it the way it would if the wall stood still and the ball
+/// came at it as fast as it does from the wall, so a wall coming at the ball
+/// sends it back faster. A ball served while it's going by starts out inside
+/// it, and just carries on its way out.
+#[derive(Component, Reflect, Serialize, Deserialize, Default, Debug, Clone, Copy)]
+pub struct MovingWall {
+    /// Steps since the match started
+    steps: u32,
+    /// How fast it's going, in pixels a second
+    pub velocity: Vec2,
+}
+
+/// A moving wall in the middle of the court, which sets off along its path on
+/// the first step.
+pub fn spawn_moving_wall<'a>(commands: &'a mut Commands) -> EntityCommands<'a> {
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_scale(MOVING_WALL_SIZE),
+            ..default()
+        },
+        ThemePart::Obstacle,
+        Collider,
+        Obstacle,
+        MovingWall::default(),
+    ))
+}
+
+/// [`MatchRules::center_net`]: a low net across the middle of the court,
+/// closer to table tennis. With gravity it stands on the bottom of the court,
+/// an [`Obstacle`] the ball has to clear. Without, seen from above, the ball
+/// goes over it, only a flat shot brushes the top of it on the way and is
+/// knocked a little off course.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct CenterNet;
+
+/// A net across the middle of the court, the kind the rules call for, which
+/// is hung in place on the first step.
+pub fn spawn_center_net<'a>(commands: &'a mut Commands, rules: &MatchRules) -> EntityCommands<'a> {
+    let mut net = commands.spawn((SpriteBundle::default(), ThemePart::Obstacle, CenterNet));
+    if rules.gravity.is_some() {
+        net.insert((Collider, Obstacle));
+    }
+    net
+}
+
+/// Steps before the ball can go through a portal again.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct PortalCooldown(u32);
+
+/// Set once someone has won and the winning ball has come to rest.
+#[derive(Resource, Reflect, Default, Debug, Clone)]
+pub struct MatchOver(pub Option<i32>);
+
+/// What a player asks their racket to do during one step.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+// Recordings leave out what isn't held
+#[serde(default)]
+pub struct RacketInput {
+    pub up: bool,
+    pub down: bool,
+    /// Held to charge a smash, which goes off when let go of
+    pub smash: bool,
+    /// Held to turn the racket's face towards the top or bottom of the court
+    pub tilt_up: bool,
+    pub tilt_down: bool,
+    /// Pressed to dash, see [`Dash`]
+    pub dash: bool,
+}
+
+impl RacketInput {
+    fn direction(self) -> f32 {
+        if self.up {
+            1.0
+        } else if self.down {
+            -1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn tilt_direction(self) -> f32 {
+        if self.tilt_up {
+            1.0
+        } else if self.tilt_down {
+            -1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// How many rackets can be on the court, two a side in doubles.
+pub const SEATS: usize = 4;
+
+/// Inputs for the next step, one for each [`Racket::seat`].
+#[derive(Resource, Default, Debug)]
+pub struct SimInput(pub [RacketInput; SEATS]);
+
+/// The simulation's only source of randomness (SplitMix64).
+#[derive(Resource, Reflect, Serialize, Deserialize, Default, Debug, Clone)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        SimRng { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Where it's got to, for telling whether two have kept in step.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// A number in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        // The top 24 bits fit an f32 mantissa exactly
+        let unit = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        min + (max - min) * unit
+    }
+}
+
+/// Which half of the court a racket or goal belongs to. Rackets change
+/// sides between sets, goals stay put.
+#[derive(
+    Component, Reflect, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq,
+)]
+#[reflect_value(Component, PartialEq, Serialize, Deserialize)]
+pub enum Side {
+    // Only there for rollback, which wants a default for everything it restores
+    #[default]
+    Left,
+    Right,
+}
+
+impl Side {
+    /// Which way this side lies from the center of the court along x.
+    pub fn direction(self) -> f32 {
+        match self {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        }
+    }
+
+    pub fn player_number(self) -> i32 {
+        match self {
+            Side::Left => 1,
+            Side::Right => 2,
+        }
+    }
+
+    pub fn opponent(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Racket {
+    /// Whose points the racket plays for
+    pub player_number: i32,
+    /// Which of the [`SimInput`]s moves it: its player's own, or in doubles
+    /// perhaps their partner's
+    pub seat: usize,
+}
+
+// Only there for scenes, which want a default for every component they spawn
+impl Default for Racket {
+    fn default() -> Self {
+        Racket::new(1)
+    }
+}
+
+impl Racket {
+    pub fn new(player_number: i32) -> Self {
+        Racket {
+            player_number,
+            seat: (player_number - 1) as usize,
+        }
+    }
+
+    /// The racket of `player_number`'s partner in doubles, moved by the
+    /// seats after both players'.
+    pub fn partner(player_number: i32) -> Self {
+        Racket {
+            player_number,
+            seat: (player_number + 1) as usize,
+        }
+    }
+}
+
+/// Whether a racket is waiting for its partner to take their turn at the
+/// ball, in doubles where they have to take turns. The ball goes straight
+/// past a waiting racket.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct Waiting(pub bool);
+
+/// How far a racket's face is turned towards the top of the court, in
+/// radians. Negative turns it towards the bottom.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct Tilt(pub f32);
+
+/// How long a racket is, end to end. Its sprite is stretched to match every step.
+#[derive(Component, Reflect, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct RacketLength {
+    /// What it goes back to once a point's over
+    pub full: f32,
+    pub current: f32,
+}
+
+impl RacketLength {
+    pub fn new(full: f32) -> Self {
+        RacketLength {
+            full,
+            current: full,
+        }
+    }
+}
+
+impl Default for RacketLength {
+    fn default() -> Self {
+        RacketLength::new(RACKET_LENGTH)
+    }
+}
+
+/// How fast a racket moves up and down the court, as a multiple of
+/// [`GameplayConfig::racket_speed`].
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct RacketSpeed(pub f32);
+
+impl Default for RacketSpeed {
+    fn default() -> Self {
+        RacketSpeed(1.0)
+    }
+}
+
+/// How a racket moved during the last step, which is what spins the ball.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct Swing {
+    /// Up the court, in pixels a second
+    velocity: f32,
+    /// Anticlockwise, in radians a second
+    turn: f32,
+}
+
+/// A racket's smash, charged while its player holds the smash key. Letting go
+/// just before the ball arrives sends it back faster and flatter, the more so
+/// the longer it was charged.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Smash {
+    /// Steps the key has been held for, up to a full charge
+    charge: u32,
+    /// The charge let go of, waiting for the ball
+    released: u32,
+    /// Steps left for the ball to arrive before the released charge is wasted
+    window: u32,
+    /// Steps before the racket can charge again
+    cooldown: u32,
+}
+
+impl Smash {
+    /// How charged the smash is, from 0 to 1, counting one that's been let go
+    /// of, with steps as long as `step` says.
+    pub fn level(&self, step: TimeStep) -> f32 {
+        self.charge.max(self.released) as f32 / step.steps(SMASH_CHARGE_TIME) as f32
+    }
+
+    /// Whether it's been let go of and is waiting for the ball.
+    pub fn is_released(&self) -> bool {
+        self.released > 0
+    }
+
+    /// The fraction of the cooldown still to go, 0 once the racket can charge.
+    pub fn cooldown(&self, step: TimeStep) -> f32 {
+        self.cooldown as f32 / step.steps(SMASH_COOLDOWN) as f32
+    }
+}
+
+/// How many points in a row a racket's player has won, up to
+/// [`MAX_MOMENTUM`]. Losing a point takes it all away, and so does smashing
+/// with it full, which makes for a full smash.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Momentum {
+    streak: u32,
+}
+
+impl Momentum {
+    /// How much momentum there is, from 0 to 1.
+    pub fn level(&self) -> f32 {
+        self.streak as f32 / MAX_MOMENTUM as f32
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.streak >= MAX_MOMENTUM
+    }
+}
+
+/// A racket's dash: pressing the dash key has it move three times as fast for
+/// a moment, to get to a ball it wouldn't have otherwise, after which it has
+/// to wait a while before it can dash again.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Dash {
+    /// Steps left of the dash going on
+    left: u32,
+    /// Steps after it before the racket can dash again
+    cooldown: u32,
+    /// Whether the key was down the step before, so holding it doesn't dash
+    /// again the moment it can
+    held: bool,
+}
+
+impl Dash {
+    pub fn is_dashing(&self) -> bool {
+        self.left > 0
+    }
+
+    /// The fraction of the cooldown still to go, 1 during the dash itself
+    /// and 0 once the racket can dash again.
+    pub fn cooldown(&self, step: TimeStep) -> f32 {
+        if self.is_dashing() {
+            1.0
+        } else {
+            self.cooldown as f32 / step.steps(DASH_COOLDOWN) as f32
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Ball;
+
+#[derive(Component, Reflect, Default, Deref, DerefMut, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Velocity(pub Vec2);
+
+/// How fast the ball is spinning, in radians a second, anticlockwise. A
+/// spinning ball curves the way its spin turns it.
+#[derive(Component, Reflect, Default, Deref, DerefMut, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Spin(pub f32);
+
+/// What a ball's made of, picked for a whole match by [`MatchRules::ball`]
+/// and kept on every ball in it. The physics go by it, and so does how much
+/// the ball squashes when it lands.
+#[derive(
+    Component, Reflect, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, Hash,
+)]
+#[reflect_value(Component, PartialEq, Serialize, Deserialize)]
+pub enum BallKind {
+    #[default]
+    Standard,
+    /// Slower, and harder for the end of a racket, spin or the wind to turn
+    /// off its line
+    Heavy,
+    /// Faster, and sent off at a sharper angle the nearer the end of a racket
+    /// it's hit
+    Light,
+    /// Twice the size and slow, and the air holds it up and blows it about
+    Beach,
+}
+
+/// The balls the settings screen cycles through, in order.
+pub const BALL_KINDS: [BallKind; 4] = [
+    BallKind::Standard,
+    BallKind::Heavy,
+    BallKind::Light,
+    BallKind::Beach,
+];
+
+impl BallKind {
+    pub fn name_key(self) -> &'static str {
+        match self {
+            BallKind::Standard => "ball.standard",
+            BallKind::Heavy => "ball.heavy",
+            BallKind::Light => "ball.light",
+            BallKind::Beach => "ball.beach",
+        }
+    }
+
+    /// How big it is, as a multiple of [`BALL_SIZE`].
+    pub fn size(self) -> f32 {
+        match self {
+            BallKind::Light => 0.8,
+            BallKind::Beach => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    /// How fast it's served, as a multiple of [`MatchRules::ball_speed`].
+    pub fn speed(self) -> f32 {
+        match self {
+            BallKind::Standard => 1.0,
+            BallKind::Heavy => 0.8,
+            BallKind::Light => 1.25,
+            BallKind::Beach => 0.7,
+        }
+    }
+
+    /// How far off straight it's sent back, as a multiple of
+    /// [`MatchRules::bounce_angle`].
+    pub fn deflection(self) -> f32 {
+        match self {
+            BallKind::Heavy => 0.6,
+            BallKind::Light => 1.5,
+            _ => 1.0,
+        }
+    }
+
+    /// How much spin curves it and the wind blows it about, as a multiple of
+    /// how much they do a standard ball.
+    pub fn drift(self) -> f32 {
+        match self {
+            BallKind::Heavy => 0.5,
+            BallKind::Beach => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    /// How hard it falls with gravity on, as a multiple of
+    /// [`MatchRules::gravity`].
+    pub fn weight(self) -> f32 {
+        match self {
+            BallKind::Beach => 0.4,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Anything the ball bounces off, other than the rackets.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Collider;
+
+/// A [`Collider`] standing in the court, as opposed to one of the walls round
+/// it. A serve that clips the top of one is a let.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Obstacle;
+
+/// What the ball bounced off in a [`CollisionEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surface {
+    Racket,
+    Wall,
+}
+
+/// Sent whenever the ball bounces. Rollback can play a step again, and then
+/// its events are sent again too, so only use these for effects.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CollisionEvent {
+    pub surface: Surface,
+    pub position: Vec2,
+    /// The ball's velocity after the bounce
+    pub velocity: Vec2,
+}
+
+/// Sent when the ball crosses a goal line, with the same caveat as
+/// [`CollisionEvent`].
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GoalEvent {
+    pub position: Vec2,
+    pub scored_by: i32,
+}
+
+/// Sent when a player returns the ball off the face of their racket, along
+/// with the [`CollisionEvent`] for the bounce. Same caveat as that, other than
+/// for the simulation's own systems, which read them in the step they're sent.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BallHitEvent {
+    pub player_number: i32,
+    pub position: Vec2,
+    /// The ball's velocity off the racket
+    pub velocity: Vec2,
+    /// How charged a smash it was hit with, from 0 for none to 1
+    pub smash: f32,
+    /// How far from the racket's center it met the ball, from -1 at one end
+    /// to 1 at the other
+    pub along: f32,
+}
+
+/// Sent when a ball is served, at the start of a match and after every point
+/// but the last, with the same caveat as [`CollisionEvent`].
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ServeEvent {
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+/// What a serve's been called, see [`MatchRules::serve_rules`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServeCall {
+    /// Back over the center line before it was returned, and the receiver's
+    /// point
+    Fault,
+    /// Clipped the top of an obstacle, and served again
+    Let,
+}
+
+/// Sent when a serve's called, with the same caveat as [`CollisionEvent`].
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ServeCallEvent {
+    pub call: ServeCall,
+    pub position: Vec2,
+    /// The player who served it
+    pub server: i32,
+}
+
+/// Sent when someone's won the match, as [`MatchOver`] is set, with the same
+/// caveat as [`CollisionEvent`].
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MatchEndedEvent {
+    pub winner: i32,
+}
+
+/// A goal's net, spawned from a [`GoalZone`](crate::arena::GoalZone).
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Goal;
+
+/// A ball that's gone once it has settled in a net, instead of being served
+/// again, and so never decides a set. A ball machine's balls are like that.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct ServedOnce;
+
+/// A ball on its way from the serve to `receiver`, until they've returned it,
+/// for [`MatchRules::serve_rules`] to call.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Served {
+    pub receiver: Side,
+    /// Whether it's clipped the top of an [`Obstacle`] on the way
+    clipped: bool,
+}
+
+impl Served {
+    pub fn new(receiver: Side) -> Self {
+        Served {
+            receiver,
+            clipped: false,
+        }
+    }
+}
+
+/// A ball that has crossed a goal line and is settling in the net.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct Netted {
+    pub goal: Entity,
+    conceded_by: Side,
+    timer: Timer,
+}
+
+impl Netted {
+    /// A ball in `goal`, which `conceded_by` let it into, that's served again
+    /// in `time_to_serve` seconds, as when a saved match is carried on with.
+    pub fn resumed(goal: Entity, conceded_by: Side, time_to_serve: f32) -> Self {
+        let mut timer = Timer::from_seconds(NET_SETTLE_TIME, TimerMode::Once);
+        timer.set_elapsed(Duration::from_secs_f32(
+            (NET_SETTLE_TIME - time_to_serve).max(0.0),
+        ));
+        Netted {
+            goal,
+            conceded_by,
+            timer,
+        }
+    }
+
+    /// Seconds until the ball is served again from the middle.
+    pub fn time_to_serve(&self) -> f32 {
+        (self.timer.duration() - self.timer.elapsed()).as_secs_f32()
+    }
+
+    /// The side of the goal the ball's in.
+    pub fn conceded_by(&self) -> Side {
+        self.conceded_by
+    }
+}
+
+impl Default for Netted {
+    fn default() -> Self {
+        Netted {
+            goal: Entity::from_raw(u32::MAX),
+            conceded_by: Side::Left,
+            timer: default(),
+        }
+    }
+}
+
+/// Size of the axis-aligned box covered by a sprite, taking its rotation into account.
+pub fn extent(transform: &Transform) -> Vec2 {
+    let width = (transform.rotation * Vec3::X).truncate().abs() * transform.scale.x;
+    let height = (transform.rotation * Vec3::Y).truncate().abs() * transform.scale.y;
+    width + height
+}
+
+/// A racket stands on end, its face turned `tilt` towards the top of the
+/// court from facing straight across it.
+fn racket_rotation(side: Side, tilt: f32) -> Quat {
+    // Turning the face up is anticlockwise on the left, clockwise on the right
+    Quat::from_rotation_z(PI / 2.0 - side.direction() * tilt)
+}
+
+/// Velocity of a fresh serve heading towards `receiver`.
+pub fn serve_velocity(
+    receiver: Side,
+    rules: &MatchRules,
+    config: &GameplayConfig,
+    rng: &mut impl Rng,
+) -> Vec2 {
+    let max_angle = config.max_serve_angle.to_radians();
+    let angle = if rules.gravity.is_some() {
+        rng.range(MIN_LOB_ANGLE, MAX_LOB_ANGLE)
+    } else {
+        rng.range(-max_angle, max_angle)
+    };
+    Vec2::new(receiver.direction() * angle.cos(), angle.sin())
+        * config.ball_speed
+        * rules.ball_speed
+        * rules.ball.speed()
+}
+
+/// Velocity of the serve starting a golden point.
+fn golden_serve_velocity(
+    receiver: Side,
+    rules: &MatchRules,
+    config: &GameplayConfig,
+    rng: &mut impl Rng,
+) -> Vec2 {
+    let max_angle = config.max_serve_angle.to_radians();
+    let angle = if rules.gravity.is_some() {
+        MAX_LOB_ANGLE
+    } else if rng.range(0.0, 1.0) < 0.5 {
+        -max_angle
+    } else {
+        max_angle
+    };
+    Vec2::new(receiver.direction() * angle.cos(), angle.sin())
+        * config.ball_speed
+        * rules.ball_speed
+        * rules.ball.speed()
+        * GOLDEN_SERVE_SPEEDUP
+}
+
+/// A ball served from the center, towards the right.
+pub fn spawn_ball<'a>(
+    commands: &'a mut Commands,
+    rules: &MatchRules,
+    config: &GameplayConfig,
+    rng: &mut impl Rng,
+    serve_events: &mut EventWriter<ServeEvent>,
+) -> EntityCommands<'a> {
+    let velocity = serve_velocity(Side::Right, rules, config, rng);
+    serve_ball(commands, rules.ball, Side::Right, velocity, serve_events)
+}
+
+/// A ball of `kind` served from the center towards `receiver` at `velocity`.
+pub fn serve_ball<'a>(
+    commands: &'a mut Commands,
+    kind: BallKind,
+    receiver: Side,
+    velocity: Vec2,
+    serve_events: &mut EventWriter<ServeEvent>,
+) -> EntityCommands<'a> {
+    let position = BALL_STARTING_POSITION.truncate();
+    serve_events.send(ServeEvent { position, velocity });
+    let mut ball = launch_ball(commands, kind, position, velocity);
+    ball.insert(Served::new(receiver));
+    ball
+}
+
+/// A ball of `kind` at `position`, already on its way at `velocity`.
+pub fn launch_ball<'a>(
+    commands: &'a mut Commands,
+    kind: BallKind,
+    position: Vec2,
+    velocity: Vec2,
+) -> EntityCommands<'a> {
+    commands.spawn((
+        Ball,
+        kind,
+        SpriteBundle {
+            transform: Transform {
+                scale: BALL_SIZE * kind.size(),
+                translation: position.extend(BALL_STARTING_POSITION.z),
+                ..default()
+            },
+            ..default()
+        },
+        ThemePart::Ball,
+        Velocity(velocity),
+        Spin::default(),
+        PortalCooldown::default(),
+    ))
+}
+
+pub fn spawn_racket<'a>(
+    commands: &'a mut Commands,
+    side: Side,
+    location: Vec3,
+) -> EntityCommands<'a> {
+    commands.spawn((
+        Racket::new(side.player_number()),
+        side,
+        (
+            Tilt::default(),
+            Swing::default(),
+            Smash::default(),
+            Momentum::default(),
+            Dash::default(),
+            RacketLength::default(),
+            RacketSpeed::default(),
+            Waiting::default(),
+        ),
+        SpriteBundle {
+            transform: Transform {
+                translation: location,
+                rotation: racket_rotation(side, 0.0),
+                scale: Vec3::new(RACKET_LENGTH, RACKET_THICCNESS, 0.0),
+            },
+            ..default()
+        },
+        ThemePart::Racket,
+    ))
+}
+
+fn move_rackets(
+    input: Res<SimInput>,
+    mut query: Query<(
+        &Racket,
+        &Side,
+        &RacketLength,
+        &RacketSpeed,
+        Option<&Dash>,
+        &mut Tilt,
+        &mut Swing,
+        &mut Transform,
+    )>,
+    bounds: Res<ArenaBounds>,
+    rules: Res<MatchRules>,
+    config: Res<GameplayConfig>,
+) {
+    let seconds = rules.time_step.seconds();
+    for (racket, side, length, speed, dash, mut tilt, mut swing, mut transform) in query.iter_mut()
+    {
+        let input = input.0[racket.seat];
+        let resized = transform.scale.x != length.current;
+        if input.direction() == 0.0 && input.tilt_direction() == 0.0 && !resized {
+            *swing = Swing::default();
+            continue;
+        }
+
+        transform.scale.x = length.current;
+        let last_tilt = tilt.0;
+        let last_y = transform.translation.y;
+        tilt.0 =
+            (tilt.0 + input.tilt_direction() * TILT_SPEED * seconds).clamp(-MAX_TILT, MAX_TILT);
+        transform.rotation = racket_rotation(*side, tilt.0);
+
+        // Tilting or growing back makes the racket taller, so it may need
+        // pulling back in
+        let half_height = extent(&transform).y / 2.0;
+        let dashing = dash.is_some_and(Dash::is_dashing);
+        let speed = speed.0
+            * config.racket_speed
+            * rules.racket_speed
+            * if dashing { DASH_SPEEDUP } else { 1.0 };
+        let new_position = transform.translation.y + input.direction() * speed * seconds;
+        transform.translation.y = new_position.clamp(
+            bounds.min.y + half_height,
+            (bounds.max.y - half_height).max(bounds.min.y + half_height),
+        );
+
+        *swing = Swing {
+            velocity: (transform.translation.y - last_y) / seconds,
+            turn: -side.direction() * (tilt.0 - last_tilt) / seconds,
+        };
+    }
+}
+
+/// Pushes rackets that have ended up in each other or in a wall or obstacle
+/// back out, the way they went the least far in, so they're as solid to each
+/// other as they are to the ball.
+fn keep_rackets_apart(
+    rules: Res<MatchRules>,
+    mut rackets: Query<(&mut Transform, &mut Swing), With<Racket>>,
+    colliders: Query<&Transform, (With<Collider>, Without<Racket>)>,
+) {
+    // A push isn't the racket being swung, so it's taken back off the swing
+    let seconds = rules.time_step.seconds();
+    let push = |transform: &mut Transform, swing: &mut Swing, by: Vec2| {
+        transform.translation += by.extend(0.0);
+        swing.velocity += by.y / seconds;
+    };
+
+    // Each racket gives way half as much as the other
+    let mut pairs = rackets.iter_combinations_mut();
+    while let Some([(mut a, mut a_swing), (mut b, mut b_swing)]) = pairs.fetch_next() {
+        let overlap = penetrate(
+            a.translation.truncate(),
+            extent(&a),
+            b.translation.truncate(),
+            extent(&b),
+        );
+        if let Some((collision, depth)) = overlap {
+            let apart = outwards(&collision) * depth / 2.0;
+            push(&mut a, &mut a_swing, apart);
+            push(&mut b, &mut b_swing, -apart);
+        }
+    }
+
+    // Walls don't give way at all, so they go last
+    for (mut transform, mut swing) in rackets.iter_mut() {
+        for collider in colliders.iter() {
+            let overlap = penetrate(
+                transform.translation.truncate(),
+                extent(&transform),
+                collider.translation.truncate(),
+                extent(collider),
+            );
+            if let Some((collision, depth)) = overlap {
+                push(&mut transform, &mut swing, outwards(&collision) * depth);
+            }
+        }
+    }
+}
+
+fn start_dashes(
+    input: Res<SimInput>,
+    rules: Res<MatchRules>,
+    mut query: Query<(&Racket, &mut Dash)>,
+) {
+    for (racket, mut dash) in query.iter_mut() {
+        let pressed = input.0[racket.seat].dash;
+        if dash.left > 0 {
+            dash.left -= 1;
+        } else if dash.cooldown > 0 {
+            dash.cooldown -= 1;
+        }
+        if pressed && !dash.held && dash.left == 0 && dash.cooldown == 0 {
+            dash.left = rules.time_step.steps(DASH_TIME);
+            dash.cooldown = rules.time_step.steps(DASH_COOLDOWN);
+        }
+        dash.held = pressed;
+    }
+}
+
+fn charge_smashes(
+    input: Res<SimInput>,
+    rules: Res<MatchRules>,
+    mut query: Query<(&Racket, &mut Smash)>,
+) {
+    for (racket, mut smash) in query.iter_mut() {
+        let held = input.0[racket.seat].smash;
+        if smash.cooldown > 0 {
+            smash.cooldown -= 1;
+            smash.charge = 0;
+        } else if held {
+            smash.charge = (smash.charge + 1).min(rules.time_step.steps(SMASH_CHARGE_TIME));
+        } else if smash.charge > 0 {
+            smash.released = std::mem::take(&mut smash.charge);
+            smash.window = rules.time_step.steps(SMASH_WINDOW);
+        }
+
+        if smash.window > 0 {
+            smash.window -= 1;
+            if smash.window == 0 {
+                smash.released = 0;
+            }
+        }
+    }
+}
+
+/// Curves a spinning ball's flight, without speeding it up or slowing it down,
+/// while the spin wears off.
+fn apply_spin(
+    rules: Res<MatchRules>,
+    mut query: Query<(&mut Velocity, &mut Spin, Option<&Netted>, Option<&BallKind>)>,
+) {
+    let seconds = rules.time_step.seconds();
+    for (mut velocity, mut spin, netted, kind) in query.iter_mut() {
+        // The net stops it dead
+        if netted.is_some() {
+            spin.0 = 0.0;
+            continue;
+        }
+
+        let speed = velocity.length();
+        let drift = kind.copied().unwrap_or_default().drift();
+        let curved = velocity.0 + velocity.perp() * spin.0 * MAGNUS_EFFECT * drift * seconds;
+        velocity.0 = curved.normalize_or_zero() * speed;
+        spin.0 *= rules.time_step.decay(SPIN_DECAY);
+    }
+}
+
+fn blow_wind(
+    rules: Res<MatchRules>,
+    mut wind: ResMut<Wind>,
+    mut query: Query<(&mut Velocity, Option<&BallKind>), (With<Ball>, Without<Netted>)>,
+) {
+    if !rules.wind {
+        return;
+    }
+    wind.steps += 1;
+    let force = wind.force(rules.time_step);
+    for (mut velocity, kind) in query.iter_mut() {
+        velocity.0 += force * kind.copied().unwrap_or_default().drift() * rules.time_step.seconds();
+    }
+}
+
+fn apply_gravity(
+    rules: Res<MatchRules>,
+    mut query: Query<(&mut Velocity, Option<&BallKind>), (With<Ball>, Without<Netted>)>,
+) {
+    let gravity = match rules.gravity {
+        Some(gravity) => gravity,
+        None => return,
+    };
+    for (mut velocity, kind) in query.iter_mut() {
+        velocity.y -=
+            gravity * kind.copied().unwrap_or_default().weight() * rules.time_step.seconds();
+    }
+}
+
+/// Takes each moving wall one step along its path, from halfway between the
+/// top and bottom of the court up first.
+fn move_walls(
+    rules: Res<MatchRules>,
+    bounds: Res<ArenaBounds>,
+    mut walls: Query<(&mut MovingWall, &mut Transform)>,
+) {
+    let path = match rules.moving_wall {
+        Some(path) => path,
+        None => return,
+    };
+    let center = (bounds.min + bounds.max) / 2.0;
+    let room = ((bounds.max.y - bounds.min.y - MOVING_WALL_SIZE.y) / 2.0).max(0.0);
+    let reach = path.reach.clamp(0.0, 1.0) * room;
+    let seconds = rules.time_step.seconds();
+    let frequency = 2.0 * PI / path.period.max(seconds);
+    for (mut wall, mut transform) in walls.iter_mut() {
+        wall.steps += 1;
+        let phase = wall.steps as f32 * seconds * frequency;
+        transform.translation.x = center.x;
+        transform.translation.y = center.y + reach * phase.sin();
+        wall.velocity = Vec2::new(0.0, reach * frequency * phase.cos());
+    }
+}
+
+/// Serves a ball afresh from the center if it's gone somewhere it can't come
+/// back from: off to infinity, or out of the court without a goal seeing it,
+/// as when it's squeezed through a corner. Nobody scores, and it goes to the
+/// end it went off, or to the right if there's no telling. A ball machine's
+/// ball is just cleared away.
+///
+/// Runs first in the step, so a ball that's gone in during the last one is
+/// already [`Netted`] by then and left to settle.
+fn recover_lost_balls(
+    mut commands: Commands,
+    mut balls: Query<
+        (
+            Entity,
+            &BallKind,
+            &mut Transform,
+            &mut Velocity,
+            &mut Spin,
+            Option<&Netted>,
+            Option<&ServedOnce>,
+        ),
+        With<Ball>,
+    >,
+    bounds: Res<ArenaBounds>,
+    score: Res<Score>,
+    rally: Res<Rally>,
+    rules: Res<MatchRules>,
+    config: Res<GameplayConfig>,
+    mut rng: ResMut<SimRng>,
+    mut serve_events: EventWriter<ServeEvent>,
+) {
+    for (ball, kind, mut transform, mut velocity, mut spin, netted, served_once) in balls.iter_mut()
+    {
+        let position = transform.translation.truncate();
+        let finite = position.is_finite() && velocity.is_finite() && spin.is_finite();
+        let inside = position.cmpge(bounds.min).all() && position.cmple(bounds.max).all();
+        if finite && (inside || netted.is_some()) {
+            continue;
+        }
+
+        warn!(
+            "Lost a {:?} ball at {} going {} with spin {} (netted: {}), {} hits into the \
+             rally at {:?}, with the random state at {}",
+            kind,
+            position,
+            velocity.0,
+            spin.0,
+            netted.is_some(),
+            rally.hits,
+            *score,
+            rng.state(),
+        );
+        if served_once.is_some() {
+            commands.entity(ball).despawn();
+            continue;
+        }
+
+        let center = (bounds.min.x + bounds.max.x) / 2.0;
+        let receiver = if position.x < center {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        transform.translation = BALL_STARTING_POSITION;
+        velocity.0 = serve_velocity(receiver, &rules, &config, &mut *rng);
+        spin.0 = 0.0;
+        commands
+            .entity(ball)
+            .remove::<Netted>()
+            .insert(Served::new(receiver));
+        serve_events.send(ServeEvent {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+        });
+    }
+}
+
+/// Moves the ball on by its velocity. With the step cut into substeps, it
+/// stops at the end of the first piece that's put it up against a wall or a
+/// racket it wasn't touching already, for [`check_for_collisions`] to bounce
+/// it off there, rather than carrying on past what it'd only have met partway
+/// through the step.
+fn apply_velocity(
+    rules: Res<MatchRules>,
+    mut query: Query<(&mut Transform, &Velocity)>,
+    colliders: Query<&Transform, (With<Collider>, Without<Velocity>)>,
+    rackets: Query<&Transform, (With<Racket>, Without<Velocity>)>,
+) {
+    let substeps = rules.time_step.substeps.max(1);
+    let seconds = rules.time_step.seconds() / substeps as f32;
+    let touching = |transform: &Transform| {
+        let position = transform.translation.truncate();
+        let size = transform.scale.truncate();
+        colliders.iter().any(|collider| {
+            penetrate(
+                position,
+                size,
+                collider.translation.truncate(),
+                extent(collider),
+            )
+            .is_some()
+        }) || rackets
+            .iter()
+            .any(|racket| touch_racket(position, size.y / 2.0, racket).is_some())
+    };
+    for (mut transform, velocity) in query.iter_mut() {
+        for piece in 0..substeps {
+            let was_touching = substeps > 1 && touching(&transform);
+            transform.translation += (velocity.0 * seconds).extend(0.0);
+            if piece + 1 < substeps && !was_touching && touching(&transform) {
+                break;
+            }
+        }
+    }
+}
+
+/// Keeps each center net across the middle of the court: standing on the
+/// bottom of it if it's in the ball's way, from wall to wall if it isn't.
+fn hang_center_net(
+    bounds: Res<ArenaBounds>,
+    mut nets: Query<(&mut Transform, Option<&Collider>), With<CenterNet>>,
+) {
+    let center = (bounds.min + bounds.max) / 2.0;
+    for (mut transform, collider) in nets.iter_mut() {
+        let (y, height) = if collider.is_some() {
+            (bounds.min.y + CENTER_NET_HEIGHT / 2.0, CENTER_NET_HEIGHT)
+        } else {
+            (center.y, bounds.max.y - bounds.min.y)
+        };
+        transform.translation = Vec3::new(center.x, y, 0.0);
+        transform.scale = Vec3::new(CENTER_NET_THICKNESS, height, 1.0);
+    }
+}
+
+/// Knocks a flat shot a little off course, and slows it down, as it goes
+/// over a center net the ball isn't stopped by. Spin carries a ball over
+/// clean.
+fn brush_center_net(
+    rules: Res<MatchRules>,
+    mut rng: ResMut<SimRng>,
+    nets: Query<&Transform, (With<CenterNet>, Without<Collider>, Without<Ball>)>,
+    mut balls: Query<(&Transform, &mut Velocity, &Spin), (With<Ball>, Without<Netted>)>,
+) {
+    for net in nets.iter() {
+        let x = net.translation.x;
+        for (transform, mut velocity, spin) in balls.iter_mut() {
+            // A serve starts right on it, and doesn't cross it
+            let before = transform.translation.x - velocity.x * rules.time_step.seconds();
+            let crossed = (before - x) * (transform.translation.x - x) < 0.0;
+            if !crossed || spin.0.abs() >= FLAT_SPIN {
+                continue;
+            }
+            let turn = rng.range(-MAX_NET_DEFLECTION, MAX_NET_DEFLECTION);
+            velocity.0 = Vec2::from_angle(turn).rotate(velocity.0) * NET_BRUSH_SPEED;
+        }
+    }
+}
+
+fn use_portals(
+    rules: Res<MatchRules>,
+    bounds: Res<ArenaBounds>,
+    mut portals: ResMut<Portals>,
+    mut rng: ResMut<SimRng>,
+    mut query: Query<
+        (&mut Transform, &mut Velocity, &mut PortalCooldown),
+        (With<Ball>, Without<Netted>),
+    >,
+) {
+    if !rules.portals {
+        return;
+    }
+    let step = rules.time_step;
+    if portals.steps == 0 || portals.steps >= step.steps(PORTAL_LIFETIME + 2.0 * PORTAL_OPENING) {
+        portals.open(&bounds, &mut *rng);
+    }
+    portals.steps += 1;
+
+    for (mut transform, mut velocity, mut cooldown) in query.iter_mut() {
+        if cooldown.0 > 0 {
+            cooldown.0 -= 1;
+            continue;
+        }
+        if portals.openness(step) < 1.0 {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let entered = portals
+            .ends
+            .iter()
+            .position(|end| end.distance(position) < PORTAL_RADIUS);
+        if let Some(entered) = entered {
+            let exit = portals.ends[1 - entered];
+            let turn = if entered == 0 {
+                portals.turn
+            } else {
+                -portals.turn
+            };
+            transform.translation = exit.extend(transform.translation.z);
+            velocity.0 = Vec2::from_angle(turn).rotate(velocity.0);
+            cooldown.0 = step.steps(PORTAL_COOLDOWN);
+        }
+    }
+}
+
+fn check_for_collisions(
+    rules: Res<MatchRules>,
+    config: Res<GameplayConfig>,
+    mut stats: ResMut<MatchStats>,
+    mut commands: Commands,
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Velocity,
+            &mut Spin,
+            &Transform,
+            Option<&BallKind>,
+            Option<&mut Served>,
+        ),
+        With<Ball>,
+    >,
+    collider_query: Query<
+        (&Transform, Option<&MovingWall>, Option<&Obstacle>),
+        (With<Collider>, Without<Ball>),
+    >,
+    mut racket_query: Query<
+        (
+            Entity,
+            &Racket,
+            &Transform,
+            Option<&Swing>,
+            Option<&mut Smash>,
+            Option<&mut Momentum>,
+            Option<&mut RacketLength>,
+            Option<&mut Waiting>,
+        ),
+        Without<Ball>,
+    >,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut hit_events: EventWriter<BallHitEvent>,
+) {
+    let _span = info_span!("physics", balls = ball_query.iter().len()).entered();
+    let max_shot_angle = config.max_shot_angle.to_radians();
+    let max_smash_speed = config.max_smash_speed * config.ball_speed;
+    for (ball, mut velocity, mut spin, ball_transform, kind, mut served) in ball_query.iter_mut() {
+        let bounce_angle = rules.bounce_angle * kind.copied().unwrap_or_default().deflection();
+        let ball_size = ball_transform.scale.truncate();
+        let position = ball_transform.translation.truncate();
+        // The ball bounces off one thing a step at most, or where two things
+        // meet it'd be turned around twice and carry on into them. Rackets
+        // go first, so a return played up against a wall still counts
+        let mut bounced = false;
+
+        let mut hit_by = None;
+        for (entity, racket, transform, swing, smash, momentum, length, waiting) in
+            racket_query.iter_mut()
+        {
+            if bounced || waiting.is_some_and(|waiting| waiting.0) {
+                continue;
+            }
+            let radius = ball_size.y / 2.0;
+            let contact = match touch_racket(position, radius, transform) {
+                // Only if the ball is coming at it, same as with walls
+                Some(contact) if velocity.dot(contact.normal) < 0.0 => contact,
+                _ => continue,
+            };
+            bounced = true;
+            if !contact.on_face {
+                // Off the end of the racket it just bounces
+                let towards = velocity.dot(contact.normal);
+                velocity.0 -= 2.0 * towards * contact.normal;
+                collision_events.send(CollisionEvent {
+                    surface: Surface::Racket,
+                    position,
+                    velocity: velocity.0,
+                });
+                continue;
+            }
+
+            hit_by = Some((entity, racket.player_number));
+            if served.is_some() {
+                commands.entity(ball).remove::<Served>();
+            }
+            if let Some(mut length) = length.filter(|_| rules.shrinking) {
+                length.current = (length.current * (1.0 - RACKET_SHRINK))
+                    .max(MIN_RACKET_LENGTH.min(length.full));
+            }
+
+            // Hitting the face of a racket sends the ball off at an angle that
+            // depends on how far from the racket's center it landed, turned
+            // with the face when it's tilted
+            let tilt = contact.normal.y.atan2(contact.normal.x.abs());
+            let mut angle =
+                (tilt + contact.along * bounce_angle).clamp(-max_shot_angle, max_shot_angle);
+            let mut speed = velocity.length();
+            let mut power = 0.0;
+            if let Some(mut smash) = smash.filter(|smash| smash.is_released()) {
+                power = smash.level(rules.time_step);
+                // Full momentum is spent on a full smash
+                if let Some(mut momentum) = momentum.filter(|momentum| momentum.is_full()) {
+                    power = 1.0;
+                    *momentum = Momentum::default();
+                }
+                angle *= 1.0 - SMASH_FLATTENING * power;
+                speed = (speed * (1.0 + SMASH_SPEEDUP * power)).min(max_smash_speed.max(speed));
+                *smash = Smash {
+                    cooldown: rules.time_step.steps(SMASH_COOLDOWN),
+                    ..default()
+                };
+            }
+            let x_direction = contact.normal.x.signum();
+            velocity.0 = Vec2::new(x_direction * angle.cos(), angle.sin()) * speed;
+
+            // Whichever way the face was moving where it met the ball, it drags
+            // that side of the ball along and sets it spinning
+            let swing = swing.copied().unwrap_or_default();
+            let touching = position - contact.normal * radius;
+            let face_velocity = Vec2::new(0.0, swing.velocity)
+                + (touching - transform.translation.truncate()).perp() * swing.turn;
+            spin.0 = -face_velocity.dot(contact.normal.perp()) / radius * SPIN_TRANSFER;
+            collision_events.send(CollisionEvent {
+                surface: Surface::Racket,
+                position,
+                velocity: velocity.0,
+            });
+            hit_events.send(BallHitEvent {
+                player_number: racket.player_number,
+                position,
+                velocity: velocity.0,
+                smash: power,
+                along: contact.along,
+            });
+        }
+
+        // Of the walls it's moving into, the one it's the furthest into. Where
+        // two meet side by side the ball is only just into the second one,
+        // which mustn't turn it back the way it came
+        let wall = collider_query
+            .iter()
+            .filter_map(|(transform, moving, obstacle)| {
+                let center = transform.translation.truncate();
+                let size = extent(transform);
+                // A ball served from inside a moving wall is let out of it
+                // whichever way it's going
+                let inside = (position - center).abs().cmplt(size / 2.0).all();
+                if moving.is_some() && inside {
+                    return None;
+                }
+                let (collision, depth) = penetrate(position, ball_size, center, size)?;
+                Some((
+                    collision,
+                    depth,
+                    moving.map_or(Vec2::ZERO, |wall| wall.velocity),
+                    obstacle.is_some(),
+                ))
+            })
+            .filter(|(collision, _, moving, _)| heading_into(velocity.0 - *moving, collision))
+            .max_by(|(_, a, ..), (_, b, ..)| a.total_cmp(b));
+        if let Some((collision, _, moving, obstacle)) = wall.filter(|_| !bounced) {
+            // Off something moving, it bounces the way it's going as seen
+            // from that
+            velocity.0 -= moving;
+            reflect(&mut velocity, &collision);
+            // Landing on top of something takes a little out of the bounce,
+            // like on a table
+            if matches!(collision, Collision::Top) && rules.gravity.is_some() {
+                velocity.y *= config.table_restitution;
+            }
+            velocity.0 += moving;
+            if obstacle && matches!(collision, Collision::Top) {
+                if let Some(served) = served.as_mut() {
+                    served.clipped = true;
+                }
+            }
+            collision_events.send(CollisionEvent {
+                surface: Surface::Wall,
+                position,
+                velocity: velocity.0,
+            });
+        }
+
+        // The hitter's partner is up next
+        if let Some((hitter, player_number)) = hit_by.filter(|_| rules.alternate_hits) {
+            for (entity, racket, .., waiting) in racket_query.iter_mut() {
+                if let Some(mut waiting) = waiting.filter(|_| racket.player_number == player_number)
+                {
+                    waiting.0 = entity == hitter;
+                }
+            }
+        }
+
+        stats.top_speed = stats.top_speed.max(velocity.length());
+    }
+}
+
+/// Where a ball touches a racket.
+struct Contact {
+    /// Out of the racket, towards the ball
+    normal: Vec2,
+    /// Whether the ball hit one of the racket's long sides, rather than an end
+    on_face: bool,
+    /// How far from the racket's center the ball hit it, from -1 at the bottom
+    /// end to 1 at the top
+    along: f32,
+}
+
+/// Checks a ball of `radius` at `position` against a racket, which can be
+/// turned any which way.
+fn touch_racket(position: Vec2, radius: f32, transform: &Transform) -> Option<Contact> {
+    // In the racket's own terms, x runs along its length and y out of its faces
+    let length_axis = (transform.rotation * Vec3::X).truncate();
+    let face_axis = (transform.rotation * Vec3::Y).truncate();
+    let half_size = transform.scale.truncate() / 2.0;
+    let offset = position - transform.translation.truncate();
+    let local = Vec2::new(offset.dot(length_axis), offset.dot(face_axis));
+
+    let closest = local.clamp(-half_size, half_size);
+    if local.distance_squared(closest) > radius * radius {
+        return None;
+    }
+
+    let along = (local.x / half_size.x).clamp(-1.0, 1.0);
+    // A ball on a corner goes off whichever side it's more over, rather than
+    // straight out of the corner, which could send it off anywhere. It's
+    // level with the face or an end otherwise, and goes off that
+    let beyond = local - closest;
+    let on_face = beyond.x.abs() <= beyond.y.abs();
+    let local_normal = if on_face {
+        Vec2::new(0.0, local.y.signum())
+    } else {
+        Vec2::new(local.x.signum(), 0.0)
+    };
+    Some(Contact {
+        normal: length_axis * local_normal.x + face_axis * local_normal.y,
+        on_face,
+        along,
+    })
+}
+
+/// A side of a box something's touching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Collision {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Whether a box at `a` of `a_size` and one at `b` of `b_size` overlap at all.
+fn overlaps(a: Vec2, a_size: Vec2, b: Vec2, b_size: Vec2) -> bool {
+    let (a_min, a_max) = (a - a_size / 2.0, a + a_size / 2.0);
+    let (b_min, b_max) = (b - b_size / 2.0, b + b_size / 2.0);
+    a_min.x < b_max.x && a_max.x > b_min.x && a_min.y < b_max.y && a_max.y > b_min.y
+}
+
+/// Which side of a box at `center` of `size` a box of `moving_size` at
+/// `position`, like the ball, is touching, and how far it'd have to go to be
+/// out of it. That's the side it's the least far into, the way it most likely
+/// came in.
+fn penetrate(
+    position: Vec2,
+    moving_size: Vec2,
+    center: Vec2,
+    size: Vec2,
+) -> Option<(Collision, f32)> {
+    let offset = position - center;
+    let depth = (moving_size + size) / 2.0 - offset.abs();
+    if depth.x <= 0.0 || depth.y <= 0.0 {
+        return None;
+    }
+    // Straight in at a corner counts as the top or bottom
+    if depth.x < depth.y {
+        let side = if offset.x < 0.0 {
+            Collision::Left
+        } else {
+            Collision::Right
+        };
+        Some((side, depth.x))
+    } else {
+        let side = if offset.y > 0.0 {
+            Collision::Top
+        } else {
+            Collision::Bottom
+        };
+        Some((side, depth.y))
+    }
+}
+
+/// Which way is out of a surface, from the side of it something's touching.
+fn outwards(collision: &Collision) -> Vec2 {
+    match collision {
+        Collision::Left => Vec2::NEG_X,
+        Collision::Right => Vec2::X,
+        Collision::Top => Vec2::Y,
+        Collision::Bottom => Vec2::NEG_Y,
+    }
+}
+
+/// Whether the ball is moving towards the side of a surface it touched. It
+/// only bounces if it is, so it can't get stuck bouncing back and forth
+/// inside a collider.
+fn heading_into(velocity: Vec2, collision: &Collision) -> bool {
+    velocity.dot(outwards(collision)) < 0.0
+}
+
+/// Bounces the ball off a side of a surface.
+fn reflect(velocity: &mut Velocity, collision: &Collision) {
+    match collision {
+        Collision::Left | Collision::Right => velocity.x = -velocity.x,
+        Collision::Top | Collision::Bottom => velocity.y = -velocity.y,
+    }
+}
+
+fn check_for_goals(
+    mut commands: Commands,
+    ball_query: Query<(Entity, &Transform), (With<Ball>, Without<Netted>)>,
+    goal_query: Query<(Entity, &Transform, &Side), (With<Goal>, Without<Ball>)>,
+    mut racket_query: Query<(&mut RacketLength, &mut Waiting)>,
+    mut score: ResMut<Score>,
+    rules: Res<MatchRules>,
+    mut goal_events: EventWriter<GoalEvent>,
+) {
+    for (ball, ball_transform) in ball_query.iter() {
+        let ball_size = ball_transform.scale.truncate();
+
+        for (goal, transform, side) in goal_query.iter() {
+            if !overlaps(
+                ball_transform.translation.truncate(),
+                ball_size,
+                transform.translation.truncate(),
+                extent(transform),
+            ) {
+                continue;
+            }
+
+            award_point(
+                score.player_on(side.opponent()),
+                ball_transform.translation.truncate(),
+                &mut score,
+                &rules,
+                &mut racket_query,
+                &mut goal_events,
+            );
+            commands.entity(ball).insert(Netted {
+                goal,
+                conceded_by: *side,
+                timer: Timer::from_seconds(NET_SETTLE_TIME, TimerMode::Once),
+            });
+            break;
+        }
+    }
+}
+
+/// Gives `scored_by` the point the ball at `position` has won them, and gets
+/// the rackets ready for the next one.
+fn award_point(
+    scored_by: i32,
+    position: Vec2,
+    score: &mut Score,
+    rules: &MatchRules,
+    racket_query: &mut Query<(&mut RacketLength, &mut Waiting)>,
+    goal_events: &mut EventWriter<GoalEvent>,
+) {
+    score.add_point(scored_by);
+    if rules.reaches_golden_point(score) {
+        score.golden_point = true;
+    }
+    goal_events.send(GoalEvent {
+        position,
+        scored_by,
+    });
+    // Whoever gets to the next ball first can have it
+    for (mut length, mut waiting) in racket_query.iter_mut() {
+        length.current = length.full;
+        *waiting = Waiting::default();
+    }
+}
+
+/// Calls serves under [`MatchRules::serve_rules`]. One that's clipped an
+/// obstacle is a let, and served again from the center, and one that's come
+/// back over the center line is a fault. That's the receiver's point, and the
+/// ball stops where it is, settling as if it had gone into the server's net
+/// until the next serve goes to them.
+fn call_serves(
+    mut commands: Commands,
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Spin,
+            &mut Served,
+        ),
+        (With<Ball>, Without<Netted>),
+    >,
+    mut racket_query: Query<(&mut RacketLength, &mut Waiting)>,
+    bounds: Res<ArenaBounds>,
+    mut score: ResMut<Score>,
+    rules: Res<MatchRules>,
+    config: Res<GameplayConfig>,
+    mut rng: ResMut<SimRng>,
+    mut goal_events: EventWriter<GoalEvent>,
+    mut serve_events: EventWriter<ServeEvent>,
+    mut call_events: EventWriter<ServeCallEvent>,
+) {
+    if !rules.serve_rules {
+        return;
+    }
+    let center = (bounds.min.x + bounds.max.x) / 2.0;
+    for (ball, mut transform, mut velocity, mut spin, mut served) in ball_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let server = served.receiver.opponent();
+        // All the way back over, not just touching the line
+        let back_over = (position.x - center) * server.direction() > transform.scale.x / 2.0;
+        let call = if served.clipped {
+            ServeCall::Let
+        } else if back_over {
+            ServeCall::Fault
+        } else {
+            continue;
+        };
+        call_events.send(ServeCallEvent {
+            call,
+            position,
+            server: score.player_on(server),
+        });
+
+        spin.0 = 0.0;
+        match call {
+            ServeCall::Let => {
+                served.clipped = false;
+                transform.translation = BALL_STARTING_POSITION;
+                velocity.0 = if score.golden_point {
+                    golden_serve_velocity(served.receiver, &rules, &config, &mut *rng)
+                } else {
+                    serve_velocity(served.receiver, &rules, &config, &mut *rng)
+                };
+                serve_events.send(ServeEvent {
+                    position: transform.translation.truncate(),
+                    velocity: velocity.0,
+                });
+            }
+            ServeCall::Fault => {
+                award_point(
+                    score.player_on(served.receiver),
+                    position,
+                    &mut score,
+                    &rules,
+                    &mut racket_query,
+                    &mut goal_events,
+                );
+                velocity.0 = Vec2::ZERO;
+                // Not in a goal, so there's no net to keep it in
+                commands.entity(ball).remove::<Served>().insert(Netted {
+                    conceded_by: server,
+                    timer: Timer::from_seconds(NET_SETTLE_TIME, TimerMode::Once),
+                    ..default()
+                });
+            }
+        }
+    }
+}
+
+/// Counts the hits of each rally, and who won it, from what happened in the
+/// step.
+fn keep_stats(
+    mut hits: EventReader<BallHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut rally: ResMut<Rally>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for hit in hits.read() {
+        rally.hits += 1;
+        stats.player_mut(hit.player_number).hits += 1;
+    }
+    for goal in goals.read() {
+        let hits = std::mem::take(&mut rally.hits);
+        rally.finished.push(hits);
+        stats.end_rally(goal.scored_by, hits);
+    }
+}
+
+/// Builds up the momentum of the rackets of whoever scored, and takes it away
+/// from everyone else's.
+fn build_momentum(mut goals: EventReader<GoalEvent>, mut rackets: Query<(&Racket, &mut Momentum)>) {
+    for goal in goals.read() {
+        for (racket, mut momentum) in rackets.iter_mut() {
+            momentum.streak = if racket.player_number == goal.scored_by {
+                (momentum.streak + 1).min(MAX_MOMENTUM)
+            } else {
+                0
+            };
+        }
+    }
+}
+
+/// Slows a netted ball down inside the goal, then serves a fresh one from the
+/// center, or ends the match if that was the winning point. A point that wins
+/// a set has the players change ends first, so the serve goes to the other
+/// end.
+fn settle_in_net(
+    mut commands: Commands,
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Netted,
+            Option<&ServedOnce>,
+        ),
+        With<Ball>,
+    >,
+    goal_query: Query<&Transform, (With<Goal>, Without<Ball>)>,
+    mut score: ResMut<Score>,
+    rules: Res<MatchRules>,
+    config: Res<GameplayConfig>,
+    mut rng: ResMut<SimRng>,
+    mut match_over: ResMut<MatchOver>,
+    mut serve_events: EventWriter<ServeEvent>,
+    mut match_ended_events: EventWriter<MatchEndedEvent>,
+) {
+    for (ball, mut ball_transform, mut velocity, mut netted, served_once) in ball_query.iter_mut() {
+        velocity.0 *= rules.time_step.decay(NET_DRAG);
+
+        // Keep the ball between the goal line and the back of the net
+        if let Ok(goal_transform) = goal_query.get(netted.goal) {
+            let ball_half_width = ball_transform.scale.x / 2.0;
+            let net_half_depth = extent(goal_transform).x / 2.0;
+            let back = goal_transform.translation.x
+                + netted.conceded_by.direction() * (net_half_depth - ball_half_width);
+            let past_back = (ball_transform.translation.x - back) * netted.conceded_by.direction();
+            if past_back > 0.0 {
+                ball_transform.translation.x = back;
+                velocity.x = -velocity.x * NET_RESTITUTION;
+            }
+        }
+
+        netted
+            .timer
+            .tick(Duration::from_secs_f32(rules.time_step.seconds()));
+        if !netted.timer.just_finished() {
+            continue;
+        }
+        if served_once.is_some() {
+            commands.entity(ball).despawn();
+            continue;
+        }
+
+        // The serve goes to whoever conceded, wherever they're standing now
+        let mut receiver = netted.conceded_by;
+        if let Some(set_winner) = rules.set_winner(&score) {
+            score.win_set(set_winner, &rules);
+            if let Some(winner) = rules.match_winner(&score) {
+                match_over.0 = Some(winner);
+                match_ended_events.send(MatchEndedEvent { winner });
+                continue;
+            }
+            // Their rackets are sent over by `swap_sides` straight after
+            receiver = receiver.opponent();
+        }
+
+        ball_transform.translation = BALL_STARTING_POSITION;
+        velocity.0 = if score.golden_point {
+            golden_serve_velocity(receiver, &rules, &config, &mut *rng)
+        } else {
+            serve_velocity(receiver, &rules, &config, &mut *rng)
+        };
+        commands
+            .entity(ball)
+            .remove::<Netted>()
+            .insert(Served::new(receiver));
+        serve_events.send(ServeEvent {
+            position: ball_transform.translation.truncate(),
+            velocity: velocity.0,
+        });
+    }
+}
+
+/// Keeps every racket at the end of the court its player is playing from in
+/// the set being played, sending it across to the same spot on the other
+/// side when the ends change. A racket tilted up on one side is still tilted
+/// up on the other, so the tilts go on doing what they did. Since it follows
+/// the score, a racket is sent back over too when rollback or a replay puts
+/// the score back to before the ends changed.
+fn swap_sides(
+    mut rackets: Query<(&Racket, &mut Side, &Tilt, &mut Transform)>,
+    bounds: Res<ArenaBounds>,
+    score: Res<Score>,
+) {
+    let center = (bounds.min.x + bounds.max.x) / 2.0;
+    for (racket, mut side, tilt, mut transform) in rackets.iter_mut() {
+        let end = score.side_of(racket.player_number);
+        if *side == end {
+            continue;
+        }
+        *side = end;
+        transform.translation.x = 2.0 * center - transform.translation.x;
+        transform.rotation = racket_rotation(end, tilt.0);
+    }
+}
+
+/// Counts a timed match down, and ends it when time's up: whoever's ahead
+/// wins, and a tie goes to a golden point.
+fn run_clock(
+    rules: Res<MatchRules>,
+    mut clock: ResMut<MatchClock>,
+    mut score: ResMut<Score>,
+    mut match_over: ResMut<MatchOver>,
+    mut match_ended_events: EventWriter<MatchEndedEvent>,
+) {
+    if clock.steps_left == 0 {
+        return;
+    }
+    clock.steps_left -= 1;
+    if clock.steps_left > 0 || match_over.0.is_some() {
+        return;
+    }
+
+    let leader = match score.player1.cmp(&score.player2) {
+        Ordering::Greater => 1,
+        Ordering::Less => 2,
+        Ordering::Equal => {
+            score.golden_point = true;
+            return;
+        }
+    };
+    score.win_set(leader, &rules);
+    match_over.0 = Some(leader);
+    match_ended_events.send(MatchEndedEvent { winner: leader });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::world::CommandQueue,
+        time::{TimePlugin, TimeUpdateStrategy},
+    };
+    use proptest::{
+        collection,
+        prelude::{any, prop_assert, proptest, ProptestConfig},
+        sample::select,
+    };
+
+    use super::*;
+    use crate::{
+        arena::{spawn_layout, Arena},
+        seams::{read_input, FixedRng, ScriptedInput},
+    };
+
+    const STEPS: usize = 3600;
+
+    fn scripted_input(step: usize) -> SimInput {
+        // Something irregular enough to get both rackets all over the court
+        let left = RacketInput {
+            up: step % 97 < 40,
+            down: step % 61 > 35,
+            smash: step % 173 < 50,
+            tilt_up: step % 89 < 20,
+            tilt_down: step % 113 > 80,
+            dash: step % 149 < 5,
+        };
+        let right = RacketInput {
+            up: (step / 13).is_multiple_of(3),
+            down: (step / 7) % 5 == 1,
+            smash: step % 131 > 90,
+            tilt_up: (step / 11) % 7 == 2,
+            tilt_down: (step / 17) % 4 == 3,
+            dash: step % 211 == 7,
+        };
+        SimInput([left, right, default(), default()])
+    }
+
+    fn classic_arena() -> Arena {
+        let bytes = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/arenas/classic.arena.ron"
+        ))
+        .unwrap();
+        ron::de::from_bytes(&bytes).unwrap()
+    }
+
+    fn simulation(seed: u64) -> App {
+        let arena = classic_arena();
+        let mut app = App::new();
+        app.insert_resource(MatchRules {
+            points_to_win: 1000,
+            ..default()
+        })
+        .insert_resource(arena.bounds())
+        .init_resource::<Score>()
+        .init_resource::<Rally>()
+        .init_resource::<MatchStats>()
+        .init_resource::<GameplayConfig>()
+        .init_resource::<MatchOver>()
+        .init_resource::<MatchClock>()
+        .init_resource::<SimInput>()
+        .init_resource::<Wind>()
+        .init_resource::<Portals>()
+        .insert_resource(SimRng::new(seed))
+        .add_event::<CollisionEvent>()
+        .add_event::<GoalEvent>()
+        .add_event::<BallHitEvent>()
+        .add_event::<ServeEvent>()
+        .add_event::<ServeCallEvent>()
+        .add_event::<MatchEndedEvent>()
+        .add_systems(Update, systems());
+        defer_commands(&mut app, Update);
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, app.world());
+        spawn_layout(&mut commands, &arena);
+        let mut rng = SimRng::new(seed);
+        let velocity = serve_velocity(
+            Side::Right,
+            &MatchRules::default(),
+            &GameplayConfig::default(),
+            &mut rng,
+        );
+        launch_ball(
+            &mut commands,
+            BallKind::Standard,
+            BALL_STARTING_POSITION.truncate(),
+            velocity,
+        );
+        queue.apply(app.world_mut());
+        app.insert_resource(rng);
+
+        app
+    }
+
+    /// Every number the simulation produced, as raw bits.
+    fn snapshot(app: &mut App) -> Vec<u32> {
+        let mut bits = Vec::new();
+        let mut balls = app
+            .world_mut()
+            .query_filtered::<(&Transform, &Velocity), With<Ball>>();
+        for (transform, velocity) in balls.iter(app.world()) {
+            bits.extend(transform.translation.to_array().map(f32::to_bits));
+            bits.extend(velocity.to_array().map(f32::to_bits));
+        }
+        let mut rackets = app.world_mut().query::<(&Racket, &Transform)>();
+        let mut rackets: Vec<_> = rackets.iter(app.world()).collect();
+        rackets.sort_by_key(|(racket, _)| racket.player_number);
+        for (_, transform) in rackets {
+            bits.extend(transform.translation.to_array().map(f32::to_bits));
+        }
+        let score = app.world().resource::<Score>();
+        bits.extend([score.player1 as u32, score.player2 as u32]);
+        bits
+    }
+
+    fn run(seed: u64) -> (Vec<Vec<u32>>, Score, Vec<u32>) {
+        let mut app = simulation(seed);
+        let mut history = Vec::with_capacity(STEPS);
+        for step in 0..STEPS {
+            app.insert_resource(scripted_input(step));
+            app.update();
+            history.push(snapshot(&mut app));
+        }
+        let score = *app.world().resource::<Score>();
+        let rallies = app.world().resource::<Rally>().finished.clone();
+        (history, score, rallies)
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_bit_identical_matches() {
+        let (first, first_score, first_rallies) = run(0x5eed);
+        let (second, second_score, second_rallies) = run(0x5eed);
+
+        // Make sure the script actually plays some points
+        assert!(first_score.player1 + first_score.player2 > 0);
+
+        for (step, (a, b)) in first.iter().zip(&second).enumerate() {
+            assert_eq!(a, b, "diverged at step {}", step);
+        }
+        assert_eq!(first_score, second_score);
+        assert_eq!(first_rallies, second_rallies);
+    }
+
+    #[test]
+    fn lost_balls_are_served_again_without_a_point() {
+        for lost in [
+            Vec3::new(0.0, 1000.0, 1.0),
+            Vec3::new(-2000.0, 0.0, 1.0),
+            Vec3::new(f32::NAN, 0.0, 1.0),
+        ] {
+            let mut app = simulation(0x5eed);
+            let mut balls = app
+                .world_mut()
+                .query_filtered::<&mut Transform, With<Ball>>();
+            balls.single_mut(app.world_mut()).translation = lost;
+            app.update();
+
+            let mut balls = app
+                .world_mut()
+                .query_filtered::<(&Transform, &Velocity), With<Ball>>();
+            let (transform, velocity) = balls.single(app.world());
+            let bounds = classic_arena().bounds();
+            assert!(transform.translation.x.abs() < bounds.max.x, "{}", lost);
+            assert!(velocity.length() > 0.0);
+            assert_eq!(*app.world().resource::<Score>(), Score::default());
+        }
+    }
+
+    /// The match's one ball, made a serve to the right with serve rules on,
+    /// and put at `position` going at `velocity`.
+    fn serving(position: Vec2, velocity: Vec2) -> (App, Entity) {
+        let mut app = simulation(0x5eed);
+        app.world_mut().resource_mut::<MatchRules>().serve_rules = true;
+        let mut balls = app.world_mut().query_filtered::<Entity, With<Ball>>();
+        let ball = balls.single(app.world());
+        app.world_mut()
+            .entity_mut(ball)
+            .insert(Served::new(Side::Right))
+            .insert(Velocity(velocity))
+            .get_mut::<Transform>()
+            .unwrap()
+            .translation = position.extend(BALL_STARTING_POSITION.z);
+        (app, ball)
+    }
+
+    fn calls(app: &App) -> Vec<ServeCall> {
+        let events = app.world().resource::<Events<ServeCallEvent>>();
+        events
+            .get_reader()
+            .read(events)
+            .map(|event| event.call)
+            .collect()
+    }
+
+    #[test]
+    fn a_serve_back_over_the_center_line_is_a_fault() {
+        let (mut app, ball) = serving(Vec2::new(-40.0, 0.0), Vec2::new(-BALL_SPEED, 0.0));
+        app.update();
+        assert_eq!(calls(&app), [ServeCall::Fault]);
+        assert_eq!(app.world().resource::<Score>().player2, 1);
+        assert_eq!(app.world().get::<Velocity>(ball).unwrap().0, Vec2::ZERO);
+        assert!(app.world().get::<Netted>(ball).is_some());
+        assert!(app.world().get::<Served>(ball).is_none());
+
+        // Nothing's called without the rules
+        let (mut app, _) = serving(Vec2::new(-40.0, 0.0), Vec2::new(-BALL_SPEED, 0.0));
+        app.world_mut().resource_mut::<MatchRules>().serve_rules = false;
+        app.update();
+        assert!(calls(&app).is_empty());
+        assert_eq!(*app.world().resource::<Score>(), Score::default());
+    }
+
+    #[test]
+    fn a_serve_clipping_an_obstacle_is_a_let() {
+        // Coming down onto the top of it
+        let (mut app, ball) = serving(Vec2::new(200.0, 33.0), Vec2::new(100.0, -200.0));
+        app.world_mut().spawn((
+            Collider,
+            Obstacle,
+            Transform {
+                translation: Vec3::new(200.0, 0.0, 0.0),
+                scale: Vec3::new(40.0, 40.0, 0.0),
+                ..default()
+            },
+        ));
+        app.update();
+        assert_eq!(calls(&app), [ServeCall::Let]);
+        assert_eq!(*app.world().resource::<Score>(), Score::default());
+        let transform = app.world().get::<Transform>(ball).unwrap();
+        assert_eq!(transform.translation, BALL_STARTING_POSITION);
+        assert!(app.world().get::<Velocity>(ball).unwrap().x > 0.0);
+        assert!(app.world().get::<Served>(ball).is_some());
+    }
+
+    /// A ball at `position` going at `velocity` in a match with a center net,
+    /// on the bottom of the court with `gravity`.
+    fn over_the_net(gravity: bool, position: Vec2, velocity: Vec2) -> (App, Entity) {
+        let mut app = simulation(0x5eed);
+        let rules = MatchRules {
+            points_to_win: 1000,
+            gravity: gravity.then_some(GRAVITY),
+            center_net: true,
+            ..default()
+        };
+        app.insert_resource(rules);
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, app.world());
+        spawn_center_net(&mut commands, &rules);
+        queue.apply(app.world_mut());
+
+        let mut balls = app.world_mut().query_filtered::<Entity, With<Ball>>();
+        let ball = balls.single(app.world());
+        app.world_mut()
+            .entity_mut(ball)
+            .insert(Velocity(velocity))
+            .get_mut::<Transform>()
+            .unwrap()
+            .translation = position.extend(BALL_STARTING_POSITION.z);
+        (app, ball)
+    }
+
+    #[test]
+    fn flat_shots_brush_the_center_net() {
+        let velocity = Vec2::new(BALL_SPEED, 50.0);
+        let (mut app, ball) = over_the_net(false, Vec2::new(-2.0, 0.0), velocity);
+        app.update();
+        let brushed = app.world().get::<Velocity>(ball).unwrap().0;
+        assert!((brushed.length() - velocity.length() * NET_BRUSH_SPEED).abs() < 1e-3);
+        assert!(brushed.angle_between(velocity).abs() <= MAX_NET_DEFLECTION + 1e-6);
+
+        // With spin on it, it goes over clean
+        let (mut app, ball) = over_the_net(false, Vec2::new(-2.0, 0.0), velocity);
+        app.world_mut()
+            .entity_mut(ball)
+            .insert(Spin(FLAT_SPIN * 2.0));
+        app.update();
+        let spun = app.world().get::<Velocity>(ball).unwrap().0;
+        assert!((spun.length() - velocity.length()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn low_balls_have_to_clear_the_center_net_with_gravity() {
+        let bounds = classic_arena().bounds();
+        let low = Vec2::new(-(CENTER_NET_THICKNESS / 2.0 + 16.0), bounds.min.y + 25.0);
+        let (mut app, ball) = over_the_net(true, low, Vec2::new(BALL_SPEED, 0.0));
+        app.update();
+        assert!(app.world().get::<Velocity>(ball).unwrap().x < 0.0);
+
+        let high = Vec2::new(low.x, bounds.min.y + CENTER_NET_HEIGHT + 50.0);
+        let (mut app, ball) = over_the_net(true, high, Vec2::new(BALL_SPEED, 0.0));
+        app.update();
+        assert!(app.world().get::<Velocity>(ball).unwrap().x > 0.0);
+    }
+
+    #[test]
+    fn resumed_balls_are_served_when_they_would_have_been() {
+        let netted = Netted::resumed(Entity::from_raw(0), Side::Right, 0.25);
+        assert!((netted.time_to_serve() - 0.25).abs() < 1e-6);
+        assert_eq!(netted.conceded_by(), Side::Right);
+        // Never further from its serve than a ball that's just gone in
+        let netted = Netted::resumed(Entity::from_raw(0), Side::Left, NET_SETTLE_TIME * 2.0);
+        assert!((netted.time_to_serve() - NET_SETTLE_TIME).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sets_go_to_deuce() {
+        let rules = MatchRules::default();
+        let score = |player1, player2| Score {
+            player1,
+            player2,
+            ..default()
+        };
+        assert_eq!(rules.set_winner(&score(11, 9)), Some(1));
+        assert_eq!(rules.set_winner(&score(11, 10)), None);
+        assert_eq!(rules.set_winner(&score(12, 14)), Some(2));
+        assert_eq!(rules.set_winner(&score(10, 0)), None);
+    }
+
+    #[test]
+    fn golden_point_decides_the_set() {
+        let rules = MatchRules {
+            golden_point: Some(10),
+            ..default()
+        };
+        let mut score = Score {
+            player1: 10,
+            player2: 9,
+            ..default()
+        };
+        score.add_point(2);
+        assert!(rules.reaches_golden_point(&score));
+        score.golden_point = true;
+        assert_eq!(rules.set_winner(&score), None);
+        score.add_point(2);
+        assert_eq!(rules.set_winner(&score), Some(2));
+    }
+
+    #[test]
+    fn match_point_is_a_point_from_the_match() {
+        let rules = MatchRules::default();
+        let score = |player1, player2, player1_sets| Score {
+            player1,
+            player2,
+            player1_sets,
+            ..default()
+        };
+        assert!(!rules.is_match_point(&score(10, 5, 0)));
+        assert!(rules.is_match_point(&score(10, 5, 1)));
+        assert!(!rules.is_match_point(&score(5, 10, 1)));
+        assert!(!rules.is_match_point(&score(10, 10, 1)));
+        assert!(rules.is_match_point(&score(11, 10, 1)));
+    }
+
+    #[test]
+    fn stakes_go_up_to_match_point() {
+        let rules = MatchRules::default();
+        let score = |player1, player2, player1_sets| Score {
+            player1,
+            player2,
+            player1_sets,
+            ..default()
+        };
+        assert_eq!(rules.stakes(&score(5, 3, 0)), None);
+        assert_eq!(rules.stakes(&score(10, 5, 0)), Some(Stakes::SetPoint));
+        assert_eq!(rules.stakes(&score(10, 10, 0)), Some(Stakes::Deuce));
+        assert_eq!(rules.stakes(&score(12, 12, 1)), Some(Stakes::Deuce));
+        assert_eq!(rules.stakes(&score(12, 11, 0)), Some(Stakes::SetPoint));
+        assert_eq!(rules.stakes(&score(12, 11, 1)), Some(Stakes::MatchPoint));
+        // Player 2 could only take the set, and that'd leave it one all
+        assert_eq!(rules.stakes(&score(5, 10, 1)), Some(Stakes::SetPoint));
+
+        // The next point after a golden one's tie takes the set, so there's
+        // no deuce about it
+        let golden = MatchRules {
+            golden_point: Some(10),
+            ..default()
+        };
+        let score = Score {
+            player1: 10,
+            player2: 10,
+            golden_point: true,
+            ..default()
+        };
+        assert_eq!(golden.stakes(&score), Some(Stakes::SetPoint));
+    }
+
+    #[test]
+    fn time_running_out_ends_a_blitz() {
+        let rules = MatchRules::blitz(1);
+        let mut app = App::new();
+        app.insert_resource(MatchClock::new(&rules))
+            .insert_resource(rules)
+            .insert_resource(Score {
+                player1: 3,
+                player2: 3,
+                ..default()
+            })
+            .init_resource::<MatchOver>()
+            .add_event::<MatchEndedEvent>()
+            .add_systems(Update, run_clock);
+
+        for _ in 0..rules.time_step.steps(1.0) {
+            app.update();
+        }
+        // A tie plays on until the next point
+        assert!(app.world().resource::<Score>().golden_point);
+        assert_eq!(app.world().resource::<MatchOver>().0, None);
+
+        let mut score = *app.world().resource::<Score>();
+        score.add_point(1);
+        assert_eq!(rules.set_winner(&score), Some(1));
+    }
+
+    #[test]
+    fn last_life_lost_ends_an_arcade_match() {
+        let rules = MatchRules::arcade(3);
+        let mut score = Score::default();
+        score.add_point(2);
+        score.add_point(2);
+        score.add_point(1);
+        assert_eq!(rules.lives_left(&score, 1), Some(1));
+        assert_eq!(rules.lives_left(&score, 2), Some(2));
+        assert_eq!(rules.set_winner(&score), None);
+        score.add_point(2);
+        assert_eq!(rules.lives_left(&score, 1), Some(0));
+        assert_eq!(rules.set_winner(&score), Some(2));
+        assert_eq!(MatchRules::default().lives_left(&score, 1), None);
+    }
+
+    #[test]
+    fn head_starts_count_in_every_set() {
+        let rules = MatchRules {
+            handicaps: [
+                Handicap {
+                    head_start: 3,
+                    ..default()
+                },
+                default(),
+            ],
+            ..default()
+        };
+        let mut score = Score::starting(&rules);
+        assert_eq!((score.player1, score.player2), (3, 0));
+        score.add_points(2, 11);
+        assert_eq!(rules.set_winner(&score), Some(2));
+        score.win_set(2, &rules);
+        assert_eq!(
+            (score.player1, score.player2, score.player2_sets),
+            (3, 0, 1)
+        );
+    }
+
+    #[test]
+    fn handicaps_keep_rackets_playable() {
+        let rules = MatchRules {
+            handicaps: [
+                Handicap {
+                    racket_length: 0.0,
+                    racket_speed: 50.0,
+                    ..default()
+                },
+                Handicap {
+                    racket_length: 1.5,
+                    ..default()
+                },
+            ],
+            ..default()
+        };
+        assert_eq!(rules.handicap(1).racket_length, HANDICAP_LIMITS.0);
+        assert_eq!(rules.handicap(1).racket_speed, HANDICAP_LIMITS.1);
+        assert_eq!(rules.handicap(2).racket_length, 1.5);
+    }
+
+    #[test]
+    fn smash_goes_off_when_let_go_of() {
+        let mut app = App::new();
+        app.init_resource::<SimInput>()
+            .init_resource::<MatchRules>()
+            .add_systems(Update, charge_smashes);
+        let racket = app
+            .world_mut()
+            .spawn((Racket::new(1), Smash::default()))
+            .id();
+        let hold = |app: &mut App, smash: bool, steps: u32| {
+            app.world_mut().resource_mut::<SimInput>().0[0].smash = smash;
+            for _ in 0..steps {
+                app.update();
+            }
+            *app.world().get::<Smash>(racket).unwrap()
+        };
+
+        let step = TimeStep::default();
+        let full = step.steps(SMASH_CHARGE_TIME);
+        let smash = hold(&mut app, true, full / 2);
+        assert!((smash.level(step) - 0.5).abs() < 0.01);
+        assert!(!smash.is_released());
+        // Charging stops at full
+        assert_eq!(hold(&mut app, true, full).level(step), 1.0);
+
+        let smash = hold(&mut app, false, 1);
+        assert!(smash.is_released());
+        assert_eq!(smash.level(step), 1.0);
+        // Nothing came along to hit in time
+        let smash = hold(&mut app, false, step.steps(SMASH_WINDOW));
+        assert!(!smash.is_released());
+        assert_eq!(smash.level(step), 0.0);
+    }
+
+    #[test]
+    fn momentum_builds_on_points_in_a_row() {
+        let mut app = App::new();
+        app.add_event::<GoalEvent>()
+            .add_systems(Update, build_momentum);
+        let rackets = [1, 2].map(|player| {
+            app.world_mut()
+                .spawn((Racket::new(player), Momentum::default()))
+                .id()
+        });
+        let score = |app: &mut App, scored_by: i32| {
+            app.world_mut()
+                .resource_mut::<Events<GoalEvent>>()
+                .send(GoalEvent {
+                    position: Vec2::ZERO,
+                    scored_by,
+                });
+            app.update();
+            rackets.map(|racket| *app.world().get::<Momentum>(racket).unwrap())
+        };
+
+        for _ in 0..MAX_MOMENTUM + 1 {
+            score(&mut app, 1);
+        }
+        let [first, second] = score(&mut app, 1);
+        assert!(first.is_full());
+        assert_eq!(first.level(), 1.0);
+        assert_eq!(second.level(), 0.0);
+        // Losing a point loses it all
+        let [first, second] = score(&mut app, 2);
+        assert_eq!(first.level(), 0.0);
+        assert!(second.level() > 0.0 && !second.is_full());
+    }
+
+    #[test]
+    fn dash_is_pressed_for_and_then_cools_down() {
+        let mut app = App::new();
+        app.init_resource::<SimInput>()
+            .init_resource::<MatchRules>()
+            .add_systems(Update, start_dashes);
+        let racket = app
+            .world_mut()
+            .spawn((Racket::new(1), Dash::default()))
+            .id();
+        let hold = |app: &mut App, dash: bool, steps: u32| {
+            app.world_mut().resource_mut::<SimInput>().0[0].dash = dash;
+            for _ in 0..steps {
+                app.update();
+            }
+            *app.world().get::<Dash>(racket).unwrap()
+        };
+
+        let step = TimeStep::default();
+        assert!(hold(&mut app, true, 1).is_dashing());
+        // Holding the key on doesn't make the dash last
+        let dash = hold(&mut app, true, step.steps(DASH_TIME));
+        assert!(!dash.is_dashing());
+        assert!(dash.cooldown(step) > 0.9);
+        // Nor dash again once it's cooled down
+        let dash = hold(&mut app, true, step.steps(DASH_COOLDOWN));
+        assert_eq!(dash.cooldown(step), 0.0);
+        assert!(!dash.is_dashing());
+        // It takes pressing again
+        hold(&mut app, false, 1);
+        assert!(hold(&mut app, true, 1).is_dashing());
+    }
+
+    #[test]
+    fn game_speed_scales_how_often_steps_come() {
+        #[derive(Resource, Default)]
+        struct Stepped(u32);
+        fn count(mut stepped: ResMut<Stepped>) {
+            stepped.0 += 1;
+        }
+
+        let second = |speed: f32, rate: u32| {
+            let mut app = App::new();
+            app.add_plugins((TimePlugin, FixedStepPlugin))
+                .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                    1.0 / 30.0,
+                )))
+                .init_resource::<Stepped>()
+                .insert_resource(GameSpeed(speed))
+                .insert_resource(MatchRules {
+                    time_step: TimeStep { rate, ..default() },
+                    ..default()
+                })
+                .add_systems(FixedUpdate, count);
+            // A second of frames at 30 a second, after the one the clock
+            // starts on
+            for _ in 0..31 {
+                app.update();
+            }
+            app.world().resource::<Stepped>().0
+        };
+
+        let full = STEP_RATES[0];
+        assert!(second(1.0, full).abs_diff(full) <= 1);
+        assert!(second(0.5, full).abs_diff(full / 2) <= 1);
+        assert!(second(1.5, full).abs_diff(full * 3 / 2) <= 1);
+        // Faster steps come more often, a few of them to a frame
+        assert!(second(1.0, 240).abs_diff(240) <= 1);
+    }
+
+    #[test]
+    fn substeps_stop_the_ball_at_what_it_meets() {
+        let run = |substeps: u32| {
+            let mut app = App::new();
+            app.insert_resource(MatchRules {
+                time_step: TimeStep {
+                    substeps,
+                    ..default()
+                },
+                ..default()
+            })
+            .add_systems(Update, apply_velocity);
+            // A thin wall the ball would be right through in a step
+            app.world_mut().spawn((
+                Collider,
+                Transform {
+                    translation: Vec3::new(20.0, 0.0, 0.0),
+                    scale: Vec3::new(4.0, 100.0, 1.0),
+                    ..default()
+                },
+            ));
+            let step = TimeStep::default();
+            let ball = app
+                .world_mut()
+                .spawn((
+                    Transform {
+                        scale: Vec3::new(10.0, 10.0, 1.0),
+                        ..default()
+                    },
+                    Velocity(Vec2::new(40.0 / step.seconds(), 0.0)),
+                ))
+                .id();
+            app.update();
+            app.world().get::<Transform>(ball).unwrap().translation.x
+        };
+
+        assert!((run(1) - 40.0).abs() < 1e-3);
+        let stopped = run(MAX_SUBSTEPS);
+        assert!(stopped > 10.0 && stopped < 20.0, "{}", stopped);
+    }
+
+    #[test]
+    fn time_step_keeps_to_the_rates_there_are() {
+        let odd = TimeStep {
+            rate: 100,
+            substeps: 0,
+        };
+        assert_eq!(odd.clamped(), TimeStep::default());
+        let fast = TimeStep {
+            rate: 240,
+            substeps: 4,
+        };
+        assert_eq!(fast.clamped(), fast);
+        assert_eq!(fast.steps(1.0), 4 * TimeStep::default().steps(1.0));
+        // Spin wears off as fast in a second at any rate
+        let kept = |step: TimeStep| step.decay(SPIN_DECAY).powi(step.rate as i32);
+        assert!((kept(fast) - kept(TimeStep::default())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn tilted_racket_turns_its_face() {
+        let racket = Transform {
+            rotation: racket_rotation(Side::Left, MAX_TILT),
+            scale: Vec3::new(RACKET_LENGTH, RACKET_THICCNESS, 0.0),
+            ..default()
+        };
+        let face = Vec2::new(MAX_TILT.cos(), MAX_TILT.sin());
+
+        // Just in front of the middle of the face
+        let contact = touch_racket(face * RACKET_THICCNESS / 2.0, 5.0, &racket).unwrap();
+        assert!(contact.on_face);
+        assert!(contact.normal.distance(face) < 1e-5);
+        assert!(contact.along.abs() < 1e-5);
+
+        // Past the top end, which is tipped back away from the court
+        let top = Vec2::new(-MAX_TILT.sin(), MAX_TILT.cos()) * RACKET_LENGTH / 2.0;
+        let contact = touch_racket(top * 1.05, 10.0, &racket).unwrap();
+        assert!(!contact.on_face);
+        assert!(touch_racket(face * RACKET_THICCNESS, 5.0, &racket).is_none());
+    }
+
+    #[test]
+    fn returns_shrink_the_racket_down_to_a_limit() {
+        let mut app = App::new();
+        app.insert_resource(MatchRules {
+            shrinking: true,
+            ..default()
+        })
+        .init_resource::<Rally>()
+        .init_resource::<MatchStats>()
+        .init_resource::<GameplayConfig>()
+        .add_event::<CollisionEvent>()
+        .add_event::<BallHitEvent>()
+        .add_event::<GoalEvent>()
+        .add_systems(
+            Update,
+            (check_for_collisions, keep_stats.after(check_for_collisions)),
+        );
+        let racket = app
+            .world_mut()
+            .spawn((
+                Racket::new(1),
+                RacketLength::default(),
+                Transform {
+                    rotation: racket_rotation(Side::Left, 0.0),
+                    scale: Vec3::new(RACKET_LENGTH, RACKET_THICCNESS, 0.0),
+                    ..default()
+                },
+            ))
+            .id();
+        // Touching the middle of the face, on its way in
+        let ball = app
+            .world_mut()
+            .spawn((
+                Ball,
+                Spin::default(),
+                Transform {
+                    translation: Vec3::new(RACKET_THICCNESS / 2.0 + 10.0, 0.0, 1.0),
+                    scale: BALL_SIZE,
+                    ..default()
+                },
+            ))
+            .id();
+        let hit = |app: &mut App| {
+            app.world_mut()
+                .entity_mut(ball)
+                .insert(Velocity(Vec2::new(-BALL_SPEED, 0.0)));
+            app.update();
+            app.world().get::<RacketLength>(racket).unwrap().current
+        };
+
+        assert_eq!(hit(&mut app), RACKET_LENGTH * (1.0 - RACKET_SHRINK));
+        for _ in 0..20 {
+            hit(&mut app);
+        }
+        assert_eq!(hit(&mut app), MIN_RACKET_LENGTH);
+        assert_eq!(app.world().resource::<Rally>().hits, 22);
+    }
+
+    #[test]
+    fn partners_take_turns_at_the_ball() {
+        let mut app = App::new();
+        app.insert_resource(MatchRules {
+            alternate_hits: true,
+            ..default()
+        })
+        .init_resource::<Rally>()
+        .init_resource::<MatchStats>()
+        .init_resource::<GameplayConfig>()
+        .add_event::<CollisionEvent>()
+        .add_event::<BallHitEvent>()
+        .add_event::<GoalEvent>()
+        .add_systems(
+            Update,
+            (check_for_collisions, keep_stats.after(check_for_collisions)),
+        );
+        let racket = |app: &mut App, racket: Racket, x: f32| {
+            app.world_mut()
+                .spawn((
+                    racket,
+                    Waiting::default(),
+                    Transform {
+                        translation: Vec3::new(x, 0.0, 0.0),
+                        rotation: racket_rotation(Side::Left, 0.0),
+                        scale: Vec3::new(RACKET_LENGTH, RACKET_THICCNESS, 0.0),
+                    },
+                ))
+                .id()
+        };
+        let front = racket(&mut app, Racket::partner(1), 0.0);
+        let back = racket(&mut app, Racket::new(1), -300.0);
+        let incoming = Vec2::new(-BALL_SPEED, 0.0);
+        // Touching the middle of the front racket's face
+        let ball = app
+            .world_mut()
+            .spawn((
+                Ball,
+                Spin::default(),
+                Velocity(incoming),
+                Transform {
+                    translation: Vec3::new(RACKET_THICCNESS / 2.0 + 10.0, 0.0, 1.0),
+                    scale: BALL_SIZE,
+                    ..default()
+                },
+            ))
+            .id();
+
+        app.update();
+        assert!(app.world().get::<Velocity>(ball).unwrap().x > 0.0);
+        assert!(app.world().get::<Waiting>(front).unwrap().0);
+        assert!(!app.world().get::<Waiting>(back).unwrap().0);
+
+        // Back again before the partner's had a go, and straight through
+        app.world_mut().entity_mut(ball).insert(Velocity(incoming));
+        app.update();
+        assert_eq!(app.world().get::<Velocity>(ball).unwrap().0, incoming);
+        assert_eq!(app.world().resource::<Rally>().hits, 1);
+    }
+
+    /// A world with just collisions in it, and a ball at `position` going at
+    /// `velocity`.
+    fn collisions(position: Vec2, velocity: Vec2) -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<MatchRules>()
+            .init_resource::<Rally>()
+            .init_resource::<MatchStats>()
+            .init_resource::<GameplayConfig>()
+            .add_event::<CollisionEvent>()
+            .add_event::<BallHitEvent>()
+            .add_event::<GoalEvent>()
+            .add_systems(
+                Update,
+                (check_for_collisions, keep_stats.after(check_for_collisions)),
+            );
+        let ball = app
+            .world_mut()
+            .spawn((
+                Ball,
+                Spin::default(),
+                Velocity(velocity),
+                Transform {
+                    translation: position.extend(1.0),
+                    scale: BALL_SIZE,
+                    ..default()
+                },
+            ))
+            .id();
+        (app, ball)
+    }
+
+    fn bounces(app: &App) -> Vec<Surface> {
+        let events = app.world().resource::<Events<CollisionEvent>>();
+        events
+            .get_reader()
+            .read(events)
+            .map(|event| event.surface)
+            .collect()
+    }
+
+    fn spawn_wall(app: &mut App, position: Vec2, size: Vec2) {
+        app.world_mut().spawn((
+            Collider,
+            Transform {
+                translation: position.extend(0.0),
+                scale: size.extend(0.0),
+                ..default()
+            },
+        ));
+    }
+
+    fn spawn_racket(app: &mut App) {
+        app.world_mut().spawn((
+            Racket::new(1),
+            Transform {
+                rotation: racket_rotation(Side::Left, 0.0),
+                scale: Vec3::new(RACKET_LENGTH, RACKET_THICCNESS, 0.0),
+                ..default()
+            },
+        ));
+    }
+
+    #[test]
+    fn a_return_up_against_a_wall_only_bounces_off_the_racket() {
+        // On the face near its top end, and into the wall just above it
+        let (mut app, ball) = collisions(
+            Vec2::new(RACKET_THICCNESS / 2.0 + 10.0, 50.0),
+            Vec2::new(-BALL_SPEED, 100.0),
+        );
+        spawn_racket(&mut app);
+        spawn_wall(&mut app, Vec2::new(0.0, 75.0), Vec2::new(400.0, 30.0));
+
+        app.update();
+        assert!(app.world().get::<Velocity>(ball).unwrap().x > 0.0);
+        assert_eq!(bounces(&app), [Surface::Racket]);
+        assert_eq!(app.world().resource::<Rally>().hits, 1);
+    }
+
+    #[test]
+    fn walls_side_by_side_dont_turn_the_ball_back() {
+        // Along the underside of two walls, only just into the second
+        let (mut app, ball) = collisions(Vec2::new(-13.0, 74.0), Vec2::new(BALL_SPEED, 100.0));
+        spawn_wall(&mut app, Vec2::new(-100.0, 100.0), Vec2::new(200.0, 30.0));
+        spawn_wall(&mut app, Vec2::new(100.0, 100.0), Vec2::new(200.0, 30.0));
+
+        app.update();
+        assert_eq!(
+            app.world().get::<Velocity>(ball).unwrap().0,
+            Vec2::new(BALL_SPEED, -100.0)
+        );
+        assert_eq!(bounces(&app), [Surface::Wall]);
+    }
+
+    #[test]
+    fn racket_corners_send_the_ball_off_one_side() {
+        let corner = Vec2::new(RACKET_THICCNESS, RACKET_LENGTH) / 2.0;
+
+        // More in front of the face than over the end is a return
+        let (mut app, ball) =
+            collisions(corner + Vec2::new(10.0, 5.0), Vec2::new(-BALL_SPEED, 0.0));
+        spawn_racket(&mut app);
+        app.update();
+        let velocity = app.world().get::<Velocity>(ball).unwrap().0;
+        assert!(velocity.x > 0.0 && velocity.y > 0.0);
+        assert_eq!(app.world().resource::<Rally>().hits, 1);
+
+        // More over the end just bounces off it
+        let (mut app, ball) = collisions(
+            corner + Vec2::new(5.0, 10.0),
+            Vec2::new(-BALL_SPEED, -100.0),
+        );
+        spawn_racket(&mut app);
+        app.update();
+        let velocity = app.world().get::<Velocity>(ball).unwrap().0;
+        assert!(velocity.distance(Vec2::new(-BALL_SPEED, 100.0)) < 1e-3);
+        assert_eq!(bounces(&app), [Surface::Racket]);
+        assert_eq!(app.world().resource::<Rally>().hits, 0);
+    }
+
+    #[test]
+    fn rackets_stay_out_of_walls_and_each_other() {
+        let mut app = App::new();
+        app.init_resource::<MatchRules>()
+            .add_systems(Update, keep_rackets_apart);
+        let racket = |app: &mut App, y: f32| {
+            app.world_mut()
+                .spawn((
+                    Racket::new(1),
+                    Swing::default(),
+                    Transform {
+                        translation: Vec3::new(0.0, y, 0.0),
+                        rotation: racket_rotation(Side::Left, 0.0),
+                        scale: Vec3::new(RACKET_LENGTH, RACKET_THICCNESS, 0.0),
+                    },
+                ))
+                .id()
+        };
+        // Into the wall's underside by 5, and into each other by 10
+        let top = racket(&mut app, 140.0);
+        let below = racket(&mut app, 30.0);
+        app.world_mut().spawn((
+            Collider,
+            Transform {
+                translation: Vec3::new(0.0, 220.0, 0.0),
+                scale: Vec3::new(400.0, 50.0, 0.0),
+                ..default()
+            },
+        ));
+
+        let y = |app: &App, racket| app.world().get::<Transform>(racket).unwrap().translation.y;
+        app.update();
+        assert!((y(&app, top) - 135.0).abs() < 1e-3);
+        assert!((y(&app, below) - 25.0).abs() < 1e-3);
+        // Pushed down, not swung
+        assert!(app.world().get::<Swing>(top).unwrap().velocity < 0.0);
+
+        // Squeezed up against the wall, only the one below gives way
+        app.update();
+        assert!((y(&app, top) - 135.0).abs() < 1e-3);
+        assert!((y(&app, below) - 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn spin_curves_the_ball_as_it_wears_off() {
+        let mut app = App::new();
+        app.init_resource::<MatchRules>()
+            .add_systems(Update, apply_spin);
+        // Clockwise, as from a racket on the left swung upwards
+        let ball = app
+            .world_mut()
+            .spawn((Velocity(Vec2::new(BALL_SPEED, 0.0)), Spin(-4.0)))
+            .id();
+        for _ in 0..TimeStep::default().steps(1.0) {
+            app.update();
+        }
+
+        let velocity = app.world().get::<Velocity>(ball).unwrap().0;
+        assert!(velocity.y < 0.0);
+        assert!((velocity.length() - BALL_SPEED).abs() < 0.01);
+        assert!(app.world().get::<Spin>(ball).unwrap().0 > -4.0);
+    }
+
+    #[test]
+    fn portals_send_the_ball_out_of_the_other_end_once() {
+        let mut app = App::new();
+        app.insert_resource(MatchRules {
+            portals: true,
+            ..default()
+        })
+        .insert_resource(ArenaBounds {
+            min: Vec2::new(-400.0, -300.0),
+            max: Vec2::new(400.0, 300.0),
+        })
+        .insert_resource(SimRng::new(7))
+        .init_resource::<Portals>()
+        .add_systems(Update, use_portals);
+        for _ in 0..TimeStep::default().steps(PORTAL_OPENING) {
+            app.update();
+        }
+        let ends = app.world().resource::<Portals>().ends;
+
+        let velocity = Vec2::new(-BALL_SPEED, 0.0);
+        let ball = app
+            .world_mut()
+            .spawn((
+                Ball,
+                Transform::from_translation(ends[0].extend(1.0)),
+                Velocity(velocity),
+                PortalCooldown::default(),
+            ))
+            .id();
+        app.update();
+        let position = app.world().get::<Transform>(ball).unwrap().translation;
+        assert_eq!(position.truncate(), ends[1]);
+        let speed = app.world().get::<Velocity>(ball).unwrap().length();
+        assert!((speed - BALL_SPEED).abs() < 0.01);
+
+        // Sitting right in the other end, and still not sent straight back
+        app.update();
+        let position = app.world().get::<Transform>(ball).unwrap().translation;
+        assert_eq!(position.truncate(), ends[1]);
+    }
+
+    #[test]
+    fn rackets_change_ends_with_the_sets() {
+        let mut app = App::new();
+        app.insert_resource(ArenaBounds {
+            min: Vec2::new(-400.0, -300.0),
+            max: Vec2::new(400.0, 300.0),
+        })
+        .init_resource::<Score>()
+        .add_systems(Update, swap_sides);
+        let left = app
+            .world_mut()
+            .spawn((
+                Racket::new(1),
+                Side::Left,
+                Tilt(MAX_TILT),
+                Transform {
+                    translation: Vec3::new(-350.0, 40.0, 0.0),
+                    rotation: racket_rotation(Side::Left, MAX_TILT),
+                    ..default()
+                },
+            ))
+            .id();
+        let side = |app: &App| *app.world().get::<Side>(left).unwrap();
+        let transform = |app: &App| *app.world().get::<Transform>(left).unwrap();
+
+        app.update();
+        assert_eq!(side(&app), Side::Left);
+
+        app.world_mut().resource_mut::<Score>().player2_sets = 1;
+        app.update();
+        assert_eq!(side(&app), Side::Right);
+        assert_eq!(transform(&app).translation, Vec3::new(350.0, 40.0, 0.0));
+        // Still tilted up, the other way round
+        let face = transform(&app).rotation * Vec3::Y;
+        assert!(face.distance(Vec3::new(-MAX_TILT.cos(), MAX_TILT.sin(), 0.0)) < 1e-5);
+
+        app.world_mut().resource_mut::<Score>().player1_sets = 1;
+        app.update();
+        assert_eq!(side(&app), Side::Left);
+        assert_eq!(transform(&app).translation, Vec3::new(-350.0, 40.0, 0.0));
+    }
+
+    #[test]
+    fn a_wall_coming_at_the_ball_sends_it_back_faster() {
+        let mut app = App::new();
+        app.init_resource::<MatchRules>()
+            .init_resource::<MatchStats>()
+            .init_resource::<GameplayConfig>()
+            .add_event::<CollisionEvent>()
+            .add_event::<BallHitEvent>()
+            .add_systems(Update, check_for_collisions);
+        app.world_mut().spawn((
+            Transform::from_scale(MOVING_WALL_SIZE),
+            Collider,
+            MovingWall {
+                velocity: Vec2::new(0.0, 100.0),
+                ..default()
+            },
+        ));
+        // Just over the top of it, on the way down
+        let above = Vec3::new(0.0, MOVING_WALL_SIZE.y / 2.0 + BALL_SIZE.y / 2.0 - 1.0, 1.0);
+        let ball = app
+            .world_mut()
+            .spawn((
+                Ball,
+                Transform::from_translation(above).with_scale(BALL_SIZE),
+                Velocity(Vec2::new(0.0, -BALL_SPEED)),
+                Spin::default(),
+            ))
+            .id();
+        app.update();
+        let velocity = app.world().get::<Velocity>(ball).unwrap().0;
+        assert!((velocity.y - (BALL_SPEED + 200.0)).abs() < 0.01);
+
+        // Still touching, but getting away faster than the wall's following,
+        // so it isn't bounced back into it
+        app.world_mut().get_mut::<Velocity>(ball).unwrap().0 = Vec2::new(0.0, 150.0);
+        app.update();
+        let velocity = app.world().get::<Velocity>(ball).unwrap().0;
+        assert_eq!(velocity, Vec2::new(0.0, 150.0));
+    }
+
+    #[test]
+    fn ball_speed_scales_the_serve() {
+        let rules = MatchRules {
+            ball_speed: 1.5,
+            ..default()
+        };
+        let velocity = serve_velocity(
+            Side::Left,
+            &rules,
+            &GameplayConfig::default(),
+            &mut SimRng::new(3),
+        );
+        assert!((velocity.length() - 1.5 * BALL_SPEED).abs() < 0.01);
+    }
+
+    #[test]
+    fn ball_kind_scales_the_serve_on_top_of_ball_speed() {
+        let rules = MatchRules {
+            ball_speed: 1.5,
+            ball: BallKind::Beach,
+            ..default()
+        };
+        let velocity = serve_velocity(
+            Side::Left,
+            &rules,
+            &GameplayConfig::default(),
+            &mut SimRng::new(3),
+        );
+        let speed = 1.5 * BallKind::Beach.speed() * BALL_SPEED;
+        assert!((velocity.length() - speed).abs() < 0.01);
+    }
+
+    #[test]
+    fn serve_goes_where_the_dice_fall() {
+        let serve = |roll: f32| {
+            serve_velocity(
+                Side::Right,
+                &MatchRules::default(),
+                &GameplayConfig::default(),
+                &mut FixedRng(roll),
+            )
+        };
+        // Halfway between the steepest serves up and down is straight across
+        assert!(serve(0.5).y.abs() < 0.01);
+        assert!(serve(0.0).y < 0.0);
+        assert!(serve(0.99).y > 0.0);
+    }
+
+    #[test]
+    fn seed_changes_the_serve() {
+        let (first, ..) = run(1);
+        let (second, ..) = run(2);
+        assert_ne!(first[0], second[0]);
+    }
+
+    /// A racket's input from the low six bits of `bits`.
+    fn racket_input(bits: u8) -> RacketInput {
+        RacketInput {
+            up: bits & 1 != 0,
+            down: bits & 2 != 0,
+            smash: bits & 4 != 0,
+            tilt_up: bits & 8 != 0,
+            tilt_down: bits & 16 != 0,
+            dash: bits & 32 != 0,
+        }
+    }
+
+    // Steps each of the random inputs is held for, long enough for rackets to
+    // get somewhere and smashes to charge
+    const HELD_FOR: usize = 10;
+
+    /// Plays a match on the classic arena with both rackets doing what
+    /// `inputs` say, calling `check` after every step.
+    fn play(seed: u64, inputs: &[(u8, u8)], mut check: impl FnMut(&mut App)) {
+        let mut app = simulation(seed);
+        let mut script = ScriptedInput::default();
+        for &(left, right) in inputs {
+            let input = [
+                racket_input(left),
+                racket_input(right),
+                default(),
+                default(),
+            ];
+            script.hold(input, HELD_FOR);
+        }
+        app.insert_resource(script)
+            .add_systems(Update, read_input::<ScriptedInput>.before(SimSystems));
+        for _ in 0..inputs.len() * HELD_FOR {
+            app.update();
+            check(&mut app);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn reflecting_keeps_the_speed(
+            x in -1000.0f32..1000.0,
+            y in -1000.0f32..1000.0,
+            collision in select(vec![
+                Collision::Left,
+                Collision::Right,
+                Collision::Top,
+                Collision::Bottom,
+            ]),
+        ) {
+            let mut velocity = Velocity(Vec2::new(x, y));
+            reflect(&mut velocity, &collision);
+            prop_assert!(velocity.length() == Vec2::new(x, y).length());
+        }
+
+        #[test]
+        fn racket_faces_send_the_ball_off_within_the_bounce_angle(
+            along in -0.5f32..0.5,
+            x in -900.0f32..-50.0,
+            y in -600.0f32..600.0,
+        ) {
+            // On the face of a racket facing right, anywhere along it
+            let incoming = Vec2::new(x, y);
+            let (mut app, ball) = collisions(
+                Vec2::new(RACKET_THICCNESS / 2.0 + 10.0, along * RACKET_LENGTH),
+                incoming,
+            );
+            spawn_racket(&mut app);
+
+            app.update();
+            let outgoing = app.world().get::<Velocity>(ball).unwrap().0;
+            prop_assert!(outgoing.x > 0.0);
+            prop_assert!((outgoing.length() - incoming.length()).abs() < 1e-3 * incoming.length());
+            let angle = outgoing.y.atan2(outgoing.x).abs();
+            prop_assert!(angle <= MatchRules::default().bounce_angle + 1e-4);
+        }
+    }
+
+    proptest! {
+        // Every case is a whole match
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn rackets_never_end_up_in_walls(
+            seed in any::<u64>(),
+            inputs in collection::vec(any::<(u8, u8)>(), 100..300),
+        ) {
+            let mut stuck = None;
+            play(seed, &inputs, |app| {
+                let mut walls = app
+                    .world_mut()
+                    .query_filtered::<&Transform, (With<Collider>, Without<Racket>)>();
+                let walls: Vec<Transform> = walls.iter(app.world()).copied().collect();
+                let mut rackets = app.world_mut().query_filtered::<&Transform, With<Racket>>();
+                for racket in rackets.iter(app.world()) {
+                    for wall in &walls {
+                        let overlap = penetrate(
+                            racket.translation.truncate(),
+                            extent(racket),
+                            wall.translation.truncate(),
+                            extent(wall),
+                        );
+                        // Up against a wall is fine, give or take rounding
+                        if let Some((_, depth)) = overlap.filter(|(_, depth)| *depth > 1e-3) {
+                            stuck.get_or_insert((racket.translation, depth));
+                        }
+                    }
+                }
+            });
+            prop_assert!(stuck.is_none(), "a racket at {:?} was in a wall", stuck);
+        }
+
+        #[test]
+        fn the_ball_never_leaves_the_arena(
+            seed in any::<u64>(),
+            inputs in collection::vec(any::<(u8, u8)>(), 100..300),
+        ) {
+            let reach = classic_arena().extent().0;
+            let mut escaped = None;
+            play(seed, &inputs, |app| {
+                let mut balls = app.world_mut().query_filtered::<&Transform, With<Ball>>();
+                for ball in balls.iter(app.world()) {
+                    let position = ball.translation.truncate();
+                    if position.abs().cmpgt(reach).any() {
+                        escaped.get_or_insert(position);
+                    }
+                }
+            });
+            prop_assert!(escaped.is_none(), "the ball got out to {:?}", escaped);
+        }
+    }
+}
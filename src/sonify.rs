@@ -0,0 +1,286 @@
+//! The ball followed by ear, for anyone who can't easily follow it by eye: a
+//! tone that rises the higher up the court the ball is, and moves from ear to
+//! ear with it, and a beeping over it as the ball comes at a racket somebody
+//! is playing, quickening the closer it gets.
+//!
+//! The tone is made up as it plays, from what [`follow_ball`] last set, so it
+//! glides along with the ball rather than being a string of separate sounds.
+
+use std::{
+    f32::consts::{FRAC_PI_4, TAU},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    audio::{AddAudioSource, AudioSink, AudioSinkPlayback, Decodable},
+    prelude::*,
+};
+use rodio::Source;
+
+use crate::{
+    ai::Cpu,
+    arena::ArenaBounds,
+    bot::Bot,
+    settings::Settings,
+    sim::{Ball, Netted, Racket, Velocity},
+    AppState, Player,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+// The tone's pitch with the ball at the bottom of the court and at the top,
+// two octaves apart
+const LOW_PITCH: f32 = 220.0;
+const HIGH_PITCH: f32 = 880.0;
+// Loud enough to hear over the rest, without drowning out the announcer
+const TONE_LEVEL: f32 = 0.2;
+const CUE_LEVEL: f32 = 0.15;
+// The beeping is an octave and a fifth over the tone
+const CUE_HARMONIC: f32 = 3.0;
+// Beeps a second, as the ball starts coming and just before it arrives
+const CUE_SLOWEST: f32 = 3.0;
+const CUE_FASTEST: f32 = 14.0;
+// How far off a racket the ball starts being beeped at, in pixels
+const CUE_DISTANCE: f32 = 350.0;
+// Share of the way to what's been set the sound goes each sample, so changes
+// take about a hundredth of a second rather than clicking
+const GLIDE: f32 = 0.002;
+// Samples between looking at what's been set
+const CONTROL_PERIOD: u32 = 64;
+
+pub struct SonifyPlugin;
+
+impl Plugin for SonifyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<BallTone>()
+            .add_systems(Update, start_and_stop)
+            .add_systems(Update, follow_ball.after(start_and_stop));
+    }
+}
+
+/// What the tone should sound like, set from the game and read as it plays.
+#[derive(Default)]
+struct ToneControl {
+    pitch: AtomicU32,
+    /// From -1, all in the left ear, to 1, all in the right
+    pan: AtomicU32,
+    volume: AtomicU32,
+    /// How close the ball is to a racket it's coming at, from 0 to 1
+    cue: AtomicU32,
+}
+
+impl ToneControl {
+    fn set(value: &AtomicU32, to: f32) {
+        value.store(to.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(value: &AtomicU32) -> f32 {
+        f32::from_bits(value.load(Ordering::Relaxed))
+    }
+}
+
+/// The tone as something Bevy's audio can play.
+#[derive(Asset, TypePath)]
+struct BallTone(Arc<ToneControl>);
+
+impl Decodable for BallTone {
+    type Decoder = ToneSource;
+    type DecoderItem = f32;
+
+    fn decoder(&self) -> ToneSource {
+        ToneSource {
+            control: self.0.clone(),
+            ..default()
+        }
+    }
+}
+
+/// Where the tone's up to, gliding towards what its [`ToneControl`] says.
+#[derive(Default)]
+struct ToneSource {
+    control: Arc<ToneControl>,
+    /// What the control said when last looked at, as pitch, pan, volume and
+    /// cue
+    targets: [f32; 4],
+    /// Where the sound is between what it was and the targets
+    current: [f32; 4],
+    /// How loud the beeping is right now, faded in and out of each beep
+    beep: f32,
+    /// Turns of the tone, the beeping's pitch and the beeping
+    phases: [f32; 3],
+    samples: u32,
+    /// Every other sample is the right ear's, worked out with the left's
+    right: Option<f32>,
+}
+
+impl ToneSource {
+    /// The next sample for each ear.
+    fn next_frame(&mut self) -> (f32, f32) {
+        if self.samples.is_multiple_of(CONTROL_PERIOD) {
+            let control = &self.control;
+            self.targets =
+                [&control.pitch, &control.pan, &control.volume, &control.cue].map(ToneControl::get);
+            // Straight to the first pitch, rather than sliding up to it
+            if self.current[0] == 0.0 {
+                self.current[0] = self.targets[0];
+            }
+        }
+        self.samples = self.samples.wrapping_add(1);
+        for (current, target) in self.current.iter_mut().zip(self.targets) {
+            *current += (target - *current) * GLIDE;
+        }
+        let [pitch, pan, volume, cue] = self.current;
+
+        let rates = [
+            pitch,
+            pitch * CUE_HARMONIC,
+            CUE_SLOWEST + (CUE_FASTEST - CUE_SLOWEST) * cue,
+        ];
+        for (phase, rate) in self.phases.iter_mut().zip(rates) {
+            *phase = (*phase + rate / SAMPLE_RATE as f32).fract();
+        }
+
+        let beeping = self.targets[3] > 0.0 && self.phases[2] < 0.5;
+        let beep = if beeping { 1.0 } else { 0.0 };
+        self.beep += (beep - self.beep) * GLIDE * 4.0;
+        let sample = volume
+            * (TONE_LEVEL * (self.phases[0] * TAU).sin()
+                + CUE_LEVEL * self.beep * (self.phases[1] * TAU).sin());
+
+        // As loud overall wherever it is between the ears
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        (sample * angle.cos(), sample * angle.sin())
+    }
+}
+
+impl Iterator for ToneSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let (left, right) = self.next_frame();
+        self.right = Some(right);
+        Some(left)
+    }
+}
+
+impl Source for ToneSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    /// It plays until it's stopped
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// The tone playing, while the settings have it on.
+#[derive(Resource)]
+struct BallSound {
+    control: Arc<ToneControl>,
+    /// The entity playing it
+    player: Entity,
+}
+
+fn start_and_stop(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut tones: ResMut<Assets<BallTone>>,
+    sinks: Query<&AudioSink>,
+    sound: Option<Res<BallSound>>,
+) {
+    match sound {
+        None if settings.ball_sound => {
+            let control = Arc::new(ToneControl::default());
+            let tone = tones.add(BallTone(control.clone()));
+            let player = commands.spawn((tone, PlaybackSettings::ONCE)).id();
+            commands.insert_resource(BallSound { control, player });
+        }
+        Some(sound) if !settings.ball_sound => {
+            if let Ok(sink) = sinks.get(sound.player) {
+                sink.stop();
+            }
+            commands.entity(sound.player).despawn();
+            commands.remove_resource::<BallSound>();
+        }
+        _ => {}
+    }
+}
+
+/// How close `ball` is to coming at the racket at `racket`, from 0 while it's
+/// too far off or heading away to 1 as it gets there.
+fn closing_in(ball: Vec2, velocity: Vec2, racket: Vec2) -> f32 {
+    let away = racket.x - ball.x;
+    if velocity.x * away <= 0.0 {
+        return 0.0;
+    }
+    (1.0 - away.abs() / CUE_DISTANCE).max(0.0)
+}
+
+/// Sets the tone to wherever the ball is, silent whenever there's no match
+/// being played or no ball in it.
+fn follow_ball(
+    sound: Option<Res<BallSound>>,
+    settings: Res<Settings>,
+    state: Res<State<AppState>>,
+    bounds: Res<ArenaBounds>,
+    balls: Query<(&Transform, &Velocity), (With<Ball>, Without<Netted>)>,
+    rackets: Query<(&Racket, &Transform)>,
+    people: Query<&Player, (Without<Cpu>, Without<Bot>)>,
+) {
+    let sound = match sound {
+        Some(sound) => sound,
+        None => return,
+    };
+    let control = &sound.control;
+
+    // Rackets played by somebody here, rather than a CPU or a bot
+    let watched: Vec<Vec2> = rackets
+        .iter()
+        .filter(|(racket, _)| people.iter().any(|player| player.seat == racket.seat))
+        .map(|(_, transform)| transform.translation.truncate())
+        .collect();
+    // With balls to spare, whichever's closest to getting to one of them
+    let ball = balls
+        .iter()
+        .map(|(transform, velocity)| {
+            let position = transform.translation.truncate();
+            let cue = watched
+                .iter()
+                .map(|racket| closing_in(position, velocity.0, *racket))
+                .fold(0.0, f32::max);
+            (position, cue)
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    let size = bounds.max - bounds.min;
+    let (position, cue) = match ball {
+        Some(ball) if *state.get() == AppState::Playing && size.min_element() > 0.0 => ball,
+        _ => {
+            ToneControl::set(&control.volume, 0.0);
+            ToneControl::set(&control.cue, 0.0);
+            return;
+        }
+    };
+
+    let across = ((position - bounds.min) / size).clamp(Vec2::ZERO, Vec2::ONE);
+    let pitch = LOW_PITCH * (HIGH_PITCH / LOW_PITCH).powf(across.y);
+    ToneControl::set(&control.pitch, pitch);
+    ToneControl::set(&control.pan, across.x * 2.0 - 1.0);
+    ToneControl::set(&control.volume, settings.volume);
+    ToneControl::set(&control.cue, cue);
+}
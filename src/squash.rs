@@ -0,0 +1,140 @@
+//! Squash and stretch for the ball: it flattens against whatever it bounces
+//! off for a moment, and draws out along the way it's going when it's fast.
+//!
+//! Only the sprite is deformed, through its size. The transform's scale is the
+//! ball's size as far as the sim is concerned and is left alone. How far it
+//! gives goes by its [`BallKind`]: a heavy ball hardly does, a beach ball
+//! flops all over.
+
+use bevy::prelude::*;
+
+use crate::{
+    settings::Settings,
+    sim::{Ball, BallKind, Netted, Velocity, BALL_SPEED},
+    tween::Ease,
+    MatchState,
+};
+
+// How flat the ball gets right after it bounces, and for how many seconds
+const SQUASH: f32 = 0.35;
+const SQUASH_TIME: f32 = 0.12;
+// A change in velocity bigger than this between frames is a bounce, smaller
+// ones are gravity, wind or spin
+const IMPACT: f32 = 150.0;
+// How drawn out the ball gets, starting from one speed and all the way from
+// another
+const STRETCH: f32 = 0.25;
+const STRETCH_FROM: f32 = 1.5 * BALL_SPEED;
+const STRETCH_FULL: f32 = 4.0 * BALL_SPEED;
+// A jump further than this between frames is a serve, not a bounce
+const MAX_FRAME_DISTANCE: f32 = 100.0;
+
+pub struct SquashPlugin;
+
+impl Plugin for SquashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (attach_squash, squash_and_stretch.after(attach_squash))
+                .run_if(in_state(MatchState::Running)),
+        );
+    }
+}
+
+/// Kept on the ball: how it was moving last frame, and the bounce it's
+/// squashed from, if there's one.
+#[derive(Component)]
+struct Squash {
+    position: Vec2,
+    velocity: Vec2,
+    /// The direction the ball was pushed from in the bounce
+    normal: Vec2,
+    timer: Timer,
+}
+
+fn attach_squash(
+    mut commands: Commands,
+    balls: Query<(Entity, &Transform, &Velocity), (With<Ball>, Without<Squash>)>,
+) {
+    for (ball, transform, velocity) in balls.iter() {
+        let mut timer = Timer::from_seconds(SQUASH_TIME, TimerMode::Once);
+        timer.tick(timer.duration());
+        commands.entity(ball).insert(Squash {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            normal: Vec2::X,
+            timer,
+        });
+    }
+}
+
+fn squash_and_stretch(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut balls: Query<
+        (
+            &Transform,
+            &mut Sprite,
+            &Velocity,
+            &mut Squash,
+            Option<&Netted>,
+            Option<&BallKind>,
+        ),
+        With<Ball>,
+    >,
+) {
+    for (transform, mut sprite, velocity, mut squash, netted, kind) in balls.iter_mut() {
+        let position = transform.translation.truncate();
+        let change = velocity.0 - squash.velocity;
+        let jumped = position.distance(squash.position) > MAX_FRAME_DISTANCE;
+        if change.length() > IMPACT && netted.is_none() && !jumped {
+            squash.normal = change.normalize();
+            squash.timer.reset();
+        }
+        squash.position = position;
+        squash.velocity = velocity.0;
+        squash.timer.tick(time.delta());
+
+        if settings.effects_reduced() {
+            sprite.custom_size = Some(Vec2::ONE);
+            continue;
+        }
+
+        // Squashing, the ball is short along the bounce's normal, otherwise
+        // it's long along the way it's going
+        let give = give(kind.copied().unwrap_or_default());
+        let (axis, length) = if !squash.timer.finished() {
+            let left = 1.0 - Ease::Out.apply(squash.timer.fraction());
+            (squash.normal, 1.0 - SQUASH * give * left)
+        } else {
+            let speed = velocity.length();
+            let fast = ((speed - STRETCH_FROM) / (STRETCH_FULL - STRETCH_FROM)).clamp(0.0, 1.0);
+            (
+                velocity.try_normalize().unwrap_or(Vec2::X),
+                1.0 + STRETCH * give * fast,
+            )
+        };
+        sprite.custom_size = Some(deform(axis, length));
+    }
+}
+
+/// How much a ball of `kind` squashes and stretches, as a multiple of how
+/// much a standard one does.
+fn give(kind: BallKind) -> f32 {
+    match kind {
+        BallKind::Standard | BallKind::Light => 1.0,
+        BallKind::Heavy => 0.4,
+        BallKind::Beach => 1.6,
+    }
+}
+
+/// The size of a unit square made `length` long along `axis`, and as much
+/// narrower across it so it keeps its area. Sprites can't be skewed, so a
+/// diagonal `axis` deforms it partly along each side.
+fn deform(axis: Vec2, length: f32) -> Vec2 {
+    let along = axis * axis;
+    Vec2::new(
+        length * along.x + along.y / length,
+        length * along.y + along.x / length,
+    )
+}
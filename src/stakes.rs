@@ -0,0 +1,88 @@
+//! The court warming up when a point matters: a faint wash of the accent
+//! colour over it at deuce, a little more on a set point and more again on
+//! a match point, fading back once the point's played.
+//!
+//! Purely visual. The HUD and the announcer say what's at stake in words.
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::ArenaBounds,
+    settings::Settings,
+    sim::{MatchOver, MatchRules, Score, Stakes},
+    start_match,
+    ui::ACCENT_COLOR,
+    AppState, GameMode, MatchState,
+};
+
+// Over the stars, under the court markings
+const TINT_Z: f32 = -1.5;
+// How strong the wash gets with each of the stakes
+const DEUCE_TINT: f32 = 0.04;
+const SET_POINT_TINT: f32 = 0.07;
+const MATCH_POINT_TINT: f32 = 0.1;
+// How quickly it fades from one to the next, the gap between them closing at
+// this rate a second
+const FADE_RATE: f32 = 3.0;
+
+pub struct StakesPlugin;
+
+impl Plugin for StakesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Playing), spawn_tint.after(start_match))
+            .add_systems(Update, tint_court.run_if(in_state(MatchState::Running)));
+    }
+}
+
+#[derive(Component)]
+struct CourtTint;
+
+fn spawn_tint(mut commands: Commands, mode: Res<GameMode>) {
+    // Practice and survival keep no score that anything rides on
+    if matches!(*mode, GameMode::Practice | GameMode::Survival) {
+        return;
+    }
+    let mut color = ACCENT_COLOR;
+    color.set_alpha(0.0);
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite { color, ..default() },
+            ..default()
+        },
+        CourtTint,
+        StateScoped(AppState::Playing),
+    ));
+}
+
+fn tint_court(
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    score: Res<Score>,
+    match_over: Res<MatchOver>,
+    settings: Res<Settings>,
+    bounds: Res<ArenaBounds>,
+    mut tints: Query<(&mut Sprite, &mut Transform), With<CourtTint>>,
+) {
+    let wanted = match rules.stakes(&score) {
+        _ if match_over.0.is_some() => 0.0,
+        Some(Stakes::MatchPoint) => MATCH_POINT_TINT,
+        Some(Stakes::SetPoint) => SET_POINT_TINT,
+        Some(Stakes::Deuce) => DEUCE_TINT,
+        None => 0.0,
+    };
+    for (mut sprite, mut transform) in tints.iter_mut() {
+        // Arenas can change size, so it's fitted to the court every frame
+        let center = (bounds.min + bounds.max) / 2.0;
+        transform.translation = center.extend(TINT_Z);
+        transform.scale = (bounds.max - bounds.min).extend(1.0);
+
+        // With reduced effects it changes all at once
+        let alpha = sprite.color.alpha();
+        let alpha = if settings.effects_reduced() {
+            wanted
+        } else {
+            alpha + (wanted - alpha) * (FADE_RATE * time.delta_seconds()).min(1.0)
+        };
+        sprite.color.set_alpha(alpha);
+    }
+}
@@ -0,0 +1,231 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    locale::Strings,
+    player_name,
+    recording::Playback,
+    seams::Clock,
+    sim::{MatchOver, MatchStats, Rally},
+    AppState, GameMode, Player,
+};
+
+const STATS_FILE: &str = "stats.json";
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StatsStore::load())
+            .init_resource::<MatchTracker>()
+            .add_systems(OnEnter(AppState::Playing), start_tracking::<Time>)
+            .add_systems(OnExit(AppState::Playing), record_match::<Time>);
+    }
+}
+
+/// Where pingis keeps files that should survive between sessions. There's
+/// nowhere in a browser, so nothing's kept there and it all starts afresh.
+pub fn data_dir() -> Option<PathBuf> {
+    if cfg!(target_arch = "wasm32") {
+        return None;
+    }
+    dirs::data_dir().map(|dir| dir.join("pingis"))
+}
+
+/// What's in the RON file `file` in the data directory, or the default if it
+/// isn't there or can't be read.
+pub fn load_ron<T: DeserializeOwned + Default>(file: &str) -> T {
+    data_dir()
+        .and_then(|dir| fs::read(dir.join(file)).ok())
+        .and_then(|bytes| match ron::de::from_bytes(&bytes) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                warn!("Ignoring unreadable {}: {}", file, error);
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Writes `value` to the RON file `file` in the data directory, if there is
+/// one.
+pub fn save_ron<T: Serialize>(file: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = match data_dir() {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join(file),
+        ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?,
+    )?;
+    Ok(())
+}
+
+/// Seconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Seconds since the Unix epoch, from the browser's clock, as there's no
+/// system clock to ask.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MatchRecord {
+    /// Seconds since the Unix epoch when the match started
+    pub started_at: u64,
+    /// Length of the match in seconds
+    pub duration: f32,
+    pub mode: GameMode,
+    pub players: [PlayerRecord; 2],
+    /// Racket hits in each finished rally
+    pub rallies: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerRecord {
+    pub name: String,
+    pub points: i32,
+    pub won: bool,
+}
+
+impl MatchRecord {
+    /// The entry for `name`, and the entry for their opponent.
+    pub fn sides(&self, name: &str) -> Option<(&PlayerRecord, &PlayerRecord)> {
+        let [first, second] = &self.players;
+        if first.name == name {
+            Some((first, second))
+        } else if second.name == name {
+            Some((second, first))
+        } else {
+            None
+        }
+    }
+
+    /// Racket hits in the longest of its rallies.
+    pub fn longest_rally(&self) -> u32 {
+        self.rallies.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// Every finished match, kept in a JSON file in the user's data directory.
+#[derive(Resource, Default)]
+pub struct StatsStore {
+    path: Option<PathBuf>,
+    pub matches: Vec<MatchRecord>,
+}
+
+impl StatsStore {
+    fn load() -> Self {
+        let path = data_dir().map(|dir| dir.join(STATS_FILE));
+        let matches = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| match serde_json::from_slice(&bytes) {
+                Ok(matches) => Some(matches),
+                Err(error) => {
+                    warn!("Ignoring unreadable stats file: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        StatsStore { path, matches }
+    }
+
+    fn push(&mut self, record: MatchRecord) {
+        self.matches.push(record);
+        if let Err(error) = self.save() {
+            warn!("Couldn't save stats: {}", error);
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(&self.matches)?)?;
+        Ok(())
+    }
+
+    /// Names of everyone who has finished a match, alphabetically.
+    pub fn profiles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .matches
+            .iter()
+            .flat_map(|record| record.players.iter().map(|player| player.name.clone()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// When the match being played right now started.
+#[derive(Resource, Default)]
+struct MatchTracker {
+    started_at: u64,
+    start_time: f64,
+}
+
+fn start_tracking<C: Clock>(mut tracker: ResMut<MatchTracker>, time: Res<C>) {
+    *tracker = MatchTracker {
+        started_at: now(),
+        start_time: time.elapsed_seconds_f64(),
+    };
+}
+
+/// Saves the match to the store if it was played to the end.
+fn record_match<C: Clock>(
+    tracker: Res<MatchTracker>,
+    match_over: Res<MatchOver>,
+    stats: Res<MatchStats>,
+    rally: Res<Rally>,
+    mode: Res<GameMode>,
+    time: Res<C>,
+    players: Query<&Player>,
+    strings: Res<Strings>,
+    playback: Option<Res<Playback>>,
+    mut store: ResMut<StatsStore>,
+) {
+    let winner = match match_over.0 {
+        Some(winner) => winner,
+        None => return,
+    };
+    // Nobody played the demo, or a replay this time, and survival runs keep
+    // a table of their own
+    if matches!(*mode, GameMode::Demo | GameMode::Survival) || playback.is_some() {
+        return;
+    }
+
+    let record_for = |player_number: i32| PlayerRecord {
+        // The side's own player, not their partner in doubles
+        name: player_name(players.iter(), player_number, &strings),
+        points: stats.player(player_number).points,
+        won: player_number == winner,
+    };
+
+    store.push(MatchRecord {
+        started_at: tracker.started_at,
+        duration: (time.elapsed_seconds_f64() - tracker.start_time) as f32,
+        mode: *mode,
+        players: [record_for(1), record_for(2)],
+        rallies: rally.finished.clone(),
+    });
+}
@@ -0,0 +1,178 @@
+//! Optional subsystems, each behind the cargo feature of the same name.
+//!
+//! A subsystem's plugin is only registered here, so everything else can stay
+//! oblivious to which features the binary was built with.
+
+use bevy::prelude::*;
+
+/// Every optional subsystem, and whether this build includes it.
+pub const SUBSYSTEMS: [(&str, bool); 9] = [
+    ("audio", cfg!(feature = "audio")),
+    ("networking", cfg!(feature = "networking")),
+    ("netdebug", cfg!(feature = "netdebug")),
+    ("leaderboard", cfg!(feature = "leaderboard")),
+    ("discord", cfg!(feature = "discord")),
+    ("scripting", cfg!(feature = "scripting")),
+    ("telemetry", cfg!(feature = "telemetry")),
+    ("bots", cfg!(feature = "bots")),
+    ("devtools", cfg!(feature = "devtools")),
+];
+
+pub struct SubsystemsPlugin;
+
+impl Plugin for SubsystemsPlugin {
+    fn build(&self, app: &mut App) {
+        let enabled = Subsystems::compiled_in();
+        info!("Enabled subsystems: {:?}", enabled.0);
+        app.insert_resource(enabled);
+
+        #[cfg(feature = "audio")]
+        app.add_plugins(crate::announcer::AnnouncerPlugin)
+            .add_plugins(crate::sonify::SonifyPlugin);
+        #[cfg(feature = "networking")]
+        app.add_plugins(crate::network::NetworkPlugin)
+            .add_plugins(crate::relay::RelayPlugin)
+            .add_plugins(crate::lobby::LobbyPlugin)
+            .add_plugins(crate::desync::DesyncPlugin)
+            .add_plugins(crate::chat::ChatPlugin);
+        #[cfg(feature = "netdebug")]
+        app.add_plugins(crate::netdebug::NetDebugPlugin);
+        #[cfg(feature = "leaderboard")]
+        app.add_plugins(crate::leaderboard::LeaderboardPlugin);
+        #[cfg(feature = "discord")]
+        app.add_plugins(crate::discord::DiscordPlugin);
+        #[cfg(feature = "telemetry")]
+        app.add_plugins(crate::telemetry::TelemetryPlugin);
+        #[cfg(feature = "bots")]
+        app.add_plugins(crate::bots::BotsPlugin);
+        #[cfg(feature = "scripting")]
+        app.add_plugins(crate::scripting::ScriptingPlugin);
+        #[cfg(feature = "devtools")]
+        app.add_plugins(crate::devtools::DevtoolsPlugin)
+            .add_plugins(crate::overlay::OverlayPlugin)
+            .add_plugins(crate::profiler::ProfilerPlugin)
+            .add_plugins(crate::eventlog::EventLogPlugin)
+            .add_plugins(crate::inspector::InspectorPlugin)
+            .add_plugins(crate::console::ConsolePlugin)
+            .add_plugins(crate::framestep::FrameStepPlugin);
+    }
+}
+
+/// Names of the subsystems built into this binary.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct Subsystems(pub Vec<&'static str>);
+
+impl Subsystems {
+    fn compiled_in() -> Self {
+        Subsystems(
+            SUBSYSTEMS
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        render::{settings::WgpuSettings, RenderPlugin},
+        winit::WinitPlugin,
+    };
+
+    use super::*;
+
+    /// The modules each subsystem's plugins are in.
+    const MODULES: [(&str, &[&str]); 9] = [
+        ("audio", &["announcer", "sonify"]),
+        (
+            "networking",
+            &["network", "relay", "lobby", "desync", "chat"],
+        ),
+        ("netdebug", &["netdebug"]),
+        ("leaderboard", &["leaderboard"]),
+        ("discord", &["discord"]),
+        ("scripting", &["scripting"]),
+        ("telemetry", &["telemetry"]),
+        ("bots", &["bots"]),
+        (
+            "devtools",
+            &[
+                "devtools",
+                "overlay",
+                "profiler",
+                "eventlog",
+                "inspector",
+                "console",
+                "framestep",
+            ],
+        ),
+    ];
+
+    // Discord and telemetry have nothing to run unless they're told where to
+    // send things, and the bots only register themselves
+    const IDLE: [&str; 3] = ["discord", "telemetry", "bots"];
+
+    fn app() -> App {
+        let mut app = App::new();
+        // Everything the game has but a window and a GPU, as some subsystems
+        // draw with egui and inspect what the others register
+        app.add_plugins(
+            DefaultPlugins
+                .set(RenderPlugin {
+                    render_creation: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    }
+                    .into(),
+                    ..default()
+                })
+                .disable::<WinitPlugin>(),
+        )
+        .add_plugins(SubsystemsPlugin);
+        app
+    }
+
+    /// Paths of the systems in every schedule of `app`.
+    fn systems(app: &App) -> Vec<String> {
+        app.world()
+            .resource::<Schedules>()
+            .iter()
+            .flat_map(|(_, schedule)| schedule.graph().systems())
+            .map(|(_, system, _)| system.name().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn schedules_systems_for_exactly_the_enabled_features() {
+        let systems = systems(&app());
+        for (name, modules) in MODULES {
+            let enabled = SUBSYSTEMS.contains(&(name, true));
+            for module in modules {
+                let prefix = format!("pingis::{}::", module);
+                let scheduled = systems.iter().any(|system| system.starts_with(&prefix));
+                if !enabled {
+                    assert!(!scheduled, "{} is scheduled without {}", module, name);
+                } else if !IDLE.contains(&name) {
+                    assert!(scheduled, "{} isn't scheduled with {}", module, name);
+                }
+            }
+        }
+    }
+
+    #[cfg(any(feature = "discord", feature = "telemetry", feature = "bots"))]
+    #[test]
+    fn adds_the_idle_subsystems_when_enabled() {
+        let app = app();
+        #[cfg(feature = "discord")]
+        assert!(app.is_plugin_added::<crate::discord::DiscordPlugin>());
+        #[cfg(feature = "telemetry")]
+        assert!(app.is_plugin_added::<crate::telemetry::TelemetryPlugin>());
+        #[cfg(feature = "bots")]
+        assert!(app
+            .world()
+            .resource::<crate::bot::BotRegistry>()
+            .contains("Follower"));
+    }
+}
@@ -0,0 +1,239 @@
+//! Survival: player 1 against a wall standing where player 2's racket was,
+//! keeping a single rally going for as long as they can while every return
+//! speeds the ball up. The longest rallies go in a high-score table.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::ArenaBounds,
+    locale::Strings,
+    player_name,
+    recording::Playback,
+    sim::{
+        Ball, Collider, MatchOver, Netted, Racket, Rally, SimSystems, Velocity, BALL_SPEED,
+        RACKET_THICCNESS,
+    },
+    stats::{data_dir, now},
+    theme::ThemePart,
+    ui::{text, UiFont, TEXT_COLOR},
+    AppState, GameMode, MatchState, Player,
+};
+
+const SCORES_FILE: &str = "survival.json";
+/// How many of the longest rallies the table keeps.
+const TABLE_SIZE: usize = 10;
+
+// Every return speeds the ball up by this fraction of a serve's speed, until
+// it's going this fast
+const SPEEDUP_PER_HIT: f32 = 0.05;
+const MAX_SPEED: f32 = 3.0 * BALL_SPEED;
+
+pub struct SurvivalPlugin;
+
+impl Plugin for SurvivalPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SurvivalScores::load())
+            .add_systems(OnEnter(AppState::Playing), start_survival)
+            .add_systems(
+                Update,
+                (build_wall, update_survival_hud)
+                    .run_if(in_state(MatchState::Running).and_then(surviving)),
+            )
+            .add_systems(OnExit(AppState::Playing), record_run)
+            .add_systems(
+                FixedUpdate,
+                speed_up_ball
+                    .run_if(in_state(MatchState::Running).and_then(surviving))
+                    .after(SimSystems),
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SurvivalRecord {
+    pub name: String,
+    /// Returns the player made before the ball got past them
+    pub hits: u32,
+    /// Seconds since the Unix epoch when the run ended
+    pub finished_at: u64,
+}
+
+/// The longest survival rallies, longest first, kept in a JSON file in the
+/// user's data directory.
+#[derive(Resource, Default)]
+pub struct SurvivalScores {
+    path: Option<PathBuf>,
+    pub best: Vec<SurvivalRecord>,
+    /// Where the last run made it into the table, if it did
+    pub last: Option<usize>,
+}
+
+impl SurvivalScores {
+    fn load() -> Self {
+        let path = data_dir().map(|dir| dir.join(SCORES_FILE));
+        let best = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| match serde_json::from_slice(&bytes) {
+                Ok(best) => Some(best),
+                Err(error) => {
+                    warn!("Ignoring unreadable survival scores: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        SurvivalScores {
+            path,
+            best,
+            last: None,
+        }
+    }
+
+    /// Adds a run to the table, if it's long enough. Ties go below the runs
+    /// that got there first.
+    fn push(&mut self, record: SurvivalRecord) {
+        let rank = self
+            .best
+            .iter()
+            .position(|best| best.hits < record.hits)
+            .unwrap_or(self.best.len());
+        self.last = (rank < TABLE_SIZE).then_some(rank);
+        if self.last.is_none() {
+            return;
+        }
+
+        self.best.insert(rank, record);
+        self.best.truncate(TABLE_SIZE);
+        if let Err(error) = self.save() {
+            warn!("Couldn't save survival scores: {}", error);
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(&self.best)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Component)]
+struct SurvivalHud;
+
+fn surviving(mode: Res<GameMode>) -> bool {
+    *mode == GameMode::Survival
+}
+
+fn start_survival(mut commands: Commands, font: Res<UiFont>, mode: Res<GameMode>) {
+    if *mode != GameMode::Survival {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(12.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            StateScoped(AppState::Playing),
+        ))
+        .with_children(|parent| {
+            parent.spawn((text(&font, "", 28.0, TEXT_COLOR), SurvivalHud));
+        });
+}
+
+/// Turns player 2's racket into a wall across the whole of their end,
+/// whenever the arena spawns one.
+fn build_wall(
+    mut commands: Commands,
+    bounds: Res<ArenaBounds>,
+    mut rackets: Query<(Entity, &Racket, &mut Transform)>,
+) {
+    for (entity, racket, mut transform) in rackets.iter_mut() {
+        if racket.player_number != 2 {
+            continue;
+        }
+        transform.translation.y = (bounds.min.y + bounds.max.y) / 2.0;
+        transform.rotation = Quat::IDENTITY;
+        transform.scale = Vec3::new(RACKET_THICCNESS, bounds.max.y - bounds.min.y, 0.0);
+        commands
+            .entity(entity)
+            .remove::<Racket>()
+            .insert(Collider)
+            .insert(ThemePart::Wall);
+    }
+}
+
+/// Keeps the ball going at least as fast as the rally so far calls for.
+fn speed_up_ball(
+    rally: Res<Rally>,
+    mut balls: Query<&mut Velocity, (With<Ball>, Without<Netted>)>,
+) {
+    let speed = (BALL_SPEED * (1.0 + SPEEDUP_PER_HIT * rally.hits as f32)).min(MAX_SPEED);
+    for mut velocity in balls.iter_mut() {
+        if velocity.length() < speed {
+            velocity.0 = velocity.normalize_or_zero() * speed;
+        }
+    }
+}
+
+fn update_survival_hud(
+    strings: Res<Strings>,
+    rally: Res<Rally>,
+    scores: Res<SurvivalScores>,
+    mut texts: Query<&mut Text, With<SurvivalHud>>,
+    added: Query<(), Added<SurvivalHud>>,
+) {
+    if !rally.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let line = match scores.best.first() {
+        Some(best) => strings.fill(
+            "survival.rally",
+            &[("hits", &rally.hits), ("best", &best.hits)],
+        ),
+        None => strings.fill("hud.rally", &[("hits", &rally.hits)]),
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = line.clone();
+    }
+}
+
+/// Puts the run in the table if the ball got past the player, rather than
+/// them leaving halfway through.
+fn record_run(
+    mode: Res<GameMode>,
+    match_over: Res<MatchOver>,
+    rally: Res<Rally>,
+    players: Query<&Player>,
+    strings: Res<Strings>,
+    mut scores: ResMut<SurvivalScores>,
+    playback: Option<Res<Playback>>,
+) {
+    if *mode != GameMode::Survival || match_over.0.is_none() || playback.is_some() {
+        return;
+    }
+
+    scores.push(SurvivalRecord {
+        name: player_name(players.iter(), 1, &strings),
+        hits: rally.finished.last().copied().unwrap_or_default(),
+        finished_at: now(),
+    });
+}
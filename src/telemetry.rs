@@ -0,0 +1,235 @@
+//! Numbers for balancing the game, written as they happen to a file of
+//! newline-separated JSON, to be looked at afterwards: how long each point
+//! lasted and how many hits its rally took, every smash, and how each match
+//! went, CPUs' difficulties and all.
+//!
+//! Nothing's written unless `--metrics <file>` says where to, and nobody's
+//! named in it, only whether each side was a person, a CPU or a bot. Online
+//! matches are left out, as rollback can send the simulation's events again.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    ai::{Cpu, Difficulty},
+    bot::Bot,
+    cli_arg,
+    recording::Playback,
+    sim::{BallHitEvent, GoalEvent, MatchOver, MatchStats, Rally, ServeEvent},
+    stats::now,
+    AppState, GameMode, MatchState, Player,
+};
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        let metrics = match Metrics::from_args() {
+            Some(metrics) => metrics,
+            None => return,
+        };
+        app.insert_resource(metrics)
+            .add_systems(OnEnter(AppState::Playing), start_match)
+            .add_systems(
+                Update,
+                (record_points, record_smashes).run_if(in_state(MatchState::Running)),
+            )
+            .add_systems(OnExit(AppState::Playing), record_match);
+    }
+}
+
+/// One line of the file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Point {
+        scored_by: i32,
+        /// Racket hits in the rally that won it
+        hits: u32,
+        /// From the serve to the goal
+        seconds: f32,
+    },
+    Smash {
+        player: i32,
+        /// How charged it was, from 0 to 1
+        power: f32,
+        /// How fast the ball went off the racket
+        speed: f32,
+    },
+    Match {
+        winner: i32,
+        seconds: f32,
+        points: [i32; 2],
+        longest_rally: u32,
+        top_speed: f32,
+        sides: [Side; 2],
+    },
+}
+
+/// Who played one end of a match, without saying who they are.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Side {
+    Human,
+    NormalCpu,
+    HardCpu,
+    Bot,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    /// Seconds since the Unix epoch
+    at: u64,
+    mode: GameMode,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// The file being written to, and when what's being timed started.
+#[derive(Resource)]
+pub struct Metrics {
+    file: BufWriter<File>,
+    match_started: f64,
+    point_started: f64,
+    /// How many of [`Rally::finished`] have been written
+    rallies: usize,
+}
+
+impl Metrics {
+    /// `--metrics <file>`, added to if it's already there.
+    fn from_args() -> Option<Self> {
+        let path = cli_arg("metrics")?;
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => {
+                info!("Writing metrics to {}", path);
+                Some(Metrics {
+                    file: BufWriter::new(file),
+                    match_started: 0.0,
+                    point_started: 0.0,
+                    rallies: 0,
+                })
+            }
+            Err(error) => {
+                warn!("Couldn't open {} for metrics: {}", path, error);
+                None
+            }
+        }
+    }
+
+    fn write(&mut self, mode: GameMode, event: &Event) {
+        if mode == GameMode::Online {
+            return;
+        }
+        let line = Line {
+            at: now(),
+            mode,
+            event,
+        };
+        let written = serde_json::to_writer(&mut self.file, &line)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(self.file))
+            .and_then(|()| self.file.flush());
+        if let Err(error) = written {
+            warn!("Couldn't write metrics: {}", error);
+        }
+    }
+}
+
+fn start_match(mut metrics: ResMut<Metrics>, time: Res<Time>) {
+    metrics.match_started = time.elapsed_seconds_f64();
+    metrics.point_started = metrics.match_started;
+    metrics.rallies = 0;
+}
+
+/// Every point, with its rally, as it's scored.
+fn record_points(
+    mut metrics: ResMut<Metrics>,
+    mode: Res<GameMode>,
+    rally: Res<Rally>,
+    time: Res<Time>,
+    mut serves: EventReader<ServeEvent>,
+    mut goals: EventReader<GoalEvent>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let seconds = (now - metrics.point_started) as f32;
+    // Each goal finished the rally that's next on the list
+    let finished = rally.finished.get(metrics.rallies..).unwrap_or_default();
+    let points: Vec<Event> = goals
+        .read()
+        .zip(finished)
+        .map(|(goal, hits)| Event::Point {
+            scored_by: goal.scored_by,
+            hits: *hits,
+            seconds,
+        })
+        .collect();
+    metrics.rallies += points.len();
+    for point in &points {
+        metrics.write(*mode, point);
+    }
+
+    if serves.read().last().is_some() {
+        metrics.point_started = now;
+    }
+}
+
+fn record_smashes(
+    mut metrics: ResMut<Metrics>,
+    mode: Res<GameMode>,
+    mut hits: EventReader<BallHitEvent>,
+) {
+    for hit in hits.read().filter(|hit| hit.smash > 0.0) {
+        metrics.write(
+            *mode,
+            &Event::Smash {
+                player: hit.player_number,
+                power: hit.smash,
+                speed: hit.velocity.length(),
+            },
+        );
+    }
+}
+
+/// How the match went, if it was played to the end. Runs as the match is
+/// left, while its players are still about.
+fn record_match(
+    mut metrics: ResMut<Metrics>,
+    mode: Res<GameMode>,
+    match_over: Res<MatchOver>,
+    stats: Res<MatchStats>,
+    time: Res<Time>,
+    players: Query<(&Player, Option<&Cpu>, Option<&Bot>)>,
+    playback: Option<Res<Playback>>,
+) {
+    let winner = match match_over.0 {
+        Some(winner) if playback.is_none() => winner,
+        _ => return,
+    };
+
+    let side = |player_number: i32| match players
+        .iter()
+        .find(|(player, _, _)| player.player_number == player_number)
+    {
+        Some((_, Some(cpu), _)) if cpu.difficulty() == Difficulty::Hard => Side::HardCpu,
+        Some((_, Some(_), _)) => Side::NormalCpu,
+        Some((_, None, Some(_))) => Side::Bot,
+        _ => Side::Human,
+    };
+    let seconds = (time.elapsed_seconds_f64() - metrics.match_started) as f32;
+    metrics.write(
+        *mode,
+        &Event::Match {
+            winner,
+            seconds,
+            points: [stats.player(1).points, stats.player(2).points],
+            longest_rally: stats.longest_rally,
+            top_speed: stats.top_speed,
+            sides: [side(1), side(2)],
+        },
+    );
+}
@@ -0,0 +1,541 @@
+//! How the court looks: colors and textures for everything on it, the
+//! background and the UI font, read from `.theme.ron` files in `assets/themes`
+//! and the mods.
+//!
+//! Only sprites carrying a [`ThemePart`] get painted, so the simulation and the
+//! server never need to know which theme is on.
+//!
+//! The settings can lay a [`Palette`] over whichever theme it is, for anyone
+//! colour blind, and a high contrast mode on top of that, which blackens the
+//! court and outlines the ball and the rackets. What's in use is the theme
+//! with them laid over it, so everything else only ever sees the one.
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState},
+    prelude::*,
+    render::view::{NoFrustumCulling, VisibilitySystems},
+    transform::TransformSystem,
+    utils::HashSet,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mods::{self, Content, Mods},
+    settings::Settings,
+    ui::{UiFont, DEFAULT_FONT},
+};
+
+/// The themes the settings screen cycles through, in order.
+pub const THEMES: [&str; 3] = ["classic", "neon", "retro"];
+
+/// The palettes the settings screen cycles through, in order.
+pub const PALETTES: [Palette; 4] = [
+    Palette::Standard,
+    Palette::Deuteranopia,
+    Palette::Protanopia,
+    Palette::Tritanopia,
+];
+
+// Okabe and Ito's colours, which stay apart for every kind of colour blindness
+const ORANGE: Color = Color::srgb(0.9, 0.62, 0.0);
+const SKY_BLUE: Color = Color::srgb(0.34, 0.71, 0.91);
+const YELLOW: Color = Color::srgb(0.94, 0.89, 0.26);
+const BLUE: Color = Color::srgb(0.0, 0.45, 0.7);
+const VERMILLION: Color = Color::srgb(0.84, 0.37, 0.0);
+const REDDISH_PURPLE: Color = Color::srgb(0.8, 0.47, 0.65);
+
+const HIGH_CONTRAST_OUTLINE: Outline = Outline {
+    color: Color::WHITE,
+    width: 4.0,
+};
+// Just behind what it outlines, ahead of its trail
+const OUTLINE_DEPTH: f32 = 0.05;
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Theme>()
+            .init_asset_loader::<ThemeLoader>()
+            .add_systems(Update, select_theme)
+            .add_systems(Update, switch_theme.after(select_theme))
+            // After everything spawned this frame is in the world
+            .add_systems(PostUpdate, paint)
+            .add_systems(PostUpdate, restyle)
+            .add_systems(Update, fall_back_to_color)
+            .add_systems(PostUpdate, outline_parts)
+            // Where it's drawn, which between the simulation's steps isn't
+            // quite where it is
+            .add_systems(
+                PostUpdate,
+                follow_outlined
+                    .after(outline_parts)
+                    .after(TransformSystem::TransformPropagate)
+                    .before(VisibilitySystems::VisibilityPropagate),
+            );
+    }
+}
+
+/// A look for the game. The one in use is also a resource, once it's loaded.
+#[derive(Asset, TypePath, Resource, Deserialize, Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// Replaces the arena's own background when set
+    #[serde(default)]
+    pub background: Option<Color>,
+    pub racket: Paint,
+    pub ball: Paint,
+    pub wall: Paint,
+    pub obstacle: Paint,
+    pub goal: Paint,
+    pub marking: Paint,
+    /// For the stars behind the court, the markings' paint when not set
+    #[serde(default)]
+    pub backdrop: Option<Paint>,
+    /// The two ends of a portal, told apart by their colors
+    #[serde(default = "default_portals")]
+    pub portals: [Paint; 2],
+    #[serde(default)]
+    pub effects: EffectPaints,
+    /// Drawn around the ball and the rackets, to pick them out from the court
+    #[serde(default)]
+    pub outline: Option<Outline>,
+    /// Font for all of the UI, as a path under `assets`
+    #[serde(default)]
+    pub font: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Paint {
+    pub color: Color,
+    /// Image stretched over the sprite and tinted with `color`, as a path under
+    /// `assets`. Without one, or if it's missing, the sprite is just the color
+    #[serde(default)]
+    pub texture: Option<String>,
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint {
+            color,
+            texture: None,
+        }
+    }
+}
+
+fn default_portals() -> [Paint; 2] {
+    [
+        Color::srgb(0.2, 0.7, 1.0).into(),
+        Color::srgb(1.0, 0.4, 0.1).into(),
+    ]
+}
+
+/// Colors for what's only on screen for a moment.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EffectPaints {
+    /// What a racket lights up when the ball hits it
+    pub hit_flash: Color,
+    /// What a goal lights up when it's scored in
+    pub goal_flash: Color,
+    /// Off the rackets
+    pub sparks: Color,
+    /// Off the walls
+    pub dust: Color,
+    pub goal_burst: Color,
+}
+
+impl Default for EffectPaints {
+    fn default() -> Self {
+        EffectPaints {
+            hit_flash: Color::WHITE,
+            goal_flash: Color::srgb(0.8, 0.1, 0.1),
+            sparks: Color::srgb(1.0, 0.8, 0.3),
+            dust: Color::srgb(0.6, 0.6, 0.55),
+            goal_burst: Color::srgb(0.9, 0.5, 0.0),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Outline {
+    pub color: Color,
+    /// How far it sticks out, in pixels
+    pub width: f32,
+}
+
+/// Colors for the ball, the rackets, the portals and the effects in place of
+/// a theme's, picked to stay apart for a kind of colour blindness. The court
+/// keeps the theme's, as it only has to stand back from what's played on it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    /// The theme's own
+    Standard,
+    /// Green looking like red
+    Deuteranopia,
+    /// Red looking like green, and dark
+    Protanopia,
+    /// Blue looking like green, and yellow like pink
+    Tritanopia,
+}
+
+impl Palette {
+    /// Key of its name in the [`Strings`](crate::locale::Strings).
+    pub fn name_key(self) -> &'static str {
+        match self {
+            Palette::Standard => "palette.standard",
+            Palette::Deuteranopia => "palette.deuteranopia",
+            Palette::Protanopia => "palette.protanopia",
+            Palette::Tritanopia => "palette.tritanopia",
+        }
+    }
+
+    fn apply(self, theme: &mut Theme) {
+        // Rackets, ball, the portals' ends, and a goal's flash and burst
+        let (racket, ball, portals, goal) = match self {
+            Palette::Standard => return,
+            Palette::Deuteranopia => (SKY_BLUE, YELLOW, [BLUE, ORANGE], ORANGE),
+            // Reds are too dark to stand out on a dark court
+            Palette::Protanopia => (SKY_BLUE, ORANGE, [BLUE, YELLOW], YELLOW),
+            Palette::Tritanopia => (SKY_BLUE, VERMILLION, [SKY_BLUE, REDDISH_PURPLE], VERMILLION),
+        };
+        theme.racket.color = racket;
+        theme.ball.color = ball;
+        for (paint, color) in theme.portals.iter_mut().zip(portals) {
+            paint.color = color;
+        }
+        theme.effects.sparks = ball;
+        theme.effects.goal_flash = goal;
+        theme.effects.goal_burst = goal;
+    }
+}
+
+/// Blackens the court and greys its pieces, so what's played on it stands
+/// out, and outlines that.
+fn high_contrast(theme: &mut Theme) {
+    theme.background = Some(Color::BLACK);
+    theme.wall.color = Color::srgb(0.6, 0.6, 0.6);
+    theme.obstacle.color = Color::srgb(0.75, 0.75, 0.75);
+    theme.goal.color = Color::srgb(0.08, 0.08, 0.08);
+    theme.marking.color = Color::srgb(0.25, 0.25, 0.25);
+    theme.backdrop = None;
+    theme.outline = Some(HIGH_CONTRAST_OUTLINE);
+}
+
+impl Theme {
+    /// Every file it names: its textures and its font.
+    pub fn paths_mut(&mut self) -> impl Iterator<Item = &mut String> + '_ {
+        let paints = [
+            &mut self.racket,
+            &mut self.ball,
+            &mut self.wall,
+            &mut self.obstacle,
+            &mut self.goal,
+            &mut self.marking,
+        ];
+        paints
+            .into_iter()
+            .chain(self.backdrop.as_mut())
+            .chain(self.portals.iter_mut())
+            .filter_map(|paint| paint.texture.as_mut())
+            .chain(self.font.as_mut())
+    }
+
+    fn paint(&self, part: ThemePart) -> &Paint {
+        match part {
+            ThemePart::Racket => &self.racket,
+            ThemePart::Ball => &self.ball,
+            ThemePart::Wall => &self.wall,
+            ThemePart::Obstacle => &self.obstacle,
+            ThemePart::Goal => &self.goal,
+            ThemePart::Marking => &self.marking,
+            ThemePart::Backdrop => self.backdrop.as_ref().unwrap_or(&self.marking),
+            ThemePart::Portal(end) => &self.portals[end],
+        }
+    }
+}
+
+/// Which of the theme's paints a sprite wears.
+#[derive(Component, Reflect, Serialize, Deserialize, Default, Debug, Clone, Copy)]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub enum ThemePart {
+    Racket,
+    Ball,
+    // Only there for scenes, which want a default for every component they
+    // spawn
+    #[default]
+    Wall,
+    Obstacle,
+    Goal,
+    Marking,
+    Backdrop,
+    /// Either end of the portals, 0 or 1
+    Portal(usize),
+}
+
+#[derive(Default)]
+pub struct ThemeLoader;
+
+impl AssetLoader for ThemeLoader {
+    type Asset = Theme;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Theme, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut theme: Theme = ron::de::from_bytes(&bytes)?;
+        for path in theme.paths_mut() {
+            *path = mods::resolve(load_context.path(), path);
+        }
+        Ok(theme)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme.ron"]
+    }
+}
+
+/// The theme picked in the settings, which may still be loading.
+#[derive(Resource)]
+struct SelectedTheme(Handle<Theme>);
+
+fn select_theme(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mods: Res<Mods>,
+    mut current: Local<Option<String>>,
+) {
+    if current.as_deref() == Some(settings.theme.as_str()) {
+        return;
+    }
+    *current = Some(settings.theme.clone());
+
+    // `<name>` is `assets/themes/<name>.theme.ron`, unless a mod brought it
+    let path = mods
+        .asset_path(Content::Theme, &settings.theme)
+        .unwrap_or_else(|| format!("themes/{}.theme.ron", settings.theme));
+    let handle = asset_server.load(&path);
+    commands.insert_resource(SelectedTheme(handle));
+}
+
+fn switch_theme(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Theme>>,
+    themes: Res<Assets<Theme>>,
+    settings: Res<Settings>,
+    selected: Option<Res<SelectedTheme>>,
+    mut pending: Local<bool>,
+    mut laid_over: Local<Option<(Palette, bool)>>,
+) {
+    let selected = match selected {
+        Some(selected) => selected,
+        None => return,
+    };
+    // Switch as soon as the theme is there, and again whenever its file changes
+    let modified = events.read().any(|event| match event {
+        AssetEvent::Modified { id } => *id == selected.0.id(),
+        _ => false,
+    });
+    let over = (settings.palette, settings.high_contrast);
+    if selected.is_changed() || modified || *laid_over != Some(over) {
+        *pending = true;
+    }
+    if !*pending {
+        return;
+    }
+
+    if let Some(theme) = themes.get(&selected.0) {
+        info!("Using theme \"{}\"", theme.name);
+        let mut theme = theme.clone();
+        settings.palette.apply(&mut theme);
+        if settings.high_contrast {
+            high_contrast(&mut theme);
+        }
+        commands.insert_resource(theme);
+        *pending = false;
+        *laid_over = Some(over);
+    }
+}
+
+fn paint(
+    theme: Option<Res<Theme>>,
+    asset_server: Res<AssetServer>,
+    mut parts: Query<(Ref<ThemePart>, &mut Sprite, &mut Handle<Image>)>,
+) {
+    let theme = match theme {
+        Some(theme) => theme,
+        None => return,
+    };
+
+    // Everything on a new theme, otherwise just what's new
+    for (part, mut sprite, mut texture) in parts.iter_mut() {
+        if !theme.is_changed() && !part.is_added() {
+            continue;
+        }
+        let paint = theme.paint(*part);
+        sprite.color = paint.color;
+        // Sprites are sized by their scale, so textures have to be fit to a unit square
+        sprite.custom_size = Some(Vec2::ONE);
+        *texture = match &paint.texture {
+            Some(path) => asset_server.load(path),
+            None => Handle::default(),
+        };
+    }
+}
+
+/// Paints sprites whose texture couldn't be loaded in just their color, so a
+/// missing image doesn't leave a racket or the ball invisible.
+fn fall_back_to_color(
+    asset_server: Res<AssetServer>,
+    mut parts: Query<&mut Handle<Image>, With<ThemePart>>,
+    mut missing: Local<HashSet<AssetId<Image>>>,
+) {
+    for mut texture in parts.iter_mut() {
+        if !matches!(
+            asset_server.get_load_state(texture.id()),
+            Some(LoadState::Failed(_))
+        ) {
+            continue;
+        }
+        if missing.insert(texture.id()) {
+            match asset_server.get_path(texture.id()) {
+                Some(path) => warn!("No texture at {:?}, painting it flat", path.path()),
+                None => warn!("Texture failed to load, painting it flat"),
+            }
+        }
+        *texture = Handle::default();
+    }
+}
+
+/// A sprite just behind a ball or a racket and a little bigger, while the
+/// theme outlines them.
+#[derive(Component)]
+struct OutlineOf(Entity);
+
+/// Kept on what's outlined, for its outline.
+#[derive(Component)]
+struct Outlined(Entity);
+
+fn outline_parts(
+    mut commands: Commands,
+    theme: Option<Res<Theme>>,
+    parts: Query<(Entity, &ThemePart, Option<&Outlined>)>,
+    mut outlines: Query<(Entity, &OutlineOf, &mut Sprite)>,
+) {
+    let outline = theme.as_ref().and_then(|theme| theme.outline);
+    for (entity, part, outlined) in parts.iter() {
+        if !matches!(part, ThemePart::Ball | ThemePart::Racket) {
+            continue;
+        }
+        match (outline, outlined) {
+            (Some(outline), None) => {
+                let sprite = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: outline.color,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        OutlineOf(entity),
+                    ))
+                    // Placed after the frusta are worked out from the cameras
+                    .insert(NoFrustumCulling)
+                    .id();
+                commands.entity(entity).insert(Outlined(sprite));
+            }
+            (None, Some(outlined)) => {
+                commands.entity(outlined.0).despawn();
+                commands.entity(entity).remove::<Outlined>();
+            }
+            _ => {}
+        }
+    }
+
+    for (entity, outline_of, mut sprite) in outlines.iter_mut() {
+        // Taken away with what it outlined
+        if parts.get(outline_of.0).is_err() {
+            commands.entity(entity).despawn();
+        } else if let Some(outline) = outline {
+            sprite.color = outline.color;
+        }
+    }
+}
+
+fn follow_outlined(
+    theme: Option<Res<Theme>>,
+    outlined: Query<(&GlobalTransform, &Sprite, &Visibility), Without<OutlineOf>>,
+    mut outlines: Query<(
+        &OutlineOf,
+        &mut GlobalTransform,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+) {
+    let width = match theme.as_ref().and_then(|theme| theme.outline) {
+        Some(outline) => outline.width,
+        None => return,
+    };
+    for (outline_of, mut transform, mut sprite, mut visibility) in outlines.iter_mut() {
+        let (owner, owner_sprite, owner_visibility) = match outlined.get(outline_of.0) {
+            Ok(owner) => owner,
+            Err(_) => continue,
+        };
+        // Rackets are squashed flat along z, so their rotation can't be
+        // taken out of the matrix, only the way their length points
+        let axes = owner.affine().matrix3;
+        let size = owner_sprite.custom_size.unwrap_or(Vec2::ONE)
+            * Vec2::new(axes.x_axis.length(), axes.y_axis.length());
+        *transform = GlobalTransform::from(Transform {
+            translation: owner.translation() - Vec3::Z * OUTLINE_DEPTH,
+            rotation: Quat::from_rotation_z(axes.x_axis.y.atan2(axes.x_axis.x)),
+            scale: (size + Vec2::splat(2.0 * width)).extend(1.0),
+        });
+        sprite.custom_size = Some(Vec2::ONE);
+        *visibility = *owner_visibility;
+    }
+}
+
+fn restyle(
+    theme: Option<Res<Theme>>,
+    asset_server: Res<AssetServer>,
+    mut clear_color: ResMut<ClearColor>,
+    mut font: ResMut<UiFont>,
+    mut texts: Query<&mut Text>,
+) {
+    let theme = match theme {
+        Some(theme) if theme.is_changed() => theme,
+        _ => return,
+    };
+
+    if let Some(background) = theme.background {
+        clear_color.0 = background;
+    }
+
+    let old_font = font.0.clone();
+    font.0 = asset_server.load(
+        theme
+            .font
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FONT.to_string()),
+    );
+    if font.0 == old_font {
+        return;
+    }
+    // Text already on screen switches over too
+    for mut text in texts.iter_mut() {
+        for section in &mut text.sections {
+            if section.style.font == old_font {
+                section.style.font = font.0.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Playing on a touch screen, for the browser build on phones and tablets.
+//! A finger on the left half of the screen moves whichever of the two
+//! players' rackets is on that side, and one on the right half the other,
+//! each racket going after the finger's height as it's dragged up and down.
+//! The halves are the screen's as it is at the time, so they follow it being
+//! turned or resized, and the rackets changing ends.
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::{Action, Actions, PressActions},
+    sim::Racket,
+    MainCamera,
+};
+
+// How far from the finger a racket can be, in pixels on screen, and still be
+// left where it is, so it doesn't jitter back and forth around it
+const SLACK: f32 = 12.0;
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, follow_fingers.in_set(PressActions));
+    }
+}
+
+fn follow_fingers(
+    touches: Res<Touches>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    rackets: Query<(&Racket, &GlobalTransform)>,
+    mut actions: ResMut<Actions>,
+) {
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let size = match camera.logical_viewport_size() {
+        Some(size) => size,
+        None => return,
+    };
+    // On screen, from the top like the touches, for the players' own
+    let on_screen: Vec<(usize, Vec2)> = rackets
+        .iter()
+        .filter(|(racket, _)| racket.seat < 2)
+        .filter_map(|(racket, transform)| {
+            let at = camera.world_to_viewport(camera_transform, transform.translation())?;
+            Some((racket.seat, at))
+        })
+        .collect();
+
+    for touch in touches.iter() {
+        let finger = touch.position();
+        let left = finger.x < size.x / 2.0;
+        let racket = on_screen
+            .iter()
+            .find(|(_, at)| (at.x < size.x / 2.0) == left);
+        if let Some((seat, at)) = racket {
+            if finger.y < at.y - SLACK {
+                actions.press(*seat, Action::MoveUp);
+            } else if finger.y > at.y + SLACK {
+                actions.press(*seat, Action::MoveDown);
+            }
+        }
+    }
+}
@@ -0,0 +1,591 @@
+//! Single-elimination tournaments between 3 to 8 local players.
+//!
+//! The players' names are typed in first, and seeded by the ratings of the
+//! profiles they belong to, see [`rating`](crate::rating). Matches are then
+//! played one after
+//! another in bracket order, each followed by the bracket with its result,
+//! until only the champion is left.
+
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+
+use crate::{
+    locale::Strings,
+    profiles::Profiles,
+    rating::with_rating,
+    sim::MatchOver,
+    ui::{
+        screen_root, spawn_button, text, typed, ButtonActivated, UiFont, ACCENT_COLOR,
+        DIM_TEXT_COLOR, PANEL_COLOR, TEXT_COLOR,
+    },
+    AppState, GameMode, MatchNames,
+};
+
+const MIN_PLAYERS: usize = 3;
+const MAX_PLAYERS: usize = 8;
+const MAX_NAME_LENGTH: usize = 16;
+
+const BRACKET_HEIGHT: f32 = 340.0;
+const MATCH_WIDTH: f32 = 180.0;
+const NEXT_MATCH_COLOR: Color = Color::srgba(0.3, 0.2, 0.05, 0.9);
+
+pub struct TournamentPlugin;
+
+impl Plugin for TournamentPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Entrants>()
+            .add_systems(Update, type_names)
+            .add_systems(OnEnter(AppState::TournamentSetup), spawn_setup)
+            .add_systems(
+                Update,
+                show_entrants.run_if(in_state(AppState::TournamentSetup)),
+            )
+            .add_systems(
+                OnEnter(AppState::Bracket),
+                (record_result, spawn_bracket.after(record_result)),
+            )
+            .add_systems(Update, bracket_action.run_if(in_state(AppState::Bracket)));
+    }
+}
+
+/// One place in a round of the bracket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// Nobody, so whoever's drawn against it goes through
+    Bye,
+    /// Waiting for the winner of the match before
+    Undecided,
+    /// Index into [`Tournament::players`]
+    Player(usize),
+}
+
+/// The bracket of a tournament, and how far it's got.
+#[derive(Resource, Debug, Clone)]
+pub struct Tournament {
+    pub players: Vec<String>,
+    /// Every round's slots, first round first. Match `i` of a round is between
+    /// slots `2i` and `2i + 1`, and its winner takes slot `i` of the next round.
+    /// The last round is a single slot, for the champion.
+    rounds: Vec<Vec<Slot>>,
+    /// The match being played, as (round, match)
+    playing: Option<(usize, usize)>,
+}
+
+impl Tournament {
+    /// A bracket for `players`, best seeds first. With fewer players than
+    /// places, the top seeds get byes through the first round.
+    pub fn new(players: Vec<String>) -> Self {
+        let size = players.len().next_power_of_two().max(2);
+        let seed = |index: usize| {
+            if index < players.len() {
+                Slot::Player(index)
+            } else {
+                Slot::Bye
+            }
+        };
+        // The best seed meets the worst, the second best the second worst, and so on
+        let first = (0..size / 2)
+            .flat_map(|index| [seed(index), seed(size - 1 - index)])
+            .collect();
+
+        let mut rounds = vec![first];
+        let mut places = size / 2;
+        while places >= 1 {
+            rounds.push(vec![Slot::Undecided; places]);
+            places /= 2;
+        }
+
+        let mut tournament = Tournament {
+            players,
+            rounds,
+            playing: None,
+        };
+        tournament.advance_byes();
+        tournament
+    }
+
+    /// Sends anyone drawn against a bye through to the next round.
+    fn advance_byes(&mut self) {
+        for round in 0..self.rounds.len() - 1 {
+            for index in 0..self.rounds[round].len() / 2 {
+                if self.rounds[round + 1][index] != Slot::Undecided {
+                    continue;
+                }
+                let through = match (
+                    self.rounds[round][2 * index],
+                    self.rounds[round][2 * index + 1],
+                ) {
+                    (Slot::Bye, other) | (other, Slot::Bye) if other != Slot::Undecided => other,
+                    _ => continue,
+                };
+                self.rounds[round + 1][index] = through;
+            }
+        }
+    }
+
+    /// The two players of a match, if both are known.
+    fn pairing(&self, round: usize, index: usize) -> Option<[usize; 2]> {
+        match (
+            self.rounds[round][2 * index],
+            self.rounds[round][2 * index + 1],
+        ) {
+            (Slot::Player(first), Slot::Player(second)) => Some([first, second]),
+            _ => None,
+        }
+    }
+
+    /// The first match in bracket order that's ready to be played.
+    pub fn next_match(&self) -> Option<(usize, usize)> {
+        (0..self.rounds.len() - 1).find_map(|round| {
+            (0..self.rounds[round].len() / 2)
+                .find(|&index| {
+                    self.rounds[round + 1][index] == Slot::Undecided
+                        && self.pairing(round, index).is_some()
+                })
+                .map(|index| (round, index))
+        })
+    }
+
+    /// Sends the winner of a match, its player 1 or 2, through to the next round.
+    pub fn record(&mut self, (round, index): (usize, usize), winner: i32) {
+        let slot = self.rounds[round][2 * index + (winner as usize - 1)];
+        self.rounds[round + 1][index] = slot;
+        self.advance_byes();
+    }
+
+    pub fn champion(&self) -> Option<&str> {
+        match self.rounds.last()?.first()? {
+            Slot::Player(index) => Some(&self.players[*index]),
+            _ => None,
+        }
+    }
+
+    fn slot_name<'a>(&'a self, slot: Slot, strings: &'a Strings) -> &'a str {
+        match slot {
+            Slot::Bye => strings.get("tournament.bye"),
+            Slot::Undecided => "-",
+            Slot::Player(index) => &self.players[index],
+        }
+    }
+}
+
+/// What a round with this many matches is called, counting from the first.
+fn round_name(round: usize, matches: usize, strings: &Strings) -> String {
+    match matches {
+        1 => strings.get("tournament.final").to_string(),
+        2 => strings.get("tournament.semifinals").to_string(),
+        4 => strings.get("tournament.quarterfinals").to_string(),
+        _ => strings.fill("tournament.round", &[("number", &(round + 1))]),
+    }
+}
+
+/// The names typed in so far, and the one being typed.
+#[derive(Resource, Default)]
+struct Entrants {
+    names: Vec<String>,
+    draft: String,
+}
+
+#[derive(Component)]
+struct EntrantList;
+
+#[derive(Component)]
+struct Draft;
+
+#[derive(Component)]
+enum BracketAction {
+    Play,
+    Leave,
+}
+
+fn spawn_setup(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    mut entrants: ResMut<Entrants>,
+) {
+    // Last tournament's players are kept, they're likely to play again
+    entrants.draft.clear();
+
+    commands
+        .spawn((screen_root(), StateScoped(AppState::TournamentSetup)))
+        .with_children(|parent| {
+            parent.spawn(
+                text(&font, strings.get("tournament.title"), 60.0, TEXT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+            parent.spawn((text(&font, "", 24.0, TEXT_COLOR), EntrantList));
+            parent.spawn((
+                text(&font, "", 28.0, ACCENT_COLOR).with_style(Style {
+                    margin: UiRect::all(Val::Px(16.0)),
+                    ..default()
+                }),
+                Draft,
+            ));
+            for line in [
+                strings.fill(
+                    "tournament.add_players",
+                    &[("min", &MIN_PLAYERS), ("max", &MAX_PLAYERS)],
+                ),
+                strings.get("tournament.controls").to_string(),
+            ] {
+                parent.spawn(text(&font, line, 18.0, DIM_TEXT_COLOR));
+            }
+        });
+}
+
+fn type_names(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut keys: EventReader<KeyboardInput>,
+    mut entrants: ResMut<Entrants>,
+    profiles: Res<Profiles>,
+    mut commands: Commands,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // Keys pressed anywhere else mustn't turn up in a name later
+    if *state.get() != AppState::TournamentSetup {
+        keys.clear();
+        return;
+    }
+
+    for character in typed(keys.read()) {
+        if !character.is_control() && entrants.draft.chars().count() < MAX_NAME_LENGTH {
+            entrants.draft.push(character);
+        }
+    }
+
+    // With nothing typed, Backspace takes back the last name added
+    if keyboard_input.just_pressed(KeyCode::Backspace) && entrants.draft.pop().is_none() {
+        entrants.names.pop();
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        next_state.set(AppState::Menu);
+    } else if keyboard_input.just_pressed(KeyCode::Enter) {
+        keyboard_input.reset(KeyCode::Enter);
+        let name = entrants.draft.trim().to_string();
+        entrants.draft.clear();
+        if !name.is_empty() {
+            // Names tell players apart in the bracket and in the stats
+            if entrants.names.len() < MAX_PLAYERS && !entrants.names.contains(&name) {
+                entrants.names.push(name);
+            }
+        } else if entrants.names.len() >= MIN_PLAYERS {
+            // Best rated first, ties in the order they were typed in
+            let mut players = entrants.names.clone();
+            players.sort_by_key(|name| std::cmp::Reverse(profiles.rating(name)));
+            commands.insert_resource(Tournament::new(players));
+            next_state.set(AppState::Bracket);
+        }
+    }
+}
+
+fn show_entrants(
+    entrants: Res<Entrants>,
+    profiles: Res<Profiles>,
+    strings: Res<Strings>,
+    mut lists: Query<&mut Text, (With<EntrantList>, Without<Draft>)>,
+    mut drafts: Query<&mut Text, (With<Draft>, Without<EntrantList>)>,
+    added: Query<(), Added<EntrantList>>,
+) {
+    if !entrants.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let list = if entrants.names.is_empty() {
+        strings.get("tournament.no_players").to_string()
+    } else {
+        entrants
+            .names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| format!("{}. {}", index + 1, with_rating(name, &profiles)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    for mut text in lists.iter_mut() {
+        text.sections[0].value = list.clone();
+    }
+    for mut text in drafts.iter_mut() {
+        text.sections[0].value = if entrants.names.len() < MAX_PLAYERS {
+            format!("> {}_", entrants.draft)
+        } else {
+            strings.get("tournament.full").to_string()
+        };
+    }
+}
+
+/// Puts the winner of the match that was just played into the bracket. A
+/// match that was left before the end just gets played again.
+fn record_result(mut tournament: Option<ResMut<Tournament>>, match_over: Res<MatchOver>) {
+    let tournament = match tournament.as_mut() {
+        Some(tournament) => tournament,
+        None => return,
+    };
+    if let (Some(playing), Some(winner)) = (tournament.playing.take(), match_over.0) {
+        tournament.record(playing, winner);
+    }
+}
+
+fn spawn_bracket(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    strings: Res<Strings>,
+    tournament: Option<Res<Tournament>>,
+) {
+    let tournament = match tournament {
+        Some(tournament) => tournament,
+        None => return,
+    };
+    let next = tournament.next_match();
+
+    commands
+        .spawn((screen_root(), StateScoped(AppState::Bracket)))
+        .with_children(|parent| {
+            match tournament.champion() {
+                Some(champion) => {
+                    parent.spawn(text(
+                        &font,
+                        strings.get("tournament.champion"),
+                        36.0,
+                        TEXT_COLOR,
+                    ));
+                    parent.spawn(text(&font, champion, 64.0, ACCENT_COLOR).with_style(Style {
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    }));
+                }
+                None => {
+                    parent.spawn(
+                        text(&font, strings.get("tournament.title"), 48.0, TEXT_COLOR).with_style(
+                            Style {
+                                margin: UiRect::all(Val::Px(10.0)),
+                                ..default()
+                            },
+                        ),
+                    );
+                }
+            }
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexStart,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let matches_in = |round: usize| tournament.rounds[round].len() / 2;
+                    for round in 0..tournament.rounds.len() - 1 {
+                        spawn_round(
+                            parent,
+                            &font,
+                            &round_name(round, matches_in(round), &strings),
+                            |parent| {
+                                for index in 0..matches_in(round) {
+                                    spawn_match(
+                                        parent,
+                                        &font,
+                                        &strings,
+                                        &tournament,
+                                        round,
+                                        index,
+                                        next,
+                                    );
+                                }
+                            },
+                        );
+                    }
+                });
+
+            match next.and_then(|(round, index)| tournament.pairing(round, index)) {
+                Some([first, second]) => {
+                    parent.spawn(text(
+                        &font,
+                        strings.fill(
+                            "tournament.next",
+                            &[
+                                ("first", &tournament.players[first]),
+                                ("second", &tournament.players[second]),
+                            ],
+                        ),
+                        24.0,
+                        TEXT_COLOR,
+                    ));
+                    spawn_button(parent, &font, 0, strings.get("tournament.play"))
+                        .insert(BracketAction::Play);
+                    spawn_button(parent, &font, 1, strings.get("leave"))
+                        .insert(BracketAction::Leave);
+                }
+                None => {
+                    spawn_button(parent, &font, 0, strings.get("back_to_menu"))
+                        .insert(BracketAction::Leave);
+                }
+            }
+        });
+}
+
+fn spawn_round(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    title: &str,
+    matches: impl FnOnce(&mut ChildBuilder),
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                margin: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(text(font, title, 18.0, DIM_TEXT_COLOR));
+            // Later rounds spread their fewer matches over the same height,
+            // so each lines up between the two it follows
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Px(BRACKET_HEIGHT),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::SpaceAround,
+                        ..default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(matches);
+        });
+}
+
+fn spawn_match(
+    parent: &mut ChildBuilder,
+    font: &UiFont,
+    strings: &Strings,
+    tournament: &Tournament,
+    round: usize,
+    index: usize,
+    next: Option<(usize, usize)>,
+) {
+    let slots = [
+        tournament.rounds[round][2 * index],
+        tournament.rounds[round][2 * index + 1],
+    ];
+    let through = tournament.rounds[round + 1][index];
+    let color = if next == Some((round, index)) {
+        NEXT_MATCH_COLOR
+    } else {
+        PANEL_COLOR
+    };
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(MATCH_WIDTH),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            background_color: color.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for slot in slots {
+                let color = match (slot, through) {
+                    (Slot::Player(_), Slot::Player(_)) if slot == through => ACCENT_COLOR,
+                    (Slot::Player(_), Slot::Undecided) => TEXT_COLOR,
+                    _ => DIM_TEXT_COLOR,
+                };
+                parent.spawn(text(font, tournament.slot_name(slot, strings), 18.0, color));
+            }
+        });
+}
+
+fn bracket_action(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut activated: EventReader<ButtonActivated>,
+    actions: Query<&BracketAction>,
+    mut tournament: Option<ResMut<Tournament>>,
+    mut names: ResMut<MatchNames>,
+    mut mode: ResMut<GameMode>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Menu);
+        return;
+    }
+
+    for event in activated.read() {
+        match actions.get(event.0) {
+            Ok(BracketAction::Play) => {
+                let tournament = match tournament.as_mut() {
+                    Some(tournament) => tournament,
+                    None => continue,
+                };
+                let next = match tournament.next_match() {
+                    Some(next) => next,
+                    None => continue,
+                };
+                if let Some([first, second]) = tournament.pairing(next.0, next.1) {
+                    names.0 = Some([
+                        tournament.players[first].clone(),
+                        tournament.players[second].clone(),
+                    ]);
+                    tournament.playing = Some(next);
+                    *mode = GameMode::Tournament;
+                    state.set(AppState::Playing);
+                }
+            }
+            Ok(BracketAction::Leave) => {
+                state.set(AppState::Menu);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(count: usize) -> Vec<String> {
+        (1..=count).map(|number| format!("P{}", number)).collect()
+    }
+
+    /// Plays the whole bracket, with player 1 of every match winning.
+    fn play_out(tournament: &mut Tournament) -> usize {
+        let mut matches = 0;
+        while let Some(next) = tournament.next_match() {
+            tournament.record(next, 1);
+            matches += 1;
+        }
+        matches
+    }
+
+    #[test]
+    fn every_size_plays_down_to_one_champion() {
+        for count in MIN_PLAYERS..=MAX_PLAYERS {
+            let mut tournament = Tournament::new(players(count));
+            // Everyone but the champion loses exactly once
+            assert_eq!(play_out(&mut tournament), count - 1, "{} players", count);
+            assert_eq!(tournament.champion(), Some("P1"), "{} players", count);
+        }
+    }
+
+    #[test]
+    fn top_seeds_get_the_byes() {
+        let tournament = Tournament::new(players(3));
+        assert_eq!(tournament.rounds[1][0], Slot::Player(0));
+        assert_eq!(tournament.next_match(), Some((0, 1)));
+    }
+}
@@ -0,0 +1,127 @@
+//! A fading trail behind the ball, drawn from where it was the last few frames.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    settings::Settings,
+    sim::{Ball, Velocity},
+    AppState, MatchState,
+};
+
+// Frames of history kept, which is also the most sprites a trail is made of
+const TRAIL_LENGTH: usize = 12;
+// Opacity of the sprite right behind the ball at full speed
+const TRAIL_OPACITY: f32 = 0.4;
+// The trail fades in as the ball speeds up, and is fully there from this speed on
+const TRAIL_FULL_SPEED: f32 = 600.0;
+// A jump further than this between frames is a serve, not movement
+const MAX_FRAME_DISTANCE: f32 = 100.0;
+
+pub struct TrailPlugin;
+
+impl Plugin for TrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                attach_trails,
+                update_trails.after(attach_trails),
+                despawn_orphaned_trails,
+            )
+                .run_if(in_state(MatchState::Running)),
+        );
+    }
+}
+
+/// Kept on the ball: where it's been, newest first, and the sprites showing it.
+#[derive(Component)]
+struct Trail {
+    positions: VecDeque<Vec2>,
+    sprites: Vec<Entity>,
+}
+
+/// One of the sprites of the trail behind a ball.
+#[derive(Component)]
+struct TrailSprite {
+    ball: Entity,
+}
+
+fn attach_trails(mut commands: Commands, balls: Query<Entity, (With<Ball>, Without<Trail>)>) {
+    for ball in balls.iter() {
+        let sprites = (1..TRAIL_LENGTH)
+            .map(|_| {
+                commands
+                    .spawn((
+                        SpriteBundle {
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        },
+                        TrailSprite { ball },
+                        StateScoped(AppState::Playing),
+                    ))
+                    .id()
+            })
+            .collect();
+        commands.entity(ball).insert(Trail {
+            positions: VecDeque::with_capacity(TRAIL_LENGTH),
+            sprites,
+        });
+    }
+}
+
+fn update_trails(
+    settings: Res<Settings>,
+    mut balls: Query<(&Transform, &Velocity, &Sprite, &mut Trail), With<Ball>>,
+    mut sprites: Query<(&mut Transform, &mut Sprite, &mut Visibility), Without<Ball>>,
+) {
+    for (ball_transform, velocity, ball_sprite, mut trail) in balls.iter_mut() {
+        let position = ball_transform.translation.truncate();
+        if let Some(last) = trail.positions.front() {
+            if last.distance(position) > MAX_FRAME_DISTANCE {
+                trail.positions.clear();
+            }
+        }
+        trail.positions.push_front(position);
+        trail.positions.truncate(TRAIL_LENGTH);
+
+        let speed = (velocity.length() / TRAIL_FULL_SPEED).min(1.0);
+        let trail = &*trail;
+        for (index, entity) in trail.sprites.iter().enumerate() {
+            let (mut transform, mut sprite, mut visibility) = match sprites.get_mut(*entity) {
+                Ok(parts) => parts,
+                Err(_) => continue,
+            };
+            // Index 0 is the ball itself
+            let position = match trail.positions.get(index + 1) {
+                Some(position) if settings.ball_trail => *position,
+                _ => {
+                    *visibility = Visibility::Hidden;
+                    continue;
+                }
+            };
+
+            let left = 1.0 - (index + 1) as f32 / TRAIL_LENGTH as f32;
+            *visibility = Visibility::Inherited;
+            transform.translation = position.extend(ball_transform.translation.z - 0.1);
+            transform.scale = (ball_transform.scale.truncate() * left).extend(1.0);
+            sprite.color = ball_sprite
+                .color
+                .with_alpha(ball_sprite.color.alpha() * TRAIL_OPACITY * left * speed);
+        }
+    }
+}
+
+/// Takes the trail away with a ball that's gone before the match is over.
+fn despawn_orphaned_trails(
+    mut commands: Commands,
+    sprites: Query<(Entity, &TrailSprite)>,
+    balls: Query<(), With<Ball>>,
+) {
+    for (entity, sprite) in sprites.iter() {
+        if balls.get(sprite.ball).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! How an online match's packets get to the other player, so nothing else
+//! in the netcode has to care.
+//!
+//! Both players start by sending straight to each other. Behind most home
+//! routers that's enough: each one's packets going out open the way for the
+//! other's coming in, the two punching a hole through from either side. When
+//! nothing's come back after [`PUNCH_TIME`] and there's a lobby to fall back
+//! on, everything goes through the lobby instead, which passes it on between
+//! players it paired up itself (see [`lobby`](crate::lobby)). So does
+//! everything once the other player's packets start coming that way, as ours
+//! can't be getting through to them.
+
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    lobby::{LobbyMessage, SeekerMessage},
+    relay::{decode, encode, receive_all},
+};
+
+/// How long to keep trying to reach the other player directly before
+/// relaying instead.
+pub const PUNCH_TIME: Duration = Duration::from_secs(3);
+
+/// Which way packets are going to the other player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// Straight there, but nothing's come back that way yet
+    Punching,
+    /// Straight there, and straight back
+    Direct,
+    /// Through the lobby
+    Relayed,
+}
+
+/// A socket for talking to the other player, whichever way it takes.
+/// Clones share the route, so the first to find one finds it for all.
+pub struct Transport {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    relay: Option<SocketAddr>,
+    route: Arc<Mutex<Route>>,
+    started: Instant,
+}
+
+impl Transport {
+    /// Opens `port` for packets to and from `peer`, falling back on `relay`
+    /// if there is one.
+    pub fn bind(port: u16, peer: SocketAddr, relay: Option<SocketAddr>) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Transport {
+            socket,
+            peer,
+            relay,
+            route: Arc::new(Mutex::new(Route::Punching)),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Transport {
+            socket: self.socket.try_clone()?,
+            peer: self.peer,
+            relay: self.relay,
+            route: self.route.clone(),
+            started: self.started,
+        })
+    }
+
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Sends `packet` to the other player.
+    pub fn send(&self, packet: &[u8]) {
+        let _span = info_span!("network", bytes = packet.len()).entered();
+        let mut route = self.route.lock().unwrap();
+        if *route == Route::Punching && self.started.elapsed() > PUNCH_TIME {
+            if let Some(relay) = self.relay {
+                info!(
+                    "Couldn't reach {} directly, relaying through {}",
+                    self.peer, relay
+                );
+                *route = Route::Relayed;
+            }
+        }
+
+        let sent = match (*route, self.relay) {
+            (Route::Relayed, Some(relay)) => self
+                .socket
+                .send_to(&encode(&SeekerMessage::Relay(packet.to_vec())), relay),
+            _ => self.socket.send_to(packet, self.peer),
+        };
+        if let Err(error) = sent {
+            debug!("Couldn't send to {}: {}", self.peer, error);
+        }
+    }
+
+    /// Every packet from the other player waiting on the socket, however it
+    /// came. Anything from anyone else is dropped.
+    pub fn receive(&self) -> Vec<Vec<u8>> {
+        let _span = info_span!("network").entered();
+        let mut packets = Vec::new();
+        for (address, packet) in receive_all(&self.socket) {
+            if address == self.peer {
+                let mut route = self.route.lock().unwrap();
+                if *route == Route::Punching {
+                    info!("Reached {} directly", self.peer);
+                    *route = Route::Direct;
+                }
+                packets.push(packet);
+            } else if Some(address) == self.relay {
+                match decode(&packet) {
+                    Some(LobbyMessage::Relayed(packet)) => {
+                        // They can't hear us directly, however well we hear them
+                        let mut route = self.route.lock().unwrap();
+                        if *route != Route::Relayed {
+                            info!("{} is relaying, so relaying back too", self.peer);
+                            *route = Route::Relayed;
+                        }
+                        packets.push(packet);
+                    }
+                    _ => debug!("Ignoring a packet from the lobby"),
+                }
+            } else {
+                debug!("Ignoring a packet from {}", address);
+            }
+        }
+        packets
+    }
+}
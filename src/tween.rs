@@ -0,0 +1,113 @@
+//! Animating things over time. A [`Tween`] on an entity eases one of its
+//! components along, from 0 at the start to 1 at the end, and is taken off
+//! again once it's done, or takes the entity with it.
+
+use bevy::{color::Mix, prelude::*};
+
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                animate::<Transform>,
+                animate::<Sprite>,
+                animate::<Text>,
+                animate::<Style>,
+                animate::<BackgroundColor>,
+            ),
+        );
+    }
+}
+
+/// How a tween speeds up and slows down on its way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ease {
+    Linear,
+    /// Quick to start with, slowing down towards the end
+    Out,
+    /// Slow at either end
+    InOut,
+}
+
+impl Ease {
+    /// How far along a tween `t` of the way through its time is.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Ease::Linear => t,
+            Ease::Out => 1.0 - (1.0 - t).powi(3),
+            Ease::InOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+type Lens<C> = Box<dyn FnMut(&mut C, f32) + Send + Sync>;
+
+/// Changes a `C` a bit at a time, by calling its lens with how far along it is.
+#[derive(Component)]
+pub struct Tween<C: Component> {
+    lens: Lens<C>,
+    ease: Ease,
+    delay: Timer,
+    timer: Timer,
+    despawn: bool,
+}
+
+impl<C: Component> Tween<C> {
+    pub fn new(
+        seconds: f32,
+        ease: Ease,
+        lens: impl FnMut(&mut C, f32) + Send + Sync + 'static,
+    ) -> Self {
+        Tween {
+            lens: Box::new(lens),
+            ease,
+            delay: Timer::from_seconds(0.0, TimerMode::Once),
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+            despawn: false,
+        }
+    }
+
+    /// Waits this many seconds before starting.
+    pub fn after(mut self, seconds: f32) -> Self {
+        self.delay = Timer::from_seconds(seconds, TimerMode::Once);
+        self
+    }
+
+    /// Despawns the entity once it's done.
+    pub fn then_despawn(mut self) -> Self {
+        self.despawn = true;
+        self
+    }
+}
+
+/// A colour `amount` of the way from `from` to `to`.
+pub fn mix(from: Color, to: Color, amount: f32) -> Color {
+    from.to_srgba().mix(&to.to_srgba(), amount).into()
+}
+
+fn animate<C: Component>(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tweens: Query<(Entity, &mut C, &mut Tween<C>)>,
+) {
+    for (entity, mut component, mut tween) in tweens.iter_mut() {
+        tween.delay.tick(time.delta());
+        if !tween.delay.finished() {
+            continue;
+        }
+        tween.timer.tick(time.delta());
+
+        let along = tween.ease.apply(tween.timer.fraction());
+        (tween.lens)(&mut component, along);
+        if tween.timer.finished() {
+            if tween.despawn {
+                commands.entity(entity).despawn_recursive();
+            } else {
+                commands.entity(entity).remove::<Tween<C>>();
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use bevy::{
+    ecs::system::EntityCommands,
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
+
+use crate::tween::{mix, Ease, Tween};
+
+pub const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+pub const DIM_TEXT_COLOR: Color = Color::srgb(0.55, 0.55, 0.55);
+pub const ACCENT_COLOR: Color = Color::srgb(0.9, 0.5, 0.0);
+pub const PANEL_COLOR: Color = Color::srgba(0.1, 0.1, 0.1, 0.9);
+
+/// Font used until a theme picks another.
+pub const DEFAULT_FONT: &str = "fonts/DejaVuSans-Bold.ttf";
+
+const BUTTON_COLOR: Color = Color::srgb(0.2, 0.2, 0.2);
+const FOCUSED_BUTTON_COLOR: Color = Color::srgb(0.35, 0.35, 0.35);
+// Seconds a button takes to light up when it's focused, and to go back
+const FOCUS_FADE: f32 = 0.12;
+
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuFocus>()
+            .add_event::<ButtonActivated>()
+            .add_systems(Startup, load_font)
+            .add_systems(
+                Update,
+                (navigate_menu, highlight_focused_button.after(navigate_menu)),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct UiFont(pub Handle<Font>);
+
+/// Index of the button that keyboard navigation currently points at. Only one
+/// menu is on screen at a time, so all menus share it.
+#[derive(Resource, Default)]
+pub struct MenuFocus(pub usize);
+
+/// A button in the current menu, numbered in the order it's navigated.
+#[derive(Component)]
+pub struct MenuButton(pub usize);
+
+/// Sent when a [`MenuButton`] is clicked or chosen with Enter.
+#[derive(Event)]
+pub struct ButtonActivated(pub Entity);
+
+fn load_font(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(UiFont(asset_server.load(DEFAULT_FONT)));
+}
+
+pub fn text_style(font: &UiFont, font_size: f32, color: Color) -> TextStyle {
+    TextStyle {
+        font: font.0.clone(),
+        font_size,
+        color,
+    }
+}
+
+pub fn text(font: &UiFont, value: impl Into<String>, font_size: f32, color: Color) -> TextBundle {
+    TextBundle::from_section(value, text_style(font, font_size, color))
+}
+
+/// A transparent node covering the whole window, laying its children out top to bottom.
+pub fn screen_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        background_color: Color::NONE.into(),
+        ..default()
+    }
+}
+
+pub fn spawn_button<'a>(
+    parent: &'a mut ChildBuilder,
+    font: &UiFont,
+    index: usize,
+    label: &str,
+) -> EntityCommands<'a> {
+    let mut button = parent.spawn((
+        ButtonBundle {
+            style: Style {
+                width: Val::Px(260.0),
+                height: Val::Px(56.0),
+                margin: UiRect::all(Val::Px(8.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BUTTON_COLOR.into(),
+            ..default()
+        },
+        MenuButton(index),
+    ));
+    button.with_children(|parent| {
+        parent.spawn(text(font, label, 28.0, TEXT_COLOR));
+    });
+    button
+}
+
+/// The characters typed by `events`, for the screens with a name or a line to
+/// type in.
+pub fn typed<'a>(
+    events: impl IntoIterator<Item = &'a KeyboardInput, IntoIter: 'a>,
+) -> impl Iterator<Item = char> + 'a {
+    events
+        .into_iter()
+        .filter(|event| event.state == ButtonState::Pressed)
+        .filter_map(|event| match &event.logical_key {
+            Key::Character(text) => Some(text.as_str()),
+            Key::Space => Some(" "),
+            _ => None,
+        })
+        .flat_map(str::chars)
+}
+
+fn navigate_menu(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut focus: ResMut<MenuFocus>,
+    added: Query<(), Added<MenuButton>>,
+    buttons: Query<(Entity, &MenuButton)>,
+    interactions: Query<(Entity, &MenuButton, &Interaction), Changed<Interaction>>,
+    mut activated: EventWriter<ButtonActivated>,
+) {
+    // A new menu starts out focused on its first button
+    if !added.is_empty() {
+        focus.0 = 0;
+    }
+
+    let count = buttons.iter().count();
+    if count == 0 {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) || keyboard_input.just_pressed(KeyCode::KeyW) {
+        focus.0 = (focus.0 + count - 1) % count;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) || keyboard_input.just_pressed(KeyCode::KeyS)
+    {
+        focus.0 = (focus.0 + 1) % count;
+    }
+
+    for (entity, button, interaction) in interactions.iter() {
+        match interaction {
+            Interaction::Hovered => focus.0 = button.0,
+            Interaction::Pressed => {
+                focus.0 = button.0;
+                activated.send(ButtonActivated(entity));
+            }
+            Interaction::None => {}
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        if let Some((entity, _)) = buttons.iter().find(|(_, button)| button.0 == focus.0) {
+            activated.send(ButtonActivated(entity));
+        }
+        // Don't let the screen we're switching to see the same key press
+        keyboard_input.reset(KeyCode::Enter);
+    }
+}
+
+fn highlight_focused_button(
+    mut commands: Commands,
+    focus: Res<MenuFocus>,
+    added: Query<(), Added<MenuButton>>,
+    buttons: Query<(Entity, &MenuButton, &BackgroundColor)>,
+) {
+    if !focus.is_changed() && added.is_empty() {
+        return;
+    }
+    for (entity, button, color) in buttons.iter() {
+        let from = color.0;
+        let to = if button.0 == focus.0 {
+            FOCUSED_BUTTON_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+        if from != to {
+            commands.entity(entity).insert(Tween::new(
+                FOCUS_FADE,
+                Ease::Out,
+                move |color: &mut BackgroundColor, along| color.0 = mix(from, to, along),
+            ));
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! What the wind looks like in a windy match: streaks drifting across the
+//! court with it, and an arrow showing which way and how hard it's blowing.
+//!
+//! Purely visual, the wind itself is part of the simulation.
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::ArenaBounds,
+    seams::Clock,
+    sim::{MatchRules, SimRng, Wind},
+    start_match,
+    ui::{UiFont, TEXT_COLOR},
+    AppState, MatchState, StartingSeed,
+};
+
+const STREAKS: usize = 40;
+// Streaks drift this many times faster than the wind speeds the ball up, give
+// or take a bit for each
+const DRIFT: f32 = 3.0;
+const DRIFT_VARIATION: (f32, f32) = (0.6, 1.4);
+const STREAK_LENGTH: f32 = 16.0;
+const STREAK_WIDTH: f32 = 2.0;
+const STREAK_OPACITY: f32 = 0.15;
+// Behind everything that matters
+const STREAK_Z: f32 = -0.5;
+
+const ARROW_SIZE: f32 = 48.0;
+// Just inside the bottom of the court, clear of the scoreboard
+const ARROW_INSET: f32 = 40.0;
+
+pub struct WindPlugin;
+
+impl Plugin for WindPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Playing), spawn_wind.after(start_match))
+            .add_systems(
+                Update,
+                (drift_streaks::<Time>, point_arrow).run_if(in_state(MatchState::Running)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct Streak {
+    drift: f32,
+}
+
+#[derive(Component)]
+struct WindArrow;
+
+fn spawn_wind(
+    mut commands: Commands,
+    rules: Res<MatchRules>,
+    bounds: Res<ArenaBounds>,
+    font: Res<UiFont>,
+    seed: Res<StartingSeed>,
+) {
+    if !rules.wind {
+        return;
+    }
+
+    // Scattered the same way every time the match is played, not that it
+    // changes anything but how it looks
+    let mut rng = SimRng::new(seed.0);
+    let mut color = TEXT_COLOR;
+    color.set_alpha(STREAK_OPACITY);
+    for _ in 0..STREAKS {
+        let position = Vec2::new(
+            rng.range(bounds.min.x, bounds.max.x),
+            rng.range(bounds.min.y, bounds.max.y),
+        );
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform::from_translation(position.extend(STREAK_Z)),
+                sprite: Sprite { color, ..default() },
+                ..default()
+            },
+            Streak {
+                drift: DRIFT * rng.range(DRIFT_VARIATION.0, DRIFT_VARIATION.1),
+            },
+            StateScoped(AppState::Playing),
+        ));
+    }
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "→",
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: ARROW_SIZE,
+                    color: TEXT_COLOR,
+                },
+            )
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(
+                (bounds.min.x + bounds.max.x) / 2.0,
+                bounds.min.y + ARROW_INSET,
+                STREAK_Z,
+            ),
+            ..default()
+        },
+        WindArrow,
+        StateScoped(AppState::Playing),
+    ));
+}
+
+/// Blows the streaks along with the wind, round to the other side of the
+/// court once they're off one edge.
+fn drift_streaks<C: Clock>(
+    time: Res<C>,
+    wind: Res<Wind>,
+    rules: Res<MatchRules>,
+    bounds: Res<ArenaBounds>,
+    mut streaks: Query<(&Streak, &mut Transform)>,
+) {
+    let force = wind.force(rules.time_step);
+    let size = bounds.max - bounds.min;
+    for (streak, mut transform) in streaks.iter_mut() {
+        let position =
+            transform.translation.truncate() + force * streak.drift * time.delta_seconds();
+        let wrapped = Vec2::new(
+            bounds.min.x + (position.x - bounds.min.x).rem_euclid(size.x),
+            bounds.min.y + (position.y - bounds.min.y).rem_euclid(size.y),
+        );
+        transform.translation = wrapped.extend(STREAK_Z);
+        // Longer the harder it blows, lying along the way it's going
+        transform.rotation = Quat::from_rotation_z(force.y.atan2(force.x));
+        transform.scale = Vec3::new(
+            STREAK_WIDTH + STREAK_LENGTH * wind.strength(rules.time_step),
+            STREAK_WIDTH,
+            1.0,
+        );
+    }
+}
+
+fn point_arrow(
+    wind: Res<Wind>,
+    rules: Res<MatchRules>,
+    mut arrows: Query<(&mut Transform, &mut Text), With<WindArrow>>,
+) {
+    let force = wind.force(rules.time_step);
+    let strength = wind.strength(rules.time_step);
+    for (mut transform, mut text) in arrows.iter_mut() {
+        transform.rotation = Quat::from_rotation_z(force.y.atan2(force.x));
+        // Fainter and smaller as the wind drops
+        text.sections[0].style.color.set_alpha(0.2 + 0.8 * strength);
+        transform.scale = Vec3::splat(0.5 + 0.5 * strength);
+    }
+}
@@ -0,0 +1,216 @@
+//! Recorded matches played through the simulation again, to catch changes to
+//! how the game plays. Each recording in `tests/golden` is a seed, the inputs
+//! both players held and for how many steps, and how the match stood after
+//! them: the score, and where the balls and rackets were. Any change to the
+//! simulation that changes what those inputs lead to fails here, so a
+//! refactor that isn't meant to change gameplay can be shown not to.
+//!
+//! A recording without an outcome fails like one that played out differently.
+//! When a recording's added, or gameplay's changed on purpose,
+//! `PINGIS_BLESS=1 cargo test --test golden` records every outcome again, for
+//! the changes to be looked over and checked in along with it.
+
+use std::{fs, path::Path};
+
+use bevy::{ecs::world::CommandQueue, prelude::*};
+use pingis::{
+    arena::{spawn_layout, Arena},
+    sim::{
+        defer_commands, launch_ball, serve_velocity, systems, Ball, BallHitEvent, CollisionEvent,
+        GameplayConfig, GoalEvent, MatchClock, MatchEndedEvent, MatchOver, MatchRules, MatchStats,
+        Portals, Racket, RacketInput, Rally, Score, ServeCallEvent, ServeEvent, Side, SimInput,
+        SimRng, Wind,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+const RECORDINGS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+/// How far anything can be from where it was recorded, in pixels. The
+/// simulation's the same every time, but the numbers are written out in
+/// decimal.
+const TOLERANCE: f32 = 0.01;
+
+/// A match as it was recorded.
+#[derive(Serialize, Deserialize, Debug)]
+struct Recording {
+    /// What's being played, and what it's there to catch
+    about: String,
+    seed: u64,
+    /// The defaults, unless the recording's of something else
+    #[serde(default)]
+    rules: Option<MatchRules>,
+    inputs: Vec<Held>,
+    /// How the match stood after every input was played, once it's recorded
+    #[serde(default)]
+    outcome: Option<Outcome>,
+}
+
+/// What both players held, for a number of steps.
+#[derive(Serialize, Deserialize, Debug)]
+struct Held {
+    steps: usize,
+    #[serde(default)]
+    left: RacketInput,
+    #[serde(default)]
+    right: RacketInput,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Outcome {
+    score: Score,
+    winner: Option<i32>,
+    /// Where the balls were, across and then up
+    balls: Vec<Vec2>,
+    /// Where the rackets were, by player number
+    rackets: Vec<Vec2>,
+}
+
+impl Outcome {
+    /// What's different about `played` from what was recorded, if anything.
+    fn differences(&self, played: &Outcome) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.score != played.score {
+            differences.push(format!("score {:?}, not {:?}", played.score, self.score));
+        }
+        if self.winner != played.winner {
+            differences.push(format!("won by {:?}, not {:?}", played.winner, self.winner));
+        }
+        for (what, recorded, played) in [
+            ("balls", &self.balls, &played.balls),
+            ("rackets", &self.rackets, &played.rackets),
+        ] {
+            let moved = recorded.len() != played.len()
+                || recorded
+                    .iter()
+                    .zip(played)
+                    .any(|(recorded, played)| !recorded.abs_diff_eq(*played, TOLERANCE));
+            if moved {
+                differences.push(format!("{} at {:?}, not {:?}", what, played, recorded));
+            }
+        }
+        differences
+    }
+}
+
+fn classic_arena() -> Arena {
+    let bytes = fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/arenas/classic.arena.ron"
+    ))
+    .unwrap();
+    ron::de::from_bytes(&bytes).unwrap()
+}
+
+/// A match on the classic arena with nothing but the simulation in it,
+/// served to the right as every match starts.
+fn simulation(seed: u64, rules: MatchRules) -> App {
+    let arena = classic_arena();
+    let mut app = App::new();
+    app.insert_resource(rules)
+        .insert_resource(arena.bounds())
+        .insert_resource(Score::starting(&rules))
+        .init_resource::<Rally>()
+        .init_resource::<MatchStats>()
+        .init_resource::<GameplayConfig>()
+        .init_resource::<MatchOver>()
+        .insert_resource(MatchClock::new(&rules))
+        .init_resource::<SimInput>()
+        .init_resource::<Wind>()
+        .init_resource::<Portals>()
+        .add_event::<CollisionEvent>()
+        .add_event::<GoalEvent>()
+        .add_event::<BallHitEvent>()
+        .add_event::<ServeEvent>()
+        .add_event::<ServeCallEvent>()
+        .add_event::<MatchEndedEvent>()
+        .add_systems(Update, systems());
+    defer_commands(&mut app, Update);
+
+    let mut rng = SimRng::new(seed);
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, app.world());
+    spawn_layout(&mut commands, &arena);
+    let velocity = serve_velocity(Side::Right, &rules, &GameplayConfig::default(), &mut rng);
+    launch_ball(&mut commands, rules.ball, Vec2::ZERO, velocity);
+    queue.apply(app.world_mut());
+    app.insert_resource(rng);
+    app
+}
+
+fn play(recording: &Recording) -> Outcome {
+    let mut app = simulation(recording.seed, recording.rules.unwrap_or_default());
+    for held in &recording.inputs {
+        for _ in 0..held.steps {
+            app.insert_resource(SimInput([held.left, held.right, default(), default()]));
+            app.update();
+        }
+    }
+
+    let mut balls = app.world_mut().query_filtered::<&Transform, With<Ball>>();
+    let mut balls: Vec<Vec2> = balls
+        .iter(app.world())
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    // However they were spawned, so a recording doesn't go by entity order
+    balls.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    let mut rackets = app.world_mut().query::<(&Racket, &Transform)>();
+    let mut rackets: Vec<_> = rackets.iter(app.world()).collect();
+    rackets.sort_by_key(|(racket, _)| (racket.player_number, racket.seat));
+    Outcome {
+        score: *app.world().resource::<Score>(),
+        winner: app.world().resource::<MatchOver>().0,
+        balls,
+        rackets: rackets
+            .into_iter()
+            .map(|(_, transform)| transform.translation.truncate())
+            .collect(),
+    }
+}
+
+fn record(path: &Path, recording: &Recording) {
+    let config = ron::ser::PrettyConfig::default();
+    let text = ron::ser::to_string_pretty(recording, config).unwrap();
+    fs::write(path, text + "\n").unwrap();
+}
+
+#[test]
+fn recorded_matches_play_out_the_same() {
+    let bless = std::env::var_os("PINGIS_BLESS").is_some();
+    let mut paths: Vec<_> = fs::read_dir(RECORDINGS)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no recordings in {}", RECORDINGS);
+
+    let mut failures = Vec::new();
+    for path in paths {
+        let bytes = fs::read(&path).unwrap();
+        let mut recording: Recording = ron::de::from_bytes(&bytes)
+            .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+        let played = play(&recording);
+        if bless {
+            recording.outcome = Some(played);
+            record(&path, &recording);
+            continue;
+        }
+        let differences = match &recording.outcome {
+            Some(outcome) => outcome.differences(&played),
+            None => vec!["no outcome recorded, PINGIS_BLESS=1 records one".to_string()],
+        };
+        if !differences.is_empty() {
+            failures.push(format!(
+                "{} ({}): {}",
+                path.display(),
+                recording.about,
+                differences.join(", ")
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "recorded matches played out differently:\n{}",
+        failures.join("\n")
+    );
+}